@@ -0,0 +1,75 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use vox::{
+    chunk,
+    generator::{Terrain, WorldGenerator},
+    noise::{Fractal, NoiseBackend},
+};
+
+/// Chunk columns generated on each iteration.
+const CHUNKS: i32 = 16;
+
+const BACKENDS: [NoiseBackend; 3] = [
+    NoiseBackend::Bracket,
+    NoiseBackend::Batch,
+    NoiseBackend::NoiseRs,
+];
+
+/**
+  Compares noise backends sampling the terrain noise one point at a time and a chunk at once, and generating
+  the terrain heights of a batch of chunks, which also samples rivers and lakes.
+*/
+fn noise(c: &mut Criterion) {
+    let size = chunk::AXIS_SIZE;
+    let fractal = Fractal {
+        seed: 1,
+        frequency: 0.03,
+        octaves: 3,
+        gain: 0.9,
+        lacunarity: 0.5,
+    };
+
+    let mut group = c.benchmark_group("noise");
+
+    for backend in BACKENDS {
+        let noise = backend.build(fractal);
+
+        group.bench_function(format!("{:?}/get", backend), |b| {
+            b.iter(|| {
+                (0..size)
+                    .flat_map(|x| (0..size).map(move |z| (x as f32, z as f32)))
+                    .map(|(x, z)| noise.get(x, z))
+                    .collect::<Vec<_>>()
+            })
+        });
+
+        group.bench_function(format!("{:?}/fill", backend), |b| {
+            b.iter(|| noise.fill(0.0, 0.0, size))
+        });
+    }
+
+    group.finish();
+}
+
+fn terrain(c: &mut Criterion) {
+    let generator = WorldGenerator::default();
+    let size = chunk::AXIS_SIZE as i32;
+
+    let mut group = c.benchmark_group("terrain");
+
+    for backend in BACKENDS {
+        let terrain = Terrain::with_backend(&generator, backend);
+
+        group.bench_function(format!("{:?}", backend), |b| {
+            b.iter(|| {
+                (0..CHUNKS)
+                    .map(|x| terrain.heights(x * size, 0, chunk::AXIS_SIZE))
+                    .collect::<Vec<_>>()
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, noise, terrain);
+criterion_main!(benches);
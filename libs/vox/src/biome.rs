@@ -0,0 +1,139 @@
+use bevy::prelude::*;
+use bracket_noise::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::chunk;
+
+/// Columns colder than this are snowy.
+const COLD_TEMPERATURE: f32 = -0.3;
+/// Columns hotter than this are deserts.
+const HOT_TEMPERATURE: f32 = 0.4;
+
+/**
+  What falls from the sky while it's precipitating.
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Precipitation {
+    Rain,
+    Snow,
+}
+
+/**
+  Climate of a world column. Biomes changes slowly across the world, so neighbor columns usually
+  shares the same biome.
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Biome {
+    Plains,
+    Desert,
+    Snowy,
+}
+
+impl Biome {
+    /// What falls on this biome when it's precipitating. Deserts never get any.
+    pub fn precipitation(&self) -> Option<Precipitation> {
+        match self {
+            Biome::Plains => Some(Precipitation::Rain),
+            Biome::Desert => None,
+            Biome::Snowy => Some(Precipitation::Snow),
+        }
+    }
+
+    fn from_temperature(temperature: f32) -> Self {
+        if temperature < COLD_TEMPERATURE {
+            Biome::Snowy
+        } else if temperature > HOT_TEMPERATURE {
+            Biome::Desert
+        } else {
+            Biome::Plains
+        }
+    }
+}
+
+fn temperature_noise() -> FastNoise {
+    let mut noise = FastNoise::seeded(42);
+    noise.set_noise_type(NoiseType::Simplex);
+    noise.set_frequency(0.002);
+    noise
+}
+
+/**
+  Temperature of the given world column, ranging from -1.0 to 1.0.
+*/
+pub fn temperature(column: IVec2) -> f32 {
+    temperature_noise().get_noise(column.x as f32, column.y as f32)
+}
+
+/**
+  Biome of the given world column.
+*/
+pub fn at(column: IVec2) -> Biome {
+    Biome::from_temperature(temperature(column))
+}
+
+/**
+  Biome of each column of a chunk, generated along it and saved on its cache, so biomes of loaded chunks are
+  known without sampling the temperature noise again.
+*/
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkBiomes(Vec<Biome>);
+
+impl ChunkBiomes {
+    pub fn generate(local: IVec3) -> Self {
+        let noise = temperature_noise();
+        let world = chunk::to_world(local);
+
+        Self(
+            (0..chunk::AXIS_SIZE)
+                .flat_map(|x| (0..chunk::AXIS_SIZE).map(move |z| (x, z)))
+                .map(|(x, z)| {
+                    let temperature = noise.get_noise(world.x + x as f32, world.z + z as f32);
+                    Biome::from_temperature(temperature)
+                })
+                .collect(),
+        )
+    }
+
+    /// Biome of the given column, relative to the chunk.
+    pub fn get(&self, x: i32, z: i32) -> Biome {
+        self.0[x as usize * chunk::AXIS_SIZE + z as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_temperature() {
+        assert_eq!(Biome::from_temperature(-1.0), Biome::Snowy);
+        assert_eq!(Biome::from_temperature(0.0), Biome::Plains);
+        assert_eq!(Biome::from_temperature(COLD_TEMPERATURE), Biome::Plains);
+        assert_eq!(Biome::from_temperature(1.0), Biome::Desert);
+    }
+
+    #[test]
+    fn at() {
+        // Same column always has the same biome
+        let column = IVec2::new(-1234, 5678);
+        assert_eq!(super::at(column), super::at(column));
+
+        // Temperature changes slowly between neighbor columns
+        let diff = (temperature(column) - temperature(column + IVec2::X)).abs();
+        assert!(diff < 0.05);
+    }
+
+    #[test]
+    fn chunk_biomes() {
+        let local = IVec3::new(-3, 2, 7);
+        let biomes = ChunkBiomes::generate(local);
+        let world = chunk::to_world(local).as_ivec3();
+
+        for (x, z) in [(0, 0), (5, 11), (15, 15)] {
+            assert_eq!(
+                biomes.get(x, z),
+                super::at(IVec2::new(world.x + x, world.z + z))
+            );
+        }
+    }
+}
@@ -0,0 +1,568 @@
+use bevy::prelude::*;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    any::Any,
+    collections::HashMap,
+    fmt::Debug,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    item::Inventory,
+    voxel::{Kind, KindRegistry},
+    world::VoxWorld,
+};
+
+const BLOCK_ENTITIES_PATH: &str = "cache/block_entities";
+const BLOCK_ENTITIES_EXT: &str = "bin";
+
+/**
+  Data attached to a single voxel, which doesn't fit on the chunk, like the items of a chest or the text of
+  a sign. Kinds refer to the block entity they carry by its [`BlockEntity::name`].
+
+  Implement [`BlockEntityData`] instead of this trait, which handles serialization and downcasting.
+*/
+pub trait BlockEntity: Any + Debug + Send + Sync {
+    /// Name the type was registered with, on [`BlockEntities::register`].
+    fn name(&self) -> &'static str;
+
+    /// Serializes the data, to be saved with the chunk.
+    fn save(&self) -> Vec<u8>;
+
+    /**
+      Runs on every simulation tick, with the current kind of the voxel. Returns the kind the voxel must turn
+      into, if any, like a furnace which lights up.
+    */
+    fn tick(&mut self, kind: Kind) -> Option<Kind>;
+
+    /// Items held, which are dropped when the voxel is broken.
+    fn contents(&self) -> Option<&Inventory>;
+
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+/**
+  Typed block entity data. Any type implementing it is a [`BlockEntity`], once registered.
+*/
+pub trait BlockEntityData:
+    Default + Debug + Serialize + DeserializeOwned + Send + Sync + 'static
+{
+    /// Name kinds use to refer to this block entity, on [`crate::voxel::KindDescription::block_entity`].
+    const NAME: &'static str;
+
+    /// See [`BlockEntity::tick`].
+    fn tick(&mut self, _kind: Kind) -> Option<Kind> {
+        None
+    }
+
+    /// See [`BlockEntity::contents`].
+    fn contents(&self) -> Option<&Inventory> {
+        None
+    }
+}
+
+impl<T: BlockEntityData> BlockEntity for T {
+    fn name(&self) -> &'static str {
+        T::NAME
+    }
+
+    fn save(&self) -> Vec<u8> {
+        bincode::serialize(self)
+            .unwrap_or_else(|_| panic!("Failed to serialize block entity {}", T::NAME))
+    }
+
+    fn tick(&mut self, kind: Kind) -> Option<Kind> {
+        BlockEntityData::tick(self, kind)
+    }
+
+    fn contents(&self) -> Option<&Inventory> {
+        BlockEntityData::contents(self)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Holds items, like a chest.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Container {
+    pub items: Inventory,
+}
+
+impl BlockEntityData for Container {
+    const NAME: &'static str = "container";
+
+    fn contents(&self) -> Option<&Inventory> {
+        Some(&self.items)
+    }
+}
+
+/// Text written on a sign.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sign {
+    pub text: String,
+}
+
+impl BlockEntityData for Sign {
+    const NAME: &'static str = "sign";
+}
+
+/// Deserializes a block entity saved by [`BlockEntity::save`].
+type LoadFn = fn(&[u8]) -> Result<Box<dyn BlockEntity>, String>;
+
+/// How a registered block entity type is created and loaded.
+struct BlockEntityType {
+    create: fn() -> Box<dyn BlockEntity>,
+    load: LoadFn,
+}
+
+fn create<T: BlockEntityData>() -> Box<dyn BlockEntity> {
+    Box::new(T::default())
+}
+
+fn load_data<T: BlockEntityData>(data: &[u8]) -> Result<Box<dyn BlockEntity>, String> {
+    bincode::deserialize::<T>(data)
+        .map(|entity| Box::new(entity) as Box<dyn BlockEntity>)
+        .map_err(|err| err.to_string())
+}
+
+/// A block entity as it's saved with its chunk, serialized by its own type.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedBlockEntity {
+    pub voxel: IVec3,
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+/**
+  Block entities of all loaded chunks, indexed by chunk and voxel, and the types they can have. The
+  [`Container`] and [`Sign`] types are always registered.
+
+  Block entities are saved on their own file when their chunk is unloaded, and loaded back with it. Chunks
+  edited while loaded also replace their cache on unload, so the voxels carrying block entities are there.
+*/
+pub struct BlockEntities {
+    types: HashMap<&'static str, BlockEntityType>,
+    chunks: HashMap<IVec3, HashMap<IVec3, Box<dyn BlockEntity>>>,
+}
+
+impl Default for BlockEntities {
+    fn default() -> Self {
+        let mut block_entities = Self {
+            types: Default::default(),
+            chunks: Default::default(),
+        };
+
+        block_entities.register::<Container>();
+        block_entities.register::<Sign>();
+        block_entities
+    }
+}
+
+impl BlockEntities {
+    /// Registers a block entity type, so kinds can refer to it by [`BlockEntityData::NAME`].
+    pub fn register<T: BlockEntityData>(&mut self) {
+        let block_entity_type = BlockEntityType {
+            create: create::<T>,
+            load: load_data::<T>,
+        };
+
+        if self.types.insert(T::NAME, block_entity_type).is_some() {
+            panic!("Duplicated block entity type {}", T::NAME);
+        }
+    }
+
+    pub fn is_registered(&self, name: &str) -> bool {
+        self.types.contains_key(name)
+    }
+
+    pub fn get(&self, local: IVec3, voxel: IVec3) -> Option<&dyn BlockEntity> {
+        self.chunks
+            .get(&local)
+            .and_then(|chunk| chunk.get(&voxel))
+            .map(|entity| entity.as_ref())
+    }
+
+    pub fn get_mut(&mut self, local: IVec3, voxel: IVec3) -> Option<&mut dyn BlockEntity> {
+        self.chunks
+            .get_mut(&local)
+            .and_then(|chunk| chunk.get_mut(&voxel))
+            .map(|entity| entity.as_mut())
+    }
+
+    /// The block entity of the given voxel, if it has one of the given type.
+    pub fn get_as<T: BlockEntityData>(&self, local: IVec3, voxel: IVec3) -> Option<&T> {
+        self.get(local, voxel)
+            .and_then(|entity| entity.as_any().downcast_ref())
+    }
+
+    pub fn get_as_mut<T: BlockEntityData>(&mut self, local: IVec3, voxel: IVec3) -> Option<&mut T> {
+        self.get_mut(local, voxel)
+            .and_then(|entity| entity.as_any_mut().downcast_mut())
+    }
+
+    /**
+      Makes sure the given voxel has a block entity with the given name, or none, creating an empty one when
+      needed. Returns the block entity which was replaced, if any, so its contents aren't silently lost.
+      Unknown names are treated as none.
+    */
+    pub fn sync(
+        &mut self,
+        local: IVec3,
+        voxel: IVec3,
+        name: Option<&str>,
+    ) -> Option<Box<dyn BlockEntity>> {
+        let block_entity_type = name.and_then(|name| self.types.get(name));
+
+        if self.get(local, voxel).map(|entity| entity.name()) == block_entity_type.and(name) {
+            return None;
+        }
+
+        let chunk = self.chunks.entry(local).or_default();
+        let removed = match block_entity_type {
+            Some(block_entity_type) => chunk.insert(voxel, (block_entity_type.create)()),
+            None => chunk.remove(&voxel),
+        };
+
+        if chunk.is_empty() {
+            self.chunks.remove(&local);
+        }
+
+        removed
+    }
+
+    /// All block entities, as chunk, voxel and the block entity, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (IVec3, IVec3, &dyn BlockEntity)> {
+        self.chunks.iter().flat_map(|(local, chunk)| {
+            chunk
+                .iter()
+                .map(|(voxel, entity)| (*local, *voxel, entity.as_ref()))
+        })
+    }
+
+    /// Number of block entities on the given chunk.
+    pub fn count(&self, local: IVec3) -> usize {
+        self.chunks.get(&local).map_or(0, |chunk| chunk.len())
+    }
+
+    /**
+      Runs the tick of every block entity whose chunk is loaded on the given world. Returns the voxels which
+      must change, as chunk, voxel and the new kind.
+    */
+    pub fn tick(&mut self, world: &VoxWorld) -> Vec<(IVec3, IVec3, Kind)> {
+        let mut changes = vec![];
+
+        for (local, chunk) in self.chunks.iter_mut() {
+            let kinds = match world.get(*local) {
+                Some(kinds) => kinds,
+                None => continue,
+            };
+
+            for (voxel, entity) in chunk.iter_mut() {
+                if let Some(kind) = entity.tick(kinds.get(*voxel)) {
+                    changes.push((*local, *voxel, kind));
+                }
+            }
+        }
+
+        changes
+    }
+
+    /**
+      Adds back the block entities of a chunk which was loaded again on the given world. Block entities of
+      types which aren't registered anymore, which can't be loaded, or which the kind of their voxel doesn't
+      carry, are dropped. Nothing is added to chunks which aren't loaded.
+    */
+    pub fn load(
+        &mut self,
+        local: IVec3,
+        saved: Vec<SavedBlockEntity>,
+        world: &VoxWorld,
+        registry: &KindRegistry,
+    ) {
+        let chunk = match world.get(local) {
+            Some(chunk) => chunk,
+            None => return,
+        };
+
+        for SavedBlockEntity { voxel, name, data } in saved {
+            let kind = chunk.get(voxel);
+
+            // Voxels may have changed without their block entity, like when the chunk wasn't saved
+            let entity = match self.types.get(name.as_str()) {
+                Some(_) if registry.block_entity(kind) != Some(name.as_str()) => {
+                    Err(format!("voxel is {}", registry.name(kind)))
+                }
+                Some(block_entity_type) => (block_entity_type.load)(&data),
+                None => Err("unknown type".to_string()),
+            };
+
+            match entity {
+                Ok(entity) => {
+                    self.chunks.entry(local).or_default().insert(voxel, entity);
+                }
+                Err(err) => warn!(
+                    "Dropping block entity {} at chunk {} voxel {} ({})",
+                    name, local, voxel, err
+                ),
+            }
+        }
+    }
+
+    /// Removes all block entities of the given chunk, sorted by voxel, so saves are always the same.
+    pub fn unload(&mut self, local: IVec3) -> Vec<SavedBlockEntity> {
+        let mut saved = self
+            .chunks
+            .remove(&local)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(voxel, entity)| SavedBlockEntity {
+                voxel,
+                name: entity.name().to_string(),
+                data: entity.save(),
+            })
+            .collect::<Vec<_>>();
+
+        saved.sort_by_key(|saved| (saved.voxel.x, saved.voxel.y, saved.voxel.z));
+        saved
+    }
+}
+
+pub fn local_path(local: IVec3) -> PathBuf {
+    Path::new(BLOCK_ENTITIES_PATH)
+        .join(format!("{}_{}_{}", local.x, local.y, local.z))
+        .with_extension(BLOCK_ENTITIES_EXT)
+}
+
+/**
+  Saves the block entities of a chunk. Chunks without block entities have their file removed, if any.
+*/
+pub fn save(path: &Path, saved: &[SavedBlockEntity]) {
+    if saved.is_empty() {
+        if path.exists() {
+            std::fs::remove_file(path)
+                .unwrap_or_else(|_| panic!("Unable to remove file {}", path.display()));
+        }
+
+        return;
+    }
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .unwrap_or_else(|_| panic!("Unable to create dir {}", dir.display()));
+    }
+
+    let file = std::fs::File::create(path)
+        .unwrap_or_else(|_| panic!("Unable to write to file {}", path.display()));
+
+    bincode::serialize_into(file, saved).unwrap_or_else(|_| {
+        panic!(
+            "Failed to serialize block entities to file {}",
+            path.display()
+        )
+    });
+}
+
+/**
+  Loads the block entities of a chunk. Chunks which were never saved have no block entities.
+*/
+pub fn load(path: &Path) -> Vec<SavedBlockEntity> {
+    if !path.exists() {
+        return vec![];
+    }
+
+    let file = std::fs::File::open(path)
+        .unwrap_or_else(|_| panic!("Unable to open file {}", path.display()));
+
+    bincode::deserialize_from(file)
+        .unwrap_or_else(|_| panic!("Failed to parse file {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::ChunkKind;
+
+    /// Turns its voxel into the next kind every few ticks.
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    struct Timer {
+        ticks: u32,
+    }
+
+    impl BlockEntityData for Timer {
+        const NAME: &'static str = "timer";
+
+        fn tick(&mut self, kind: Kind) -> Option<Kind> {
+            self.ticks += 1;
+
+            if self.ticks < 3 {
+                return None;
+            }
+
+            self.ticks = 0;
+            Some((kind.id() + 1).into())
+        }
+    }
+
+    fn chest(kind: u16, count: u32) -> Container {
+        let mut items = Inventory::default();
+        items.add(kind.into(), count);
+        Container { items }
+    }
+
+    #[test]
+    fn sync() {
+        let mut entities = BlockEntities::default();
+        let voxel = IVec3::new(1, 2, 3);
+
+        assert!(entities
+            .sync(IVec3::ZERO, voxel, Some(Container::NAME))
+            .is_none());
+        assert_eq!(
+            entities.get_as::<Container>(IVec3::ZERO, voxel),
+            Some(&Container::default())
+        );
+
+        // Syncing the same type again keeps the contents
+        *entities
+            .get_as_mut::<Container>(IVec3::ZERO, voxel)
+            .unwrap() = chest(3, 5);
+        assert!(entities
+            .sync(IVec3::ZERO, voxel, Some(Container::NAME))
+            .is_none());
+
+        // Unknown types are treated as none
+        let removed = entities.sync(IVec3::ZERO, voxel, Some("unknown")).unwrap();
+        assert_eq!(removed.contents(), Some(&chest(3, 5).items));
+        assert!(entities.get(IVec3::ZERO, voxel).is_none());
+        assert_eq!(entities.count(IVec3::ZERO), 0);
+
+        // Replacing a sign with a container drops its text
+        entities.sync(IVec3::X, voxel, Some(Sign::NAME));
+        entities.get_as_mut::<Sign>(IVec3::X, voxel).unwrap().text = "Hello".to_string();
+        let removed = entities.sync(IVec3::X, voxel, Some(Container::NAME));
+        assert_eq!(removed.map(|entity| entity.name()), Some(Sign::NAME));
+        assert_eq!(
+            entities
+                .iter()
+                .map(|(local, voxel, entity)| (local, voxel, entity.name()))
+                .collect::<Vec<_>>(),
+            vec![(IVec3::X, voxel, Container::NAME)]
+        );
+    }
+
+    #[test]
+    fn tick() {
+        let world = VoxWorld::default();
+        world.add(IVec3::ZERO, ChunkKind::default());
+
+        let mut entities = BlockEntities::default();
+        entities.register::<Timer>();
+        entities.sync(IVec3::ZERO, IVec3::ONE, Some(Timer::NAME));
+        entities.sync(IVec3::X, IVec3::ONE, Some(Timer::NAME));
+
+        assert!(entities.tick(&world).is_empty());
+        assert!(entities.tick(&world).is_empty());
+
+        // Chunks which aren't loaded don't tick
+        assert_eq!(
+            entities.tick(&world),
+            vec![(IVec3::ZERO, IVec3::ONE, 1.into())]
+        );
+        assert_eq!(
+            entities
+                .get_as::<Timer>(IVec3::ZERO, IVec3::ONE)
+                .map(|timer| timer.ticks),
+            Some(0)
+        );
+    }
+
+    fn registry() -> KindRegistry {
+        KindRegistry::load(std::path::Path::new(&format!(
+            "{}assets/voxels/kind_descriptions.ron",
+            env!("CARGO_WORKSPACE_DIR")
+        )))
+    }
+
+    #[test]
+    fn save_load() {
+        let path = std::env::temp_dir().join("eterno_block_entities_save_load.bin");
+        let local = IVec3::new(4, -1, 2);
+        let registry = registry();
+        let chest_kind = registry.find("Chest").unwrap();
+
+        let world = VoxWorld::default();
+        world.add(local, ChunkKind::default());
+        world.get_mut(local).unwrap().set(IVec3::ONE, chest_kind);
+
+        let mut entities = BlockEntities::default();
+        entities.register::<Timer>();
+        entities.sync(local, IVec3::ONE, Some(Container::NAME));
+        entities.sync(local, IVec3::ZERO, Some(Timer::NAME));
+        *entities.get_as_mut::<Container>(local, IVec3::ONE).unwrap() = chest(1, 2);
+        assert_eq!(entities.count(local), 2);
+
+        let unloaded = entities.unload(local);
+        assert_eq!(unloaded[0].voxel, IVec3::ZERO);
+        assert_eq!(entities.count(local), 0);
+
+        save(&path, &unloaded);
+        assert_eq!(load(&path), unloaded);
+
+        // Types which aren't registered anymore are dropped
+        let mut without_timer = BlockEntities::default();
+        without_timer.load(local, load(&path), &world, &registry);
+        assert_eq!(without_timer.count(local), 1);
+        assert_eq!(
+            without_timer.get_as::<Container>(local, IVec3::ONE),
+            Some(&chest(1, 2))
+        );
+
+        save(&path, &[]);
+        assert!(!path.exists());
+        assert!(load(&path).is_empty());
+    }
+
+    #[test]
+    fn load_mismatched() {
+        let local = IVec3::new(-3, 0, 7);
+        let registry = registry();
+        let saved = vec![SavedBlockEntity {
+            voxel: IVec3::ONE,
+            name: Container::NAME.to_string(),
+            data: chest(1, 2).save(),
+        }];
+
+        // Chunks which aren't loaded have nothing to attach block entities to
+        let world = VoxWorld::default();
+        let mut entities = BlockEntities::default();
+        entities.load(local, saved.clone(), &world, &registry);
+        assert_eq!(entities.count(local), 0);
+
+        // The chest was placed on a chunk which wasn't saved, so it's terrain again
+        world.add(local, ChunkKind::default());
+        world
+            .get_mut(local)
+            .unwrap()
+            .set(IVec3::ONE, registry.find("Dirt").unwrap());
+        entities.load(local, saved.clone(), &world, &registry);
+        assert_eq!(entities.count(local), 0);
+
+        world
+            .get_mut(local)
+            .unwrap()
+            .set(IVec3::ONE, registry.find("Chest").unwrap());
+        entities.load(local, saved, &world, &registry);
+        assert_eq!(
+            entities.get_as::<Container>(local, IVec3::ONE),
+            Some(&chest(1, 2))
+        );
+    }
+}
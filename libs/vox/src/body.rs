@@ -0,0 +1,358 @@
+use bevy::prelude::*;
+
+use crate::{bounds::Bounds, broadphase, query};
+
+/// Bodies closer than this to a voxel don't touch it, so bodies standing or leaning on voxels can still move.
+const SKIN: f32 = 0.001;
+/// How far below the feet the ground is looked for.
+const GROUND_PROBE: f32 = 0.01;
+
+/// Height of sneaking bodies, relative to their usual height.
+pub const SNEAK_HEIGHT: f32 = 0.8;
+/// Speed of sneaking bodies, relative to their usual speed.
+pub const SNEAK_SPEED: f32 = 0.3;
+
+/**
+  Box shaped body of something walking around the world, standing on the center of its bottom. Ledges up to
+  `max_step` high are stepped up on automatically, instead of blocking the way.
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Body {
+    pub width: f32,
+    pub height: f32,
+    pub max_step: f32,
+}
+
+impl Default for Body {
+    fn default() -> Self {
+        Self {
+            width: 0.6,
+            height: 1.8,
+            max_step: 1.0,
+        }
+    }
+}
+
+impl Body {
+    /// This body while sneaking, which is lower.
+    pub fn sneaking(&self) -> Self {
+        Self {
+            height: self.height * SNEAK_HEIGHT,
+            ..*self
+        }
+    }
+
+    /// Half the body width, leaving the skin out.
+    fn half_width(&self) -> f32 {
+        self.width / 2.0 - SKIN
+    }
+
+    /// Bounds of the body standing on `feet`, leaving the skin out.
+    pub fn bounds(&self, feet: Vec3) -> Bounds {
+        let half = Vec3::new(self.half_width(), -SKIN, self.half_width());
+        Bounds::new(
+            feet - half,
+            feet + half + Vec3::Y * (self.height - SKIN * 2.0),
+        )
+    }
+
+    /// Whether the body, standing on `feet`, overlaps any voxel `solid` returns true for.
+    pub fn collides(&self, feet: Vec3, solid: &impl Fn(IVec3) -> bool) -> bool {
+        broadphase::collides_voxels(&self.bounds(feet), solid)
+    }
+
+    /// Whether any voxel `solid` returns true for is right below the body, standing on `feet`.
+    pub fn on_ground(&self, feet: Vec3, solid: &impl Fn(IVec3) -> bool) -> bool {
+        let half = self.half_width();
+        let below = feet - Vec3::Y * GROUND_PROBE;
+        let min = (below - Vec3::new(half, 0.0, half)).floor().as_ivec3();
+        let max = (below + Vec3::new(half, 0.0, half)).floor().as_ivec3();
+
+        query::range_inclusive(min, max).any(solid)
+    }
+
+    /**
+      Moves the body standing on `feet` horizontally by `offset`, returning where it ends up. Each axis moves
+      on its own, so walking diagonally into a wall slides along it. When a ledge blocks an axis, the body
+      steps on top of it, as long as it's low enough and there's room for the body on both where it is and
+      on top of the ledge. Otherwise it stays where it was on that axis.
+    */
+    pub fn step_move(&self, feet: Vec3, offset: Vec3, solid: impl Fn(IVec3) -> bool) -> Vec3 {
+        let mut feet = feet;
+
+        for axis in [Vec3::X, Vec3::Z] {
+            let moved = feet + axis * offset;
+
+            if axis.dot(offset) == 0.0 {
+                continue;
+            }
+
+            if !self.collides(moved, &solid) {
+                feet = moved;
+                continue;
+            }
+
+            // Ledges are voxels, so their top is always the next whole height above the feet
+            let lift = (feet.y + SKIN).floor() + 1.0 - feet.y;
+
+            if lift > self.max_step + SKIN {
+                continue;
+            }
+
+            let lifted = Vec3::Y * lift;
+            if !self.collides(feet + lifted, &solid) && !self.collides(moved + lifted, &solid) {
+                feet = moved + lifted;
+            }
+        }
+
+        feet
+    }
+
+    /**
+      Moves the body standing on `feet` vertically by `offset`, returning where it ends up and whether something
+      was in the way. Falling bodies land on top of the voxel below them, while rising ones stay where they were.
+    */
+    pub fn fall(&self, feet: Vec3, offset: f32, solid: impl Fn(IVec3) -> bool) -> (Vec3, bool) {
+        let moved = feet + Vec3::Y * offset;
+
+        if !self.collides(moved, &solid) {
+            return (moved, false);
+        }
+
+        let landed = Vec3::new(feet.x, moved.y.ceil(), feet.z);
+        if offset < 0.0 && !self.collides(landed, &solid) {
+            (landed, true)
+        } else {
+            (feet, true)
+        }
+    }
+
+    /**
+      Like [`Body::step_move`], but bodies on the ground don't walk off edges: they stop right before the
+      last voxel below them would be left behind. Bodies in the air move as usual.
+    */
+    pub fn sneak_move(&self, feet: Vec3, offset: Vec3, solid: impl Fn(IVec3) -> bool) -> Vec3 {
+        if !self.on_ground(feet, &solid) {
+            return self.step_move(feet, offset, solid);
+        }
+
+        let mut feet = feet;
+        let half = self.half_width();
+
+        for axis in [Vec3::X, Vec3::Z] {
+            let along = axis.dot(offset);
+
+            if along == 0.0 {
+                continue;
+            }
+
+            let moved = self.step_move(feet, axis * along, &solid);
+            if self.on_ground(moved, &solid) {
+                feet = moved;
+                continue;
+            }
+
+            // Walks up to where the back of the body would leave the voxel below it
+            let back = axis.dot(feet) - half * along.signum();
+            let edge = if along > 0.0 {
+                back.ceil() - SKIN + half
+            } else {
+                back.floor() + SKIN - half
+            };
+
+            let distance = edge - axis.dot(feet);
+            let clamped = feet + axis * distance;
+
+            if distance * along > 0.0
+                && distance.abs() <= along.abs()
+                && self.on_ground(clamped, &solid)
+                && !self.collides(clamped, &solid)
+            {
+                feet = clamped;
+            }
+        }
+
+        feet
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Floor made of the voxels below y = 0, with a ledge one voxel high from x = 2 on.
+    fn ledge(voxel: IVec3) -> bool {
+        voxel.y < 0 || (voxel.x >= 2 && voxel.y < 1)
+    }
+
+    #[test]
+    fn collides() {
+        let body = Body::default();
+
+        assert!(!body.collides(Vec3::new(0.5, 0.0, 0.5), &ledge));
+        assert!(body.collides(Vec3::new(1.9, 0.0, 0.5), &ledge));
+        assert!(!body.collides(Vec3::new(2.5, 1.0, 0.5), &ledge));
+
+        // Leaning right against the ledge isn't overlapping it
+        assert!(!body.collides(Vec3::new(1.7, 0.0, 0.5), &ledge));
+    }
+
+    #[test]
+    fn step_move() {
+        let body = Body::default();
+
+        // Flat ground is walked on as usual
+        assert_eq!(
+            body.step_move(Vec3::new(0.5, 0.0, 0.5), Vec3::new(0.5, 0.0, 0.5), ledge),
+            Vec3::new(1.0, 0.0, 1.0)
+        );
+
+        // Single voxel ledges are stepped on
+        assert_eq!(
+            body.step_move(Vec3::new(1.5, 0.0, 0.5), Vec3::X * 0.25, ledge),
+            Vec3::new(1.75, 1.0, 0.5)
+        );
+
+        // Higher ledges aren't
+        let low = Body {
+            max_step: 0.5,
+            ..body
+        };
+        assert_eq!(
+            low.step_move(Vec3::new(1.5, 0.0, 0.5), Vec3::X * 0.25, ledge),
+            Vec3::new(1.5, 0.0, 0.5)
+        );
+
+        // Unless the body is already halfway up, like while jumping
+        assert_eq!(
+            low.step_move(Vec3::new(1.5, 0.5, 0.5), Vec3::X * 0.25, ledge),
+            Vec3::new(1.75, 1.0, 0.5)
+        );
+    }
+
+    #[test]
+    fn step_move_blocked() {
+        let body = Body::default();
+        let feet = Vec3::new(1.5, 0.0, 0.5);
+
+        // Walls two voxels high block the way
+        let wall = |voxel: IVec3| voxel.y < 0 || (voxel.x >= 2 && voxel.y < 2);
+        assert_eq!(body.step_move(feet, Vec3::X * 0.25, wall), feet);
+
+        // No room for the body on top of the ledge
+        let tunnel = |voxel: IVec3| ledge(voxel) || (voxel.x >= 2 && voxel.y == 2);
+        assert_eq!(body.step_move(feet, Vec3::X * 0.25, tunnel), feet);
+
+        // No room to lift the body where it is
+        let ceiling = |voxel: IVec3| ledge(voxel) || voxel.y == 2;
+        assert_eq!(body.step_move(feet, Vec3::X * 0.25, ceiling), feet);
+    }
+
+    #[test]
+    fn step_move_corners() {
+        let body = Body::default();
+        let feet = Vec3::new(1.5, 0.0, 0.5);
+        let diagonal = Vec3::new(0.25, 0.0, 0.25);
+
+        // Walking diagonally into a wall slides along it
+        let wall = |voxel: IVec3| voxel.y < 0 || voxel.x >= 2;
+        assert_eq!(
+            body.step_move(feet, diagonal, wall),
+            Vec3::new(1.5, 0.0, 0.75)
+        );
+
+        // Walking diagonally onto a ledge steps on it once
+        assert_eq!(
+            body.step_move(feet, diagonal, ledge),
+            Vec3::new(1.75, 1.0, 0.75)
+        );
+
+        // Walking diagonally into an inner corner of ledges steps up once, onto both
+        let corner = |voxel: IVec3| ledge(voxel) || (voxel.z >= 1 && voxel.y < 1);
+        assert_eq!(
+            body.step_move(feet, diagonal, corner),
+            Vec3::new(1.75, 1.0, 0.75)
+        );
+    }
+
+    #[test]
+    fn fall() {
+        let body = Body::default();
+
+        assert_eq!(
+            body.fall(Vec3::new(0.5, 2.0, 0.5), -0.5, ledge),
+            (Vec3::new(0.5, 1.5, 0.5), false)
+        );
+
+        // Lands on top of the ground
+        assert_eq!(
+            body.fall(Vec3::new(0.5, 0.2, 0.5), -0.5, ledge),
+            (Vec3::new(0.5, 0.0, 0.5), true)
+        );
+        assert_eq!(
+            body.fall(Vec3::new(2.5, 1.2, 0.5), -0.5, ledge),
+            (Vec3::new(2.5, 1.0, 0.5), true)
+        );
+
+        // Hitting the ceiling stops rising bodies where they are
+        let ceiling = |voxel: IVec3| ledge(voxel) || voxel.y == 2;
+        assert_eq!(
+            body.fall(Vec3::new(0.5, 0.0, 0.5), 0.5, ceiling),
+            (Vec3::new(0.5, 0.0, 0.5), true)
+        );
+    }
+
+    #[test]
+    fn on_ground() {
+        let body = Body::default();
+
+        assert!(body.on_ground(Vec3::new(0.5, 0.0, 0.5), &ledge));
+        assert!(body.on_ground(Vec3::new(2.5, 1.0, 0.5), &ledge));
+        assert!(!body.on_ground(Vec3::new(0.5, 0.5, 0.5), &ledge));
+
+        // Standing over the edge of the ledge still stands on it
+        assert!(body.on_ground(Vec3::new(1.8, 1.0, 0.5), &ledge));
+        assert!(!body.on_ground(Vec3::new(1.6, 1.0, 0.5), &ledge));
+    }
+
+    #[test]
+    fn sneaking() {
+        let body = Body::default().sneaking();
+
+        assert_eq!(body.height, Body::default().height * SNEAK_HEIGHT);
+        assert_eq!(body.width, Body::default().width);
+    }
+
+    #[test]
+    fn sneak_move() {
+        let body = Body::default();
+        // A single row of ground voxels, from x = 0 to x = 1
+        let ground = |voxel: IVec3| voxel.y == -1 && (0..2).contains(&voxel.x) && voxel.z == 0;
+
+        // Walks over the edge, as long as some of the body is still over the ground
+        assert_eq!(
+            body.sneak_move(Vec3::new(1.5, 0.0, 0.5), Vec3::X * 0.5, ground),
+            Vec3::new(2.0, 0.0, 0.5)
+        );
+
+        // Stops right before leaving it
+        let feet = body.sneak_move(Vec3::new(2.0, 0.0, 0.5), Vec3::X * 0.5, ground);
+        assert!((feet.x - 2.3).abs() < 0.01);
+        assert!(body.on_ground(feet, &ground));
+
+        let feet = body.sneak_move(Vec3::new(0.0, 0.0, 0.5), -Vec3::X * 0.5, ground);
+        assert!((feet.x + 0.3).abs() < 0.01);
+        assert!(body.on_ground(feet, &ground));
+
+        // Walking diagonally off the side slides along the edge
+        let feet = body.sneak_move(Vec3::new(1.0, 0.0, 1.0), Vec3::new(0.25, 0.0, 0.5), ground);
+        assert_eq!(feet.x, 1.25);
+        assert!((feet.z - 1.3).abs() < 0.01);
+
+        // Bodies in the air aren't held back
+        assert_eq!(
+            body.sneak_move(Vec3::new(2.0, 3.0, 0.5), Vec3::X * 0.5, ground),
+            Vec3::new(2.5, 3.0, 0.5)
+        );
+    }
+}
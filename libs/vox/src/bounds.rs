@@ -0,0 +1,89 @@
+use bevy::prelude::*;
+
+use crate::chunk;
+
+/**
+  Axis aligned box, from `min` to `max`. Bounds of chunks and voxels are in world space, see [`chunk()`] and
+  [`voxel()`], while [`crate::pipeline::WorldOrigin`] gives them in render space.
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Bounds {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) / 2.0
+    }
+
+    pub fn size(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    /// Whether the point is inside the bounds, including their faces.
+    pub fn contains(&self, point: Vec3) -> bool {
+        point.cmpge(self.min).all() && point.cmple(self.max).all()
+    }
+
+    /// Whether both bounds overlap. Bounds which only touch each other don't.
+    pub fn intersects(&self, other: &Bounds) -> bool {
+        self.min.cmplt(other.max).all() && other.min.cmplt(self.max).all()
+    }
+
+    /// Same bounds moved by `offset`.
+    pub fn translated(&self, offset: Vec3) -> Self {
+        Self::new(self.min + offset, self.max + offset)
+    }
+}
+
+/// World space bounds of the chunk at the given local.
+pub fn chunk(local: IVec3) -> Bounds {
+    let min = chunk::to_world(local);
+    Bounds::new(min, min + Vec3::splat(chunk::AXIS_SIZE as f32))
+}
+
+/// World space bounds of the given world voxel.
+pub fn voxel(world: IVec3) -> Bounds {
+    let min = world.as_vec3();
+    Bounds::new(min, min + Vec3::ONE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunk() {
+        let bounds = super::chunk(IVec3::new(1, -1, 0));
+        let size = chunk::AXIS_SIZE as f32;
+
+        assert_eq!(bounds.min, Vec3::new(size, -size, 0.0));
+        assert_eq!(bounds.size(), Vec3::splat(size));
+        assert_eq!(bounds.center(), Vec3::new(1.5, -0.5, 0.5) * size);
+    }
+
+    #[test]
+    fn voxel() {
+        let bounds = super::voxel(IVec3::new(-3, 2, 0));
+
+        assert_eq!(bounds.center(), Vec3::new(-2.5, 2.5, 0.5));
+        assert!(bounds.contains(Vec3::new(-3.0, 2.9, 1.0)));
+        assert!(!bounds.contains(Vec3::new(-3.1, 2.5, 0.5)));
+    }
+
+    #[test]
+    fn intersects() {
+        let bounds = super::voxel(IVec3::ZERO);
+
+        assert!(bounds.intersects(&Bounds::new(Vec3::splat(0.5), Vec3::splat(2.0))));
+        assert!(!bounds.intersects(&super::voxel(IVec3::X)));
+        assert!(bounds
+            .translated(Vec3::X * 0.5)
+            .intersects(&super::voxel(IVec3::X)));
+    }
+}
@@ -0,0 +1,198 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{bounds::Bounds, query};
+
+/// Size, in voxels, of each broadphase cell. Most entities are smaller than it, so they fit on a few cells.
+pub const CELL_SIZE: f32 = 4.0;
+
+/**
+  Spatial hash of entity bounds, rebuilt as entities move, so finding what's near something only checks
+  entities on the cells it overlaps, instead of every entity around. Bounds may be in any space, as long as
+  all of them, and the queries, are in the same one.
+*/
+#[derive(Debug, Default)]
+pub struct Broadphase {
+    cells: HashMap<IVec3, Vec<usize>>,
+    entries: Vec<(Entity, Bounds)>,
+}
+
+impl Broadphase {
+    /// Removes every entity, keeping the memory around to be reused.
+    pub fn clear(&mut self) {
+        self.cells.values_mut().for_each(Vec::clear);
+        self.entries.clear();
+    }
+
+    pub fn insert(&mut self, entity: Entity, bounds: Bounds) {
+        let idx = self.entries.len();
+        self.entries.push((entity, bounds));
+
+        for cell in cells(&bounds) {
+            self.cells.entry(cell).or_default().push(idx);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Entities whose bounds overlap the given ones, each returned once.
+    pub fn query(&self, bounds: &Bounds) -> Vec<Entity> {
+        let mut found = cells(bounds)
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+            .filter(|idx| self.entries[*idx].1.intersects(bounds))
+            .collect::<Vec<_>>();
+
+        found.sort_unstable();
+        found.dedup();
+
+        found.into_iter().map(|idx| self.entries[idx].0).collect()
+    }
+
+    /// Every pair of entities whose bounds overlap, each returned once.
+    pub fn pairs(&self) -> Vec<(Entity, Entity)> {
+        let mut pairs = self
+            .cells
+            .values()
+            .flat_map(|entries| {
+                entries.iter().enumerate().flat_map(move |(i, a)| {
+                    entries[i + 1..].iter().map(move |b| (*a.min(b), *a.max(b)))
+                })
+            })
+            .filter(|(a, b)| self.entries[*a].1.intersects(&self.entries[*b].1))
+            .collect::<Vec<_>>();
+
+        // Pairs sharing more than a cell are found on each of them
+        pairs.sort_unstable();
+        pairs.dedup();
+
+        pairs
+            .into_iter()
+            .map(|(a, b)| (self.entries[a].0, self.entries[b].0))
+            .collect()
+    }
+}
+
+/// Cells the given bounds overlap.
+fn cells(bounds: &Bounds) -> impl Iterator<Item = IVec3> {
+    let to_cell = |point: Vec3| (point / CELL_SIZE).floor().as_ivec3();
+    query::range_inclusive(to_cell(bounds.min), to_cell(bounds.max))
+}
+
+/**
+  Voxel narrowphase. Whether the given bounds overlap any voxel `solid` returns true for. Bounds must be in
+  the same space `solid` takes voxels on.
+*/
+pub fn collides_voxels(bounds: &Bounds, solid: &impl Fn(IVec3) -> bool) -> bool {
+    let min = bounds.min.floor().as_ivec3();
+    let max = bounds.max.floor().as_ivec3();
+
+    query::range_inclusive(min, max).any(solid)
+}
+
+/**
+  Horizontal direction which pushes something at `center` away from something else at `other`, so entities
+  walking into each other are kept apart. Entities right on top of each other are pushed along X.
+*/
+pub fn push_apart(center: Vec3, other: Vec3) -> Vec3 {
+    let away = Vec3::new(center.x - other.x, 0.0, center.z - other.z);
+
+    if away == Vec3::ZERO {
+        Vec3::X
+    } else {
+        away.normalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cube(center: Vec3, half: f32) -> Bounds {
+        Bounds::new(center - Vec3::splat(half), center + Vec3::splat(half))
+    }
+
+    fn broadphase(centers: &[Vec3]) -> Broadphase {
+        let mut broadphase = Broadphase::default();
+
+        for (idx, center) in centers.iter().enumerate() {
+            broadphase.insert(Entity::from_raw(idx as u32), cube(*center, 0.5));
+        }
+
+        broadphase
+    }
+
+    #[test]
+    fn query() {
+        let broadphase = broadphase(&[
+            Vec3::new(1.0, 1.0, 1.0),
+            // Crosses cell borders
+            Vec3::new(4.0, 4.0, 4.0),
+            Vec3::new(20.0, 1.0, 1.0),
+        ]);
+
+        let found = broadphase.query(&cube(Vec3::new(2.5, 2.5, 2.5), 1.5));
+        assert_eq!(found, vec![Entity::from_raw(0), Entity::from_raw(1)]);
+
+        // Overlapping the cell isn't enough, the bounds must overlap too
+        assert!(broadphase
+            .query(&cube(Vec3::new(18.0, 1.0, 1.0), 0.5))
+            .is_empty());
+    }
+
+    #[test]
+    fn pairs() {
+        let mut broadphase = broadphase(&[
+            Vec3::new(4.0, 4.0, 4.0),
+            Vec3::new(4.5, 4.0, 4.0),
+            Vec3::new(10.0, 1.0, 1.0),
+            Vec3::new(4.0, 4.8, 4.0),
+        ]);
+
+        assert_eq!(
+            broadphase.pairs(),
+            vec![
+                (Entity::from_raw(0), Entity::from_raw(1)),
+                (Entity::from_raw(0), Entity::from_raw(3)),
+                (Entity::from_raw(1), Entity::from_raw(3)),
+            ]
+        );
+
+        broadphase.clear();
+        assert!(broadphase.is_empty());
+        assert!(broadphase.pairs().is_empty());
+    }
+
+    #[test]
+    fn collides_voxels() {
+        let solid = |voxel: IVec3| voxel == IVec3::new(1, 0, 0);
+
+        assert!(super::collides_voxels(
+            &cube(Vec3::new(0.8, 0.5, 0.5), 0.3),
+            &solid
+        ));
+        assert!(!super::collides_voxels(
+            &cube(Vec3::new(0.5, 0.5, 0.5), 0.3),
+            &solid
+        ));
+    }
+
+    #[test]
+    fn push_apart() {
+        assert_eq!(
+            super::push_apart(Vec3::new(1.0, 5.0, 0.0), Vec3::ZERO),
+            Vec3::X
+        );
+        assert_eq!(
+            super::push_apart(Vec3::new(0.0, 0.0, -2.0), Vec3::ZERO),
+            -Vec3::Z
+        );
+        assert_eq!(super::push_apart(Vec3::ONE, Vec3::ONE), Vec3::X);
+    }
+}
@@ -0,0 +1,215 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    chunk::{self, ChunkKind},
+    generator::chunk_rng,
+    voxel::Kind,
+};
+
+const DIRT: u16 = 7;
+const WATER: u16 = 23;
+const STALACTITE: u16 = 27;
+const STALAGMITE: u16 = 28;
+const GLOW_MUSHROOM: u16 = 29;
+
+/// Chance of a cave ceiling voxel getting a stalactite hanging from it.
+const STALACTITE_CHANCE: f32 = 0.08;
+/// Chance of a cave floor voxel getting a stalagmite.
+const STALAGMITE_CHANCE: f32 = 0.05;
+/// Chance of a cave floor voxel getting a glowing mushroom, when it didn't get a stalagmite.
+const MUSHROOM_CHANCE: f32 = 0.03;
+
+/// How deep, in voxels, below its shallowest depth ores take to reach their full density.
+const ORE_RAMP: f32 = 8.0;
+
+/// An ore buried in the dirt, which gets more common the deeper it is.
+struct Ore {
+    kind: u16,
+    /// Shallowest depth, in voxels below the surface, the ore is found at.
+    min_depth: f32,
+    /// Chance of each dirt voxel being this ore, once it's deep enough.
+    density: f32,
+}
+
+/// Ores from the shallowest to the deepest. Deeper ores are checked first, so they win over shallower ones.
+const ORES: [Ore; 3] = [
+    Ore {
+        kind: 30,
+        min_depth: 4.0,
+        density: 0.02,
+    },
+    Ore {
+        kind: 31,
+        min_depth: 12.0,
+        density: 0.012,
+    },
+    Ore {
+        kind: 32,
+        min_depth: 20.0,
+        density: 0.006,
+    },
+];
+
+/// Chance of each dirt voxel at the given depth below the surface being the given ore.
+fn ore_density(ore: &Ore, depth: f32) -> f32 {
+    if depth < ore.min_depth {
+        return 0.0;
+    }
+
+    ((depth - ore.min_depth + 1.0) / ORE_RAMP).min(1.0) * ore.density
+}
+
+/// Whether cave decorations can hang from, or stand on, the given kind. They can't stack on each other.
+fn is_rock(kind: Kind) -> bool {
+    !kind.is_empty() && ![WATER, STALACTITE, STALAGMITE, GLOW_MUSHROOM].contains(&kind.id())
+}
+
+/**
+  Decorates the underground of a freshly generated chunk. Dirt gets ores, more of them the deeper it is, and
+  air pockets below the terrain surface, like caves carved by post processes, get stalactites hanging from
+  their ceilings and stalagmites and glowing mushrooms on their floors.
+
+  `heights` are the terrain heights of the chunk columns, as returned by [`crate::generator::Terrain::heights`].
+  Ores and decorations are rolled from their own [`chunk_rng`] streams, so the same chunk is always decorated
+  the same way, whichever order chunks are generated in. Pockets are only looked for inside the chunk, so
+  voxels on its top and bottom aren't decorated.
+*/
+pub fn decorate(seed: u64, local: IVec3, kind: &mut ChunkKind, heights: &[f32]) {
+    let world = chunk::to_world(local).as_ivec3();
+    let mut ores = chunk_rng(seed, local, "ores");
+    let mut caves = chunk_rng(seed, local, "caves");
+
+    for x in 0..chunk::AXIS_SIZE as i32 {
+        for z in 0..chunk::AXIS_SIZE as i32 {
+            let surface = heights[(x * chunk::AXIS_SIZE as i32 + z) as usize];
+            let column = kind.column_mut(x, z);
+
+            for y in 0..chunk::AXIS_SIZE {
+                let depth = surface - (world.y + y as i32) as f32;
+
+                if depth < 1.0 {
+                    break;
+                }
+
+                if column[y].id() == DIRT {
+                    let ore = ORES
+                        .iter()
+                        .rev()
+                        .find(|ore| ores.gen::<f32>() < ore_density(ore, depth));
+
+                    if let Some(ore) = ore {
+                        column[y] = ore.kind.into();
+                    }
+
+                    continue;
+                }
+
+                if !column[y].is_empty() || y == 0 || y == chunk::AXIS_ENDING {
+                    continue;
+                }
+
+                let roll = caves.gen::<f32>();
+                if is_rock(column[y + 1]) && roll < STALACTITE_CHANCE {
+                    column[y] = STALACTITE.into();
+                } else if is_rock(column[y - 1]) {
+                    if roll < STALAGMITE_CHANCE {
+                        column[y] = STALAGMITE.into();
+                    } else if roll < STALAGMITE_CHANCE + MUSHROOM_CHANCE {
+                        column[y] = GLOW_MUSHROOM.into();
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ore_density() {
+        let coal = &ORES[0];
+
+        assert_eq!(super::ore_density(coal, 0.0), 0.0);
+        assert!(super::ore_density(coal, coal.min_depth) > 0.0);
+        assert!(
+            super::ore_density(coal, coal.min_depth + 2.0)
+                > super::ore_density(coal, coal.min_depth)
+        );
+        assert_eq!(super::ore_density(coal, 100.0), coal.density);
+    }
+
+    #[test]
+    fn decorate() {
+        let heights = vec![chunk::AXIS_SIZE as f32 * 4.0; chunk::AXIS_SIZE * chunk::AXIS_SIZE];
+
+        // A deep chunk, filled with dirt, with a cave in the middle
+        let mut kind = ChunkKind::default();
+        for x in 0..chunk::AXIS_SIZE as i32 {
+            for z in 0..chunk::AXIS_SIZE as i32 {
+                let column = kind.column_mut(x, z);
+                column.fill(DIRT.into());
+                column[4..12].fill(0.into());
+            }
+        }
+
+        let cave = kind.clone();
+        super::decorate(1, IVec3::new(0, 1, 0), &mut kind, &heights);
+
+        // Same seed and chunk, same decorations
+        let mut again = cave;
+        super::decorate(1, IVec3::new(0, 1, 0), &mut again, &heights);
+        assert!(again == kind);
+
+        let count = |id: u16| {
+            (0..chunk::AXIS_SIZE as i32)
+                .flat_map(|x| (0..chunk::AXIS_SIZE as i32).map(move |z| (x, z)))
+                .flat_map(|(x, z)| kind.column(x, z).to_vec())
+                .filter(|kind| kind.id() == id)
+                .count()
+        };
+
+        assert!(count(STALACTITE) > 0);
+        assert!(count(STALAGMITE) > 0);
+        assert!(count(GLOW_MUSHROOM) > 0);
+        assert!(ORES.iter().any(|ore| count(ore.kind) > 0));
+
+        // Decorations only hang from ceilings or stand on floors
+        for x in 0..chunk::AXIS_SIZE as i32 {
+            for z in 0..chunk::AXIS_SIZE as i32 {
+                let column = kind.column(x, z);
+                for (y, voxel) in column.iter().enumerate() {
+                    match voxel.id() {
+                        STALACTITE => assert_eq!(y, 11),
+                        STALAGMITE | GLOW_MUSHROOM => assert_eq!(y, 4),
+                        _ => (),
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn decorate_surface() {
+        let heights = vec![8.0; chunk::AXIS_SIZE * chunk::AXIS_SIZE];
+
+        // Air above the surface isn't a cave
+        let mut kind = ChunkKind::default();
+        for x in 0..chunk::AXIS_SIZE as i32 {
+            for z in 0..chunk::AXIS_SIZE as i32 {
+                kind.column_mut(x, z)[..8].fill(DIRT.into());
+            }
+        }
+
+        let before = kind.clone();
+        super::decorate(1, IVec3::ZERO, &mut kind, &heights);
+
+        for x in 0..chunk::AXIS_SIZE as i32 {
+            for z in 0..chunk::AXIS_SIZE as i32 {
+                assert_eq!(kind.column(x, z)[8..], before.column(x, z)[8..]);
+            }
+        }
+    }
+}
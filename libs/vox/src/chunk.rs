@@ -104,6 +104,21 @@ impl<T: ChunkStorageType> ChunkStorage<T> {
         self.main[to_index(local)] = value;
     }
 
+    /**
+      Values of the given column, from the bottom to the top of the chunk. Columns are contiguous in memory, so
+      going through a column this way is faster than getting each voxel.
+    */
+    pub fn column(&self, x: i32, z: i32) -> &[T] {
+        let begin = to_index((x, 0, z).into());
+        &self.main[begin..begin + AXIS_SIZE]
+    }
+
+    /// Same as [`ChunkStorage::column`], but the values can be changed.
+    pub fn column_mut(&mut self, x: i32, z: i32) -> &mut [T] {
+        let begin = to_index((x, 0, z).into());
+        &mut self.main[begin..begin + AXIS_SIZE]
+    }
+
     #[cfg(test)]
     pub fn set_all(&mut self, value: T) {
         self.main.fill(value);
@@ -113,6 +128,12 @@ impl<T: ChunkStorageType> ChunkStorage<T> {
         self.main.iter()
     }
 
+    /// Checksum of the chunk values, used to detect corrupted saves. Neighborhood values aren't included.
+    pub fn checksum(&self) -> u64 {
+        let bytes = bincode::serialize(self).expect("Chunk storage must be serializable");
+        math::fnv_hash(bytes)
+    }
+
     #[cfg(test)]
     pub fn is_default(&self) -> bool {
         self.is_all(T::default())
@@ -251,6 +272,29 @@ pub fn to_local(world: Vec3) -> IVec3 {
     )
 }
 
+/// World voxel position of the given voxel inside the chunk at `local`. This is the opposite of [`split_voxel`].
+pub fn join_voxel(local: IVec3, voxel: IVec3) -> IVec3 {
+    local * AXIS_SIZE as i32 + voxel
+}
+
+/// Splits a world voxel position into the local of its chunk and the voxel inside it.
+pub fn split_voxel(world: IVec3) -> (IVec3, IVec3) {
+    let size = AXIS_SIZE as i32;
+
+    (
+        IVec3::new(
+            world.x.div_euclid(size),
+            world.y.div_euclid(size),
+            world.z.div_euclid(size),
+        ),
+        IVec3::new(
+            world.x.rem_euclid(size),
+            world.y.rem_euclid(size),
+            world.z.rem_euclid(size),
+        ),
+    )
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct ChunkNeighborhood<T: ChunkStorageType>(
     [Option<[T; AXIS_SIZE * AXIS_SIZE]>; voxel::SIDE_COUNT],
@@ -434,6 +478,23 @@ mod tests {
         }
     }
 
+    #[test]
+    fn split_voxel() {
+        assert_eq!(
+            super::split_voxel((1, 17, -1).into()),
+            ((0, 1, -1).into(), (1, 1, 15).into())
+        );
+        assert_eq!(
+            super::split_voxel((-16, 0, 15).into()),
+            ((-1, 0, 0).into(), (0, 0, 15).into())
+        );
+
+        for world in [IVec3::new(1, 17, -1), IVec3::new(-16, 0, 15)] {
+            let (local, voxel) = super::split_voxel(world);
+            assert_eq!(super::join_voxel(local, voxel), world);
+        }
+    }
+
     #[test]
     fn to_local() {
         use super::*;
@@ -521,6 +582,24 @@ mod tests {
         }
     }
 
+    #[test]
+    fn column() {
+        let mut chunk = ChunkStorage::<u8>::default();
+        chunk.set((3, 0, 5).into(), 1);
+        chunk.set((3, 15, 5).into(), 2);
+        chunk.set((5, 7, 3).into(), 3);
+
+        let column = chunk.column(3, 5);
+        assert_eq!(column.len(), AXIS_SIZE);
+        assert_eq!((column[0], column[7], column[15]), (1, 0, 2));
+
+        chunk.column_mut(5, 3)[..4].fill(4);
+        assert_eq!(chunk.get((5, 3, 3).into()), 4);
+        assert_eq!(chunk.get((5, 4, 3).into()), 0);
+        assert_eq!(chunk.get((5, 7, 3).into()), 3);
+        assert_eq!(chunk.get((3, 3, 5).into()), 0);
+    }
+
     #[test]
     fn overlap_voxel() {
         assert_eq!(
@@ -0,0 +1,126 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::edit::Region;
+
+/**
+  A region where only its owner can change voxels. Regions without owner are protected from every player,
+  and only the server itself can change them.
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Claim {
+    pub name: String,
+    pub owner: Option<String>,
+    pub region: Region,
+}
+
+/**
+  All claimed regions of the world, saved with the world metadata.
+*/
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Claims(Vec<Claim>);
+
+impl Claims {
+    pub fn iter(&self) -> impl Iterator<Item = &Claim> {
+        self.0.iter()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Claim> {
+        self.0.iter().find(|claim| claim.name == name)
+    }
+
+    /// All claims containing the given world voxel.
+    pub fn at(&self, position: IVec3) -> impl Iterator<Item = &Claim> {
+        self.0
+            .iter()
+            .filter(move |claim| claim.region.contains(position))
+    }
+
+    /// Whether the given player can change the given world voxel.
+    pub fn can_edit(&self, player: &str, position: IVec3) -> bool {
+        self.at(position)
+            .all(|claim| claim.owner.as_deref() == Some(player))
+    }
+
+    /**
+      Adds a new claim. Names must be unique, and claims can't overlap claims of other owners.
+    */
+    pub fn claim(&mut self, claim: Claim) -> Result<(), String> {
+        if self.get(&claim.name).is_some() {
+            return Err(format!("Claim {} already exists", claim.name));
+        }
+
+        if let Some(other) = self
+            .0
+            .iter()
+            .find(|other| other.owner != claim.owner && other.region.intersects(&claim.region))
+        {
+            return Err(format!("Overlaps claim {}", other.name));
+        }
+
+        self.0.push(claim);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Claim> {
+        let idx = self.0.iter().position(|claim| claim.name == name)?;
+        Some(self.0.remove(idx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claim(name: &str, owner: Option<&str>, min: (i32, i32, i32), max: (i32, i32, i32)) -> Claim {
+        Claim {
+            name: name.to_string(),
+            owner: owner.map(str::to_string),
+            region: Region::from_corners(min.into(), max.into()),
+        }
+    }
+
+    #[test]
+    fn can_edit() {
+        let mut claims = Claims::default();
+        claims
+            .claim(claim("house", Some("alice"), (0, 0, 0), (9, 9, 9)))
+            .unwrap();
+        claims
+            .claim(claim("spawn", None, (-20, 0, -20), (-10, 9, -10)))
+            .unwrap();
+
+        assert!(claims.can_edit("alice", (5, 5, 5).into()));
+        assert!(!claims.can_edit("bob", (5, 5, 5).into()));
+        assert!(claims.can_edit("bob", (10, 5, 5).into()));
+
+        assert!(!claims.can_edit("alice", (-15, 0, -15).into()));
+        assert_eq!(claims.at((-15, 0, -15).into()).count(), 1);
+    }
+
+    #[test]
+    fn claim_overlap() {
+        let mut claims = Claims::default();
+        claims
+            .claim(claim("house", Some("alice"), (0, 0, 0), (9, 9, 9)))
+            .unwrap();
+
+        // Same name
+        assert!(claims
+            .claim(claim("house", Some("alice"), (20, 0, 0), (29, 9, 9)))
+            .is_err());
+
+        // Owners can extend their own claims
+        assert!(claims
+            .claim(claim("garden", Some("alice"), (5, 0, 0), (15, 9, 9)))
+            .is_ok());
+
+        assert!(claims
+            .claim(claim("shop", Some("bob"), (9, 9, 9), (12, 12, 12)))
+            .is_err());
+
+        assert!(claims.remove("house").is_some());
+        assert!(claims.remove("house").is_none());
+        assert!(claims.get("garden").is_some());
+    }
+}
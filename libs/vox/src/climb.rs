@@ -0,0 +1,101 @@
+use bevy::prelude::*;
+
+use crate::{query, voxel::KindRegistry, world::VoxWorld};
+
+/// Speed, in voxels per second, bodies climb up or down climbable voxels.
+pub const CLIMB_SPEED: f32 = 3.0;
+
+/**
+  Whether a body touches any climbable voxel, like a ladder. The body is a box standing on `bottom`, in world
+  coordinates, with the given width and height.
+*/
+pub fn touches_climbable(
+    world: &VoxWorld,
+    registry: &KindRegistry,
+    bottom: Vec3,
+    width: f32,
+    height: f32,
+) -> bool {
+    let half = Vec3::new(width / 2.0, 0.0, width / 2.0);
+    let min = (bottom - half).floor().as_ivec3();
+    let max = (bottom + half + Vec3::Y * height).floor().as_ivec3();
+
+    query::range_inclusive(min, max).any(
+        |position| matches!(world.get_voxel(position), Some(kind) if registry.is_climbable(kind)),
+    )
+}
+
+/**
+  Velocity of a body climbing with the given vertical input, from -1 to go down to 1 to go up. Climbing bodies
+  hold on where they are when there's no input, so the caller must not apply gravity to them.
+*/
+pub fn climb(velocity: Vec3, input: f32) -> Vec3 {
+    Vec3::new(velocity.x, input.clamp(-1.0, 1.0) * CLIMB_SPEED, velocity.z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chunk::ChunkKind,
+        voxel::{KindDescription, MeshShape},
+    };
+
+    #[test]
+    fn touches_climbable() {
+        let world = VoxWorld::default();
+        world.add(IVec3::ZERO, ChunkKind::default());
+        world
+            .get_mut(IVec3::ZERO)
+            .unwrap()
+            .set((2, 3, 2).into(), 1.into());
+
+        let registry = KindRegistry::new(vec![KindDescription {
+            name: "Ladder".to_string(),
+            id: 1,
+            color: (1.0, 1.0, 1.0, 1.0),
+            shape: MeshShape::Panel,
+            prop: None,
+            directional: true,
+            light: 0,
+            tick: None,
+            signal: None,
+            toggle: None,
+            hardness: 0.0,
+            tool_tier: 0,
+            block_entity: None,
+            explosive: None,
+            liquid: None,
+            climbable: true,
+            surface: None,
+            foliage: false,
+            tint: None,
+            portal: None,
+        }]);
+
+        let touches = |bottom: Vec3| super::touches_climbable(&world, &registry, bottom, 0.6, 1.8);
+
+        // Standing below the ladder, reaching it with the head
+        assert!(touches(Vec3::new(2.5, 2.0, 2.5)));
+        // Standing next to it, within the body width
+        assert!(touches(Vec3::new(1.9, 3.0, 2.5)));
+
+        assert!(!touches(Vec3::new(2.5, 0.0, 2.5)));
+        assert!(!touches(Vec3::new(1.5, 3.0, 2.5)));
+    }
+
+    #[test]
+    fn climb() {
+        let velocity = Vec3::new(1.0, -10.0, 2.0);
+
+        assert_eq!(
+            super::climb(velocity, 1.0),
+            Vec3::new(1.0, CLIMB_SPEED, 2.0)
+        );
+        assert_eq!(
+            super::climb(velocity, -2.0),
+            Vec3::new(1.0, -CLIMB_SPEED, 2.0)
+        );
+        assert_eq!(super::climb(velocity, 0.0), Vec3::new(1.0, 0.0, 2.0));
+    }
+}
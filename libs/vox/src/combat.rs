@@ -0,0 +1,126 @@
+use bevy::prelude::*;
+
+/// Upwards speed, relative to the knockback strength, things knocked back are thrown at.
+const KNOCKBACK_LIFT: f32 = 0.4;
+
+/// Health points of something which can be hurt, like mobs.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Self { current: max, max }
+    }
+
+    /// Takes `amount` of health away, never below zero. Returns whether it was the killing blow.
+    pub fn damage(&mut self, amount: f32) -> bool {
+        let alive = !self.is_dead();
+        self.current = (self.current - amount).max(0.0);
+        alive && self.is_dead()
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.0
+    }
+
+    pub fn is_damaged(&self) -> bool {
+        self.current < self.max
+    }
+
+    /// How much health is left, from 0.0 (dead) to 1.0 (full).
+    pub fn fraction(&self) -> f32 {
+        if self.max <= 0.0 {
+            return 0.0;
+        }
+
+        self.current / self.max
+    }
+}
+
+/**
+  Whether a target sphere, of the given `radius` around `target`, is hit by a melee sweep from `eyes` looking
+  towards `forward`. Sweeps reach `reach` voxels and cover `half_angle` radians on each side of `forward`, so
+  targets don't need to be exactly under the crosshair.
+*/
+pub fn in_sweep(
+    eyes: Vec3,
+    forward: Vec3,
+    reach: f32,
+    half_angle: f32,
+    target: Vec3,
+    radius: f32,
+) -> bool {
+    let offset = target - eyes;
+    let distance = offset.length();
+
+    if distance - radius > reach {
+        return false;
+    }
+
+    // Targets overlapping the eyes are always hit
+    if distance <= radius {
+        return true;
+    }
+
+    // Wider targets are hit further away from the sweep center
+    let spread = (radius / distance).asin();
+    offset.angle_between(forward) <= half_angle + spread
+}
+
+/**
+  Velocity, in voxels per second, of something at `to` knocked back by a hit coming from `from`. It's
+  pushed horizontally away from the hit, and a bit upwards, so it's thrown off the ground.
+*/
+pub fn knockback(from: Vec3, to: Vec3, strength: f32) -> Vec3 {
+    let away = Vec3::new(to.x - from.x, 0.0, to.z - from.z).normalize_or_zero();
+    (away + Vec3::Y * KNOCKBACK_LIFT) * strength
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn damage() {
+        let mut health = Health::new(10.0);
+        assert!(!health.is_damaged());
+
+        assert!(!health.damage(4.0));
+        assert!(health.is_damaged());
+        assert_eq!(health.fraction(), 0.6);
+
+        assert!(health.damage(7.0));
+        assert!(health.is_dead());
+        assert_eq!(health.current, 0.0);
+
+        // Only the first killing blow counts
+        assert!(!health.damage(1.0));
+    }
+
+    #[test]
+    fn in_sweep() {
+        let hit = |target: Vec3| super::in_sweep(Vec3::ZERO, Vec3::X, 3.0, 0.5, target, 0.3);
+
+        assert!(hit(Vec3::new(2.0, 0.0, 0.0)));
+        assert!(hit(Vec3::new(2.0, 0.5, 0.5)));
+        assert!(hit(Vec3::new(0.1, 0.0, 0.1)));
+
+        // Too far, behind or too far to the side
+        assert!(!hit(Vec3::new(4.0, 0.0, 0.0)));
+        assert!(!hit(Vec3::new(-2.0, 0.0, 0.0)));
+        assert!(!hit(Vec3::new(1.0, 0.0, 2.0)));
+    }
+
+    #[test]
+    fn knockback() {
+        let velocity = super::knockback(Vec3::ZERO, Vec3::new(0.0, 5.0, 2.0), 10.0);
+        assert_eq!(velocity, Vec3::new(0.0, 10.0 * KNOCKBACK_LIFT, 10.0));
+
+        // Straight above, there's no way to push it but upwards
+        let velocity = super::knockback(Vec3::ZERO, Vec3::Y, 10.0);
+        assert_eq!(velocity, Vec3::Y * 10.0 * KNOCKBACK_LIFT);
+    }
+}
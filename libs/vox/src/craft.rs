@@ -0,0 +1,164 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::{
+    item::Inventory,
+    voxel::{Kind, KindRegistry},
+};
+
+/// A count of voxels of the same kind, by kind id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ItemStack {
+    pub kind: u16,
+    pub count: u32,
+}
+
+/**
+  Turns the `inputs`, taken from the inventory, into the `output`.
+*/
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Recipe {
+    pub inputs: Vec<ItemStack>,
+    pub output: ItemStack,
+}
+
+impl Recipe {
+    pub fn can_craft(&self, inventory: &Inventory) -> bool {
+        self.inputs
+            .iter()
+            .all(|input| inventory.count(input.kind.into()) >= input.count)
+    }
+
+    /// Takes the inputs from the given inventory and adds the output to it, if it has all inputs.
+    pub fn craft(&self, inventory: &mut Inventory) -> bool {
+        if !self.can_craft(inventory) {
+            return false;
+        }
+
+        for input in &self.inputs {
+            inventory.remove(input.kind.into(), input.count);
+        }
+
+        inventory.add(self.output.kind.into(), self.output.count);
+        true
+    }
+}
+
+/**
+  All known recipes, in the order they were defined.
+*/
+#[derive(Debug, Default, Clone)]
+pub struct RecipeRegistry {
+    recipes: Vec<Recipe>,
+}
+
+impl RecipeRegistry {
+    pub fn load(path: &Path, kinds: &KindRegistry) -> Self {
+        let file = std::fs::File::open(path)
+            .unwrap_or_else(|_| panic!("Failed opening recipes file at {}", path.display()));
+
+        let recipes: Vec<Recipe> = ron::de::from_reader(file)
+            .unwrap_or_else(|_| panic!("Failed to parse recipes {}", path.display()));
+
+        Self::new(recipes, kinds)
+    }
+
+    /// Creates the registry. All stacks must be of known, non-empty, kinds and have at least one voxel.
+    pub fn new(recipes: Vec<Recipe>, kinds: &KindRegistry) -> Self {
+        for (idx, recipe) in recipes.iter().enumerate() {
+            assert!(!recipe.inputs.is_empty(), "Recipe {} has no inputs", idx);
+
+            for stack in recipe.inputs.iter().chain(std::iter::once(&recipe.output)) {
+                let kind = Kind::from(stack.kind);
+
+                assert!(
+                    !kind.is_empty() && kinds.get(kind).is_some(),
+                    "Recipe {} uses unknown kind id {}",
+                    idx,
+                    stack.kind
+                );
+                assert!(stack.count > 0, "Recipe {} has an empty stack", idx);
+            }
+        }
+
+        Self { recipes }
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&Recipe> {
+        self.recipes.get(idx)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Recipe> {
+        self.recipes.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.recipes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.recipes.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds() -> KindRegistry {
+        let input_path = format!(
+            "{}assets/voxels/kind_descriptions.ron",
+            env!("CARGO_WORKSPACE_DIR")
+        );
+
+        KindRegistry::load(Path::new(&input_path))
+    }
+
+    fn stack(kind: u16, count: u32) -> ItemStack {
+        ItemStack { kind, count }
+    }
+
+    #[test]
+    fn recipe_registry() {
+        let input_path = format!("{}assets/items/recipes.ron", env!("CARGO_WORKSPACE_DIR"));
+
+        let recipes = RecipeRegistry::load(Path::new(&input_path), &kinds());
+
+        assert!(!recipes.is_empty());
+        assert!(recipes.get(recipes.len()).is_none());
+    }
+
+    #[test]
+    #[should_panic]
+    fn unknown_kind() {
+        RecipeRegistry::new(
+            vec![Recipe {
+                inputs: vec![stack(1, 1)],
+                output: stack(4000, 1),
+            }],
+            &kinds(),
+        );
+    }
+
+    #[test]
+    fn craft() {
+        let recipe = Recipe {
+            inputs: vec![stack(9, 2), stack(5, 1)],
+            output: stack(16, 1),
+        };
+
+        let mut inventory = Inventory::default();
+        inventory.add(9.into(), 3);
+
+        assert!(!recipe.craft(&mut inventory));
+        assert_eq!(inventory.count(9.into()), 3);
+
+        inventory.add(5.into(), 1);
+        assert!(recipe.craft(&mut inventory));
+
+        assert_eq!(inventory.count(9.into()), 1);
+        assert_eq!(inventory.count(5.into()), 0);
+        assert_eq!(inventory.count(16.into()), 1);
+        assert!(!recipe.can_craft(&inventory));
+    }
+}
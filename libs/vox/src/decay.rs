@@ -0,0 +1,250 @@
+use bevy::{prelude::*, utils::HashSet};
+use rand::Rng;
+use std::ops::RangeInclusive;
+
+use crate::{
+    query,
+    voxel::{self, Kind, KindRegistry, TickBehavior},
+    world::VoxWorld,
+};
+
+/**
+  Ticks a voxel waits, after it was found without support, until it decays. It's random, so leaves of a cut tree
+  fall one after another instead of all at once.
+*/
+pub const DECAY_DELAY: RangeInclusive<u64> = 20..=200;
+
+/**
+  Whether the voxel at `position`, of the given kind, is within `range` steps of a `support` voxel. Steps only
+  go through neighbor voxels of the same kind, like leaves connected to a log. Unloaded voxels may hold a
+  support, so reaching one also counts as supported.
+*/
+pub fn is_supported(
+    world: &VoxWorld,
+    position: IVec3,
+    kind: Kind,
+    support: Kind,
+    range: u8,
+) -> bool {
+    let mut visited = HashSet::default();
+    visited.insert(position);
+
+    let mut frontier = vec![position];
+
+    for _ in 0..range {
+        let mut next = vec![];
+
+        for voxel in frontier {
+            for side in voxel::SIDES {
+                let neighbor = voxel + side.dir();
+
+                if !visited.insert(neighbor) {
+                    continue;
+                }
+
+                match world.get_voxel(neighbor) {
+                    None => return true,
+                    Some(found) if found.id() == support.id() => return true,
+                    Some(found) if found.id() == kind.id() => next.push(neighbor),
+                    Some(_) => (),
+                }
+            }
+        }
+
+        if next.is_empty() {
+            break;
+        }
+
+        frontier = next;
+    }
+
+    false
+}
+
+/// Whether the voxel at `position` decays, since it has a decay behavior and nothing supports it.
+pub fn should_decay(world: &VoxWorld, registry: &KindRegistry, position: IVec3) -> bool {
+    let kind = match world.get_voxel(position) {
+        Some(kind) => kind,
+        None => return false,
+    };
+
+    match registry.tick(kind) {
+        Some(TickBehavior::Decay { support, range }) => {
+            !is_supported(world, position, kind, support.into(), range)
+        }
+        _ => false,
+    }
+}
+
+/**
+  Voxels around `position` which decay without the given support kind, like leaves around a log which was
+  cut. Only voxels within the range of their kind are returned, so they may have lost their support.
+*/
+pub fn around(
+    world: &VoxWorld,
+    registry: &KindRegistry,
+    position: IVec3,
+    support: Kind,
+) -> Vec<IVec3> {
+    let range = |kind: Kind| match registry.tick(kind) {
+        Some(TickBehavior::Decay {
+            support: supported_by,
+            range,
+        }) if supported_by == support.id() => Some(range as i32),
+        _ => None,
+    };
+
+    let max_range = match registry.kinds().filter_map(range).max() {
+        Some(range) => range,
+        None => return vec![],
+    };
+
+    query::range_inclusive(
+        position - IVec3::splat(max_range),
+        position + IVec3::splat(max_range),
+    )
+    .filter(|voxel| {
+        world
+            .get_voxel(*voxel)
+            .and_then(range)
+            .is_some_and(|range| (*voxel - position).abs().max_element() <= range)
+    })
+    .collect()
+}
+
+/// Random delay, in ticks, until a voxel without support decays.
+pub fn delay(rng: &mut impl Rng) -> u64 {
+    rng.gen_range(DECAY_DELAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chunk::ChunkKind,
+        voxel::{KindDescription, MeshShape},
+    };
+
+    const LOG: u16 = 1;
+    const LEAVES: u16 = 2;
+    const STONE: u16 = 3;
+
+    fn description(id: u16, tick: Option<TickBehavior>) -> KindDescription {
+        KindDescription {
+            name: id.to_string(),
+            id,
+            color: (1.0, 1.0, 1.0, 1.0),
+            shape: MeshShape::Cube,
+            prop: None,
+            directional: false,
+            light: 0,
+            tick,
+            signal: None,
+            toggle: None,
+            hardness: 0.0,
+            tool_tier: 0,
+            block_entity: None,
+            explosive: None,
+            liquid: None,
+            climbable: false,
+            surface: None,
+            foliage: false,
+            tint: None,
+            portal: None,
+        }
+    }
+
+    fn registry() -> KindRegistry {
+        KindRegistry::new(vec![
+            description(LOG, None),
+            description(
+                LEAVES,
+                Some(TickBehavior::Decay {
+                    support: LOG,
+                    range: 3,
+                }),
+            ),
+            description(STONE, None),
+        ])
+    }
+
+    fn world(voxels: &[((i32, i32, i32), u16)]) -> VoxWorld {
+        let world = VoxWorld::default();
+        let mut kind = ChunkKind::default();
+
+        for (voxel, id) in voxels {
+            kind.set((*voxel).into(), (*id).into());
+        }
+
+        world.add(IVec3::ZERO, kind);
+        world
+    }
+
+    #[test]
+    fn is_supported() {
+        let world = world(&[
+            ((5, 5, 5), LOG),
+            ((6, 5, 5), LEAVES),
+            ((7, 5, 5), LEAVES),
+            ((8, 5, 5), LEAVES),
+            ((9, 5, 5), LEAVES),
+            ((5, 8, 5), STONE),
+            ((5, 9, 5), LEAVES),
+        ]);
+        let supported =
+            |x, y| super::is_supported(&world, (x, y, 5).into(), LEAVES.into(), LOG.into(), 3);
+
+        assert!(supported(6, 5));
+        assert!(supported(8, 5));
+
+        // Too far away from the log
+        assert!(!supported(9, 5));
+
+        // Other kinds don't carry the support
+        assert!(!supported(5, 9));
+    }
+
+    #[test]
+    fn is_supported_unloaded() {
+        let world = world(&[((0, 5, 5), LEAVES)]);
+
+        // The neighbor chunk isn't loaded, so there may be a log there
+        assert!(super::is_supported(
+            &world,
+            (0, 5, 5).into(),
+            LEAVES.into(),
+            LOG.into(),
+            3
+        ));
+    }
+
+    #[test]
+    fn should_decay() {
+        let registry = registry();
+        let world = world(&[((5, 5, 5), LOG), ((6, 5, 5), LEAVES), ((9, 9, 9), LEAVES)]);
+
+        assert!(!super::should_decay(&world, &registry, (6, 5, 5).into()));
+        assert!(super::should_decay(&world, &registry, (9, 9, 9).into()));
+
+        // Kinds without a decay behavior never decay
+        assert!(!super::should_decay(&world, &registry, (5, 5, 5).into()));
+    }
+
+    #[test]
+    fn around() {
+        let registry = registry();
+        let world = world(&[
+            ((5, 5, 5), LEAVES),
+            ((8, 5, 5), LEAVES),
+            ((9, 5, 5), LEAVES),
+            ((5, 6, 5), STONE),
+        ]);
+
+        let mut around = super::around(&world, &registry, (5, 5, 5).into(), LOG.into());
+        around.sort_by_key(|voxel| voxel.x);
+        assert_eq!(around, vec![(5, 5, 5).into(), (8, 5, 5).into()]);
+
+        // Nothing decays without stone
+        assert!(super::around(&world, &registry, (5, 5, 5).into(), STONE.into()).is_empty());
+    }
+}
@@ -0,0 +1,158 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chunk,
+    generator::{GeneratorKind, WorldGenerator},
+    pipeline::TERRAIN_SEED,
+};
+
+/// Name of the dimension worlds start on, which is generated by [`crate::meta::WorldMeta::generator`].
+pub const OVERWORLD: &str = "overworld";
+
+/**
+  Chunks along the X axis between the centers of two neighbor dimensions. All dimensions share the same
+  world, each one on its own range of it, so chunks, and everything saved along them, never mix.
+*/
+pub const STRIDE: i32 = 1 << 16;
+
+/// A world of its own, reached through portals, with its own terrain and chunk cache.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Dimension {
+    /// Unique name, also used as the directory its chunks are cached in.
+    pub name: String,
+    pub generator: WorldGenerator,
+}
+
+/**
+  Dimensions of a world, other than the overworld. Their index, used to place them on the world, is their
+  position here plus one, since the overworld is always the first one.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Dimensions(pub Vec<Dimension>);
+
+impl Default for Dimensions {
+    fn default() -> Self {
+        Self(vec![Dimension {
+            name: "highlands".to_string(),
+            generator: WorldGenerator {
+                kind: GeneratorKind::Noise,
+                seed: TERRAIN_SEED + 1,
+                height: 4 * chunk::AXIS_SIZE as u32,
+            },
+        }])
+    }
+}
+
+impl Dimensions {
+    /// Index of the dimension with the given name, if there is one.
+    pub fn index(&self, name: &str) -> Option<usize> {
+        if name == OVERWORLD {
+            return Some(0);
+        }
+
+        self.0
+            .iter()
+            .position(|dimension| dimension.name == name)
+            .map(|idx| idx + 1)
+    }
+
+    /// Name of the dimension at the given index, if there is one.
+    pub fn name(&self, index: usize) -> Option<&str> {
+        match index {
+            0 => Some(OVERWORLD),
+            _ => self
+                .0
+                .get(index - 1)
+                .map(|dimension| dimension.name.as_str()),
+        }
+    }
+
+    /// Name and generator of each dimension, by index, starting with the overworld.
+    pub fn all(&self, overworld: &WorldGenerator) -> Vec<(String, WorldGenerator)> {
+        std::iter::once((OVERWORLD.to_string(), overworld.clone()))
+            .chain(
+                self.0
+                    .iter()
+                    .map(|dimension| (dimension.name.clone(), dimension.generator.clone())),
+            )
+            .collect()
+    }
+}
+
+/**
+  Index of the dimension the given chunk is in. Chunks too far from any dimension get indices of dimensions
+  which doesn't exist, even negative ones.
+*/
+pub fn index_of(local: IVec3) -> i32 {
+    (local.x + STRIDE / 2).div_euclid(STRIDE)
+}
+
+/// Chunk as seen from inside its dimension, as if it was the only one in the world.
+pub fn to_relative(local: IVec3) -> IVec3 {
+    local - IVec3::X * index_of(local) * STRIDE
+}
+
+/// World chunk of the given chunk of a dimension. This is the opposite of [`to_relative`].
+pub fn to_absolute(index: usize, relative: IVec3) -> IVec3 {
+    relative + IVec3::X * index as i32 * STRIDE
+}
+
+/// World voxel at the same place as the given one, but on the dimension of the given index.
+pub fn move_voxel(voxel: IVec3, index: usize) -> IVec3 {
+    let (local, _) = chunk::split_voxel(voxel);
+    let offset = (index as i32 - index_of(local)) * STRIDE * chunk::AXIS_SIZE as i32;
+
+    voxel + IVec3::X * offset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative() {
+        let local = IVec3::new(-3, 1, 12);
+
+        assert_eq!(index_of(local), 0);
+        assert_eq!(to_relative(local), local);
+
+        let absolute = to_absolute(2, local);
+        assert_eq!(index_of(absolute), 2);
+        assert_eq!(to_relative(absolute), local);
+
+        // Dimensions span half the stride to each side of their center
+        assert_eq!(index_of(IVec3::X * (STRIDE / 2 - 1)), 0);
+        assert_eq!(index_of(IVec3::X * STRIDE / 2), 1);
+        assert_eq!(index_of(IVec3::X * -STRIDE), -1);
+    }
+
+    #[test]
+    fn move_voxel() {
+        let voxel = IVec3::new(-20, 5, 7);
+
+        let moved = super::move_voxel(voxel, 1);
+        assert_eq!(index_of(chunk::split_voxel(moved).0), 1);
+        assert_eq!(super::move_voxel(moved, 0), voxel);
+        assert_eq!(super::move_voxel(voxel, 0), voxel);
+    }
+
+    #[test]
+    fn dimensions() {
+        let dimensions = Dimensions::default();
+        let overworld = WorldGenerator::default();
+
+        assert_eq!(dimensions.index(OVERWORLD), Some(0));
+        assert_eq!(dimensions.index("highlands"), Some(1));
+        assert_eq!(dimensions.index("nowhere"), None);
+
+        assert_eq!(dimensions.name(0), Some(OVERWORLD));
+        assert_eq!(dimensions.name(1), Some("highlands"));
+        assert_eq!(dimensions.name(2), None);
+
+        let all = dimensions.all(&overworld);
+        assert_eq!(all[0], (OVERWORLD.to_string(), overworld));
+        assert_eq!(all[1].0, "highlands");
+    }
+}
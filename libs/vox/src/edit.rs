@@ -0,0 +1,414 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::{chunk, pipeline::SetVoxel, query, voxel::Kind, world::VoxWorld};
+
+const STRUCTURES_PATH: &str = "structures";
+const STRUCTURES_EXT: &str = "ron";
+
+/**
+  Box of world voxels, including both corners.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Region {
+    pub min: IVec3,
+    pub max: IVec3,
+}
+
+impl Region {
+    /// Region between two opposite corners, in any order.
+    pub fn from_corners(a: IVec3, b: IVec3) -> Self {
+        Self {
+            min: a.min(b),
+            max: a.max(b),
+        }
+    }
+
+    /// Number of voxels on each axis.
+    pub fn size(&self) -> IVec3 {
+        self.max - self.min + IVec3::ONE
+    }
+
+    pub fn contains(&self, position: IVec3) -> bool {
+        position.cmpge(self.min).all() && position.cmple(self.max).all()
+    }
+
+    pub fn intersects(&self, other: &Region) -> bool {
+        self.min.cmple(other.max).all() && other.min.cmple(self.max).all()
+    }
+
+    /// Whether the given position is on any face of the region.
+    pub fn is_border(&self, position: IVec3) -> bool {
+        self.contains(position)
+            && (position.cmpeq(self.min).any() || position.cmpeq(self.max).any())
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = IVec3> {
+        query::range_inclusive(self.min, self.max)
+    }
+}
+
+fn set(position: IVec3, kind: Kind) -> SetVoxel {
+    let (chunk, voxel) = chunk::split_voxel(position);
+    SetVoxel { chunk, voxel, kind }
+}
+
+/**
+  Returns the changes needed to set the given voxels. Voxels which already have the kind, or which are on
+  chunks not loaded, are skipped.
+*/
+pub fn set_all(world: &VoxWorld, voxels: impl Iterator<Item = (IVec3, Kind)>) -> Vec<SetVoxel> {
+    voxels
+        .filter(|(position, kind)| matches!(world.get_voxel(*position), Some(current) if current != *kind))
+        .map(|(position, kind)| set(position, kind))
+        .collect()
+}
+
+/// Sets all voxels of the region to the given kind.
+pub fn fill(world: &VoxWorld, region: &Region, kind: Kind) -> Vec<SetVoxel> {
+    set_all(world, region.positions().map(|position| (position, kind)))
+}
+
+/// Sets the faces of the region to the given kind, and empties everything inside.
+pub fn hollow(world: &VoxWorld, region: &Region, kind: Kind) -> Vec<SetVoxel> {
+    set_all(
+        world,
+        region.positions().map(|position| {
+            if region.is_border(position) {
+                (position, kind)
+            } else {
+                (position, Kind::default())
+            }
+        }),
+    )
+}
+
+/// Replaces all voxels of the region with the kind `from` by the kind `to`.
+pub fn replace(world: &VoxWorld, region: &Region, from: Kind, to: Kind) -> Vec<SetVoxel> {
+    set_all(
+        world,
+        region
+            .positions()
+            .filter(|position| world.get_voxel(*position) == Some(from))
+            .map(|position| (position, to)),
+    )
+}
+
+/**
+  Shape of a brush applied around a center voxel.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrushShape {
+    Sphere,
+    /// Vertical cylinder, as tall as it's wide.
+    Cylinder,
+    /// Smooths the terrain inside a sphere, ignoring the brush kind.
+    Smooth,
+}
+
+impl BrushShape {
+    /// Whether the given offset from the brush center is inside a brush with the given radius.
+    fn contains(&self, offset: IVec3, radius: i32) -> bool {
+        match self {
+            BrushShape::Sphere | BrushShape::Smooth => offset.dot(offset) <= radius * radius,
+            BrushShape::Cylinder => {
+                offset.x * offset.x + offset.z * offset.z <= radius * radius
+                    && offset.y.abs() <= radius
+            }
+        }
+    }
+}
+
+/**
+  Applies the brush around the given center. Filling with the empty kind erases voxels instead.
+*/
+pub fn brush(
+    world: &VoxWorld,
+    shape: BrushShape,
+    center: IVec3,
+    radius: i32,
+    kind: Kind,
+) -> Vec<SetVoxel> {
+    let region = Region {
+        min: center - IVec3::splat(radius),
+        max: center + IVec3::splat(radius),
+    };
+
+    let positions = region
+        .positions()
+        .filter(|position| shape.contains(*position - center, radius));
+
+    if shape == BrushShape::Smooth {
+        set_all(
+            world,
+            positions.map(|position| (position, smoothed(world, position))),
+        )
+    } else {
+        set_all(world, positions.map(|position| (position, kind)))
+    }
+}
+
+/**
+  Kind of the voxel after smoothing. A voxel becomes solid when most of its 26 neighbors are solid,
+  using their most common kind, and empty otherwise.
+*/
+fn smoothed(world: &VoxWorld, position: IVec3) -> Kind {
+    let mut kinds = Vec::with_capacity(27);
+
+    for neighbor in query::range_inclusive(position - IVec3::ONE, position + IVec3::ONE) {
+        if neighbor == position {
+            continue;
+        }
+
+        match world.get_voxel(neighbor) {
+            Some(kind) if !kind.is_empty() => kinds.push(kind),
+            Some(_) => (),
+            // Unknown neighbors keeps the voxel as it is
+            None => return world.get_voxel(position).unwrap_or_default(),
+        }
+    }
+
+    if kinds.len() < 14 {
+        return Kind::default();
+    }
+
+    kinds.sort_by_key(|kind| kind.id());
+
+    let mut best = (kinds[0], 0);
+    let mut current = (kinds[0], 0);
+
+    for kind in kinds {
+        if kind == current.0 {
+            current.1 += 1;
+        } else {
+            current = (kind, 1);
+        }
+
+        if current.1 > best.1 {
+            best = current;
+        }
+    }
+
+    best.0
+}
+
+/**
+  A copy of the voxels of a region, which can be pasted elsewhere or saved as a template.
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Structure {
+    pub size: IVec3,
+    /// Kinds ordered by x, then y, then z.
+    pub kinds: Vec<Kind>,
+}
+
+impl Structure {
+    /// Copies the voxels of the region. Voxels of chunks not loaded are copied as empty.
+    pub fn copy(world: &VoxWorld, region: &Region) -> Self {
+        Self {
+            size: region.size(),
+            kinds: region
+                .positions()
+                .map(|position| world.get_voxel(position).unwrap_or_default())
+                .collect(),
+        }
+    }
+
+    /// Returns the changes needed to paste this structure with its min corner at the given position.
+    pub fn paste(&self, world: &VoxWorld, position: IVec3) -> Vec<SetVoxel> {
+        let region = Region {
+            min: position,
+            max: position + self.size - IVec3::ONE,
+        };
+
+        set_all(world, region.positions().zip(self.kinds.iter().copied()))
+    }
+
+    pub fn load(path: &Path) -> Self {
+        let file = std::fs::File::open(path)
+            .unwrap_or_else(|_| panic!("Unable to open file {}", path.display()));
+
+        ron::de::from_reader(file)
+            .unwrap_or_else(|_| panic!("Failed to parse file {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .unwrap_or_else(|_| panic!("Unable to create dir {}", dir.display()));
+        }
+
+        let file = std::fs::File::create(path)
+            .unwrap_or_else(|_| panic!("Unable to write to file {}", path.display()));
+
+        ron::ser::to_writer(file, self)
+            .unwrap_or_else(|_| panic!("Failed to serialize to file {}", path.display()));
+    }
+}
+
+/// Path where the structure template with the given name is saved.
+pub fn structure_path(name: &str) -> PathBuf {
+    Path::new(STRUCTURES_PATH)
+        .join(name)
+        .with_extension(STRUCTURES_EXT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::ChunkKind;
+
+    fn apply(world: &VoxWorld, edits: Vec<SetVoxel>) {
+        for SetVoxel { chunk, voxel, kind } in edits {
+            world.get_mut(chunk).unwrap().set(voxel, kind);
+        }
+    }
+
+    fn world() -> VoxWorld {
+        let world = VoxWorld::default();
+
+        for local in query::range_inclusive((-1, 0, -1).into(), (0, 0, 0).into()) {
+            world.add(local, ChunkKind::default());
+        }
+
+        world
+    }
+
+    #[test]
+    fn region() {
+        let region = Region::from_corners((2, 5, -1).into(), (-1, 3, 1).into());
+
+        assert_eq!(region.min, (-1, 3, -1).into());
+        assert_eq!(region.max, (2, 5, 1).into());
+        assert_eq!(region.size(), (4, 3, 3).into());
+        assert_eq!(region.positions().count(), 36);
+
+        assert!(region.is_border((-1, 4, 0).into()));
+        assert!(!region.is_border((0, 4, 0).into()));
+        assert!(!region.is_border((-2, 4, 0).into()));
+
+        assert!(region.intersects(&Region::from_corners((2, 5, 1).into(), (3, 6, 2).into())));
+        assert!(!region.intersects(&Region::from_corners((3, 5, 1).into(), (3, 6, 2).into())));
+    }
+
+    #[test]
+    fn fill_and_hollow() {
+        let world = world();
+        let region = Region::from_corners((-2, 1, -2).into(), (2, 5, 2).into());
+
+        let edits = fill(&world, &region, 1.into());
+        assert_eq!(edits.len(), 125);
+        apply(&world, edits);
+
+        // Nothing changes when filling again
+        assert!(fill(&world, &region, 1.into()).is_empty());
+
+        let edits = hollow(&world, &region, 2.into());
+        assert_eq!(edits.len(), 125);
+        apply(&world, edits);
+
+        assert_eq!(world.get_voxel((0, 3, 0).into()), Some(0.into()));
+        assert_eq!(world.get_voxel((-2, 3, 0).into()), Some(2.into()));
+    }
+
+    #[test]
+    fn fill_unloaded() {
+        let world = world();
+        let region = Region::from_corners((14, 0, 0).into(), (17, 0, 0).into());
+
+        assert_eq!(fill(&world, &region, 1.into()).len(), 2);
+    }
+
+    #[test]
+    fn replace() {
+        let world = world();
+        let region = Region::from_corners((0, 0, 0).into(), (3, 0, 0).into());
+
+        apply(&world, vec![set((1, 0, 0).into(), 1.into())]);
+        apply(&world, vec![set((2, 0, 0).into(), 2.into())]);
+
+        let edits = super::replace(&world, &region, 1.into(), 3.into());
+        assert_eq!(edits.len(), 1);
+        apply(&world, edits);
+
+        assert_eq!(world.get_voxel((1, 0, 0).into()), Some(3.into()));
+        assert_eq!(world.get_voxel((2, 0, 0).into()), Some(2.into()));
+    }
+
+    #[test]
+    fn copy_paste() {
+        let world = world();
+
+        apply(&world, vec![set((1, 1, 1).into(), 1.into())]);
+        apply(&world, vec![set((2, 1, 1).into(), 2.into())]);
+
+        let structure = Structure::copy(
+            &world,
+            &Region::from_corners((1, 1, 1).into(), (2, 2, 1).into()),
+        );
+        assert_eq!(structure.size, (2, 2, 1).into());
+
+        // Pasting across chunks
+        let edits = structure.paste(&world, (-1, 5, -1).into());
+        assert_eq!(edits.len(), 2);
+        apply(&world, edits);
+
+        assert_eq!(world.get_voxel((-1, 5, -1).into()), Some(1.into()));
+        assert_eq!(world.get_voxel((0, 5, -1).into()), Some(2.into()));
+        assert_eq!(world.get_voxel((0, 6, -1).into()), Some(0.into()));
+    }
+
+    #[test]
+    fn brush_shapes() {
+        let world = world();
+        let center = IVec3::new(0, 8, 0);
+
+        let sphere = brush(&world, BrushShape::Sphere, center, 2, 1.into());
+        assert_eq!(sphere.len(), 33);
+
+        let cylinder = brush(&world, BrushShape::Cylinder, center, 2, 1.into());
+        assert_eq!(cylinder.len(), 13 * 5);
+
+        // Erasing where there is nothing does nothing
+        assert!(brush(&world, BrushShape::Sphere, center, 2, 0.into()).is_empty());
+    }
+
+    #[test]
+    fn smooth() {
+        let world = world();
+
+        // A flat floor with a single voxel spike and a single voxel hole
+        apply(
+            &world,
+            fill(
+                &world,
+                &Region::from_corners((-8, 0, -8).into(), (7, 4, 7).into()),
+                1.into(),
+            ),
+        );
+        apply(&world, vec![set((0, 5, 0).into(), 2.into())]);
+        apply(&world, vec![set((3, 4, 3).into(), 0.into())]);
+
+        let edits = brush(&world, BrushShape::Smooth, (1, 4, 1).into(), 3, 0.into());
+        apply(&world, edits);
+
+        assert_eq!(world.get_voxel((0, 5, 0).into()), Some(0.into()));
+        assert_eq!(world.get_voxel((3, 4, 3).into()), Some(1.into()));
+        assert_eq!(world.get_voxel((1, 4, 1).into()), Some(1.into()));
+    }
+
+    #[test]
+    fn save_load() {
+        let path = std::env::temp_dir().join("eterno_structure_save_load.ron");
+
+        let structure = Structure {
+            size: (1, 2, 1).into(),
+            kinds: vec![1.into(), 3.into()],
+        };
+        structure.save(&path);
+
+        assert_eq!(Structure::load(&path), structure);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,216 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    query,
+    voxel::{Explosive, Kind, KindRegistry},
+    world::VoxWorld,
+};
+
+/// Speed, in voxels per second, things are pushed away by each unit of power left where they are.
+const KNOCKBACK_PER_POWER: f32 = 4.0;
+
+/**
+  A blast centered on a world position. Its power fades linearly from the center to the radius, and voxels
+  are destroyed where the power left is higher than their hardness.
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Explosion {
+    /// World coordinates, in voxels.
+    pub center: Vec3,
+    pub radius: f32,
+    pub power: f32,
+}
+
+impl Explosion {
+    pub fn new(center: Vec3, radius: f32, power: f32) -> Self {
+        Self {
+            center,
+            radius,
+            power,
+        }
+    }
+
+    /// Power left at the given distance from the center.
+    pub fn power_at(&self, distance: f32) -> f32 {
+        if distance >= self.radius {
+            0.0
+        } else {
+            self.power * (1.0 - distance / self.radius)
+        }
+    }
+
+    /**
+      Velocity things at the given world position are pushed with, away from the center. Things right on the
+      center are thrown up.
+    */
+    pub fn knockback(&self, position: Vec3) -> Vec3 {
+        let offset = position - self.center;
+        let dir = offset.try_normalize().unwrap_or(Vec3::Y);
+
+        dir * self.power_at(offset.length()) * KNOCKBACK_PER_POWER
+    }
+
+    /// World voxels which may be reached by the explosion.
+    fn positions(&self) -> impl Iterator<Item = IVec3> {
+        let min = (self.center - Vec3::splat(self.radius)).floor().as_ivec3();
+        let max = (self.center + Vec3::splat(self.radius)).floor().as_ivec3();
+
+        query::range_inclusive(min, max)
+    }
+}
+
+impl Explosive {
+    /// Explosion of this kind when it blows up at the given world voxel.
+    pub fn explosion(&self, position: IVec3) -> Explosion {
+        Explosion::new(
+            position.as_vec3() + Vec3::splat(0.5),
+            self.radius,
+            self.power,
+        )
+    }
+
+    /**
+      Ticks until this kind blows up when caught in another explosion. It's a random part of the fuse, so
+      chain reactions ripple out quickly, but not all at once.
+    */
+    pub fn chain_fuse(&self, rng: &mut impl Rng) -> u64 {
+        rng.gen_range(self.fuse / 4..=self.fuse / 2).max(1)
+    }
+}
+
+/**
+  Returns the voxels destroyed by the explosion, with the kind each had. Empty voxels, and the ones on chunks
+  not loaded, are left alone. Nothing is changed, so the caller can apply the result as a single batch of
+  voxel changes.
+*/
+pub fn explode(
+    world: &VoxWorld,
+    registry: &KindRegistry,
+    explosion: &Explosion,
+) -> Vec<(IVec3, Kind)> {
+    explosion
+        .positions()
+        .filter_map(|position| {
+            let kind = world.get_voxel(position).filter(|kind| !kind.is_empty())?;
+
+            // Distance to the voxel center, so the voxel on the center gets the full power
+            let distance = (position.as_vec3() + Vec3::splat(0.5)).distance(explosion.center);
+            let hardness = registry
+                .get(kind)
+                .map(|desc| desc.hardness)
+                .unwrap_or_default();
+
+            if explosion.power_at(distance) > hardness {
+                Some((position, kind))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chunk::ChunkKind,
+        voxel::{KindDescription, MeshShape},
+    };
+
+    fn description(id: u16, hardness: f32) -> KindDescription {
+        KindDescription {
+            name: id.to_string(),
+            id,
+            color: (1.0, 1.0, 1.0, 1.0),
+            shape: MeshShape::Cube,
+            prop: None,
+            directional: false,
+            light: 0,
+            tick: None,
+            signal: None,
+            toggle: None,
+            hardness,
+            tool_tier: 0,
+            block_entity: None,
+            explosive: None,
+            liquid: None,
+            climbable: false,
+            surface: None,
+            foliage: false,
+            tint: None,
+            portal: None,
+        }
+    }
+
+    #[test]
+    fn power_at() {
+        let explosion = Explosion::new(Vec3::ZERO, 4.0, 2.0);
+
+        assert_eq!(explosion.power_at(0.0), 2.0);
+        assert_eq!(explosion.power_at(2.0), 1.0);
+        assert_eq!(explosion.power_at(4.0), 0.0);
+        assert_eq!(explosion.power_at(10.0), 0.0);
+    }
+
+    #[test]
+    fn knockback() {
+        let explosion = Explosion::new(Vec3::ZERO, 4.0, 2.0);
+
+        let push = explosion.knockback(Vec3::X * 2.0);
+        assert_eq!(push, Vec3::X * KNOCKBACK_PER_POWER);
+
+        // Closer things are pushed harder
+        assert!(explosion.knockback(Vec3::X).length() > push.length());
+        assert_eq!(explosion.knockback(Vec3::X * 5.0), Vec3::ZERO);
+
+        assert!(explosion.knockback(Vec3::ZERO).y > 0.0);
+    }
+
+    #[test]
+    fn chain_fuse() {
+        let explosive = Explosive {
+            fuse: 80,
+            radius: 4.0,
+            power: 4.0,
+        };
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..100 {
+            let fuse = explosive.chain_fuse(&mut rng);
+            assert!((20..=40).contains(&fuse));
+        }
+
+        // Even the shortest fuses wait for the next tick
+        let instant = Explosive {
+            fuse: 0,
+            ..explosive
+        };
+        assert_eq!(instant.chain_fuse(&mut rng), 1);
+    }
+
+    #[test]
+    fn explode() {
+        let world = VoxWorld::default();
+        world.add(IVec3::ZERO, ChunkKind::default());
+
+        {
+            let mut chunk = world.get_mut(IVec3::ZERO).unwrap();
+            for x in 0..8 {
+                chunk.set((x, 0, 0).into(), 1.into());
+            }
+            chunk.set((1, 1, 0).into(), 2.into());
+        }
+
+        let registry = KindRegistry::new(vec![description(1, 0.5), description(2, 10.0)]);
+
+        let explosion = Explosion::new(Vec3::new(0.5, 0.5, 0.5), 4.0, 1.0);
+        let destroyed = super::explode(&world, &registry, &explosion);
+
+        // Power fades below the hardness halfway to the radius, and hard voxels withstand it
+        assert_eq!(
+            destroyed,
+            vec![(IVec3::ZERO, 1.into()), ((1, 0, 0).into(), 1.into())]
+        );
+    }
+}
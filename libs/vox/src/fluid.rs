@@ -0,0 +1,202 @@
+use bevy::prelude::*;
+
+use crate::{
+    voxel::{KindRegistry, Liquid, Surface},
+    world::VoxWorld,
+};
+
+/// Seconds of air a full [`AirMeter`] lasts inside liquids.
+pub const MAX_AIR: f32 = 10.0;
+/// How many times faster air comes back than it runs out.
+const AIR_REFILL_RATE: f32 = 4.0;
+/// Damage taken for each second spent without air.
+pub const DROWNING_DAMAGE: f32 = 1.0;
+
+/// Liquids are swum through like a sticky surface, which is also how their bottom is walked on.
+pub const SWIM_SURFACE: Surface = Surface {
+    friction: 0.5,
+    speed: 0.5,
+};
+
+/**
+  Air left to something which breathes. It runs out while its eyes are inside a liquid, and once it's gone
+  it starts drowning.
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AirMeter {
+    /// Seconds of air left, up to [`MAX_AIR`].
+    pub air: f32,
+}
+
+impl Default for AirMeter {
+    fn default() -> Self {
+        Self { air: MAX_AIR }
+    }
+}
+
+impl AirMeter {
+    /// Breathes for `dt` seconds, returning the drowning damage taken meanwhile.
+    pub fn breathe(&mut self, submerged: bool, dt: f32) -> f32 {
+        if !submerged {
+            self.air = (self.air + dt * AIR_REFILL_RATE).min(MAX_AIR);
+            return 0.0;
+        }
+
+        let left = self.air - dt;
+        self.air = left.max(0.0);
+
+        // Only the time spent after air ran out hurts
+        (-left).clamp(0.0, dt) * DROWNING_DAMAGE
+    }
+
+    pub fn is_drowning(&self) -> bool {
+        self.air <= 0.0
+    }
+}
+
+/**
+  How deep something is inside a liquid, given its bottom in world coordinates and its height. Returns the
+  part of its height inside liquid voxels, from zero to one, along with the first liquid found from the
+  bottom up. Unloaded voxels count as dry.
+*/
+pub fn submersion(
+    world: &VoxWorld,
+    registry: &KindRegistry,
+    bottom: Vec3,
+    height: f32,
+) -> Option<(f32, Liquid)> {
+    let top = bottom.y + height;
+    let (x, z) = (bottom.x.floor() as i32, bottom.z.floor() as i32);
+
+    let mut found = None;
+    let mut depth = 0.0;
+
+    for y in bottom.y.floor() as i32..=top.floor() as i32 {
+        let liquid = world
+            .get_voxel(IVec3::new(x, y, z))
+            .and_then(|kind| registry.liquid(kind));
+
+        if let Some(liquid) = liquid {
+            depth += (top.min(y as f32 + 1.0) - bottom.y.max(y as f32)).max(0.0);
+            found.get_or_insert(liquid);
+        }
+    }
+
+    found.map(|liquid| ((depth / height.max(f32::EPSILON)).min(1.0), liquid))
+}
+
+/**
+  Applies a liquid to the velocity of something `submersion` deep inside it, over `dt` seconds: it's pushed
+  up against `gravity`, which the caller still applies as usual, and slowed down by drag.
+*/
+pub fn float(velocity: Vec3, submersion: f32, liquid: Liquid, gravity: f32, dt: f32) -> Vec3 {
+    let velocity = velocity + Vec3::Y * gravity * liquid.buoyancy * submersion * dt;
+
+    velocity * (1.0 - liquid.drag * submersion * dt).max(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chunk::ChunkKind,
+        voxel::{KindDescription, MeshShape},
+    };
+
+    const WATER: Liquid = Liquid {
+        buoyancy: 1.5,
+        drag: 2.0,
+    };
+
+    fn description(id: u16, liquid: Option<Liquid>) -> KindDescription {
+        KindDescription {
+            name: id.to_string(),
+            id,
+            color: (1.0, 1.0, 1.0, 1.0),
+            shape: MeshShape::Cube,
+            prop: None,
+            directional: false,
+            light: 0,
+            tick: None,
+            signal: None,
+            toggle: None,
+            hardness: 0.0,
+            tool_tier: 0,
+            block_entity: None,
+            explosive: None,
+            liquid,
+            climbable: false,
+            surface: None,
+            foliage: false,
+            tint: None,
+            portal: None,
+        }
+    }
+
+    #[test]
+    fn breathe() {
+        let mut meter = AirMeter::default();
+
+        assert_eq!(meter.breathe(true, MAX_AIR - 1.0), 0.0);
+        assert!(!meter.is_drowning());
+
+        // Only the second after air ran out hurts
+        assert_eq!(meter.breathe(true, 2.0), DROWNING_DAMAGE);
+        assert!(meter.is_drowning());
+        assert_eq!(meter.breathe(true, 1.0), DROWNING_DAMAGE);
+
+        assert_eq!(meter.breathe(false, 1.0), 0.0);
+        assert_eq!(meter.air, AIR_REFILL_RATE);
+        assert!(!meter.is_drowning());
+
+        meter.breathe(false, MAX_AIR);
+        assert_eq!(meter.air, MAX_AIR);
+    }
+
+    #[test]
+    fn submersion() {
+        let world = VoxWorld::default();
+        world.add(IVec3::ZERO, ChunkKind::default());
+
+        {
+            let mut chunk = world.get_mut(IVec3::ZERO).unwrap();
+            chunk.set((0, 0, 0).into(), 1.into());
+            chunk.set((0, 1, 0).into(), 2.into());
+            chunk.set((0, 2, 0).into(), 2.into());
+        }
+
+        let registry = KindRegistry::new(vec![description(1, None), description(2, Some(WATER))]);
+
+        // Half of the body is in the water, which is two voxels deep above the solid one
+        assert_eq!(
+            super::submersion(&world, &registry, Vec3::new(0.5, 2.0, 0.5), 2.0),
+            Some((0.5, WATER))
+        );
+        assert_eq!(
+            super::submersion(&world, &registry, Vec3::new(0.5, 1.5, 0.5), 1.0),
+            Some((1.0, WATER))
+        );
+        assert_eq!(
+            super::submersion(&world, &registry, Vec3::new(0.5, 3.0, 0.5), 1.0),
+            None
+        );
+        assert_eq!(
+            super::submersion(&world, &registry, Vec3::new(1.5, 1.5, 0.5), 1.0),
+            None
+        );
+    }
+
+    #[test]
+    fn float() {
+        // Things outside liquids aren't affected
+        let velocity = Vec3::new(1.0, -2.0, 0.0);
+        assert_eq!(super::float(velocity, 0.0, WATER, 20.0, 0.1), velocity);
+
+        // Buoyancy above one beats gravity, and drag slows things down
+        let velocity = super::float(Vec3::ZERO, 1.0, WATER, 20.0, 0.1) - Vec3::Y * 20.0 * 0.1;
+        assert!(velocity.y > 0.0);
+
+        let velocity = super::float(Vec3::X * 10.0, 1.0, WATER, 20.0, 0.1);
+        assert!(velocity.x < 10.0 && velocity.x > 0.0);
+    }
+}
@@ -0,0 +1,291 @@
+use bevy::prelude::*;
+use rand::{rngs::StdRng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::{
+    chunk,
+    hydrology::Hydrology,
+    noise::{Fractal, NoiseBackend, NoiseSource},
+    pipeline::TERRAIN_SEED,
+    voxel::{Kind, KindRegistry},
+};
+
+/// How the terrain of new chunks is shaped.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GeneratorKind {
+    /// Rolling hills made of noise.
+    Noise,
+    /// Flat ground, all at the same height.
+    Flat,
+    /// Heights read from the grayscale image at the given path, one pixel per column, from the world origin.
+    Heightmap(String),
+}
+
+/**
+  How the terrain of a world is generated, saved on the world metadata. It must be chosen when the world is
+  created, since chunks are only generated once and cached.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorldGenerator {
+    pub kind: GeneratorKind,
+    pub seed: u64,
+    /// Highest the terrain goes, in voxels.
+    pub height: u32,
+}
+
+impl Default for WorldGenerator {
+    fn default() -> Self {
+        Self {
+            kind: GeneratorKind::Noise,
+            seed: TERRAIN_SEED,
+            height: 2 * chunk::AXIS_SIZE as u32,
+        }
+    }
+}
+
+/**
+  Kinds terrain is made of. They're looked up by name on the [`KindRegistry`] the terrain is generated with,
+  so terrain follows whatever ids the registry gives them.
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TerrainKinds {
+    pub grass: Kind,
+    pub dirt: Kind,
+    pub water: Kind,
+}
+
+impl TerrainKinds {
+    /// Looks the kinds up on the given registry. Fails when any of them isn't described.
+    pub fn new(registry: &KindRegistry) -> Result<Self, String> {
+        let find = |name| {
+            registry
+                .find(name)
+                .ok_or_else(|| format!("Kind {} is needed to generate terrain", name))
+        };
+
+        Ok(Self {
+            grass: find("Grass")?,
+            dirt: find("Dirt")?,
+            water: find("Water")?,
+        })
+    }
+}
+
+/**
+  Random number generator of the given generation stage, like `"ores"`, on the given chunk. Each chunk and
+  stage gets its own stream, derived only from the world seed, the chunk and the stage name, so what a stage
+  rolls doesn't depend on which chunks were generated before, nor on what other stages rolled.
+*/
+pub fn chunk_rng(seed: u64, local: IVec3, stage: &str) -> StdRng {
+    // FNV-1a, which unlike the std hasher is the same on every run and platform
+    let bytes = seed
+        .to_le_bytes()
+        .into_iter()
+        .chain(local.to_array().into_iter().flat_map(i32::to_le_bytes))
+        .chain(stage.bytes());
+
+    let hash = bytes.fold(0xcbf2_9ce4_8422_2325, |hash: u64, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100_0000_01b3)
+    });
+
+    StdRng::seed_from_u64(hash)
+}
+
+/// Pixels of a heightmap image, from 0 on the lowest to 255 on the highest.
+struct HeightmapImage {
+    width: u32,
+    depth: u32,
+    pixels: Vec<u8>,
+}
+
+/**
+  Terrain shaped by a [`WorldGenerator`], ready to generate chunks. Noise terrains have rivers and lakes
+  carved into them, while flat and heightmap ones are kept as they're described.
+*/
+pub struct Terrain {
+    generator: WorldGenerator,
+    image: Option<HeightmapImage>,
+    noise: Box<dyn NoiseSource>,
+    hydrology: Option<Hydrology>,
+}
+
+impl Terrain {
+    /// Prepares the terrain of the given generator. Heightmap images are read right away.
+    pub fn new(generator: &WorldGenerator) -> Self {
+        Self::with_backend(generator, NoiseBackend::default())
+    }
+
+    /// Same as [`Terrain::new`], but noise is sampled by the given backend.
+    pub fn with_backend(generator: &WorldGenerator, backend: NoiseBackend) -> Self {
+        let image = match &generator.kind {
+            GeneratorKind::Heightmap(path) => {
+                let image = image::open(Path::new(path))
+                    .unwrap_or_else(|_| panic!("Unable to read heightmap image {}", path))
+                    .into_luma8();
+
+                Some(HeightmapImage {
+                    width: image.width(),
+                    depth: image.height(),
+                    pixels: image.into_raw(),
+                })
+            }
+            _ => None,
+        };
+
+        let hydrology = match generator.kind {
+            GeneratorKind::Noise => Some(Hydrology::new(generator.seed, generator.height, backend)),
+            _ => None,
+        };
+
+        Self {
+            generator: generator.clone(),
+            image,
+            noise: backend.build(Fractal {
+                seed: generator.seed,
+                frequency: 0.03,
+                octaves: 3,
+                gain: 0.9,
+                lacunarity: 0.5,
+            }),
+            hydrology,
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.generator.seed
+    }
+
+    /// Height, in voxels, up to which columns lower than it are filled with water, if the terrain has any.
+    pub fn water_level(&self) -> Option<f32> {
+        self.hydrology.as_ref().map(Hydrology::water_level)
+    }
+
+    /**
+      Heights of the terrain surface, in voxels, of the `size` by `size` columns starting at the given world
+      column. Heights are listed by x, then z. Columns without any terrain have height zero.
+    */
+    pub fn heights(&self, x: i32, z: i32, size: usize) -> Vec<f32> {
+        let max = self.generator.height as f32;
+        let columns = (0..size).flat_map(|dx| (0..size).map(move |dz| (dx as i32, dz as i32)));
+
+        match (&self.generator.kind, &self.image) {
+            (GeneratorKind::Noise, _) => {
+                let mut heights = self.noise.fill(x as f32, z as f32, size);
+                for h in heights.iter_mut() {
+                    *h = ((*h + 1.0) / 2.0) * max;
+                }
+
+                if let Some(hydrology) = &self.hydrology {
+                    hydrology.carve_all(x, z, size, &mut heights);
+                }
+
+                heights
+            }
+            (GeneratorKind::Flat, _) => vec![max; size * size],
+            (GeneratorKind::Heightmap(_), Some(image)) => columns
+                .map(
+                    |(dx, dz)| match (u32::try_from(x + dx), u32::try_from(z + dz)) {
+                        (Ok(px), Ok(pz)) if px < image.width && pz < image.depth => {
+                            let pixel = image.pixels[(pz * image.width + px) as usize];
+                            pixel as f32 / u8::MAX as f32 * max
+                        }
+                        _ => 0.0,
+                    },
+                )
+                .collect(),
+            (GeneratorKind::Heightmap(_), None) => unreachable!("Heightmap images are read on new"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn noise() {
+        let generator = WorldGenerator::default();
+        let terrain = Terrain::new(&generator);
+
+        let heights = terrain.heights(-8, 20, 4);
+        assert_eq!(heights.len(), 16);
+        assert!(heights
+            .iter()
+            .all(|h| (0.0..=generator.height as f32).contains(h)));
+
+        // Same seed, same terrain, while other seeds make other terrains
+        assert_eq!(terrain.heights(-8, 20, 4), heights);
+        let other = Terrain::new(&WorldGenerator {
+            seed: generator.seed + 1,
+            ..generator
+        });
+        assert_ne!(other.heights(-8, 20, 4), heights);
+
+        // Rivers and lakes are carved below the water level
+        assert_eq!(terrain.water_level(), Some(12.0));
+    }
+
+    #[test]
+    fn terrain_kinds() {
+        let input_path = format!(
+            "{}assets/voxels/kind_descriptions.ron",
+            env!("CARGO_WORKSPACE_DIR")
+        );
+        let registry = KindRegistry::load(Path::new(&input_path));
+
+        let kinds = TerrainKinds::new(&registry).unwrap();
+        assert_eq!(registry.name(kinds.grass), "Grass");
+        assert_eq!(registry.name(kinds.dirt), "Dirt");
+        assert_eq!(registry.name(kinds.water), "Water");
+
+        assert!(TerrainKinds::new(&KindRegistry::default()).is_err());
+    }
+
+    #[test]
+    fn chunk_rng() {
+        let roll = |seed, local, stage| super::chunk_rng(seed, local, stage).gen::<u64>();
+        let local = IVec3::new(-3, 1, 12);
+
+        // Streams depend only on their seed, chunk and stage
+        assert_eq!(roll(1, local, "ores"), roll(1, local, "ores"));
+        assert_ne!(roll(1, local, "ores"), roll(2, local, "ores"));
+        assert_ne!(roll(1, local, "ores"), roll(1, local + IVec3::Y, "ores"));
+        assert_ne!(roll(1, local, "ores"), roll(1, local, "caves"));
+    }
+
+    #[test]
+    fn flat() {
+        let terrain = Terrain::new(&WorldGenerator {
+            kind: GeneratorKind::Flat,
+            height: 5,
+            ..Default::default()
+        });
+
+        assert_eq!(terrain.heights(100, -100, 2), vec![5.0; 4]);
+        assert_eq!(terrain.water_level(), None);
+    }
+
+    #[test]
+    fn heightmap() {
+        let path = std::env::temp_dir().join("eterno_heightmap.png");
+        image::GrayImage::from_raw(2, 1, vec![0, 255])
+            .unwrap()
+            .save(&path)
+            .unwrap();
+
+        let terrain = Terrain::new(&WorldGenerator {
+            kind: GeneratorKind::Heightmap(path.to_str().unwrap().to_string()),
+            height: 10,
+            ..Default::default()
+        });
+
+        // Listed by x then z, and outside the image there's no terrain
+        assert_eq!(terrain.heights(0, 0, 2), vec![0.0, 0.0, 10.0, 0.0]);
+        assert_eq!(terrain.heights(-1, 0, 1), vec![0.0]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
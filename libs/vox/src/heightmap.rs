@@ -0,0 +1,285 @@
+use bevy::prelude::*;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::{
+    chunk::{self, ChunkKind},
+    voxel::KindRegistry,
+};
+
+const COLUMN_COUNT: usize = chunk::AXIS_SIZE * chunk::AXIS_SIZE;
+
+/// Highest opaque voxel, in chunk coordinates, of each column of a chunk. `None` when the column has none.
+type ChunkHeights = [Option<u8>; COLUMN_COUNT];
+
+fn column_index(x: i32, z: i32) -> usize {
+    x as usize * chunk::AXIS_SIZE + z as usize
+}
+
+fn chunk_column(local: IVec3) -> IVec2 {
+    IVec2::new(local.x, local.z)
+}
+
+fn column_height(kind: &ChunkKind, registry: &KindRegistry, x: i32, z: i32) -> Option<u8> {
+    kind.column(x, z)
+        .iter()
+        .rposition(|kind| registry.is_opaque(*kind))
+        .map(|y| y as u8)
+}
+
+/**
+  Caches the highest opaque voxel of each world column, considering only loaded chunks.
+
+  Heights are kept per chunk and grouped by chunk column, so loading, unloading and editing a chunk only
+  touches its own heights. Columns without any opaque voxel on loaded chunks have no height, so they're
+  treated as open to the sky.
+*/
+#[derive(Default)]
+pub struct Heightmap {
+    columns: HashMap<IVec2, BTreeMap<i32, ChunkHeights>>,
+}
+
+impl Heightmap {
+    /**
+      World height of the highest opaque voxel on the given world column.
+    */
+    pub fn height(&self, column: IVec2) -> Option<i32> {
+        let size = chunk::AXIS_SIZE as i32;
+        let chunks = self.columns.get(&IVec2::new(
+            column.x.div_euclid(size),
+            column.y.div_euclid(size),
+        ))?;
+
+        let index = column_index(column.x.rem_euclid(size), column.y.rem_euclid(size));
+
+        chunks.iter().rev().find_map(|(chunk_y, heights)| {
+            heights[index].map(|height| chunk_y * size + height as i32)
+        })
+    }
+
+    /**
+      First voxel above the ground on the given world column, where something can stand on.
+    */
+    pub fn surface(&self, column: IVec2) -> Option<IVec3> {
+        self.height(column)
+            .map(|height| IVec3::new(column.x, height + 1, column.y))
+    }
+
+    /**
+      Whether the given world position is directly under the sky, with no opaque voxel above it.
+    */
+    pub fn is_exposed(&self, world: IVec3) -> bool {
+        match self.height(IVec2::new(world.x, world.z)) {
+            Some(height) => world.y > height,
+            None => true,
+        }
+    }
+
+    /**
+      Computes the heights of a newly loaded chunk. Returns all loaded chunks of the same chunk column which
+      had any voxel covered or uncovered by it.
+    */
+    pub fn load(
+        &mut self,
+        local: IVec3,
+        kind: &ChunkKind,
+        registry: &KindRegistry,
+    ) -> HashSet<IVec3> {
+        let mut heights = [None; COLUMN_COUNT];
+
+        for x in 0..chunk::AXIS_SIZE as i32 {
+            for z in 0..chunk::AXIS_SIZE as i32 {
+                heights[column_index(x, z)] = column_height(kind, registry, x, z);
+            }
+        }
+
+        let before = self.chunk_column_heights(chunk_column(local));
+
+        self.columns
+            .entry(chunk_column(local))
+            .or_default()
+            .insert(local.y, heights);
+
+        self.stale_chunks(chunk_column(local), &before)
+    }
+
+    pub fn unload(&mut self, local: IVec3) {
+        let column = chunk_column(local);
+
+        if let Some(chunks) = self.columns.get_mut(&column) {
+            chunks.remove(&local.y);
+
+            if chunks.is_empty() {
+                self.columns.remove(&column);
+            }
+        }
+    }
+
+    /**
+      Updates the height of the column which contains the given voxel, after it was changed.
+      Returns all loaded chunks of the same chunk column which had any voxel covered or uncovered by it.
+    */
+    pub fn update(
+        &mut self,
+        local: IVec3,
+        voxel: IVec3,
+        kind: &ChunkKind,
+        registry: &KindRegistry,
+    ) -> HashSet<IVec3> {
+        let column = chunk_column(local);
+        let before = self.chunk_column_heights(column);
+
+        match self
+            .columns
+            .get_mut(&column)
+            .and_then(|chunks| chunks.get_mut(&local.y))
+        {
+            Some(heights) => {
+                heights[column_index(voxel.x, voxel.z)] =
+                    column_height(kind, registry, voxel.x, voxel.z)
+            }
+            None => return HashSet::default(),
+        }
+
+        self.stale_chunks(column, &before)
+    }
+
+    /// World height of each column of a chunk column.
+    fn chunk_column_heights(&self, column: IVec2) -> Vec<Option<i32>> {
+        let size = chunk::AXIS_SIZE as i32;
+
+        (0..COLUMN_COUNT)
+            .map(|index| {
+                let (x, z) = (index / chunk::AXIS_SIZE, index % chunk::AXIS_SIZE);
+                self.height(column * size + IVec2::new(x as i32, z as i32))
+            })
+            .collect()
+    }
+
+    /// Loaded chunks between the old and the new height of any column which changed.
+    fn stale_chunks(&self, column: IVec2, before: &[Option<i32>]) -> HashSet<IVec3> {
+        let after = self.chunk_column_heights(column);
+        let size = chunk::AXIS_SIZE as i32;
+
+        let chunks = match self.columns.get(&column) {
+            Some(chunks) => chunks,
+            None => return HashSet::default(),
+        };
+
+        before
+            .iter()
+            .zip(after)
+            .filter(|(before, after)| **before != *after)
+            .flat_map(|(before, after)| {
+                // Columns without height are open all the way down
+                let low = before.min(&after).map_or(i32::MIN, |h| h.div_euclid(size));
+                let high = before.max(&after).map_or(i32::MIN, |h| h.div_euclid(size));
+
+                chunks.range(low..=high).map(|(y, _)| *y)
+            })
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .map(|y| IVec3::new(column.x, y, column.y))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ground(height: i32) -> ChunkKind {
+        let mut kind = ChunkKind::default();
+
+        for x in 0..chunk::AXIS_SIZE as i32 {
+            for z in 0..chunk::AXIS_SIZE as i32 {
+                kind.column_mut(x, z)[..=height as usize].fill(1.into());
+            }
+        }
+
+        kind
+    }
+
+    #[test]
+    fn height() {
+        let registry = KindRegistry::default();
+        let mut heightmap = Heightmap::default();
+
+        assert_eq!(heightmap.height(IVec2::ZERO), None);
+
+        heightmap.load(IVec3::ZERO, &ground(5), &registry);
+        heightmap.load((0, -1, 0).into(), &ground(15), &registry);
+
+        assert_eq!(heightmap.height(IVec2::ZERO), Some(5));
+        assert_eq!(heightmap.height((15, 15).into()), Some(5));
+        assert_eq!(heightmap.height((16, 0).into()), None);
+
+        assert_eq!(heightmap.surface((3, 4).into()), Some((3, 6, 4).into()));
+        assert!(heightmap.is_exposed((3, 6, 4).into()));
+        assert!(!heightmap.is_exposed((3, 5, 4).into()));
+
+        // Chunks below ground are still considered when the top one is unloaded
+        heightmap.unload(IVec3::ZERO);
+        assert_eq!(heightmap.height(IVec2::ZERO), Some(-1));
+    }
+
+    #[test]
+    fn height_negative_columns() {
+        let registry = KindRegistry::default();
+        let mut heightmap = Heightmap::default();
+
+        let mut kind = ChunkKind::default();
+        kind.set((15, 3, 0).into(), 1.into());
+        heightmap.load((-1, 2, -1).into(), &kind, &registry);
+
+        assert_eq!(heightmap.height((-1, -16).into()), Some(35));
+        assert_eq!(heightmap.height((-1, -15).into()), None);
+    }
+
+    #[test]
+    fn load_stale_chunks() {
+        let registry = KindRegistry::default();
+        let mut heightmap = Heightmap::default();
+
+        heightmap.load((0, -1, 0).into(), &ground(10), &registry);
+        heightmap.load((0, 0, 0).into(), &ChunkKind::default(), &registry);
+
+        // A floating island covers the empty chunk and part of the ground chunk below it
+        let mut island = ChunkKind::default();
+        island.set((3, 0, 3).into(), 1.into());
+        let stale = heightmap.load((0, 1, 0).into(), &island, &registry);
+
+        assert_eq!(stale.len(), 3);
+        assert!(stale.contains(&IVec3::ZERO));
+        assert!(stale.contains(&(0, -1, 0).into()));
+
+        // Loading empty chunks above changes nothing
+        let stale = heightmap.load((0, 2, 0).into(), &ChunkKind::default(), &registry);
+        assert!(stale.is_empty());
+    }
+
+    #[test]
+    fn update() {
+        let registry = KindRegistry::default();
+        let mut heightmap = Heightmap::default();
+
+        let mut kind = ground(4);
+        heightmap.load(IVec3::ZERO, &kind, &registry);
+
+        kind.set((2, 9, 2).into(), 1.into());
+        let stale = heightmap.update(IVec3::ZERO, (2, 9, 2).into(), &kind, &registry);
+
+        assert_eq!(heightmap.height((2, 2).into()), Some(9));
+        assert_eq!(heightmap.height((2, 3).into()), Some(4));
+        assert_eq!(stale, [IVec3::ZERO].into_iter().collect());
+
+        kind.set((2, 9, 2).into(), 0.into());
+        heightmap.update(IVec3::ZERO, (2, 9, 2).into(), &kind, &registry);
+        assert_eq!(heightmap.height((2, 2).into()), Some(4));
+
+        // Changes below the top doesn't change anything
+        kind.set((2, 1, 2).into(), 0.into());
+        assert!(heightmap
+            .update(IVec3::ZERO, (2, 1, 2).into(), &kind, &registry)
+            .is_empty());
+    }
+}
@@ -0,0 +1,159 @@
+use crate::noise::{Fractal, NoiseBackend, NoiseSource};
+
+/// Water level of rivers and lakes, relative to the highest the terrain goes.
+const WATER_LEVEL: f32 = 0.4;
+
+/// How far from the middle of the river noise, which ranges from -1.0 to 1.0, river beds go.
+const RIVER_WIDTH: f32 = 0.03;
+/// How far past river beds their banks go, sloping from the bed up to the terrain.
+const BANK_WIDTH: f32 = 0.08;
+/// How deep, in voxels, below the water level river beds are carved.
+const RIVER_DEPTH: f32 = 3.0;
+
+/// Lake noise, from -1.0 to 1.0, above which lake basins start.
+const LAKE_THRESHOLD: f32 = 0.45;
+/// How much lake noise past the threshold it takes for basins to reach their full depth.
+const LAKE_SLOPE: f32 = 0.2;
+/// How deep, in voxels, below the water level lake basins get on their middle.
+const LAKE_DEPTH: f32 = 5.0;
+
+/**
+  Rivers and lakes of a noise terrain. Rivers run along the middle of a noise, so they're long and winding
+  and never just end, while lakes are basins where another noise is high. Both are carved below the same
+  water level, so rivers flowing by lakes connect to them.
+
+  Carving only ever lowers the terrain, blending it down towards river and lake beds, so they're surrounded
+  by slopes instead of walls, and low terrain is left untouched, flooding as a lake of its own.
+*/
+pub struct Hydrology {
+    rivers: Box<dyn NoiseSource>,
+    lakes: Box<dyn NoiseSource>,
+    water_level: f32,
+}
+
+impl Hydrology {
+    /// Rivers and lakes of the given seed, on a terrain which goes up to `height` voxels.
+    pub fn new(seed: u64, height: u32, backend: NoiseBackend) -> Self {
+        Self {
+            rivers: backend.build(Fractal {
+                octaves: 2,
+                ..Fractal::simplex(seed.wrapping_add(1), 0.004)
+            }),
+            lakes: backend.build(Fractal::simplex(seed.wrapping_add(2), 0.008)),
+            water_level: (height as f32 * WATER_LEVEL).floor(),
+        }
+    }
+
+    /// Height, in voxels, up to which carved columns are filled with water.
+    pub fn water_level(&self) -> f32 {
+        self.water_level
+    }
+
+    /// How much the given world column is carved towards a river bed, from 0.0 (not at all) to 1.0 (bed).
+    fn river(&self, x: f32, z: f32) -> f32 {
+        river(self.rivers.get(x, z))
+    }
+
+    /// How much the given world column is carved towards a lake bed, from 0.0 (not at all) to 1.0 (middle).
+    fn lake(&self, x: f32, z: f32) -> f32 {
+        lake(self.lakes.get(x, z))
+    }
+
+    /// Height carved towards a bed by the given amount, from 0.0 (not at all) to 1.0 (down to the bed).
+    fn carve_towards(height: f32, bed: f32, amount: f32) -> f32 {
+        let bed = bed.max(0.0);
+        if height > bed {
+            height + (bed - height) * amount
+        } else {
+            height
+        }
+    }
+
+    /// Terrain height carved by the river and lake amounts of its column.
+    fn carve_column(&self, height: f32, river: f32, lake: f32) -> f32 {
+        let height = Self::carve_towards(height, self.water_level - RIVER_DEPTH, river);
+        Self::carve_towards(height, self.water_level - LAKE_DEPTH, lake)
+    }
+
+    /// Terrain height of the given world column, once carved by rivers and lakes.
+    pub fn carve(&self, x: f32, z: f32, height: f32) -> f32 {
+        self.carve_column(height, self.river(x, z), self.lake(x, z))
+    }
+
+    /**
+      Same as [`Hydrology::carve`], but for the `size` by `size` columns starting at the given world column,
+      listed by x, then z, like [`crate::generator::Terrain::heights`]. Noise is sampled all at once.
+    */
+    pub fn carve_all(&self, x: i32, z: i32, size: usize, heights: &mut [f32]) {
+        let rivers = self.rivers.fill(x as f32, z as f32, size);
+        let lakes = self.lakes.fill(x as f32, z as f32, size);
+
+        for (idx, height) in heights.iter_mut().enumerate() {
+            *height = self.carve_column(*height, river(rivers[idx]), lake(lakes[idx]));
+        }
+    }
+}
+
+fn river(noise: f32) -> f32 {
+    1.0 - smoothstep((noise.abs() - RIVER_WIDTH) / BANK_WIDTH)
+}
+
+fn lake(noise: f32) -> f32 {
+    smoothstep((noise - LAKE_THRESHOLD) / LAKE_SLOPE)
+}
+
+/// Eases the given value, clamped from 0.0 to 1.0, in and out.
+fn smoothstep(t: f32) -> f32 {
+    let t = t.clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn carve() {
+        let hydrology = Hydrology::new(7, 32, NoiseBackend::Bracket);
+        assert_eq!(hydrology.water_level(), 12.0);
+
+        let columns =
+            (-500..500).flat_map(|x| (-20..20).map(move |z| (x as f32 * 4.0, z as f32 * 4.0)));
+
+        // Find a river bed and a column with no water around it
+        let bed = columns
+            .clone()
+            .find(|(x, z)| hydrology.river(*x, *z) == 1.0)
+            .unwrap();
+        let dry = columns
+            .clone()
+            .find(|(x, z)| hydrology.river(*x, *z) == 0.0 && hydrology.lake(*x, *z) == 0.0)
+            .unwrap();
+
+        assert_eq!(hydrology.carve(bed.0, bed.1, 30.0), 12.0 - RIVER_DEPTH);
+        assert_eq!(hydrology.carve(dry.0, dry.1, 30.0), 30.0);
+
+        // Carving never raises the terrain
+        for (x, z) in columns.step_by(37) {
+            assert!(hydrology.carve(x, z, 20.0) <= 20.0);
+            assert_eq!(hydrology.carve(x, z, 1.0), 1.0);
+        }
+    }
+
+    #[test]
+    fn carve_all() {
+        let hydrology = Hydrology::new(7, 32, NoiseBackend::Bracket);
+        let mut heights = vec![20.0; 16 * 16];
+
+        hydrology.carve_all(-40, 200, 16, &mut heights);
+
+        assert_eq!(heights[3 * 16 + 5], hydrology.carve(-37.0, 205.0, 20.0));
+    }
+
+    #[test]
+    fn smoothstep() {
+        assert_eq!(super::smoothstep(-1.0), 0.0);
+        assert_eq!(super::smoothstep(0.5), 0.5);
+        assert_eq!(super::smoothstep(2.0), 1.0);
+    }
+}
@@ -0,0 +1,301 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use crate::voxel::{Kind, KindDescription};
+
+/// Where items dropped on unloaded chunks are saved, next to the chunks cache.
+pub const DROPS_PATH: &str = "cache/drops";
+
+/**
+  A tool the player can hold while breaking voxels. Higher tiers can break harder kinds, and faster tools
+  break them in less time.
+*/
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ToolDescription {
+    pub name: String,
+    pub tier: u8,
+    /// How many times faster than bare hands this tool breaks voxels.
+    pub speed: f32,
+}
+
+/**
+  All known tools, in the order they were defined.
+*/
+#[derive(Debug, Default, Clone)]
+pub struct ToolRegistry {
+    tools: Vec<ToolDescription>,
+}
+
+impl ToolRegistry {
+    pub fn load(path: &Path) -> Self {
+        let file = std::fs::File::open(path).unwrap_or_else(|_| {
+            panic!(
+                "Failed opening tool descriptions file at {}",
+                path.display()
+            )
+        });
+
+        let tools: Vec<ToolDescription> = ron::de::from_reader(file)
+            .unwrap_or_else(|_| panic!("Failed to parse tool descriptions {}", path.display()));
+
+        Self::new(tools)
+    }
+
+    pub fn new(tools: Vec<ToolDescription>) -> Self {
+        for tool in &tools {
+            assert!(
+                tool.speed > 0.0,
+                "Tool {} must have a positive speed",
+                tool.name
+            );
+        }
+
+        Self { tools }
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&ToolDescription> {
+        self.tools.get(idx)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tools.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+}
+
+/**
+  How long, in seconds, it takes to break a voxel of the given kind while holding the given tool, or bare
+  hands when there is none. Returns none when the tool tier is too low to break it at all.
+*/
+pub fn mining_time(kind: &KindDescription, tool: Option<&ToolDescription>) -> Option<f32> {
+    let (tier, speed) = tool.map_or((0, 1.0), |tool| (tool.tier, tool.speed));
+
+    if tier < kind.tool_tier {
+        None
+    } else {
+        Some(kind.hardness / speed)
+    }
+}
+
+/**
+  Voxels collected by the player, counted by kind. Facing is dropped, since it's only chosen when placing.
+*/
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Inventory {
+    items: BTreeMap<u16, u32>,
+}
+
+impl Inventory {
+    pub fn add(&mut self, kind: Kind, count: u32) {
+        if count > 0 {
+            *self.items.entry(kind.id()).or_default() += count;
+        }
+    }
+
+    /// Removes the given count of the given kind. Nothing is removed when there isn't enough of it.
+    pub fn remove(&mut self, kind: Kind, count: u32) -> bool {
+        match self.items.get_mut(&kind.id()) {
+            Some(current) if *current > count => {
+                *current -= count;
+                true
+            }
+            Some(current) if *current == count => {
+                self.items.remove(&kind.id());
+                true
+            }
+            _ => count == 0,
+        }
+    }
+
+    pub fn count(&self, kind: Kind) -> u32 {
+        self.items.get(&kind.id()).copied().unwrap_or_default()
+    }
+
+    /// All held kinds and their counts, ordered by kind id.
+    pub fn iter(&self) -> impl Iterator<Item = (Kind, u32)> + '_ {
+        self.items.iter().map(|(id, count)| ((*id).into(), *count))
+    }
+
+    /// Number of different kinds held.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+/**
+  An item lying on the ground, saved while its chunk is unloaded. The position is relative to the chunk, so
+  it doesn't depend on the world origin.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DroppedItem {
+    pub kind: Kind,
+    pub count: u32,
+    pub offset: Vec3,
+    /// For how long, in seconds, it's been lying around.
+    pub age: f32,
+}
+
+fn drops_path(dir: &Path, local: IVec3) -> PathBuf {
+    dir.join(format!("{}_{}_{}.ron", local.x, local.y, local.z))
+}
+
+/**
+  Saves the items dropped on the given chunk, in the given dir. Chunks without items have their file removed.
+*/
+pub fn save_drops(dir: &Path, local: IVec3, drops: &[DroppedItem]) {
+    let path = drops_path(dir, local);
+
+    if drops.is_empty() {
+        if path.exists() {
+            std::fs::remove_file(&path)
+                .unwrap_or_else(|_| panic!("Unable to remove file {}", path.display()));
+        }
+        return;
+    }
+
+    std::fs::create_dir_all(dir)
+        .unwrap_or_else(|_| panic!("Unable to create dir {}", dir.display()));
+
+    let file = std::fs::File::create(&path)
+        .unwrap_or_else(|_| panic!("Unable to write to file {}", path.display()));
+
+    ron::ser::to_writer(file, drops)
+        .unwrap_or_else(|_| panic!("Failed to serialize to file {}", path.display()));
+}
+
+/**
+  Loads the items dropped on the given chunk, from the given dir, and removes their file, since they're
+  owned by the world again until the chunk is unloaded.
+*/
+pub fn take_drops(dir: &Path, local: IVec3) -> Vec<DroppedItem> {
+    let path = drops_path(dir, local);
+
+    if !path.exists() {
+        return vec![];
+    }
+
+    let file = std::fs::File::open(&path)
+        .unwrap_or_else(|_| panic!("Unable to open file {}", path.display()));
+
+    let drops = ron::de::from_reader(file)
+        .unwrap_or_else(|_| panic!("Failed to parse file {}", path.display()));
+
+    std::fs::remove_file(&path)
+        .unwrap_or_else(|_| panic!("Unable to remove file {}", path.display()));
+
+    drops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel::MeshShape;
+
+    fn kind(hardness: f32, tool_tier: u8) -> KindDescription {
+        KindDescription {
+            name: "Stone".to_string(),
+            id: 1,
+            color: (0.5, 0.5, 0.5, 1.0),
+            shape: MeshShape::Cube,
+            prop: None,
+            directional: false,
+            light: 0,
+            tick: None,
+            signal: None,
+            toggle: None,
+            hardness,
+            tool_tier,
+            block_entity: None,
+            explosive: None,
+            liquid: None,
+            climbable: false,
+            surface: None,
+            foliage: false,
+            tint: None,
+            portal: None,
+        }
+    }
+
+    #[test]
+    fn tool_registry() {
+        let input_path = format!(
+            "{}assets/items/tool_descriptions.ron",
+            env!("CARGO_WORKSPACE_DIR")
+        );
+
+        let tools = ToolRegistry::load(Path::new(&input_path));
+
+        assert!(!tools.is_empty());
+        assert!(tools.get(tools.len()).is_none());
+    }
+
+    #[test]
+    fn mining_time() {
+        let pickaxe = ToolDescription {
+            name: "Pickaxe".to_string(),
+            tier: 1,
+            speed: 4.0,
+        };
+
+        assert_eq!(super::mining_time(&kind(0.0, 0), None), Some(0.0));
+        assert_eq!(super::mining_time(&kind(2.0, 0), None), Some(2.0));
+        assert_eq!(super::mining_time(&kind(2.0, 0), Some(&pickaxe)), Some(0.5));
+
+        // Too hard for bare hands
+        assert_eq!(super::mining_time(&kind(2.0, 1), None), None);
+        assert_eq!(super::mining_time(&kind(2.0, 1), Some(&pickaxe)), Some(0.5));
+        assert_eq!(super::mining_time(&kind(2.0, 2), Some(&pickaxe)), None);
+    }
+
+    #[test]
+    fn inventory() {
+        let mut inventory = Inventory::default();
+
+        inventory.add(2.into(), 3);
+        inventory.add(Kind::from(2).with_facing(crate::voxel::Side::Left), 1);
+        inventory.add(1.into(), 1);
+
+        assert_eq!(inventory.count(2.into()), 4);
+        assert_eq!(
+            inventory.iter().collect::<Vec<_>>(),
+            vec![(1.into(), 1), (2.into(), 4)]
+        );
+
+        assert!(!inventory.remove(1.into(), 2));
+        assert!(inventory.remove(1.into(), 1));
+        assert!(inventory.remove(2.into(), 3));
+        assert_eq!(inventory.count(1.into()), 0);
+        assert_eq!(inventory.count(2.into()), 1);
+    }
+
+    #[test]
+    fn save_take_drops() {
+        let dir = std::env::temp_dir().join("eterno_save_take_drops");
+        let local = IVec3::new(-3, 1, 2);
+        let drops = vec![DroppedItem {
+            kind: 3.into(),
+            count: 2,
+            offset: Vec3::new(1.5, 2.0, 15.5),
+            age: 12.0,
+        }];
+
+        save_drops(&dir, local, &drops);
+
+        assert!(take_drops(&dir, IVec3::ZERO).is_empty());
+        assert_eq!(take_drops(&dir, local), drops);
+
+        // Taken items are owned by the world, so they aren't loaded twice
+        assert!(take_drops(&dir, local).is_empty());
+    }
+}
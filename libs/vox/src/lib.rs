@@ -1,9 +1,41 @@
+pub mod biome;
+pub mod block_entity;
+pub mod body;
+pub mod bounds;
+pub mod broadphase;
+pub mod caves;
+pub mod chunk;
+pub mod claim;
+pub mod climb;
+pub mod combat;
+pub mod craft;
+pub mod decay;
+pub mod dimension;
+pub mod edit;
+pub mod explosion;
+pub mod fluid;
+pub mod generator;
+pub mod heightmap;
+pub mod hydrology;
+pub mod item;
+pub mod light;
 pub mod math;
+pub mod meta;
+pub mod mob;
+pub mod modding;
+pub mod noise;
 pub mod query;
-pub mod chunk;
+pub mod replay;
+pub mod schedule;
+pub mod signal;
+pub mod stats;
+pub mod surface;
+pub mod tick;
+pub mod ticket;
 pub mod voxel;
+pub mod weather;
 pub mod world;
 
-mod pipeline;
+pub mod pipeline;
 
-// MOVE Genesis to here
\ No newline at end of file
+// MOVE Genesis to here
@@ -0,0 +1,718 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::{
+    chunk::{self, ChunkKind, ChunkStorage, ChunkStorageType},
+    heightmap::Heightmap,
+    query,
+    voxel::{self, Kind, KindRegistry},
+    world::VoxWorld,
+};
+
+/// Brightest light level. Light decreases by one on each voxel it spreads to.
+pub const MAX_LIGHT: u8 = 15;
+
+/// How many block light channels each voxel has. Colored light has a red, a green and a blue channel.
+#[cfg(not(feature = "colored_light"))]
+pub const CHANNELS: usize = 1;
+#[cfg(feature = "colored_light")]
+pub const CHANNELS: usize = 3;
+
+/// Channel of the light coming from the sky, which is stored after the block light channels.
+pub const SKY: usize = CHANNELS;
+
+const CHANNEL_COUNT: usize = CHANNELS + 1;
+
+#[cfg(not(feature = "colored_light"))]
+type Packed = u8;
+#[cfg(feature = "colored_light")]
+type Packed = u16;
+
+/// Each channel fits a level up to `MAX_LIGHT`.
+const CHANNEL_BITS: usize = 4;
+const CHANNEL_MASK: Packed = 0b1111;
+
+/**
+  Light of a voxel, with all channels packed together. Colored light doubles the light layer memory,
+  so it's only enabled by the `colored_light` feature.
+*/
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Light(Packed);
+
+impl Light {
+    // Packed is already an u8 without colored light
+    #[allow(clippy::unnecessary_cast)]
+    pub fn channel(&self, channel: usize) -> u8 {
+        ((self.0 >> (channel * CHANNEL_BITS)) & CHANNEL_MASK) as u8
+    }
+
+    pub fn set_channel(&mut self, channel: usize, level: u8) {
+        let shift = channel * CHANNEL_BITS;
+        self.0 = (self.0 & !(CHANNEL_MASK << shift)) | ((level as Packed & CHANNEL_MASK) << shift);
+    }
+
+    /// Brightest level among all block light channels.
+    pub fn level(&self) -> u8 {
+        (0..CHANNELS).map(|c| self.channel(c)).max().unwrap_or(0)
+    }
+
+    pub fn sky(&self) -> u8 {
+        self.channel(SKY)
+    }
+
+    /// Red, green and blue levels. Without colored light, all of them are the same.
+    pub fn rgb(&self) -> [u8; 3] {
+        if CHANNELS == 1 {
+            [self.channel(0); 3]
+        } else {
+            [0, 1, 2].map(|c| self.channel(c))
+        }
+    }
+}
+
+impl ChunkStorageType for Light {}
+
+pub type ChunkLight = ChunkStorage<Light>;
+
+/// Light level which must be applied to a voxel, if it's brighter than the current one, and then spread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct LightNode {
+    voxel: IVec3,
+    channel: usize,
+    level: u8,
+}
+
+/**
+  Level emitted by the given kind on a channel. Colored light is tinted by the kind color.
+*/
+pub fn emission(registry: &KindRegistry, kind: Kind, channel: usize) -> u8 {
+    if channel == SKY {
+        return 0;
+    }
+
+    let level = registry.light(kind);
+
+    if CHANNELS == 1 || level == 0 {
+        return level;
+    }
+
+    let (r, g, b, _) = registry
+        .get(kind)
+        .map_or((1.0, 1.0, 1.0, 1.0), |desc| desc.color);
+    let tint = [r, g, b][channel].clamp(0.0, 1.0);
+
+    (level as f32 * tint).round() as u8
+}
+
+/**
+  Light levels of all lit chunks.
+
+  Light is propagated by a BFS inside each chunk. When it reaches a chunk border, it's deferred to a queue
+  of the neighbor chunk, which is propagated after the current chunk is done. This way light crosses
+  chunk borders while each chunk is visited as few times as possible.
+
+  Light never spreads further than `MAX_LIGHT - 1` voxels, which is less than a chunk, so an emitter only
+  affects its own chunk and the chunks directly around it.
+*/
+#[derive(Default)]
+pub struct LightWorld {
+    chunks: HashMap<IVec3, ChunkLight>,
+    /// Light which crossed chunk borders and wasn't propagated yet.
+    pending: HashMap<IVec3, Vec<LightNode>>,
+}
+
+impl LightWorld {
+    pub fn get(&self, local: IVec3) -> Option<&ChunkLight> {
+        self.chunks.get(&local)
+    }
+
+    /// Block light level of the given voxel, on the brightest channel. Voxels of chunks which aren't lit are dark.
+    pub fn light(&self, local: IVec3, voxel: IVec3) -> u8 {
+        self.chunks
+            .get(&local)
+            .map_or(0, |light| light.get(voxel).level())
+    }
+
+    /// Sky light level of the given voxel. Voxels of chunks which aren't lit are dark.
+    pub fn sky(&self, local: IVec3, voxel: IVec3) -> u8 {
+        self.chunks
+            .get(&local)
+            .map_or(0, |light| light.get(voxel).sky())
+    }
+
+    pub fn is_lit(&self, local: IVec3) -> bool {
+        self.chunks.contains_key(&local)
+    }
+
+    pub fn unload(&mut self, local: IVec3) {
+        self.chunks.remove(&local);
+        self.pending.remove(&local);
+    }
+
+    /**
+      Lights a newly loaded chunk, by its own emitters, the sky and the light coming from lit neighbors.
+      Returns all chunks which had their light changed.
+    */
+    pub fn load(
+        &mut self,
+        world: &VoxWorld,
+        registry: &KindRegistry,
+        heightmap: &Heightmap,
+        local: IVec3,
+    ) -> HashSet<IVec3> {
+        if world.get(local).is_none() {
+            return HashSet::default();
+        }
+
+        self.chunks.insert(local, ChunkLight::default());
+
+        let region = [local].into_iter().collect();
+        self.seed(world, registry, heightmap, &region);
+        self.propagate(world, registry)
+    }
+
+    /**
+      Relights chunks which had their voxels, or the sky above them, changed. Chunks around them may hold
+      light coming from removed emitters or which passed through removed voxels, so they're cleared and relit too.
+      Returns all chunks which had their light changed.
+    */
+    pub fn relight(
+        &mut self,
+        world: &VoxWorld,
+        registry: &KindRegistry,
+        heightmap: &Heightmap,
+        locals: impl IntoIterator<Item = IVec3>,
+    ) -> HashSet<IVec3> {
+        let region = locals
+            .into_iter()
+            .flat_map(|local| query::range_inclusive(local - IVec3::ONE, local + IVec3::ONE))
+            .filter(|neighbor| self.is_lit(*neighbor))
+            .collect::<HashSet<_>>();
+
+        for neighbor in &region {
+            self.chunks.insert(*neighbor, ChunkLight::default());
+        }
+
+        self.seed(world, registry, heightmap, &region);
+
+        let mut changed = self.propagate(world, registry);
+        changed.extend(region);
+
+        changed
+    }
+
+    /**
+      Queues the emitters and sky exposed voxels of all chunks in the region, and the light on the borders
+      of lit chunks around it.
+    */
+    fn seed(
+        &mut self,
+        world: &VoxWorld,
+        registry: &KindRegistry,
+        heightmap: &Heightmap,
+        region: &HashSet<IVec3>,
+    ) {
+        for &local in region {
+            if let Some(kind) = world.get(local) {
+                let nodes = emitters(&kind, registry);
+                self.pending.entry(local).or_default().extend(nodes);
+            }
+
+            self.pending
+                .entry(local)
+                .or_default()
+                .extend(sky_nodes(heightmap, local));
+
+            for side in voxel::SIDES {
+                let neighbor = local + side.dir();
+
+                if region.contains(&neighbor) {
+                    continue;
+                }
+
+                if let Some(light) = self.chunks.get(&neighbor) {
+                    let nodes = border_nodes(light, side.opposite());
+                    self.pending.entry(local).or_default().extend(nodes);
+                }
+            }
+        }
+    }
+
+    /**
+      Propagates all queued light, one chunk at a time, until no light is left to cross chunk borders.
+    */
+    fn propagate(&mut self, world: &VoxWorld, registry: &KindRegistry) -> HashSet<IVec3> {
+        let mut changed = HashSet::default();
+
+        while let Some(&local) = self.pending.keys().next() {
+            let nodes = self.pending.remove(&local).unwrap_or_default();
+
+            // Light reaching chunks which aren't lit yet is dropped, it'll be seeded again when they're loaded
+            let (kind, light) = match (world.get(local), self.chunks.get_mut(&local)) {
+                (Some(kind), Some(light)) => (kind, light),
+                _ => continue,
+            };
+
+            if spread(&kind, registry, light, nodes, |dir, node| {
+                self.pending.entry(local + dir).or_default().push(node);
+            }) {
+                changed.insert(local);
+            }
+        }
+
+        changed
+    }
+}
+
+/**
+  Nodes of all voxels which emits light on the given chunk. Emitters are lit even if they're opaque.
+*/
+fn emitters(kind: &ChunkKind, registry: &KindRegistry) -> Vec<LightNode> {
+    chunk::voxels()
+        .flat_map(|voxel| {
+            let voxel_kind = kind.get(voxel);
+
+            (0..CHANNELS).map(move |channel| LightNode {
+                voxel,
+                channel,
+                level: emission(registry, voxel_kind, channel),
+            })
+        })
+        .filter(|node| node.level > 0)
+        .collect()
+}
+
+/**
+  Nodes of all voxels of the given chunk which are directly under the sky. Sky light doesn't fade while going
+  straight down, only when it spreads sideways or below something.
+*/
+fn sky_nodes(heightmap: &Heightmap, local: IVec3) -> Vec<LightNode> {
+    let origin = chunk::to_world(local).as_ivec3();
+    let mut nodes = vec![];
+
+    for x in 0..chunk::AXIS_SIZE as i32 {
+        for z in 0..chunk::AXIS_SIZE as i32 {
+            let height = heightmap.height(IVec2::new(origin.x + x, origin.z + z));
+            let lowest = height.map_or(0, |height| height + 1 - origin.y).max(0);
+
+            nodes.extend((lowest..chunk::AXIS_SIZE as i32).map(|y| LightNode {
+                voxel: (x, y, z).into(),
+                channel: SKY,
+                level: MAX_LIGHT,
+            }));
+        }
+    }
+
+    nodes
+}
+
+/**
+  Nodes of the light entering a chunk through the given side of its neighbor.
+  Voxels are already converted to the chunk which is receiving the light.
+*/
+fn border_nodes(neighbor_light: &ChunkLight, side: voxel::Side) -> Vec<LightNode> {
+    const END: i32 = chunk::AXIS_ENDING as i32;
+
+    let dir = side.dir();
+    let (begin, end) = match side {
+        voxel::Side::Right => ((END, 0, 0).into(), (END, END, END).into()),
+        voxel::Side::Left => ((0, 0, 0).into(), (0, END, END).into()),
+        voxel::Side::Up => ((0, END, 0).into(), (END, END, END).into()),
+        voxel::Side::Down => ((0, 0, 0).into(), (END, 0, END).into()),
+        voxel::Side::Front => ((0, 0, END).into(), (END, END, END).into()),
+        voxel::Side::Back => ((0, 0, 0).into(), (END, END, 0).into()),
+    };
+
+    query::range_inclusive(begin, end)
+        .flat_map(|voxel| {
+            let light = neighbor_light.get(voxel);
+
+            (0..CHANNEL_COUNT).map(move |channel| LightNode {
+                voxel: chunk::overlap_voxel(voxel + dir).1,
+                channel,
+                level: light.channel(channel).saturating_sub(1),
+            })
+        })
+        .filter(|node| node.level > 0)
+        .collect()
+}
+
+/**
+  Spreads the given nodes inside a chunk. Light leaving the chunk is handed to `deferred`, together with
+  the direction of the neighbor chunk. Returns true if any voxel light changed.
+*/
+fn spread(
+    kind: &ChunkKind,
+    registry: &KindRegistry,
+    light: &mut ChunkLight,
+    nodes: Vec<LightNode>,
+    mut deferred: impl FnMut(IVec3, LightNode),
+) -> bool {
+    let mut changed = false;
+    let mut queue = nodes.into_iter().collect::<VecDeque<_>>();
+
+    while let Some(LightNode {
+        voxel,
+        channel,
+        level,
+    }) = queue.pop_front()
+    {
+        let voxel_kind = kind.get(voxel);
+        let mut voxel_light = light.get(voxel);
+
+        // Opaque voxels are only lit by their own emission
+        if level <= voxel_light.channel(channel)
+            || (registry.is_opaque(voxel_kind) && level > emission(registry, voxel_kind, channel))
+        {
+            continue;
+        }
+
+        voxel_light.set_channel(channel, level);
+        light.set(voxel, voxel_light);
+        changed = true;
+
+        if level <= 1 {
+            continue;
+        }
+
+        for side in voxel::SIDES {
+            let neighbor = voxel + side.dir();
+            let node = LightNode {
+                voxel: neighbor,
+                channel,
+                level: level - 1,
+            };
+
+            if !chunk::is_within_bounds(neighbor) {
+                let (dir, neighbor) = chunk::overlap_voxel(neighbor);
+                deferred(
+                    dir,
+                    LightNode {
+                        voxel: neighbor,
+                        ..node
+                    },
+                );
+            } else if light.get(neighbor).channel(channel) < node.level {
+                queue.push_back(node);
+            }
+        }
+    }
+
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel::{KindDescription, MeshShape, PropShape};
+
+    const TORCH: u16 = 3;
+    const TORCH_LIGHT: u8 = 14;
+
+    fn registry() -> KindRegistry {
+        KindRegistry::new(vec![
+            KindDescription {
+                name: "Stone".to_string(),
+                id: 1,
+                color: (0.5, 0.5, 0.5, 1.0),
+                shape: MeshShape::Cube,
+                prop: None,
+                directional: false,
+                light: 0,
+                tick: None,
+                signal: None,
+                toggle: None,
+                hardness: 0.0,
+                tool_tier: 0,
+                block_entity: None,
+                explosive: None,
+                liquid: None,
+                climbable: false,
+                surface: None,
+                foliage: false,
+                tint: None,
+                portal: None,
+            },
+            KindDescription {
+                name: "Torch".to_string(),
+                id: TORCH,
+                color: (1.0, 0.8, 0.3, 1.0),
+                shape: MeshShape::Cube,
+                prop: Some(PropShape::Post),
+                directional: false,
+                light: TORCH_LIGHT,
+                tick: None,
+                signal: None,
+                toggle: None,
+                hardness: 0.0,
+                tool_tier: 0,
+                block_entity: None,
+                explosive: None,
+                liquid: None,
+                climbable: false,
+                surface: None,
+                foliage: false,
+                tint: None,
+                portal: None,
+            },
+        ])
+    }
+
+    fn corners() -> impl Iterator<Item = IVec3> {
+        query::range_inclusive(IVec3::ZERO, IVec3::ONE)
+    }
+
+    #[test]
+    fn spread_single_chunk() {
+        let world = VoxWorld::default();
+        let registry = registry();
+        let mut light = LightWorld::default();
+        let heightmap = Heightmap::default();
+
+        let mut kind = ChunkKind::default();
+        kind.set((8, 8, 8).into(), TORCH.into());
+        // A wall blocks the light going left
+        for voxel in query::range_inclusive((6, 0, 0).into(), (6, 15, 15).into()) {
+            kind.set(voxel, 1.into());
+        }
+        world.add(IVec3::ZERO, kind);
+
+        let changed = light.load(&world, &registry, &heightmap, IVec3::ZERO);
+        assert!(changed.contains(&IVec3::ZERO));
+
+        assert_eq!(light.light(IVec3::ZERO, (8, 8, 8).into()), TORCH_LIGHT);
+        assert_eq!(light.light(IVec3::ZERO, (10, 8, 8).into()), TORCH_LIGHT - 2);
+        assert_eq!(light.light(IVec3::ZERO, (8, 5, 9).into()), TORCH_LIGHT - 4);
+        assert_eq!(light.light(IVec3::ZERO, (6, 8, 8).into()), 0);
+        assert_eq!(light.light(IVec3::ZERO, (5, 8, 8).into()), 0);
+    }
+
+    #[test]
+    fn torch_at_chunk_corner() {
+        let world = VoxWorld::default();
+        let registry = registry();
+        let mut light = LightWorld::default();
+        let heightmap = Heightmap::default();
+
+        let mut kind = ChunkKind::default();
+        kind.set(IVec3::splat(chunk::AXIS_ENDING as i32), TORCH.into());
+        world.add(IVec3::ZERO, kind);
+
+        for local in corners().filter(|local| *local != IVec3::ZERO) {
+            world.add(local, ChunkKind::default());
+        }
+
+        // Neighbors loaded later must receive the light coming from the torch chunk
+        let mut changed = light.load(&world, &registry, &heightmap, IVec3::ZERO);
+        for local in corners().filter(|local| *local != IVec3::ZERO) {
+            changed.extend(light.load(&world, &registry, &heightmap, local));
+        }
+
+        for local in corners() {
+            assert!(changed.contains(&local), "Chunk {} wasn't lit", local);
+        }
+
+        // The voxel diagonally touching the torch is three voxels away from it
+        assert_eq!(light.light(IVec3::ONE, IVec3::ZERO), TORCH_LIGHT - 3);
+        assert_eq!(
+            light.light((1, 0, 0).into(), (0, 15, 15).into()),
+            TORCH_LIGHT - 1
+        );
+        assert_eq!(
+            light.light((0, 1, 0).into(), (15, 5, 15).into()),
+            TORCH_LIGHT - 6
+        );
+
+        // Light never reaches chunks further away
+        assert_eq!(light.light((2, 0, 0).into(), (0, 15, 15).into()), 0);
+    }
+
+    #[test]
+    fn torch_at_chunk_corner_loaded_last() {
+        let world = VoxWorld::default();
+        let registry = registry();
+        let mut light = LightWorld::default();
+        let heightmap = Heightmap::default();
+
+        for local in corners().filter(|local| *local != IVec3::ZERO) {
+            world.add(local, ChunkKind::default());
+            light.load(&world, &registry, &heightmap, local);
+        }
+
+        let mut kind = ChunkKind::default();
+        kind.set(IVec3::splat(chunk::AXIS_ENDING as i32), TORCH.into());
+        world.add(IVec3::ZERO, kind);
+
+        let changed = light.load(&world, &registry, &heightmap, IVec3::ZERO);
+
+        assert_eq!(changed.len(), 8);
+        assert_eq!(light.light(IVec3::ONE, IVec3::ZERO), TORCH_LIGHT - 3);
+        assert_eq!(light.light(IVec3::ONE, (0, 0, 2).into()), TORCH_LIGHT - 5);
+    }
+
+    #[test]
+    fn relight_removed_torch() {
+        let world = VoxWorld::default();
+        let registry = registry();
+        let mut light = LightWorld::default();
+        let heightmap = Heightmap::default();
+
+        let corner = IVec3::splat(chunk::AXIS_ENDING as i32);
+        let mut kind = ChunkKind::default();
+        kind.set(corner, TORCH.into());
+        world.add(IVec3::ZERO, kind);
+
+        for local in corners().filter(|local| *local != IVec3::ZERO) {
+            world.add(local, ChunkKind::default());
+        }
+
+        for local in corners() {
+            light.load(&world, &registry, &heightmap, local);
+        }
+
+        assert!(light.light(IVec3::ONE, IVec3::ZERO) > 0);
+
+        world.get_mut(IVec3::ZERO).unwrap().set(corner, 0.into());
+        let changed = light.relight(&world, &registry, &heightmap, [IVec3::ZERO]);
+
+        // Light left by the torch on the neighbors must be cleared too
+        for local in corners() {
+            assert!(changed.contains(&local));
+            assert!(light
+                .get(local)
+                .unwrap()
+                .iter()
+                .all(|light| light.level() == 0));
+        }
+    }
+
+    #[test]
+    fn relight_keeps_other_emitters() {
+        let world = VoxWorld::default();
+        let registry = registry();
+        let mut light = LightWorld::default();
+        let heightmap = Heightmap::default();
+
+        world.add(IVec3::ZERO, ChunkKind::default());
+
+        let mut kind = ChunkKind::default();
+        kind.set((0, 8, 8).into(), TORCH.into());
+        world.add(IVec3::X, kind);
+
+        light.load(&world, &registry, &heightmap, IVec3::ZERO);
+        light.load(&world, &registry, &heightmap, IVec3::X);
+
+        assert_eq!(light.light(IVec3::ZERO, (15, 8, 8).into()), TORCH_LIGHT - 1);
+
+        // Placing a wall in front of the torch blocks its light
+        world
+            .get_mut(IVec3::ZERO)
+            .unwrap()
+            .set((15, 8, 8).into(), 1.into());
+        light.relight(&world, &registry, &heightmap, [IVec3::ZERO]);
+
+        assert_eq!(light.light(IVec3::ZERO, (15, 8, 8).into()), 0);
+        assert_eq!(light.light(IVec3::ZERO, (14, 8, 8).into()), TORCH_LIGHT - 4);
+        assert_eq!(light.light(IVec3::X, (0, 8, 8).into()), TORCH_LIGHT);
+    }
+
+    #[test]
+    fn sky_light() {
+        let world = VoxWorld::default();
+        let registry = registry();
+        let mut light = LightWorld::default();
+        let mut heightmap = Heightmap::default();
+
+        // A roof covering half of the chunk
+        let mut kind = ChunkKind::default();
+        for voxel in query::range_inclusive((0, 10, 0).into(), (7, 10, 15).into()) {
+            kind.set(voxel, 1.into());
+        }
+        heightmap.load(IVec3::ZERO, &kind, &registry);
+        world.add(IVec3::ZERO, kind);
+
+        light.load(&world, &registry, &heightmap, IVec3::ZERO);
+
+        assert_eq!(light.sky(IVec3::ZERO, (2, 15, 2).into()), MAX_LIGHT);
+        assert_eq!(light.sky(IVec3::ZERO, (8, 0, 2).into()), MAX_LIGHT);
+        assert_eq!(light.sky(IVec3::ZERO, (7, 10, 2).into()), 0);
+        assert_eq!(light.sky(IVec3::ZERO, (5, 9, 2).into()), MAX_LIGHT - 3);
+        assert_eq!(light.light(IVec3::ZERO, (5, 9, 2).into()), 0);
+
+        // Opening a hole on the roof lets the sky in
+        world
+            .get_mut(IVec3::ZERO)
+            .unwrap()
+            .set((2, 10, 2).into(), 0.into());
+        let stale = heightmap.update(
+            IVec3::ZERO,
+            (2, 10, 2).into(),
+            &world.get(IVec3::ZERO).unwrap(),
+            &registry,
+        );
+        light.relight(&world, &registry, &heightmap, stale);
+
+        assert_eq!(light.sky(IVec3::ZERO, (2, 0, 2).into()), MAX_LIGHT);
+        assert_eq!(light.sky(IVec3::ZERO, (2, 0, 3).into()), MAX_LIGHT - 1);
+    }
+
+    #[test]
+    fn light_channels() {
+        let mut light = Light::default();
+        assert_eq!(light.level(), 0);
+
+        light.set_channel(0, MAX_LIGHT);
+        assert_eq!(light.channel(0), MAX_LIGHT);
+        assert_eq!(light.level(), MAX_LIGHT);
+
+        light.set_channel(0, 3);
+        assert_eq!(light.channel(0), 3);
+        assert_eq!(light.level(), 3);
+    }
+
+    #[cfg(feature = "colored_light")]
+    #[test]
+    fn colored_light() {
+        let world = VoxWorld::default();
+        let registry = registry();
+        let mut light = LightWorld::default();
+        let heightmap = Heightmap::default();
+
+        let mut kind = ChunkKind::default();
+        kind.set((8, 8, 8).into(), TORCH.into());
+        world.add(IVec3::ZERO, kind);
+        light.load(&world, &registry, &heightmap, IVec3::ZERO);
+
+        // Torches are orange, so there is less blue light around them
+        let torch = light.get(IVec3::ZERO).unwrap().get((8, 8, 8).into());
+        assert_eq!(torch.rgb(), [14, 11, 4]);
+
+        let near = light.get(IVec3::ZERO).unwrap().get((8, 10, 8).into());
+        assert_eq!(near.rgb(), [12, 9, 2]);
+
+        let far = light.get(IVec3::ZERO).unwrap().get((8, 12, 8).into());
+        assert_eq!(far.rgb(), [10, 7, 0]);
+    }
+
+    #[test]
+    fn unload() {
+        let world = VoxWorld::default();
+        let registry = registry();
+        let mut light = LightWorld::default();
+        let heightmap = Heightmap::default();
+
+        world.add(IVec3::ZERO, ChunkKind::default());
+        light.load(&world, &registry, &heightmap, IVec3::ZERO);
+        assert!(light.is_lit(IVec3::ZERO));
+
+        light.unload(IVec3::ZERO);
+        assert!(!light.is_lit(IVec3::ZERO));
+
+        // Chunks which doesn't exist can't be lit
+        assert!(light
+            .load(&world, &registry, &heightmap, IVec3::ONE)
+            .is_empty());
+        assert!(!light.is_lit(IVec3::ONE));
+    }
+}
@@ -1,9 +1,21 @@
 use bevy::prelude::*;
 
+use crate::bounds::Bounds;
+
 pub fn is_within_cubic_bounds(pos: IVec3, min: i32, max: i32) -> bool {
     pos.min_element() >= min && pos.max_element() <= max
 }
 
+/**
+  FNV-1a hash of the given bytes. Unlike std hashers, it's guaranteed to be the same across builds, so it can
+  be saved or compared with other games.
+*/
+pub fn fnv_hash(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    bytes.into_iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
 pub fn floor(vec: Vec3) -> IVec3 {
     IVec3::new(
         vec.x.floor() as i32,
@@ -63,8 +75,185 @@ pub fn to_unit_dir(dir: IVec3) -> Vec<IVec3> {
     result
 }
 
+/**
+  Where a ray enters and leaves the bounds, as distances along `dir`, with the normal of the face it enters
+  through. Rays starting inside the bounds enter at zero, with no normal.
+*/
+fn slab(origin: Vec3, dir: Vec3, bounds: &Bounds) -> Option<(f32, f32, IVec3)> {
+    let mut enter = f32::NEG_INFINITY;
+    let mut leave = f32::INFINITY;
+    let mut normal = IVec3::ZERO;
+
+    for axis in 0..3 {
+        if dir[axis] == 0.0 {
+            // Parallel rays never cross this axis slabs, so they must already be between them
+            if origin[axis] < bounds.min[axis] || origin[axis] > bounds.max[axis] {
+                return None;
+            }
+
+            continue;
+        }
+
+        let near = (bounds.min[axis] - origin[axis]) / dir[axis];
+        let far = (bounds.max[axis] - origin[axis]) / dir[axis];
+        let (near, far) = if near < far { (near, far) } else { (far, near) };
+
+        if near > enter {
+            enter = near;
+            normal = IVec3::ZERO;
+            normal[axis] = -dir[axis].signum() as i32;
+        }
+
+        leave = leave.min(far);
+    }
+
+    if enter > leave || leave < 0.0 {
+        None
+    } else if enter < 0.0 {
+        Some((0.0, leave, IVec3::ZERO))
+    } else {
+        Some((enter, leave, normal))
+    }
+}
+
+/**
+  Distance along `dir` where the ray starting on `origin` enters the bounds, or `None` when it misses them.
+  Rays starting inside the bounds hit them right away, at zero. Distances are only lengths when `dir` is
+  normalized.
+*/
+pub fn ray_aabb(origin: Vec3, dir: Vec3, bounds: &Bounds) -> Option<f32> {
+    slab(origin, dir, bounds).map(|(enter, _, _)| enter)
+}
+
+/**
+  Distance along `dir` where the ray starting on `origin` crosses the plane through `point` facing `normal`.
+  Rays parallel to the plane or going away from it never cross it.
+*/
+pub fn ray_plane(origin: Vec3, dir: Vec3, point: Vec3, normal: Vec3) -> Option<f32> {
+    let facing = dir.dot(normal);
+
+    if facing.abs() <= f32::EPSILON {
+        return None;
+    }
+
+    let distance = (point - origin).dot(normal) / facing;
+    (distance >= 0.0).then_some(distance)
+}
+
+/**
+  Sweeps `moving` by `offset` against `target`. Returns the fraction of `offset`, from 0 to 1, moved before
+  both touch, with the normal of the `target` face touched. Bounds already overlapping touch at zero, with no
+  normal. Like [`Bounds::intersects`], bounds which would only touch at the very end of `offset` don't.
+*/
+pub fn sweep_aabb(moving: &Bounds, offset: Vec3, target: &Bounds) -> Option<(f32, IVec3)> {
+    // Sweeping a box against a box is the same as casting a ray from its center against the target grown by it
+    let half = moving.size() / 2.0;
+    let expanded = Bounds::new(target.min - half, target.max + half);
+
+    match slab(moving.center(), offset, &expanded) {
+        Some((enter, leave, normal)) if enter < 1.0 && leave > 0.0 => Some((enter, normal)),
+        _ => None,
+    }
+}
+
+/// Cell of a grid crossed by a ray, see [`grid_traversal`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GridHit {
+    pub cell: IVec3,
+    /// Distance along the ray where it entered the cell.
+    pub distance: f32,
+    /// Normal of the cell face the ray entered through. The cell the ray starts on has no normal.
+    pub normal: IVec3,
+}
+
+/// Iterator over the cells crossed by a ray, see [`grid_traversal`].
+pub struct GridTraversal {
+    cell: IVec3,
+    step: IVec3,
+    next: Vec3,
+    delta: Vec3,
+    range: f32,
+    current: Option<GridHit>,
+}
+
+impl Iterator for GridTraversal {
+    type Item = GridHit;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let hit = self.current.take()?;
+
+        let axis = if self.next.x <= self.next.y && self.next.x <= self.next.z {
+            0
+        } else if self.next.y <= self.next.z {
+            1
+        } else {
+            2
+        };
+
+        let distance = self.next[axis];
+        if distance <= self.range {
+            self.cell[axis] += self.step[axis];
+            self.next[axis] += self.delta[axis];
+
+            let mut normal = IVec3::ZERO;
+            normal[axis] = -self.step[axis];
+
+            self.current = Some(GridHit {
+                cell: self.cell,
+                distance,
+                normal,
+            });
+        }
+
+        Some(hit)
+    }
+}
+
+/**
+  Every cell of a grid made of `cell_size` cubes crossed by the ray starting on `origin`, in the order they
+  are crossed, up to `range` away. Unlike stepping along the ray, no cell is ever skipped, not even the ones
+  whose corners are barely touched. Rays without a direction only cross the cell they start on.
+*/
+pub fn grid_traversal(origin: Vec3, dir: Vec3, range: f32, cell_size: f32) -> GridTraversal {
+    let cell = (origin / cell_size).floor().as_ivec3();
+    let mut step = IVec3::ZERO;
+    let mut next = Vec3::splat(f32::INFINITY);
+    let mut delta = Vec3::splat(f32::INFINITY);
+
+    for axis in 0..3 {
+        if dir[axis] == 0.0 {
+            continue;
+        }
+
+        let boundary = if dir[axis] > 0.0 {
+            step[axis] = 1;
+            (cell[axis] + 1) as f32 * cell_size
+        } else {
+            step[axis] = -1;
+            cell[axis] as f32 * cell_size
+        };
+
+        next[axis] = (boundary - origin[axis]) / dir[axis];
+        delta[axis] = cell_size / dir[axis].abs();
+    }
+
+    GridTraversal {
+        cell,
+        step,
+        next,
+        delta,
+        range,
+        current: Some(GridHit {
+            cell,
+            distance: 0.0,
+            normal: IVec3::ZERO,
+        }),
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
 
     #[test]
     fn is_within_cubic_bounds() {
@@ -75,6 +264,13 @@ mod tests {
         assert!(!super::is_within_cubic_bounds((15, 16, 15).into(), 0, 15));
     }
 
+    #[test]
+    fn fnv_hash() {
+        assert_eq!(super::fnv_hash([]), 0xcbf29ce484222325);
+        assert_eq!(super::fnv_hash(*b"a"), 0xaf63dc4c8601ec8c);
+        assert_ne!(super::fnv_hash(*b"ab"), super::fnv_hash(*b"ba"));
+    }
+
     #[test]
     fn floor() {
         let floor = super::floor((14.3, -1.1, -17.0).into());
@@ -122,4 +318,169 @@ mod tests {
             vec![(1, 0, 0).into(), (0, 1, 0).into(), (0, 0, -1).into()]
         );
     }
+
+    #[test]
+    fn ray_aabb() {
+        let bounds = Bounds::new(Vec3::ONE, Vec3::splat(2.0));
+
+        assert_eq!(
+            super::ray_aabb(Vec3::new(0.0, 1.5, 1.5), Vec3::X, &bounds),
+            Some(1.0)
+        );
+        assert_eq!(
+            super::ray_aabb(Vec3::new(3.0, 1.5, 1.5), -Vec3::X, &bounds),
+            Some(1.0)
+        );
+
+        // Diagonal rays enter through the last slab they reach
+        let dir = Vec3::new(1.0, 2.0, 0.0).normalize();
+        let distance = super::ray_aabb(Vec3::new(1.0, 0.0, 1.5), dir, &bounds).unwrap();
+        assert!((distance - 5.0f32.sqrt() / 2.0).abs() < 1e-5);
+
+        // Rays starting inside hit right away
+        assert_eq!(
+            super::ray_aabb(Vec3::splat(1.5), Vec3::Y, &bounds),
+            Some(0.0)
+        );
+
+        // Rays going away, passing by, or parallel and outside miss
+        assert_eq!(
+            super::ray_aabb(Vec3::new(0.0, 1.5, 1.5), -Vec3::X, &bounds),
+            None
+        );
+        assert_eq!(
+            super::ray_aabb(Vec3::new(0.0, 2.5, 1.5), dir, &bounds),
+            None
+        );
+        assert_eq!(
+            super::ray_aabb(Vec3::new(0.0, 0.5, 1.5), Vec3::X, &bounds),
+            None
+        );
+
+        // Without a direction, rays only hit bounds they start in
+        assert_eq!(
+            super::ray_aabb(Vec3::splat(1.5), Vec3::ZERO, &bounds),
+            Some(0.0)
+        );
+        assert_eq!(super::ray_aabb(Vec3::ZERO, Vec3::ZERO, &bounds), None);
+    }
+
+    #[test]
+    fn ray_plane() {
+        let point = Vec3::Y * 2.0;
+
+        assert_eq!(
+            super::ray_plane(Vec3::ZERO, Vec3::Y, point, Vec3::Y),
+            Some(2.0)
+        );
+        assert_eq!(
+            super::ray_plane(Vec3::Y * 4.0, -Vec3::Y, point, Vec3::Y),
+            Some(2.0)
+        );
+        assert_eq!(super::ray_plane(Vec3::ZERO, -Vec3::Y, point, Vec3::Y), None);
+        assert_eq!(super::ray_plane(Vec3::ZERO, Vec3::X, point, Vec3::Y), None);
+    }
+
+    #[test]
+    fn sweep_aabb() {
+        let moving = Bounds::new(Vec3::ZERO, Vec3::ONE);
+        let target = Bounds::new(Vec3::new(3.0, 0.5, 0.0), Vec3::new(4.0, 1.5, 1.0));
+
+        assert_eq!(
+            super::sweep_aabb(&moving, Vec3::X * 4.0, &target),
+            Some((0.5, IVec3::new(-1, 0, 0)))
+        );
+
+        // Falling short, or only touching at the very end, doesn't touch
+        assert_eq!(super::sweep_aabb(&moving, Vec3::X, &target), None);
+        assert_eq!(super::sweep_aabb(&moving, Vec3::X * 2.0, &target), None);
+
+        // Passing over doesn't touch either
+        let above = moving.translated(Vec3::Y * 2.0);
+        assert_eq!(super::sweep_aabb(&above, Vec3::X * 4.0, &target), None);
+
+        // Overlapping bounds touch right away, unless moving away from touching faces
+        let inside = moving.translated(Vec3::X * 2.5);
+        assert_eq!(
+            super::sweep_aabb(&inside, Vec3::X, &target),
+            Some((0.0, IVec3::ZERO))
+        );
+        let touching = moving.translated(Vec3::X * 2.0);
+        assert_eq!(
+            super::sweep_aabb(&touching, Vec3::X, &target),
+            Some((0.0, IVec3::new(-1, 0, 0)))
+        );
+        assert_eq!(super::sweep_aabb(&touching, -Vec3::X, &target), None);
+    }
+
+    fn cells(traversal: GridTraversal) -> Vec<IVec3> {
+        traversal.map(|hit| hit.cell).collect()
+    }
+
+    #[test]
+    fn grid_traversal() {
+        let hits =
+            super::grid_traversal(Vec3::new(0.5, 0.5, 0.5), Vec3::X, 2.0, 1.0).collect::<Vec<_>>();
+        assert_eq!(
+            hits,
+            vec![
+                GridHit {
+                    cell: IVec3::ZERO,
+                    distance: 0.0,
+                    normal: IVec3::ZERO
+                },
+                GridHit {
+                    cell: IVec3::X,
+                    distance: 0.5,
+                    normal: -IVec3::X
+                },
+                GridHit {
+                    cell: IVec3::X * 2,
+                    distance: 1.5,
+                    normal: -IVec3::X
+                },
+            ]
+        );
+
+        // Negative directions enter cells through their positive faces
+        let hits = super::grid_traversal(Vec3::new(0.5, -0.5, 0.5), -Vec3::Y, 1.0, 1.0)
+            .collect::<Vec<_>>();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[1].cell, IVec3::new(0, -2, 0));
+        assert_eq!(hits[1].normal, IVec3::Y);
+
+        // Diagonal rays cross every cell on the way, one axis at a time
+        let dir = Vec3::new(1.0, 1.0, 0.0).normalize();
+        assert_eq!(
+            cells(super::grid_traversal(
+                Vec3::new(0.5, 0.2, 0.5),
+                dir,
+                2.0,
+                1.0
+            )),
+            vec![IVec3::ZERO, IVec3::X, IVec3::new(1, 1, 0)]
+        );
+
+        // Larger cells, like chunks
+        assert_eq!(
+            cells(super::grid_traversal(
+                Vec3::new(-1.0, 0.0, 0.0),
+                Vec3::X,
+                20.0,
+                16.0
+            )),
+            vec![-IVec3::X, IVec3::ZERO, IVec3::X]
+        );
+
+        // Rays without a direction only cross where they start
+        assert_eq!(
+            cells(super::grid_traversal(
+                Vec3::new(-0.5, 3.5, 0.5),
+                Vec3::ZERO,
+                5.0,
+                1.0
+            )),
+            vec![IVec3::new(-1, 3, 0)]
+        );
+    }
 }
@@ -0,0 +1,83 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::{
+    claim::Claims, dimension::Dimensions, generator::WorldGenerator, stats::WorldStats,
+    ticket::Tickets, weather::Weather,
+};
+
+/// Where the world metadata is saved, next to the chunks cache.
+pub const META_PATH: &str = "cache/world.ron";
+
+/**
+  World wide state which doesn't belong to any chunk. Saved as RON, so it can be easily inspected.
+*/
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorldMeta {
+    /// Name given to the world when it was created.
+    pub name: String,
+    /// Generator of the overworld, the dimension the world starts on.
+    pub generator: WorldGenerator,
+    /// Dimensions reached through portals, besides the overworld.
+    pub dimensions: Dimensions,
+    pub weather: Weather,
+    pub claims: Claims,
+    /// Chunk regions kept loaded even without players around.
+    pub tickets: Tickets,
+    pub stats: WorldStats,
+}
+
+impl WorldMeta {
+    /**
+      Loads the metadata from the given path. New worlds, which doesn't have it yet, get the default one.
+    */
+    pub fn load(path: &Path) -> Self {
+        if !path.exists() {
+            return Self::default();
+        }
+
+        let file = std::fs::File::open(path)
+            .unwrap_or_else(|_| panic!("Unable to open file {}", path.display()));
+
+        ron::de::from_reader(file)
+            .unwrap_or_else(|_| panic!("Failed to parse file {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .unwrap_or_else(|_| panic!("Unable to create dir {}", dir.display()));
+        }
+
+        let file = std::fs::File::create(path)
+            .unwrap_or_else(|_| panic!("Unable to write to file {}", path.display()));
+
+        ron::ser::to_writer(file, self)
+            .unwrap_or_else(|_| panic!("Failed to serialize to file {}", path.display()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_load() {
+        let path = std::env::temp_dir().join("eterno_meta_save_load.ron");
+
+        let meta = WorldMeta {
+            weather: Weather {
+                precipitating: true,
+                remaining: 42.0,
+            },
+            ..Default::default()
+        };
+        meta.save(&path);
+
+        assert_eq!(WorldMeta::load(&path), meta);
+
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(WorldMeta::load(&path), WorldMeta::default());
+    }
+}
@@ -0,0 +1,391 @@
+use bevy::prelude::*;
+use rand::Rng;
+use std::ops::Range;
+
+use crate::{
+    chunk,
+    light::LightWorld,
+    voxel::{KindRegistry, MeshShape},
+    world::VoxWorld,
+};
+
+/// How many voxels below a spawn candidate are looked for a floor to stand on.
+const FLOOR_SEARCH: i32 = 16;
+
+/// Height, in voxels, of the room a mob needs to spawn.
+const MOB_HEIGHT: i32 = 2;
+
+/**
+  Where and how many hostile mobs spawn. Mobs spawn on dark spots at a distance band around the player,
+  so they're never seen popping in, and despawn once the player is far away from them.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpawnRules {
+    /// Brightest light, on both block and sky light, mobs spawn on.
+    pub max_light: u8,
+    /// Horizontal distance band, in voxels, around the player where mobs spawn.
+    pub distance: Range<f32>,
+    /// How far, in voxels, above and below the player mobs spawn.
+    pub vertical: i32,
+    /// Mobs further than this, in voxels, from the player are despawned.
+    pub despawn_distance: f32,
+    /// Max number of mobs alive at once.
+    pub budget: usize,
+}
+
+impl Default for SpawnRules {
+    fn default() -> Self {
+        Self {
+            max_light: 7,
+            distance: 24.0..48.0,
+            vertical: 16,
+            despawn_distance: 64.0,
+            budget: 16,
+        }
+    }
+}
+
+impl SpawnRules {
+    /// Random world voxel, on the distance band around `center`, to look for a spawn spot from.
+    pub fn candidate(&self, center: IVec3, rng: &mut impl Rng) -> IVec3 {
+        let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+        let distance = rng.gen_range(self.distance.clone());
+        let offset = Vec2::new(angle.cos(), angle.sin()) * distance;
+
+        center
+            + IVec3::new(
+                offset.x.round() as i32,
+                rng.gen_range(-self.vertical..=self.vertical),
+                offset.y.round() as i32,
+            )
+    }
+
+    /// Whether a mob at `position` is too far away from `center` and must be despawned.
+    pub fn should_despawn(&self, position: Vec3, center: Vec3) -> bool {
+        position.distance(center) > self.despawn_distance
+    }
+}
+
+/// How hostile mobs behave once spawned.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MobBehavior {
+    pub health: f32,
+    /// Walking speed, in voxels per second.
+    pub speed: f32,
+    /// Mobs start chasing the player once it's this close, in voxels.
+    pub aggro_distance: f32,
+    /// Mobs give up chasing once the player is this far away, in voxels.
+    pub lose_distance: f32,
+    /// How close, in voxels, mobs must be to attack.
+    pub reach: f32,
+    /// Seconds between attacks.
+    pub attack_cooldown: f32,
+    pub damage: f32,
+}
+
+impl Default for MobBehavior {
+    fn default() -> Self {
+        Self {
+            health: 10.0,
+            speed: 2.5,
+            aggro_distance: 12.0,
+            lose_distance: 32.0,
+            reach: 1.5,
+            attack_cooldown: 1.0,
+            damage: 2.0,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MobState {
+    /// Stands still, until the player gets close or hits it.
+    #[default]
+    Idle,
+    /// Walks towards the player, attacking it once in reach.
+    Chasing,
+}
+
+/// What a mob does this frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MobAction {
+    Wait,
+    /// Walk towards the player.
+    Walk,
+    Attack,
+}
+
+/**
+  State machine driving a hostile mob. Mobs chase the player once it gets close, and retaliate when hit from
+  afar, giving up only once the player is far away.
+*/
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq)]
+pub struct MobBrain {
+    pub state: MobState,
+    /// Seconds left until the mob can attack again.
+    cooldown: f32,
+}
+
+impl MobBrain {
+    /// The mob was hit, so it chases whoever hit it, no matter how far away.
+    pub fn provoke(&mut self) {
+        self.state = MobState::Chasing;
+    }
+
+    /// Updates the state for `dt` seconds, with the player `distance` voxels away, and picks an action.
+    pub fn think(&mut self, behavior: &MobBehavior, distance: f32, dt: f32) -> MobAction {
+        self.cooldown = (self.cooldown - dt).max(0.0);
+
+        self.state = match self.state {
+            MobState::Idle if distance <= behavior.aggro_distance => MobState::Chasing,
+            MobState::Chasing if distance > behavior.lose_distance => MobState::Idle,
+            state => state,
+        };
+
+        match self.state {
+            MobState::Idle => MobAction::Wait,
+            MobState::Chasing if distance > behavior.reach => MobAction::Walk,
+            MobState::Chasing if self.cooldown > 0.0 => MobAction::Wait,
+            MobState::Chasing => {
+                self.cooldown = behavior.attack_cooldown;
+                MobAction::Attack
+            }
+        }
+    }
+}
+
+/// Brightest light of the given world voxel, be it block or sky light.
+pub fn brightness(light: &LightWorld, position: IVec3) -> u8 {
+    let (local, voxel) = chunk::split_voxel(position);
+    light.light(local, voxel).max(light.sky(local, voxel))
+}
+
+/**
+  Whether a mob can spawn standing on `position`, which must be on top of a solid voxel, with room for the
+  mob and no brighter than `max_light`. Voxels of chunks which aren't loaded are never spawned on.
+*/
+pub fn is_spawnable(
+    world: &VoxWorld,
+    registry: &KindRegistry,
+    light: &LightWorld,
+    position: IVec3,
+    max_light: u8,
+) -> bool {
+    let floor = match world.get_voxel(position - IVec3::Y) {
+        Some(kind) => !kind.is_empty() && registry.mesh_shape(kind) == Some(MeshShape::Cube),
+        None => false,
+    };
+
+    floor
+        && (0..MOB_HEIGHT).all(|y| {
+            world
+                .get_voxel(position + IVec3::Y * y)
+                .is_some_and(|kind| kind.is_empty())
+        })
+        && brightness(light, position) <= max_light
+}
+
+/**
+  First spot a mob can spawn on, looking down from `candidate`, so mobs land on the floor of caves and
+  beneath trees, instead of only spawning on the exact candidate.
+*/
+pub fn find_spot(
+    world: &VoxWorld,
+    registry: &KindRegistry,
+    light: &LightWorld,
+    candidate: IVec3,
+    max_light: u8,
+) -> Option<IVec3> {
+    (0..FLOOR_SEARCH)
+        .map(|depth| candidate - IVec3::Y * depth)
+        .find(|position| {
+            world
+                .get_voxel(*position - IVec3::Y)
+                .is_none_or(|kind| !kind.is_empty())
+        })
+        .filter(|position| is_spawnable(world, registry, light, *position, max_light))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chunk::ChunkKind, heightmap::Heightmap, query, voxel::KindDescription};
+
+    const STONE: u16 = 1;
+    const TORCH: u16 = 2;
+
+    fn description(id: u16, shape: MeshShape, light: u8) -> KindDescription {
+        KindDescription {
+            name: id.to_string(),
+            id,
+            color: (1.0, 1.0, 1.0, 1.0),
+            shape,
+            prop: None,
+            directional: false,
+            light,
+            tick: None,
+            signal: None,
+            toggle: None,
+            hardness: 0.0,
+            tool_tier: 0,
+            block_entity: None,
+            explosive: None,
+            liquid: None,
+            climbable: false,
+            surface: None,
+            foliage: false,
+            tint: None,
+            portal: None,
+        }
+    }
+
+    fn registry() -> KindRegistry {
+        KindRegistry::new(vec![
+            description(STONE, MeshShape::Cube, 0),
+            description(TORCH, MeshShape::Cross, 14),
+        ])
+    }
+
+    /**
+      A stone floor at y = 2, with a closed room below a stone roof at y = 12 covering x < 8. There is a torch on
+      the room at (6, 3, 2) and a stone hanging at (2, 4, 12).
+    */
+    fn world(registry: &KindRegistry) -> (VoxWorld, LightWorld) {
+        let world = VoxWorld::default();
+        let mut heightmap = Heightmap::default();
+        let mut light = LightWorld::default();
+
+        let mut kind = ChunkKind::default();
+        let stone = [
+            ((0, 2, 0), (15, 2, 15)),
+            ((0, 12, 0), (8, 12, 15)),
+            ((8, 3, 0), (8, 11, 15)),
+        ];
+        for (min, max) in stone {
+            for voxel in query::range_inclusive(min.into(), max.into()) {
+                kind.set(voxel, STONE.into());
+            }
+        }
+        kind.set((2, 4, 12).into(), STONE.into());
+        kind.set((6, 3, 2).into(), TORCH.into());
+
+        heightmap.load(IVec3::ZERO, &kind, registry);
+        world.add(IVec3::ZERO, kind);
+        light.load(&world, registry, &heightmap, IVec3::ZERO);
+
+        (world, light)
+    }
+
+    #[test]
+    fn is_spawnable() {
+        let registry = registry();
+        let (world, light) = world(&registry);
+        let spawnable =
+            |x, y, z| super::is_spawnable(&world, &registry, &light, (x, y, z).into(), 7);
+
+        // Dark spot inside the room
+        assert!(spawnable(1, 3, 12));
+
+        // Under the open sky and next to the torch is too bright
+        assert!(!spawnable(10, 3, 2));
+        assert!(!spawnable(5, 3, 2));
+        assert!(super::is_spawnable(
+            &world,
+            &registry,
+            &light,
+            (10, 3, 2).into(),
+            15
+        ));
+
+        // Floating, inside the floor or on top of the torch
+        assert!(!spawnable(1, 5, 12));
+        assert!(!spawnable(1, 2, 12));
+        assert!(!spawnable(6, 4, 2));
+
+        // Not enough room below the hanging stone
+        assert!(!spawnable(2, 3, 12));
+
+        // Chunks which aren't loaded
+        assert!(!spawnable(1, 3, 20));
+    }
+
+    #[test]
+    fn find_spot() {
+        let registry = registry();
+        let (world, light) = world(&registry);
+        let find = |x, y, z| super::find_spot(&world, &registry, &light, (x, y, z).into(), 7);
+
+        assert_eq!(find(1, 9, 12), Some((1, 3, 12).into()));
+        assert_eq!(find(1, 3, 12), Some((1, 3, 12).into()));
+
+        // Stops at the first floor, which is too bright
+        assert_eq!(find(10, 9, 2), None);
+        assert_eq!(find(1, 15, 12), None);
+
+        // Chunks which aren't loaded
+        assert_eq!(find(1, 20, 12), None);
+    }
+
+    #[test]
+    fn candidate() {
+        let rules = SpawnRules::default();
+        let center = IVec3::new(100, 50, -100);
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..100 {
+            let candidate = rules.candidate(center, &mut rng);
+            let horizontal = (candidate - center).as_vec3() * Vec3::new(1.0, 0.0, 1.0);
+
+            assert!(horizontal.length() >= rules.distance.start - 1.0);
+            assert!(horizontal.length() <= rules.distance.end + 1.0);
+            assert!((candidate.y - center.y).abs() <= rules.vertical);
+        }
+    }
+
+    #[test]
+    fn think() {
+        let behavior = MobBehavior::default();
+        let mut brain = MobBrain::default();
+        let far = behavior.aggro_distance + 1.0;
+
+        assert_eq!(brain.think(&behavior, far, 0.1), MobAction::Wait);
+        assert_eq!(brain.state, MobState::Idle);
+
+        // Hitting it from afar makes it retaliate
+        brain.provoke();
+        assert_eq!(brain.think(&behavior, far, 0.1), MobAction::Walk);
+
+        assert_eq!(
+            brain.think(&behavior, behavior.reach, 0.1),
+            MobAction::Attack
+        );
+        assert_eq!(brain.think(&behavior, behavior.reach, 0.1), MobAction::Wait);
+        assert_eq!(
+            brain.think(&behavior, behavior.reach, behavior.attack_cooldown),
+            MobAction::Attack
+        );
+
+        assert_eq!(
+            brain.think(&behavior, behavior.lose_distance + 1.0, 0.1),
+            MobAction::Wait
+        );
+        assert_eq!(brain.state, MobState::Idle);
+
+        // Getting close also starts the chase
+        assert_eq!(
+            brain.think(&behavior, behavior.aggro_distance, 0.1),
+            MobAction::Walk
+        );
+    }
+
+    #[test]
+    fn should_despawn() {
+        let rules = SpawnRules::default();
+
+        assert!(!rules.should_despawn(Vec3::new(10.0, 0.0, 0.0), Vec3::ZERO));
+        assert!(rules.should_despawn(
+            Vec3::new(rules.despawn_distance + 1.0, 0.0, 0.0),
+            Vec3::ZERO
+        ));
+    }
+}
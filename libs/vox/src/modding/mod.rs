@@ -0,0 +1,345 @@
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use std::path::Path;
+
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+/// Voxel events mods can subscribe to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlockEvent {
+    /// Any voxel change applied to the world, made by players, mods or the simulation.
+    Changed,
+    /// A voxel broken by the local player.
+    Broken,
+}
+
+/**
+  What the host sends to mods. Positions are in world voxel coordinates, so mods don't need to know about
+  chunks.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModEvent {
+    Changed {
+        position: IVec3,
+        kind: u16,
+    },
+    Broken {
+        position: IVec3,
+        kind: u16,
+    },
+    /// Reply to [`ModCall::GetVoxel`]. The kind is none when the voxel isn't loaded.
+    Voxel {
+        position: IVec3,
+        kind: Option<u16>,
+    },
+    /// A console command the mod registered was run.
+    Command {
+        name: String,
+        args: Vec<String>,
+    },
+}
+
+/// What mods ask the host to do. Calls are applied in order, after the mod handles an event.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModCall {
+    /// Queries a voxel, which is replied with [`ModEvent::Voxel`] on the next frame.
+    GetVoxel(IVec3),
+    SetVoxel {
+        position: IVec3,
+        kind: u16,
+    },
+    Subscribe(BlockEvent),
+    /// Registers a console command.
+    RegisterCommand(String),
+    /// Prints a line on the console.
+    Print(String),
+}
+
+/**
+  A loaded mod. Mods only talk to the engine through [`ModEvent`] and [`ModCall`], so the same API can be
+  exposed by any sandboxed runtime, like a WASM interpreter.
+*/
+pub trait ModRuntime: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Runs once, when the mod is added, usually to subscribe to events and register commands.
+    fn start(&mut self) -> Vec<ModCall>;
+
+    fn handle(&mut self, event: &ModEvent) -> Vec<ModCall>;
+}
+
+/// Creates a mod with the given name, which is its file name without extension, from the contents of its file.
+pub type ModLoader = fn(&str, &[u8]) -> Result<Box<dyn ModRuntime>, String>;
+
+struct LoadedMod {
+    runtime: Box<dyn ModRuntime>,
+    subscriptions: HashSet<BlockEvent>,
+}
+
+/**
+  Loaded mods, and the loaders of each mod file extension. Only `wasm` files are loaded by default, and only
+  when the `wasm` feature is enabled. Other runtimes can be added with [`Mods::register_loader`].
+*/
+pub struct Mods {
+    mods: Vec<LoadedMod>,
+    loaders: HashMap<String, ModLoader>,
+    /// Command names, and the index of the mod which registered them.
+    commands: HashMap<String, usize>,
+    /// Events for the next frame, like voxel query replies.
+    pending: Vec<(usize, ModEvent)>,
+}
+
+impl Default for Mods {
+    fn default() -> Self {
+        #[allow(unused_mut)]
+        let mut mods = Self {
+            mods: vec![],
+            loaders: Default::default(),
+            commands: Default::default(),
+            pending: vec![],
+        };
+
+        #[cfg(feature = "wasm")]
+        mods.register_loader("wasm", wasm::load);
+
+        mods
+    }
+}
+
+impl Mods {
+    /// Loads files with the given extension, like `wasm`, with the given loader.
+    pub fn register_loader(&mut self, extension: &str, loader: ModLoader) {
+        self.loaders.insert(extension.to_string(), loader);
+    }
+
+    /// Adds and starts a mod. Returns the calls it made which must be applied by the host.
+    pub fn add(&mut self, mut runtime: Box<dyn ModRuntime>) -> Vec<(usize, ModCall)> {
+        let idx = self.mods.len();
+        let calls = runtime.start();
+
+        self.mods.push(LoadedMod {
+            runtime,
+            subscriptions: Default::default(),
+        });
+
+        self.register(idx, calls)
+    }
+
+    /**
+      Loads every mod of the given dir which has a loader for its extension. Mods which fail to load are
+      skipped.
+    */
+    pub fn load_dir(&mut self, dir: &Path) -> Vec<(usize, ModCall)> {
+        let mut paths = match std::fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .collect::<Vec<_>>(),
+            Err(_) => return vec![],
+        };
+
+        // Mods are started in a stable order, so they override each other's commands the same way every time
+        paths.sort();
+
+        let mut calls = vec![];
+
+        for path in paths {
+            let loader = match path
+                .extension()
+                .and_then(|ext| self.loaders.get(ext.to_string_lossy().as_ref()))
+            {
+                Some(loader) => *loader,
+                None => {
+                    warn!("No mod runtime for {}, skipping it", path.display());
+                    continue;
+                }
+            };
+
+            let data = std::fs::read(&path)
+                .unwrap_or_else(|_| panic!("Unable to read mod file {}", path.display()));
+
+            let name = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy())
+                .unwrap_or_default();
+
+            match loader(&name, &data) {
+                Ok(runtime) => {
+                    info!("Loaded mod {} from {}", runtime.name(), path.display());
+                    calls.extend(self.add(runtime));
+                }
+                Err(err) => error!("Failed to load mod {}: {}", path.display(), err),
+            }
+        }
+
+        calls
+    }
+
+    /// Sends a voxel event to the mods subscribed to it.
+    pub fn dispatch(&mut self, event: BlockEvent, payload: ModEvent) -> Vec<(usize, ModCall)> {
+        let subscribers = self
+            .mods
+            .iter()
+            .enumerate()
+            .filter(|(_, loaded)| loaded.subscriptions.contains(&event))
+            .map(|(idx, _)| idx)
+            .collect::<Vec<_>>();
+
+        subscribers
+            .into_iter()
+            .flat_map(|idx| self.send(idx, &payload))
+            .collect()
+    }
+
+    /// Runs a console command on the mod which registered it.
+    pub fn run_command(
+        &mut self,
+        name: &str,
+        args: &[String],
+    ) -> Result<Vec<(usize, ModCall)>, String> {
+        let idx = match self.commands.get(name) {
+            Some(idx) => *idx,
+            None => return Err(format!("Unknown mod command: {}", name)),
+        };
+
+        Ok(self.send(
+            idx,
+            &ModEvent::Command {
+                name: name.to_string(),
+                args: args.to_vec(),
+            },
+        ))
+    }
+
+    /// Queues an event to be sent to the given mod on the next [`Mods::flush`], like a voxel query reply.
+    pub fn reply(&mut self, idx: usize, event: ModEvent) {
+        self.pending.push((idx, event));
+    }
+
+    /// Sends the events queued on the last frame.
+    pub fn flush(&mut self) -> Vec<(usize, ModCall)> {
+        std::mem::take(&mut self.pending)
+            .into_iter()
+            .flat_map(|(idx, event)| self.send(idx, &event))
+            .collect()
+    }
+
+    fn send(&mut self, idx: usize, event: &ModEvent) -> Vec<(usize, ModCall)> {
+        let calls = match self.mods.get_mut(idx) {
+            Some(loaded) => loaded.runtime.handle(event),
+            None => return vec![],
+        };
+
+        self.register(idx, calls)
+    }
+
+    /// Handles subscriptions and command registrations, returning the calls which must be applied by the host.
+    fn register(&mut self, idx: usize, calls: Vec<ModCall>) -> Vec<(usize, ModCall)> {
+        calls
+            .into_iter()
+            .filter_map(|call| match call {
+                ModCall::Subscribe(event) => {
+                    self.mods[idx].subscriptions.insert(event);
+                    None
+                }
+                ModCall::RegisterCommand(name) => {
+                    self.commands.insert(name, idx);
+                    None
+                }
+                call => Some((idx, call)),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Replaces broken stone with dirt, prints its arguments on `echo` and reports unloaded voxels.
+    struct TestMod;
+
+    impl ModRuntime for TestMod {
+        fn name(&self) -> &str {
+            "test"
+        }
+
+        fn start(&mut self) -> Vec<ModCall> {
+            vec![
+                ModCall::Subscribe(BlockEvent::Broken),
+                ModCall::RegisterCommand("echo".to_string()),
+                ModCall::Print("started".to_string()),
+            ]
+        }
+
+        fn handle(&mut self, event: &ModEvent) -> Vec<ModCall> {
+            match event {
+                ModEvent::Broken { position, kind: 1 } => vec![ModCall::SetVoxel {
+                    position: *position,
+                    kind: 7,
+                }],
+                ModEvent::Command { args, .. } => vec![ModCall::Print(args.join(" "))],
+                ModEvent::Voxel { kind: None, .. } => vec![ModCall::Print("unloaded".to_string())],
+                _ => vec![],
+            }
+        }
+    }
+
+    #[test]
+    fn mods() {
+        let mut mods = Mods::default();
+
+        // Registrations are kept by the host, the remaining calls must be applied
+        assert_eq!(
+            mods.add(Box::new(TestMod)),
+            vec![(0, ModCall::Print("started".to_string()))]
+        );
+
+        let position = IVec3::new(1, -2, 3);
+        assert!(mods
+            .dispatch(BlockEvent::Changed, ModEvent::Changed { position, kind: 1 })
+            .is_empty());
+        assert_eq!(
+            mods.dispatch(BlockEvent::Broken, ModEvent::Broken { position, kind: 1 }),
+            vec![(0, ModCall::SetVoxel { position, kind: 7 })]
+        );
+
+        assert_eq!(
+            mods.run_command("echo", &["hi".to_string()]),
+            Ok(vec![(0, ModCall::Print("hi".to_string()))])
+        );
+        assert!(mods.run_command("nope", &[]).is_err());
+
+        // Replies are sent on the next flush
+        mods.reply(
+            0,
+            ModEvent::Voxel {
+                position,
+                kind: None,
+            },
+        );
+        assert_eq!(
+            mods.flush(),
+            vec![(0, ModCall::Print("unloaded".to_string()))]
+        );
+        assert!(mods.flush().is_empty());
+    }
+
+    #[test]
+    fn load_dir() {
+        let dir = std::env::temp_dir().join("eterno_mods_load_dir");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.test"), b"").unwrap();
+        std::fs::write(dir.join("b.wasm"), b"").unwrap();
+
+        let mut mods = Mods::default();
+        mods.register_loader("test", |_, _| Ok(Box::new(TestMod)));
+
+        // Only files with a registered loader are loaded
+        assert_eq!(mods.load_dir(&dir).len(), 1);
+        assert_eq!(mods.mods.len(), 1);
+        assert!(mods.load_dir(&dir.join("missing")).is_empty());
+    }
+}
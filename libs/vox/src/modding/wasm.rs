@@ -0,0 +1,317 @@
+use bevy::prelude::*;
+use wasmi::{
+    core::Trap, errors::LinkerError, AsContextMut, Caller, Config, Engine, Extern, Instance,
+    Linker, Module, Store, WasmParams,
+};
+
+use super::{BlockEvent, ModCall, ModEvent, ModRuntime};
+
+/// Instructions, roughly, a mod can run while handling a single event, so a stuck mod can't hang the game.
+const FUEL_PER_CALL: u64 = 10_000_000;
+
+/// Kind sent to `on_voxel` when the voxel isn't loaded.
+const UNLOADED: i32 = -1;
+
+/**
+  A mod compiled to WebAssembly, run by an interpreter. Mods only see their own memory, and talk to the engine
+  through the following functions, all of them optional.
+
+  Imported from the `env` module, each one queuing a [`ModCall`]:
+
+  - `get_voxel(x, y, z)`
+  - `set_voxel(x, y, z, kind)`
+  - `subscribe(event)`: `0` for [`BlockEvent::Changed`] and `1` for [`BlockEvent::Broken`].
+  - `register_command(ptr, len)` and `print(ptr, len)`: UTF-8 text at the given place of the `memory` export.
+
+  Exported, each one receiving a [`ModEvent`]:
+
+  - `on_start()`: runs once, when the mod is added.
+  - `on_voxel(event, x, y, z, kind)`: `0` for changed voxels, `1` for broken ones and `2` for replies of
+    `get_voxel`, with the kind being `-1` when the voxel isn't loaded.
+  - `alloc(len) -> ptr` and `on_command(ptr, len)`: the command line, with the name and the arguments split by
+    spaces, is written where `alloc` tells before `on_command` runs.
+*/
+pub struct WasmMod {
+    name: String,
+    store: Store<Vec<ModCall>>,
+    instance: Instance,
+}
+
+impl WasmMod {
+    pub fn new(name: &str, data: &[u8]) -> Result<Self, String> {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, data).map_err(|err| err.to_string())?;
+        let mut store = Store::new(&engine, vec![]);
+
+        let instance = linker(&engine)
+            .map_err(|err| err.to_string())?
+            .instantiate(&mut store, &module)
+            .and_then(|instance| instance.start(&mut store))
+            .map_err(|err| err.to_string())?;
+
+        Ok(Self {
+            name: name.to_string(),
+            store,
+            instance,
+        })
+    }
+
+    /**
+      Calls the given export, if the mod has it, returning the calls the mod made meanwhile. Mods which trap,
+      or run out of fuel, have their calls dropped.
+    */
+    fn call<P: WasmParams>(&mut self, export: &str, params: P) -> Vec<ModCall> {
+        let func = match self.instance.get_typed_func::<P, ()>(&self.store, export) {
+            Ok(func) => func,
+            Err(_) => return vec![],
+        };
+
+        refuel(&mut self.store);
+
+        match func.call(&mut self.store, params) {
+            Ok(()) => std::mem::take(self.store.data_mut()),
+            Err(err) => {
+                error!("Mod {} failed on {}: {}", self.name, export, err);
+                self.store.data_mut().clear();
+                vec![]
+            }
+        }
+    }
+
+    /// Writes the given text where the mod `alloc` export tells, returning where it is and its length.
+    fn write(&mut self, text: &str) -> Option<(i32, i32)> {
+        let len = i32::try_from(text.len()).ok()?;
+
+        let alloc = self
+            .instance
+            .get_typed_func::<i32, i32>(&self.store, "alloc")
+            .ok()?;
+        let memory = self.instance.get_memory(&self.store, "memory")?;
+
+        refuel(&mut self.store);
+
+        let ptr = alloc.call(&mut self.store, len).ok()?;
+        memory
+            .write(&mut self.store, usize::try_from(ptr).ok()?, text.as_bytes())
+            .ok()?;
+
+        Some((ptr, len))
+    }
+}
+
+impl ModRuntime for WasmMod {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn start(&mut self) -> Vec<ModCall> {
+        self.call("on_start", ())
+    }
+
+    fn handle(&mut self, event: &ModEvent) -> Vec<ModCall> {
+        let (event, position, kind) = match event {
+            ModEvent::Changed { position, kind } => (0, *position, i32::from(*kind)),
+            ModEvent::Broken { position, kind } => (1, *position, i32::from(*kind)),
+            ModEvent::Voxel { position, kind } => (2, *position, kind.map_or(UNLOADED, i32::from)),
+            ModEvent::Command { name, args } => {
+                let line = std::iter::once(name)
+                    .chain(args)
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(" ");
+
+                return match self.write(&line) {
+                    Some(text) => self.call("on_command", text),
+                    None => {
+                        error!("Mod {} is unable to receive command {}", self.name, name);
+                        vec![]
+                    }
+                };
+            }
+        };
+
+        self.call(
+            "on_voxel",
+            (event, position.x, position.y, position.z, kind),
+        )
+    }
+}
+
+/// Loads a WASM mod. This is the [`super::ModLoader`] of `wasm` files.
+pub fn load(name: &str, data: &[u8]) -> Result<Box<dyn ModRuntime>, String> {
+    Ok(Box::new(WasmMod::new(name, data)?))
+}
+
+/// Leaves the mod with exactly [`FUEL_PER_CALL`], so fuel left by a call isn't saved for the next one.
+fn refuel(store: &mut Store<Vec<ModCall>>) {
+    let remaining = store
+        .consume_fuel(0)
+        .expect("Mods must be run with fuel metering");
+
+    store
+        .consume_fuel(remaining)
+        .and_then(|_| store.add_fuel(FUEL_PER_CALL))
+        .expect("Mods must be run with fuel metering");
+}
+
+/// Reads UTF-8 text from the memory of the calling mod.
+fn read_text(caller: &mut Caller<Vec<ModCall>>, ptr: i32, len: i32) -> Result<String, Trap> {
+    let memory = caller
+        .get_export("memory")
+        .and_then(Extern::into_memory)
+        .ok_or_else(|| Trap::new("Mod must export its memory to send text"))?;
+
+    let (ptr, len) = match (usize::try_from(ptr), usize::try_from(len)) {
+        (Ok(ptr), Ok(len)) => (ptr, len),
+        _ => return Err(Trap::new("Text out of mod memory")),
+    };
+
+    let mut bytes = vec![0; len];
+    memory
+        .read(caller.as_context_mut(), ptr, &mut bytes)
+        .map_err(|_| Trap::new("Text out of mod memory"))?;
+
+    String::from_utf8(bytes).map_err(|_| Trap::new("Text must be UTF-8"))
+}
+
+/// Functions imported by mods, which queue their calls on the store.
+fn linker(engine: &Engine) -> Result<Linker<Vec<ModCall>>, LinkerError> {
+    let mut linker = Linker::new(engine);
+
+    linker.func_wrap(
+        "env",
+        "get_voxel",
+        |mut caller: Caller<Vec<ModCall>>, x: i32, y: i32, z: i32| {
+            caller
+                .data_mut()
+                .push(ModCall::GetVoxel(IVec3::new(x, y, z)));
+        },
+    )?;
+
+    linker.func_wrap(
+        "env",
+        "set_voxel",
+        |mut caller: Caller<Vec<ModCall>>, x: i32, y: i32, z: i32, kind: i32| {
+            let kind = u16::try_from(kind).map_err(|_| Trap::new("Invalid voxel kind"))?;
+
+            caller.data_mut().push(ModCall::SetVoxel {
+                position: IVec3::new(x, y, z),
+                kind,
+            });
+
+            Ok(())
+        },
+    )?;
+
+    linker.func_wrap(
+        "env",
+        "subscribe",
+        |mut caller: Caller<Vec<ModCall>>, event: i32| {
+            let event = match event {
+                0 => BlockEvent::Changed,
+                1 => BlockEvent::Broken,
+                _ => return Err(Trap::new("Unknown block event")),
+            };
+
+            caller.data_mut().push(ModCall::Subscribe(event));
+            Ok(())
+        },
+    )?;
+
+    linker.func_wrap(
+        "env",
+        "register_command",
+        |mut caller: Caller<Vec<ModCall>>, ptr: i32, len: i32| {
+            let name = read_text(&mut caller, ptr, len)?;
+            caller.data_mut().push(ModCall::RegisterCommand(name));
+            Ok(())
+        },
+    )?;
+
+    linker.func_wrap(
+        "env",
+        "print",
+        |mut caller: Caller<Vec<ModCall>>, ptr: i32, len: i32| {
+            let text = read_text(&mut caller, ptr, len)?;
+            caller.data_mut().push(ModCall::Print(text));
+            Ok(())
+        },
+    )?;
+
+    Ok(linker)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modding::Mods;
+
+    /**
+      Subscribes to broken voxels, replacing broken stone with dirt, and prints every command it registered,
+      which is only `echo`, with its arguments.
+    */
+    #[rustfmt::skip]
+    const MODULE: &[u8] = &[
+        // Header
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+        // Types
+        0x01, 0x21, 0x06, 0x60, 0x01, 0x7f, 0x00, 0x60, 0x02, 0x7f, 0x7f, 0x00, 0x60, 0x04, 0x7f, 0x7f,
+        0x7f, 0x7f, 0x00, 0x60, 0x00, 0x00, 0x60, 0x05, 0x7f, 0x7f, 0x7f, 0x7f, 0x7f, 0x00, 0x60, 0x01,
+        0x7f, 0x01, 0x7f,
+        // Imports: subscribe, set_voxel, register_command and print
+        0x02, 0x44, 0x04, 0x03, 0x65, 0x6e, 0x76, 0x09, 0x73, 0x75, 0x62, 0x73, 0x63, 0x72, 0x69, 0x62,
+        0x65, 0x00, 0x00, 0x03, 0x65, 0x6e, 0x76, 0x09, 0x73, 0x65, 0x74, 0x5f, 0x76, 0x6f, 0x78, 0x65,
+        0x6c, 0x00, 0x02, 0x03, 0x65, 0x6e, 0x76, 0x10, 0x72, 0x65, 0x67, 0x69, 0x73, 0x74, 0x65, 0x72,
+        0x5f, 0x63, 0x6f, 0x6d, 0x6d, 0x61, 0x6e, 0x64, 0x00, 0x01, 0x03, 0x65, 0x6e, 0x76, 0x05, 0x70,
+        0x72, 0x69, 0x6e, 0x74, 0x00, 0x01,
+        // Functions
+        0x03, 0x05, 0x04, 0x03, 0x04, 0x05, 0x01,
+        // Memory
+        0x05, 0x03, 0x01, 0x00, 0x01,
+        // Exports: memory, on_start, on_voxel, alloc and on_command
+        0x07, 0x35, 0x05, 0x06, 0x6d, 0x65, 0x6d, 0x6f, 0x72, 0x79, 0x02, 0x00, 0x08, 0x6f, 0x6e, 0x5f,
+        0x73, 0x74, 0x61, 0x72, 0x74, 0x00, 0x04, 0x08, 0x6f, 0x6e, 0x5f, 0x76, 0x6f, 0x78, 0x65, 0x6c,
+        0x00, 0x05, 0x05, 0x61, 0x6c, 0x6c, 0x6f, 0x63, 0x00, 0x06, 0x0a, 0x6f, 0x6e, 0x5f, 0x63, 0x6f,
+        0x6d, 0x6d, 0x61, 0x6e, 0x64, 0x00, 0x07,
+        // Code
+        0x0a, 0x3e, 0x04, 0x12, 0x00, 0x41, 0x01, 0x10, 0x00, 0x41, 0x00, 0x41, 0x07, 0x10, 0x03, 0x41,
+        0x07, 0x41, 0x04, 0x10, 0x02, 0x0b, 0x1a, 0x00, 0x20, 0x00, 0x41, 0x01, 0x46, 0x20, 0x04, 0x41,
+        0x01, 0x46, 0x71, 0x04, 0x40, 0x20, 0x01, 0x20, 0x02, 0x20, 0x03, 0x41, 0x07, 0x10, 0x01, 0x0b,
+        0x0b, 0x05, 0x00, 0x41, 0x80, 0x08, 0x0b, 0x08, 0x00, 0x20, 0x00, 0x20, 0x01, 0x10, 0x03, 0x0b,
+        // Data
+        0x0b, 0x11, 0x01, 0x00, 0x41, 0x00, 0x0b, 0x0b, 0x73, 0x74, 0x61, 0x72, 0x74, 0x65, 0x64, 0x65,
+        0x63, 0x68, 0x6f,
+    ];
+
+    #[test]
+    fn wasm_mod() {
+        let mut mods = Mods::default();
+
+        assert_eq!(
+            mods.add(load("test", MODULE).unwrap()),
+            vec![(0, ModCall::Print("started".to_string()))]
+        );
+
+        let position = IVec3::new(1, -2, 3);
+        assert!(mods
+            .dispatch(BlockEvent::Changed, ModEvent::Changed { position, kind: 1 })
+            .is_empty());
+        assert_eq!(
+            mods.dispatch(BlockEvent::Broken, ModEvent::Broken { position, kind: 1 }),
+            vec![(0, ModCall::SetVoxel { position, kind: 7 })]
+        );
+        assert!(mods
+            .dispatch(BlockEvent::Broken, ModEvent::Broken { position, kind: 2 })
+            .is_empty());
+
+        assert_eq!(
+            mods.run_command("echo", &["hi".to_string()]),
+            Ok(vec![(0, ModCall::Print("echo hi".to_string()))])
+        );
+
+        assert!(load("empty", &[]).is_err());
+    }
+}
@@ -0,0 +1,457 @@
+use ::noise::{Fbm, MultiFractal, NoiseFn, Simplex};
+use bracket_noise::prelude::*;
+use bracket_random::prelude::RandomNumberGenerator;
+
+/// Points sampled together by [`BatchSimplex`]. Eight `f32`s fill a 256 bits register.
+const LANES: usize = 8;
+
+#[allow(clippy::excessive_precision)]
+const SQRT3: f32 = 1.7320508075688772935274463415059;
+/// Skews points into the simplex grid.
+const F2: f32 = 0.5 * (SQRT3 - 1.0);
+/// Unskews simplex cells back into points.
+const G2: f32 = (3.0 - SQRT3) / 6.0;
+
+/// Gradients of the simplex cell corners, the same ones as FastNoise.
+const GRAD_X: [f32; 12] = [1.0, -1., 1., -1., 1., -1., 1., -1., 0., 0., 0., 0.];
+const GRAD_Y: [f32; 12] = [1., 1., -1., -1., 0., 0., 0., 0., 1., -1., 1., -1.];
+
+/**
+  Two dimensional noise sampled by world generation, like terrain heights and rivers. Generation speed gates
+  how fast chunks stream in, so sources are kept behind this trait to be swapped for faster implementations
+  without touching the generator.
+*/
+pub trait NoiseSource: Send + Sync {
+    /// Noise at the given point, from -1.0 to 1.0.
+    fn get(&self, x: f32, z: f32) -> f32;
+
+    /**
+      Noise of the `size` by `size` points starting at the given one, one unit apart, listed by x, then z.
+      Sources which sample many points faster than one at a time, like SIMD ones, should override it.
+    */
+    fn fill(&self, x: f32, z: f32, size: usize) -> Vec<f32> {
+        (0..size)
+            .flat_map(|dx| (0..size).map(move |dz| (dx as f32, dz as f32)))
+            .map(|(dx, dz)| self.get(x + dx, z + dz))
+            .collect()
+    }
+}
+
+impl NoiseSource for FastNoise {
+    fn get(&self, x: f32, z: f32) -> f32 {
+        self.get_noise(x, z)
+    }
+}
+
+impl NoiseSource for Fbm<Simplex> {
+    fn get(&self, x: f32, z: f32) -> f32 {
+        NoiseFn::get(self, [x as f64, z as f64]) as f32
+    }
+}
+
+/**
+  Simplex noise, summed over octaves of growing frequency and shrinking amplitude. The `Bracket` and `Batch`
+  backends sample the exact same noise out of it, so worlds look alike whichever one generates them, while
+  `NoiseRs` samples its own noise, within the same range.
+*/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fractal {
+    pub seed: u64,
+    pub frequency: f32,
+    /// Octaves summed. A single octave is plain simplex noise.
+    pub octaves: i32,
+    /// How much the amplitude changes on each octave.
+    pub gain: f32,
+    /// How much the frequency changes on each octave.
+    pub lacunarity: f32,
+}
+
+impl Fractal {
+    /// Plain simplex noise, with a single octave.
+    pub fn simplex(seed: u64, frequency: f32) -> Self {
+        Self {
+            seed,
+            frequency,
+            octaves: 1,
+            gain: 0.5,
+            lacunarity: 2.0,
+        }
+    }
+}
+
+/// Implementation which noise sources are built with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NoiseBackend {
+    /// The `bracket-noise` port of FastNoise, sampling one point at a time. Kept as a fallback.
+    Bracket,
+    /// [`BatchSimplex`], sampling many points at once. It samples the exact same noise as `Bracket`.
+    #[default]
+    Batch,
+    /**
+      Simplex noise of the `noise` crate, sampling one point at a time in double precision. Its permutation
+      tables and octave weights aren't the FastNoise ones, so worlds generated with it look different.
+    */
+    NoiseRs,
+}
+
+impl NoiseBackend {
+    pub fn build(self, fractal: Fractal) -> Box<dyn NoiseSource> {
+        match self {
+            NoiseBackend::Bracket => {
+                let mut noise = FastNoise::seeded(fractal.seed);
+                noise.set_frequency(fractal.frequency);
+
+                if fractal.octaves > 1 {
+                    noise.set_noise_type(NoiseType::SimplexFractal);
+                    noise.set_fractal_type(FractalType::FBM);
+                    noise.set_fractal_octaves(fractal.octaves);
+                    noise.set_fractal_gain(fractal.gain);
+                    noise.set_fractal_lacunarity(fractal.lacunarity);
+                } else {
+                    noise.set_noise_type(NoiseType::Simplex);
+                }
+
+                Box::new(noise)
+            }
+            NoiseBackend::Batch => Box::new(BatchSimplex::new(fractal)),
+            NoiseBackend::NoiseRs => {
+                // Seeds are 32 bits there, so the high half is folded into the low one
+                let seed = (fractal.seed ^ (fractal.seed >> 32)) as u32;
+
+                Box::new(
+                    Fbm::<Simplex>::new(seed)
+                        .set_octaves(fractal.octaves.max(1) as usize)
+                        .set_frequency(fractal.frequency as f64)
+                        .set_persistence(fractal.gain as f64)
+                        .set_lacunarity(fractal.lacunarity as f64),
+                )
+            }
+        }
+    }
+}
+
+fn fast_floor(f: f32) -> i32 {
+    if f >= 0.0 {
+        f as i32
+    } else {
+        f as i32 - 1
+    }
+}
+
+/**
+  Simplex noise which samples [`LANES`] points at a time. Arithmetic runs on all lanes at once, in loops the
+  compiler turns into SIMD instructions, and only gradient lookups, which gather from the permutation
+  tables, are done lane by lane.
+
+  It's a port of the FastNoise simplex, from `bracket-noise`, down to its permutation tables and the order of
+  float operations, so both sample the exact same values for the same [`Fractal`].
+*/
+pub struct BatchSimplex {
+    fractal: Fractal,
+    perm: [u8; 512],
+    perm12: [u8; 512],
+    /// Scales the octaves sum back to the -1.0 to 1.0 range.
+    bounding: f32,
+}
+
+impl BatchSimplex {
+    pub fn new(fractal: Fractal) -> Self {
+        // Shuffled the same way as FastNoise::set_seed
+        let mut rng = RandomNumberGenerator::seeded(fractal.seed);
+        let mut perm = [0; 512];
+        let mut perm12 = [0; 512];
+
+        for (i, value) in perm.iter_mut().take(256).enumerate() {
+            *value = i as u8;
+        }
+
+        for j in 0..256 {
+            let k = (rng.next_u64() % (256 - j as u64)) as usize + j;
+            let l = perm[j];
+            perm[j] = perm[k];
+            perm[j + 256] = perm[k];
+            perm[k] = l;
+            perm12[j] = perm[j] % 12;
+            perm12[j + 256] = perm[j] % 12;
+        }
+
+        let mut amp = fractal.gain;
+        let mut amp_fractal = 1.0;
+        for _ in 0..fractal.octaves {
+            amp_fractal += amp;
+            amp *= fractal.gain;
+        }
+
+        Self {
+            fractal,
+            perm,
+            perm12,
+            bounding: 1.0 / amp_fractal,
+        }
+    }
+
+    /// Gradient index of the given simplex corner.
+    fn gradient(&self, offset: u8, i: i32, j: i32) -> usize {
+        let row = self.perm[(j & 0xff) as usize + offset as usize] as usize;
+        self.perm12[(i & 0xff) as usize + row] as usize
+    }
+
+    /// Contribution of a simplex corner to each lane, given the lane offsets to the corner.
+    fn corner(
+        &self,
+        offset: u8,
+        i: &[i32; LANES],
+        j: &[i32; LANES],
+        x: &[f32; LANES],
+        y: &[f32; LANES],
+    ) -> [f32; LANES] {
+        let mut gx = [0.0; LANES];
+        let mut gy = [0.0; LANES];
+        for lane in 0..LANES {
+            let gradient = self.gradient(offset, i[lane], j[lane]);
+            gx[lane] = GRAD_X[gradient];
+            gy[lane] = GRAD_Y[gradient];
+        }
+
+        let mut n = [0.0; LANES];
+        for lane in 0..LANES {
+            let t = 0.5 - x[lane] * x[lane] - y[lane] * y[lane];
+            let t = t.max(0.0) * t.max(0.0);
+            n[lane] = t * t * (x[lane] * gx[lane] + y[lane] * gy[lane]);
+        }
+
+        n
+    }
+
+    /// Single octave of simplex noise on each lane.
+    fn simplex(&self, offset: u8, x: &[f32; LANES], y: &[f32; LANES]) -> [f32; LANES] {
+        let mut i = [0; LANES];
+        let mut j = [0; LANES];
+        let mut x0 = [0.0; LANES];
+        let mut y0 = [0.0; LANES];
+
+        for lane in 0..LANES {
+            let t = (x[lane] + y[lane]) * F2;
+            i[lane] = fast_floor(x[lane] + t);
+            j[lane] = fast_floor(y[lane] + t);
+
+            let t = (i[lane] + j[lane]) as f32 * G2;
+            x0[lane] = x[lane] - (i[lane] as f32 - t);
+            y0[lane] = y[lane] - (j[lane] as f32 - t);
+        }
+
+        // Second corner is one step along x or z, depending on which simplex of the cell the point is on
+        let mut i1 = [0; LANES];
+        let mut j1 = [0; LANES];
+        let mut x1 = [0.0; LANES];
+        let mut y1 = [0.0; LANES];
+        let mut i2 = [0; LANES];
+        let mut j2 = [0; LANES];
+        let mut x2 = [0.0; LANES];
+        let mut y2 = [0.0; LANES];
+
+        for lane in 0..LANES {
+            let step = (x0[lane] > y0[lane]) as i32;
+            i1[lane] = i[lane] + step;
+            j1[lane] = j[lane] + 1 - step;
+            x1[lane] = x0[lane] - step as f32 + G2;
+            y1[lane] = y0[lane] - (1 - step) as f32 + G2;
+
+            i2[lane] = i[lane] + 1;
+            j2[lane] = j[lane] + 1;
+            x2[lane] = x0[lane] - 1.0 + 2.0 * G2;
+            y2[lane] = y0[lane] - 1.0 + 2.0 * G2;
+        }
+
+        let n0 = self.corner(offset, &i, &j, &x0, &y0);
+        let n1 = self.corner(offset, &i1, &j1, &x1, &y1);
+        let n2 = self.corner(offset, &i2, &j2, &x2, &y2);
+
+        let mut n = [0.0; LANES];
+        for lane in 0..LANES {
+            n[lane] = 70.0 * (n0[lane] + n1[lane] + n2[lane]);
+        }
+
+        n
+    }
+
+    /// Noise of the given points, summing all octaves.
+    fn sample(&self, mut x: [f32; LANES], mut y: [f32; LANES]) -> [f32; LANES] {
+        let Fractal {
+            frequency,
+            octaves,
+            gain,
+            lacunarity,
+            ..
+        } = self.fractal;
+
+        for lane in 0..LANES {
+            x[lane] *= frequency;
+            y[lane] *= frequency;
+        }
+
+        // Single octaves are plain simplex, which FastNoise samples without the fractal offset nor bounding
+        if octaves <= 1 {
+            return self.simplex(0, &x, &y);
+        }
+
+        let mut sum = self.simplex(self.perm[0], &x, &y);
+        let mut amp = 1.0;
+
+        for octave in 1..octaves {
+            for lane in 0..LANES {
+                x[lane] *= lacunarity;
+                y[lane] *= lacunarity;
+            }
+
+            amp *= gain;
+            let n = self.simplex(self.perm[octave as usize], &x, &y);
+            for lane in 0..LANES {
+                sum[lane] += n[lane] * amp;
+            }
+        }
+
+        for value in sum.iter_mut() {
+            *value *= self.bounding;
+        }
+
+        sum
+    }
+}
+
+impl NoiseSource for BatchSimplex {
+    /// Samples a single point on every lane, so it's slower than `Bracket`. Prefer [`NoiseSource::fill`].
+    fn get(&self, x: f32, z: f32) -> f32 {
+        self.sample([x; LANES], [z; LANES])[0]
+    }
+
+    fn fill(&self, x: f32, z: f32, size: usize) -> Vec<f32> {
+        let count = size * size;
+        let mut values = Vec::with_capacity(count + LANES);
+
+        for start in (0..count).step_by(LANES) {
+            let mut xs = [0.0; LANES];
+            let mut zs = [0.0; LANES];
+
+            // Lanes past the last point repeat it, and are dropped once sampled
+            for lane in 0..LANES {
+                let point = (start + lane).min(count - 1);
+                xs[lane] = x + (point / size) as f32;
+                zs[lane] = z + (point % size) as f32;
+            }
+
+            values.extend(self.sample(xs, zs));
+        }
+
+        values.truncate(count);
+        values
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Sum;
+
+    impl NoiseSource for Sum {
+        fn get(&self, x: f32, z: f32) -> f32 {
+            x + z
+        }
+    }
+
+    #[test]
+    fn fill() {
+        assert_eq!(Sum.fill(1.0, 10.0, 2), vec![11.0, 12.0, 12.0, 13.0]);
+
+        let noise = FastNoise::seeded(1);
+        let points = noise.fill(-5.0, 3.0, 4);
+        assert_eq!(points[2 * 4 + 1], noise.get_noise(-3.0, 4.0));
+    }
+
+    #[test]
+    fn bracket() {
+        let mut expected = FastNoise::seeded(3);
+        expected.set_noise_type(NoiseType::Simplex);
+        expected.set_frequency(0.01);
+
+        let noise = NoiseBackend::Bracket.build(Fractal::simplex(3, 0.01));
+        assert_eq!(noise.get(12.0, -40.0), expected.get_noise(12.0, -40.0));
+
+        // More octaves add detail
+        let fractal = NoiseBackend::Bracket.build(Fractal {
+            octaves: 3,
+            ..Fractal::simplex(3, 0.01)
+        });
+        assert_ne!(fractal.get(12.0, -40.0), noise.get(12.0, -40.0));
+    }
+
+    #[test]
+    fn batch() {
+        let fractals = [
+            Fractal::simplex(3, 0.01),
+            Fractal {
+                seed: 99,
+                frequency: 0.03,
+                octaves: 3,
+                gain: 0.9,
+                lacunarity: 0.5,
+            },
+            Fractal {
+                octaves: 2,
+                ..Fractal::simplex(u64::MAX, 0.004)
+            },
+        ];
+
+        // Both backends sample the exact same noise, so worlds are the same whichever generates them
+        for fractal in fractals {
+            let bracket = NoiseBackend::Bracket.build(fractal);
+            let batch = NoiseBackend::Batch.build(fractal);
+
+            assert_eq!(batch.fill(-300.0, 41.0, 13), bracket.fill(-300.0, 41.0, 13));
+            assert_eq!(batch.get(1e4, -2.5), bracket.get(1e4, -2.5));
+        }
+
+        assert!(NoiseBackend::Batch
+            .build(Fractal::simplex(1, 1.0))
+            .fill(0.0, 0.0, 0)
+            .is_empty());
+    }
+
+    #[test]
+    fn noise_rs() {
+        let fractals = [
+            Fractal::simplex(3, 0.05),
+            Fractal {
+                octaves: 4,
+                ..Fractal::simplex(u64::MAX, 0.02)
+            },
+        ];
+
+        for fractal in fractals {
+            let noise_rs = NoiseBackend::NoiseRs.build(fractal);
+            let batch = NoiseBackend::Batch.build(fractal);
+
+            // It's other noise, but it spreads over the same range, so terrain keeps its shape
+            for values in [
+                noise_rs.fill(-500.0, 200.0, 64),
+                batch.fill(-500.0, 200.0, 64),
+            ] {
+                let min = values.iter().copied().fold(f32::MAX, f32::min);
+                let max = values.iter().copied().fold(f32::MIN, f32::max);
+                let mean = values.iter().sum::<f32>() / values.len() as f32;
+
+                assert!(min >= -1.0 && max <= 1.0, "{} to {}", min, max);
+                assert!(min < -0.4 && max > 0.4, "{} to {}", min, max);
+                assert!(mean.abs() < 0.2, "{}", mean);
+            }
+
+            assert_ne!(noise_rs.get(12.0, -40.0), batch.get(12.0, -40.0));
+        }
+
+        // Seeds which only differ on the high bits still sample different noise
+        let low = NoiseBackend::NoiseRs.build(Fractal::simplex(1, 0.05));
+        let high = NoiseBackend::NoiseRs.build(Fractal::simplex(1 | 1 << 40, 0.05));
+        assert_ne!(low.get(12.0, -40.0), high.get(12.0, -40.0));
+    }
+}
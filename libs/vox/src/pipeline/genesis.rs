@@ -17,20 +17,25 @@ use bevy::prelude::*;
 
 use std::collections::HashSet;
 
+use super::{io::CacheWriter, GenesisHooks, GenesisWorkers};
+use crate::biome::ChunkBiomes;
+use crate::caves;
 use crate::chunk;
+use crate::dimension;
+use crate::generator::{Terrain, TerrainKinds};
 use crate::math;
 use crate::voxel;
 use crate::world::VoxWorld;
 
-fn update_voxel(
-    world: &mut VoxWorld,
+pub(super) fn update_voxel(
+    world: &VoxWorld,
     local: IVec3,
     voxels: &[(IVec3, voxel::Kind)],
 ) -> HashSet<IVec3> {
     trace!("Updating chunk {} values {:?}", local, voxels);
     let mut dirty_chunks = HashSet::default();
 
-    if let Some(chunk) = world.get_mut(local) {
+    if let Some(mut chunk) = world.get_mut(local) {
         for (voxel, kind) in voxels {
             chunk.set(*voxel, *kind);
 
@@ -51,7 +56,7 @@ fn update_voxel(
     dirty_chunks
 }
 
-fn unload_chunk(world: &mut VoxWorld, local: IVec3) -> HashSet<IVec3> {
+pub(super) fn unload_chunk(world: &VoxWorld, local: IVec3) -> HashSet<IVec3> {
     let mut dirty_chunks = HashSet::default();
 
     if world.remove(local).is_none() {
@@ -63,16 +68,57 @@ fn unload_chunk(world: &mut VoxWorld, local: IVec3) -> HashSet<IVec3> {
     dirty_chunks
 }
 
-fn load_chunk(world: &mut VoxWorld, local: IVec3) -> HashSet<IVec3> {
-    let path = cache::local_path(local);
+/**
+  Queues the cache of the given chunk, as it's now, to replace the one it was generated or loaded with, so
+  changes done since are kept. Chunks outside of any of the given dimensions are never cached.
+*/
+pub(super) fn save_chunk(
+    world: &VoxWorld,
+    local: IVec3,
+    dimensions: &dimension::Dimensions,
+    workers: &GenesisWorkers,
+) {
+    let name = match usize::try_from(dimension::index_of(local))
+        .ok()
+        .and_then(|index| dimensions.name(index))
+    {
+        Some(name) => name,
+        None => return,
+    };
 
-    let chunk = if path.exists() {
-        cache::load(&path)
-    } else {
-        cache::generate(local)
+    // Guards are taken one at a time, since chunks and biomes may be on the same shard
+    let chunk = match world.get(local) {
+        Some(chunk) => chunk.clone(),
+        None => return,
+    };
+    let biomes = match world.biomes(local) {
+        Some(biomes) => biomes.clone(),
+        None => return,
     };
 
+    let relative = dimension::to_relative(local);
+    workers.replace(
+        cache::dimension_path(name, relative),
+        cache::encode(relative, &chunk, &biomes),
+    );
+}
+
+/// Name and terrain of the dimension the given chunk is in, out of `dimensions`, which are listed by index.
+fn find_dimension(dimensions: &[(String, Terrain)], local: IVec3) -> Option<&(String, Terrain)> {
+    usize::try_from(dimension::index_of(local))
+        .ok()
+        .and_then(|index| dimensions.get(index))
+}
+
+/// Adds the chunk to the world, returning the chunks which need to be updated because of it.
+fn add_chunk(
+    world: &VoxWorld,
+    local: IVec3,
+    chunk: chunk::ChunkKind,
+    biomes: ChunkBiomes,
+) -> HashSet<IVec3> {
     world.add(local, chunk);
+    world.set_biomes(local, biomes);
 
     voxel::SIDES
         .iter()
@@ -81,7 +127,75 @@ fn load_chunk(world: &mut VoxWorld, local: IVec3) -> HashSet<IVec3> {
         .collect()
 }
 
-fn update_chunk(world: &mut VoxWorld, local: IVec3) -> bool {
+/**
+  Loads the given chunk from the cache of its dimension, out of `dimensions`, which lists the name and terrain
+  of each one by index. Returns `None` when it isn't cached, or its cache is broken, so it must be generated.
+  Chunks outside of any dimension are left empty, without ever being cached. This is run by the IO thread.
+*/
+pub(super) fn load_chunk(
+    world: &VoxWorld,
+    local: IVec3,
+    dimensions: &[(String, Terrain)],
+) -> Option<HashSet<IVec3>> {
+    let relative = dimension::to_relative(local);
+
+    let (name, _) = match find_dimension(dimensions, local) {
+        Some(found) => found,
+        None => {
+            let biomes = ChunkBiomes::generate(relative);
+            return Some(add_chunk(world, local, chunk::ChunkKind::default(), biomes));
+        }
+    };
+
+    let path = cache::dimension_path(name, relative);
+
+    if !path.exists() {
+        return None;
+    }
+
+    match cache::load(&path, relative) {
+        Ok((chunk, biomes)) => Some(add_chunk(world, local, chunk, biomes)),
+        Err(reason) => {
+            warn!(
+                "Chunk cache {} is broken ({}), generating it again",
+                path.display(),
+                reason
+            );
+
+            std::fs::remove_file(&path)
+                .unwrap_or_else(|_| panic!("Unable to remove file {}", path.display()));
+            None
+        }
+    }
+}
+
+/**
+  Generates the given chunk with the terrain of its dimension, made of the given kinds, queueing its cache to
+  be written by the IO thread. Dimensions are generated as if each one was the only one on the world, so their chunks are seen
+  relative to them.
+*/
+pub(super) fn generate_chunk(
+    world: &VoxWorld,
+    local: IVec3,
+    hooks: &GenesisHooks,
+    dimensions: &[(String, Terrain)],
+    kinds: &TerrainKinds,
+    writer: &CacheWriter,
+) -> HashSet<IVec3> {
+    let relative = dimension::to_relative(local);
+    let (name, terrain) = find_dimension(dimensions, local)
+        .expect("Chunks outside of dimensions are never generated");
+
+    let (chunk, biomes) = cache::generate(relative, hooks, terrain, kinds);
+    writer.write(
+        cache::dimension_path(name, relative),
+        cache::encode(relative, &chunk, &biomes),
+    );
+
+    add_chunk(world, local, chunk, biomes)
+}
+
+pub(super) fn update_chunk(world: &VoxWorld, local: IVec3) -> bool {
     if world.get(local).is_some() {
         world.update_neighborhood(local);
         true
@@ -91,12 +205,11 @@ fn update_chunk(world: &mut VoxWorld, local: IVec3) -> bool {
 }
 
 /**
-    Chunk genesis caching related code
- */
+   Chunk genesis caching related code
+*/
 mod cache {
     use super::*;
 
-    use bracket_noise::prelude::*;
     use serde::{Deserialize, Serialize};
     use std::path::Path;
     use std::path::PathBuf;
@@ -107,30 +220,44 @@ mod cache {
     #[derive(Debug, Deserialize, Serialize)]
     struct ChunkCache {
         local: IVec3,
+        /// [`chunk::ChunkKind::checksum`] of `kind`, checked when loading.
+        checksum: u64,
         kind: chunk::ChunkKind,
+        biomes: ChunkBiomes,
     }
 
     #[cfg(test)]
     impl PartialEq for ChunkCache {
         fn eq(&self, other: &Self) -> bool {
-            self.local == other.local && self.kind == other.kind
+            self.local == other.local && self.kind == other.kind && self.biomes == other.biomes
         }
     }
 
-    pub(super) fn generate(local: IVec3) -> chunk::ChunkKind {
-        let mut noise = FastNoise::seeded(15);
-        noise.set_noise_type(NoiseType::SimplexFractal);
-        noise.set_frequency(0.03);
-        noise.set_fractal_type(FractalType::FBM);
-        noise.set_fractal_octaves(3);
-        noise.set_fractal_gain(0.9);
-        noise.set_fractal_lacunarity(0.5);
+    /// Generates the chunk and its biomes, which are cached together.
+    pub(super) fn generate(
+        local: IVec3,
+        hooks: &GenesisHooks,
+        terrain: &Terrain,
+        kinds: &TerrainKinds,
+    ) -> (chunk::ChunkKind, ChunkBiomes) {
         let world = chunk::to_world(local);
+        let heights = terrain.heights(world.x as i32, world.z as i32, chunk::AXIS_SIZE);
+        let water_level = terrain.water_level();
         let mut kind = chunk::ChunkKind::default();
         for x in 0..chunk::AXIS_SIZE {
             for z in 0..chunk::AXIS_SIZE {
-                let h = noise.get_noise(world.x + x as f32, world.z + z as f32);
-                let world_height = ((h + 1.0) / 2.0) * (2 * chunk::AXIS_SIZE) as f32;
+                let world_height = heights[x * chunk::AXIS_SIZE + z];
+                let column = kind.column_mut(x as i32, z as i32);
+
+                // Columns carved below the water level are flooded, up to it
+                let flooded = water_level.filter(|level| world_height.floor() < *level);
+                if let Some(level) = flooded {
+                    let start = (world_height - world.y).max(0.0) as usize;
+                    let end = ((level - world.y).max(0.0) as usize).min(chunk::AXIS_SIZE);
+                    if start < end {
+                        column[start..end].fill(kinds.water);
+                    }
+                }
 
                 let height_local = world_height - world.y;
 
@@ -139,58 +266,88 @@ mod cache {
                 }
 
                 let end = usize::min(height_local as usize, chunk::AXIS_SIZE);
-
-                for y in 0..end {
-                    kind.set((x as i32, y as i32, z as i32).into(), 1.into());
+                column[..end].fill(kinds.dirt);
+
+                // Only the top of dry columns is covered by grass, while river and lake beds are left bare
+                let top = (height_local as usize).checked_sub(1);
+                if let Some(top) = top.and_then(|top| column.get_mut(top)) {
+                    if flooded.is_none() {
+                        *top = kinds.grass;
+                    }
                 }
             }
         }
-        let path = local_path(local);
 
-        assert!(!path.exists(), "Cache already exists!");
+        // Post processed chunks are cached, so processes run only once per chunk
+        hooks.apply(local, &mut kind);
 
-        save(&path, local, &kind);
+        // Decorated after post processes, so caves carved by them get decorated too
+        caves::decorate(terrain.seed(), local, &mut kind, &heights);
 
-        kind
+        let biomes = ChunkBiomes::generate(local);
+
+        (kind, biomes)
     }
 
-    pub(super) fn save(path: &Path, local: IVec3, kind: &chunk::ChunkKind) {
+    /// Serializes the cache of the given chunk, ready to be written by the IO thread.
+    pub(super) fn encode(local: IVec3, kind: &chunk::ChunkKind, biomes: &ChunkBiomes) -> Vec<u8> {
         let cache = ChunkCache {
             local,
+            checksum: kind.checksum(),
             kind: kind.clone(),
+            biomes: biomes.clone(),
         };
 
-        let file = std::fs::OpenOptions::new()
-            .write(true)
-            .truncate(true)
-            .create(true)
-            .open(path)
-            .unwrap_or_else(|_| panic!("Unable to write to file {}", path.display()));
-
         #[cfg(not(feature = "serde_ron"))]
-        bincode::serialize_into(file, &cache)
-            .unwrap_or_else(|_| panic!("Failed to serialize cache to file {}", path.display()));
+        let bytes = bincode::serialize(&cache)
+            .unwrap_or_else(|_| panic!("Failed to serialize cache of chunk {}", local));
 
         #[cfg(feature = "serde_ron")]
-        ron::ser::to_writer(file, cache)
-            .unwrap_or_else(|_| panic!("Failed to serialize cache to file {}", path.display()));
+        let bytes = ron::ser::to_string(&cache)
+            .unwrap_or_else(|_| panic!("Failed to serialize cache of chunk {}", local))
+            .into_bytes();
+
+        bytes
     }
 
-    pub(super) fn load(path: &Path) -> chunk::ChunkKind {
+    /**
+      Loads the cache of the given chunk. On failure, returns why the cache is broken, like when it can't be
+      parsed or its checksum doesn't match. Caches saved before biomes were cached can't be parsed either, so
+      they're generated again.
+    */
+    pub(super) fn load(
+        path: &Path,
+        local: IVec3,
+    ) -> Result<(chunk::ChunkKind, ChunkBiomes), String> {
+        #[cfg(all(feature = "mmap", unix, not(feature = "serde_ron")))]
+        let bytes = super::super::mmap::MappedFile::open(path)
+            .unwrap_or_else(|_| panic!("Unable to map file {}", path.display()));
+
+        #[cfg(not(any(all(feature = "mmap", unix), feature = "serde_ron")))]
+        let bytes = std::fs::read(path)
+            .unwrap_or_else(|_| panic!("Unable to read file {}", path.display()));
+
+        #[cfg(not(feature = "serde_ron"))]
+        let cache: ChunkCache = bincode::deserialize(&bytes).map_err(|err| err.to_string())?;
+
+        #[cfg(feature = "serde_ron")]
         let file = std::fs::OpenOptions::new()
             .read(true)
             .open(path)
             .unwrap_or_else(|_| panic!("Unable to open file {}", path.display()));
 
-        #[cfg(not(feature = "serde_ron"))]
-        let cache: ChunkCache = bincode::deserialize_from(file)
-            .unwrap_or_else(|_| panic!("Failed to parse file {}", path.display()));
-
         #[cfg(feature = "serde_ron")]
-        let cache =
-            ron::de::from_reader(file).expect(&format!("Failed to parse file {}", path.display()));
+        let cache: ChunkCache = ron::de::from_reader(file).map_err(|err| err.to_string())?;
+
+        if cache.local != local {
+            return Err(format!("it holds chunk {}", cache.local));
+        }
+
+        if cache.kind.checksum() != cache.checksum {
+            return Err("checksum mismatch".to_string());
+        }
 
-        cache.kind
+        Ok((cache.kind, cache.biomes))
     }
 
     pub(super) fn local_path(local: IVec3) -> PathBuf {
@@ -199,6 +356,21 @@ mod cache {
             .with_extension(CACHE_EXT)
     }
 
+    /**
+      Path of the cache of the given chunk of a dimension. Overworld chunks are kept where they were before
+      worlds had other dimensions, while the ones of each other dimension are kept on their own directory.
+    */
+    pub(super) fn dimension_path(dimension: &str, local: IVec3) -> PathBuf {
+        if dimension == dimension::OVERWORLD {
+            return local_path(local);
+        }
+
+        PathBuf::from(CACHE_PATH)
+            .join(dimension)
+            .join(format_local(local))
+            .with_extension(CACHE_EXT)
+    }
+
     fn format_local(local: IVec3) -> String {
         local
             .to_string()
@@ -214,16 +386,31 @@ mod cache {
     #[cfg(test)]
     mod tests {
         use super::*;
+        use crate::pipeline::io::{self, CacheWrite};
         use std::fs::remove_file;
 
         #[test]
         #[should_panic]
         fn generate_cache_panic() {
             let local = (9999, 9998, 9997).into();
-            let _ = remove_file(local_path(local));
-
-            super::generate(local);
-            super::generate(local);
+            let path = local_path(local);
+            let _ = remove_file(&path);
+
+            let terrain = Terrain::new(&Default::default());
+            let kinds = TerrainKinds {
+                grass: 1.into(),
+                dirt: 2.into(),
+                water: 3.into(),
+            };
+            let (kind, biomes) = super::generate(local, &GenesisHooks::default(), &terrain, &kinds);
+
+            for _ in 0..2 {
+                io::write(CacheWrite {
+                    path: path.clone(),
+                    bytes: super::encode(local, &kind, &biomes),
+                    replace: false,
+                });
+            }
         }
 
         #[test]
@@ -250,6 +437,21 @@ mod cache {
             assert!(path.ends_with(&format!("-1_3333_-461.{}", CACHE_EXT)));
         }
 
+        #[test]
+        fn dimension_path() {
+            let local = IVec3::new(-1, 2, 3);
+
+            // Overworld chunks stay where they always were
+            assert_eq!(
+                super::dimension_path(dimension::OVERWORLD, local),
+                local_path(local)
+            );
+
+            let path = super::dimension_path("highlands", local);
+            assert!(path.starts_with(Path::new(CACHE_PATH).join("highlands")));
+            assert!(path.ends_with(format!("-1_2_3.{}", CACHE_EXT)));
+        }
+
         #[test]
         fn test_ser_de() {
             let mut temp_file = std::env::temp_dir();
@@ -257,7 +459,9 @@ mod cache {
 
             let cache = ChunkCache {
                 local: IVec3::ZERO,
+                checksum: 0,
                 kind: chunk::ChunkKind::default(),
+                biomes: ChunkBiomes::generate(IVec3::ZERO),
             };
 
             create_cache(&temp_file, &cache);
@@ -316,51 +520,102 @@ mod cache {
         fn load_cache() {
             let local = (-9998, 0, 9998).into();
 
+            let kind = chunk::ChunkKind::default();
             let cache = ChunkCache {
                 local,
-                kind: chunk::ChunkKind::default(),
+                checksum: kind.checksum(),
+                kind,
+                biomes: ChunkBiomes::generate(local),
             };
 
             let path = get_test_path(local);
             create_cache(&path, &cache);
 
-            let loaded_kind = super::load(&path);
+            let (loaded_kind, loaded_biomes) = super::load(&path, local).unwrap();
 
             assert_eq!(
                 cache,
                 ChunkCache {
                     local,
+                    checksum: 0,
                     kind: loaded_kind,
+                    biomes: loaded_biomes,
                 }
             );
 
             remove_file(path).unwrap();
         }
 
+        #[test]
+        fn load_broken_cache() {
+            let local = (-9997, 0, 9997).into();
+            let path = get_test_path(local);
+
+            let mut kind = chunk::ChunkKind::default();
+            let checksum = kind.checksum();
+            kind.set((1, 2, 3).into(), 4.into());
+
+            create_cache(
+                &path,
+                &ChunkCache {
+                    local,
+                    checksum,
+                    kind: kind.clone(),
+                    biomes: ChunkBiomes::generate(local),
+                },
+            );
+            assert!(super::load(&path, local).is_err());
+
+            // Cache of another chunk
+            create_cache(
+                &path,
+                &ChunkCache {
+                    local: IVec3::ZERO,
+                    checksum: kind.checksum(),
+                    kind,
+                    biomes: ChunkBiomes::generate(IVec3::ZERO),
+                },
+            );
+            assert!(super::load(&path, local).is_err());
+
+            std::fs::write(&path, [1, 2, 3]).unwrap();
+            assert!(super::load(&path, local).is_err());
+
+            remove_file(path).unwrap();
+        }
+
         #[test]
         fn save_cache() {
             let local = (-921, 0, 2319).into();
 
             let cache = ChunkCache {
                 local,
+                checksum: 0,
                 kind: chunk::ChunkKind::default(),
+                biomes: ChunkBiomes::generate(local),
             };
 
             let path = get_test_path(local);
 
             assert!(!path.exists());
 
-            super::save(&path, cache.local, &cache.kind);
+            io::write(CacheWrite {
+                path: path.clone(),
+                bytes: super::encode(cache.local, &cache.kind, &cache.biomes),
+                replace: false,
+            });
 
             assert!(path.exists());
 
-            let loaded_kind = super::load(&path);
+            let (loaded_kind, loaded_biomes) = super::load(&path, local).unwrap();
 
             assert_eq!(
                 cache,
                 ChunkCache {
                     local,
+                    checksum: 0,
                     kind: loaded_kind,
+                    biomes: loaded_biomes,
                 }
             );
 
@@ -368,3 +623,64 @@ mod cache {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::{start_genesis, GenesisConfig, WorldMeta};
+    use crate::voxel::KindRegistry;
+    use std::path::Path;
+    use std::time::{Duration, Instant};
+
+    fn drain(workers: &mut GenesisWorkers, local: IVec3) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+
+        while !workers
+            .drain_finished()
+            .iter()
+            .any(|result| result.local == local)
+        {
+            assert!(Instant::now() < deadline, "Timed out waiting {}", local);
+            std::thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn unload_reload() {
+        let registry = KindRegistry::load(Path::new(&format!(
+            "{}assets/voxels/kind_descriptions.ron",
+            env!("CARGO_WORKSPACE_DIR")
+        )));
+        let meta = WorldMeta::default();
+        let local = IVec3::new(1234, 9990, 4321);
+        let path = cache::dimension_path(meta.dimensions.name(0).unwrap(), local);
+        let _ = std::fs::remove_file(&path);
+
+        let world = VoxWorld::default();
+        let mut workers = start_genesis(
+            &world,
+            GenesisConfig::default(),
+            GenesisHooks::default(),
+            &meta,
+            &registry,
+        );
+
+        workers.request(local).unwrap();
+        drain(&mut workers, local);
+
+        let chest = registry.find("Chest").unwrap();
+        world.get_mut(local).unwrap().set(IVec3::ONE, chest);
+
+        save_chunk(&world, local, &meta.dimensions, &workers);
+        unload_chunk(&world, local);
+        assert!(!world.exists(local));
+
+        workers.request(local).unwrap();
+        drain(&mut workers, local);
+
+        assert_eq!(world.get(local).unwrap().get(IVec3::ONE), chest);
+
+        drop(workers);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
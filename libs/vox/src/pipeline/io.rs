@@ -0,0 +1,142 @@
+use bevy::prelude::*;
+use crossbeam_channel::{Receiver, Sender};
+use std::{io::Write, path::PathBuf, sync::Arc};
+
+use crate::world::VoxWorld;
+
+use super::worker::{GenesisResult, LoadJob};
+
+/// A cache file waiting to be written by the IO thread.
+pub struct CacheWrite {
+    pub path: PathBuf,
+    pub bytes: Vec<u8>,
+    /// Generated chunks are only cached once, while edited ones replace their cache.
+    pub replace: bool,
+}
+
+/**
+  Queues cache files to be written by the IO thread of [`super::GenesisWorkers`], so generation workers don't
+  wait on the disk. The queue is bounded by [`super::GenesisConfig::io_capacity`], and writing blocks while
+  it's full, until the IO thread catches up.
+*/
+#[derive(Clone)]
+pub struct CacheWriter(Sender<CacheWrite>);
+
+impl CacheWriter {
+    pub(super) fn new(sender: Sender<CacheWrite>) -> Self {
+        Self(sender)
+    }
+
+    pub fn write(&self, path: PathBuf, bytes: Vec<u8>) {
+        self.send(CacheWrite {
+            path,
+            bytes,
+            replace: false,
+        });
+    }
+
+    /// Like [`CacheWriter::write`], but replaces the cache file, if it exists.
+    pub fn replace(&self, path: PathBuf, bytes: Vec<u8>) {
+        self.send(CacheWrite {
+            path,
+            bytes,
+            replace: true,
+        });
+    }
+
+    fn send(&self, write: CacheWrite) {
+        self.0.send(write).expect("Cache IO thread died");
+    }
+}
+
+/**
+  Writes the given cache file, creating its dir if needed. Generated chunks are only cached once, so panics if
+  the file already exists, unless it's meant to be replaced.
+*/
+pub(super) fn write(
+    CacheWrite {
+        path,
+        bytes,
+        replace,
+    }: CacheWrite,
+) {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .unwrap_or_else(|_| panic!("Unable to create cache dir {}", dir.display()));
+    }
+
+    assert!(replace || !path.exists(), "Cache already exists!");
+
+    std::fs::File::create(&path)
+        .and_then(|mut file| file.write_all(&bytes))
+        .unwrap_or_else(|_| panic!("Unable to write to file {}", path.display()));
+}
+
+/// Channels the IO thread works with.
+pub(super) struct IoChannels {
+    /// Chunks requested to the pool, which are loaded from the cache first.
+    pub loads: Receiver<IVec3>,
+    pub writes: Receiver<CacheWrite>,
+    /// Chunks which aren't cached, handed to generation workers.
+    pub generate: Sender<IVec3>,
+    pub results: Sender<GenesisResult>,
+}
+
+/**
+  Runs the IO thread, which loads requested chunks with the given job, handing the ones which aren't cached to
+  generation workers, and writes the caches they queue.
+
+  Queued writes always go first, so a chunk unloaded and requested again right after being generated is found
+  on the cache. Once the pool is dropped, it keeps writing until every generation worker is gone, so nothing
+  they generated is lost.
+*/
+pub(super) fn run(world: VoxWorld, load: Arc<LoadJob>, channels: IoChannels) {
+    let IoChannels {
+        loads,
+        writes,
+        generate,
+        results,
+    } = channels;
+
+    loop {
+        crossbeam_channel::select! {
+            recv(writes) -> pending => match pending {
+                Ok(pending) => write(pending),
+                // Writers are only gone once every generation worker died
+                Err(_) => break,
+            },
+            recv(loads) -> local => {
+                let local = match local {
+                    Ok(local) => local,
+                    Err(_) => break,
+                };
+
+                // Writes queued while this request was waiting may be of this very chunk
+                for pending in writes.try_iter() {
+                    write(pending);
+                }
+
+                let sent = match load(&world, local) {
+                    Some(dirty_chunks) => results
+                        .send(GenesisResult {
+                            local,
+                            dirty_chunks,
+                        })
+                        .is_ok(),
+                    None => generate.send(local).is_ok(),
+                };
+
+                if !sent {
+                    break;
+                }
+            },
+        }
+    }
+
+    // Generation workers leave once they're out of requests, dropping their writers
+    drop(generate);
+
+    for pending in writes.iter() {
+        write(pending);
+    }
+}
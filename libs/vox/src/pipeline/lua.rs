@@ -0,0 +1,255 @@
+use bevy::prelude::*;
+use mlua::{HookTriggers, Lua, LuaOptions, StdLib};
+use std::{cell::RefCell, path::Path, time::Instant};
+
+use super::{GenesisHooks, PostProcess};
+use crate::chunk::{self, ChunkKind};
+
+/// Where the script of the world is, next to its chunks cache, so each world has its own.
+pub const SCRIPT_PATH: &str = "cache/generation.lua";
+
+/// Base library functions which load other code, and so are removed too.
+const REMOVED_GLOBALS: [&str; 3] = ["dofile", "loadfile", "load"];
+
+/// Instructions run between deadline checks.
+const INSTRUCTIONS_PER_CHECK: u32 = 1000;
+/// Memory, in bytes, a script may use on a single chunk.
+const MEMORY_LIMIT: usize = 16 * 1024 * 1024;
+
+/**
+  Post process running a Lua script, which must define a `process(chunk)` function. It's called for each
+  generated chunk with a table holding:
+
+  - `x`, `y` and `z`: the chunk position.
+  - `get(x, y, z)`: kind of the given voxel of the chunk.
+  - `set(x, y, z, kind)`: changes the given voxel of the chunk.
+
+  Scripts are sandboxed: only the table, string, math and utf8 libraries are there, and they run on a fresh
+  state for each chunk, so chunks can't depend on the order they were generated. Scripts which are still
+  running past the deadline are stopped with an error.
+*/
+pub struct LuaScript {
+    name: String,
+    source: String,
+}
+
+impl LuaScript {
+    /// Checks the given script loads and defines `process`. Loading it must not take longer than a chunk.
+    pub fn new(name: &str, source: &str, deadline: Instant) -> Result<Self, String> {
+        let script = Self {
+            name: name.to_string(),
+            source: source.to_string(),
+        };
+
+        let lua = script.load(deadline).map_err(|err| err.to_string())?;
+        lua.globals()
+            .get::<_, mlua::Function>("process")
+            .map_err(|_| "The script must define a process function".to_string())?;
+
+        Ok(script)
+    }
+
+    /// Runs the script on a new sandboxed state, which errors once the deadline is reached.
+    fn load(&self, deadline: Instant) -> mlua::Result<Lua> {
+        // Anything reaching outside of the script, like files or the OS, is left out
+        let libs = StdLib::TABLE | StdLib::STRING | StdLib::MATH | StdLib::UTF8;
+        let lua = Lua::new_with(libs, LuaOptions::new())?;
+        lua.set_memory_limit(MEMORY_LIMIT)?;
+
+        for name in REMOVED_GLOBALS {
+            lua.globals().set(name, mlua::Value::Nil)?;
+        }
+
+        lua.set_hook(
+            HookTriggers::new().every_nth_instruction(INSTRUCTIONS_PER_CHECK),
+            move |lua, _| {
+                if Instant::now() <= deadline {
+                    return Ok(());
+                }
+
+                // Scripts catching the error get it again on every instruction, until nothing catches it
+                lua.set_hook(HookTriggers::new().every_nth_instruction(1), |_, _| {
+                    Err(time_limit_reached())
+                });
+                Err(time_limit_reached())
+            },
+        );
+
+        lua.load(&self.source).set_name(&self.name).exec()?;
+
+        Ok(lua)
+    }
+}
+
+fn time_limit_reached() -> mlua::Error {
+    mlua::Error::RuntimeError("time limit reached".to_string())
+}
+
+/// Voxel of a chunk given by a script, which errors when it's outside the chunk.
+fn voxel(x: i32, y: i32, z: i32) -> mlua::Result<IVec3> {
+    let voxel = IVec3::new(x, y, z);
+
+    if chunk::is_within_bounds(voxel) {
+        Ok(voxel)
+    } else {
+        Err(mlua::Error::RuntimeError(format!(
+            "voxel {} is outside the chunk",
+            voxel
+        )))
+    }
+}
+
+impl PostProcess for LuaScript {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn process(&self, local: IVec3, kind: &mut ChunkKind, deadline: Instant) -> Result<(), String> {
+        let lua = self.load(deadline).map_err(|err| err.to_string())?;
+        let kind = RefCell::new(kind);
+
+        lua.scope(|scope| {
+            let chunk = lua.create_table()?;
+            chunk.set("x", local.x)?;
+            chunk.set("y", local.y)?;
+            chunk.set("z", local.z)?;
+            chunk.set(
+                "get",
+                scope.create_function(|_, (x, y, z): (i32, i32, i32)| {
+                    Ok(kind.borrow().get(voxel(x, y, z)?).id())
+                })?,
+            )?;
+            chunk.set(
+                "set",
+                scope.create_function(|_, (x, y, z, value): (i32, i32, i32, u16)| {
+                    kind.borrow_mut().set(voxel(x, y, z)?, value.into());
+                    Ok(())
+                })?,
+            )?;
+
+            lua.globals()
+                .get::<_, mlua::Function>("process")?
+                .call::<_, ()>(chunk)
+        })
+        .map_err(|err| err.to_string())
+    }
+}
+
+/**
+  Adds the world script at the given path, when there's one, to the given hooks. Scripts which don't load are
+  left out, so the world is generated without them.
+*/
+pub fn with_world_script(mut hooks: GenesisHooks, path: &Path) -> GenesisHooks {
+    let source = match std::fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(_) => return hooks,
+    };
+
+    let deadline = Instant::now() + hooks.time_limit;
+    match LuaScript::new(&path.display().to_string(), &source, deadline) {
+        Ok(script) => hooks.add(script),
+        Err(err) => error!("Failed to load world script {}: {}", path.display(), err),
+    }
+
+    hooks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const PILLAR: &str = r#"
+        function process(chunk)
+            for y = 0, 3 do
+                chunk.set(0, y, 0, chunk.get(1, 1, 1) + 5)
+            end
+        end
+    "#;
+
+    fn script(source: &str) -> Result<LuaScript, String> {
+        LuaScript::new("test", source, Instant::now() + Duration::from_secs(1))
+    }
+
+    fn hooks(source: &str) -> GenesisHooks {
+        let mut hooks = GenesisHooks::default();
+        hooks.time_limit = Duration::from_millis(50);
+        hooks.add(script(source).unwrap());
+        hooks
+    }
+
+    #[test]
+    fn process() {
+        let mut kind = ChunkKind::default();
+        kind.set(IVec3::ONE, 2.into());
+
+        hooks(PILLAR).apply(IVec3::ZERO, &mut kind);
+        assert_eq!(kind.get(IVec3::new(0, 3, 0)), 7.into());
+
+        // The chunk position is given too
+        let mut kind = ChunkKind::default();
+        hooks("function process(chunk) chunk.set(0, 0, 0, chunk.x - chunk.z) end")
+            .apply(IVec3::new(5, 1, 2), &mut kind);
+        assert_eq!(kind.get(IVec3::ZERO), 3.into());
+    }
+
+    #[test]
+    fn discarded() {
+        let scripts = [
+            // Stuck
+            "function process(chunk) chunk.set(0, 0, 0, 1) while true do end end",
+            // Caught time limit errors are raised again
+            "function process(chunk) chunk.set(0, 0, 0, 1) while true do pcall(function() while true do end end) end end",
+            // Broken
+            "function process(chunk) chunk.set(0, 0, 0, 1) error('broken') end",
+            // Outside of the chunk
+            "function process(chunk) chunk.set(0, 0, 0, 1) chunk.set(-1, 0, 0, 1) end",
+        ];
+
+        for source in scripts {
+            let mut kind = ChunkKind::default();
+            hooks(source).apply(IVec3::ZERO, &mut kind);
+            assert!(kind.get(IVec3::ZERO).is_empty(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn sandbox() {
+        for source in [
+            "function process(chunk) os.remove('file') end",
+            "function process(chunk) io.open('file') end",
+            "function process(chunk) dofile('file') end",
+            "function process(chunk) require('file') end",
+        ] {
+            let mut kind = ChunkKind::default();
+            assert!(script(source)
+                .unwrap()
+                .process(
+                    IVec3::ZERO,
+                    &mut kind,
+                    Instant::now() + Duration::from_secs(1)
+                )
+                .is_err());
+        }
+
+        assert!(script("process = 3").is_err());
+        assert!(script("function process(").is_err());
+        assert!(script("while true do end").is_err());
+    }
+
+    #[test]
+    fn with_world_script() {
+        let path = std::env::temp_dir().join("eterno_lua_with_world_script.lua");
+        let _ = std::fs::remove_file(&path);
+
+        assert!(super::with_world_script(GenesisHooks::default(), &path).is_empty());
+
+        std::fs::write(&path, "broken").unwrap();
+        assert!(super::with_world_script(GenesisHooks::default(), &path).is_empty());
+
+        std::fs::write(&path, PILLAR).unwrap();
+        assert!(!super::with_world_script(GenesisHooks::default(), &path).is_empty());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
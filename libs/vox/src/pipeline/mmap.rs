@@ -0,0 +1,87 @@
+use std::{fs::File, io, ops::Deref, os::unix::io::AsRawFd, path::Path};
+
+/**
+  A whole file mapped into memory as read only. The file contents are only read from disk when accessed,
+  without copying them into a buffer first.
+*/
+pub(super) struct MappedFile {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+impl MappedFile {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len() as usize;
+
+        // Empty mappings aren't allowed
+        if len == 0 {
+            return Ok(Self {
+                ptr: std::ptr::null_mut(),
+                len,
+            });
+        }
+
+        // SAFETY: The mapping is private and read only, and it's kept valid until it's dropped, even after the
+        // file is closed. Caches are never written while they are being loaded.
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self { ptr, len })
+    }
+}
+
+impl Deref for MappedFile {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        if self.len == 0 {
+            return &[];
+        }
+
+        // SAFETY: The mapping is valid and `len` bytes long until it's dropped.
+        unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            // SAFETY: The mapping was created by `open` and isn't used anymore.
+            unsafe {
+                libc::munmap(self.ptr, self.len);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mapped_file() {
+        let path = std::env::temp_dir().join("eterno_mapped_file.bin");
+
+        std::fs::write(&path, [1, 2, 3, 4]).unwrap();
+        assert_eq!(&*MappedFile::open(&path).unwrap(), &[1, 2, 3, 4]);
+
+        std::fs::write(&path, []).unwrap();
+        assert!(MappedFile::open(&path).unwrap().is_empty());
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(MappedFile::open(&path).is_err());
+    }
+}
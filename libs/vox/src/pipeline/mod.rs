@@ -1 +1,786 @@
-mod genesis;
\ No newline at end of file
+use bevy::{ecs::schedule::StageLabel, prelude::*};
+use std::sync::Arc;
+
+use crate::{
+    block_entity::{self, BlockEntities, BlockEntity},
+    chunk, decay, edit,
+    explosion::{self, Explosion},
+    generator::{Terrain, TerrainKinds},
+    heightmap::Heightmap,
+    light::LightWorld,
+    meta::WorldMeta,
+    replay::{ReplayPlayer, ReplayRecorder},
+    schedule::{self, ScheduledEvent, UpdateSchedule},
+    signal::SignalNetwork,
+    tick::{self, RandomTickConfig},
+    voxel::{self, KindRegistry},
+    world::VoxWorld,
+};
+
+mod genesis;
+mod io;
+#[cfg(feature = "lua")]
+pub mod lua;
+#[cfg(all(feature = "mmap", unix))]
+mod mmap;
+mod origin;
+mod post_process;
+mod simulation;
+mod streaming;
+mod worker;
+
+pub use origin::{OriginShifted, WorldOrigin};
+pub use post_process::{GenesisHooks, PostProcess};
+pub use simulation::SimulationControl;
+pub use streaming::{StreamingAnchor, StreamingCenter, StreamingConfig};
+pub use worker::{GenesisConfig, GenesisResult, GenesisWorkers, RequestError};
+
+/// Seed of the terrain noise of worlds which don't choose their own. Games sharing a world must use the same one.
+pub const TERRAIN_SEED: u64 = 15;
+
+/// Seconds between simulation ticks.
+pub const TICK_STEP: f64 = 1.0 / 20.0;
+
+/**
+  Stage where the world is simulated, on a fixed rate of one tick each [`TICK_STEP`], independent of the
+  frame rate. Voxel changes, random ticks, scheduled updates and signals all run on it. It runs after
+  [`CoreStage::Update`], so changes requested by the frame are applied on the next tick. It can be paused
+  and stepped through [`SimulationControl`].
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash, StageLabel)]
+pub struct SimulationStage;
+
+/// Sent when a chunk was loaded or changed and needs to be processed again by other systems, like rendering.
+pub struct ChunkUpdated(pub IVec3);
+
+/**
+  Sent along [`ChunkUpdated`] when only some voxels of the chunk changed, like after edits, so systems can
+  update just the affected part of it. Voxels are in chunk coordinates, so changes on the border of a
+  neighbor chunk are one voxel out of bounds.
+*/
+pub struct VoxelsUpdated {
+    pub chunk: IVec3,
+    pub voxels: Vec<IVec3>,
+}
+
+/// Sent when a chunk was generated, or loaded from cache, and added to the world.
+pub struct ChunkLoaded(pub IVec3);
+
+/// Sent when a chunk was removed from the world.
+pub struct ChunkUnloaded(pub IVec3);
+
+/**
+  Sent when a voxel change was applied and the voxel isn't empty anymore, be it placed by a player, a mod or
+  the simulation. Replacing a voxel also counts as placing it.
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct BlockPlaced {
+    pub chunk: IVec3,
+    pub voxel: IVec3,
+    pub kind: voxel::Kind,
+    /// Kind which was there before, which is empty when nothing was replaced.
+    pub replaced: voxel::Kind,
+}
+
+/// Sent when a voxel change was applied and emptied a voxel, with the kind which was removed.
+#[derive(Debug, Clone, Copy)]
+pub struct BlockBroken {
+    pub chunk: IVec3,
+    pub voxel: IVec3,
+    pub kind: voxel::Kind,
+}
+
+/**
+  Sent when a voxel without support decayed, like leaves of a cut tree, so it can drop as an item. The voxel is
+  emptied on the next simulation tick, which also sends [`BlockBroken`].
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct BlockDecayed {
+    pub chunk: IVec3,
+    pub voxel: IVec3,
+    pub kind: voxel::Kind,
+}
+
+/**
+  Sent when an entity with a [`StreamingAnchor`], like a player, moves to another voxel. Positions are in
+  world voxel coordinates, so they don't change when the origin is rebased.
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct PlayerMoved {
+    pub entity: Entity,
+    pub from: IVec3,
+    pub to: IVec3,
+}
+
+/**
+  Sent when a voxel change replaced a block entity, so its contents can be handled, like dropping the items
+  of a broken chest.
+*/
+pub struct BlockEntityRemoved {
+    pub chunk: IVec3,
+    pub voxel: IVec3,
+    pub entity: Box<dyn BlockEntity>,
+}
+
+/**
+  Send this when the streaming center jumps far away, like on a teleport.
+  Queued chunk requests around the old position are cancelled, so the new surroundings are loaded first.
+*/
+pub struct RecenterStreaming;
+
+/**
+  Send this to change a voxel of a loaded chunk. The chunk, and neighbors touching the voxel,
+  are updated on the next simulation tick.
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct SetVoxel {
+    pub chunk: IVec3,
+    pub voxel: IVec3,
+    pub kind: voxel::Kind,
+}
+
+/**
+  Send this to blow up the world around a position. Destroyed voxels are emptied by a single batch of voxel
+  changes, applied on the next simulation tick like any other.
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct Explode(pub Explosion);
+
+/**
+  Sent when an explosion was processed, with the voxels it destroyed and the kind each had, so effects like
+  debris, drops and knockback can be handled.
+*/
+#[derive(Debug, Clone)]
+pub struct Exploded {
+    pub explosion: Explosion,
+    pub destroyed: Vec<(IVec3, voxel::Kind)>,
+}
+
+/// Send this to light the fuse of an explosive voxel, like when the player interacts with it.
+#[derive(Debug, Clone, Copy)]
+pub struct LightFuse {
+    pub chunk: IVec3,
+    pub voxel: IVec3,
+}
+
+/**
+  Sent when the fuse of an explosive voxel was lit, be it by [`LightFuse`] or by another explosion. The voxel
+  is removed right away, so it can be shown as an entity until it blows up, after the given ticks.
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct FuseLit {
+    pub chunk: IVec3,
+    pub voxel: IVec3,
+    pub kind: voxel::Kind,
+    pub ticks: u64,
+}
+
+/// Voxel changes waiting for the next simulation tick, in the order they were sent.
+#[derive(Default)]
+struct PendingVoxels(Vec<SetVoxel>);
+
+/**
+  Starts genesis workers which load chunks from the cache, on their IO thread, or generate them with the
+  generator of the dimension they're in, out of the [`TerrainKinds`] of the given registry. The pipeline starts
+  them with the dimensions of [`WorldMeta`], so this is only needed to switch to other ones, like when creating
+  a new world, or when kinds change, like when using the ones of a server.
+*/
+pub fn start_genesis(
+    world: &VoxWorld,
+    config: GenesisConfig,
+    hooks: GenesisHooks,
+    meta: &WorldMeta,
+    registry: &KindRegistry,
+) -> GenesisWorkers {
+    let kinds = TerrainKinds::new(registry).unwrap_or_else(|err| panic!("{}", err));
+
+    #[cfg(feature = "lua")]
+    let hooks = lua::with_world_script(hooks, std::path::Path::new(lua::SCRIPT_PATH));
+
+    let dimensions = Arc::new(
+        meta.dimensions
+            .all(&meta.generator)
+            .into_iter()
+            .map(|(name, generator)| (name, Terrain::new(&generator)))
+            .collect::<Vec<_>>(),
+    );
+    let cached = dimensions.clone();
+
+    GenesisWorkers::new(
+        world.clone(),
+        config,
+        Arc::new(move |world, local| genesis::load_chunk(world, local, &cached)),
+        Arc::new(move |world, local, writer| {
+            genesis::generate_chunk(world, local, &hooks, &dimensions, &kinds, writer)
+        }),
+    )
+}
+
+pub struct PipelinePlugin;
+
+impl Plugin for PipelinePlugin {
+    fn build(&self, app: &mut App) {
+        let world = app
+            .world
+            .get_resource::<VoxWorld>()
+            .cloned()
+            .unwrap_or_default();
+
+        let config = app
+            .world
+            .get_resource::<GenesisConfig>()
+            .copied()
+            .unwrap_or_default();
+
+        let hooks = app
+            .world
+            .get_resource::<GenesisHooks>()
+            .cloned()
+            .unwrap_or_default();
+
+        let meta = app
+            .world
+            .get_resource::<WorldMeta>()
+            .cloned()
+            .unwrap_or_default();
+
+        let registry = app
+            .world
+            .get_resource::<KindRegistry>()
+            .expect("KindRegistry must be inserted before PipelinePlugin");
+
+        let workers = start_genesis(&world, config, hooks.clone(), &meta, registry);
+
+        app.insert_resource(world)
+            .insert_resource(workers)
+            .insert_resource(config)
+            .insert_resource(hooks)
+            .init_resource::<Heightmap>()
+            .init_resource::<LightWorld>()
+            .init_resource::<StreamingConfig>()
+            .init_resource::<streaming::PendingUnloads>()
+            .init_resource::<streaming::EditedChunks>()
+            .init_resource::<WorldMeta>()
+            .init_resource::<RandomTickConfig>()
+            .init_resource::<UpdateSchedule>()
+            .init_resource::<SignalNetwork>()
+            .init_resource::<BlockEntities>()
+            .init_resource::<PendingVoxels>()
+            .init_resource::<SimulationControl>()
+            .add_event::<ChunkUpdated>()
+            .add_event::<VoxelsUpdated>()
+            .add_event::<ChunkLoaded>()
+            .add_event::<ChunkUnloaded>()
+            .add_event::<RecenterStreaming>()
+            .add_event::<SetVoxel>()
+            .add_event::<BlockEntityRemoved>()
+            .add_event::<BlockPlaced>()
+            .add_event::<BlockBroken>()
+            .add_event::<BlockDecayed>()
+            .add_event::<PlayerMoved>()
+            .add_event::<Explode>()
+            .add_event::<Exploded>()
+            .add_event::<LightFuse>()
+            .add_event::<FuseLit>()
+            .init_resource::<WorldOrigin>()
+            .add_event::<OriginShifted>()
+            .add_startup_system(check_block_entities)
+            .add_system(origin::rebase_origin.before(streaming::stream_chunks))
+            .add_system(streaming::stream_chunks.before(process_genesis_results))
+            .add_system(process_genesis_results)
+            .add_system(track_players.after(origin::rebase_origin))
+            .add_system(light_fuses.before(queue_set_voxels))
+            .add_system(process_explosions.before(queue_set_voxels))
+            .add_system(queue_set_voxels)
+            .add_system(load_schedule.after(process_genesis_results))
+            .add_system(load_signals.after(process_genesis_results))
+            .add_system(load_block_entities.after(process_genesis_results))
+            .add_system(unload_light.after(streaming::stream_chunks))
+            .add_system(unload_schedule.after(streaming::stream_chunks))
+            .add_system(unload_signals.after(streaming::stream_chunks))
+            .add_system(unload_block_entities.after(streaming::stream_chunks))
+            .add_stage_after(
+                CoreStage::Update,
+                SimulationStage,
+                SystemStage::parallel()
+                    .with_run_criteria(simulation::simulation_tick)
+                    .with_system(play_replay.before(record_voxels))
+                    .with_system(record_voxels.before(process_set_voxels))
+                    .with_system(process_set_voxels)
+                    .with_system(random_tick.after(process_set_voxels))
+                    .with_system(fire_scheduled_updates.after(process_set_voxels))
+                    .with_system(schedule_decay.after(process_set_voxels))
+                    .with_system(update_signals.after(process_set_voxels))
+                    .with_system(tick_block_entities.after(process_set_voxels)),
+            );
+    }
+}
+
+fn process_genesis_results(
+    world: Res<VoxWorld>,
+    registry: Res<KindRegistry>,
+    mut heightmap: ResMut<Heightmap>,
+    mut light: ResMut<LightWorld>,
+    mut workers: ResMut<GenesisWorkers>,
+    mut writer: EventWriter<ChunkUpdated>,
+    mut loaded_writer: EventWriter<ChunkLoaded>,
+) {
+    for result in workers.drain_finished() {
+        loaded_writer.send(ChunkLoaded(result.local));
+
+        for local in result.dirty_chunks {
+            if genesis::update_chunk(&world, local) {
+                writer.send(ChunkUpdated(local));
+            }
+        }
+
+        let stale = match world.get(result.local) {
+            Some(kind) => heightmap.load(result.local, &kind, &registry),
+            None => continue,
+        };
+
+        light.load(&world, &registry, &heightmap, result.local);
+
+        // Chunks below the new one may have lost, or gained, sky light
+        let stale = stale
+            .into_iter()
+            .filter(|local| *local != result.local && light.is_lit(*local))
+            .collect::<Vec<_>>();
+
+        if !stale.is_empty() {
+            light.relight(&world, &registry, &heightmap, stale);
+        }
+    }
+}
+
+fn light_fuses(
+    world: Res<VoxWorld>,
+    registry: Res<KindRegistry>,
+    mut scheduled: ResMut<UpdateSchedule>,
+    mut reader: EventReader<LightFuse>,
+    mut writer: EventWriter<SetVoxel>,
+    mut lit_writer: EventWriter<FuseLit>,
+) {
+    let mut lit = std::collections::HashSet::new();
+
+    for LightFuse { chunk, voxel } in reader.iter() {
+        let kind = match world.get_voxel(chunk::join_voxel(*chunk, *voxel)) {
+            Some(kind) => kind,
+            None => continue,
+        };
+
+        // Fuses lit twice before the voxel is removed would blow up twice
+        let explosive = match registry.explosive(kind) {
+            Some(explosive) if lit.insert((*chunk, *voxel)) => explosive,
+            _ => continue,
+        };
+
+        scheduled.schedule(
+            *chunk,
+            *voxel,
+            explosive.fuse,
+            ScheduledEvent::Explode(kind),
+        );
+
+        writer.send(SetVoxel {
+            chunk: *chunk,
+            voxel: *voxel,
+            kind: Default::default(),
+        });
+        lit_writer.send(FuseLit {
+            chunk: *chunk,
+            voxel: *voxel,
+            kind,
+            ticks: explosive.fuse,
+        });
+    }
+}
+
+/**
+  Blows up the world for each [`Explode`]. Explosive voxels caught in it aren't destroyed, but get their fuse
+  lit instead, so they blow up a bit later.
+*/
+fn process_explosions(
+    world: Res<VoxWorld>,
+    registry: Res<KindRegistry>,
+    mut scheduled: ResMut<UpdateSchedule>,
+    mut reader: EventReader<Explode>,
+    mut writer: EventWriter<SetVoxel>,
+    mut exploded_writer: EventWriter<Exploded>,
+    mut lit_writer: EventWriter<FuseLit>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for Explode(explosion) in reader.iter() {
+        let (lit, destroyed): (Vec<_>, Vec<_>) = explosion::explode(&world, &registry, explosion)
+            .into_iter()
+            .partition(|(_, kind)| registry.explosive(*kind).is_some());
+
+        let edits = edit::set_all(
+            &world,
+            lit.iter()
+                .chain(destroyed.iter())
+                .map(|(position, _)| (*position, voxel::Kind::default())),
+        );
+        writer.send_batch(edits.into_iter());
+
+        for (position, kind) in lit {
+            let ticks = match registry.explosive(kind) {
+                Some(explosive) => explosive.chain_fuse(&mut rng),
+                None => continue,
+            };
+
+            let (chunk, voxel) = chunk::split_voxel(position);
+            scheduled.schedule(chunk, voxel, ticks, ScheduledEvent::Explode(kind));
+            lit_writer.send(FuseLit {
+                chunk,
+                voxel,
+                kind,
+                ticks,
+            });
+        }
+
+        exploded_writer.send(Exploded {
+            explosion: *explosion,
+            destroyed,
+        });
+    }
+}
+
+/**
+  Keeps voxel changes until the next simulation tick. Events only live for two frames, so the simulation,
+  which may skip frames, can't read them directly.
+*/
+fn queue_set_voxels(mut pending: ResMut<PendingVoxels>, mut reader: EventReader<SetVoxel>) {
+    pending.0.extend(reader.iter().copied());
+}
+
+/// Feeds the voxel changes of the replay being played, if any, to the simulation.
+fn play_replay(
+    scheduled: Res<UpdateSchedule>,
+    player: Option<ResMut<ReplayPlayer>>,
+    mut pending: ResMut<PendingVoxels>,
+) {
+    if let Some(mut player) = player {
+        pending.0.extend(player.due(scheduled.tick()));
+    }
+}
+
+fn record_voxels(
+    scheduled: Res<UpdateSchedule>,
+    pending: Res<PendingVoxels>,
+    recorder: Option<ResMut<ReplayRecorder>>,
+) {
+    if let Some(mut recorder) = recorder {
+        if !pending.0.is_empty() {
+            recorder.record(scheduled.tick(), &pending.0);
+        }
+    }
+}
+
+/**
+  Sends [`PlayerMoved`] when anchors move to another voxel. Anchors are only tracked from the frame they're
+  first seen, so spawning doesn't count as moving.
+*/
+fn track_players(
+    origin: Res<WorldOrigin>,
+    mut last: Local<bevy::utils::HashMap<Entity, IVec3>>,
+    mut writer: EventWriter<PlayerMoved>,
+    q: Query<(Entity, &GlobalTransform), With<StreamingAnchor>>,
+) {
+    let mut current = bevy::utils::HashMap::default();
+
+    for (entity, transform) in q.iter() {
+        let to = origin.to_voxel(transform.translation);
+
+        if let Some(&from) = last.get(&entity).filter(|from| **from != to) {
+            writer.send(PlayerMoved { entity, from, to });
+        }
+
+        current.insert(entity, to);
+    }
+
+    // Despawned anchors are forgotten
+    *last = current;
+}
+
+#[allow(clippy::too_many_arguments)]
+fn process_set_voxels(
+    world: Res<VoxWorld>,
+    registry: Res<KindRegistry>,
+    mut heightmap: ResMut<Heightmap>,
+    mut light: ResMut<LightWorld>,
+    mut signals: ResMut<SignalNetwork>,
+    mut block_entities: ResMut<BlockEntities>,
+    mut pending: ResMut<PendingVoxels>,
+    mut edited: ResMut<streaming::EditedChunks>,
+    mut writer: EventWriter<ChunkUpdated>,
+    mut voxels_writer: EventWriter<VoxelsUpdated>,
+    mut removed_writer: EventWriter<BlockEntityRemoved>,
+    mut placed_writer: EventWriter<BlockPlaced>,
+    mut broken_writer: EventWriter<BlockBroken>,
+) {
+    let mut dirty_chunks = std::collections::HashMap::<_, Vec<_>>::new();
+    let mut edited_chunks = std::collections::HashSet::new();
+
+    for SetVoxel { chunk, voxel, kind } in pending.0.drain(..) {
+        // Changes to chunks which aren't loaded are dropped, so there is nothing to tell about them
+        if let Some(replaced) = world.get_voxel(chunk::join_voxel(chunk, voxel)) {
+            if !kind.is_empty() {
+                placed_writer.send(BlockPlaced {
+                    chunk,
+                    voxel,
+                    kind,
+                    replaced,
+                });
+            } else if !replaced.is_empty() {
+                broken_writer.send(BlockBroken {
+                    chunk,
+                    voxel,
+                    kind: replaced,
+                });
+            }
+        }
+
+        for local in genesis::update_voxel(&world, chunk, &[(voxel, kind)]) {
+            let offset = (chunk - local) * chunk::AXIS_SIZE as i32;
+            dirty_chunks.entry(local).or_default().push(voxel + offset);
+        }
+        edited_chunks.insert(chunk);
+        signals.mark_dirty(chunk::join_voxel(chunk, voxel));
+
+        if world.exists(chunk) {
+            edited.mark(chunk);
+
+            if let Some(entity) = block_entities.sync(chunk, voxel, registry.block_entity(kind)) {
+                removed_writer.send(BlockEntityRemoved {
+                    chunk,
+                    voxel,
+                    entity,
+                });
+            }
+        }
+
+        if let Some(kind) = world.get(chunk) {
+            edited_chunks.extend(heightmap.update(chunk, voxel, &kind, &registry));
+        }
+    }
+
+    if !edited_chunks.is_empty() {
+        light.relight(&world, &registry, &heightmap, edited_chunks);
+    }
+
+    for (local, voxels) in dirty_chunks {
+        if genesis::update_chunk(&world, local) {
+            writer.send(ChunkUpdated(local));
+            voxels_writer.send(VoxelsUpdated {
+                chunk: local,
+                voxels,
+            });
+        }
+    }
+}
+
+fn unload_light(
+    mut heightmap: ResMut<Heightmap>,
+    mut light: ResMut<LightWorld>,
+    mut reader: EventReader<ChunkUnloaded>,
+) {
+    for ChunkUnloaded(local) in reader.iter() {
+        // Chunks below keep their current sky light, they are only relit when something changes around them
+        heightmap.unload(*local);
+        light.unload(*local);
+    }
+}
+
+/// Voxels without support found by random ticks are scheduled to decay, so they keep checking periodically.
+fn random_tick(
+    world: Res<VoxWorld>,
+    registry: Res<KindRegistry>,
+    config: Res<RandomTickConfig>,
+    mut scheduled: ResMut<UpdateSchedule>,
+    mut writer: EventWriter<SetVoxel>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for position in tick::random_positions(&world, &config, &mut rng) {
+        if decay::should_decay(&world, &registry, position) {
+            let (chunk, voxel) = chunk::split_voxel(position);
+            scheduled.schedule(chunk, voxel, decay::delay(&mut rng), ScheduledEvent::Decay);
+            continue;
+        }
+
+        let edits = tick::tick_voxel(&world, &registry, position, &mut rng);
+        writer.send_batch(edits.into_iter());
+    }
+}
+
+/**
+  Schedules a decay check on voxels which may have lost their support, like leaves around a log which was
+  broken. Each one is checked again when fired, so voxels still supported by another log stay.
+*/
+fn schedule_decay(
+    world: Res<VoxWorld>,
+    registry: Res<KindRegistry>,
+    mut scheduled: ResMut<UpdateSchedule>,
+    mut reader: EventReader<BlockBroken>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for BlockBroken { chunk, voxel, kind } in reader.iter() {
+        let position = chunk::join_voxel(*chunk, *voxel);
+
+        for position in decay::around(&world, &registry, position, *kind) {
+            let (chunk, voxel) = chunk::split_voxel(position);
+            scheduled.schedule(chunk, voxel, decay::delay(&mut rng), ScheduledEvent::Decay);
+        }
+    }
+}
+
+fn load_signals(
+    world: Res<VoxWorld>,
+    registry: Res<KindRegistry>,
+    mut signals: ResMut<SignalNetwork>,
+    mut reader: EventReader<ChunkLoaded>,
+) {
+    for ChunkLoaded(local) in reader.iter() {
+        if let Some(kind) = world.get(*local) {
+            signals.load(*local, &kind, &registry);
+        }
+    }
+}
+
+fn update_signals(
+    world: Res<VoxWorld>,
+    registry: Res<KindRegistry>,
+    mut signals: ResMut<SignalNetwork>,
+    mut writer: EventWriter<SetVoxel>,
+) {
+    // Actuators changes are applied on the next tick, like any other voxel change
+    let edits = signals.update(&world, &registry);
+    writer.send_batch(edits.into_iter());
+}
+
+fn unload_signals(mut signals: ResMut<SignalNetwork>, mut reader: EventReader<ChunkUnloaded>) {
+    for ChunkUnloaded(local) in reader.iter() {
+        signals.unload(*local);
+    }
+}
+
+fn load_schedule(mut scheduled: ResMut<UpdateSchedule>, mut reader: EventReader<ChunkLoaded>) {
+    for ChunkLoaded(local) in reader.iter() {
+        let updates = schedule::load(&schedule::local_path(*local));
+        scheduled.load(*local, updates);
+    }
+}
+
+fn fire_scheduled_updates(
+    world: Res<VoxWorld>,
+    registry: Res<KindRegistry>,
+    mut scheduled: ResMut<UpdateSchedule>,
+    mut writer: EventWriter<SetVoxel>,
+    mut explode_writer: EventWriter<Explode>,
+    mut decayed_writer: EventWriter<BlockDecayed>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for (local, voxel, event) in scheduled.advance() {
+        match event {
+            ScheduledEvent::Set(kind) => writer.send(SetVoxel {
+                chunk: local,
+                voxel,
+                kind,
+            }),
+            ScheduledEvent::Tick => {
+                let position = chunk::join_voxel(local, voxel);
+                let edits = tick::tick_voxel(&world, &registry, position, &mut rng);
+                writer.send_batch(edits.into_iter());
+            }
+            ScheduledEvent::Explode(kind) => {
+                if let Some(explosive) = registry.explosive(kind) {
+                    let position = chunk::join_voxel(local, voxel);
+                    explode_writer.send(Explode(explosive.explosion(position)));
+                }
+            }
+            ScheduledEvent::Decay => {
+                let position = chunk::join_voxel(local, voxel);
+
+                // A support may have been placed back, or the voxel removed, since it was scheduled
+                let kind = match world.get_voxel(position) {
+                    Some(kind) if decay::should_decay(&world, &registry, position) => kind,
+                    _ => continue,
+                };
+
+                decayed_writer.send(BlockDecayed {
+                    chunk: local,
+                    voxel,
+                    kind,
+                });
+                writer.send(SetVoxel {
+                    chunk: local,
+                    voxel,
+                    kind: 0.into(),
+                });
+            }
+        }
+    }
+}
+
+fn unload_schedule(mut scheduled: ResMut<UpdateSchedule>, mut reader: EventReader<ChunkUnloaded>) {
+    for ChunkUnloaded(local) in reader.iter() {
+        let updates = scheduled.unload(*local);
+        schedule::save(&schedule::local_path(*local), &updates);
+    }
+}
+
+/**
+  Checks all kinds refer to registered block entity types, since typos would silently leave them without one.
+*/
+fn check_block_entities(registry: Res<KindRegistry>, block_entities: Res<BlockEntities>) {
+    for desc in registry.descriptions() {
+        if let Some(name) = &desc.block_entity {
+            assert!(
+                block_entities.is_registered(name),
+                "Kind {} uses unknown block entity type {}",
+                desc.name,
+                name
+            );
+        }
+    }
+}
+
+fn tick_block_entities(
+    world: Res<VoxWorld>,
+    mut block_entities: ResMut<BlockEntities>,
+    mut writer: EventWriter<SetVoxel>,
+) {
+    // Changes are applied on the next tick, like any other voxel change
+    let edits = block_entities
+        .tick(&world)
+        .into_iter()
+        .map(|(chunk, voxel, kind)| SetVoxel { chunk, voxel, kind });
+
+    writer.send_batch(edits);
+}
+
+fn load_block_entities(
+    world: Res<VoxWorld>,
+    registry: Res<KindRegistry>,
+    mut block_entities: ResMut<BlockEntities>,
+    mut reader: EventReader<ChunkLoaded>,
+) {
+    for ChunkLoaded(local) in reader.iter() {
+        let saved = block_entity::load(&block_entity::local_path(*local));
+        block_entities.load(*local, saved, &world, &registry);
+    }
+}
+
+fn unload_block_entities(
+    mut block_entities: ResMut<BlockEntities>,
+    mut reader: EventReader<ChunkUnloaded>,
+) {
+    for ChunkUnloaded(local) in reader.iter() {
+        let saved = block_entities.unload(*local);
+        block_entity::save(&block_entity::local_path(*local), &saved);
+    }
+}
@@ -0,0 +1,175 @@
+use bevy::prelude::*;
+
+use crate::{
+    bounds::{self, Bounds},
+    chunk,
+};
+
+use super::StreamingCenter;
+
+/// How far, in voxels, the streaming center can move away from the origin before the world is shifted.
+const REBASE_DISTANCE: f32 = 512.0;
+
+/**
+  Floating origin. Holds which chunk is placed at (0, 0, 0) of the render space, so entities near the
+  streaming center always have small `Transform` values and don't suffer from `f32` precision loss.
+
+  Any `Transform` translation is relative to this origin. Use [`WorldOrigin::to_local`] and
+  [`WorldOrigin::to_render`] to convert between chunk coordinates and render space.
+*/
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct WorldOrigin(pub IVec3);
+
+impl WorldOrigin {
+    /// Returns the chunk local which contains the given render space position.
+    pub fn to_local(&self, render: Vec3) -> IVec3 {
+        chunk::to_local(render) + self.0
+    }
+
+    /// Returns the render space position of the given chunk.
+    pub fn to_render(&self, local: IVec3) -> Vec3 {
+        chunk::to_world(local - self.0)
+    }
+
+    /// Returns the render space position of the given world position.
+    pub fn from_world(&self, world: Vec3) -> Vec3 {
+        let local = chunk::to_local(world);
+        self.to_render(local) + (world - chunk::to_world(local))
+    }
+
+    /// Returns the render space bounds of the given chunk.
+    pub fn chunk_bounds(&self, local: IVec3) -> Bounds {
+        bounds::chunk(local - self.0)
+    }
+
+    /// Returns the render space bounds of the given voxel inside the chunk at `local`.
+    pub fn voxel_bounds(&self, local: IVec3, voxel: IVec3) -> Bounds {
+        bounds::voxel(chunk::join_voxel(local - self.0, voxel))
+    }
+
+    /// Returns the world voxel which contains the given render space position, without losing precision.
+    pub fn to_voxel(&self, render: Vec3) -> IVec3 {
+        chunk::join_voxel(self.0, render.floor().as_ivec3())
+    }
+
+    /**
+      Returns the world position of the given render space position.
+      Should only be used for displaying, since it'll lose precision far from origin.
+    */
+    pub fn to_world(&self, render: Vec3) -> Vec3 {
+        chunk::to_world(self.0) + render
+    }
+}
+
+/// Sent when the [`WorldOrigin`] moves. Contains how much render space positions were moved.
+pub struct OriginShifted(pub Vec3);
+
+/**
+  Returns by how many chunks the origin must be shifted, if the center is too far away.
+*/
+pub fn rebase_offset(center: Vec3) -> Option<IVec3> {
+    if center.abs().max_element() < REBASE_DISTANCE {
+        None
+    } else {
+        Some(chunk::to_local(center))
+    }
+}
+
+/**
+  Shifts the origin when the streaming center goes too far. All root entities in world space are moved,
+  while UI entities are kept in place.
+*/
+#[allow(clippy::type_complexity)]
+pub(super) fn rebase_origin(
+    mut origin: ResMut<WorldOrigin>,
+    mut entities: Query<
+        (
+            &mut Transform,
+            Option<&mut GlobalTransform>,
+            Option<&StreamingCenter>,
+        ),
+        (Without<Parent>, Without<Node>, Without<CameraUi>),
+    >,
+    mut writer: EventWriter<OriginShifted>,
+) {
+    let offset = entities
+        .iter()
+        .find(|(_, _, center)| center.is_some())
+        .and_then(|(transform, _, _)| rebase_offset(transform.translation));
+
+    let offset = match offset {
+        Some(offset) => offset,
+        None => return,
+    };
+
+    origin.0 += offset;
+
+    let shift = -chunk::to_world(offset);
+
+    for (mut transform, global_transform, _) in entities.iter_mut() {
+        transform.translation += shift;
+
+        // Keep global transform in sync, so systems running before transform propagation sees the new position
+        if let Some(mut global_transform) = global_transform {
+            global_transform.translation += shift;
+        }
+    }
+
+    writer.send(OriginShifted(shift));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_local() {
+        let origin = WorldOrigin((1_000_000, 0, -1_000_000).into());
+
+        assert_eq!(origin.to_local(Vec3::ZERO), origin.0);
+        assert_eq!(
+            origin.to_local(Vec3::new(-0.5, 20.0, 16.0)),
+            origin.0 + IVec3::new(-1, 1, 1)
+        );
+    }
+
+    #[test]
+    fn to_render() {
+        let origin = WorldOrigin((1_000_000, 0, -1_000_000).into());
+
+        assert_eq!(origin.to_render(origin.0), Vec3::ZERO);
+        assert_eq!(
+            origin.to_render(origin.0 + IVec3::new(1, -1, 2)),
+            Vec3::new(1.0, -1.0, 2.0) * chunk::AXIS_SIZE as f32
+        );
+
+        let local = origin.0 + IVec3::new(-3, 2, 5);
+        assert_eq!(origin.to_local(origin.to_render(local)), local);
+    }
+
+    #[test]
+    fn from_world() {
+        let origin = WorldOrigin((10, 0, -10).into());
+        let world = Vec3::new(165.5, 3.25, -150.0);
+
+        let render = origin.from_world(world);
+        assert_eq!(render, Vec3::new(5.5, 3.25, 10.0));
+        assert_eq!(origin.to_world(render), world);
+        assert_eq!(origin.to_voxel(render), IVec3::new(165, 3, -150));
+    }
+
+    #[test]
+    fn rebase_offset() {
+        assert_eq!(super::rebase_offset(Vec3::ZERO), None);
+        assert_eq!(super::rebase_offset(Vec3::new(100.0, -300.0, 500.0)), None);
+
+        let center = Vec3::new(REBASE_DISTANCE + 1.0, -5.0, 3.0);
+        let offset = super::rebase_offset(center).unwrap();
+
+        assert_eq!(offset, chunk::to_local(center));
+
+        // After shifting, the center must be back within the first chunk
+        let shifted = center - chunk::to_world(offset);
+        assert_eq!(chunk::to_local(shifted), IVec3::ZERO);
+    }
+}
@@ -0,0 +1,143 @@
+use bevy::prelude::*;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::chunk::ChunkKind;
+
+/// How long each post process may take on a single chunk, by default.
+const DEFAULT_TIME_LIMIT: Duration = Duration::from_millis(50);
+
+/**
+  Changes freshly generated chunks, before they're cached, like carving custom structures. Post processes
+  run on genesis workers, so they must be thread safe, and they only see the chunk being generated.
+
+  This is the extension point for world scripts, like the Lua ones of the `lua` feature, which run the script
+  of the world against each chunk.
+*/
+pub trait PostProcess: Send + Sync {
+    fn name(&self) -> &str;
+
+    /**
+      Changes the given chunk. Processes should check the deadline on long loops and give up once it's
+      reached, since late results are discarded anyway.
+    */
+    fn process(&self, local: IVec3, kind: &mut ChunkKind, deadline: Instant) -> Result<(), String>;
+}
+
+/**
+  Post processes applied, in order, to every generated chunk. Insert it before [`super::PipelinePlugin`],
+  like [`super::GenesisConfig`], since workers are started with it.
+
+  Each process works on a copy of the chunk, which is only kept when it succeeds within the time limit. This
+  way a broken or slow process can't leave chunks half changed.
+*/
+#[derive(Clone)]
+pub struct GenesisHooks {
+    processes: Vec<Arc<dyn PostProcess>>,
+    pub time_limit: Duration,
+}
+
+impl Default for GenesisHooks {
+    fn default() -> Self {
+        Self {
+            processes: vec![],
+            time_limit: DEFAULT_TIME_LIMIT,
+        }
+    }
+}
+
+impl GenesisHooks {
+    pub fn add(&mut self, process: impl PostProcess + 'static) {
+        self.processes.push(Arc::new(process));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.processes.is_empty()
+    }
+
+    pub fn apply(&self, local: IVec3, kind: &mut ChunkKind) {
+        for process in &self.processes {
+            let start = Instant::now();
+            let mut processed = kind.clone();
+
+            match process.process(local, &mut processed, start + self.time_limit) {
+                Ok(()) if start.elapsed() <= self.time_limit => *kind = processed,
+                Ok(()) => warn!(
+                    "Post process {} took too long on chunk {}, discarding it",
+                    process.name(),
+                    local
+                ),
+                Err(err) => warn!(
+                    "Post process {} failed on chunk {} ({}), discarding it",
+                    process.name(),
+                    local,
+                    err
+                ),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Places the given kind on the chunk origin, after waiting for the given time.
+    struct Pillar {
+        kind: u16,
+        delay: Duration,
+    }
+
+    impl PostProcess for Pillar {
+        fn name(&self) -> &str {
+            "pillar"
+        }
+
+        fn process(&self, _: IVec3, kind: &mut ChunkKind, _: Instant) -> Result<(), String> {
+            kind.set(IVec3::ZERO, self.kind.into());
+            std::thread::sleep(self.delay);
+            Ok(())
+        }
+    }
+
+    struct Broken;
+
+    impl PostProcess for Broken {
+        fn name(&self) -> &str {
+            "broken"
+        }
+
+        fn process(&self, _: IVec3, kind: &mut ChunkKind, _: Instant) -> Result<(), String> {
+            kind.set(IVec3::ONE, 1.into());
+            Err("script error".to_string())
+        }
+    }
+
+    #[test]
+    fn apply() {
+        let mut hooks = GenesisHooks {
+            time_limit: Duration::from_millis(20),
+            ..Default::default()
+        };
+        assert!(hooks.is_empty());
+
+        hooks.add(Pillar {
+            kind: 3,
+            delay: Duration::ZERO,
+        });
+        hooks.add(Broken);
+        hooks.add(Pillar {
+            kind: 4,
+            delay: Duration::from_millis(50),
+        });
+
+        let mut kind = ChunkKind::default();
+        hooks.apply(IVec3::ZERO, &mut kind);
+
+        // Only the process which succeeded in time was kept
+        assert_eq!(kind.get(IVec3::ZERO), 3.into());
+        assert!(kind.get(IVec3::ONE).is_empty());
+    }
+}
@@ -0,0 +1,141 @@
+use bevy::{ecs::schedule::ShouldRun, prelude::*};
+
+use super::TICK_STEP;
+
+/**
+  Controls when [`super::SimulationStage`] runs. The simulation ticks on a fixed rate, unless it's paused,
+  in which case it only advances by explicitly requested steps.
+*/
+#[derive(Debug, Default)]
+pub struct SimulationControl {
+    paused: bool,
+    /// Ticks requested while paused, which are run one per frame.
+    steps: u32,
+    /// Time, in seconds, not simulated yet.
+    accumulator: f64,
+    /// Whether the stage is running the ticks accumulated on this frame.
+    looping: bool,
+}
+
+impl SimulationControl {
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Resumes the simulation. Time spent paused isn't simulated.
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.steps = 0;
+        self.accumulator = 0.0;
+    }
+
+    /// Runs the given number of ticks, one per frame, while paused. Does nothing when not paused.
+    pub fn step(&mut self, count: u32) {
+        if self.paused {
+            self.steps = self.steps.saturating_add(count);
+        }
+    }
+
+    /**
+      How far, from 0 to 1, the frame is between the last tick and the next one. Visuals of things moved by
+      the simulation can use it to interpolate between their last two ticks.
+    */
+    pub fn overstep(&self) -> f64 {
+        (self.accumulator / TICK_STEP).min(1.0)
+    }
+
+    /**
+      Decides if a tick must run, after the given seconds elapsed since the last frame. It's called again,
+      without elapsed time, after each tick which ran, so late frames run many ticks.
+    */
+    pub fn advance(&mut self, elapsed: f64) -> ShouldRun {
+        if self.paused {
+            self.accumulator = 0.0;
+
+            return if self.steps > 0 {
+                self.steps -= 1;
+                ShouldRun::Yes
+            } else {
+                ShouldRun::No
+            };
+        }
+
+        if !self.looping {
+            self.accumulator += elapsed;
+        }
+
+        if self.accumulator >= TICK_STEP {
+            self.accumulator -= TICK_STEP;
+            self.looping = true;
+            ShouldRun::YesAndCheckAgain
+        } else {
+            self.looping = false;
+            ShouldRun::No
+        }
+    }
+}
+
+pub(super) fn simulation_tick(
+    time: Res<Time>,
+    mut control: ResMut<SimulationControl>,
+) -> ShouldRun {
+    control.advance(time.delta_seconds_f64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs a frame, returning how many ticks ran.
+    fn frame(control: &mut SimulationControl, elapsed: f64) -> usize {
+        let mut ticks = 0;
+        let mut elapsed = elapsed;
+
+        loop {
+            match control.advance(elapsed) {
+                ShouldRun::Yes => return ticks + 1,
+                ShouldRun::YesAndCheckAgain => ticks += 1,
+                _ => return ticks,
+            }
+
+            elapsed = 0.0;
+        }
+    }
+
+    #[test]
+    fn fixed_rate() {
+        let mut control = SimulationControl::default();
+
+        assert_eq!(frame(&mut control, TICK_STEP / 2.0), 0);
+        assert!((control.overstep() - 0.5).abs() < 1e-6);
+        assert_eq!(frame(&mut control, TICK_STEP / 2.0), 1);
+
+        // A slow frame catches up
+        assert_eq!(frame(&mut control, TICK_STEP * 3.5), 3);
+    }
+
+    #[test]
+    fn pause_step() {
+        let mut control = SimulationControl::default();
+
+        // Steps are ignored while running
+        control.step(2);
+        control.pause();
+        assert!(control.is_paused());
+        assert_eq!(frame(&mut control, TICK_STEP * 10.0), 0);
+
+        control.step(2);
+        assert_eq!(frame(&mut control, 0.0), 1);
+        assert_eq!(frame(&mut control, 0.0), 1);
+        assert_eq!(frame(&mut control, TICK_STEP * 10.0), 0);
+
+        // Time spent paused isn't simulated
+        control.resume();
+        assert_eq!(frame(&mut control, TICK_STEP / 2.0), 0);
+        assert_eq!(frame(&mut control, TICK_STEP / 2.0), 1);
+    }
+}
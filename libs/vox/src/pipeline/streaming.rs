@@ -0,0 +1,366 @@
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+use crate::{meta::WorldMeta, query, world::VoxWorld};
+
+use super::{
+    genesis, ChunkUnloaded, ChunkUpdated, GenesisWorkers, RecenterStreaming, RequestError,
+    WorldOrigin,
+};
+
+/// How much further chunks behind the view are considered, when compared to chunks in front of it.
+const VIEW_WEIGHT: f32 = 2.0;
+
+/// Chunks closer than this are always loaded first, no matter where the view is pointing to.
+const NEAR_DISTANCE: f32 = 1.5;
+
+/**
+  Marks the entity the world is viewed from, usually the camera. The world origin follows it, so it must
+  also have a [`StreamingAnchor`] to keep the chunks around it loaded.
+*/
+#[derive(Component, Default)]
+pub struct StreamingCenter;
+
+/**
+  Keeps chunks loaded around the entity, like players, spectator cameras or remote players on the server.
+  Chunks are kept while they're inside any anchor, and each anchor loads the chunks it's facing first.
+*/
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct StreamingAnchor {
+    /// Radius, in chunks, kept loaded around the entity. When none, [`StreamingConfig::radius`] is used.
+    pub radius: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct StreamingConfig {
+    /// Radius, in chunks, which will be kept loaded around anchors without their own radius.
+    pub radius: u32,
+    /**
+      Seconds chunks are kept loaded after leaving every anchor, so moving back and forth along the edge of
+      the radius doesn't unload and load the same chunks over and over.
+    */
+    pub unload_delay: f64,
+}
+
+impl Default for StreamingConfig {
+    fn default() -> Self {
+        Self {
+            radius: 8,
+            unload_delay: 5.0,
+        }
+    }
+}
+
+/**
+  Loaded chunks changed since they were generated or loaded from their cache. Their cache is replaced when
+  they're unloaded, so the changes are there once they're loaded again.
+*/
+#[derive(Debug, Default)]
+pub(super) struct EditedChunks(HashSet<IVec3>);
+
+impl EditedChunks {
+    pub fn mark(&mut self, local: IVec3) {
+        self.0.insert(local);
+    }
+
+    /// Forgets the given chunk, returning if it was edited.
+    pub fn take(&mut self, local: IVec3) -> bool {
+        self.0.remove(&local)
+    }
+}
+
+/**
+  Loaded chunks which aren't kept by anything anymore, with when they stopped being kept, in seconds. They're
+  unloaded together once their delay is over, unless something keeps them again before that.
+*/
+#[derive(Debug, Default)]
+pub(super) struct PendingUnloads(HashMap<IVec3, f64>);
+
+impl PendingUnloads {
+    /// Starts the delay of the given chunk, unless it's already pending.
+    pub fn mark(&mut self, local: IVec3, now: f64) {
+        self.0.entry(local).or_insert(now);
+    }
+
+    /// Cancels the unloading of the given chunk, since it's kept again.
+    pub fn keep(&mut self, local: IVec3) {
+        self.0.remove(&local);
+    }
+
+    /// Removes and returns the chunks which were pending for at least `delay` seconds at `now`.
+    pub fn drain_due(&mut self, now: f64, delay: f64) -> Vec<IVec3> {
+        let due = self
+            .0
+            .iter()
+            .filter(|(_, since)| now - **since >= delay)
+            .map(|(local, _)| *local)
+            .collect::<Vec<_>>();
+
+        for local in &due {
+            self.0.remove(local);
+        }
+
+        due
+    }
+}
+
+/**
+  Computes the loading priority of the given chunk. Lower values should be loaded first.
+
+  The priority is the chunk distance to center, weighted by how aligned it is with the view direction,
+  so a chunk right behind the center is treated as if it was `1 + VIEW_WEIGHT` times further.
+*/
+pub fn priority(center: IVec3, view_dir: Vec3, local: IVec3) -> f32 {
+    let offset = (local - center).as_vec3();
+    let distance = offset.length();
+
+    if distance <= NEAR_DISTANCE {
+        return distance;
+    }
+
+    let alignment = offset.dot(view_dir.normalize_or_zero()) / distance;
+
+    distance * (1.0 + VIEW_WEIGHT * (1.0 - alignment) / 2.0)
+}
+
+/// Where a [`StreamingAnchor`] is, in chunks, how far it reaches and where it's facing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Anchor {
+    pub center: IVec3,
+    pub radius: u32,
+    pub view_dir: Vec3,
+}
+
+impl Anchor {
+    pub fn contains(&self, local: IVec3) -> bool {
+        let dist = local - self.center;
+        dist.dot(dist) <= (self.radius * self.radius) as i32
+    }
+}
+
+/// Checks if the given chunk is inside any of the given anchors.
+pub fn is_anchored(anchors: &[Anchor], local: IVec3) -> bool {
+    anchors.iter().any(|anchor| anchor.contains(local))
+}
+
+/**
+  Returns every chunk inside any of the given anchors, without duplicates, sorted by the best priority
+  any anchor gives it.
+*/
+pub fn anchored_chunks(anchors: &[Anchor]) -> Vec<IVec3> {
+    let mut seen = HashSet::default();
+
+    let mut chunks = anchors
+        .iter()
+        .flat_map(|anchor| query::sphere(anchor.center, anchor.radius))
+        .filter(|local| seen.insert(*local))
+        .map(|local| {
+            let best = anchors
+                .iter()
+                .filter(|anchor| anchor.contains(local))
+                .map(|anchor| priority(anchor.center, anchor.view_dir, local))
+                .fold(f32::MAX, f32::min);
+
+            (local, best)
+        })
+        .collect::<Vec<_>>();
+
+    chunks.sort_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    chunks.into_iter().map(|(local, _)| local).collect()
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(super) fn stream_chunks(
+    time: Res<Time>,
+    config: Res<StreamingConfig>,
+    world: Res<VoxWorld>,
+    origin: Res<WorldOrigin>,
+    meta: Res<WorldMeta>,
+    mut workers: ResMut<GenesisWorkers>,
+    mut pending: ResMut<PendingUnloads>,
+    mut edited: ResMut<EditedChunks>,
+    q: Query<(&GlobalTransform, &StreamingAnchor)>,
+    mut updated_writer: EventWriter<ChunkUpdated>,
+    mut unloaded_writer: EventWriter<ChunkUnloaded>,
+    mut recenter_reader: EventReader<RecenterStreaming>,
+) {
+    if recenter_reader.iter().count() > 0 {
+        workers.cancel_queued();
+    }
+
+    let anchors = q
+        .iter()
+        .map(|(transform, anchor)| Anchor {
+            center: origin.to_local(transform.translation),
+            radius: anchor.radius.unwrap_or(config.radius),
+            view_dir: transform.forward(),
+        })
+        .collect::<Vec<_>>();
+
+    // Nothing is unloaded until there's something to keep loaded, since the world may not be set up yet
+    if anchors.is_empty() && meta.tickets.is_empty() {
+        return;
+    }
+
+    let now = time.seconds_since_startup();
+
+    for local in world.locals() {
+        if is_anchored(&anchors, local) || meta.tickets.contains(local) {
+            pending.keep(local);
+        } else {
+            pending.mark(local, now);
+        }
+    }
+
+    for local in pending.drain_due(now, config.unload_delay) {
+        // Chunks may have been removed by something else while pending
+        if !world.exists(local) {
+            continue;
+        }
+
+        if edited.take(local) {
+            genesis::save_chunk(&world, local, &meta.dimensions, &workers);
+        }
+
+        for dirty in genesis::unload_chunk(&world, local) {
+            if genesis::update_chunk(&world, dirty) {
+                updated_writer.send(ChunkUpdated(dirty));
+            }
+        }
+
+        unloaded_writer.send(ChunkUnloaded(local));
+    }
+
+    if workers.is_saturated() {
+        return;
+    }
+
+    // Tickets are loaded after anchors, since players are waiting for the chunks around them
+    let missing = anchored_chunks(&anchors)
+        .into_iter()
+        .chain(meta.tickets.chunks())
+        .filter(|local| !world.exists(*local) && !workers.is_pending(*local))
+        .collect::<Vec<_>>();
+
+    for local in missing {
+        if let Err(RequestError::Full) = workers.request(local) {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn priority() {
+        let center = IVec3::ZERO;
+        let view_dir = Vec3::X;
+
+        let front = super::priority(center, view_dir, (4, 0, 0).into());
+        let side = super::priority(center, view_dir, (0, 0, 4).into());
+        let back = super::priority(center, view_dir, (-4, 0, 0).into());
+
+        assert!(front < side);
+        assert!(side < back);
+        assert_eq!(front, 4.0);
+        assert_eq!(back, 4.0 * (1.0 + VIEW_WEIGHT));
+
+        // Closer chunks on the side should still be loaded before further chunks in front
+        let near_side = super::priority(center, view_dir, (0, 0, 2).into());
+        let far_front = super::priority(center, view_dir, (5, 0, 0).into());
+        assert!(near_side < far_front);
+    }
+
+    #[test]
+    fn priority_near() {
+        let center = (3, 3, 3).into();
+
+        for side in crate::voxel::SIDES {
+            assert_eq!(super::priority(center, Vec3::X, center + side.dir()), 1.0);
+        }
+
+        assert_eq!(super::priority(center, Vec3::X, center), 0.0);
+    }
+
+    #[test]
+    fn anchored_chunks_by_priority() {
+        let center = (10, 0, -10).into();
+        let view_dir = -Vec3::Z;
+
+        let locals = super::anchored_chunks(&[Anchor {
+            center,
+            radius: 4,
+            view_dir,
+        }]);
+
+        assert_eq!(locals[0], center);
+        assert_eq!(locals.last().unwrap(), &(center + IVec3::new(0, 0, 4)));
+
+        for pair in locals.windows(2) {
+            assert!(
+                super::priority(center, view_dir, pair[0])
+                    <= super::priority(center, view_dir, pair[1])
+            );
+        }
+    }
+
+    #[test]
+    fn pending_unloads() {
+        let mut pending = PendingUnloads::default();
+        let local = IVec3::new(1, 2, 3);
+
+        // Marking again doesn't restart the delay
+        pending.mark(local, 1.0);
+        pending.mark(local, 2.0);
+        assert!(pending.drain_due(3.0, 5.0).is_empty());
+        assert_eq!(pending.drain_due(6.0, 5.0), vec![local]);
+        assert!(pending.drain_due(20.0, 5.0).is_empty());
+
+        // Chunks kept again before their delay is over aren't unloaded
+        pending.mark(local, 10.0);
+        pending.keep(local);
+        pending.mark(local, 12.0);
+        assert!(pending.drain_due(16.0, 5.0).is_empty());
+        assert_eq!(pending.drain_due(17.0, 5.0), vec![local]);
+    }
+
+    #[test]
+    fn anchored_chunks() {
+        let anchors = [
+            Anchor {
+                center: IVec3::ZERO,
+                radius: 2,
+                view_dir: Vec3::X,
+            },
+            Anchor {
+                center: (3, 0, 0).into(),
+                radius: 1,
+                view_dir: -Vec3::X,
+            },
+        ];
+
+        assert!(is_anchored(&anchors, (-2, 0, 0).into()));
+        assert!(is_anchored(&anchors, (4, 0, 0).into()));
+        assert!(!is_anchored(&anchors, (5, 0, 0).into()));
+        assert!(!is_anchored(&anchors, (0, 3, 0).into()));
+
+        let chunks = super::anchored_chunks(&anchors);
+
+        // Spheres overlap on (2, 0, 0), which is listed only once
+        let overlap = chunks.iter().filter(|local| **local == (2, 0, 0).into());
+        assert_eq!(overlap.count(), 1);
+        assert_eq!(
+            chunks.len(),
+            query::sphere(IVec3::ZERO, 2).count() + query::sphere((3, 0, 0).into(), 1).count() - 1
+        );
+
+        // Both centers come first, since they're the closest chunks to an anchor
+        assert!(chunks[..2].contains(&IVec3::ZERO));
+        assert!(chunks[..2].contains(&(3, 0, 0).into()));
+        assert!(chunks.iter().all(|local| is_anchored(&anchors, *local)));
+    }
+}
@@ -0,0 +1,471 @@
+use bevy::prelude::*;
+use crossbeam_channel::{Receiver, Sender};
+use std::{collections::HashSet, path::PathBuf, sync::Arc, thread::JoinHandle};
+
+use crate::world::VoxWorld;
+
+use super::io::{self, CacheWriter, IoChannels};
+
+/// Generates a chunk into the world, on a generation worker, queueing its cache to be written.
+pub type GenesisJob = dyn Fn(&VoxWorld, IVec3, &CacheWriter) -> HashSet<IVec3> + Send + Sync;
+
+/// Loads a chunk into the world from its cache, on the IO thread. Returns `None` when it must be generated.
+pub type LoadJob = dyn Fn(&VoxWorld, IVec3) -> Option<HashSet<IVec3>> + Send + Sync;
+
+#[derive(Debug, Clone, Copy)]
+pub struct GenesisConfig {
+    pub worker_count: usize,
+    /// Max number of chunks which can be in flight (queued, being generated or waiting to be drained).
+    pub capacity: usize,
+    /// Max number of cache writes waiting for the IO thread, before generation workers wait for it.
+    pub io_capacity: usize,
+}
+
+impl Default for GenesisConfig {
+    fn default() -> Self {
+        let cores = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(2);
+
+        Self {
+            // Keep one core free for the main and render threads
+            worker_count: usize::max(1, cores - 1),
+            capacity: 64,
+            io_capacity: 64,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RequestError {
+    /// All slots are in use. The caller should try again after draining finished chunks.
+    Full,
+    /// The chunk was already requested and it's still in flight.
+    Pending,
+}
+
+#[derive(Debug)]
+pub struct GenesisResult {
+    pub local: IVec3,
+    pub dirty_chunks: HashSet<IVec3>,
+}
+
+/**
+  Persistent pool of threads which generates or loads chunks into the world.
+
+  Requests go to a single IO thread first, which loads chunks from the disk, while the ones which aren't
+  cached are handed to generation workers. Workers queue the caches of generated chunks back to the IO
+  thread, so neither side waits on the other. Caches of edited chunks are queued there too, with
+  [`GenesisWorkers::replace`].
+
+  Requests are bounded by [`GenesisConfig::capacity`] so fast moving anchors can't flood memory with
+  in flight chunks. When the pool is saturated, [`GenesisWorkers::request`] returns [`RequestError::Full`]
+  and it's up to the caller to retry later.
+*/
+pub struct GenesisWorkers {
+    sender: Option<Sender<IVec3>>,
+    /// Same queues the IO thread and workers read from, used to cancel requests which weren't picked up yet.
+    queues: [Receiver<IVec3>; 2],
+    receiver: Receiver<GenesisResult>,
+    writer: Option<CacheWriter>,
+    in_flight: HashSet<IVec3>,
+    capacity: usize,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl GenesisWorkers {
+    pub fn new(
+        world: VoxWorld,
+        config: GenesisConfig,
+        load: Arc<LoadJob>,
+        job: Arc<GenesisJob>,
+    ) -> Self {
+        assert!(config.worker_count > 0, "There must be at least one worker");
+        assert!(config.capacity > 0, "Capacity must be greater than zero");
+        assert!(
+            config.io_capacity > 0,
+            "IO capacity must be greater than zero"
+        );
+
+        // Since in flight chunks are bounded by capacity, neither queue will ever block
+        let (sender, load_receiver) = crossbeam_channel::bounded::<IVec3>(config.capacity);
+        let (generate_sender, request_receiver) =
+            crossbeam_channel::bounded::<IVec3>(config.capacity);
+        let (result_sender, receiver) = crossbeam_channel::bounded(config.capacity);
+        let (write_sender, write_receiver) = crossbeam_channel::bounded(config.io_capacity);
+
+        let mut handles = (0..config.worker_count)
+            .map(|idx| {
+                let world = world.clone();
+                let job = job.clone();
+                let request_receiver = request_receiver.clone();
+                let result_sender = result_sender.clone();
+                let writer = CacheWriter::new(write_sender.clone());
+
+                std::thread::Builder::new()
+                    .name(format!("genesis_worker_{}", idx))
+                    .spawn(move || {
+                        // Pool was dropped when the channel gets disconnected, so there is nothing else to do
+                        for local in request_receiver.iter() {
+                            let dirty_chunks = job(&world, local, &writer);
+
+                            if result_sender
+                                .send(GenesisResult {
+                                    local,
+                                    dirty_chunks,
+                                })
+                                .is_err()
+                            {
+                                break;
+                            }
+                        }
+                    })
+                    .expect("Failed to spawn genesis worker")
+            })
+            .collect::<Vec<_>>();
+
+        // Only workers and the pool may hold writers, so the IO thread knows when they're all gone
+        let writer = CacheWriter::new(write_sender);
+
+        let channels = IoChannels {
+            loads: load_receiver.clone(),
+            writes: write_receiver,
+            generate: generate_sender,
+            results: result_sender,
+        };
+
+        handles.push(
+            std::thread::Builder::new()
+                .name("genesis_io".to_string())
+                .spawn(move || io::run(world, load, channels))
+                .expect("Failed to spawn genesis IO thread"),
+        );
+
+        Self {
+            sender: Some(sender),
+            queues: [load_receiver, request_receiver],
+            receiver,
+            writer: Some(writer),
+            in_flight: HashSet::default(),
+            capacity: config.capacity,
+            handles,
+        }
+    }
+
+    pub fn request(&mut self, local: IVec3) -> Result<(), RequestError> {
+        if self.in_flight.contains(&local) {
+            return Err(RequestError::Pending);
+        }
+
+        if self.is_saturated() {
+            return Err(RequestError::Full);
+        }
+
+        self.sender
+            .as_ref()
+            .expect("Sender is only taken on drop")
+            .send(local)
+            .expect("All genesis workers died");
+
+        self.in_flight.insert(local);
+
+        Ok(())
+    }
+
+    /**
+      Returns all chunks finished since last call, without blocking.
+      Each drained chunk frees one slot, which relieves the backpressure.
+    */
+    pub fn drain_finished(&mut self) -> Vec<GenesisResult> {
+        let finished = self.receiver.try_iter().collect::<Vec<_>>();
+
+        for result in &finished {
+            self.in_flight.remove(&result.local);
+        }
+
+        finished
+    }
+
+    /**
+      Cancels all requests which weren't picked up by the IO thread or a worker yet, freeing their slots.
+      Chunks already being loaded or generated aren't affected. Returns the cancelled chunks.
+    */
+    pub fn cancel_queued(&mut self) -> Vec<IVec3> {
+        let cancelled = self
+            .queues
+            .iter()
+            .flat_map(|queue| queue.try_iter())
+            .collect::<Vec<_>>();
+
+        for local in &cancelled {
+            self.in_flight.remove(local);
+        }
+
+        cancelled
+    }
+
+    /**
+      Queues a cache file to replace the existing one, like the cache of a chunk edited since it was loaded.
+      It's written before any chunk requested after it is loaded.
+    */
+    pub fn replace(&self, path: PathBuf, bytes: Vec<u8>) {
+        self.writer
+            .as_ref()
+            .expect("Writer is only taken on drop")
+            .replace(path, bytes);
+    }
+
+    pub fn is_pending(&self, local: IVec3) -> bool {
+        self.in_flight.contains(&local)
+    }
+
+    pub fn is_saturated(&self) -> bool {
+        self.in_flight.len() >= self.capacity
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    pub fn remaining_capacity(&self) -> usize {
+        self.capacity - self.in_flight.len()
+    }
+}
+
+impl Drop for GenesisWorkers {
+    fn drop(&mut self) {
+        // Dropping the sender stops the IO thread, and then every worker, once their queues are empty
+        self.sender.take();
+        self.writer.take();
+
+        for handle in self.handles.drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::ChunkKind;
+    use std::time::{Duration, Instant};
+
+    fn add_job() -> Arc<GenesisJob> {
+        Arc::new(|world: &VoxWorld, local: IVec3, _: &CacheWriter| {
+            world.add(local, ChunkKind::default());
+            [local].into_iter().collect()
+        })
+    }
+
+    fn no_cache() -> Arc<LoadJob> {
+        Arc::new(|_: &VoxWorld, _: IVec3| None)
+    }
+
+    fn drain_all(workers: &mut GenesisWorkers, count: usize) -> Vec<GenesisResult> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut results = vec![];
+
+        while results.len() < count {
+            assert!(
+                Instant::now() < deadline,
+                "Timed out waiting genesis workers"
+            );
+            results.extend(workers.drain_finished());
+            std::thread::yield_now();
+        }
+
+        results
+    }
+
+    #[test]
+    fn request() {
+        let world = VoxWorld::default();
+        let config = GenesisConfig {
+            worker_count: 2,
+            capacity: 10,
+            ..Default::default()
+        };
+        let mut workers = GenesisWorkers::new(world.clone(), config, no_cache(), add_job());
+
+        for x in 0..10 {
+            assert_eq!(workers.request((x, 0, 0).into()), Ok(()));
+        }
+
+        let results = drain_all(&mut workers, 10);
+
+        for x in 0..10 {
+            let local = (x, 0, 0).into();
+            assert!(world.exists(local));
+
+            let result = results.iter().find(|r| r.local == local).unwrap();
+            assert!(result.dirty_chunks.contains(&local));
+        }
+
+        assert_eq!(workers.in_flight(), 0);
+    }
+
+    #[test]
+    fn backpressure() {
+        let world = VoxWorld::default();
+        let config = GenesisConfig {
+            worker_count: 1,
+            capacity: 3,
+            ..Default::default()
+        };
+        let mut workers = GenesisWorkers::new(world, config, no_cache(), add_job());
+
+        for x in 0..3 {
+            assert_eq!(workers.request((x, 0, 0).into()), Ok(()));
+        }
+
+        assert!(workers.is_saturated());
+        assert_eq!(workers.remaining_capacity(), 0);
+        assert_eq!(workers.request((9, 0, 0).into()), Err(RequestError::Full));
+
+        // Finished chunks still holds their slots until drained
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(workers.is_saturated());
+
+        drain_all(&mut workers, 3);
+
+        assert!(!workers.is_saturated());
+        assert_eq!(workers.request((9, 0, 0).into()), Ok(()));
+    }
+
+    #[test]
+    fn request_pending() {
+        let world = VoxWorld::default();
+        let mut workers =
+            GenesisWorkers::new(world, GenesisConfig::default(), no_cache(), add_job());
+
+        assert_eq!(workers.request(IVec3::ONE), Ok(()));
+        assert!(workers.is_pending(IVec3::ONE));
+        assert_eq!(workers.request(IVec3::ONE), Err(RequestError::Pending));
+
+        drain_all(&mut workers, 1);
+
+        assert!(!workers.is_pending(IVec3::ONE));
+    }
+
+    #[test]
+    fn cancel_queued() {
+        let world = VoxWorld::default();
+        let config = GenesisConfig {
+            worker_count: 1,
+            capacity: 10,
+            ..Default::default()
+        };
+        let job = Arc::new(|world: &VoxWorld, local: IVec3, _: &CacheWriter| {
+            std::thread::sleep(Duration::from_millis(20));
+            world.add(local, ChunkKind::default());
+            [local].into_iter().collect()
+        });
+        let mut workers = GenesisWorkers::new(world.clone(), config, no_cache(), job);
+
+        for x in 0..10 {
+            workers.request((x, 0, 0).into()).unwrap();
+        }
+
+        let cancelled = workers.cancel_queued();
+
+        // The single worker can't have picked up all requests yet
+        assert!(!cancelled.is_empty());
+        assert_eq!(workers.in_flight(), 10 - cancelled.len());
+
+        for local in &cancelled {
+            assert!(!workers.is_pending(*local));
+        }
+
+        let remaining = workers.in_flight();
+        drain_all(&mut workers, remaining);
+
+        for local in cancelled {
+            assert!(!world.exists(local));
+        }
+        assert_eq!(world.len(), remaining);
+    }
+
+    #[test]
+    fn drop_with_pending_requests() {
+        let world = VoxWorld::default();
+        let config = GenesisConfig {
+            worker_count: 2,
+            capacity: 100,
+            ..Default::default()
+        };
+        let mut workers = GenesisWorkers::new(world, config, no_cache(), add_job());
+
+        for x in 0..100 {
+            workers.request((x, 0, 0).into()).unwrap();
+        }
+
+        // Must not hang even if results were never drained
+        drop(workers);
+    }
+
+    #[test]
+    fn load_cached() {
+        let world = VoxWorld::default();
+
+        // Chunks on even columns are cached, so only the others are generated
+        let load = Arc::new(|world: &VoxWorld, local: IVec3| {
+            (local.x % 2 == 0).then(|| {
+                world.add(local, ChunkKind::default());
+                [local].into_iter().collect()
+            })
+        });
+        let job = Arc::new(|world: &VoxWorld, local: IVec3, _: &CacheWriter| {
+            assert!(local.x % 2 != 0, "Cached chunk {} was generated", local);
+            world.add(local, ChunkKind::default());
+            [local].into_iter().collect()
+        });
+        let mut workers = GenesisWorkers::new(world.clone(), GenesisConfig::default(), load, job);
+
+        for x in 0..10 {
+            workers.request((x, 0, 0).into()).unwrap();
+        }
+
+        drain_all(&mut workers, 10);
+        assert_eq!(world.len(), 10);
+    }
+
+    #[test]
+    fn write_cache() {
+        let dir = std::env::temp_dir().join("eterno_write_cache");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let job = {
+            let dir = dir.clone();
+            Arc::new(
+                move |world: &VoxWorld, local: IVec3, writer: &CacheWriter| {
+                    world.add(local, ChunkKind::default());
+                    writer.write(dir.join(format!("{}.bin", local.x)), vec![local.x as u8]);
+                    [local].into_iter().collect()
+                },
+            )
+        };
+        let mut workers = GenesisWorkers::new(
+            VoxWorld::default(),
+            GenesisConfig::default(),
+            no_cache(),
+            job,
+        );
+
+        for x in 0..10 {
+            workers.request((x, 0, 0).into()).unwrap();
+        }
+
+        // Queued writes are done before the pool is gone
+        drain_all(&mut workers, 10);
+        drop(workers);
+
+        for x in 0..10 {
+            assert_eq!(
+                std::fs::read(dir.join(format!("{}.bin", x))).unwrap(),
+                vec![x as u8]
+            );
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
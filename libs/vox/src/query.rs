@@ -74,6 +74,19 @@ pub fn range_inclusive(begin: IVec3, end_inclusive: IVec3) -> impl Iterator<Item
     }
 }
 
+/**
+  Iterates over all positions whose distance to center is less than or equal to the radius.
+*/
+pub fn sphere(center: IVec3, radius: u32) -> impl Iterator<Item = IVec3> {
+    let radius_sq = (radius * radius) as i32;
+    let radius_vec = IVec3::splat(radius as i32);
+
+    range_inclusive(center - radius_vec, center + radius_vec).filter(move |pos| {
+        let dist = *pos - center;
+        dist.dot(dist) <= radius_sq
+    })
+}
+
 #[derive(Default, Debug, Clone, Copy)]
 pub struct RaycastHit {
     pub local: IVec3,
@@ -122,6 +135,59 @@ pub fn raycast(origin: Vec3, dir: Vec3, range: f32) -> Vec<(RaycastHit, Vec<Rayc
     result
 }
 
+/**
+  Swept variant of [`raycast`], for things moving from `from` to `to` in a single step, like projectiles.
+  Every voxel crossed by the segment between both is returned, walked by [`math::grid_traversal`], so fast
+  movers can't skip thin walls, as they would by only checking where they end up, nor voxels whose corners
+  are barely touched. Things which didn't move only touch the voxel they are in.
+*/
+pub fn sweep(from: Vec3, to: Vec3) -> Vec<(RaycastHit, Vec<RaycastHit>)> {
+    let offset = to - from;
+    let range = offset.length();
+    let dir = if range <= f32::EPSILON {
+        Vec3::ZERO
+    } else {
+        offset / range
+    };
+
+    let mut result: Vec<(RaycastHit, Vec<RaycastHit>)> = vec![];
+
+    for math::GridHit {
+        cell,
+        distance,
+        normal,
+    } in math::grid_traversal(from, dir, range, 1.0)
+    {
+        let (local, voxel) = chunk::split_voxel(cell);
+        let position = from + dir * distance;
+
+        match result.last_mut() {
+            Some((chunk_hit, voxels_hit)) if chunk_hit.local == local => {
+                voxels_hit.push(RaycastHit {
+                    local: voxel,
+                    position,
+                    normal,
+                })
+            }
+            // Like on raycast, the first voxel of each chunk leaves the normal to the chunk
+            _ => result.push((
+                RaycastHit {
+                    local,
+                    position,
+                    normal,
+                },
+                vec![RaycastHit {
+                    local: voxel,
+                    position,
+                    normal: IVec3::ZERO,
+                }],
+            )),
+        }
+    }
+
+    result
+}
+
 fn chunk_raycast(origin: Vec3, dir: Vec3, range: f32) -> (Vec<IVec3>, Vec<Vec3>, Vec<IVec3>) {
     let mut visited_locals = vec![];
     let mut visited_positions = vec![];
@@ -288,6 +354,24 @@ mod test {
         }
     }
 
+    #[test]
+    fn sphere() {
+        let center = (1, 2, 3).into();
+
+        assert_eq!(super::sphere(center, 0).collect::<Vec<_>>(), vec![center]);
+        assert_eq!(super::sphere(center, 1).count(), 7);
+
+        for pos in super::sphere(center, 5) {
+            assert!((pos - center).as_vec3().length() <= 5.0);
+        }
+
+        let outside = super::range_inclusive(center - IVec3::splat(5), center + IVec3::splat(5))
+            .filter(|pos| (*pos - center).as_vec3().length() > 5.0)
+            .count();
+
+        assert_eq!(super::sphere(center, 5).count() + outside, 11 * 11 * 11);
+    }
+
     pub fn eq(vec_a: Vec3, vec_b: Vec3) -> bool {
         vec_a.abs_diff_eq(vec_b, f32::EPSILON)
     }
@@ -1604,6 +1688,35 @@ mod test {
         assert_raycast(origin, dir, range, &ok_res);
     }
 
+    #[test]
+    fn sweep() {
+        let hits = super::sweep(Vec3::new(14.5, 0.5, 0.5), Vec3::new(17.5, 0.5, 0.5));
+        let voxels = hits
+            .iter()
+            .flat_map(|(chunk_hit, voxels_hit)| {
+                voxels_hit
+                    .iter()
+                    .map(move |voxel_hit| (chunk_hit.local, voxel_hit.local))
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            voxels,
+            vec![
+                (IVec3::ZERO, IVec3::new(14, 0, 0)),
+                (IVec3::ZERO, IVec3::new(15, 0, 0)),
+                (IVec3::X, IVec3::new(0, 0, 0)),
+                (IVec3::X, IVec3::new(1, 0, 0)),
+            ]
+        );
+
+        // Still things only touch the voxel they are in
+        let hits = super::sweep(Vec3::new(-0.5, 3.5, 0.5), Vec3::new(-0.5, 3.5, 0.5));
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].0.local, IVec3::new(-1, 0, 0));
+        assert_eq!(hits[0].1[0].local, IVec3::new(15, 3, 0));
+    }
+
     #[test]
     fn raycast_neg_dir() {
         let origin = Vec3::new(17.44164, 4.6248555, 6.514827);
@@ -0,0 +1,160 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, ErrorKind, Write},
+    path::Path,
+};
+
+use crate::{pipeline::SetVoxel, voxel::Kind};
+
+/**
+  A voxel change applied by the simulation on the given tick, as counted by [`crate::schedule::UpdateSchedule`].
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ReplayEntry {
+    pub tick: u64,
+    pub chunk: IVec3,
+    pub voxel: IVec3,
+    pub kind: Kind,
+}
+
+/**
+  Records every voxel change applied by the simulation into a file, while this resource is inserted.
+  Entries are written as they happen, so the recording survives crashes, which is when it's most useful.
+*/
+pub struct ReplayRecorder {
+    writer: BufWriter<File>,
+}
+
+impl ReplayRecorder {
+    pub fn create(path: &Path) -> Self {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .unwrap_or_else(|_| panic!("Unable to create dir {}", dir.display()));
+        }
+
+        let file = File::create(path)
+            .unwrap_or_else(|_| panic!("Unable to write to file {}", path.display()));
+
+        Self {
+            writer: BufWriter::new(file),
+        }
+    }
+
+    pub fn record(&mut self, tick: u64, edits: &[SetVoxel]) {
+        for SetVoxel { chunk, voxel, kind } in edits {
+            let entry = ReplayEntry {
+                tick,
+                chunk: *chunk,
+                voxel: *voxel,
+                kind: *kind,
+            };
+
+            bincode::serialize_into(&mut self.writer, &entry)
+                .expect("Failed to write replay entry");
+        }
+
+        self.writer.flush().expect("Failed to flush replay file");
+    }
+}
+
+/**
+  Plays back a recording, applying each voxel change on the tick it was recorded on. The world is expected
+  to be a fresh one, where the player stands where the recording started, so the same chunks are loaded.
+*/
+pub struct ReplayPlayer {
+    entries: Vec<ReplayEntry>,
+    next: usize,
+}
+
+impl ReplayPlayer {
+    /**
+      Loads a recording. A truncated last entry, left by a crash while recording, is ignored.
+    */
+    pub fn load(path: &Path) -> Self {
+        let file =
+            File::open(path).unwrap_or_else(|_| panic!("Unable to open file {}", path.display()));
+        let mut reader = BufReader::new(file);
+        let mut entries = vec![];
+
+        loop {
+            match bincode::deserialize_from(&mut reader) {
+                Ok(entry) => entries.push(entry),
+                Err(err) => match *err {
+                    bincode::ErrorKind::Io(io) if io.kind() == ErrorKind::UnexpectedEof => break,
+                    _ => panic!("Failed to parse replay file {}", path.display()),
+                },
+            }
+        }
+
+        Self::new(entries)
+    }
+
+    pub fn new(entries: Vec<ReplayEntry>) -> Self {
+        Self { entries, next: 0 }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.entries.len()
+    }
+
+    /// Returns the changes recorded up to the given tick, which weren't returned yet.
+    pub fn due(&mut self, tick: u64) -> Vec<SetVoxel> {
+        let due = self.entries[self.next..]
+            .iter()
+            .take_while(|entry| entry.tick <= tick)
+            .map(|entry| SetVoxel {
+                chunk: entry.chunk,
+                voxel: entry.voxel,
+                kind: entry.kind,
+            })
+            .collect::<Vec<_>>();
+
+        self.next += due.len();
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(x: i32, kind: u16) -> SetVoxel {
+        SetVoxel {
+            chunk: IVec3::ZERO,
+            voxel: IVec3::new(x, 0, 0),
+            kind: kind.into(),
+        }
+    }
+
+    #[test]
+    fn record_play() {
+        let path = std::env::temp_dir().join("eterno_replay_record_play.bin");
+
+        let mut recorder = ReplayRecorder::create(&path);
+        recorder.record(1, &[set(0, 1), set(1, 2)]);
+        recorder.record(5, &[set(2, 3)]);
+        drop(recorder);
+
+        // Crashed while writing an entry
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes.extend([1, 2, 3]);
+        std::fs::write(&path, bytes).unwrap();
+
+        let mut player = ReplayPlayer::load(&path);
+        assert!(player.due(0).is_empty());
+
+        let due = player.due(3);
+        assert_eq!(due.len(), 2);
+        assert_eq!(due[1].voxel, IVec3::new(1, 0, 0));
+        assert_eq!(due[1].kind, 2.into());
+        assert!(!player.is_finished());
+
+        assert_eq!(player.due(10).len(), 1);
+        assert!(player.is_finished());
+        assert!(player.due(11).is_empty());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
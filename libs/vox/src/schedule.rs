@@ -0,0 +1,262 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::voxel::Kind;
+
+const UPDATES_PATH: &str = "cache/updates";
+const UPDATES_EXT: &str = "bin";
+
+/**
+  What happens to a voxel when its scheduled update is due.
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ScheduledEvent {
+    /// Replaces the voxel kind, like a furnace which finished smelting.
+    Set(Kind),
+    /// Runs the voxel tick behavior, like water which flows after some ticks.
+    Tick,
+    /**
+      Blows up like the given explosive kind, as its fuse ran out. The voxel itself was already removed when
+      the fuse was lit, so it doesn't matter what is there now.
+    */
+    Explode(Kind),
+    /// Removes the voxel if it still has no support, like leaves of a cut tree. See [`crate::decay`].
+    Decay,
+}
+
+/**
+  An update waiting to be fired, as it's saved while its chunk isn't loaded.
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledUpdate {
+    pub voxel: IVec3,
+    /// How many ticks are left until the update is fired.
+    pub delay: u64,
+    pub event: ScheduledEvent,
+}
+
+/// Update queued on a loaded chunk, fired once the schedule reaches `due`.
+#[derive(Debug, Clone, Copy)]
+struct Queued {
+    due: u64,
+    voxel: IVec3,
+    event: ScheduledEvent,
+}
+
+/**
+  Updates scheduled to be fired on a later simulation tick, grouped by chunk.
+
+  Only updates of loaded chunks are kept here. When a chunk is unloaded, its updates are saved together with
+  the remaining delay, so time doesn't pass while chunks aren't loaded.
+*/
+#[derive(Default)]
+pub struct UpdateSchedule {
+    tick: u64,
+    chunks: HashMap<IVec3, Vec<Queued>>,
+}
+
+impl UpdateSchedule {
+    /// Current simulation tick.
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /**
+      Schedules an update to be fired after the given number of ticks. A delay of zero fires on the next tick.
+    */
+    pub fn schedule(&mut self, local: IVec3, voxel: IVec3, delay: u64, event: ScheduledEvent) {
+        self.chunks.entry(local).or_default().push(Queued {
+            due: self.tick + delay,
+            voxel,
+            event,
+        });
+    }
+
+    /// Number of updates waiting on the given chunk.
+    pub fn pending(&self, local: IVec3) -> usize {
+        self.chunks.get(&local).map_or(0, |queue| queue.len())
+    }
+
+    /**
+      Moves to the next tick and returns all updates which are due, in the order they were scheduled to fire.
+    */
+    pub fn advance(&mut self) -> Vec<(IVec3, IVec3, ScheduledEvent)> {
+        let tick = self.tick;
+        self.tick += 1;
+
+        let mut due = vec![];
+
+        for (local, queue) in self.chunks.iter_mut() {
+            queue.retain(|queued| {
+                if queued.due <= tick {
+                    due.push((queued.due, *local, queued.voxel, queued.event));
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+
+        self.chunks.retain(|_, queue| !queue.is_empty());
+
+        due.sort_by_key(|(due, _, _, _)| *due);
+        due.into_iter()
+            .map(|(_, local, voxel, event)| (local, voxel, event))
+            .collect()
+    }
+
+    /**
+      Adds back updates of a chunk which was loaded again.
+    */
+    pub fn load(&mut self, local: IVec3, updates: Vec<ScheduledUpdate>) {
+        for update in updates {
+            self.schedule(local, update.voxel, update.delay, update.event);
+        }
+    }
+
+    /**
+      Removes all updates of the given chunk, returning them with their remaining delay.
+    */
+    pub fn unload(&mut self, local: IVec3) -> Vec<ScheduledUpdate> {
+        let tick = self.tick;
+
+        self.chunks
+            .remove(&local)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|queued| ScheduledUpdate {
+                voxel: queued.voxel,
+                delay: queued.due.saturating_sub(tick),
+                event: queued.event,
+            })
+            .collect()
+    }
+}
+
+pub fn local_path(local: IVec3) -> PathBuf {
+    Path::new(UPDATES_PATH)
+        .join(format!("{}_{}_{}", local.x, local.y, local.z))
+        .with_extension(UPDATES_EXT)
+}
+
+/**
+  Saves the updates of a chunk. Chunks without updates have their file removed, if any.
+*/
+pub fn save(path: &Path, updates: &[ScheduledUpdate]) {
+    if updates.is_empty() {
+        if path.exists() {
+            std::fs::remove_file(path)
+                .unwrap_or_else(|_| panic!("Unable to remove file {}", path.display()));
+        }
+
+        return;
+    }
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .unwrap_or_else(|_| panic!("Unable to create dir {}", dir.display()));
+    }
+
+    let file = std::fs::File::create(path)
+        .unwrap_or_else(|_| panic!("Unable to write to file {}", path.display()));
+
+    bincode::serialize_into(file, updates)
+        .unwrap_or_else(|_| panic!("Failed to serialize updates to file {}", path.display()));
+}
+
+/**
+  Loads the updates of a chunk. Chunks which were never saved have no updates.
+*/
+pub fn load(path: &Path) -> Vec<ScheduledUpdate> {
+    if !path.exists() {
+        return vec![];
+    }
+
+    let file = std::fs::File::open(path)
+        .unwrap_or_else(|_| panic!("Unable to open file {}", path.display()));
+
+    bincode::deserialize_from(file)
+        .unwrap_or_else(|_| panic!("Failed to parse file {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn advance() {
+        let mut schedule = UpdateSchedule::default();
+
+        schedule.schedule(IVec3::ZERO, IVec3::ONE, 2, ScheduledEvent::Tick);
+        schedule.schedule(IVec3::X, IVec3::ZERO, 1, ScheduledEvent::Set(3.into()));
+        schedule.schedule(IVec3::ZERO, IVec3::ZERO, 0, ScheduledEvent::Tick);
+
+        assert_eq!(
+            schedule.advance(),
+            vec![(IVec3::ZERO, IVec3::ZERO, ScheduledEvent::Tick)]
+        );
+        assert_eq!(
+            schedule.advance(),
+            vec![(IVec3::X, IVec3::ZERO, ScheduledEvent::Set(3.into()))]
+        );
+        assert_eq!(
+            schedule.advance(),
+            vec![(IVec3::ZERO, IVec3::ONE, ScheduledEvent::Tick)]
+        );
+        assert!(schedule.advance().is_empty());
+        assert_eq!(schedule.tick(), 4);
+    }
+
+    #[test]
+    fn unload_keeps_remaining_delay() {
+        let mut schedule = UpdateSchedule::default();
+
+        schedule.schedule(IVec3::ZERO, IVec3::ONE, 5, ScheduledEvent::Tick);
+        schedule.advance();
+        schedule.advance();
+
+        let updates = schedule.unload(IVec3::ZERO);
+        assert_eq!(updates[0].delay, 3);
+        assert_eq!(schedule.pending(IVec3::ZERO), 0);
+
+        // Time doesn't pass while the chunk isn't loaded
+        for _ in 0..10 {
+            assert!(schedule.advance().is_empty());
+        }
+
+        schedule.load(IVec3::ZERO, updates);
+        for _ in 0..3 {
+            assert!(schedule.advance().is_empty());
+        }
+        assert_eq!(schedule.advance().len(), 1);
+    }
+
+    #[test]
+    fn save_load() {
+        let path = std::env::temp_dir().join("eterno_schedule_save_load.bin");
+
+        let updates = vec![ScheduledUpdate {
+            voxel: (1, 2, 3).into(),
+            delay: 42,
+            event: ScheduledEvent::Set(7.into()),
+        }];
+
+        save(&path, &updates);
+        assert_eq!(load(&path), updates);
+
+        // Saving nothing removes the file
+        save(&path, &[]);
+        assert!(!path.exists());
+        assert!(load(&path).is_empty());
+    }
+
+    #[test]
+    fn local_path() {
+        let path = super::local_path((-1, 3333, -461).into());
+        assert!(path.ends_with("-1_3333_-461.bin"));
+    }
+}
@@ -0,0 +1,345 @@
+use bevy::prelude::*;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::{
+    chunk::{self, ChunkKind},
+    pipeline::SetVoxel,
+    voxel::{self, KindRegistry, SignalRole},
+    world::VoxWorld,
+};
+
+/// Signal level of emitters. Signal decreases by one on each wire it passes through.
+pub const MAX_SIGNAL: u8 = 15;
+
+/**
+  Signal levels of all powered wires and actuators, by world voxel position.
+
+  Networks are evaluated incrementally. Changed voxels are marked as dirty and, on the next update, only the
+  networks touching them are cleared and evaluated again from their emitters.
+*/
+#[derive(Default)]
+pub struct SignalNetwork {
+    levels: HashMap<IVec3, u8>,
+    dirty: HashSet<IVec3>,
+}
+
+impl SignalNetwork {
+    /// Signal level reaching the given world voxel.
+    pub fn level(&self, position: IVec3) -> u8 {
+        self.levels.get(&position).copied().unwrap_or_default()
+    }
+
+    pub fn is_powered(&self, position: IVec3) -> bool {
+        self.level(position) > 0
+    }
+
+    /// Marks the given world voxel as changed, so networks touching it are evaluated on the next update.
+    pub fn mark_dirty(&mut self, position: IVec3) {
+        self.dirty.insert(position);
+    }
+
+    /**
+      Marks all voxels of a newly loaded chunk which takes part on signal networks.
+    */
+    pub fn load(&mut self, local: IVec3, kind: &ChunkKind, registry: &KindRegistry) {
+        let origin = local * chunk::AXIS_SIZE as i32;
+
+        self.dirty.extend(
+            chunk::voxels()
+                .filter(|voxel| registry.signal(kind.get(*voxel)).is_some())
+                .map(|voxel| origin + voxel),
+        );
+    }
+
+    /**
+      Forgets levels of an unloaded chunk. Networks crossing it are kept as they are, until something changes.
+    */
+    pub fn unload(&mut self, local: IVec3) {
+        self.levels
+            .retain(|position, _| chunk::split_voxel(*position).0 != local);
+    }
+
+    /**
+      Evaluates all networks touching dirty voxels. Returns the voxel changes needed by actuators which
+      got powered or unpowered.
+    */
+    pub fn update(&mut self, world: &VoxWorld, registry: &KindRegistry) -> Vec<SetVoxel> {
+        if self.dirty.is_empty() {
+            return vec![];
+        }
+
+        let dirty = std::mem::take(&mut self.dirty);
+        let members = network(world, registry, dirty.iter().copied());
+
+        // Dirty voxels which aren't part of any network anymore, like removed wires, are cleared too
+        for position in dirty.iter().chain(members.keys()) {
+            self.levels.remove(position);
+        }
+
+        self.propagate(&members);
+
+        members
+            .iter()
+            .filter_map(|(position, (kind, role))| match role {
+                SignalRole::Actuator { powered, unpowered } => {
+                    let id = if self.is_powered(*position) {
+                        *powered
+                    } else {
+                        *unpowered
+                    };
+
+                    (kind.id() != id).then(|| {
+                        let (chunk, voxel) = chunk::split_voxel(*position);
+
+                        SetVoxel {
+                            chunk,
+                            voxel,
+                            kind: voxel::Kind::from(id).with_facing(kind.facing()),
+                        }
+                    })
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /**
+      Spreads the signal from all emitters of the given network. Actuators are powered by any powered
+      neighbor, but only wires carries the signal further.
+    */
+    fn propagate(&mut self, members: &HashMap<IVec3, (voxel::Kind, SignalRole)>) {
+        let mut queue = members
+            .iter()
+            .filter(|(_, (_, role))| *role == SignalRole::Emitter)
+            .map(|(position, _)| (*position, MAX_SIGNAL))
+            .collect::<VecDeque<_>>();
+
+        while let Some((position, level)) = queue.pop_front() {
+            if level <= self.level(position) {
+                continue;
+            }
+
+            self.levels.insert(position, level);
+
+            if matches!(
+                members.get(&position),
+                Some((_, SignalRole::Actuator { .. }))
+            ) {
+                continue;
+            }
+
+            for side in voxel::SIDES {
+                let neighbor = position + side.dir();
+
+                let next = match members.get(&neighbor) {
+                    Some((_, SignalRole::Wire)) => level - 1,
+                    Some((_, SignalRole::Actuator { .. })) => level,
+                    _ => continue,
+                };
+
+                if next > self.level(neighbor) {
+                    queue.push_back((neighbor, next));
+                }
+            }
+        }
+    }
+}
+
+/**
+  Finds all voxels connected to the given positions, or to their neighbors, which takes part on signal networks.
+*/
+fn network(
+    world: &VoxWorld,
+    registry: &KindRegistry,
+    positions: impl Iterator<Item = IVec3>,
+) -> HashMap<IVec3, (voxel::Kind, SignalRole)> {
+    let mut members = HashMap::new();
+    let mut visited = HashSet::new();
+
+    let mut queue = positions
+        .flat_map(|position| {
+            std::iter::once(position)
+                .chain(voxel::SIDES.iter().map(move |side| position + side.dir()))
+        })
+        .collect::<VecDeque<_>>();
+
+    while let Some(position) = queue.pop_front() {
+        if !visited.insert(position) {
+            continue;
+        }
+
+        let kind = match world.get_voxel(position) {
+            Some(kind) => kind,
+            None => continue,
+        };
+
+        if let Some(role) = registry.signal(kind) {
+            members.insert(position, (kind, role));
+            queue.extend(voxel::SIDES.iter().map(|side| position + side.dir()));
+        }
+    }
+
+    members
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel::{KindDescription, MeshShape};
+
+    const LEVER: u16 = 1;
+    const WIRE: u16 = 2;
+    const LAMP: u16 = 3;
+    const LIT_LAMP: u16 = 4;
+
+    fn description(id: u16, signal: SignalRole) -> KindDescription {
+        KindDescription {
+            name: format!("Kind {}", id),
+            id,
+            color: (1.0, 1.0, 1.0, 1.0),
+            shape: MeshShape::Cube,
+            prop: None,
+            directional: false,
+            light: 0,
+            tick: None,
+            signal: Some(signal),
+            toggle: None,
+            hardness: 0.0,
+            tool_tier: 0,
+            block_entity: None,
+            explosive: None,
+            liquid: None,
+            climbable: false,
+            surface: None,
+            foliage: false,
+            tint: None,
+            portal: None,
+        }
+    }
+
+    fn registry() -> KindRegistry {
+        let lamp = SignalRole::Actuator {
+            powered: LIT_LAMP,
+            unpowered: LAMP,
+        };
+
+        KindRegistry::new(vec![
+            description(LEVER, SignalRole::Emitter),
+            description(WIRE, SignalRole::Wire),
+            description(LAMP, lamp),
+            description(LIT_LAMP, lamp),
+        ])
+    }
+
+    fn apply(world: &VoxWorld, network: &mut SignalNetwork, edits: Vec<SetVoxel>) {
+        for SetVoxel { chunk, voxel, kind } in edits {
+            world.get_mut(chunk).unwrap().set(voxel, kind);
+            network.mark_dirty(chunk::join_voxel(chunk, voxel));
+        }
+    }
+
+    /// Position along the test circuit, where the lever is at zero.
+    fn at(offset: i32) -> IVec3 {
+        IVec3::new(8 + offset, 0, 0)
+    }
+
+    /// Places a lever, followed by wires along the X axis and a lamp at the end.
+    fn circuit(world: &VoxWorld, length: i32) {
+        for local in [IVec3::ZERO, IVec3::X, 2 * IVec3::X] {
+            world.add(local, ChunkKind::default());
+        }
+
+        let set = |position: IVec3, id: u16| {
+            let (local, voxel) = chunk::split_voxel(position);
+            world.get_mut(local).unwrap().set(voxel, id.into());
+        };
+
+        set(at(0), LEVER);
+        for x in 1..=length {
+            set(at(x), WIRE);
+        }
+        set(at(length + 1), LAMP);
+    }
+
+    #[test]
+    fn power_lamp_across_chunks() {
+        let world = VoxWorld::default();
+        let registry = registry();
+        let mut network = SignalNetwork::default();
+
+        circuit(&world, 14);
+
+        for local in [IVec3::ZERO, IVec3::X] {
+            network.load(local, &world.get(local).unwrap(), &registry);
+        }
+
+        let edits = network.update(&world, &registry);
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].chunk, IVec3::X);
+        assert_eq!(edits[0].kind, LIT_LAMP.into());
+
+        assert_eq!(network.level(at(1)), MAX_SIGNAL - 1);
+        assert_eq!(network.level(at(14)), 1);
+        assert!(network.is_powered(at(15)));
+
+        apply(&world, &mut network, edits);
+
+        // Evaluating again doesn't change anything
+        assert!(network.update(&world, &registry).is_empty());
+    }
+
+    #[test]
+    fn signal_fades() {
+        let world = VoxWorld::default();
+        let registry = registry();
+        let mut network = SignalNetwork::default();
+
+        circuit(&world, 15);
+        network.load(IVec3::ZERO, &world.get(IVec3::ZERO).unwrap(), &registry);
+
+        assert!(network.update(&world, &registry).is_empty());
+        assert_eq!(network.level(at(15)), 0);
+        assert!(!network.is_powered(at(16)));
+    }
+
+    #[test]
+    fn remove_emitter() {
+        let world = VoxWorld::default();
+        let registry = registry();
+        let mut network = SignalNetwork::default();
+
+        circuit(&world, 3);
+        network.load(IVec3::ZERO, &world.get(IVec3::ZERO).unwrap(), &registry);
+
+        let edits = network.update(&world, &registry);
+        apply(&world, &mut network, edits);
+        network.update(&world, &registry);
+        assert_eq!(world.get_voxel(at(4)), Some(LIT_LAMP.into()));
+
+        world.get_mut(IVec3::ZERO).unwrap().set(at(0), 0.into());
+        network.mark_dirty(at(0));
+
+        let edits = network.update(&world, &registry);
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].kind, LAMP.into());
+        assert!(!network.is_powered(at(1)));
+        assert!(!network.is_powered(at(0)));
+    }
+
+    #[test]
+    fn unload() {
+        let world = VoxWorld::default();
+        let registry = registry();
+        let mut network = SignalNetwork::default();
+
+        circuit(&world, 20);
+        network.load(IVec3::ZERO, &world.get(IVec3::ZERO).unwrap(), &registry);
+        network.update(&world, &registry);
+        assert!(network.is_powered(at(5)));
+        assert!(network.is_powered(at(14)));
+
+        network.unload(IVec3::ZERO);
+        assert!(!network.is_powered(at(5)));
+        assert!(network.is_powered(at(14)));
+    }
+}
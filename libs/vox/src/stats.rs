@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::voxel::Kind;
+
+/**
+  What the player did on the world, saved with the world metadata. Kinds are counted by id, so voxels facing
+  other sides are counted as the same kind. Achievements are meant to be checked against it.
+*/
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorldStats {
+    placed: BTreeMap<u16, u64>,
+    broken: BTreeMap<u16, u64>,
+    /// Distance traveled, in voxels.
+    distance: f64,
+    deaths: u64,
+}
+
+impl WorldStats {
+    pub fn record_placed(&mut self, kind: Kind) {
+        *self.placed.entry(kind.id()).or_default() += 1;
+    }
+
+    pub fn record_broken(&mut self, kind: Kind) {
+        *self.broken.entry(kind.id()).or_default() += 1;
+    }
+
+    pub fn record_distance(&mut self, distance: f32) {
+        self.distance += distance as f64;
+    }
+
+    pub fn record_death(&mut self) {
+        self.deaths += 1;
+    }
+
+    /// How many voxels of the given kind were placed.
+    pub fn placed(&self, kind: Kind) -> u64 {
+        self.placed.get(&kind.id()).copied().unwrap_or_default()
+    }
+
+    /// How many voxels of the given kind were broken.
+    pub fn broken(&self, kind: Kind) -> u64 {
+        self.broken.get(&kind.id()).copied().unwrap_or_default()
+    }
+
+    pub fn total_placed(&self) -> u64 {
+        self.placed.values().sum()
+    }
+
+    pub fn total_broken(&self) -> u64 {
+        self.broken.values().sum()
+    }
+
+    /// Kinds ever placed, by id, with how many of each were placed.
+    pub fn placed_kinds(&self) -> impl Iterator<Item = (Kind, u64)> + '_ {
+        self.placed.iter().map(|(id, count)| ((*id).into(), *count))
+    }
+
+    /// Kinds ever broken, by id, with how many of each were broken.
+    pub fn broken_kinds(&self) -> impl Iterator<Item = (Kind, u64)> + '_ {
+        self.broken.iter().map(|(id, count)| ((*id).into(), *count))
+    }
+
+    /// Distance traveled, in voxels.
+    pub fn distance(&self) -> f64 {
+        self.distance
+    }
+
+    pub fn deaths(&self) -> u64 {
+        self.deaths
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel::Side;
+
+    #[test]
+    fn record() {
+        let mut stats = WorldStats::default();
+        let stone = Kind::from(3);
+
+        stats.record_placed(stone);
+        stats.record_placed(stone.with_facing(Side::Right));
+        stats.record_placed(1.into());
+        stats.record_broken(stone);
+
+        // Facing doesn't matter
+        assert_eq!(stats.placed(stone), 2);
+        assert_eq!(stats.broken(stone), 1);
+        assert_eq!(stats.broken(1.into()), 0);
+        assert_eq!(stats.total_placed(), 3);
+        assert_eq!(stats.total_broken(), 1);
+        assert_eq!(
+            stats.placed_kinds().collect::<Vec<_>>(),
+            vec![(1.into(), 1), (stone, 2)]
+        );
+
+        stats.record_distance(1.5);
+        stats.record_distance(2.0);
+        stats.record_death();
+        assert_eq!(stats.distance(), 3.5);
+        assert_eq!(stats.deaths(), 1);
+    }
+}
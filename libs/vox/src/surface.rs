@@ -0,0 +1,149 @@
+use bevy::prelude::*;
+
+use crate::{
+    voxel::{KindRegistry, Surface},
+    world::VoxWorld,
+};
+
+/// How fast, in voxels per second squared, things speed up and stop on the usual ground.
+pub const GROUND_ACCELERATION: f32 = 40.0;
+/// How far below the feet the ground is looked for, so things resting right on top of a voxel stand on it.
+const GROUND_PROBE: f32 = 0.01;
+
+/**
+  Surface of the voxel right below the given feet, in world coordinates, or `None` when there's nothing to
+  stand on there, like empty voxels, props, liquids and unloaded chunks.
+*/
+pub fn ground(world: &VoxWorld, registry: &KindRegistry, feet: Vec3) -> Option<Surface> {
+    let below = (feet - Vec3::Y * GROUND_PROBE).floor().as_ivec3();
+    let kind = world.get_voxel(below)?;
+
+    if registry.mesh_shape(kind).is_none() || registry.liquid(kind).is_some() {
+        None
+    } else {
+        Some(registry.surface(kind))
+    }
+}
+
+/**
+  Moves the horizontal velocity of something on the ground towards `wish`, the velocity it's trying to walk
+  at, over `dt` seconds. Slippery surfaces take longer to get there, and sticky ones cap it. Things which
+  aren't trying to walk anywhere slide to a stop. The vertical velocity is kept.
+*/
+pub fn walk(velocity: Vec3, wish: Vec3, surface: Surface, dt: f32) -> Vec3 {
+    let current = Vec3::new(velocity.x, 0.0, velocity.z);
+    let target = Vec3::new(wish.x, 0.0, wish.z) * surface.speed;
+
+    let diff = target - current;
+    let step = GROUND_ACCELERATION * surface.friction * dt;
+
+    let horizontal = if diff.length() <= step {
+        target
+    } else {
+        current + diff.normalize() * step
+    };
+
+    Vec3::new(horizontal.x, velocity.y, horizontal.z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chunk::ChunkKind,
+        voxel::{KindDescription, MeshShape, PropShape},
+    };
+
+    const ICE: Surface = Surface {
+        friction: 0.05,
+        speed: 1.2,
+    };
+
+    fn description(id: u16, prop: Option<PropShape>, surface: Option<Surface>) -> KindDescription {
+        KindDescription {
+            name: id.to_string(),
+            id,
+            color: (1.0, 1.0, 1.0, 1.0),
+            shape: MeshShape::Cube,
+            prop,
+            directional: false,
+            light: 0,
+            tick: None,
+            signal: None,
+            toggle: None,
+            hardness: 0.0,
+            tool_tier: 0,
+            block_entity: None,
+            explosive: None,
+            liquid: None,
+            climbable: false,
+            surface,
+            foliage: false,
+            tint: None,
+            portal: None,
+        }
+    }
+
+    #[test]
+    fn ground() {
+        let world = VoxWorld::default();
+        world.add(IVec3::ZERO, ChunkKind::default());
+
+        {
+            let mut chunk = world.get_mut(IVec3::ZERO).unwrap();
+            chunk.set((0, 0, 0).into(), 1.into());
+            chunk.set((1, 0, 0).into(), 2.into());
+            chunk.set((2, 0, 0).into(), 3.into());
+        }
+
+        let registry = KindRegistry::new(vec![
+            description(1, None, None),
+            description(2, None, Some(ICE)),
+            description(3, Some(PropShape::Billboard), None),
+        ]);
+
+        assert_eq!(
+            super::ground(&world, &registry, Vec3::new(0.5, 1.0, 0.5)),
+            Some(Surface::default())
+        );
+        assert_eq!(
+            super::ground(&world, &registry, Vec3::new(1.5, 1.0, 0.5)),
+            Some(ICE)
+        );
+
+        // Props and empty voxels can't be stood on
+        assert_eq!(
+            super::ground(&world, &registry, Vec3::new(2.5, 1.0, 0.5)),
+            None
+        );
+        assert_eq!(
+            super::ground(&world, &registry, Vec3::new(0.5, 2.0, 0.5)),
+            None
+        );
+    }
+
+    #[test]
+    fn walk() {
+        let ground = Surface::default();
+
+        // Speeds up towards the wished velocity, keeping the vertical one
+        let velocity = super::walk(Vec3::Y, Vec3::X * 4.0, ground, 0.05);
+        assert_eq!(velocity, Vec3::new(2.0, 1.0, 0.0));
+        let velocity = super::walk(velocity, Vec3::X * 4.0, ground, 0.05);
+        assert_eq!(velocity, Vec3::new(4.0, 1.0, 0.0));
+
+        // Slides for a long while on ice, which is also a bit faster
+        let velocity = super::walk(Vec3::X * 4.0, Vec3::ZERO, ICE, 0.05);
+        assert!(velocity.x > 3.5);
+        let velocity = super::walk(Vec3::ZERO, Vec3::X * 4.0, ICE, 10.0);
+        assert!(velocity.x > 4.0);
+
+        // Mud slows things down
+        let mud = Surface {
+            friction: 1.0,
+            speed: 0.4,
+        };
+        let velocity = super::walk(Vec3::X * 4.0, Vec3::X * 4.0, mud, 1.0);
+        assert!((velocity.x - 1.6).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,375 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    chunk,
+    pipeline::SetVoxel,
+    query,
+    voxel::{Kind, KindRegistry, TickBehavior},
+    world::VoxWorld,
+};
+
+/**
+  How many random voxels of each loaded chunk receives a tick, on each simulation tick.
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct RandomTickConfig {
+    pub per_chunk: usize,
+}
+
+impl Default for RandomTickConfig {
+    fn default() -> Self {
+        Self { per_chunk: 3 }
+    }
+}
+
+fn set(position: IVec3, kind: Kind) -> SetVoxel {
+    let (chunk, voxel) = chunk::split_voxel(position);
+    SetVoxel { chunk, voxel, kind }
+}
+
+/// Picks the random world voxels which receive a tick, on all loaded chunks.
+pub fn random_positions(
+    world: &VoxWorld,
+    config: &RandomTickConfig,
+    rng: &mut impl Rng,
+) -> Vec<IVec3> {
+    let mut positions = vec![];
+
+    for local in world.locals() {
+        for _ in 0..config.per_chunk {
+            let voxel = IVec3::new(
+                rng.gen_range(0..chunk::AXIS_SIZE as i32),
+                rng.gen_range(0..chunk::AXIS_SIZE as i32),
+                rng.gen_range(0..chunk::AXIS_SIZE as i32),
+            );
+
+            positions.push(chunk::join_voxel(local, voxel));
+        }
+    }
+
+    positions
+}
+
+/**
+  Picks random voxels on all loaded chunks and returns the changes caused by their tick behaviors.
+  Changes are returned instead of applied, so they go through the same path as any other voxel change.
+*/
+pub fn random_ticks(
+    world: &VoxWorld,
+    registry: &KindRegistry,
+    config: &RandomTickConfig,
+    rng: &mut impl Rng,
+) -> Vec<SetVoxel> {
+    let mut edits = vec![];
+
+    for position in random_positions(world, config, rng) {
+        edits.extend(tick_voxel(world, registry, position, rng));
+    }
+
+    edits
+}
+
+/**
+  Runs the tick behavior of the given world voxel, if it has any.
+*/
+pub fn tick_voxel(
+    world: &VoxWorld,
+    registry: &KindRegistry,
+    position: IVec3,
+    rng: &mut impl Rng,
+) -> Vec<SetVoxel> {
+    let kind = match world.get_voxel(position) {
+        Some(kind) => kind,
+        None => return vec![],
+    };
+
+    match registry.tick(kind) {
+        Some(TickBehavior::Spread { target }) => {
+            let offset = IVec3::new(
+                rng.gen_range(-1..=1),
+                rng.gen_range(-1..=1),
+                rng.gen_range(-1..=1),
+            );
+            spread(world, registry, position, kind, target.into(), offset)
+        }
+        Some(TickBehavior::Grow {
+            trunk,
+            leaves,
+            height,
+        }) => grow(world, position, trunk.into(), leaves.into(), height as i32),
+        // Decaying voxels are scheduled to be removed instead, see [`crate::decay`]
+        Some(TickBehavior::Decay { .. }) | None => vec![],
+    }
+}
+
+/**
+  Converts the neighbor at the given offset into `kind`, if it's `target` and isn't covered.
+  A covered voxel turns back into `target` instead.
+*/
+fn spread(
+    world: &VoxWorld,
+    registry: &KindRegistry,
+    position: IVec3,
+    kind: Kind,
+    target: Kind,
+    offset: IVec3,
+) -> Vec<SetVoxel> {
+    // Voxels on top of unloaded chunks are left alone, since it's unknown if they're covered
+    let is_covered = |position: IVec3| {
+        world
+            .get_voxel(position + IVec3::Y)
+            .map(|above| registry.is_opaque(above))
+    };
+
+    match is_covered(position) {
+        Some(true) => return vec![set(position, target)],
+        Some(false) => (),
+        None => return vec![],
+    }
+
+    let neighbor = position + offset;
+
+    if world.get_voxel(neighbor) == Some(target) && is_covered(neighbor) == Some(false) {
+        vec![set(neighbor, kind)]
+    } else {
+        vec![]
+    }
+}
+
+/**
+  Replaces the voxel by a tree trunk with leaves on top. Nothing happens when any voxel where the trunk
+  would be is taken or unloaded. Leaves only replaces empty voxels.
+*/
+fn grow(
+    world: &VoxWorld,
+    position: IVec3,
+    trunk: Kind,
+    leaves: Kind,
+    height: i32,
+) -> Vec<SetVoxel> {
+    let is_empty = |position: IVec3| {
+        world
+            .get_voxel(position)
+            .filter(|kind| kind.is_empty())
+            .is_some()
+    };
+
+    if !(1..height).all(|y| is_empty(position + IVec3::Y * y)) {
+        return vec![];
+    }
+
+    let mut edits = (0..height)
+        .map(|y| set(position + IVec3::Y * y, trunk))
+        .collect::<Vec<_>>();
+
+    let top = position + IVec3::Y * height;
+
+    // Two wide layers around the top of the trunk and a narrow one above it
+    for y in -2..=0 {
+        let radius = if y == 0 { 1 } else { 2 };
+
+        for offset in
+            query::range_inclusive((-radius, y, -radius).into(), (radius, y, radius).into())
+        {
+            let is_corner = radius > 1 && offset.x.abs() == radius && offset.z.abs() == radius;
+            let is_trunk = offset.x == 0 && offset.z == 0 && y < 0;
+
+            if !is_corner && !is_trunk && is_empty(top + offset) {
+                edits.push(set(top + offset, leaves));
+            }
+        }
+    }
+
+    edits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chunk::ChunkKind,
+        voxel::{KindDescription, MeshShape, PropShape},
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+
+    const GRASS: u16 = 1;
+    const DIRT: u16 = 2;
+    const SAPLING: u16 = 3;
+    const LOG: u16 = 4;
+    const LEAVES: u16 = 5;
+
+    fn description(name: &str, id: u16, tick: Option<TickBehavior>) -> KindDescription {
+        KindDescription {
+            name: name.to_string(),
+            id,
+            color: (1.0, 1.0, 1.0, 1.0),
+            shape: MeshShape::Cube,
+            prop: None,
+            directional: false,
+            light: 0,
+            tick,
+            signal: None,
+            toggle: None,
+            hardness: 0.0,
+            tool_tier: 0,
+            block_entity: None,
+            explosive: None,
+            liquid: None,
+            climbable: false,
+            surface: None,
+            foliage: false,
+            tint: None,
+            portal: None,
+        }
+    }
+
+    fn registry() -> KindRegistry {
+        KindRegistry::new(vec![
+            description("Grass", GRASS, Some(TickBehavior::Spread { target: DIRT })),
+            description("Dirt", DIRT, None),
+            KindDescription {
+                prop: Some(PropShape::Billboard),
+                ..description(
+                    "Sapling",
+                    SAPLING,
+                    Some(TickBehavior::Grow {
+                        trunk: LOG,
+                        leaves: LEAVES,
+                        height: 4,
+                    }),
+                )
+            },
+            description("Log", LOG, None),
+            description("Leaves", LEAVES, None),
+        ])
+    }
+
+    fn apply(world: &VoxWorld, edits: Vec<SetVoxel>) {
+        for SetVoxel { chunk, voxel, kind } in edits {
+            world.get_mut(chunk).unwrap().set(voxel, kind);
+        }
+    }
+
+    #[test]
+    fn spread() {
+        let world = VoxWorld::default();
+        let registry = registry();
+
+        let mut kind = ChunkKind::default();
+        kind.set((5, 5, 5).into(), GRASS.into());
+        kind.set((6, 5, 5).into(), DIRT.into());
+        kind.set((5, 4, 6).into(), DIRT.into());
+        kind.set((5, 5, 6).into(), DIRT.into());
+        world.add(IVec3::ZERO, kind);
+
+        let grass = IVec3::new(5, 5, 5);
+
+        let edits = super::spread(
+            &world,
+            &registry,
+            grass,
+            GRASS.into(),
+            DIRT.into(),
+            IVec3::X,
+        );
+        apply(&world, edits);
+        assert_eq!(world.get_voxel((6, 5, 5).into()), Some(GRASS.into()));
+
+        // Dirt covered by other dirt is never converted
+        let edits = super::spread(
+            &world,
+            &registry,
+            grass,
+            GRASS.into(),
+            DIRT.into(),
+            (0, -1, 1).into(),
+        );
+        assert!(edits.is_empty());
+
+        // Covered grass dies
+        world
+            .get_mut(IVec3::ZERO)
+            .unwrap()
+            .set((6, 6, 5).into(), DIRT.into());
+        let edits = super::spread(
+            &world,
+            &registry,
+            (6, 5, 5).into(),
+            GRASS.into(),
+            DIRT.into(),
+            IVec3::X,
+        );
+        apply(&world, edits);
+        assert_eq!(world.get_voxel((6, 5, 5).into()), Some(DIRT.into()));
+
+        // Grass at the top of the chunk doesn't know what is above it
+        world
+            .get_mut(IVec3::ZERO)
+            .unwrap()
+            .set((1, 15, 1).into(), GRASS.into());
+        let edits = super::spread(
+            &world,
+            &registry,
+            (1, 15, 1).into(),
+            GRASS.into(),
+            DIRT.into(),
+            IVec3::X,
+        );
+        assert!(edits.is_empty());
+    }
+
+    #[test]
+    fn grow_across_chunks() {
+        let world = VoxWorld::default();
+        let registry = registry();
+
+        for local in query::range_inclusive((-1, 0, -1).into(), (1, 1, 1).into()) {
+            world.add(local, ChunkKind::default());
+        }
+
+        let sapling = IVec3::new(0, 14, 0);
+        world
+            .get_mut(IVec3::ZERO)
+            .unwrap()
+            .set(sapling, SAPLING.into());
+
+        let mut rng = StdRng::seed_from_u64(1);
+        let edits = tick_voxel(&world, &registry, sapling, &mut rng);
+        apply(&world, edits);
+
+        for y in 14..18 {
+            assert_eq!(world.get_voxel((0, y, 0).into()), Some(LOG.into()));
+        }
+
+        assert_eq!(world.get_voxel((0, 18, 0).into()), Some(LEAVES.into()));
+        assert_eq!(world.get_voxel((-2, 16, -1).into()), Some(LEAVES.into()));
+        assert_eq!(world.get_voxel((-2, 16, -2).into()), Some(0.into()));
+    }
+
+    #[test]
+    fn grow_blocked() {
+        let world = VoxWorld::default();
+        let registry = registry();
+
+        let mut kind = ChunkKind::default();
+        kind.set((3, 3, 3).into(), SAPLING.into());
+        kind.set((3, 6, 3).into(), DIRT.into());
+        world.add(IVec3::ZERO, kind);
+
+        let mut rng = StdRng::seed_from_u64(1);
+        assert!(tick_voxel(&world, &registry, (3, 3, 3).into(), &mut rng).is_empty());
+    }
+
+    #[test]
+    fn random_ticks() {
+        let world = VoxWorld::default();
+        let registry = registry();
+        world.add(IVec3::ZERO, ChunkKind::default());
+
+        // Empty chunks never change
+        let mut rng = StdRng::seed_from_u64(1);
+        let config = RandomTickConfig { per_chunk: 100 };
+        assert!(super::random_ticks(&world, &registry, &config, &mut rng).is_empty());
+    }
+}
@@ -0,0 +1,105 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::edit::Region;
+
+/// Max number of chunks a single ticket can keep loaded, so a typo doesn't load half the world.
+pub const MAX_TICKET_CHUNKS: i64 = 1024;
+
+/**
+  A region of chunks kept loaded, and simulated, even when no player is around, like spawn or machines
+  which must keep running. The region is in chunk coordinates.
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Ticket {
+    pub name: String,
+    pub region: Region,
+}
+
+/**
+  All tickets of the world, saved with the world metadata.
+*/
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tickets(Vec<Ticket>);
+
+impl Tickets {
+    pub fn iter(&self) -> impl Iterator<Item = &Ticket> {
+        self.0.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Ticket> {
+        self.0.iter().find(|ticket| ticket.name == name)
+    }
+
+    /// Whether the given chunk is kept loaded by any ticket.
+    pub fn contains(&self, local: IVec3) -> bool {
+        self.0.iter().any(|ticket| ticket.region.contains(local))
+    }
+
+    /// All chunks kept loaded by tickets. Chunks on overlapping tickets are returned more than once.
+    pub fn chunks(&self) -> impl Iterator<Item = IVec3> + '_ {
+        self.0.iter().flat_map(|ticket| ticket.region.positions())
+    }
+
+    /// Adds a new ticket. Names must be unique and tickets can't be bigger than [`MAX_TICKET_CHUNKS`].
+    pub fn add(&mut self, ticket: Ticket) -> Result<(), String> {
+        if self.get(&ticket.name).is_some() {
+            return Err(format!("Ticket {} already exists", ticket.name));
+        }
+
+        let size = ticket.region.size();
+        let chunks = size.x as i64 * size.y as i64 * size.z as i64;
+        if chunks > MAX_TICKET_CHUNKS {
+            return Err(format!(
+                "Ticket {} has {} chunks, but at most {} are allowed",
+                ticket.name, chunks, MAX_TICKET_CHUNKS
+            ));
+        }
+
+        self.0.push(ticket);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Ticket> {
+        let idx = self.0.iter().position(|ticket| ticket.name == name)?;
+        Some(self.0.remove(idx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ticket(name: &str, min: (i32, i32, i32), max: (i32, i32, i32)) -> Ticket {
+        Ticket {
+            name: name.to_string(),
+            region: Region::from_corners(min.into(), max.into()),
+        }
+    }
+
+    #[test]
+    fn add_remove() {
+        let mut tickets = Tickets::default();
+
+        tickets
+            .add(ticket("spawn", (-1, 0, -1), (1, 0, 1)))
+            .unwrap();
+        tickets.add(ticket("farm", (1, 0, 1), (2, 0, 1))).unwrap();
+
+        assert!(tickets.add(ticket("spawn", (5, 5, 5), (5, 5, 5))).is_err());
+        assert!(tickets.add(ticket("huge", (0, 0, 0), (99, 0, 99))).is_err());
+
+        assert!(tickets.contains((0, 0, 0).into()));
+        assert!(tickets.contains((2, 0, 1).into()));
+        assert!(!tickets.contains((0, 1, 0).into()));
+        assert_eq!(tickets.chunks().count(), 9 + 2);
+
+        assert_eq!(tickets.remove("spawn").unwrap().name, "spawn");
+        assert!(tickets.remove("spawn").is_none());
+        assert!(!tickets.contains((0, 0, 0).into()));
+    }
+}
@@ -1,6 +1,8 @@
 use bevy::prelude::*;
 use serde::Deserialize;
 use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
 
 use crate::math;
 
@@ -9,13 +11,201 @@ use super::chunk::ChunkStorageType;
 
 pub const SIDE_COUNT: usize = 6;
 
-#[derive(Deserialize)]
+/**
+  Shape of small decorations, which are rendered by instancing instead of being meshed with the chunk.
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum PropShape {
+    /// Two crossed quads, used by grass and flowers.
+    Billboard,
+    /// Thin vertical stick, used by torches.
+    Post,
+}
+
+/**
+  Geometry emitted by the mesher for a kind. Only cubes occlude their neighbors.
+*/
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum MeshShape {
+    #[default]
+    Cube,
+    /// Two diagonal quads crossing each other, used by vegetation.
+    Cross,
+    /// Bottom half of a cube.
+    Slab,
+    /// A slab with an extra step on the back half.
+    Stairs,
+    /// Thin plate standing on the back side, used by closed doors and open trapdoors.
+    Panel,
+    /// Thin plate standing on the left side, used by open doors.
+    SidePanel,
+    /// Thin plate lying on the bottom side, used by closed trapdoors.
+    Plate,
+}
+
+/**
+  What happens when a voxel receives a random tick. See [`crate::tick`].
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum TickBehavior {
+    /// Converts a neighbor of the `target` kind which has nothing on top, like grass spreading to dirt.
+    /// Turns back into `target` when covered.
+    Spread { target: u16 },
+    /// Grows into a tree, if there is enough room above it.
+    Grow { trunk: u16, leaves: u16, height: u8 },
+    /// Decays, like leaves of a cut tree, when there is no `support` kind within `range` voxels of the same kind.
+    /// See [`crate::decay`].
+    Decay { support: u16, range: u8 },
+}
+
+/**
+  How a kind takes part on signal networks. See [`crate::signal`].
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum SignalRole {
+    /// Powers its neighbors with the strongest signal, like a lever which is on.
+    Emitter,
+    /// Carries the signal, which gets weaker on each wire voxel.
+    Wire,
+    /// Switches to the `powered` kind while receiving any signal, and back to `unpowered` otherwise.
+    Actuator { powered: u16, unpowered: u16 },
+}
+
+/**
+  How an explosive kind blows up. Its fuse is lit when the player interacts with it, or when it's caught in
+  another explosion, which lights a shorter fuse so chain reactions ripple out.
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Explosive {
+    /// Simulation ticks from lighting the fuse until it blows up.
+    pub fuse: u64,
+    pub radius: f32,
+    pub power: f32,
+}
+
+/**
+  How a liquid kind acts on things inside it. Liquids aren't solid, so things sink into them, and are slowed
+  down and pushed up the deeper they are.
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Liquid {
+    /// Upwards push on things fully inside it, relative to gravity. Things float when it's above one.
+    pub buoyancy: f32,
+    /// How much of the velocity of things fully inside it is lost each second.
+    pub drag: f32,
+}
+
+/**
+  How a kind feels to walk on. Slippery kinds, like ice, take longer to speed up and stop on, while sticky
+  ones, like mud, slow down whoever walks on them.
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Surface {
+    /// How fast things speed up and stop on it, relative to the usual ground.
+    pub friction: f32,
+    /// How fast things walk on it, relative to their usual speed.
+    pub speed: f32,
+}
+
+/**
+  Colors which change with the biome voxels are on, like grass turning yellow on deserts. Tints of the same
+  kind are shared by many kinds, so all grass looks the same on each biome.
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum Tint {
+    Grass,
+    Foliage,
+    Water,
+}
+
+impl Default for Surface {
+    fn default() -> Self {
+        Self {
+            friction: 1.0,
+            speed: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct KindDescription {
     pub name: String,
     pub id: u16,
     pub color: (f32, f32, f32, f32),
+    #[serde(default)]
+    pub shape: MeshShape,
+    /// When set, this kind is rendered as a prop and doesn't occlude its neighbors. Takes precedence over `shape`.
+    #[serde(default)]
+    pub prop: Option<PropShape>,
+    /// When set, placed voxels of this kind faces the player, like stairs and furnaces.
+    #[serde(default)]
+    pub directional: bool,
+    /// Light level emitted by this kind, up to [`crate::light::MAX_LIGHT`].
+    #[serde(default)]
+    pub light: u8,
+    /// What this kind does when it receives a random tick.
+    #[serde(default)]
+    pub tick: Option<TickBehavior>,
+    /// How this kind takes part on signal networks.
+    #[serde(default)]
+    pub signal: Option<SignalRole>,
+    /// Kind which this one turns into when the player interacts with it, like a door being opened.
+    #[serde(default)]
+    pub toggle: Option<u16>,
+    /// How long, in seconds, it takes to break voxels of this kind by hand. Zero breaks them right away.
+    #[serde(default)]
+    pub hardness: f32,
+    /// Lowest [`crate::item::ToolDescription::tier`] which can break voxels of this kind. Zero allows bare hands.
+    #[serde(default)]
+    pub tool_tier: u8,
+    /// Name of the [`crate::block_entity::BlockEntity`] attached to each voxel of this kind, like the items of
+    /// a chest.
+    #[serde(default)]
+    pub block_entity: Option<String>,
+    /// When set, voxels of this kind blow up some time after their fuse is lit, like TNT.
+    #[serde(default)]
+    pub explosive: Option<Explosive>,
+    /// When set, voxels of this kind are a liquid, like water, which things swim or float in.
+    #[serde(default)]
+    pub liquid: Option<Liquid>,
+    /// When set, voxels of this kind can be climbed up and down, like ladders.
+    #[serde(default)]
+    pub climbable: bool,
+    /// How this kind feels to walk on. When not set, it's the usual [`Surface::default`].
+    #[serde(default)]
+    pub surface: Option<Surface>,
+    /// When set, voxels of this kind sway in the wind, like grass and leaves.
+    #[serde(default)]
+    pub foliage: bool,
+    /// When set, voxels of this kind are tinted by the biome they're on, like grass and water.
+    #[serde(default)]
+    pub tint: Option<Tint>,
+    /// When set, voxels of this kind are portals to the dimension with this name, which the player walks into.
+    #[serde(default)]
+    pub portal: Option<String>,
 }
 
+/// Bits of [`Kind`] used to store the kind id. The remaining top nibble holds the facing.
+const KIND_ID_MASK: u16 = 0x0FFF;
+const FACING_SHIFT: u16 = 12;
+
+/**
+  Directions which a voxel can face, indexed by the facing nibble. The default facing is the first one,
+  which keeps the shape geometry as it is.
+*/
+const FACINGS: [Side; SIDE_COUNT] = [
+    Side::Front,
+    Side::Right,
+    Side::Back,
+    Side::Left,
+    Side::Up,
+    Side::Down,
+];
+
+/**
+  Voxel kind. The lower 12 bits are the kind id while the top nibble holds which direction the voxel faces,
+  used by directional kinds like stairs.
+*/
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Default, Deserialize, Serialize)]
 pub struct Kind(u16);
 
@@ -27,7 +217,198 @@ impl From<u16> for Kind {
 
 impl Kind {
     pub fn is_empty(&self) -> bool {
-        self.0 == 0
+        self.id() == 0
+    }
+
+    pub fn id(&self) -> u16 {
+        self.0 & KIND_ID_MASK
+    }
+
+    pub fn facing(&self) -> Side {
+        FACINGS
+            .get((self.0 >> FACING_SHIFT) as usize)
+            .copied()
+            .unwrap_or(FACINGS[0])
+    }
+
+    pub fn with_facing(self, facing: Side) -> Self {
+        let nibble = FACINGS.iter().position(|f| *f == facing).unwrap_or(0) as u16;
+        Self(self.id() | nibble << FACING_SHIFT)
+    }
+}
+
+/**
+  All known kinds, indexed by their id.
+*/
+#[derive(Default)]
+pub struct KindRegistry {
+    descriptions: HashMap<u16, KindDescription>,
+}
+
+impl KindRegistry {
+    pub fn load(path: &Path) -> Self {
+        let file = std::fs::File::open(path).unwrap_or_else(|_| {
+            panic!(
+                "Failed opening kind descriptions file at {}",
+                path.display()
+            )
+        });
+
+        let descriptions: Vec<KindDescription> = ron::de::from_reader(file)
+            .unwrap_or_else(|_| panic!("Failed to parse kind descriptions {}", path.display()));
+
+        Self::new(descriptions)
+    }
+
+    pub fn new(descriptions: Vec<KindDescription>) -> Self {
+        Self::try_new(descriptions).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Same as [`KindRegistry::new`], but returns an error instead of panicking, for descriptions not trusted.
+    pub fn try_new(descriptions: Vec<KindDescription>) -> Result<Self, String> {
+        let mut registry = Self::default();
+
+        for desc in descriptions {
+            if desc.id > KIND_ID_MASK {
+                return Err(format!("Kind id {} ({}) is too big", desc.id, desc.name));
+            }
+
+            if let Some(existing) = registry.descriptions.insert(desc.id, desc) {
+                return Err(format!(
+                    "Duplicated kind id {} ({})",
+                    existing.id, existing.name
+                ));
+            }
+        }
+
+        Ok(registry)
+    }
+
+    pub fn get(&self, kind: Kind) -> Option<&KindDescription> {
+        self.descriptions.get(&kind.id())
+    }
+
+    /// Kind with the given name, if there is one. Names are compared ignoring case.
+    pub fn find(&self, name: &str) -> Option<Kind> {
+        self.descriptions
+            .values()
+            .find(|desc| desc.name.eq_ignore_ascii_case(name))
+            .map(|desc| desc.id.into())
+    }
+
+    pub fn name(&self, kind: Kind) -> &str {
+        self.get(kind).map_or("Unknown", |desc| &desc.name)
+    }
+
+    /**
+      Hash of all descriptions, which is the same for registries loaded from equivalent files. It's stable
+      across builds, so it can be compared with registries of other games.
+    */
+    pub fn hash(&self) -> u64 {
+        let mut ids = self.descriptions.keys().copied().collect::<Vec<_>>();
+        ids.sort_unstable();
+
+        math::fnv_hash(
+            ids.iter()
+                .map(|id| format!("{:?}", self.descriptions[id]))
+                .flat_map(String::into_bytes),
+        )
+    }
+
+    pub fn descriptions(&self) -> impl Iterator<Item = &KindDescription> {
+        self.descriptions.values()
+    }
+
+    pub fn kinds(&self) -> impl Iterator<Item = Kind> + '_ {
+        self.descriptions.keys().map(|id| Kind(*id))
+    }
+
+    pub fn prop(&self, kind: Kind) -> Option<PropShape> {
+        self.get(kind).and_then(|desc| desc.prop)
+    }
+
+    pub fn is_directional(&self, kind: Kind) -> bool {
+        self.get(kind).filter(|desc| desc.directional).is_some()
+    }
+
+    pub fn is_climbable(&self, kind: Kind) -> bool {
+        self.get(kind).filter(|desc| desc.climbable).is_some()
+    }
+
+    pub fn is_foliage(&self, kind: Kind) -> bool {
+        self.get(kind).filter(|desc| desc.foliage).is_some()
+    }
+
+    pub fn tint(&self, kind: Kind) -> Option<Tint> {
+        self.get(kind).and_then(|desc| desc.tint)
+    }
+
+    /// Light level emitted by the given kind.
+    pub fn light(&self, kind: Kind) -> u8 {
+        self.get(kind)
+            .map_or(0, |desc| u8::min(desc.light, crate::light::MAX_LIGHT))
+    }
+
+    pub fn tick(&self, kind: Kind) -> Option<TickBehavior> {
+        self.get(kind).and_then(|desc| desc.tick)
+    }
+
+    pub fn block_entity(&self, kind: Kind) -> Option<&str> {
+        self.get(kind).and_then(|desc| desc.block_entity.as_deref())
+    }
+
+    pub fn explosive(&self, kind: Kind) -> Option<Explosive> {
+        self.get(kind).and_then(|desc| desc.explosive)
+    }
+
+    pub fn liquid(&self, kind: Kind) -> Option<Liquid> {
+        self.get(kind).and_then(|desc| desc.liquid)
+    }
+
+    /// Name of the dimension voxels of this kind lead to, if they're portals.
+    pub fn portal(&self, kind: Kind) -> Option<&str> {
+        self.get(kind).and_then(|desc| desc.portal.as_deref())
+    }
+
+    pub fn surface(&self, kind: Kind) -> Surface {
+        self.get(kind)
+            .and_then(|desc| desc.surface)
+            .unwrap_or_default()
+    }
+
+    /// Kind which the given one turns into when interacted with. The facing is kept.
+    pub fn toggle(&self, kind: Kind) -> Option<Kind> {
+        self.get(kind)
+            .and_then(|desc| desc.toggle)
+            .map(|id| Kind(id).with_facing(kind.facing()))
+    }
+
+    pub fn signal(&self, kind: Kind) -> Option<SignalRole> {
+        self.get(kind).and_then(|desc| desc.signal)
+    }
+
+    /**
+      Shape which the given kind must be meshed with, or `None` if it isn't meshed at all, like empty and prop kinds.
+      Unknown kinds are meshed as cubes.
+    */
+    pub fn mesh_shape(&self, kind: Kind) -> Option<MeshShape> {
+        if kind.is_empty() {
+            return None;
+        }
+
+        match self.get(kind) {
+            Some(desc) if desc.prop.is_some() => None,
+            Some(desc) => Some(desc.shape),
+            None => Some(MeshShape::Cube),
+        }
+    }
+
+    /**
+      Whether the given kind is a full cube which hides its neighbors faces.
+      Unknown kinds are treated as opaque.
+    */
+    pub fn is_opaque(&self, kind: Kind) -> bool {
+        self.mesh_shape(kind) == Some(MeshShape::Cube)
     }
 }
 
@@ -74,21 +455,89 @@ impl Side {
             Side::Back => -IVec3::Z,
         }
     }
-}
 
+    /**
+      Rotation which turns the default facing, [`Side::Front`], into the given facing.
+      Horizontal facings rotates around the Y axis, so shapes are kept upright.
+    */
+    pub fn facing_rotation(facing: Side) -> Quat {
+        use std::f32::consts::{FRAC_PI_2, PI};
+
+        match facing {
+            Side::Front => Quat::IDENTITY,
+            Side::Right => Quat::from_rotation_y(FRAC_PI_2),
+            Side::Back => Quat::from_rotation_y(PI),
+            Side::Left => Quat::from_rotation_y(-FRAC_PI_2),
+            Side::Up => Quat::from_rotation_x(-FRAC_PI_2),
+            Side::Down => Quat::from_rotation_x(FRAC_PI_2),
+        }
+    }
+
+    /**
+      Facing of a voxel placed by someone looking at the given direction. Only horizontal facings are returned,
+      pointing back to whoever is placing it.
+    */
+    pub fn facing_towards(look: Vec3) -> Side {
+        Self::from_normal(-Vec3::new(look.x, 0.0, look.z))
+    }
+
+    fn from_normal(normal: Vec3) -> Side {
+        SIDES
+            .into_iter()
+            .max_by(|a, b| a.normal().dot(normal).total_cmp(&b.normal().dot(normal)))
+            .unwrap()
+    }
+
+    /// Returns where this side ends up, when a shape is rotated to the given facing.
+    pub fn rotated(&self, facing: Side) -> Side {
+        Self::from_normal(Self::facing_rotation(facing) * self.normal())
+    }
+
+    /// Inverse of [`Side::rotated`].
+    pub fn unrotated(&self, facing: Side) -> Side {
+        Self::from_normal(Self::facing_rotation(facing).inverse() * self.normal())
+    }
+
+    pub fn opposite(&self) -> Side {
+        match self {
+            Side::Right => Side::Left,
+            Side::Left => Side::Right,
+            Side::Up => Side::Down,
+            Side::Down => Side::Up,
+            Side::Front => Side::Back,
+            Side::Back => Side::Front,
+        }
+    }
+}
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct VoxelFace {
-    pub vertices: [IVec3; 4],
+    pub vertices: [Vec3; 4],
     pub side: Side,
-    //TODO: light and color
+    /// Ambient occlusion level of each vertex, from 0 (fully occluded) to 3 (not occluded).
+    pub ao: [u8; 4],
+    /// How much each vertex sways in the wind, from 0.0 (still) to 1.0. Only foliage faces sway.
+    pub sway: [f32; 4],
+    /// Biome tint of the voxel which owns the face, if any.
+    pub tint: Option<Tint>,
+    //TODO: light
 }
 
 #[derive(Debug, Default, PartialEq)]
 pub struct VoxelVertex {
     pub position: Vec3,
     pub normal: Vec3,
-    //TODO: light and color
+    /// Ambient occlusion factor, from 0.0 (fully occluded) to 1.0 (not occluded).
+    pub ao: f32,
+    /// Texture coordinates within the block texture.
+    pub uv: Vec2,
+    /// Points to where `u` grows. The `w` component holds the bitangent sign, like glTF tangents.
+    pub tangent: Vec4,
+    /// How much the vertex sways in the wind, from 0.0 (still) to 1.0.
+    pub sway: f32,
+    /// Where the vertex color is looked up on the biome colormap, the same way textures are by `uv`.
+    pub tint: Vec2,
+    //TODO: light
 }
 
 pub fn to_local(world: Vec3) -> IVec3 {
@@ -102,7 +551,7 @@ pub fn to_local(world: Vec3) -> IVec3 {
 }
 
 pub fn to_world(local: IVec3, chunk_local: IVec3) -> Vec3 {
-    chunk::to_world(chunk_local) + local.as_vec3()
+    chunk::join_voxel(chunk_local, local).as_vec3()
 }
 
 #[cfg(test)]
@@ -113,7 +562,53 @@ mod tests {
 
     use crate::voxel::KindDescription;
 
-   
+    #[test]
+    fn facing() {
+        let kind = Kind::from(42);
+        assert_eq!(kind.facing(), Side::Front);
+
+        for side in SIDES {
+            let rotated = kind.with_facing(side);
+
+            assert_eq!(rotated.id(), 42);
+            assert_eq!(rotated.facing(), side);
+            assert!(!rotated.is_empty());
+        }
+
+        assert!(Kind::default().with_facing(Side::Up).is_empty());
+    }
+
+    #[test]
+    fn rotated() {
+        for facing in SIDES {
+            assert_eq!(Side::Front.rotated(facing), facing);
+
+            for side in SIDES {
+                assert_eq!(side.rotated(facing).unrotated(facing), side);
+            }
+        }
+
+        // Horizontal facings keeps shapes upright
+        assert_eq!(Side::Up.rotated(Side::Right), Side::Up);
+        assert_eq!(Side::Back.rotated(Side::Right), Side::Left);
+        assert_eq!(Side::Right.rotated(Side::Back), Side::Left);
+    }
+
+    #[test]
+    fn facing_towards() {
+        assert_eq!(Side::facing_towards(Vec3::new(0.2, -0.9, 0.8)), Side::Back);
+        assert_eq!(Side::facing_towards(Vec3::new(-0.7, 0.5, 0.1)), Side::Right);
+        assert_eq!(Side::facing_towards(-Vec3::Z), Side::Front);
+    }
+
+    #[test]
+    fn opposite() {
+        for side in SIDES {
+            assert_eq!(side.opposite().dir(), -side.dir());
+            assert_eq!(side.opposite().opposite(), side);
+        }
+    }
+
     #[test]
     fn to_world() {
         use super::*;
@@ -218,4 +713,240 @@ mod tests {
 
         let _: Vec<KindDescription> = from_reader(f).unwrap();
     }
+
+    #[test]
+    fn kind_registry() {
+        let input_path = format!(
+            "{}assets/voxels/kind_descriptions.ron",
+            env!("CARGO_WORKSPACE_DIR")
+        );
+
+        let registry = KindRegistry::load(Path::new(&input_path));
+
+        assert_eq!(registry.name(0.into()), "None");
+        assert_eq!(registry.name(1.into()), "Grass");
+        assert_eq!(registry.name(u16::MAX.into()), "Unknown");
+        assert_eq!(registry.find("grass"), Some(1.into()));
+        assert_eq!(registry.find("Nothing"), None);
+        assert!(registry.kinds().any(|k| k == 1.into()));
+        assert_eq!(registry.tint(1.into()), Some(Tint::Grass));
+        assert_eq!(registry.tint(0.into()), None);
+
+        // Toggling twice must return to the same kind
+        for kind in registry.kinds() {
+            if let Some(toggled) = registry.toggle(kind) {
+                assert_eq!(registry.toggle(toggled), Some(kind));
+            }
+        }
+    }
+
+    #[test]
+    fn toggle_keeps_facing() {
+        let registry = KindRegistry::new(vec![KindDescription {
+            name: "Door".to_string(),
+            id: 1,
+            color: (0.5, 0.5, 0.5, 1.0),
+            shape: MeshShape::Panel,
+            prop: None,
+            directional: true,
+            light: 0,
+            tick: None,
+            signal: None,
+            toggle: Some(2),
+            hardness: 0.0,
+            tool_tier: 0,
+            block_entity: None,
+            explosive: None,
+            liquid: None,
+            climbable: false,
+            surface: None,
+            foliage: false,
+            tint: None,
+            portal: None,
+        }]);
+
+        let door = Kind::from(1).with_facing(Side::Left);
+        assert_eq!(
+            registry.toggle(door),
+            Some(Kind::from(2).with_facing(Side::Left))
+        );
+        assert_eq!(registry.toggle(2.into()), None);
+    }
+
+    #[test]
+    fn is_opaque() {
+        let registry = KindRegistry::new(vec![
+            KindDescription {
+                name: "Stone".to_string(),
+                id: 1,
+                color: (0.5, 0.5, 0.5, 1.0),
+                shape: MeshShape::Cube,
+                prop: None,
+                directional: false,
+                light: 0,
+                tick: None,
+                signal: None,
+                toggle: None,
+                hardness: 0.0,
+                tool_tier: 0,
+                block_entity: None,
+                explosive: None,
+                liquid: None,
+                climbable: false,
+                surface: None,
+                foliage: false,
+                tint: None,
+                portal: None,
+            },
+            KindDescription {
+                name: "Flower".to_string(),
+                id: 2,
+                color: (1.0, 0.0, 0.0, 1.0),
+                shape: MeshShape::Cube,
+                prop: Some(PropShape::Billboard),
+                directional: false,
+                light: 0,
+                tick: None,
+                signal: None,
+                toggle: None,
+                hardness: 0.0,
+                tool_tier: 0,
+                block_entity: None,
+                explosive: None,
+                liquid: None,
+                climbable: false,
+                surface: None,
+                foliage: false,
+                tint: None,
+                portal: None,
+            },
+            KindDescription {
+                name: "Fern".to_string(),
+                id: 3,
+                color: (0.0, 1.0, 0.0, 1.0),
+                shape: MeshShape::Cross,
+                prop: None,
+                directional: false,
+                light: 0,
+                tick: None,
+                signal: None,
+                toggle: None,
+                hardness: 0.0,
+                tool_tier: 0,
+                block_entity: None,
+                explosive: None,
+                liquid: None,
+                climbable: false,
+                surface: None,
+                foliage: false,
+                tint: None,
+                portal: None,
+            },
+        ]);
+
+        assert!(!registry.is_opaque(0.into()));
+        assert!(registry.is_opaque(1.into()));
+        assert!(!registry.is_opaque(2.into()));
+        assert!(!registry.is_opaque(3.into()));
+        assert!(registry.is_opaque(99.into()));
+
+        assert_eq!(registry.mesh_shape(0.into()), None);
+        assert_eq!(registry.mesh_shape(1.into()), Some(MeshShape::Cube));
+        assert_eq!(registry.mesh_shape(2.into()), None);
+        assert_eq!(registry.mesh_shape(3.into()), Some(MeshShape::Cross));
+
+        assert_eq!(registry.prop(1.into()), None);
+        assert_eq!(registry.prop(2.into()), Some(PropShape::Billboard));
+
+        assert!(!registry.is_directional(1.into()));
+        assert!(!registry.is_directional(99.into()));
+    }
+
+    #[test]
+    #[should_panic]
+    fn kind_registry_duplicated() {
+        let desc = || KindDescription {
+            name: "Test".to_string(),
+            id: 1,
+            color: (0.0, 0.0, 0.0, 0.0),
+            shape: MeshShape::Cube,
+            prop: None,
+            directional: false,
+            light: 0,
+            tick: None,
+            signal: None,
+            toggle: None,
+            hardness: 0.0,
+            tool_tier: 0,
+            block_entity: None,
+            explosive: None,
+            liquid: None,
+            climbable: false,
+            surface: None,
+            foliage: false,
+            tint: None,
+            portal: None,
+        };
+
+        KindRegistry::new(vec![desc(), desc()]);
+    }
+
+    #[test]
+    fn kind_registry_try_new() {
+        let input_path = format!(
+            "{}assets/voxels/kind_descriptions.ron",
+            env!("CARGO_WORKSPACE_DIR")
+        );
+
+        let registry = KindRegistry::load(Path::new(&input_path));
+        let descriptions = registry.descriptions().cloned().collect::<Vec<_>>();
+
+        assert!(KindRegistry::try_new(descriptions.clone()).is_ok());
+
+        let mut duplicated = descriptions.clone();
+        duplicated.push(descriptions[0].clone());
+        assert!(KindRegistry::try_new(duplicated).is_err());
+
+        let mut too_big = descriptions;
+        too_big[0].id = KIND_ID_MASK + 1;
+        assert!(KindRegistry::try_new(too_big).is_err());
+    }
+
+    #[test]
+    fn kind_registry_hash() {
+        let desc = |id, name: &str| KindDescription {
+            name: name.to_string(),
+            id,
+            color: (0.0, 0.0, 0.0, 0.0),
+            shape: MeshShape::Cube,
+            prop: None,
+            directional: false,
+            light: 0,
+            tick: None,
+            signal: None,
+            toggle: None,
+            hardness: 0.0,
+            tool_tier: 0,
+            block_entity: None,
+            explosive: None,
+            liquid: None,
+            climbable: false,
+            surface: None,
+            foliage: false,
+            tint: None,
+            portal: None,
+        };
+
+        let registry = KindRegistry::new(vec![desc(1, "Stone"), desc(2, "Dirt")]);
+
+        // Descriptions order doesn't matter, but their content does
+        assert_eq!(
+            registry.hash(),
+            KindRegistry::new(vec![desc(2, "Dirt"), desc(1, "Stone")]).hash()
+        );
+        assert_ne!(
+            registry.hash(),
+            KindRegistry::new(vec![desc(1, "Stone"), desc(2, "Sand")]).hash()
+        );
+    }
 }
@@ -0,0 +1,129 @@
+use bevy::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+use crate::{biome::Precipitation, heightmap::Heightmap, world::VoxWorld};
+
+/// How long, in seconds, the sky stays clear.
+const CLEAR_DURATION: Range<f32> = 120.0..600.0;
+/// How long, in seconds, it keeps precipitating.
+const PRECIPITATING_DURATION: Range<f32> = 60.0..300.0;
+
+/**
+  Global weather of the world. What actually falls on each column depends on its biome, and nothing falls
+  under roofs.
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Weather {
+    pub precipitating: bool,
+    /// Seconds until the weather changes.
+    pub remaining: f32,
+}
+
+impl Default for Weather {
+    fn default() -> Self {
+        Self {
+            precipitating: false,
+            remaining: CLEAR_DURATION.start,
+        }
+    }
+}
+
+impl Weather {
+    /**
+      Advances the weather by the given seconds. Returns true if it started or stopped precipitating.
+    */
+    pub fn tick(&mut self, delta: f32, rng: &mut impl Rng) -> bool {
+        self.remaining -= delta;
+
+        if self.remaining > 0.0 {
+            return false;
+        }
+
+        self.precipitating = !self.precipitating;
+        self.remaining = if self.precipitating {
+            rng.gen_range(PRECIPITATING_DURATION)
+        } else {
+            rng.gen_range(CLEAR_DURATION)
+        };
+
+        true
+    }
+
+    /**
+      What is falling on the given world voxel, if any.
+    */
+    pub fn precipitation_at(
+        &self,
+        world: &VoxWorld,
+        heightmap: &Heightmap,
+        voxel: IVec3,
+    ) -> Option<Precipitation> {
+        if !self.precipitating || !heightmap.is_exposed(voxel) {
+            return None;
+        }
+
+        world.biome(voxel).precipitation()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{biome, chunk::ChunkKind, voxel::KindRegistry};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn tick() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut weather = Weather::default();
+
+        assert!(!weather.tick(CLEAR_DURATION.start - 1.0, &mut rng));
+        assert!(!weather.precipitating);
+
+        assert!(weather.tick(1.0, &mut rng));
+        assert!(weather.precipitating);
+        assert!(PRECIPITATING_DURATION.contains(&weather.remaining));
+
+        assert!(weather.tick(PRECIPITATING_DURATION.end, &mut rng));
+        assert!(!weather.precipitating);
+        assert!(CLEAR_DURATION.contains(&weather.remaining));
+    }
+
+    #[test]
+    fn precipitation_at() {
+        let registry = KindRegistry::default();
+        let world = VoxWorld::default();
+        let mut heightmap = Heightmap::default();
+
+        // Look for a column where it rains
+        let column = (0..1000)
+            .map(|x| IVec2::new(x * 64, 0))
+            .find(|column| biome::at(*column).precipitation() == Some(Precipitation::Rain))
+            .unwrap();
+
+        let mut roof = ChunkKind::default();
+        roof.set((0, 10, 0).into(), 1.into());
+        heightmap.load((column.x / 16, 0, 0).into(), &roof, &registry);
+
+        let mut weather = Weather::default();
+        let under_roof = IVec3::new(column.x, 5, 0);
+        let above_roof = IVec3::new(column.x, 11, 0);
+
+        assert_eq!(
+            weather.precipitation_at(&world, &heightmap, above_roof),
+            None
+        );
+
+        weather.precipitating = true;
+        assert_eq!(
+            weather.precipitation_at(&world, &heightmap, under_roof),
+            None
+        );
+        assert_eq!(
+            weather.precipitation_at(&world, &heightmap, above_roof),
+            Some(Precipitation::Rain)
+        );
+    }
+}
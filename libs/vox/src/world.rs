@@ -1,47 +1,160 @@
 use bevy::prelude::*;
-use std::collections::HashMap;
+use parking_lot::{
+    MappedRwLockReadGuard, MappedRwLockWriteGuard, RwLock, RwLockReadGuard, RwLockWriteGuard,
+};
+use std::{collections::HashMap, sync::Arc};
 
 use super::{
-    chunk::{ChunkKind, ChunkNeighborhood},
-    voxel,
+    biome::{self, Biome, ChunkBiomes},
+    chunk::{self, ChunkKind, ChunkNeighborhood},
+    query, voxel,
 };
 
-#[derive(Default)]
+const SHARD_COUNT: usize = 16;
+
+pub type ChunkRef<'a> = MappedRwLockReadGuard<'a, ChunkKind>;
+pub type ChunkRefMut<'a> = MappedRwLockWriteGuard<'a, ChunkKind>;
+
+type Shard = RwLock<HashMap<IVec3, ChunkKind>>;
+
+/**
+  Shared handle to all loaded chunks.
+
+  Chunks are spread across a fixed number of shards, each one behind its own RwLock, so workers can read
+  and write chunks in parallel as long as they don't hit the same shard. Cloning is cheap and returns a
+  handle to the same underlying world.
+
+  Guards returned by [`VoxWorld::get`] and [`VoxWorld::get_mut`] lock the whole shard, so avoid holding
+  them while accessing other chunks or it may deadlock.
+
+  Chunks may also have their [`ChunkBiomes`], which are kept apart, since they're only read after the chunk
+  is generated.
+*/
+#[derive(Clone)]
 pub struct VoxWorld {
-    chunks: HashMap<IVec3, ChunkKind>,
+    shards: Arc<Vec<Shard>>,
+    biomes: Arc<RwLock<HashMap<IVec3, ChunkBiomes>>>,
+}
+
+impl Default for VoxWorld {
+    fn default() -> Self {
+        Self {
+            shards: Arc::new((0..SHARD_COUNT).map(|_| Shard::default()).collect()),
+            biomes: Default::default(),
+        }
+    }
 }
 
 impl VoxWorld {
-    pub fn add(&mut self, local: IVec3, kind: ChunkKind) {
-        if self.chunks.insert(local, kind).is_some() {
+    fn shard(&self, local: IVec3) -> &Shard {
+        let hash = (local.x.wrapping_mul(73_856_093)
+            ^ local.y.wrapping_mul(19_349_663)
+            ^ local.z.wrapping_mul(83_492_791))
+        .rem_euclid(SHARD_COUNT as i32);
+
+        &self.shards[hash as usize]
+    }
+
+    pub fn add(&self, local: IVec3, kind: ChunkKind) {
+        if self.shard(local).write().insert(local, kind).is_some() {
             panic!("Created a duplicated chunk at {:?}", &local);
         }
     }
 
-    pub fn remove(&mut self, local: IVec3) -> Option<ChunkKind> {
-        self.chunks.remove(&local)
+    /// Removes the chunk, along its biomes.
+    pub fn remove(&self, local: IVec3) -> Option<ChunkKind> {
+        self.biomes.write().remove(&local);
+        self.shard(local).write().remove(&local)
+    }
+
+    /// Keeps the biomes of the given chunk, until the chunk is removed.
+    pub fn set_biomes(&self, local: IVec3, biomes: ChunkBiomes) {
+        self.biomes.write().insert(local, biomes);
+    }
+
+    pub fn biomes(&self, local: IVec3) -> Option<MappedRwLockReadGuard<'_, ChunkBiomes>> {
+        RwLockReadGuard::try_map(self.biomes.read(), |biomes| biomes.get(&local)).ok()
+    }
+
+    /**
+      Biome of the column of the given world voxel. Chunks without biomes, like the ones received from a
+      server, fall back to sampling them from noise.
+    */
+    pub fn biome(&self, world: IVec3) -> Biome {
+        let (local, voxel) = chunk::split_voxel(world);
+
+        match self.biomes(local) {
+            Some(biomes) => biomes.get(voxel.x, voxel.z),
+            None => biome::at(IVec2::new(world.x, world.z)),
+        }
+    }
+
+    pub fn get(&self, local: IVec3) -> Option<ChunkRef<'_>> {
+        RwLockReadGuard::try_map(self.shard(local).read(), |chunks| chunks.get(&local)).ok()
+    }
+
+    pub fn get_mut(&self, local: IVec3) -> Option<ChunkRefMut<'_>> {
+        RwLockWriteGuard::try_map(self.shard(local).write(), |chunks| chunks.get_mut(&local)).ok()
+    }
+
+    /// Kind of the given world voxel, or `None` if its chunk isn't loaded.
+    pub fn get_voxel(&self, world: IVec3) -> Option<voxel::Kind> {
+        let (local, voxel) = chunk::split_voxel(world);
+        self.get(local).map(|chunk| chunk.get(voxel))
+    }
+
+    pub fn exists(&self, local: IVec3) -> bool {
+        self.shard(local).read().contains_key(&local)
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| shard.read().len()).sum()
     }
 
-    pub fn get(&self, local: IVec3) -> Option<&ChunkKind> {
-        self.chunks.get(&local)
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|shard| shard.read().is_empty())
     }
 
-    pub fn get_mut(&mut self, local: IVec3) -> Option<&mut ChunkKind> {
-        self.chunks.get_mut(&local)
+    /**
+      Returns the local of all chunks loaded at the moment this function is called.
+    */
+    pub fn locals(&self) -> Vec<IVec3> {
+        self.shards
+            .iter()
+            .flat_map(|shard| shard.read().keys().copied().collect::<Vec<_>>())
+            .collect()
     }
 
-    pub fn update_neighborhood(&mut self, local: IVec3) {
+    /**
+      Iterates over all chunks, locking a single shard at a time for each returned chunk.
+      Chunks removed while iterating are skipped.
+    */
+    pub fn iter_chunks(&self) -> impl Iterator<Item = (IVec3, ChunkRef<'_>)> {
+        self.locals()
+            .into_iter()
+            .filter_map(move |local| self.get(local).map(|chunk| (local, chunk)))
+    }
+
+    /**
+      Returns the local of all existing chunks which are within the given radius of center.
+      The distance is computed in chunk local coordinates, so a radius of 1 returns the center and its 6 direct neighbors.
+    */
+    pub fn chunks_in_radius(&self, center: IVec3, radius: u32) -> impl Iterator<Item = IVec3> + '_ {
+        query::sphere(center, radius).filter(move |local| self.exists(*local))
+    }
+
+    pub fn update_neighborhood(&self, local: IVec3) {
         let mut neighborhood = ChunkNeighborhood::default();
         for side in voxel::SIDES {
             let dir = side.dir();
             let neighbor = local + dir;
 
             if let Some(neighbor_chunk) = self.get(neighbor) {
-                neighborhood.set(side, neighbor_chunk);
+                neighborhood.set(side, &neighbor_chunk);
             }
         }
 
-        if let Some(chunk) = self.get_mut(local) {
+        if let Some(mut chunk) = self.get_mut(local) {
             chunk.neighborhood = neighborhood;
         }
     }
@@ -54,7 +167,7 @@ mod test {
 
     #[test]
     fn add() {
-        let mut world = VoxWorld::default();
+        let world = VoxWorld::default();
         assert!(world.get(IVec3::ONE).is_none());
         world.add(IVec3::ONE, ChunkKind::default());
         assert!(world.get(IVec3::ONE).is_some());
@@ -63,14 +176,14 @@ mod test {
     #[test]
     #[should_panic]
     fn add_duplicated() {
-        let mut world = VoxWorld::default();
+        let world = VoxWorld::default();
         world.add(IVec3::ONE, ChunkKind::default());
         world.add(IVec3::ONE, ChunkKind::default());
     }
 
     #[test]
     fn remove() {
-        let mut world = VoxWorld::default();
+        let world = VoxWorld::default();
         world.add(IVec3::ONE, ChunkKind::default());
         assert!(world.remove(IVec3::ONE).is_some());
         assert!(world.get(IVec3::ONE).is_none());
@@ -78,14 +191,186 @@ mod test {
 
     #[test]
     fn remove_none() {
-        let mut world = VoxWorld::default();
+        let world = VoxWorld::default();
         assert!(world.remove(IVec3::ONE).is_none());
         assert!(world.get(IVec3::ONE).is_none());
     }
 
+    #[test]
+    fn exists() {
+        let world = VoxWorld::default();
+        assert!(!world.exists(IVec3::ONE));
+
+        world.add(IVec3::ONE, ChunkKind::default());
+        assert!(world.exists(IVec3::ONE));
+        assert!(!world.exists(IVec3::ZERO));
+
+        world.remove(IVec3::ONE);
+        assert!(!world.exists(IVec3::ONE));
+    }
+
+    #[test]
+    fn iter_chunks() {
+        let world = VoxWorld::default();
+        assert_eq!(world.iter_chunks().count(), 0);
+
+        let locals: Vec<IVec3> = vec![(0, 0, 0).into(), (1, -2, 3).into(), (-5, 0, 9).into()];
+        for local in &locals {
+            let mut kind = ChunkKind::default();
+            kind.set_all((local.x as u16).into());
+            world.add(*local, kind);
+        }
+
+        assert_eq!(world.len(), locals.len());
+
+        let mut visited = world
+            .iter_chunks()
+            .map(|(local, kind)| {
+                assert!(kind.is_all((local.x as u16).into()));
+                local
+            })
+            .collect::<Vec<_>>();
+
+        visited.sort_by_key(|v| (v.x, v.y, v.z));
+        let mut expected = locals.clone();
+        expected.sort_by_key(|v| (v.x, v.y, v.z));
+
+        assert_eq!(visited, expected);
+    }
+
+    #[test]
+    fn chunks_in_radius() {
+        let world = VoxWorld::default();
+
+        for local in query::range_inclusive((-3, -3, -3).into(), (3, 3, 3).into()) {
+            world.add(local, ChunkKind::default());
+        }
+
+        let center = IVec3::ZERO;
+        let in_radius = world.chunks_in_radius(center, 0).collect::<Vec<_>>();
+        assert_eq!(in_radius, vec![center]);
+
+        let in_radius = world.chunks_in_radius(center, 1).collect::<Vec<_>>();
+        assert_eq!(in_radius.len(), 1 + voxel::SIDE_COUNT);
+        for side in voxel::SIDES {
+            assert!(in_radius.contains(&side.dir()));
+        }
+
+        // Chunks outside the world should never be returned
+        let in_radius = world
+            .chunks_in_radius((3, 3, 3).into(), 1)
+            .collect::<Vec<_>>();
+        assert_eq!(in_radius.len(), 4);
+
+        for local in world.chunks_in_radius(center, 2) {
+            assert!(local.as_vec3().length() <= 2.0);
+        }
+    }
+
+    #[test]
+    fn get_mut() {
+        let world = VoxWorld::default();
+        assert!(world.get_mut(IVec3::ONE).is_none());
+
+        world.add(IVec3::ONE, ChunkKind::default());
+        world
+            .get_mut(IVec3::ONE)
+            .unwrap()
+            .set(IVec3::ZERO, 3.into());
+
+        assert_eq!(world.get(IVec3::ONE).unwrap().get(IVec3::ZERO), 3.into());
+    }
+
+    #[test]
+    fn biome() {
+        let world = VoxWorld::default();
+        let voxel = IVec3::new(-20, 3, 40);
+        let (local, _) = chunk::split_voxel(voxel);
+
+        // Without biomes, they come from noise
+        let expected = biome::at(IVec2::new(voxel.x, voxel.z));
+        assert_eq!(world.biome(voxel), expected);
+
+        // Stored biomes are used instead, even when they don't match the noise
+        let (_, relative) = chunk::split_voxel(voxel);
+        let stored = ChunkBiomes::generate(IVec3::new(500, 0, -500));
+        let biome = stored.get(relative.x, relative.z);
+
+        world.add(local, ChunkKind::default());
+        world.set_biomes(local, stored);
+        assert_eq!(world.biome(voxel), biome);
+
+        // Biomes are gone with their chunk
+        world.remove(local);
+        assert!(world.biomes(local).is_none());
+    }
+
+    #[test]
+    fn get_voxel() {
+        let world = VoxWorld::default();
+        assert_eq!(world.get_voxel(IVec3::ZERO), None);
+
+        world.add((-1, 0, 0).into(), ChunkKind::default());
+        world
+            .get_mut((-1, 0, 0).into())
+            .unwrap()
+            .set((15, 2, 3).into(), 3.into());
+
+        assert_eq!(world.get_voxel((-1, 2, 3).into()), Some(3.into()));
+        assert_eq!(world.get_voxel((-2, 2, 3).into()), Some(0.into()));
+    }
+
+    #[test]
+    fn shared_handle() {
+        let world = VoxWorld::default();
+        let handle = world.clone();
+
+        handle.add(IVec3::ONE, ChunkKind::default());
+        assert!(world.exists(IVec3::ONE));
+
+        world.remove(IVec3::ONE);
+        assert!(!handle.exists(IVec3::ONE));
+    }
+
+    #[test]
+    fn parallel_access() {
+        const THREAD_COUNT: i32 = 8;
+        const CHUNKS_PER_THREAD: i32 = 50;
+
+        let world = VoxWorld::default();
+
+        let workers = (0..THREAD_COUNT)
+            .map(|t| {
+                let world = world.clone();
+                std::thread::spawn(move || {
+                    for i in 0..CHUNKS_PER_THREAD {
+                        let local = (t, i, -i).into();
+                        let mut kind = ChunkKind::default();
+                        kind.set_all((t as u16).into());
+                        world.add(local, kind);
+
+                        // Read back other chunks while others threads are writing
+                        assert!(world.get(local).unwrap().is_all((t as u16).into()));
+                        world.update_neighborhood(local);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        assert_eq!(world.len(), (THREAD_COUNT * CHUNKS_PER_THREAD) as usize);
+
+        for (local, kind) in world.iter_chunks() {
+            assert!(kind.is_all((local.x as u16).into()));
+        }
+    }
+
     #[test]
     fn update_neighborhood() {
-        let mut world = VoxWorld::default();
+        let world = VoxWorld::default();
 
         let center = (1, 1, 1).into();
         let mut kind = ChunkKind::default();
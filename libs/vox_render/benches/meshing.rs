@@ -0,0 +1,99 @@
+use bevy::{prelude::IVec3, tasks::TaskPool};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use vox::{
+    chunk::{self, ChunkKind},
+    voxel::KindRegistry,
+    world::VoxWorld,
+};
+use vox_render::entities::{self, MeshJob, MeshUpdate};
+
+/// Chunks meshed on each iteration, same as the default upload budget.
+const BATCH_SIZE: i32 = 16;
+
+/// Rolling terrain, so chunks have both hidden and visible faces.
+fn terrain() -> ChunkKind {
+    let mut kind = ChunkKind::default();
+
+    for voxel in chunk::voxels() {
+        let height = 8 + (voxel.x / 3 + voxel.z / 5) % 12;
+
+        if voxel.y < height {
+            kind.set(voxel, 1.into());
+        }
+    }
+
+    kind
+}
+
+fn world() -> (VoxWorld, Vec<IVec3>) {
+    let world = VoxWorld::default();
+    let locals = (0..BATCH_SIZE)
+        .map(|x| IVec3::new(x, 0, 0))
+        .collect::<Vec<_>>();
+
+    for &local in &locals {
+        world.add(local, terrain());
+    }
+
+    (world, locals)
+}
+
+fn jobs(locals: &[IVec3]) -> Vec<MeshJob> {
+    locals
+        .iter()
+        .map(|&local| MeshJob {
+            local,
+            update: MeshUpdate::Full,
+            faces: vec![],
+            occlusion: None,
+        })
+        .collect()
+}
+
+/**
+  Compares meshing a batch of chunks one after another, in parallel with rayon, and with a task per chunk
+  on a bevy task pool.
+*/
+fn mesh_batch(c: &mut Criterion) {
+    let (world, locals) = world();
+    let registry = KindRegistry::default();
+    let pool = TaskPool::new();
+
+    let mut group = c.benchmark_group("mesh_batch");
+
+    group.bench_function("sequential", |b| {
+        b.iter_batched(
+            || jobs(&locals),
+            |jobs| entities::mesh_batch(jobs, &world, &registry, false),
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.bench_function("rayon", |b| {
+        b.iter_batched(
+            || jobs(&locals),
+            |jobs| entities::mesh_batch(jobs, &world, &registry, true),
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.bench_function("task_pool", |b| {
+        b.iter_batched(
+            || jobs(&locals),
+            |jobs| {
+                pool.scope(|scope| {
+                    for job in jobs {
+                        let (world, registry) = (&world, &registry);
+                        scope.spawn(async move { job.run(world, registry) });
+                    }
+                })
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, mesh_batch);
+criterion_main!(benches);
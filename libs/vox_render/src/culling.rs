@@ -0,0 +1,1087 @@
+use std::num::NonZeroU32;
+
+use bevy::{
+    core::FloatOrd,
+    core_pipeline::{draw_3d_graph, Opaque3d},
+    ecs::system::{
+        lifetimeless::{Read, SQuery, SRes},
+        SystemParamItem,
+    },
+    pbr::{
+        MeshPipeline, MeshPipelineKey, MeshUniform, SetMaterialBindGroup, SetMeshBindGroup,
+        SetMeshViewBindGroup, SpecializedMaterial,
+    },
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        camera::Camera3d,
+        mesh::{GpuBufferInfo, MeshVertexBufferLayout},
+        render_asset::RenderAssets,
+        render_component::{ExtractComponent, ExtractComponentPlugin},
+        render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext, SlotInfo, SlotType},
+        render_phase::{
+            sort_phase_system, AddRenderCommand, CachedRenderPipelinePhaseItem, DrawFunctionId,
+            DrawFunctions, EntityPhaseItem, EntityRenderCommand, PhaseItem, RenderCommandResult,
+            RenderPhase, SetItemPipeline, TrackedRenderPass,
+        },
+        render_resource::{
+            std140::{AsStd140, Std140},
+            BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+            BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, Buffer,
+            BufferBindingType, BufferDescriptor, BufferInitDescriptor, BufferSize, BufferUsages,
+            CachedComputePipelineId, CachedRenderPipelineId, ComputePassDescriptor,
+            ComputePipelineDescriptor, Extent3d, LoadOp, Operations, PipelineCache,
+            RenderPassDepthStencilAttachment, RenderPassDescriptor, RenderPipelineDescriptor,
+            ShaderStages, SpecializedMeshPipeline, SpecializedMeshPipelineError,
+            SpecializedMeshPipelines, StorageTextureAccess, TextureAspect, TextureDescriptor,
+            TextureDimension, TextureFormat, TextureSampleType, TextureUsages, TextureView,
+            TextureViewDescriptor, TextureViewDimension,
+        },
+        renderer::{RenderContext, RenderDevice, RenderQueue},
+        texture::{CachedTexture, TextureCache},
+        view::ExtractedView,
+        RenderApp, RenderStage,
+    },
+    utils::{HashMap, HashSet},
+};
+use bytemuck::{Pod, Zeroable};
+use vox::bounds;
+
+use crate::{
+    entities::ChunkEntity,
+    material::{ChunkAoMaterial, ChunkPbrMaterial},
+    mesher,
+};
+
+pub const CHUNK_DEPTH_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 9_614_027_385_160_492_731);
+pub const CHUNK_CULL_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 3_270_851_946_203_117_584);
+pub const HIZ_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 14_852_316_097_442_608_259);
+
+pub mod node {
+    /// Label of the node culling chunks, which runs before the main pass of the 3d graph.
+    pub const CHUNK_CULLING: &str = "chunk_culling";
+}
+
+/// Must match the workgroup size of the chunk entry points on chunk_cull.wgsl.
+const CHUNK_WORKGROUP_SIZE: u32 = 64;
+/// Must match the workgroup size of the entry points on hiz.wgsl.
+const HIZ_WORKGROUP_SIZE: u32 = 8;
+
+/// Size, in bytes, of the arguments of `draw_indexed_indirect`.
+const DRAW_ARGS_SIZE: u64 = 5 * std::mem::size_of::<u32>() as u64;
+
+const DEPTH_FORMAT: TextureFormat = TextureFormat::Depth32Float;
+const PYRAMID_FORMAT: TextureFormat = TextureFormat::R32Float;
+
+/**
+  Culls chunks on the GPU and draws them indirectly, so chunks hidden behind others aren't drawn. Each frame
+  of the 3d camera:
+
+  1. Chunks visible on the last frame are drawn on a depth prepass.
+  2. The prepass depth is reduced to a depth pyramid, where each level holds the farthest depth of the level
+     below.
+  3. Each chunk is tested against the view and the pyramid, which fills its `draw_indexed_indirect` arguments
+     with either one instance or none.
+
+  Chunks are then drawn by the main pass with those arguments. Chunks outside of the view are still left out
+  by frustum culling on the CPU, before they reach the GPU.
+*/
+pub struct GpuCullingPlugin;
+
+impl Plugin for GpuCullingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(ExtractComponentPlugin::<ChunkEntity>::default());
+
+        let render_app = app.sub_app_mut(RenderApp);
+        render_app
+            .init_resource::<DrawFunctions<ChunkDepth>>()
+            .add_render_command::<ChunkDepth, DrawChunkDepth>()
+            .add_render_command::<Opaque3d, DrawCulledChunk<ChunkPbrMaterial>>()
+            .add_render_command::<Opaque3d, DrawCulledChunk<ChunkAoMaterial>>()
+            .init_resource::<ChunkCulling>()
+            .init_resource::<ChunkCullingPipeline>()
+            .init_resource::<SpecializedMeshPipelines<ChunkCullingPipeline>>()
+            .add_system_to_stage(RenderStage::Extract, extract_chunk_depth_phases)
+            .add_system_to_stage(RenderStage::Prepare, prepare_chunks)
+            .add_system_to_stage(
+                RenderStage::Prepare,
+                prepare_view_culling.after(prepare_chunks),
+            )
+            .add_system_to_stage(RenderStage::Queue, queue_chunk_depth)
+            .add_system_to_stage(RenderStage::PhaseSort, sort_phase_system::<ChunkDepth>)
+            .add_system_to_stage(RenderStage::PhaseSort, use_culled_draws::<ChunkPbrMaterial>)
+            .add_system_to_stage(RenderStage::PhaseSort, use_culled_draws::<ChunkAoMaterial>);
+
+        let culling_node = ChunkCullingNode::new(&mut render_app.world);
+        let mut graph = render_app.world.resource_mut::<RenderGraph>();
+        let draw_3d_graph = graph.get_sub_graph_mut(draw_3d_graph::NAME).unwrap();
+        draw_3d_graph.add_node(node::CHUNK_CULLING, culling_node);
+        draw_3d_graph
+            .add_node_edge(node::CHUNK_CULLING, draw_3d_graph::node::MAIN_PASS)
+            .unwrap();
+        draw_3d_graph
+            .add_slot_edge(
+                draw_3d_graph.input_node().unwrap().id,
+                draw_3d_graph::input::VIEW_ENTITY,
+                node::CHUNK_CULLING,
+                ChunkCullingNode::IN_VIEW,
+            )
+            .unwrap();
+    }
+}
+
+impl ExtractComponent for ChunkEntity {
+    type Query = &'static ChunkEntity;
+    type Filter = ();
+
+    fn extract_component(item: bevy::ecs::query::QueryItem<Self::Query>) -> Self {
+        *item
+    }
+}
+
+/**
+  Bounds and index count of a chunk, uploaded as a storage buffer. Bounds are in world space.
+  Must match `Chunk` on chunk_cull.wgsl.
+*/
+#[derive(Debug, Default, Clone, Copy, PartialEq, Pod, Zeroable)]
+#[repr(C)]
+pub struct GpuChunk {
+    pub min: Vec3,
+    /// Slots of chunks which aren't drawn anymore have no indices.
+    pub index_count: u32,
+    pub max: Vec3,
+    _padding: u32,
+}
+
+impl GpuChunk {
+    pub fn new(transform: Mat4, index_count: u32) -> Self {
+        let bounds = bounds::chunk(IVec3::ZERO);
+
+        Self {
+            min: transform.transform_point3(bounds.min),
+            index_count,
+            max: transform.transform_point3(bounds.max),
+            _padding: 0,
+        }
+    }
+}
+
+/// Must match `ChunkCullUniform` on chunk_cull.wgsl.
+#[derive(Debug, Clone, Copy, Default, PartialEq, AsStd140)]
+struct ChunkCullUniform {
+    view_proj: Mat4,
+    /// Width and height of the depth pyramid, its levels and how many chunks there are.
+    size: UVec4,
+}
+
+/**
+  Slot of each drawn chunk on the culling buffers. Chunks keep their slot while they're drawn, so the
+  visibility the GPU found on the last frame is still there for them on the next one.
+*/
+#[derive(Debug, Default)]
+pub struct CullSlots {
+    slots: HashMap<IVec3, u32>,
+    free: Vec<u32>,
+    len: u32,
+}
+
+impl CullSlots {
+    /// Frees the slots of chunks which aren't on the given set.
+    pub fn retain(&mut self, chunks: &HashSet<IVec3>) {
+        let free = &mut self.free;
+
+        self.slots.retain(|local, slot| {
+            let keep = chunks.contains(local);
+            if !keep {
+                free.push(*slot);
+            }
+            keep
+        });
+    }
+
+    /// Slot of the given chunk, reusing a freed one for new chunks.
+    pub fn get_or_insert(&mut self, local: IVec3) -> u32 {
+        if let Some(slot) = self.slots.get(&local) {
+            return *slot;
+        }
+
+        let slot = self.free.pop().unwrap_or_else(|| {
+            self.len += 1;
+            self.len - 1
+        });
+
+        self.slots.insert(local, slot);
+        slot
+    }
+
+    /// How many slots there are, including freed ones.
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// How many levels the depth pyramid of the given size has, down to a single texel.
+pub fn pyramid_levels(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+/// Number of workgroups needed to cover the given number of items.
+fn workgroups(items: u32, size: u32) -> u32 {
+    items.div_ceil(size)
+}
+
+/// Slot of a chunk on the culling buffers, only on chunks drawn indirectly.
+#[derive(Component, Debug, Clone, Copy)]
+struct ChunkSlot(u32);
+
+struct CullBuffers {
+    chunks: Buffer,
+    /// Written by the GPU and read by `draw_indexed_indirect`.
+    args: Buffer,
+    /// Whether each chunk was visible on the last frame.
+    visibility: Buffer,
+    capacity: usize,
+}
+
+impl CullBuffers {
+    fn new(render_device: &RenderDevice, capacity: usize) -> Self {
+        let buffer = |label, size, usage| {
+            render_device.create_buffer(&BufferDescriptor {
+                label: Some(label),
+                size,
+                usage,
+                mapped_at_creation: false,
+            })
+        };
+
+        Self {
+            chunks: buffer(
+                "chunk_cull_chunks_buffer",
+                (capacity * std::mem::size_of::<GpuChunk>()) as u64,
+                BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            ),
+            args: buffer(
+                "chunk_cull_args_buffer",
+                capacity as u64 * DRAW_ARGS_SIZE,
+                BufferUsages::STORAGE | BufferUsages::INDIRECT,
+            ),
+            visibility: buffer(
+                "chunk_cull_visibility_buffer",
+                (capacity * std::mem::size_of::<u32>()) as u64,
+                BufferUsages::STORAGE,
+            ),
+            capacity,
+        }
+    }
+}
+
+/// Chunks drawn indirectly on the render world, and the buffers the GPU culls them on.
+#[derive(Default)]
+struct ChunkCulling {
+    slots: CullSlots,
+    buffers: Option<CullBuffers>,
+}
+
+/// Culling resources of a view, which is only there once every culling pipeline is ready.
+#[derive(Component)]
+struct ViewCulling {
+    depth: CachedTexture,
+    size: UVec2,
+    chunk_count: u32,
+    chunk_bind_group: BindGroup,
+    pyramid_bind_group: BindGroup,
+    /// One per pyramid level, the first one copying the prepass depth into it.
+    hiz_bind_groups: Vec<BindGroup>,
+}
+
+/// Chunk drawn on the depth prepass, closest first.
+pub struct ChunkDepth {
+    distance: f32,
+    pipeline: CachedRenderPipelineId,
+    entity: Entity,
+    draw_function: DrawFunctionId,
+}
+
+impl PhaseItem for ChunkDepth {
+    type SortKey = FloatOrd;
+
+    fn sort_key(&self) -> Self::SortKey {
+        FloatOrd(self.distance)
+    }
+
+    fn draw_function(&self) -> DrawFunctionId {
+        self.draw_function
+    }
+}
+
+impl EntityPhaseItem for ChunkDepth {
+    fn entity(&self) -> Entity {
+        self.entity
+    }
+}
+
+impl CachedRenderPipelinePhaseItem for ChunkDepth {
+    fn cached_pipeline(&self) -> CachedRenderPipelineId {
+        self.pipeline
+    }
+}
+
+/**
+  Depth prepass pipeline of chunks, along the compute pipelines which build the depth pyramid and cull chunks.
+*/
+struct ChunkCullingPipeline {
+    mesh_pipeline: MeshPipeline,
+    chunk_layout: BindGroupLayout,
+    pyramid_layout: BindGroupLayout,
+    copy_layout: BindGroupLayout,
+    downsample_layout: BindGroupLayout,
+    occluders: CachedComputePipelineId,
+    copy_depth: CachedComputePipelineId,
+    downsample: CachedComputePipelineId,
+    cull: CachedComputePipelineId,
+}
+
+impl FromWorld for ChunkCullingPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let mesh_pipeline = world
+            .get_resource::<MeshPipeline>()
+            .expect("PbrPlugin must be added before VoxRenderPlugin")
+            .clone();
+
+        let render_device = world.get_resource::<RenderDevice>().unwrap();
+
+        let storage = |binding, read_only| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let texture = |binding, sample_type| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Texture {
+                multisampled: false,
+                sample_type,
+                view_dimension: TextureViewDimension::D2,
+            },
+            count: None,
+        };
+        let pyramid_output = BindGroupLayoutEntry {
+            binding: 2,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::StorageTexture {
+                access: StorageTextureAccess::WriteOnly,
+                format: PYRAMID_FORMAT,
+                view_dimension: TextureViewDimension::D2,
+            },
+            count: None,
+        };
+        let pyramid_input = TextureSampleType::Float { filterable: false };
+
+        let chunk_layout = render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(
+                            ChunkCullUniform::std140_size_static() as u64
+                        ),
+                    },
+                    count: None,
+                },
+                storage(1, true),
+                storage(2, false),
+                storage(3, false),
+            ],
+            label: Some("chunk_cull_chunk_layout"),
+        });
+        let pyramid_layout = render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[texture(0, pyramid_input)],
+            label: Some("chunk_cull_pyramid_layout"),
+        });
+        let copy_layout = render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[texture(0, TextureSampleType::Depth), pyramid_output],
+            label: Some("hiz_copy_layout"),
+        });
+        let downsample_layout =
+            render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                entries: &[texture(1, pyramid_input), pyramid_output],
+                label: Some("hiz_downsample_layout"),
+            });
+
+        let mut pipeline_cache = world.resource_mut::<PipelineCache>();
+        let mut compute = |label: &'static str,
+                           layout: Vec<BindGroupLayout>,
+                           shader: HandleUntyped,
+                           entry_point: &'static str| {
+            pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+                label: Some(label.into()),
+                layout: Some(layout),
+                shader: shader.typed(),
+                shader_defs: vec![],
+                entry_point: entry_point.into(),
+            })
+        };
+
+        let occluders = compute(
+            "chunk_cull_occluders_pipeline",
+            vec![chunk_layout.clone()],
+            CHUNK_CULL_SHADER_HANDLE,
+            "occluders",
+        );
+        let cull = compute(
+            "chunk_cull_pipeline",
+            vec![chunk_layout.clone(), pyramid_layout.clone()],
+            CHUNK_CULL_SHADER_HANDLE,
+            "cull",
+        );
+        let copy_depth = compute(
+            "hiz_copy_depth_pipeline",
+            vec![copy_layout.clone()],
+            HIZ_SHADER_HANDLE,
+            "copy_depth",
+        );
+        let downsample = compute(
+            "hiz_downsample_pipeline",
+            vec![downsample_layout.clone()],
+            HIZ_SHADER_HANDLE,
+            "downsample",
+        );
+
+        Self {
+            mesh_pipeline,
+            chunk_layout,
+            pyramid_layout,
+            copy_layout,
+            downsample_layout,
+            occluders,
+            copy_depth,
+            downsample,
+            cull,
+        }
+    }
+}
+
+impl ChunkCullingPipeline {
+    fn is_ready(&self, pipeline_cache: &PipelineCache) -> bool {
+        [self.occluders, self.copy_depth, self.downsample, self.cull]
+            .into_iter()
+            .all(|id| pipeline_cache.get_compute_pipeline(id).is_some())
+    }
+}
+
+impl SpecializedMeshPipeline for ChunkCullingPipeline {
+    type Key = MeshPipelineKey;
+
+    fn specialize(
+        &self,
+        key: Self::Key,
+        layout: &MeshVertexBufferLayout,
+    ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
+        let mut descriptor = self.mesh_pipeline.specialize(key, layout)?;
+
+        descriptor.label = Some("chunk_depth_pipeline".into());
+        descriptor.vertex.shader = CHUNK_DEPTH_SHADER_HANDLE.typed();
+        descriptor.vertex.buffers = vec![layout.get_layout(&[
+            Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
+            mesher::ATTRIBUTE_SWAY.at_shader_location(1),
+        ])?];
+
+        // Only depth is written, on a texture of its own
+        if let Some(fragment) = descriptor.fragment.as_mut() {
+            fragment.shader = CHUNK_DEPTH_SHADER_HANDLE.typed();
+            fragment.targets.clear();
+        }
+
+        Ok(descriptor)
+    }
+}
+
+/// Every 3d view gets a depth prepass, which is only drawn once [`ViewCulling`] is there.
+fn extract_chunk_depth_phases(mut commands: Commands, cameras: Query<Entity, With<Camera3d>>) {
+    for entity in cameras.iter() {
+        commands
+            .get_or_spawn(entity)
+            .insert(RenderPhase::<ChunkDepth>::default());
+    }
+}
+
+/**
+  Uploads the bounds of visible chunks, giving each one a slot on the culling buffers. Buffers only grow, so
+  chunks keep their visibility across frames.
+*/
+fn prepare_chunks(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    meshes: Res<RenderAssets<Mesh>>,
+    mut culling: ResMut<ChunkCulling>,
+    chunks: Query<(Entity, &ChunkEntity, &MeshUniform, &Handle<Mesh>)>,
+) {
+    let culling = &mut *culling;
+
+    let drawn = chunks
+        .iter()
+        .map(|(_, chunk, _, _)| chunk.0)
+        .collect::<HashSet<_>>();
+    culling.slots.retain(&drawn);
+
+    let mut gpu_chunks = vec![];
+
+    for (entity, chunk, mesh_uniform, mesh_handle) in chunks.iter() {
+        let index_count = match meshes.get(mesh_handle).map(|mesh| &mesh.buffer_info) {
+            Some(GpuBufferInfo::Indexed { count, .. }) => *count,
+            _ => continue,
+        };
+
+        let slot = culling.slots.get_or_insert(chunk.0);
+        commands.entity(entity).insert(ChunkSlot(slot));
+
+        let slot = slot as usize;
+        if gpu_chunks.len() <= slot {
+            gpu_chunks.resize(slot + 1, GpuChunk::default());
+        }
+        gpu_chunks[slot] = GpuChunk::new(mesh_uniform.transform, index_count);
+    }
+
+    // Freed slots at the end have no indices too
+    gpu_chunks.resize(culling.slots.len(), GpuChunk::default());
+
+    if gpu_chunks.is_empty() {
+        return;
+    }
+
+    let capacity = culling
+        .buffers
+        .as_ref()
+        .map_or(0, |buffers| buffers.capacity);
+    if gpu_chunks.len() > capacity {
+        culling.buffers = Some(CullBuffers::new(
+            &render_device,
+            gpu_chunks.len().next_power_of_two(),
+        ));
+    }
+
+    if let Some(buffers) = &culling.buffers {
+        render_queue.write_buffer(&buffers.chunks, 0, bytemuck::cast_slice(&gpu_chunks));
+    }
+}
+
+/// View of a single level of the depth pyramid.
+fn pyramid_level(pyramid: &CachedTexture, level: u32) -> TextureView {
+    pyramid.texture.create_view(&TextureViewDescriptor {
+        label: Some("hiz_level_view"),
+        base_mip_level: level,
+        mip_level_count: NonZeroU32::new(1),
+        ..Default::default()
+    })
+}
+
+/**
+  Creates the depth prepass texture, the depth pyramid and the bind groups culling chunks on each 3d view.
+  Nothing is culled until the compute pipelines are ready, so chunks are drawn as usual meanwhile.
+*/
+#[allow(clippy::too_many_arguments)]
+fn prepare_view_culling(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    pipeline: Res<ChunkCullingPipeline>,
+    pipeline_cache: Res<PipelineCache>,
+    culling: Res<ChunkCulling>,
+    mut texture_cache: ResMut<TextureCache>,
+    views: Query<(Entity, &ExtractedView), With<RenderPhase<ChunkDepth>>>,
+) {
+    let buffers = match &culling.buffers {
+        Some(buffers) if pipeline.is_ready(&pipeline_cache) => buffers,
+        _ => return,
+    };
+
+    let chunk_count = culling.slots.len() as u32;
+
+    for (entity, view) in views.iter() {
+        let size = Extent3d {
+            width: view.width.max(1),
+            height: view.height.max(1),
+            depth_or_array_layers: 1,
+        };
+        let levels = pyramid_levels(size.width, size.height);
+
+        let depth = texture_cache.get(
+            &render_device,
+            TextureDescriptor {
+                label: Some("chunk_depth_texture"),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: DEPTH_FORMAT,
+                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            },
+        );
+        let pyramid = texture_cache.get(
+            &render_device,
+            TextureDescriptor {
+                label: Some("hiz_pyramid_texture"),
+                size,
+                mip_level_count: levels,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: PYRAMID_FORMAT,
+                usage: TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING,
+            },
+        );
+
+        let uniform = ChunkCullUniform {
+            view_proj: view.projection * view.transform.compute_matrix().inverse(),
+            size: UVec4::new(size.width, size.height, levels, chunk_count),
+        };
+        let uniform_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("chunk_cull_uniform_buffer"),
+            contents: uniform.as_std140().as_bytes(),
+            usage: BufferUsages::UNIFORM,
+        });
+
+        let chunk_bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: buffers.chunks.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: buffers.args.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: buffers.visibility.as_entire_binding(),
+                },
+            ],
+            label: Some("chunk_cull_chunk_bind_group"),
+            layout: &pipeline.chunk_layout,
+        });
+
+        let pyramid_bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(&pyramid.default_view),
+            }],
+            label: Some("chunk_cull_pyramid_bind_group"),
+            layout: &pipeline.pyramid_layout,
+        });
+
+        let depth_view = depth.texture.create_view(&TextureViewDescriptor {
+            label: Some("chunk_depth_view"),
+            aspect: TextureAspect::DepthOnly,
+            ..Default::default()
+        });
+
+        let hiz_bind_groups = (0..levels)
+            .map(|level| {
+                let output = pyramid_level(&pyramid, level);
+
+                if level == 0 {
+                    render_device.create_bind_group(&BindGroupDescriptor {
+                        entries: &[
+                            BindGroupEntry {
+                                binding: 0,
+                                resource: BindingResource::TextureView(&depth_view),
+                            },
+                            BindGroupEntry {
+                                binding: 2,
+                                resource: BindingResource::TextureView(&output),
+                            },
+                        ],
+                        label: Some("hiz_copy_bind_group"),
+                        layout: &pipeline.copy_layout,
+                    })
+                } else {
+                    let input = pyramid_level(&pyramid, level - 1);
+
+                    render_device.create_bind_group(&BindGroupDescriptor {
+                        entries: &[
+                            BindGroupEntry {
+                                binding: 1,
+                                resource: BindingResource::TextureView(&input),
+                            },
+                            BindGroupEntry {
+                                binding: 2,
+                                resource: BindingResource::TextureView(&output),
+                            },
+                        ],
+                        label: Some("hiz_downsample_bind_group"),
+                        layout: &pipeline.downsample_layout,
+                    })
+                }
+            })
+            .collect();
+
+        commands.entity(entity).insert(ViewCulling {
+            depth,
+            size: UVec2::new(size.width, size.height),
+            chunk_count,
+            chunk_bind_group,
+            pyramid_bind_group,
+            hiz_bind_groups,
+        });
+    }
+}
+
+/// Queues the depth prepass of chunks drawn indirectly.
+#[allow(clippy::too_many_arguments)]
+fn queue_chunk_depth(
+    draw_functions: Res<DrawFunctions<ChunkDepth>>,
+    culling_pipeline: Res<ChunkCullingPipeline>,
+    meshes: Res<RenderAssets<Mesh>>,
+    mut pipelines: ResMut<SpecializedMeshPipelines<ChunkCullingPipeline>>,
+    mut pipeline_cache: ResMut<PipelineCache>,
+    chunks: Query<(Entity, &MeshUniform, &Handle<Mesh>), With<ChunkSlot>>,
+    mut views: Query<(&ExtractedView, &mut RenderPhase<ChunkDepth>), With<ViewCulling>>,
+) {
+    let draw_chunk_depth = draw_functions.read().get_id::<DrawChunkDepth>().unwrap();
+
+    for (view, mut depth_phase) in views.iter_mut() {
+        let inverse_view_row_2 = view.transform.compute_matrix().inverse().row(2);
+
+        for (entity, mesh_uniform, mesh_handle) in chunks.iter() {
+            let mesh = match meshes.get(mesh_handle) {
+                Some(mesh) => mesh,
+                None => continue,
+            };
+
+            let key = MeshPipelineKey::from_msaa_samples(1)
+                | MeshPipelineKey::from_primitive_topology(mesh.primitive_topology);
+
+            let pipeline = match pipelines.specialize(
+                &mut pipeline_cache,
+                &culling_pipeline,
+                key,
+                &mesh.layout,
+            ) {
+                Ok(pipeline) => pipeline,
+                Err(err) => {
+                    error!("Failed to specialize chunk depth pipeline: {}", err);
+                    continue;
+                }
+            };
+
+            depth_phase.add(ChunkDepth {
+                entity,
+                pipeline,
+                draw_function: draw_chunk_depth,
+                distance: -inverse_view_row_2.dot(mesh_uniform.transform.col(3)),
+            });
+        }
+    }
+}
+
+/**
+  Chunks of the given material on views being culled are drawn with the arguments filled by the GPU, instead
+  of the usual [`bevy::pbr::DrawMesh`].
+*/
+fn use_culled_draws<M: SpecializedMaterial>(
+    draw_functions: Res<DrawFunctions<Opaque3d>>,
+    chunks: Query<(), (With<ChunkSlot>, With<Handle<M>>)>,
+    mut views: Query<&mut RenderPhase<Opaque3d>, With<ViewCulling>>,
+) {
+    let draw_culled_chunk = draw_functions
+        .read()
+        .get_id::<DrawCulledChunk<M>>()
+        .unwrap();
+
+    for mut opaque_phase in views.iter_mut() {
+        for item in opaque_phase.items.iter_mut() {
+            if chunks.get(item.entity).is_ok() {
+                item.draw_function = draw_culled_chunk;
+            }
+        }
+    }
+}
+
+type DrawChunkDepth = (
+    SetItemPipeline,
+    SetMeshViewBindGroup<0>,
+    SetMeshBindGroup<1>,
+    DrawChunkIndirect,
+);
+
+type DrawCulledChunk<M> = (
+    SetItemPipeline,
+    SetMeshViewBindGroup<0>,
+    SetMaterialBindGroup<M, 1>,
+    SetMeshBindGroup<2>,
+    DrawChunkIndirect,
+);
+
+/// Draws the chunk mesh with the arguments on its slot.
+struct DrawChunkIndirect;
+
+impl EntityRenderCommand for DrawChunkIndirect {
+    type Param = (
+        SRes<RenderAssets<Mesh>>,
+        SRes<ChunkCulling>,
+        SQuery<Read<Handle<Mesh>>>,
+        SQuery<Read<ChunkSlot>>,
+    );
+
+    fn render<'w>(
+        _view: Entity,
+        item: Entity,
+        (meshes, culling, mesh_query, slot_query): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let (mesh_handle, slot) = match (mesh_query.get(item), slot_query.get(item)) {
+            (Ok(mesh_handle), Ok(slot)) => (mesh_handle, slot),
+            _ => return RenderCommandResult::Failure,
+        };
+
+        let buffers = match &culling.into_inner().buffers {
+            Some(buffers) => buffers,
+            None => return RenderCommandResult::Failure,
+        };
+
+        let gpu_mesh = match meshes.into_inner().get(mesh_handle) {
+            Some(gpu_mesh) => gpu_mesh,
+            None => return RenderCommandResult::Failure,
+        };
+
+        match &gpu_mesh.buffer_info {
+            GpuBufferInfo::Indexed {
+                buffer,
+                index_format,
+                ..
+            } => {
+                pass.set_vertex_buffer(0, gpu_mesh.vertex_buffer.slice(..));
+                pass.set_index_buffer(buffer.slice(..), 0, *index_format);
+                pass.draw_indexed_indirect(&buffers.args, slot.0 as u64 * DRAW_ARGS_SIZE);
+
+                RenderCommandResult::Success
+            }
+            GpuBufferInfo::NonIndexed { .. } => RenderCommandResult::Failure,
+        }
+    }
+}
+
+/**
+  Draws the depth prepass, builds the depth pyramid and culls chunks, before the main pass draws them.
+*/
+pub struct ChunkCullingNode {
+    query: QueryState<(Read<ViewCulling>, Read<RenderPhase<ChunkDepth>>)>,
+}
+
+impl ChunkCullingNode {
+    pub const IN_VIEW: &'static str = "view";
+
+    pub fn new(world: &mut World) -> Self {
+        Self {
+            query: QueryState::new(world),
+        }
+    }
+}
+
+impl Node for ChunkCullingNode {
+    fn input(&self) -> Vec<SlotInfo> {
+        vec![SlotInfo::new(ChunkCullingNode::IN_VIEW, SlotType::Entity)]
+    }
+
+    fn update(&mut self, world: &mut World) {
+        self.query.update_archetypes(world);
+    }
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let view_entity = graph.get_input_entity(Self::IN_VIEW)?;
+        let (view_culling, depth_phase) = match self.query.get_manual(world, view_entity) {
+            Ok(query) => query,
+            Err(_) => return Ok(()),
+        };
+
+        let culling_pipeline = world.resource::<ChunkCullingPipeline>();
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let pipeline = |id| pipeline_cache.get_compute_pipeline(id);
+
+        let (occluders, copy_depth, downsample, cull) = match (
+            pipeline(culling_pipeline.occluders),
+            pipeline(culling_pipeline.copy_depth),
+            pipeline(culling_pipeline.downsample),
+            pipeline(culling_pipeline.cull),
+        ) {
+            (Some(occluders), Some(copy_depth), Some(downsample), Some(cull)) => {
+                (occluders, copy_depth, downsample, cull)
+            }
+            _ => return Ok(()),
+        };
+
+        let chunk_groups = workgroups(view_culling.chunk_count, CHUNK_WORKGROUP_SIZE);
+
+        {
+            let mut pass =
+                render_context
+                    .command_encoder
+                    .begin_compute_pass(&ComputePassDescriptor {
+                        label: Some("chunk_occluders_pass"),
+                    });
+            pass.set_pipeline(occluders);
+            pass.set_bind_group(0, &view_culling.chunk_bind_group, &[]);
+            pass.dispatch(chunk_groups, 1, 1);
+        }
+
+        {
+            let render_pass =
+                render_context
+                    .command_encoder
+                    .begin_render_pass(&RenderPassDescriptor {
+                        label: Some("chunk_depth_pass"),
+                        color_attachments: &[],
+                        depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                            view: &view_culling.depth.default_view,
+                            // Depth is reversed, so nothing is cleared to the farthest depth
+                            depth_ops: Some(Operations {
+                                load: LoadOp::Clear(0.0),
+                                store: true,
+                            }),
+                            stencil_ops: None,
+                        }),
+                    });
+
+            let draw_functions = world.resource::<DrawFunctions<ChunkDepth>>();
+            let mut draw_functions = draw_functions.write();
+            let mut tracked_pass = TrackedRenderPass::new(render_pass);
+            for item in &depth_phase.items {
+                let draw_function = draw_functions.get_mut(item.draw_function).unwrap();
+                draw_function.draw(world, &mut tracked_pass, view_entity, item);
+            }
+        }
+
+        {
+            let mut pass =
+                render_context
+                    .command_encoder
+                    .begin_compute_pass(&ComputePassDescriptor {
+                        label: Some("hiz_pass"),
+                    });
+
+            for (level, bind_group) in view_culling.hiz_bind_groups.iter().enumerate() {
+                let width = (view_culling.size.x >> level).max(1);
+                let height = (view_culling.size.y >> level).max(1);
+
+                pass.set_pipeline(if level == 0 { copy_depth } else { downsample });
+                pass.set_bind_group(0, bind_group, &[]);
+                pass.dispatch(
+                    workgroups(width, HIZ_WORKGROUP_SIZE),
+                    workgroups(height, HIZ_WORKGROUP_SIZE),
+                    1,
+                );
+            }
+        }
+
+        {
+            let mut pass =
+                render_context
+                    .command_encoder
+                    .begin_compute_pass(&ComputePassDescriptor {
+                        label: Some("chunk_cull_pass"),
+                    });
+            pass.set_pipeline(cull);
+            pass.set_bind_group(0, &view_culling.chunk_bind_group, &[]);
+            pass.set_bind_group(1, &view_culling.pyramid_bind_group, &[]);
+            pass.dispatch(chunk_groups, 1, 1);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(source: &str) {
+        let module = naga::front::wgsl::parse_str(source).unwrap();
+
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::empty(),
+        )
+        .validate(&module)
+        .unwrap();
+    }
+
+    #[test]
+    fn shaders() {
+        validate(include_str!("shaders/chunk_cull.wgsl"));
+        validate(include_str!("shaders/hiz.wgsl"));
+    }
+
+    #[test]
+    fn gpu_chunk() {
+        // Must match the layout of Chunk on chunk_cull.wgsl
+        assert_eq!(std::mem::size_of::<GpuChunk>(), 32);
+
+        let chunk = GpuChunk::new(Mat4::from_translation(Vec3::new(1.0, 2.0, 3.0)), 6);
+        let bounds = bounds::chunk(IVec3::ZERO);
+
+        assert_eq!(chunk.min, bounds.min + Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(chunk.max, bounds.max + Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(chunk.index_count, 6);
+    }
+
+    #[test]
+    fn cull_slots() {
+        let mut slots = CullSlots::default();
+        assert!(slots.is_empty());
+
+        let a = IVec3::new(0, 0, 0);
+        let b = IVec3::new(1, 0, 0);
+        let c = IVec3::new(2, 0, 0);
+
+        assert_eq!(slots.get_or_insert(a), 0);
+        assert_eq!(slots.get_or_insert(b), 1);
+        assert_eq!(slots.get_or_insert(a), 0);
+
+        // Freed slots are reused by new chunks, while kept ones don't move
+        slots.retain(&[b].into_iter().collect());
+        assert_eq!(slots.get_or_insert(c), 0);
+        assert_eq!(slots.get_or_insert(b), 1);
+        assert_eq!(slots.get_or_insert(a), 2);
+        assert_eq!(slots.len(), 3);
+    }
+
+    #[test]
+    fn pyramid_levels() {
+        assert_eq!(super::pyramid_levels(1, 1), 1);
+        assert_eq!(super::pyramid_levels(2, 1), 2);
+        assert_eq!(super::pyramid_levels(1920, 1080), 11);
+        assert_eq!(super::pyramid_levels(1024, 1024), 11);
+        assert_eq!(super::pyramid_levels(0, 0), 1);
+    }
+
+    #[test]
+    fn workgroups() {
+        assert_eq!(super::workgroups(0, 64), 0);
+        assert_eq!(super::workgroups(1, 64), 1);
+        assert_eq!(super::workgroups(64, 64), 1);
+        assert_eq!(super::workgroups(65, 64), 2);
+    }
+}
@@ -0,0 +1,758 @@
+use bevy::{
+    pbr::NotShadowCaster,
+    prelude::*,
+    render::{primitives::Aabb, render_resource::PrimitiveTopology},
+    utils::{HashMap, HashSet},
+};
+use rayon::prelude::*;
+use std::collections::VecDeque;
+use vox::{
+    bounds,
+    pipeline::{ChunkUnloaded, ChunkUpdated, OriginShifted, VoxelsUpdated, WorldOrigin},
+    voxel::{KindRegistry, PropShape, VoxelFace, VoxelVertex},
+    world::VoxWorld,
+};
+
+use crate::{
+    material::{ChunkAoMaterial, ChunkPbrMaterial},
+    mesher,
+    occlusion::{self, ChunkFacesOcclusion},
+    props::{self, PropInstance, PropInstances, PropMeshes},
+    tint::{self, BiomeBlend},
+    RenderQuality, RenderSettings,
+};
+
+/// How deep, in voxels, newly spawned chunks starts before rising to their final position.
+const FADE_IN_DEPTH: f32 = 4.0;
+
+/// How many meshes of unloaded chunks are kept around to be reused by new chunks.
+const MESH_POOL_SIZE: usize = 32;
+
+#[derive(Component, Debug, Clone, Copy)]
+pub struct ChunkEntity(pub IVec3);
+
+#[derive(Component, Debug)]
+pub struct ChunkFadeIn {
+    target: Vec3,
+    elapsed: f32,
+}
+
+#[derive(Default)]
+pub struct ChunkEntityMap(pub HashMap<IVec3, Entity>);
+
+/// What must be re-meshed on a chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MeshUpdate {
+    Full,
+    /// Only what the changed voxels touch.
+    Dirty(DirtyRegion),
+}
+
+/**
+  Voxels changed on a chunk since it was last meshed, along the sub-regions, see [`mesher::REGION_SIZE`],
+  whose faces they may change. Voxels are in chunk coordinates, like on [`VoxelsUpdated`].
+*/
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DirtyRegion {
+    pub voxels: HashSet<IVec3>,
+    pub regions: HashSet<usize>,
+}
+
+impl DirtyRegion {
+    pub fn mark(&mut self, voxel: IVec3) {
+        if self.voxels.insert(voxel) {
+            self.regions.extend(mesher::dirty_regions(voxel));
+        }
+    }
+
+    pub fn merge(&mut self, other: DirtyRegion) {
+        self.voxels.extend(other.voxels);
+        self.regions.extend(other.regions);
+    }
+}
+
+/**
+  Chunks waiting to be meshed, in the order they were updated. Only a few meshes are uploaded each frame, as
+  set by [`RenderSettings`], so bursts of updates, like after mass edits, don't stutter.
+*/
+#[derive(Default)]
+pub struct MeshQueue {
+    queue: VecDeque<IVec3>,
+    queued: HashMap<IVec3, MeshUpdate>,
+}
+
+impl MeshQueue {
+    /// Queues the whole chunk, unless it's already waiting. Updates are merged, since chunks are meshed as they are.
+    pub fn push(&mut self, local: IVec3) {
+        if self.queued.insert(local, MeshUpdate::Full).is_none() {
+            self.queue.push_back(local);
+        }
+    }
+
+    /**
+      Queues the given chunks again, ahead of the others and in the given order, like when they were meshed but
+      couldn't be uploaded on this frame.
+    */
+    pub fn retry(&mut self, locals: Vec<IVec3>) {
+        for local in locals.into_iter().rev() {
+            if self
+                .queued
+                .insert(local, MeshUpdate::Dirty(DirtyRegion::default()))
+                .is_none()
+            {
+                self.queue.push_front(local);
+            }
+        }
+    }
+
+    /// Queues only what changed on the chunk, merging it with what is already waiting.
+    pub fn push_dirty(&mut self, local: IVec3, dirty: DirtyRegion) {
+        match self.queued.get_mut(&local) {
+            Some(MeshUpdate::Full) => (),
+            Some(MeshUpdate::Dirty(queued)) => queued.merge(dirty),
+            None => {
+                self.queued.insert(local, MeshUpdate::Dirty(dirty));
+                self.queue.push_back(local);
+            }
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<(IVec3, MeshUpdate)> {
+        let local = self.queue.pop_front()?;
+        let update = self.queued.remove(&local).unwrap_or(MeshUpdate::Full);
+        Some((local, update))
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+/**
+  Faces of each spawned chunk, split by sub-region, so edits only compute the faces of the regions they touch
+  and reuse the others when rebuilding the mesh.
+*/
+#[derive(Default)]
+pub struct ChunkRegionFaces(pub HashMap<IVec3, Vec<Vec<VoxelFace>>>);
+
+/**
+  Faces occlusion of each spawned chunk, so edits only recompute the occlusion of the voxels they change and
+  their neighbors.
+*/
+#[derive(Default)]
+pub struct ChunkOcclusion(pub HashMap<IVec3, ChunkFacesOcclusion>);
+
+/// Chunk to be meshed, along the faces and occlusion kept from the last time it was meshed.
+pub struct MeshJob {
+    pub local: IVec3,
+    pub update: MeshUpdate,
+    pub faces: Vec<Vec<VoxelFace>>,
+    pub occlusion: Option<ChunkFacesOcclusion>,
+}
+
+/// Mesh data of a chunk, ready to be uploaded, along the faces and occlusion to be kept for the next time.
+pub struct MeshedChunk {
+    pub local: IVec3,
+    pub faces: Vec<Vec<VoxelFace>>,
+    pub occlusion: ChunkFacesOcclusion,
+    pub vertices: Vec<VoxelVertex>,
+    pub instances: HashMap<PropShape, Vec<PropInstance>>,
+}
+
+impl MeshJob {
+    /**
+      Computes the occlusion, faces and vertices of the chunk. Edited chunks only patch what their dirty
+      region touches. Returns `None` when the chunk was unloaded while waiting.
+    */
+    pub fn run(self, world: &VoxWorld, registry: &KindRegistry) -> Option<MeshedChunk> {
+        let MeshJob {
+            local,
+            update,
+            mut faces,
+            occlusion,
+        } = self;
+
+        let kind = world.get(local)?;
+
+        let occlusion = match (update, occlusion) {
+            (MeshUpdate::Dirty(dirty), Some(mut cached)) if faces.len() == mesher::REGION_COUNT => {
+                let affected = dirty
+                    .voxels
+                    .iter()
+                    .flat_map(|voxel| occlusion::affected_voxels(*voxel))
+                    .collect::<HashSet<_>>();
+                occlusion::update_faces_occlusion(&mut cached, &kind, registry, affected);
+
+                for region in dirty.regions {
+                    faces[region] = mesher::region_faces(&kind, registry, &cached, region);
+                }
+
+                cached
+            }
+            // Chunks never meshed before have no faces nor occlusion to patch
+            _ => {
+                let cached = occlusion::faces_occlusion(&kind, registry);
+                faces = (0..mesher::REGION_COUNT)
+                    .map(|region| mesher::region_faces(&kind, registry, &cached, region))
+                    .collect();
+
+                cached
+            }
+        };
+
+        Some(MeshedChunk {
+            local,
+            vertices: mesher::vertices(&faces.concat(), &BiomeBlend::new(world, local)),
+            instances: props::prop_instances(&kind, registry),
+            faces,
+            occlusion,
+        })
+    }
+}
+
+/**
+  Meshes all the given chunks, in parallel on the rayon thread pool when `parallel` is set, and returns the
+  meshed ones in the given order. Each job only locks its own chunk, and just while it's running.
+*/
+pub fn mesh_batch(
+    jobs: Vec<MeshJob>,
+    world: &VoxWorld,
+    registry: &KindRegistry,
+    parallel: bool,
+) -> Vec<MeshedChunk> {
+    if parallel {
+        jobs.into_par_iter()
+            .filter_map(|job| job.run(world, registry))
+            .collect()
+    } else {
+        jobs.into_iter()
+            .filter_map(|job| job.run(world, registry))
+            .collect()
+    }
+}
+
+/**
+  Chunk meshes to be reused. Re-meshed chunks refill their own mesh, and meshes of unloaded chunks are kept
+  here for new chunks, so their buffers are reused instead of allocating new ones and new mesh assets.
+*/
+#[derive(Default)]
+pub struct MeshPool {
+    free: Vec<Handle<Mesh>>,
+}
+
+impl MeshPool {
+    /// Keeps the given mesh for reuse, unless the pool is full, in which case it's dropped.
+    pub fn release(&mut self, mesh: Handle<Mesh>) {
+        if self.free.len() < MESH_POOL_SIZE {
+            self.free.push(mesh);
+        }
+    }
+
+    /// Takes a pooled mesh, or adds a new empty one when there is none.
+    pub fn take(&mut self, meshes: &mut Assets<Mesh>) -> Handle<Mesh> {
+        self.free
+            .pop()
+            .unwrap_or_else(|| meshes.add(Mesh::new(PrimitiveTopology::TriangleList)))
+    }
+
+    pub fn len(&self) -> usize {
+        self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.free.is_empty()
+    }
+}
+
+/// Material used by chunks on each render quality tier.
+pub struct ChunkMaterial {
+    pub pbr: Handle<ChunkPbrMaterial>,
+    pub ao: Handle<ChunkAoMaterial>,
+}
+
+impl FromWorld for ChunkMaterial {
+    fn from_world(world: &mut World) -> Self {
+        // Chunk colors come from the biome colormap, which the base color would only darken
+        let colormap = world
+            .get_resource_mut::<Assets<Image>>()
+            .expect("RenderPlugin must be added before ChunkMaterial")
+            .add(tint::colormap());
+
+        let mut pbr_materials = world
+            .get_resource_mut::<Assets<ChunkPbrMaterial>>()
+            .expect("MaterialPlugin<ChunkPbrMaterial> must be added before ChunkMaterial");
+        let pbr = pbr_materials.add(ChunkPbrMaterial {
+            colormap: Some(colormap.clone()),
+            ..Default::default()
+        });
+
+        let mut ao_materials = world
+            .get_resource_mut::<Assets<ChunkAoMaterial>>()
+            .expect("MaterialPlugin<ChunkAoMaterial> must be added before ChunkMaterial");
+        let ao = ao_materials.add(ChunkAoMaterial {
+            colormap: Some(colormap),
+            ..Default::default()
+        });
+
+        Self { pbr, ao }
+    }
+}
+
+pub fn chunk_aabb() -> Aabb {
+    let bounds = bounds::chunk(IVec3::ZERO);
+    Aabb::from_min_max(bounds.min, bounds.max)
+}
+
+/**
+  Vertical offset of a fading in chunk, which starts at `-FADE_IN_DEPTH` and eases out to zero.
+*/
+pub fn fade_in_offset(elapsed: f32, duration: f32) -> f32 {
+    if duration <= 0.0 {
+        return 0.0;
+    }
+
+    let t = (elapsed / duration).clamp(0.0, 1.0);
+    let eased = 1.0 - (1.0 - t).powi(3);
+
+    -FADE_IN_DEPTH * (1.0 - eased)
+}
+
+/// Size, in bytes, of the vertex and index buffers uploaded to the GPU for the given mesh.
+pub fn mesh_size(mesh: &Mesh) -> usize {
+    let stride = mesh.get_mesh_vertex_buffer_layout().layout().array_stride as usize;
+    let indices = mesh.get_index_buffer_bytes().map_or(0, |bytes| bytes.len());
+
+    stride * mesh.count_vertices() + indices
+}
+
+/**
+  Whether another mesh can be uploaded on this frame, after the given number of meshes and bytes were. The
+  first mesh is always allowed, so a single huge mesh can't stall the queue.
+*/
+pub fn within_budget(settings: &RenderSettings, uploads: usize, bytes: usize) -> bool {
+    uploads == 0 || (uploads < settings.max_mesh_uploads && bytes < settings.max_upload_bytes)
+}
+
+/**
+  Spawns one child entity per prop shape, which draws all props of that shape in the chunk at once.
+*/
+fn spawn_props(
+    parent: &mut ChildBuilder,
+    instances: HashMap<PropShape, Vec<PropInstance>>,
+    prop_meshes: &PropMeshes,
+) {
+    for (shape, instances) in instances {
+        parent.spawn_bundle((
+            prop_meshes.get(shape),
+            PropInstances(instances),
+            Transform::default(),
+            GlobalTransform::default(),
+            Visibility::default(),
+            ComputedVisibility::default(),
+            // Props never leave their chunk, and the shadow pass would draw only a single instance
+            chunk_aabb(),
+            NotShadowCaster,
+        ));
+    }
+}
+
+/**
+  Meshes queued chunks, within the upload budget. Chunks are meshed as a batch, see [`mesh_batch`], and edits
+  only re-mesh the sub-regions they touch, which are patched into the faces kept on [`ChunkRegionFaces`],
+  using the occlusion kept on [`ChunkOcclusion`]. Edited chunks get both [`ChunkUpdated`] and
+  [`VoxelsUpdated`], so any [`ChunkUpdated`] left unmatched means the whole chunk changed.
+*/
+#[allow(clippy::too_many_arguments)]
+pub(super) fn mesh_chunks(
+    mut commands: Commands,
+    world: Res<VoxWorld>,
+    registry: Res<KindRegistry>,
+    origin: Res<WorldOrigin>,
+    settings: Res<RenderSettings>,
+    material: Res<ChunkMaterial>,
+    prop_meshes: Res<PropMeshes>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut entity_map: ResMut<ChunkEntityMap>,
+    mut queue: ResMut<MeshQueue>,
+    mut pool: ResMut<MeshPool>,
+    mut region_faces: ResMut<ChunkRegionFaces>,
+    mut occlusions: ResMut<ChunkOcclusion>,
+    mut reader: EventReader<ChunkUpdated>,
+    mut voxels_reader: EventReader<VoxelsUpdated>,
+    handles: Query<&Handle<Mesh>, With<ChunkEntity>>,
+) {
+    let mut partial = HashMap::<IVec3, (usize, DirtyRegion)>::default();
+
+    for VoxelsUpdated { chunk, voxels } in voxels_reader.iter() {
+        let (count, dirty) = partial.entry(*chunk).or_default();
+        *count += 1;
+
+        for voxel in voxels {
+            dirty.mark(*voxel);
+        }
+    }
+
+    for ChunkUpdated(local) in reader.iter() {
+        match partial.get_mut(local).filter(|(count, _)| *count > 0) {
+            Some((count, _)) => *count -= 1,
+            None => queue.push(*local),
+        }
+    }
+
+    for (local, (_, dirty)) in partial {
+        queue.push_dirty(local, dirty);
+    }
+
+    let jobs = (0..settings.max_mesh_uploads.max(1))
+        .map_while(|_| queue.pop())
+        .map(|(local, update)| MeshJob {
+            local,
+            update,
+            faces: region_faces.0.remove(&local).unwrap_or_default(),
+            occlusion: occlusions.0.remove(&local),
+        })
+        .collect();
+
+    let mut uploads = 0;
+    let mut bytes = 0;
+    let mut retries = vec![];
+
+    for meshed in mesh_batch(jobs, &world, &registry, settings.parallel_meshing) {
+        let MeshedChunk {
+            local,
+            faces,
+            occlusion,
+            vertices,
+            instances,
+        } = meshed;
+
+        region_faces.0.insert(local, faces);
+        occlusions.0.insert(local, occlusion);
+
+        // Faces are kept, so chunks over the budget only need their vertices to be rebuilt on the next frame
+        if !within_budget(&settings, uploads, bytes) {
+            retries.push(local);
+            continue;
+        }
+
+        let existing = entity_map.0.get(&local).copied();
+
+        // Spawned chunks refill their own mesh, so only new chunks need one
+        let mesh = match existing.and_then(|entity| handles.get(entity).ok()) {
+            Some(mesh) => mesh.clone(),
+            None => pool.take(&mut meshes),
+        };
+
+        if let Some(mesh) = meshes.get_mut(&mesh) {
+            mesher::fill_mesh(mesh, &vertices);
+
+            uploads += 1;
+            bytes += mesh_size(mesh);
+        }
+
+        if let Some(entity) = existing {
+            let mut entity = commands.entity(entity);
+            entity.despawn_descendants();
+            entity.with_children(|parent| spawn_props(parent, instances, &prop_meshes));
+            continue;
+        }
+
+        let target = origin.to_render(local);
+        let fade_in = settings.chunk_fade_in > 0.0;
+
+        let translation = if fade_in {
+            target + Vec3::Y * fade_in_offset(0.0, settings.chunk_fade_in)
+        } else {
+            target
+        };
+
+        let transform = Transform::from_translation(translation);
+
+        let mut entity = match settings.quality {
+            RenderQuality::High => commands.spawn_bundle(MaterialMeshBundle {
+                mesh,
+                material: material.pbr.clone(),
+                transform,
+                ..Default::default()
+            }),
+            RenderQuality::Low => commands.spawn_bundle(MaterialMeshBundle {
+                mesh,
+                material: material.ao.clone(),
+                transform,
+                ..Default::default()
+            }),
+        };
+
+        // Chunk meshes never leave chunk bounds, so a fixed AABB is used for frustum culling.
+        // This way it doesn't need to be computed from the mesh nor gets stale when the chunk is remeshed.
+        entity
+            .insert(ChunkEntity(local))
+            .insert(chunk_aabb())
+            .with_children(|parent| spawn_props(parent, instances, &prop_meshes));
+
+        if fade_in {
+            entity.insert(ChunkFadeIn {
+                target,
+                elapsed: 0.0,
+            });
+        }
+
+        entity_map.0.insert(local, entity.id());
+    }
+
+    queue.retry(retries);
+}
+
+pub(super) fn despawn_chunks(
+    mut commands: Commands,
+    mut entity_map: ResMut<ChunkEntityMap>,
+    mut pool: ResMut<MeshPool>,
+    mut region_faces: ResMut<ChunkRegionFaces>,
+    mut occlusions: ResMut<ChunkOcclusion>,
+    mut reader: EventReader<ChunkUnloaded>,
+    handles: Query<&Handle<Mesh>, With<ChunkEntity>>,
+) {
+    for ChunkUnloaded(local) in reader.iter() {
+        region_faces.0.remove(local);
+        occlusions.0.remove(local);
+
+        if let Some(entity) = entity_map.0.remove(local) {
+            if let Ok(mesh) = handles.get(entity) {
+                pool.release(mesh.clone());
+            }
+
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+pub(super) fn fade_in_chunks(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<RenderSettings>,
+    mut reader: EventReader<OriginShifted>,
+    mut q: Query<(Entity, &mut Transform, &mut ChunkFadeIn)>,
+) {
+    let shift = reader.iter().fold(Vec3::ZERO, |acc, evt| acc + evt.0);
+
+    for (entity, mut transform, mut fade_in) in q.iter_mut() {
+        // Targets are in render space, so they must follow the origin
+        fade_in.target += shift;
+        fade_in.elapsed += time.delta_seconds();
+
+        if fade_in.elapsed >= settings.chunk_fade_in {
+            transform.translation = fade_in.target;
+            commands.entity(entity).remove::<ChunkFadeIn>();
+        } else {
+            let offset = fade_in_offset(fade_in.elapsed, settings.chunk_fade_in);
+            transform.translation = fade_in.target + Vec3::Y * offset;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::occlusion;
+    use bevy::asset::HandleId;
+    use vox::chunk;
+
+    #[test]
+    fn chunk_aabb() {
+        let mut kind = vox::chunk::ChunkKind::default();
+        kind.set(IVec3::ZERO, 1.into());
+        kind.set(IVec3::splat(chunk::AXIS_ENDING as i32), 1.into());
+
+        let registry = KindRegistry::default();
+        let occlusion = occlusion::faces_occlusion(&kind, &registry);
+        let mesh = mesher::mesh(&mesher::vertices(
+            &mesher::faces(&kind, &registry, &occlusion),
+            &BiomeBlend::new(&VoxWorld::default(), IVec3::ZERO),
+        ));
+
+        // Even the largest chunk mesh must fit in the chunk AABB
+        let mesh_aabb = mesh.compute_aabb().unwrap();
+        let aabb = super::chunk_aabb();
+
+        assert_eq!(mesh_aabb.min(), aabb.min());
+        assert_eq!(mesh_aabb.max(), aabb.max());
+    }
+
+    #[test]
+    fn mesh_queue() {
+        let dirty = |voxels: &[IVec3]| {
+            let mut dirty = DirtyRegion::default();
+            for voxel in voxels {
+                dirty.mark(*voxel);
+            }
+            dirty
+        };
+
+        let mut queue = MeshQueue::default();
+        queue.push(IVec3::X);
+        queue.push_dirty(IVec3::Y, dirty(&[IVec3::splat(3)]));
+        queue.push_dirty(IVec3::X, dirty(&[IVec3::splat(9)]));
+        queue.push_dirty(IVec3::Y, dirty(&[IVec3::new(3, 3, 7)]));
+
+        assert_eq!(queue.len(), 2);
+
+        // Whole chunk updates include any region
+        assert_eq!(queue.pop(), Some((IVec3::X, MeshUpdate::Full)));
+
+        // Popped chunks can be queued again
+        queue.push(IVec3::X);
+        assert_eq!(
+            queue.pop(),
+            Some((
+                IVec3::Y,
+                MeshUpdate::Dirty(dirty(&[IVec3::splat(3), IVec3::new(3, 3, 7)]))
+            ))
+        );
+        assert_eq!(queue.pop(), Some((IVec3::X, MeshUpdate::Full)));
+        assert!(queue.is_empty());
+
+        // Retried chunks go ahead of the others, keeping their order
+        queue.push(IVec3::Z);
+        queue.retry(vec![IVec3::X, IVec3::Y]);
+        assert_eq!(
+            queue.pop(),
+            Some((IVec3::X, MeshUpdate::Dirty(DirtyRegion::default())))
+        );
+        assert_eq!(queue.pop().map(|(local, _)| local), Some(IVec3::Y));
+        assert_eq!(queue.pop(), Some((IVec3::Z, MeshUpdate::Full)));
+    }
+
+    #[test]
+    fn mesh_batch() {
+        let registry = KindRegistry::default();
+        let world = VoxWorld::default();
+
+        let mut kind = vox::chunk::ChunkKind::default();
+        kind.set((3, 3, 3).into(), 1.into());
+        kind.set((3, 4, 3).into(), 1.into());
+        world.add(IVec3::ZERO, kind.clone());
+        world.add(IVec3::X, kind);
+
+        let job = |local, update, cached: Option<&MeshedChunk>| MeshJob {
+            local,
+            update,
+            faces: cached.map(|c| c.faces.clone()).unwrap_or_default(),
+            occlusion: cached.map(|c| c.occlusion.clone()),
+        };
+        let jobs = || {
+            [IVec3::ZERO, IVec3::Y, IVec3::X]
+                .into_iter()
+                .map(|local| job(local, MeshUpdate::Full, None))
+                .collect()
+        };
+
+        // Unloaded chunks are skipped, and both paths mesh the same
+        let sequential = super::mesh_batch(jobs(), &world, &registry, false);
+        let parallel = super::mesh_batch(jobs(), &world, &registry, true);
+        assert_eq!(
+            parallel.iter().map(|c| c.local).collect::<Vec<_>>(),
+            vec![IVec3::ZERO, IVec3::X]
+        );
+        for (a, b) in sequential.iter().zip(&parallel) {
+            assert_eq!(a.faces, b.faces);
+            assert_eq!(a.vertices.len(), b.vertices.len());
+        }
+
+        // Patching the kept faces and occlusion matches meshing it all again
+        let changed = IVec3::new(3, 5, 3);
+        world.get_mut(IVec3::ZERO).unwrap().set(changed, 1.into());
+
+        let mut dirty = DirtyRegion::default();
+        dirty.mark(changed);
+
+        let patched = job(IVec3::ZERO, MeshUpdate::Dirty(dirty), Some(&parallel[0]));
+        let patched = patched.run(&world, &registry).unwrap();
+        let full = job(IVec3::ZERO, MeshUpdate::Full, None);
+        let full = full.run(&world, &registry).unwrap();
+
+        assert_eq!(patched.faces, full.faces);
+        assert!(patched.occlusion.iter().eq(full.occlusion.iter()));
+    }
+
+    #[test]
+    fn mesh_pool() {
+        let mesh = || Handle::<Mesh>::weak(HandleId::random::<Mesh>());
+        let mut pool = MeshPool::default();
+
+        let released = mesh();
+        pool.release(released.clone());
+        assert_eq!(pool.free.pop(), Some(released));
+        assert!(pool.is_empty());
+
+        // Meshes beyond the pool size are dropped
+        for _ in 0..MESH_POOL_SIZE + 1 {
+            pool.release(mesh());
+        }
+        assert_eq!(pool.len(), MESH_POOL_SIZE);
+    }
+
+    #[test]
+    fn within_budget() {
+        let settings = RenderSettings {
+            max_mesh_uploads: 2,
+            max_upload_bytes: 100,
+            ..Default::default()
+        };
+
+        assert!(super::within_budget(&settings, 0, 0));
+        assert!(super::within_budget(&settings, 1, 99));
+        assert!(!super::within_budget(&settings, 2, 0));
+        assert!(!super::within_budget(&settings, 1, 100));
+
+        // A single mesh is always uploaded, even over budget
+        let settings = RenderSettings {
+            max_mesh_uploads: 0,
+            max_upload_bytes: 0,
+            ..Default::default()
+        };
+        assert!(super::within_budget(&settings, 0, 0));
+        assert!(!super::within_budget(&settings, 1, 0));
+    }
+
+    #[test]
+    fn mesh_size() {
+        let mut kind = vox::chunk::ChunkKind::default();
+        kind.set(IVec3::ONE, 1.into());
+
+        let registry = KindRegistry::default();
+        let occlusion = occlusion::faces_occlusion(&kind, &registry);
+        let faces = mesher::faces(&kind, &registry, &occlusion);
+        let mesh = mesher::mesh(&mesher::vertices(
+            &faces,
+            &BiomeBlend::new(&VoxWorld::default(), IVec3::ZERO),
+        ));
+
+        // Each face has 6 indices
+        let indices = faces.len() * 6 * std::mem::size_of::<u32>();
+        assert_eq!(
+            super::mesh_size(&mesh),
+            mesh.get_vertex_buffer_data().len() + indices
+        );
+    }
+
+    #[test]
+    fn fade_in_offset() {
+        assert_eq!(super::fade_in_offset(0.0, 0.3), -super::FADE_IN_DEPTH);
+        assert_eq!(super::fade_in_offset(0.3, 0.3), 0.0);
+        assert_eq!(super::fade_in_offset(1.0, 0.3), 0.0);
+
+        // Disabled fade in should never offset chunks
+        assert_eq!(super::fade_in_offset(0.0, 0.0), 0.0);
+
+        let mut last = super::fade_in_offset(0.0, 0.3);
+        for i in 1..=30 {
+            let offset = super::fade_in_offset(i as f32 * 0.01, 0.3);
+            assert!(offset >= last);
+            last = offset;
+        }
+    }
+}
@@ -1,11 +1,204 @@
-use bevy::prelude::*;
+use bevy::{
+    prelude::*,
+    render::render_resource::{Shader, WgpuAdapterInfo},
+};
+use wgpu::DeviceType;
 
+pub mod culling;
+pub mod entities;
+pub mod material;
+pub mod mesher;
 pub mod occlusion;
+pub mod post_process;
+pub mod props;
+pub mod tint;
+pub mod wind;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderQuality {
+    /// Lit by the PBR pipeline, with shadow maps.
+    High,
+    /// No shadow maps. Chunks are shaded only by baked vertex ambient occlusion and hemisphere tinting.
+    Low,
+}
+
+/**
+  Picks the render quality tier for the given GPU. Integrated, virtual and software adapters gets the low tier.
+*/
+pub fn detect_quality(device_type: DeviceType) -> RenderQuality {
+    match device_type {
+        DeviceType::DiscreteGpu | DeviceType::Other => RenderQuality::High,
+        DeviceType::IntegratedGpu | DeviceType::VirtualGpu | DeviceType::Cpu => RenderQuality::Low,
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RenderSettings {
+    /// Duration, in seconds, of the rise animation played when a chunk is spawned. Zero disables it.
+    pub chunk_fade_in: f32,
+    /// If no settings are inserted before [`VoxRenderPlugin`], it's detected from the GPU adapter.
+    pub quality: RenderQuality,
+    /// Max number of chunk meshes uploaded each frame. Remaining chunks are meshed on the next frames.
+    pub max_mesh_uploads: usize,
+    /// Max size, in bytes, of the chunk meshes uploaded each frame. At least one mesh is always uploaded.
+    pub max_upload_bytes: usize,
+    /// Whether queued chunks are meshed in parallel, on the rayon thread pool, instead of one after another.
+    pub parallel_meshing: bool,
+    /**
+      Post processing applied to the frame, like bloom and vignette. When `None`, the 3d camera renders right
+      to the window. It can only be turned on or off before [`VoxRenderPlugin`] is added.
+    */
+    pub post_process: Option<post_process::PostProcessSettings>,
+    /// Whether foliage, like grass and leaves, sways in the [`wind::Wind`].
+    pub wind: bool,
+    /**
+      Whether chunks hidden behind others are culled on the GPU, see [`culling::GpuCullingPlugin`]. It pays off
+      on very large view distances. It can only be turned on or off before [`VoxRenderPlugin`] is added.
+    */
+    pub gpu_culling: bool,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            chunk_fade_in: 0.3,
+            quality: RenderQuality::High,
+            max_mesh_uploads: 16,
+            max_upload_bytes: 4 * 1024 * 1024,
+            parallel_meshing: true,
+            post_process: None,
+            wind: true,
+            gpu_culling: false,
+        }
+    }
+}
 
 pub struct VoxRenderPlugin;
 
 impl Plugin for VoxRenderPlugin {
     fn build(&self, app: &mut App) {
-        todo!()
+        let settings = app
+            .world
+            .get_resource::<RenderSettings>()
+            .copied()
+            .unwrap_or_else(|| {
+                let quality = app
+                    .world
+                    .get_resource::<WgpuAdapterInfo>()
+                    .map(|info| detect_quality(info.device_type))
+                    .unwrap_or(RenderQuality::High);
+
+                RenderSettings {
+                    quality,
+                    ..Default::default()
+                }
+            });
+
+        info!("Using {:?} render quality", settings.quality);
+
+        let mut shaders = app
+            .world
+            .get_resource_mut::<Assets<Shader>>()
+            .expect("RenderPlugin must be added before VoxRenderPlugin");
+
+        shaders.set_untracked(
+            wind::WIND_SHADER_HANDLE,
+            Shader::from_wgsl(include_str!("shaders/wind.wgsl")),
+        );
+        shaders.set_untracked(
+            material::CHUNK_PBR_SHADER_HANDLE,
+            Shader::from_wgsl(include_str!("shaders/chunk_pbr.wgsl")),
+        );
+        shaders.set_untracked(
+            material::CHUNK_AO_SHADER_HANDLE,
+            Shader::from_wgsl(include_str!("shaders/chunk_ao.wgsl")),
+        );
+        shaders.set_untracked(
+            props::PROPS_SHADER_HANDLE,
+            Shader::from_wgsl(include_str!("shaders/props.wgsl")),
+        );
+        shaders.set_untracked(
+            post_process::POST_PROCESS_SHADER_HANDLE,
+            Shader::from_wgsl(include_str!("shaders/post_process.wgsl")),
+        );
+
+        shaders.set_untracked(
+            culling::CHUNK_DEPTH_SHADER_HANDLE,
+            Shader::from_wgsl(include_str!("shaders/chunk_depth.wgsl")),
+        );
+        shaders.set_untracked(
+            culling::CHUNK_CULL_SHADER_HANDLE,
+            Shader::from_wgsl(include_str!("shaders/chunk_cull.wgsl")),
+        );
+        shaders.set_untracked(
+            culling::HIZ_SHADER_HANDLE,
+            Shader::from_wgsl(include_str!("shaders/hiz.wgsl")),
+        );
+
+        if settings.post_process.is_some() {
+            app.add_plugin(post_process::PostProcessPlugin);
+        }
+
+        if settings.gpu_culling {
+            app.add_plugin(culling::GpuCullingPlugin);
+        }
+
+        app.insert_resource(settings)
+            .add_plugin(wind::WindPlugin)
+            .add_plugin(MaterialPlugin::<material::ChunkPbrMaterial>::default())
+            .add_plugin(MaterialPlugin::<material::ChunkAoMaterial>::default())
+            .add_plugin(props::PropsPlugin)
+            .init_resource::<entities::ChunkMaterial>()
+            .init_resource::<props::PropMeshes>()
+            .init_resource::<entities::ChunkEntityMap>()
+            .init_resource::<entities::MeshQueue>()
+            .init_resource::<entities::MeshPool>()
+            .init_resource::<entities::ChunkRegionFaces>()
+            .init_resource::<entities::ChunkOcclusion>()
+            .add_system(entities::mesh_chunks)
+            .add_system(entities::despawn_chunks.after(entities::mesh_chunks))
+            .add_system(entities::fade_in_chunks)
+            .add_system(disable_shadows);
+    }
+}
+
+/// Shadow maps are disabled on the low quality tier, so lights must not cast shadows.
+fn disable_shadows(
+    settings: Res<RenderSettings>,
+    mut directional_lights: Query<&mut DirectionalLight>,
+    mut point_lights: Query<&mut PointLight>,
+) {
+    if settings.quality != RenderQuality::Low {
+        return;
+    }
+
+    for mut light in directional_lights.iter_mut() {
+        if light.shadows_enabled {
+            light.shadows_enabled = false;
+        }
+    }
+
+    for mut light in point_lights.iter_mut() {
+        if light.shadows_enabled {
+            light.shadows_enabled = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_quality() {
+        assert_eq!(
+            super::detect_quality(DeviceType::DiscreteGpu),
+            RenderQuality::High
+        );
+        assert_eq!(
+            super::detect_quality(DeviceType::IntegratedGpu),
+            RenderQuality::Low
+        );
+        assert_eq!(super::detect_quality(DeviceType::Cpu), RenderQuality::Low);
     }
-}
\ No newline at end of file
+}
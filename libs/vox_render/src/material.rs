@@ -0,0 +1,441 @@
+use bevy::{
+    ecs::system::{lifetimeless::SRes, SystemParamItem},
+    pbr::{
+        MaterialPipeline, StandardMaterialFlags, StandardMaterialUniformData, PBR_SHADER_HANDLE,
+    },
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        mesh::MeshVertexBufferLayout,
+        render_asset::{PrepareAssetError, RenderAsset, RenderAssets},
+        render_resource::{
+            std140::{AsStd140, Std140},
+            BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+            BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, Buffer,
+            BufferBindingType, BufferInitDescriptor, BufferSize, BufferUsages,
+            RenderPipelineDescriptor, SamplerBindingType, ShaderStages,
+            SpecializedMeshPipelineError, TextureSampleType, TextureViewDimension,
+        },
+        renderer::RenderDevice,
+    },
+};
+
+use crate::{
+    mesher,
+    wind::{WindBuffer, WindUniform},
+};
+
+pub const CHUNK_AO_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 7_382_061_954_417_216_553);
+pub const CHUNK_PBR_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 16_027_493_581_240_665_319);
+
+/// How many colors the material uniform holds.
+const UNIFORM_COLORS: usize = 3;
+/// How many bindings the standard material has. Its uniform comes first, followed by texture and sampler pairs.
+const STANDARD_BINDINGS: u32 = 11;
+
+/**
+  Unlit material used by the low quality render tier. Instead of shadow maps and lights, chunks are shaded
+  by the baked vertex ambient occlusion and a tint which blends between sky and ground colors.
+  Vertex colors are looked up on the `colormap`, see [`crate::tint::colormap`].
+*/
+#[derive(Debug, Clone, TypeUuid)]
+#[uuid = "5c7a9f0e-3b1d-4d8e-9a62-1f0c7e2b4a91"]
+pub struct ChunkAoMaterial {
+    pub base_color: Color,
+    /// Tint applied on faces pointing up.
+    pub sky_color: Color,
+    /// Tint applied on faces pointing down.
+    pub ground_color: Color,
+    /// When not set, vertices are white.
+    pub colormap: Option<Handle<Image>>,
+}
+
+impl Default for ChunkAoMaterial {
+    fn default() -> Self {
+        Self {
+            base_color: Color::WHITE,
+            sky_color: Color::rgb(1.0, 1.0, 0.95),
+            ground_color: Color::rgb(0.45, 0.4, 0.4),
+            colormap: None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct GpuChunkAoMaterial {
+    _buffer: Buffer,
+    bind_group: BindGroup,
+}
+
+impl RenderAsset for ChunkAoMaterial {
+    type ExtractedAsset = ChunkAoMaterial;
+    type PreparedAsset = GpuChunkAoMaterial;
+    type Param = (
+        SRes<RenderDevice>,
+        SRes<MaterialPipeline<Self>>,
+        SRes<RenderAssets<Image>>,
+        SRes<WindBuffer>,
+    );
+
+    fn extract_asset(&self) -> Self::ExtractedAsset {
+        self.clone()
+    }
+
+    fn prepare_asset(
+        material: Self::ExtractedAsset,
+        (render_device, material_pipeline, gpu_images, wind_buffer): &mut SystemParamItem<
+            Self::Param,
+        >,
+    ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>> {
+        let (colormap, colormap_sampler) = match material_pipeline
+            .mesh_pipeline
+            .get_image_texture(gpu_images, &material.colormap)
+        {
+            Some(texture) => texture,
+            None => return Err(PrepareAssetError::RetryNextUpdate(material)),
+        };
+
+        let contents = [
+            material.base_color,
+            material.sky_color,
+            material.ground_color,
+        ]
+        .iter()
+        .flat_map(|color| {
+            Vec4::from_slice(&color.as_linear_rgba_f32())
+                .as_std140()
+                .as_bytes()
+                .to_vec()
+        })
+        .collect::<Vec<_>>();
+
+        let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            contents: &contents,
+            label: Some("chunk_ao_material_buffer"),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: wind_buffer.0.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::TextureView(colormap),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: BindingResource::Sampler(colormap_sampler),
+                },
+            ],
+            label: Some("chunk_ao_material_bind_group"),
+            layout: &material_pipeline.material_layout,
+        });
+
+        Ok(GpuChunkAoMaterial {
+            _buffer: buffer,
+            bind_group,
+        })
+    }
+}
+
+impl Material for ChunkAoMaterial {
+    fn vertex_shader(_: &AssetServer) -> Option<Handle<Shader>> {
+        Some(CHUNK_AO_SHADER_HANDLE.typed())
+    }
+
+    fn fragment_shader(_: &AssetServer) -> Option<Handle<Shader>> {
+        Some(CHUNK_AO_SHADER_HANDLE.typed())
+    }
+
+    fn bind_group(material: &<Self as RenderAsset>::PreparedAsset) -> &BindGroup {
+        &material.bind_group
+    }
+
+    fn bind_group_layout(render_device: &RenderDevice) -> BindGroupLayout {
+        render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(
+                            (Vec4::std140_size_static() * UNIFORM_COLORS) as u64,
+                        ),
+                    },
+                    count: None,
+                },
+                wind_layout_entry(1),
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("chunk_ao_material_layout"),
+        })
+    }
+
+    fn specialize(
+        _pipeline: &MaterialPipeline<Self>,
+        descriptor: &mut RenderPipelineDescriptor,
+        layout: &MeshVertexBufferLayout,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        let vertex_layout = layout.get_layout(&[
+            Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
+            Mesh::ATTRIBUTE_NORMAL.at_shader_location(1),
+            mesher::ATTRIBUTE_AO.at_shader_location(2),
+            mesher::ATTRIBUTE_SWAY.at_shader_location(3),
+            mesher::ATTRIBUTE_TINT.at_shader_location(4),
+        ])?;
+
+        descriptor.vertex.buffers = vec![vertex_layout];
+
+        Ok(())
+    }
+}
+
+/// Layout entry of the wind uniform, which only the vertex shader reads.
+fn wind_layout_entry(binding: u32) -> BindGroupLayoutEntry {
+    BindGroupLayoutEntry {
+        binding,
+        visibility: ShaderStages::VERTEX,
+        ty: BindingType::Buffer {
+            ty: BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: BufferSize::new(WindUniform::std140_size_static() as u64),
+        },
+        count: None,
+    }
+}
+
+/**
+  Material used by the high quality render tier. It's lit by the PBR pipeline, like an untextured
+  [`StandardMaterial`], but foliage vertices sway in the wind. Only the vertex shader is replaced, so its
+  bind group holds the standard material bindings, followed by the wind uniform.
+
+  Chunks have no textures, so the base color texture is the `colormap` instead, which the vertex shader
+  looks up by the vertex tint, see [`crate::tint::colormap`].
+*/
+#[derive(Debug, Clone, TypeUuid)]
+#[uuid = "0e4d2b7a-6c19-4f83-b5a0-8d3e1f9c2a64"]
+pub struct ChunkPbrMaterial {
+    pub base_color: Color,
+    pub perceptual_roughness: f32,
+    pub metallic: f32,
+    pub reflectance: f32,
+    pub colormap: Option<Handle<Image>>,
+}
+
+impl Default for ChunkPbrMaterial {
+    fn default() -> Self {
+        let standard = StandardMaterial::default();
+
+        Self {
+            base_color: standard.base_color,
+            perceptual_roughness: standard.perceptual_roughness,
+            metallic: standard.metallic,
+            reflectance: standard.reflectance,
+            colormap: None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct GpuChunkPbrMaterial {
+    _buffer: Buffer,
+    bind_group: BindGroup,
+}
+
+impl RenderAsset for ChunkPbrMaterial {
+    type ExtractedAsset = ChunkPbrMaterial;
+    type PreparedAsset = GpuChunkPbrMaterial;
+    type Param = (
+        SRes<RenderDevice>,
+        SRes<MaterialPipeline<Self>>,
+        SRes<RenderAssets<Image>>,
+        SRes<WindBuffer>,
+    );
+
+    fn extract_asset(&self) -> Self::ExtractedAsset {
+        self.clone()
+    }
+
+    fn prepare_asset(
+        material: Self::ExtractedAsset,
+        (render_device, material_pipeline, gpu_images, wind_buffer): &mut SystemParamItem<
+            Self::Param,
+        >,
+    ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>> {
+        let mut flags = StandardMaterialFlags::ALPHA_MODE_OPAQUE;
+        if material.colormap.is_some() {
+            flags |= StandardMaterialFlags::BASE_COLOR_TEXTURE;
+        }
+
+        let uniform = StandardMaterialUniformData {
+            base_color: material.base_color.as_linear_rgba_f32().into(),
+            emissive: Vec4::ZERO,
+            roughness: material.perceptual_roughness,
+            metallic: material.metallic,
+            reflectance: material.reflectance,
+            flags: flags.bits(),
+            alpha_cutoff: 0.5,
+        };
+
+        let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            contents: uniform.as_std140().as_bytes(),
+            label: Some("chunk_pbr_material_buffer"),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let (colormap, colormap_sampler) = match material_pipeline
+            .mesh_pipeline
+            .get_image_texture(gpu_images, &material.colormap)
+        {
+            Some(texture) => texture,
+            None => return Err(PrepareAssetError::RetryNextUpdate(material)),
+        };
+
+        // There are no other textures, so every other texture binding gets the dummy white image
+        let (texture, sampler) = material_pipeline
+            .mesh_pipeline
+            .get_image_texture(gpu_images, &None)
+            .unwrap();
+
+        let mut entries = vec![
+            BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: BindingResource::TextureView(colormap),
+            },
+            BindGroupEntry {
+                binding: 2,
+                resource: BindingResource::Sampler(colormap_sampler),
+            },
+        ];
+
+        for texture_binding in (3..STANDARD_BINDINGS).step_by(2) {
+            entries.push(BindGroupEntry {
+                binding: texture_binding,
+                resource: BindingResource::TextureView(texture),
+            });
+            entries.push(BindGroupEntry {
+                binding: texture_binding + 1,
+                resource: BindingResource::Sampler(sampler),
+            });
+        }
+
+        entries.push(BindGroupEntry {
+            binding: STANDARD_BINDINGS,
+            resource: wind_buffer.0.as_entire_binding(),
+        });
+
+        let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+            entries: &entries,
+            label: Some("chunk_pbr_material_bind_group"),
+            layout: &material_pipeline.material_layout,
+        });
+
+        Ok(GpuChunkPbrMaterial {
+            _buffer: buffer,
+            bind_group,
+        })
+    }
+}
+
+impl Material for ChunkPbrMaterial {
+    fn vertex_shader(_: &AssetServer) -> Option<Handle<Shader>> {
+        Some(CHUNK_PBR_SHADER_HANDLE.typed())
+    }
+
+    fn fragment_shader(_: &AssetServer) -> Option<Handle<Shader>> {
+        Some(PBR_SHADER_HANDLE.typed())
+    }
+
+    fn bind_group(material: &<Self as RenderAsset>::PreparedAsset) -> &BindGroup {
+        &material.bind_group
+    }
+
+    fn bind_group_layout(render_device: &RenderDevice) -> BindGroupLayout {
+        let mut entries = vec![BindGroupLayoutEntry {
+            binding: 0,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: BufferSize::new(
+                    StandardMaterialUniformData::std140_size_static() as u64,
+                ),
+            },
+            count: None,
+        }];
+
+        for texture_binding in (1..STANDARD_BINDINGS).step_by(2) {
+            entries.push(BindGroupLayoutEntry {
+                binding: texture_binding,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    multisampled: false,
+                    sample_type: TextureSampleType::Float { filterable: true },
+                    view_dimension: TextureViewDimension::D2,
+                },
+                count: None,
+            });
+            entries.push(BindGroupLayoutEntry {
+                binding: texture_binding + 1,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                count: None,
+            });
+        }
+
+        entries.push(wind_layout_entry(STANDARD_BINDINGS));
+
+        render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &entries,
+            label: Some("chunk_pbr_material_layout"),
+        })
+    }
+
+    fn specialize(
+        _pipeline: &MaterialPipeline<Self>,
+        descriptor: &mut RenderPipelineDescriptor,
+        layout: &MeshVertexBufferLayout,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        let vertex_layout = layout.get_layout(&[
+            Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
+            Mesh::ATTRIBUTE_NORMAL.at_shader_location(1),
+            mesher::ATTRIBUTE_TINT.at_shader_location(2),
+            Mesh::ATTRIBUTE_TANGENT.at_shader_location(3),
+            mesher::ATTRIBUTE_SWAY.at_shader_location(4),
+        ])?;
+
+        descriptor.vertex.buffers = vec![vertex_layout];
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1187 @@
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, MeshVertexAttribute, VertexAttributeValues},
+        render_resource::{PrimitiveTopology, VertexFormat},
+    },
+    utils::HashSet,
+};
+use vox::{
+    chunk::{self, ChunkKind},
+    voxel::{self, KindRegistry, MeshShape, VoxelFace, VoxelVertex},
+};
+
+use crate::{
+    occlusion::ChunkFacesOcclusion,
+    tint::{self, BiomeBlend},
+};
+
+/// Baked ambient occlusion factor of each vertex, used by the low quality render tier.
+pub const ATTRIBUTE_AO: MeshVertexAttribute =
+    MeshVertexAttribute::new("Vertex_AO", 915_254_117, VertexFormat::Float32);
+
+/// How much each vertex sways in the wind, used to animate foliage.
+pub const ATTRIBUTE_SWAY: MeshVertexAttribute =
+    MeshVertexAttribute::new("Vertex_Sway", 915_254_118, VertexFormat::Float32);
+
+/// Where each vertex color is looked up on the biome colormap, see [`crate::tint::colormap`].
+pub const ATTRIBUTE_TINT: MeshVertexAttribute =
+    MeshVertexAttribute::new("Vertex_Tint", 915_254_119, VertexFormat::Float32x2);
+
+/// Ambient occlusion level of a vertex with no occluding voxels around it.
+const MAX_AO: u8 = 3;
+
+/**
+  Sway of foliage voxels which fill their whole shape, like leaves. It's the same on every vertex, so
+  neighbor voxels keep sharing their corners while swaying.
+*/
+const FOLIAGE_SWAY: f32 = 0.3;
+/// Sway of cross vertices, which are rooted to the ground and sway only at the top.
+const CROSS_SWAY: [f32; 4] = [0.0, 0.0, 1.0, 1.0];
+
+/**
+  Returns the vertices of the given side of a unit voxel, in counter-clockwise order when looking at the face.
+*/
+fn face_vertices(side: voxel::Side) -> [IVec3; 4] {
+    use voxel::Side;
+
+    match side {
+        Side::Right => [(1, 0, 0), (1, 1, 0), (1, 1, 1), (1, 0, 1)],
+        Side::Left => [(0, 0, 1), (0, 1, 1), (0, 1, 0), (0, 0, 0)],
+        Side::Up => [(0, 1, 1), (1, 1, 1), (1, 1, 0), (0, 1, 0)],
+        Side::Down => [(0, 0, 0), (1, 0, 0), (1, 0, 1), (0, 0, 1)],
+        Side::Front => [(0, 0, 1), (1, 0, 1), (1, 1, 1), (0, 1, 1)],
+        Side::Back => [(1, 0, 0), (0, 0, 0), (0, 1, 0), (1, 1, 0)],
+    }
+    .map(|v| v.into())
+}
+
+/**
+  Returns the two diagonal quads of a cross shaped voxel. Each quad is returned with both windings,
+  so it's visible from both sides.
+*/
+fn cross_vertices() -> [[IVec3; 4]; 4] {
+    let diagonals = [
+        [(0, 0, 0), (1, 0, 1), (1, 1, 1), (0, 1, 0)],
+        [(1, 0, 0), (0, 0, 1), (0, 1, 1), (1, 1, 0)],
+    ]
+    .map(|quad| quad.map(IVec3::from));
+
+    let [a, b] = diagonals;
+    let reversed = |[v0, v1, v2, v3]: [IVec3; 4]| [v1, v0, v3, v2];
+
+    [a, reversed(a), b, reversed(b)]
+}
+
+/// Height of slabs and of each stairs step.
+const HALF: f32 = 0.5;
+
+/// Thickness of panels and plates.
+const THIN: f32 = 3.0 / 16.0;
+
+/**
+  Returns the vertices of the given side of the box between `min` and `max`, in the same order as [`face_vertices`].
+*/
+fn box_face(side: voxel::Side, min: Vec3, max: Vec3) -> [Vec3; 4] {
+    face_vertices(side).map(|v| min + v.as_vec3() * (max - min))
+}
+
+/// Returns all faces of the box between `min` and `max`.
+fn box_faces(min: Vec3, max: Vec3) -> Vec<(voxel::Side, [Vec3; 4])> {
+    voxel::SIDES
+        .iter()
+        .map(|&side| (side, box_face(side, min, max)))
+        .collect()
+}
+
+/**
+  Returns the faces of shapes which doesn't fill the whole voxel. Faces inside the shape are omitted.
+*/
+fn partial_shape_faces(shape: MeshShape) -> Vec<(voxel::Side, [Vec3; 4])> {
+    use voxel::Side;
+
+    let bottom = (Vec3::ZERO, Vec3::new(1.0, HALF, 1.0));
+
+    match shape {
+        MeshShape::Slab => box_faces(bottom.0, bottom.1),
+        MeshShape::Stairs => {
+            // The step stands on the back half of the bottom slab
+            let step = (Vec3::new(0.0, HALF, 0.0), Vec3::new(1.0, 1.0, HALF));
+            let back = (Vec3::ZERO, Vec3::new(1.0, 1.0, HALF));
+            let front = (Vec3::new(0.0, 0.0, HALF), Vec3::new(1.0, HALF, 1.0));
+
+            [
+                (Side::Down, bottom),
+                (Side::Back, back),
+                (Side::Front, front),
+                (Side::Front, step),
+                (Side::Up, front),
+                (Side::Up, step),
+                (Side::Left, bottom),
+                (Side::Left, step),
+                (Side::Right, bottom),
+                (Side::Right, step),
+            ]
+            .into_iter()
+            .map(|(side, (min, max))| (side, box_face(side, min, max)))
+            .collect()
+        }
+        MeshShape::Panel => box_faces(Vec3::ZERO, Vec3::new(1.0, 1.0, THIN)),
+        MeshShape::SidePanel => box_faces(Vec3::ZERO, Vec3::new(THIN, 1.0, 1.0)),
+        MeshShape::Plate => box_faces(Vec3::ZERO, Vec3::new(1.0, THIN, 1.0)),
+        // Those are meshed by their own functions
+        MeshShape::Cube | MeshShape::Cross => vec![],
+    }
+}
+
+/**
+  Rotates the faces of a shape around the voxel center, so the shape front points to the given facing.
+*/
+fn rotate_faces(
+    faces: Vec<(voxel::Side, [Vec3; 4])>,
+    facing: voxel::Side,
+) -> Vec<(voxel::Side, [Vec3; 4])> {
+    if facing == voxel::Side::Front {
+        return faces;
+    }
+
+    let rotation = voxel::Side::facing_rotation(facing);
+    let center = Vec3::splat(0.5);
+
+    faces
+        .into_iter()
+        .map(|(side, vertices)| {
+            // Shape vertices are always on 1/16 voxel steps, so rounding removes any rotation imprecision
+            let vertices =
+                vertices.map(|v| ((rotation * (v - center) + center) * 16.0).round() / 16.0);
+
+            (side.rotated(facing), vertices)
+        })
+        .collect()
+}
+
+/// Whether the given face of a unit voxel lies on the voxel boundary, so it may be hidden by neighbors.
+fn is_on_boundary(side: voxel::Side, vertices: &[Vec3; 4]) -> bool {
+    (vertices[0] - Vec3::splat(0.5)).dot(side.normal()) == 0.5
+}
+
+fn is_solid(kind: &ChunkKind, registry: &KindRegistry, voxel: IVec3) -> bool {
+    if chunk::is_within_bounds(voxel) {
+        return registry.is_opaque(kind.get(voxel));
+    }
+
+    let (dir, neighbor_voxel) = chunk::overlap_voxel(voxel);
+
+    // Only direct neighbors are known, so voxels on diagonal chunks are considered empty
+    match voxel::SIDES.iter().find(|side| side.dir() == dir) {
+        Some(side) => {
+            matches!(kind.neighborhood.get(*side, neighbor_voxel), Some(k) if registry.is_opaque(k))
+        }
+        None => false,
+    }
+}
+
+/**
+  Computes the ambient occlusion level of the given face vertex, based on the two side voxels and
+  the corner voxel which touches the vertex, in front of the face.
+
+  `vertex` is relative to `voxel`, like the ones returned by [`face_vertices`].
+*/
+fn vertex_ao(
+    kind: &ChunkKind,
+    registry: &KindRegistry,
+    voxel: IVec3,
+    side: voxel::Side,
+    vertex: IVec3,
+) -> u8 {
+    use voxel::Side;
+
+    // Direction from the voxel center to the vertex, on both axes tangent to the face
+    let offset = vertex * 2 - IVec3::ONE;
+
+    let (tangent_a, tangent_b) = match side {
+        Side::Right | Side::Left => (offset * IVec3::Y, offset * IVec3::Z),
+        Side::Up | Side::Down => (offset * IVec3::X, offset * IVec3::Z),
+        Side::Front | Side::Back => (offset * IVec3::X, offset * IVec3::Y),
+    };
+
+    let front = voxel + side.dir();
+    let side_a = is_solid(kind, registry, front + tangent_a);
+    let side_b = is_solid(kind, registry, front + tangent_b);
+    let corner = is_solid(kind, registry, front + tangent_a + tangent_b);
+
+    if side_a && side_b {
+        0
+    } else {
+        MAX_AO - (side_a as u8 + side_b as u8 + corner as u8)
+    }
+}
+
+pub fn faces(
+    kind: &ChunkKind,
+    registry: &KindRegistry,
+    occlusion: &ChunkFacesOcclusion,
+) -> Vec<VoxelFace> {
+    faces_in(kind, registry, occlusion, chunk::voxels())
+}
+
+/// Same as [`faces`], but only for the given voxels.
+pub fn faces_in(
+    kind: &ChunkKind,
+    registry: &KindRegistry,
+    occlusion: &ChunkFacesOcclusion,
+    voxels: impl IntoIterator<Item = IVec3>,
+) -> Vec<VoxelFace> {
+    let mut faces = vec![];
+
+    for voxel in voxels {
+        let voxel_kind = kind.get(voxel);
+        let foliage = registry.is_foliage(voxel_kind);
+        let sway = [if foliage { FOLIAGE_SWAY } else { 0.0 }; 4];
+        let tint = registry.tint(voxel_kind);
+
+        match registry.mesh_shape(voxel_kind) {
+            Some(MeshShape::Cube) => (),
+            Some(MeshShape::Cross) => {
+                // Crosses are lit like the ground they stand on, so their normal points up
+                faces.extend(cross_vertices().map(|vertices| VoxelFace {
+                    vertices: vertices.map(|v| (v + voxel).as_vec3()),
+                    side: voxel::Side::Up,
+                    ao: [MAX_AO; 4],
+                    sway: if foliage { CROSS_SWAY } else { [0.0; 4] },
+                    tint,
+                }));
+                continue;
+            }
+            Some(shape) => {
+                let faces_occlusion = occlusion.get(voxel);
+
+                // Faces inside the voxel are never hidden by neighbors
+                faces.extend(
+                    rotate_faces(partial_shape_faces(shape), voxel_kind.facing())
+                        .into_iter()
+                        .filter(|(side, vertices)| {
+                            !is_on_boundary(*side, vertices) || !faces_occlusion.is_occluded(*side)
+                        })
+                        .map(|(side, vertices)| VoxelFace {
+                            vertices: vertices.map(|v| v + voxel.as_vec3()),
+                            side,
+                            ao: [MAX_AO; 4],
+                            sway,
+                            tint,
+                        }),
+                );
+                continue;
+            }
+            None => continue,
+        }
+
+        let faces_occlusion = occlusion.get(voxel);
+
+        if faces_occlusion.is_fully_occluded() {
+            continue;
+        }
+
+        for side in voxel::SIDES {
+            if !faces_occlusion.is_occluded(side) {
+                let vertices = face_vertices(side);
+
+                faces.push(VoxelFace {
+                    vertices: vertices.map(|v| (v + voxel).as_vec3()),
+                    side,
+                    ao: vertices.map(|v| vertex_ao(kind, registry, voxel, side, v)),
+                    sway,
+                    tint,
+                });
+            }
+        }
+    }
+
+    faces
+}
+
+/// Size of the cubic sub-regions chunk meshes are split in, so edits only re-mesh the regions they touch.
+pub const REGION_SIZE: usize = 8;
+pub const REGION_AXIS: usize = chunk::AXIS_SIZE / REGION_SIZE;
+pub const REGION_COUNT: usize = REGION_AXIS * REGION_AXIS * REGION_AXIS;
+
+fn region_index(region: IVec3) -> usize {
+    let region = region.as_uvec3();
+    (region.x as usize * REGION_AXIS + region.y as usize) * REGION_AXIS + region.z as usize
+}
+
+/// Voxels inside the given sub-region.
+pub fn region_voxels(region: usize) -> impl Iterator<Item = IVec3> {
+    let base = IVec3::new(
+        (region / (REGION_AXIS * REGION_AXIS)) as i32,
+        (region / REGION_AXIS % REGION_AXIS) as i32,
+        (region % REGION_AXIS) as i32,
+    ) * REGION_SIZE as i32;
+
+    chunk::voxels()
+        .filter(|voxel| voxel.cmplt(IVec3::splat(REGION_SIZE as i32)).all())
+        .map(move |voxel| base + voxel)
+}
+
+/**
+  Sub-regions whose faces may change when the given voxel changes. Occlusion and ambient occlusion of a face
+  only look at voxels around it, so every region touched by the voxel or its direct neighbors is returned.
+  The voxel may be out of the chunk bounds, when it changed on the border of a neighbor chunk.
+*/
+pub fn dirty_regions(voxel: IVec3) -> HashSet<usize> {
+    let min = ((voxel - IVec3::ONE).max(IVec3::ZERO) / REGION_SIZE as i32)
+        .min(IVec3::splat(REGION_AXIS as i32 - 1));
+    let max = ((voxel + IVec3::ONE).min(IVec3::splat(chunk::AXIS_ENDING as i32))
+        / REGION_SIZE as i32)
+        .max(IVec3::ZERO);
+
+    let mut regions = HashSet::default();
+
+    for x in min.x..=max.x {
+        for y in min.y..=max.y {
+            for z in min.z..=max.z {
+                regions.insert(region_index(IVec3::new(x, y, z)));
+            }
+        }
+    }
+
+    regions
+}
+
+/// Faces of the voxels inside the given sub-region, using the faces occlusion kept for the chunk.
+pub fn region_faces(
+    kind: &ChunkKind,
+    registry: &KindRegistry,
+    occlusion: &ChunkFacesOcclusion,
+    region: usize,
+) -> Vec<VoxelFace> {
+    faces_in(kind, registry, occlusion, region_voxels(region))
+}
+
+/// Whether the given position lies on the chunk boundary plane of the given side.
+fn is_on_chunk_side(side: voxel::Side, v: Vec3) -> bool {
+    let normal = side.normal();
+    let distance = v.dot(normal);
+
+    if normal.dot(Vec3::ONE) > 0.0 {
+        distance == chunk::AXIS_SIZE as f32
+    } else {
+        distance == 0.0
+    }
+}
+
+/**
+  Returns skirts which hide cracks between chunks meshed at different levels of detail. Every face edge lying
+  on a chunk side gets a quad on that side, hanging `depth` voxels behind the face, so the gap to a coarser
+  neighbor, which may be up to `depth` away, is covered. Faces on chunk corners touch many sides, so they
+  get a skirt on each, and adjacent skirts meet along the corner.
+*/
+pub fn skirts(faces: &[VoxelFace], depth: f32) -> Vec<VoxelFace> {
+    let mut skirts = vec![];
+
+    for face in faces {
+        let behind = -face.side.normal() * depth;
+
+        for i in 0..4 {
+            let j = (i + 1) % 4;
+            let (a, b) = (face.vertices[i], face.vertices[j]);
+
+            for side in voxel::SIDES {
+                // Faces lying on a chunk side are already on it, so they need no skirt there
+                if side.normal().dot(face.side.normal()).abs() > 0.5
+                    || !is_on_chunk_side(side, a)
+                    || !is_on_chunk_side(side, b)
+                {
+                    continue;
+                }
+
+                let mut vertices = [a, b, b + behind, a + behind];
+                let mut ao = [face.ao[i], face.ao[j], face.ao[j], face.ao[i]];
+                let mut sway = [face.sway[i], face.sway[j], face.sway[j], face.sway[i]];
+
+                // Skirts must face outwards, like the chunk side they're on
+                let [v0, v1, v2, _] = vertices;
+                if (v1 - v0).cross(v2 - v0).dot(side.normal()) < 0.0 {
+                    vertices = [b, a, a + behind, b + behind];
+                    ao = [face.ao[j], face.ao[i], face.ao[i], face.ao[j]];
+                    sway = [face.sway[j], face.sway[i], face.sway[i], face.sway[j]];
+                }
+
+                skirts.push(VoxelFace {
+                    vertices,
+                    side,
+                    ao,
+                    sway,
+                    tint: face.tint,
+                });
+            }
+        }
+    }
+
+    skirts
+}
+
+/**
+  Returns the tangent and bitangent of a face, pointing to where texture `u` and `v` grows.
+  Textures are kept upright on vertical faces, while horizontal faces are aligned to the X axis.
+*/
+fn tangent_space(vertices: &[Vec3; 4]) -> (Vec3, Vec3) {
+    let [v0, v1, v2, _] = *vertices;
+    let normal = (v1 - v0).cross(v2 - v0).normalize();
+
+    if normal.y.abs() > 0.5 {
+        (Vec3::X, Vec3::Z * normal.y.signum())
+    } else {
+        let bitangent = -Vec3::Y;
+        (normal.cross(bitangent).normalize(), bitangent)
+    }
+}
+
+/**
+  Projects the face vertices on its tangent space, so each voxel is mapped to a whole texture.
+  Faces smaller than a voxel, like the sides of slabs, gets only the matching part of the texture.
+*/
+fn face_uvs(vertices: &[Vec3; 4], tangent: Vec3, bitangent: Vec3) -> [Vec2; 4] {
+    // Dividing by the sum of components makes diagonal quads, like crosses, also map to a whole texture
+    let tangent = tangent / tangent.abs().dot(Vec3::ONE);
+
+    // The face center is always inside, or on the boundary, of the voxel which owns it
+    let voxel = (vertices.iter().sum::<Vec3>() / 4.0).floor();
+
+    // Where the voxel corner closest to the texture origin is projected to
+    let origin = Vec2::new(
+        tangent.min(Vec3::ZERO).dot(Vec3::ONE),
+        bitangent.min(Vec3::ZERO).dot(Vec3::ONE),
+    );
+
+    vertices.map(|v| Vec2::new((v - voxel).dot(tangent), (v - voxel).dot(bitangent)) - origin)
+}
+
+/// Vertices of the given faces of a chunk, colored by the biomes the chunk `blend` has.
+pub fn vertices(faces: &[VoxelFace], blend: &BiomeBlend) -> Vec<VoxelVertex> {
+    faces
+        .iter()
+        .flat_map(|face| {
+            let (tangent, bitangent) = tangent_space(&face.vertices);
+            let uvs = face_uvs(&face.vertices, tangent, bitangent);
+
+            face.vertices
+                .iter()
+                .zip(face.ao)
+                .zip(uvs)
+                .zip(face.sway)
+                .map(|(((v, ao), uv), sway)| VoxelVertex {
+                    position: *v,
+                    normal: face.side.normal(),
+                    ao: ao as f32 / MAX_AO as f32,
+                    uv,
+                    // Bitangents are always the cross product of tangent and normal
+                    tangent: tangent.extend(-1.0),
+                    sway,
+                    tint: tint::coordinates(face.tint, blend.get(*v)),
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/**
+  Builds a triangle list mesh, with two triangles per face.
+  Vertices must be grouped by face, four at a time, like [`vertices`] returns.
+
+  Faces are split along the diagonal which keeps ambient occlusion interpolation symmetric.
+*/
+pub fn mesh(vertices: &[VoxelVertex]) -> Mesh {
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    fill_mesh(&mut mesh, vertices);
+    mesh
+}
+
+/**
+  Same as [`mesh`], but writes the vertices into an existing mesh. Attribute and index buffers the mesh
+  already has are cleared and refilled, so re-meshing a chunk reuses their allocations instead of making
+  new ones.
+*/
+pub fn fill_mesh(mesh: &mut Mesh, vertices: &[VoxelVertex]) {
+    debug_assert_eq!(vertices.len() % 4, 0);
+
+    fill_attribute(
+        mesh,
+        Mesh::ATTRIBUTE_POSITION,
+        |values| match values {
+            VertexAttributeValues::Float32x3(buffer) => Some(buffer),
+            _ => None,
+        },
+        vertices.iter().map(|v| v.position.to_array()),
+    );
+
+    fill_attribute(
+        mesh,
+        Mesh::ATTRIBUTE_NORMAL,
+        |values| match values {
+            VertexAttributeValues::Float32x3(buffer) => Some(buffer),
+            _ => None,
+        },
+        vertices.iter().map(|v| v.normal.to_array()),
+    );
+
+    fill_attribute(
+        mesh,
+        ATTRIBUTE_AO,
+        |values| match values {
+            VertexAttributeValues::Float32(buffer) => Some(buffer),
+            _ => None,
+        },
+        vertices.iter().map(|v| v.ao),
+    );
+
+    // With tangents the PBR pipeline enables normal mapping, once a material has a normal map texture
+    fill_attribute(
+        mesh,
+        Mesh::ATTRIBUTE_UV_0,
+        |values| match values {
+            VertexAttributeValues::Float32x2(buffer) => Some(buffer),
+            _ => None,
+        },
+        vertices.iter().map(|v| v.uv.to_array()),
+    );
+
+    fill_attribute(
+        mesh,
+        Mesh::ATTRIBUTE_TANGENT,
+        |values| match values {
+            VertexAttributeValues::Float32x4(buffer) => Some(buffer),
+            _ => None,
+        },
+        vertices.iter().map(|v| v.tangent.to_array()),
+    );
+
+    fill_attribute(
+        mesh,
+        ATTRIBUTE_SWAY,
+        |values| match values {
+            VertexAttributeValues::Float32(buffer) => Some(buffer),
+            _ => None,
+        },
+        vertices.iter().map(|v| v.sway),
+    );
+
+    fill_attribute(
+        mesh,
+        ATTRIBUTE_TINT,
+        |values| match values {
+            VertexAttributeValues::Float32x2(buffer) => Some(buffer),
+            _ => None,
+        },
+        vertices.iter().map(|v| v.tint.to_array()),
+    );
+
+    let indices = (0..vertices.len() as u32 / 4).flat_map(|face| {
+        let base = face * 4;
+        let i = base as usize;
+        let ao = |offset: usize| vertices[i + offset].ao;
+
+        if ao(0) + ao(2) < ao(1) + ao(3) {
+            [base + 1, base + 2, base + 3, base + 3, base, base + 1]
+        } else {
+            [base, base + 1, base + 2, base + 2, base + 3, base]
+        }
+    });
+
+    match mesh.indices_mut() {
+        Some(Indices::U32(buffer)) => {
+            buffer.clear();
+            buffer.extend(indices);
+        }
+        _ => mesh.set_indices(Some(Indices::U32(indices.collect()))),
+    }
+}
+
+/**
+  Refills the given attribute buffer, when the mesh has one of the expected format, or inserts a new one.
+*/
+fn fill_attribute<T>(
+    mesh: &mut Mesh,
+    attribute: MeshVertexAttribute,
+    buffer: fn(&mut VertexAttributeValues) -> Option<&mut Vec<T>>,
+    values: impl Iterator<Item = T>,
+) where
+    Vec<T>: Into<VertexAttributeValues>,
+{
+    match mesh.attribute_mut(attribute.id).and_then(buffer) {
+        Some(buffer) => {
+            buffer.clear();
+            buffer.extend(values);
+        }
+        None => mesh.insert_attribute(attribute, values.collect::<Vec<_>>()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::occlusion;
+    use vox::world::VoxWorld;
+
+    #[test]
+    fn face_vertices() {
+        // Vertices must be in counter-clockwise order, so the resulting normal points outwards
+        for side in voxel::SIDES {
+            let [v0, v1, v2, _] = super::face_vertices(side).map(|v| v.as_vec3());
+            let normal = (v1 - v0).cross(v2 - v0).normalize();
+
+            assert_eq!(normal, side.normal(), "Wrong winding order on {:?}", side);
+        }
+    }
+
+    #[test]
+    fn faces_single_voxel() {
+        let registry = KindRegistry::default();
+        let mut kind = ChunkKind::default();
+        kind.set((1, 2, 3).into(), 1.into());
+
+        let occlusion = occlusion::faces_occlusion(&kind, &registry);
+        let faces = super::faces(&kind, &registry, &occlusion);
+
+        assert_eq!(faces.len(), voxel::SIDE_COUNT);
+
+        for face in &faces {
+            for v in face.vertices {
+                assert!(vox::math::is_within_cubic_bounds(
+                    (v - Vec3::new(1.0, 2.0, 3.0)).as_ivec3(),
+                    0,
+                    1
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn faces_hidden() {
+        let registry = KindRegistry::default();
+        let mut kind = ChunkKind::default();
+        kind.set((1, 1, 1).into(), 1.into());
+        kind.set((2, 1, 1).into(), 1.into());
+
+        let occlusion = occlusion::faces_occlusion(&kind, &registry);
+        let faces = super::faces(&kind, &registry, &occlusion);
+
+        // The two touching faces are hidden
+        assert_eq!(faces.len(), voxel::SIDE_COUNT * 2 - 2);
+    }
+
+    #[test]
+    fn skirts() {
+        let registry = KindRegistry::default();
+        let mut kind = ChunkKind::default();
+
+        // Voxels away from chunk sides need no skirts
+        kind.set((1, 2, 3).into(), 1.into());
+        let occlusion = occlusion::faces_occlusion(&kind, &registry);
+        let faces = super::faces(&kind, &registry, &occlusion);
+        assert!(super::skirts(&faces, 1.0).is_empty());
+
+        let top = chunk::AXIS_SIZE as i32 - 1;
+        let up = VoxelFace {
+            vertices: super::face_vertices(voxel::Side::Up)
+                .map(|v| (v + IVec3::new(top, 4, 5)).as_vec3()),
+            side: voxel::Side::Up,
+            ao: [0, 1, 2, 3],
+            sway: [0.0; 4],
+            tint: None,
+        };
+
+        let skirts = super::skirts(&[up], 2.0);
+        assert_eq!(skirts.len(), 1);
+
+        let skirt = &skirts[0];
+        assert_eq!(skirt.side, voxel::Side::Right);
+
+        let [v0, v1, v2, _] = skirt.vertices;
+        assert_eq!(
+            (v1 - v0).cross(v2 - v0).normalize(),
+            voxel::Side::Right.normal()
+        );
+
+        for v in skirt.vertices {
+            assert_eq!(v.x, chunk::AXIS_SIZE as f32);
+            assert!(v.y == 5.0 || v.y == 3.0);
+        }
+
+        // Corner voxels get a skirt on each side they touch
+        let mut kind = ChunkKind::default();
+        kind.set(IVec3::ZERO, 1.into());
+        let occlusion = occlusion::faces_occlusion(&kind, &registry);
+        let faces = super::faces(&kind, &registry, &occlusion);
+        let skirts = super::skirts(&faces, 1.0);
+
+        for side in [voxel::Side::Left, voxel::Side::Down, voxel::Side::Back] {
+            assert_eq!(skirts.iter().filter(|skirt| skirt.side == side).count(), 4);
+        }
+        assert_eq!(skirts.len(), 12);
+    }
+
+    #[test]
+    fn dirty_regions() {
+        let regions = |regions: &[usize]| regions.iter().copied().collect::<HashSet<_>>();
+
+        assert_eq!(super::dirty_regions((3, 3, 3).into()), regions(&[0]));
+        assert_eq!(super::dirty_regions((3, 3, 7).into()), regions(&[0, 1]));
+        assert_eq!(
+            super::dirty_regions((8, 3, 3).into()),
+            regions(&[0, REGION_AXIS * REGION_AXIS])
+        );
+        assert_eq!(super::dirty_regions(IVec3::splat(8)).len(), REGION_COUNT);
+
+        // Changes on the border of neighbor chunks only touch the regions next to it
+        assert_eq!(super::dirty_regions((-1, 3, 3).into()), regions(&[0]));
+        assert_eq!(
+            super::dirty_regions((3, 3, chunk::AXIS_SIZE as i32).into()),
+            regions(&[REGION_AXIS - 1])
+        );
+    }
+
+    #[test]
+    fn region_faces() {
+        let registry = KindRegistry::default();
+        let mut kind = ChunkKind::default();
+        kind.set((7, 7, 7).into(), 1.into());
+        kind.set((8, 7, 7).into(), 1.into());
+        kind.set((3, 12, 9).into(), 1.into());
+
+        let occlusion = occlusion::faces_occlusion(&kind, &registry);
+        let faces = super::faces(&kind, &registry, &occlusion);
+
+        let region_faces = (0..REGION_COUNT)
+            .flat_map(|region| super::region_faces(&kind, &registry, &occlusion, region))
+            .collect::<Vec<_>>();
+
+        // Faces between regions are still hidden, and every face belongs to a single region
+        assert_eq!(region_faces.len(), faces.len());
+        assert!(faces.iter().all(|face| region_faces.contains(face)));
+    }
+
+    #[test]
+    fn cross_vertices() {
+        let quads = super::cross_vertices();
+
+        for [v0, v1, v2, v3] in quads {
+            // Each quad cuts the voxel diagonally, from bottom to top
+            assert_eq!(v0.y, 0);
+            assert_eq!(v1.y, 0);
+            assert_eq!(v2.y, 1);
+            assert_eq!(v3.y, 1);
+        }
+
+        // Both windings of each quad are emitted
+        let normal = |[v0, v1, v2, _]: [IVec3; 4]| {
+            (v1 - v0).as_vec3().cross((v2 - v0).as_vec3()).normalize()
+        };
+        assert_eq!(normal(quads[0]), -normal(quads[1]));
+        assert_eq!(normal(quads[2]), -normal(quads[3]));
+    }
+
+    #[test]
+    fn faces_cross() {
+        let registry = KindRegistry::new(vec![voxel::KindDescription {
+            name: "Fern".to_string(),
+            id: 2,
+            color: (0.0, 1.0, 0.0, 1.0),
+            shape: MeshShape::Cross,
+            prop: None,
+            directional: false,
+            light: 0,
+            tick: None,
+            signal: None,
+            toggle: None,
+            hardness: 0.0,
+            tool_tier: 0,
+            block_entity: None,
+            explosive: None,
+            liquid: None,
+            climbable: false,
+            surface: None,
+            foliage: false,
+            tint: None,
+            portal: None,
+        }]);
+
+        let mut kind = ChunkKind::default();
+        kind.set((1, 1, 1).into(), 1.into());
+        kind.set((1, 2, 1).into(), 2.into());
+
+        let occlusion = occlusion::faces_occlusion(&kind, &registry);
+        let faces = super::faces(&kind, &registry, &occlusion);
+
+        // The cross doesn't hide the cube below it
+        assert_eq!(faces.len(), voxel::SIDE_COUNT + 4);
+        assert_eq!(
+            faces
+                .iter()
+                .filter(|face| face.vertices.iter().any(|v| v.y == 3.0))
+                .count(),
+            4
+        );
+    }
+
+    #[test]
+    fn faces_sway() {
+        let foliage = |id: u16, shape| voxel::KindDescription {
+            name: id.to_string(),
+            id,
+            color: (0.0, 1.0, 0.0, 1.0),
+            shape,
+            prop: None,
+            directional: false,
+            light: 0,
+            tick: None,
+            signal: None,
+            toggle: None,
+            hardness: 0.0,
+            tool_tier: 0,
+            block_entity: None,
+            explosive: None,
+            liquid: None,
+            climbable: false,
+            surface: None,
+            foliage: true,
+            tint: None,
+            portal: None,
+        };
+        let registry = KindRegistry::new(vec![
+            foliage(2, MeshShape::Cross),
+            foliage(3, MeshShape::Cube),
+        ]);
+
+        let mut kind = ChunkKind::default();
+        kind.set((1, 1, 1).into(), 1.into());
+        kind.set((1, 2, 1).into(), 2.into());
+        kind.set((5, 5, 5).into(), 3.into());
+
+        let occlusion = occlusion::faces_occlusion(&kind, &registry);
+        let vertices = super::vertices(
+            &super::faces(&kind, &registry, &occlusion),
+            &BiomeBlend::new(&VoxWorld::default(), IVec3::ZERO),
+        );
+
+        for vertex in vertices {
+            // Crosses are rooted to the ground, so only their top sways, while the stone below never does
+            let sway = match vertex.position.as_ivec3() {
+                p if p.y == 3 => 1.0,
+                p if p.x >= 5 => FOLIAGE_SWAY,
+                _ => 0.0,
+            };
+
+            assert_eq!(vertex.sway, sway, "Wrong sway at {}", vertex.position);
+        }
+    }
+
+    #[test]
+    fn partial_shape_faces() {
+        for shape in [
+            MeshShape::Slab,
+            MeshShape::Stairs,
+            MeshShape::Panel,
+            MeshShape::SidePanel,
+            MeshShape::Plate,
+        ] {
+            for (side, vertices) in super::partial_shape_faces(shape) {
+                let [v0, v1, v2, _] = vertices;
+                let normal = (v1 - v0).cross(v2 - v0).normalize();
+
+                assert_eq!(normal, side.normal(), "Wrong winding on {:?}", shape);
+                assert!(vertices
+                    .iter()
+                    .all(|v| v.cmpge(Vec3::ZERO).all() && v.cmple(Vec3::ONE).all()));
+            }
+        }
+
+        assert_eq!(super::partial_shape_faces(MeshShape::Slab).len(), 6);
+        assert_eq!(super::partial_shape_faces(MeshShape::Stairs).len(), 10);
+        assert_eq!(super::partial_shape_faces(MeshShape::Panel).len(), 6);
+    }
+
+    #[test]
+    fn rotate_faces() {
+        for facing in voxel::SIDES {
+            let faces = super::rotate_faces(super::partial_shape_faces(MeshShape::Stairs), facing);
+
+            for (side, vertices) in faces {
+                let [v0, v1, v2, _] = vertices;
+                let normal = (v1 - v0).cross(v2 - v0).normalize();
+
+                assert_eq!(normal, side.normal(), "Wrong winding facing {:?}", facing);
+                assert!(vertices
+                    .iter()
+                    .all(|v| v.cmpge(Vec3::ZERO).all() && v.cmple(Vec3::ONE).all()));
+            }
+        }
+
+        // The full back of stairs facing right is on the left side
+        let faces = super::rotate_faces(
+            super::partial_shape_faces(MeshShape::Stairs),
+            voxel::Side::Right,
+        );
+        assert!(faces
+            .iter()
+            .any(|(side, vertices)| *side == voxel::Side::Left
+                && vertices.iter().all(|v| v.x == 0.0)
+                && vertices.iter().any(|v| v.y == 1.0)
+                && vertices.iter().any(|v| v.z == 1.0)));
+
+        // Thin shapes keep their thickness when rotated
+        let faces = super::rotate_faces(
+            super::partial_shape_faces(MeshShape::Panel),
+            voxel::Side::Right,
+        );
+        assert!(faces
+            .iter()
+            .any(|(side, vertices)| *side == voxel::Side::Right
+                && vertices.iter().all(|v| v.x == super::THIN)));
+    }
+
+    #[test]
+    fn faces_partial() {
+        let registry = KindRegistry::new(vec![voxel::KindDescription {
+            name: "Slab".to_string(),
+            id: 2,
+            color: (1.0, 1.0, 1.0, 1.0),
+            shape: MeshShape::Slab,
+            prop: None,
+            directional: false,
+            light: 0,
+            tick: None,
+            signal: None,
+            toggle: None,
+            hardness: 0.0,
+            tool_tier: 0,
+            block_entity: None,
+            explosive: None,
+            liquid: None,
+            climbable: false,
+            surface: None,
+            foliage: false,
+            tint: None,
+            portal: None,
+        }]);
+
+        let mut kind = ChunkKind::default();
+        kind.set((1, 1, 1).into(), 2.into());
+        kind.set((2, 1, 1).into(), 2.into());
+        kind.set((1, 2, 1).into(), 1.into());
+
+        let occlusion = occlusion::faces_occlusion(&kind, &registry);
+        let faces = super::faces(&kind, &registry, &occlusion);
+
+        // Touching slab sides are hidden, but the slab top isn't hidden by the cube above
+        let slab_faces = faces.iter().filter(|face| face.vertices[0].y < 2.0).count();
+        assert_eq!(slab_faces, 6 * 2 - 2);
+
+        let top = faces
+            .iter()
+            .filter(|face| face.side == voxel::Side::Up && face.vertices[0].y == 1.5)
+            .count();
+        assert_eq!(top, 2);
+    }
+
+    #[test]
+    fn tangent_space() {
+        for side in voxel::SIDES {
+            let vertices = super::face_vertices(side).map(|v| v.as_vec3());
+            let (tangent, bitangent) = super::tangent_space(&vertices);
+
+            assert_eq!(tangent.dot(side.normal()), 0.0);
+            assert_eq!(bitangent.dot(side.normal()), 0.0);
+            assert_eq!(side.normal().cross(tangent) * -1.0, bitangent);
+
+            // Each face maps to the whole texture
+            let uvs = super::face_uvs(&vertices, tangent, bitangent);
+            for corner in [Vec2::ZERO, Vec2::X, Vec2::Y, Vec2::ONE] {
+                assert!(uvs.contains(&corner), "Missing {:?} on {:?}", corner, side);
+            }
+        }
+
+        // Textures are upright on vertical faces
+        let vertices = super::face_vertices(voxel::Side::Front).map(|v| v.as_vec3());
+        let (_, bitangent) = super::tangent_space(&vertices);
+        assert_eq!(bitangent, -Vec3::Y);
+    }
+
+    #[test]
+    fn face_uvs() {
+        // The side of a slab only gets the bottom half of the texture
+        let slab_side = box_face(
+            voxel::Side::Front,
+            Vec3::new(3.0, 5.0, 0.0),
+            Vec3::new(4.0, 5.5, 1.0),
+        );
+        let (tangent, bitangent) = super::tangent_space(&slab_side);
+        let uvs = super::face_uvs(&slab_side, tangent, bitangent);
+
+        assert!(uvs.iter().all(|uv| uv.y >= 0.5 && uv.y <= 1.0));
+        assert!(uvs.iter().all(|uv| uv.x >= 0.0 && uv.x <= 1.0));
+
+        // Cross quads are diagonal, but still maps to the whole texture
+        for quad in super::cross_vertices() {
+            let vertices = quad.map(|v| v.as_vec3());
+            let (tangent, bitangent) = super::tangent_space(&vertices);
+            let uvs = super::face_uvs(&vertices, tangent, bitangent);
+
+            for uv in uvs {
+                assert!(uv.abs_diff_eq(uv.round(), 0.0001), "{:?}", uv);
+                assert!(uv.cmpge(Vec2::ZERO).all() && uv.cmple(Vec2::ONE).all());
+            }
+        }
+    }
+
+    #[test]
+    fn vertex_ao() {
+        let registry = KindRegistry::default();
+        let mut kind = ChunkKind::default();
+        let voxel = IVec3::new(5, 5, 5);
+        kind.set(voxel, 1.into());
+
+        // Nothing around, so no vertex is occluded
+        for side in voxel::SIDES {
+            for v in super::face_vertices(side) {
+                assert_eq!(super::vertex_ao(&kind, &registry, voxel, side, v), MAX_AO);
+            }
+        }
+
+        // A voxel on top of the right neighbor occludes the up face vertices on the right edge
+        kind.set(voxel + IVec3::new(1, 1, 0), 1.into());
+
+        let up = voxel::Side::Up;
+        assert_eq!(
+            super::vertex_ao(&kind, &registry, voxel, up, (1, 1, 0).into()),
+            2
+        );
+        assert_eq!(
+            super::vertex_ao(&kind, &registry, voxel, up, (1, 1, 1).into()),
+            2
+        );
+        assert_eq!(
+            super::vertex_ao(&kind, &registry, voxel, up, (0, 1, 0).into()),
+            MAX_AO
+        );
+
+        // Two sides occluding the vertex makes it fully occluded, no matter the corner
+        kind.set(voxel + IVec3::new(0, 1, 1), 1.into());
+        assert_eq!(
+            super::vertex_ao(&kind, &registry, voxel, up, (1, 1, 1).into()),
+            0
+        );
+
+        // Only the corner
+        kind.set(voxel + IVec3::new(-1, 1, -1), 1.into());
+        assert_eq!(
+            super::vertex_ao(&kind, &registry, voxel, up, (0, 1, 0).into()),
+            2
+        );
+    }
+
+    #[test]
+    fn vertex_ao_neighborhood() {
+        let registry = KindRegistry::default();
+        let mut kind = ChunkKind::default();
+        let voxel = IVec3::new(chunk::AXIS_ENDING as i32, 5, 5);
+        kind.set(voxel, 1.into());
+
+        let up = voxel::Side::Up;
+        assert_eq!(
+            super::vertex_ao(&kind, &registry, voxel, up, (1, 1, 0).into()),
+            MAX_AO
+        );
+
+        let mut neighbor = ChunkKind::default();
+        neighbor.set((0, 6, 5).into(), 1.into());
+        kind.neighborhood.set(voxel::Side::Right, &neighbor);
+
+        assert_eq!(
+            super::vertex_ao(&kind, &registry, voxel, up, (1, 1, 0).into()),
+            2
+        );
+        assert_eq!(
+            super::vertex_ao(&kind, &registry, voxel, up, (0, 1, 0).into()),
+            MAX_AO
+        );
+    }
+
+    #[test]
+    fn mesh_ao_flip() {
+        let vertex = |ao| VoxelVertex {
+            ao,
+            ..Default::default()
+        };
+
+        let mesh = super::mesh(&[vertex(1.0), vertex(1.0), vertex(1.0), vertex(1.0)]);
+        assert!(matches!(mesh.indices(), Some(Indices::U32(i)) if i == &[0, 1, 2, 2, 3, 0]));
+
+        // First vertex is occluded, so faces must be split along the other diagonal
+        let mesh = super::mesh(&[vertex(0.0), vertex(1.0), vertex(1.0), vertex(1.0)]);
+        assert!(matches!(mesh.indices(), Some(Indices::U32(i)) if i == &[1, 2, 3, 3, 0, 1]));
+    }
+
+    #[test]
+    fn mesh() {
+        let registry = KindRegistry::default();
+        let mut kind = ChunkKind::default();
+        kind.set((0, 0, 0).into(), 1.into());
+
+        let occlusion = occlusion::faces_occlusion(&kind, &registry);
+        let vertices = super::vertices(
+            &super::faces(&kind, &registry, &occlusion),
+            &BiomeBlend::new(&VoxWorld::default(), IVec3::ZERO),
+        );
+
+        assert_eq!(vertices.len(), voxel::SIDE_COUNT * 4);
+
+        let mesh = super::mesh(&vertices);
+
+        assert_eq!(mesh.count_vertices(), voxel::SIDE_COUNT * 4);
+        assert_eq!(mesh.indices().unwrap().len(), voxel::SIDE_COUNT * 6);
+    }
+
+    #[test]
+    fn fill_mesh() {
+        let registry = KindRegistry::default();
+        let mut kind = ChunkKind::default();
+        kind.set((0, 0, 0).into(), 1.into());
+        kind.set((2, 0, 0).into(), 1.into());
+
+        let occlusion = occlusion::faces_occlusion(&kind, &registry);
+        let mut mesh = super::mesh(&super::vertices(
+            &super::faces(&kind, &registry, &occlusion),
+            &BiomeBlend::new(&VoxWorld::default(), IVec3::ZERO),
+        ));
+
+        let positions = |mesh: &Mesh| match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+            Some(VertexAttributeValues::Float32x3(buffer)) => (buffer.as_ptr(), buffer.capacity()),
+            _ => panic!("Mesh must have positions"),
+        };
+        let (ptr, capacity) = positions(&mesh);
+
+        // Re-meshing with fewer voxels keeps the same buffers
+        kind.set((2, 0, 0).into(), 0.into());
+        let occlusion = occlusion::faces_occlusion(&kind, &registry);
+        let vertices = super::vertices(
+            &super::faces(&kind, &registry, &occlusion),
+            &BiomeBlend::new(&VoxWorld::default(), IVec3::ZERO),
+        );
+        super::fill_mesh(&mut mesh, &vertices);
+
+        assert_eq!(positions(&mesh), (ptr, capacity));
+        assert_eq!(mesh.count_vertices(), voxel::SIDE_COUNT * 4);
+        assert_eq!(mesh.indices().unwrap().len(), voxel::SIDE_COUNT * 6);
+
+        let expected = super::mesh(&vertices);
+        assert_eq!(
+            mesh.get_vertex_buffer_data(),
+            expected.get_vertex_buffer_data()
+        );
+        assert_eq!(
+            mesh.get_index_buffer_bytes(),
+            expected.get_index_buffer_bytes()
+        );
+    }
+}
@@ -1,6 +1,10 @@
+use bevy::prelude::IVec3;
 use serde::Deserialize;
 use serde::Serialize;
-use vox::*;
+use vox::{
+    chunk::{self, ChunkKind, ChunkStorage},
+    voxel::{self, KindRegistry, MeshShape},
+};
 
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
 pub struct FacesOcclusion(u8);
@@ -49,6 +53,147 @@ impl From<[bool; 6]> for FacesOcclusion {
 
 impl vox::chunk::ChunkStorageType for FacesOcclusion {}
 
+pub type ChunkFacesOcclusion = ChunkStorage<FacesOcclusion>;
+
+/// How much of a voxel side is covered by the faces of a shape.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Coverage {
+    /// No face touches the side.
+    Empty,
+    Partial,
+    Full,
+}
+
+/**
+  Coverage of the given side of a shape. Each row of the table is indexed by [`voxel::Side`].
+*/
+pub fn side_coverage(shape: MeshShape, side: voxel::Side) -> Coverage {
+    use Coverage::*;
+
+    let table = match shape {
+        MeshShape::Cube => [Full; voxel::SIDE_COUNT],
+        MeshShape::Cross => [Empty; voxel::SIDE_COUNT],
+        // Right, Left, Up, Down, Front, Back
+        MeshShape::Slab => [Partial, Partial, Empty, Full, Partial, Partial],
+        MeshShape::Stairs => [Partial, Partial, Partial, Full, Partial, Full],
+        MeshShape::Panel => [Partial, Partial, Partial, Partial, Empty, Full],
+        MeshShape::SidePanel => [Empty, Full, Partial, Partial, Partial, Partial],
+        MeshShape::Plate => [Partial, Partial, Empty, Full, Partial, Partial],
+    };
+
+    table[side as usize]
+}
+
+/**
+  Coverage of the given side of a shape rotated to the given facing.
+*/
+pub fn facing_coverage(shape: MeshShape, facing: voxel::Side, side: voxel::Side) -> Coverage {
+    side_coverage(shape, side.unrotated(facing))
+}
+
+/**
+  Whether the face on the given side of a shape is hidden by the neighbor shape on that side.
+  Each shape is paired with its facing.
+
+  Partial faces are only hidden by the same shape with the same facing, which has the same profile on the opposite side.
+*/
+pub fn is_side_hidden(
+    (shape, facing): (MeshShape, voxel::Side),
+    side: voxel::Side,
+    neighbor: Option<(MeshShape, voxel::Side)>,
+) -> bool {
+    let (neighbor, neighbor_facing) = match neighbor {
+        Some(neighbor) => neighbor,
+        None => return false,
+    };
+
+    match (
+        facing_coverage(shape, facing, side),
+        facing_coverage(neighbor, neighbor_facing, side.opposite()),
+    ) {
+        (Coverage::Empty, _) => false,
+        (_, Coverage::Full) => true,
+        (Coverage::Partial, Coverage::Partial) => shape == neighbor && facing == neighbor_facing,
+        _ => false,
+    }
+}
+
+/**
+  Computes which faces of each voxel are hidden by their neighbors, based on the coverage of each shape side.
+
+  Voxels on chunk boundaries uses the chunk neighborhood. If a neighbor chunk isn't loaded,
+  faces facing it are considered visible. Props are rendered by instancing and crosses never
+  hides, nor are hidden, so both are fully occluded.
+*/
+pub fn faces_occlusion(kind: &ChunkKind, registry: &KindRegistry) -> ChunkFacesOcclusion {
+    let mut occlusion = ChunkFacesOcclusion::default();
+    update_faces_occlusion(&mut occlusion, kind, registry, chunk::voxels());
+
+    occlusion
+}
+
+/**
+  Recomputes the faces occlusion of the given voxels only, keeping the others as they are. This way the
+  occlusion of a chunk can be kept around and patched with [`affected_voxels`] when some voxels change.
+*/
+pub fn update_faces_occlusion(
+    occlusion: &mut ChunkFacesOcclusion,
+    kind: &ChunkKind,
+    registry: &KindRegistry,
+    voxels: impl IntoIterator<Item = IVec3>,
+) {
+    for voxel in voxels {
+        occlusion.set(voxel, voxel_faces_occlusion(kind, registry, voxel));
+    }
+}
+
+/**
+  Voxels whose faces occlusion may change when the given voxel changes, which are the voxel itself and its
+  direct neighbors inside the chunk. The voxel may be out of the chunk bounds, when it changed on the border
+  of a neighbor chunk.
+*/
+pub fn affected_voxels(voxel: IVec3) -> impl Iterator<Item = IVec3> {
+    std::iter::once(voxel)
+        .chain(voxel::SIDES.into_iter().map(move |side| voxel + side.dir()))
+        .filter(|voxel| chunk::is_within_bounds(*voxel))
+}
+
+fn voxel_faces_occlusion(
+    kind: &ChunkKind,
+    registry: &KindRegistry,
+    voxel: IVec3,
+) -> FacesOcclusion {
+    let mut faces = FacesOcclusion::default();
+
+    let voxel_kind = kind.get(voxel);
+
+    match registry.mesh_shape(voxel_kind) {
+        None | Some(MeshShape::Cross) => faces.set_all(true),
+        Some(shape) => {
+            for side in voxel::SIDES {
+                let neighbor = voxel + side.dir();
+
+                let neighbor_kind = if chunk::is_within_bounds(neighbor) {
+                    Some(kind.get(neighbor))
+                } else {
+                    let (_, neighbor_voxel) = chunk::overlap_voxel(neighbor);
+                    kind.neighborhood.get(side, neighbor_voxel)
+                };
+
+                let neighbor_shape = neighbor_kind
+                    .and_then(|k| registry.mesh_shape(k).map(|shape| (shape, k.facing())));
+
+                faces.set(
+                    side,
+                    is_side_hidden((shape, voxel_kind.facing()), side, neighbor_shape),
+                );
+            }
+        }
+    }
+
+    faces
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -91,4 +236,281 @@ mod tests {
             assert!(!occlusion.is_occluded(side));
         }
     }
+
+    #[test]
+    fn faces_occlusion_empty() {
+        let kind = ChunkKind::default();
+        let occlusion = super::faces_occlusion(&kind, &KindRegistry::default());
+
+        assert!(occlusion.iter().all(|faces| faces.is_fully_occluded()));
+    }
+
+    #[test]
+    fn faces_occlusion_single() {
+        let mut kind = ChunkKind::default();
+        let center = IVec3::new(5, 5, 5);
+        kind.set(center, 1.into());
+
+        let occlusion = super::faces_occlusion(&kind, &KindRegistry::default());
+
+        for side in voxel::SIDES {
+            assert!(!occlusion.get(center).is_occluded(side));
+        }
+    }
+
+    #[test]
+    fn faces_occlusion_neighbors() {
+        let mut kind = ChunkKind::default();
+        let center = IVec3::new(5, 5, 5);
+        kind.set(center, 1.into());
+        kind.set(center + IVec3::X, 1.into());
+        kind.set(center + IVec3::Y, 1.into());
+
+        let occlusion = super::faces_occlusion(&kind, &KindRegistry::default());
+
+        let faces = occlusion.get(center);
+        assert!(faces.is_occluded(voxel::Side::Right));
+        assert!(faces.is_occluded(voxel::Side::Up));
+        assert!(!faces.is_occluded(voxel::Side::Left));
+        assert!(!faces.is_occluded(voxel::Side::Down));
+        assert!(!faces.is_occluded(voxel::Side::Front));
+        assert!(!faces.is_occluded(voxel::Side::Back));
+
+        assert!(occlusion
+            .get(center + IVec3::X)
+            .is_occluded(voxel::Side::Left));
+        assert!(occlusion
+            .get(center + IVec3::Y)
+            .is_occluded(voxel::Side::Down));
+    }
+
+    #[test]
+    fn update_faces_occlusion() {
+        let registry = KindRegistry::default();
+        let mut kind = ChunkKind::default();
+        kind.set((5, 5, 5).into(), 1.into());
+        kind.set((6, 5, 5).into(), 1.into());
+
+        let mut occlusion = super::faces_occlusion(&kind, &registry);
+
+        // Only the changed voxel and its neighbors need to be recomputed
+        let changed = IVec3::new(5, 6, 5);
+        kind.set(changed, 1.into());
+        super::update_faces_occlusion(
+            &mut occlusion,
+            &kind,
+            &registry,
+            super::affected_voxels(changed),
+        );
+        assert!(occlusion
+            .iter()
+            .eq(super::faces_occlusion(&kind, &registry).iter()));
+        assert!(occlusion.get((5, 5, 5).into()).is_occluded(voxel::Side::Up));
+
+        kind.set((5, 5, 5).into(), 0.into());
+        super::update_faces_occlusion(
+            &mut occlusion,
+            &kind,
+            &registry,
+            super::affected_voxels((5, 5, 5).into()),
+        );
+        assert!(occlusion
+            .iter()
+            .eq(super::faces_occlusion(&kind, &registry).iter()));
+    }
+
+    #[test]
+    fn affected_voxels() {
+        assert_eq!(super::affected_voxels(IVec3::splat(5)).count(), 7);
+        assert_eq!(super::affected_voxels(IVec3::ZERO).count(), 4);
+
+        // Changes on the border of a neighbor chunk only affect the voxel next to it
+        assert_eq!(
+            super::affected_voxels((-1, 3, 3).into()).collect::<Vec<_>>(),
+            vec![IVec3::new(0, 3, 3)]
+        );
+    }
+
+    #[test]
+    fn faces_occlusion_neighborhood() {
+        let mut kind = ChunkKind::default();
+        let border = IVec3::new(chunk::AXIS_ENDING as i32, 0, 3);
+        kind.set(border, 1.into());
+
+        let occlusion = super::faces_occlusion(&kind, &KindRegistry::default());
+        assert!(!occlusion.get(border).is_occluded(voxel::Side::Right));
+
+        let mut neighbor = ChunkKind::default();
+        neighbor.set((0, 0, 3).into(), 1.into());
+        kind.neighborhood.set(voxel::Side::Right, &neighbor);
+
+        let occlusion = super::faces_occlusion(&kind, &KindRegistry::default());
+        assert!(occlusion.get(border).is_occluded(voxel::Side::Right));
+        assert!(!occlusion.get(border).is_occluded(voxel::Side::Down));
+    }
+
+    #[test]
+    fn faces_occlusion_props() {
+        let registry = KindRegistry::new(vec![voxel::KindDescription {
+            name: "Flower".to_string(),
+            id: 2,
+            color: (1.0, 0.0, 0.0, 1.0),
+            shape: voxel::MeshShape::Cube,
+            prop: Some(voxel::PropShape::Billboard),
+            directional: false,
+            light: 0,
+            tick: None,
+            signal: None,
+            toggle: None,
+            hardness: 0.0,
+            tool_tier: 0,
+            block_entity: None,
+            explosive: None,
+            liquid: None,
+            climbable: false,
+            surface: None,
+            foliage: false,
+            tint: None,
+            portal: None,
+        }]);
+
+        let mut kind = ChunkKind::default();
+        let center = IVec3::new(5, 5, 5);
+        kind.set(center, 1.into());
+        kind.set(center + IVec3::Y, 2.into());
+
+        let occlusion = super::faces_occlusion(&kind, &registry);
+
+        // Props are never meshed and doesn't hide the voxel below them
+        assert!(occlusion.get(center + IVec3::Y).is_fully_occluded());
+        assert!(!occlusion.get(center).is_occluded(voxel::Side::Up));
+    }
+
+    #[test]
+    fn is_side_hidden() {
+        use voxel::Side;
+
+        // Cubes hides anything, but are only hidden by full sides
+        assert!(super::is_side_hidden(
+            (MeshShape::Slab, Side::Front),
+            Side::Right,
+            Some((MeshShape::Cube, Side::Front))
+        ));
+        assert!(!super::is_side_hidden(
+            (MeshShape::Cube, Side::Front),
+            Side::Right,
+            Some((MeshShape::Slab, Side::Front))
+        ));
+        assert!(super::is_side_hidden(
+            (MeshShape::Cube, Side::Front),
+            Side::Up,
+            Some((MeshShape::Slab, Side::Front))
+        ));
+
+        // Partial sides of the same shape matches
+        assert!(super::is_side_hidden(
+            (MeshShape::Slab, Side::Front),
+            Side::Left,
+            Some((MeshShape::Slab, Side::Front))
+        ));
+        assert!(!super::is_side_hidden(
+            (MeshShape::Slab, Side::Front),
+            Side::Left,
+            Some((MeshShape::Stairs, Side::Front))
+        ));
+
+        // The top of a slab is never on the voxel boundary
+        assert!(!super::is_side_hidden(
+            (MeshShape::Slab, Side::Front),
+            Side::Up,
+            Some((MeshShape::Cube, Side::Front))
+        ));
+
+        assert!(!super::is_side_hidden(
+            (MeshShape::Cube, Side::Front),
+            Side::Up,
+            Some((MeshShape::Cross, Side::Front))
+        ));
+        assert!(!super::is_side_hidden(
+            (MeshShape::Cube, Side::Front),
+            Side::Up,
+            None
+        ));
+
+        // Stairs facing right has its full back side on the left
+        assert!(super::is_side_hidden(
+            (MeshShape::Slab, Side::Front),
+            Side::Right,
+            Some((MeshShape::Stairs, Side::Right))
+        ));
+        assert!(!super::is_side_hidden(
+            (MeshShape::Slab, Side::Front),
+            Side::Right,
+            Some((MeshShape::Stairs, Side::Front))
+        ));
+
+        // Partial sides only matches when both shapes faces the same way
+        assert!(super::is_side_hidden(
+            (MeshShape::Stairs, Side::Back),
+            Side::Right,
+            Some((MeshShape::Stairs, Side::Back))
+        ));
+        assert!(!super::is_side_hidden(
+            (MeshShape::Stairs, Side::Back),
+            Side::Right,
+            Some((MeshShape::Stairs, Side::Front))
+        ));
+    }
+
+    #[test]
+    fn faces_occlusion_partial() {
+        let shape = |id, shape| voxel::KindDescription {
+            name: format!("{:?}", shape),
+            id,
+            color: (1.0, 1.0, 1.0, 1.0),
+            shape,
+            prop: None,
+            directional: false,
+            light: 0,
+            tick: None,
+            signal: None,
+            toggle: None,
+            hardness: 0.0,
+            tool_tier: 0,
+            block_entity: None,
+            explosive: None,
+            liquid: None,
+            climbable: false,
+            surface: None,
+            foliage: false,
+            tint: None,
+            portal: None,
+        };
+        let registry =
+            KindRegistry::new(vec![shape(1, MeshShape::Cube), shape(2, MeshShape::Slab)]);
+
+        let mut kind = ChunkKind::default();
+        let slab = IVec3::new(5, 5, 5);
+        kind.set(slab, 2.into());
+        kind.set(slab + IVec3::X, 1.into());
+        kind.set(slab - IVec3::X, 2.into());
+        kind.set(slab + IVec3::Y, 1.into());
+
+        let occlusion = super::faces_occlusion(&kind, &registry);
+
+        let faces = occlusion.get(slab);
+        assert!(faces.is_occluded(voxel::Side::Right));
+        assert!(faces.is_occluded(voxel::Side::Left));
+        assert!(!faces.is_occluded(voxel::Side::Up));
+        assert!(!faces.is_occluded(voxel::Side::Front));
+
+        // The slab doesn't fully cover the cube side
+        assert!(!occlusion
+            .get(slab + IVec3::X)
+            .is_occluded(voxel::Side::Left));
+        // Neither is the bottom of the cube above it, since the slab top is below the voxel boundary
+        assert!(!occlusion
+            .get(slab + IVec3::Y)
+            .is_occluded(voxel::Side::Down));
+    }
 }
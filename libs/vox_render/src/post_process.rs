@@ -0,0 +1,413 @@
+use bevy::{
+    ecs::system::{lifetimeless::SRes, SystemParamItem},
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        camera::{Camera3d, RenderTarget},
+        render_asset::{PrepareAssetError, RenderAsset, RenderAssets},
+        render_resource::{
+            std140::{AsStd140, Std140},
+            BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+            BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, Buffer,
+            BufferBindingType, BufferInitDescriptor, BufferSize, BufferUsages, Extent3d,
+            SamplerBindingType, ShaderStages, TextureDescriptor, TextureDimension, TextureFormat,
+            TextureSampleType, TextureUsages, TextureViewDimension,
+        },
+        renderer::RenderDevice,
+        texture::BevyDefault,
+    },
+    sprite::{Material2d, Material2dPipeline, Material2dPlugin, MaterialMesh2dBundle},
+    window::WindowResized,
+};
+
+use crate::RenderSettings;
+
+pub const POST_PROCESS_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 4_861_273_905_118_342_770);
+
+/**
+  Curve mapping the colors of the frame to the colors shown on screen. The PBR pipeline already maps lit
+  colors into displayable ones, so this is applied on top of it, after the exposure.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tonemapping {
+    None,
+    Reinhard,
+    Aces,
+}
+
+/// Glow around the brightest parts of the frame, like light emitting kinds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bloom {
+    /// Luminance, from 0 to 1, above which pixels glow.
+    pub threshold: f32,
+    pub intensity: f32,
+    /// How far, in pixels, the glow spreads.
+    pub radius: f32,
+}
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Self {
+            threshold: 0.8,
+            intensity: 0.6,
+            radius: 12.0,
+        }
+    }
+}
+
+/// Darkening of the frame corners.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vignette {
+    /// How dark the corners get, from 0 to 1.
+    pub strength: f32,
+    /// Distance from the center, relative to the corners, where the darkening starts.
+    pub radius: f32,
+}
+
+impl Default for Vignette {
+    fn default() -> Self {
+        Self {
+            strength: 0.35,
+            radius: 0.6,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PostProcessSettings {
+    /// Multiplies the frame colors before they're tonemapped.
+    pub exposure: f32,
+    pub tonemapping: Tonemapping,
+    pub bloom: Option<Bloom>,
+    pub vignette: Option<Vignette>,
+}
+
+impl Default for PostProcessSettings {
+    fn default() -> Self {
+        Self {
+            exposure: 1.0,
+            tonemapping: Tonemapping::None,
+            bloom: Some(Bloom::default()),
+            vignette: Some(Vignette::default()),
+        }
+    }
+}
+
+/// Uniform of [`PostProcessMaterial`]. Effects which are off have no intensity or strength.
+#[derive(Debug, Clone, Copy, Default, PartialEq, AsStd140)]
+pub struct PostProcessUniform {
+    pub exposure: f32,
+    pub tonemapping: u32,
+    pub bloom_threshold: f32,
+    pub bloom_intensity: f32,
+    pub bloom_radius: f32,
+    pub vignette_strength: f32,
+    pub vignette_radius: f32,
+}
+
+impl From<&PostProcessSettings> for PostProcessUniform {
+    fn from(settings: &PostProcessSettings) -> Self {
+        let bloom = settings.bloom.unwrap_or(Bloom {
+            intensity: 0.0,
+            ..Default::default()
+        });
+        let vignette = settings.vignette.unwrap_or(Vignette {
+            strength: 0.0,
+            ..Default::default()
+        });
+
+        Self {
+            exposure: settings.exposure,
+            // Must match the constants on post_process.wgsl
+            tonemapping: match settings.tonemapping {
+                Tonemapping::None => 0,
+                Tonemapping::Reinhard => 1,
+                Tonemapping::Aces => 2,
+            },
+            bloom_threshold: bloom.threshold,
+            bloom_intensity: bloom.intensity,
+            bloom_radius: bloom.radius,
+            vignette_strength: vignette.strength,
+            vignette_radius: vignette.radius,
+        }
+    }
+}
+
+/// Material of the fullscreen quad showing the `frame` rendered by the 3d camera, with the post processing.
+#[derive(Debug, Clone, TypeUuid)]
+#[uuid = "8d2b6f41-7c3e-4a5f-b1d9-0e6a3c9f2d74"]
+pub struct PostProcessMaterial {
+    pub frame: Handle<Image>,
+    pub settings: PostProcessSettings,
+}
+
+#[derive(Clone)]
+pub struct GpuPostProcessMaterial {
+    _buffer: Buffer,
+    bind_group: BindGroup,
+}
+
+impl RenderAsset for PostProcessMaterial {
+    type ExtractedAsset = PostProcessMaterial;
+    type PreparedAsset = GpuPostProcessMaterial;
+    type Param = (
+        SRes<RenderDevice>,
+        SRes<Material2dPipeline<Self>>,
+        SRes<RenderAssets<Image>>,
+    );
+
+    fn extract_asset(&self) -> Self::ExtractedAsset {
+        self.clone()
+    }
+
+    fn prepare_asset(
+        material: Self::ExtractedAsset,
+        (render_device, material_pipeline, gpu_images): &mut SystemParamItem<Self::Param>,
+    ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>> {
+        let frame = match gpu_images.get(&material.frame) {
+            Some(frame) => frame,
+            None => return Err(PrepareAssetError::RetryNextUpdate(material)),
+        };
+
+        let uniform = PostProcessUniform::from(&material.settings);
+        let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            contents: uniform.as_std140().as_bytes(),
+            label: Some("post_process_material_buffer"),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&frame.texture_view),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::Sampler(&frame.sampler),
+                },
+            ],
+            label: Some("post_process_material_bind_group"),
+            layout: &material_pipeline.material2d_layout,
+        });
+
+        Ok(GpuPostProcessMaterial {
+            _buffer: buffer,
+            bind_group,
+        })
+    }
+}
+
+impl Material2d for PostProcessMaterial {
+    fn fragment_shader(_: &AssetServer) -> Option<Handle<Shader>> {
+        Some(POST_PROCESS_SHADER_HANDLE.typed())
+    }
+
+    fn bind_group(material: &<Self as RenderAsset>::PreparedAsset) -> &BindGroup {
+        &material.bind_group
+    }
+
+    fn bind_group_layout(render_device: &RenderDevice) -> BindGroupLayout {
+        render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(
+                            PostProcessUniform::std140_size_static() as u64
+                        ),
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("post_process_material_layout"),
+        })
+    }
+}
+
+/**
+  Frame the 3d camera renders to, instead of the window, and the fullscreen quad which shows it on the window
+  with the post processing.
+*/
+struct PostProcessTarget {
+    frame: Handle<Image>,
+    material: Handle<PostProcessMaterial>,
+    quad: Handle<Mesh>,
+}
+
+/**
+  Renders the 3d camera into a frame image, which a 2d camera then shows on the window through
+  [`PostProcessMaterial`]. Only added when [`RenderSettings::post_process`] is set.
+*/
+pub struct PostProcessPlugin;
+
+impl Plugin for PostProcessPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(Material2dPlugin::<PostProcessMaterial>::default())
+            .add_startup_system(setup_post_process)
+            .add_system(render_to_frame)
+            .add_system(resize_frame)
+            .add_system(update_settings);
+    }
+}
+
+fn frame_size(window: &Window) -> Extent3d {
+    Extent3d {
+        width: window.physical_width().max(1),
+        height: window.physical_height().max(1),
+        ..Default::default()
+    }
+}
+
+fn setup_post_process(
+    mut commands: Commands,
+    settings: Res<RenderSettings>,
+    windows: Res<Windows>,
+    mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<PostProcessMaterial>>,
+) {
+    let window = windows.primary();
+    let size = frame_size(window);
+
+    let mut image = Image {
+        texture_descriptor: TextureDescriptor {
+            label: Some("post_process_frame"),
+            size,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::bevy_default(),
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_DST
+                | TextureUsages::RENDER_ATTACHMENT,
+        },
+        ..Default::default()
+    };
+    image.resize(size);
+
+    let frame = images.add(image);
+    let quad = meshes.add(Mesh::from(shape::Quad::new(Vec2::new(
+        window.width(),
+        window.height(),
+    ))));
+    let material = materials.add(PostProcessMaterial {
+        frame: frame.clone(),
+        settings: settings.post_process.unwrap_or_default(),
+    });
+
+    commands.spawn_bundle(OrthographicCameraBundle::new_2d());
+    commands.spawn_bundle(MaterialMesh2dBundle {
+        mesh: quad.clone().into(),
+        material: material.clone(),
+        ..Default::default()
+    });
+
+    commands.insert_resource(PostProcessTarget {
+        frame,
+        material,
+        quad,
+    });
+}
+
+/// Points 3d cameras to the frame, including ones spawned later.
+fn render_to_frame(
+    target: Res<PostProcessTarget>,
+    mut q: Query<&mut Camera, (With<Camera3d>, Added<Camera>)>,
+) {
+    for mut camera in q.iter_mut() {
+        camera.target = RenderTarget::Image(target.frame.clone());
+    }
+}
+
+/// Keeps the frame and the quad showing it the same size as the window.
+fn resize_frame(
+    windows: Res<Windows>,
+    target: Res<PostProcessTarget>,
+    mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut reader: EventReader<WindowResized>,
+) {
+    let window = match reader.iter().last() {
+        Some(resized) => match windows.get(resized.id) {
+            Some(window) if window.id() == windows.primary().id() => window,
+            _ => return,
+        },
+        None => return,
+    };
+
+    if let Some(image) = images.get_mut(&target.frame) {
+        image.resize(frame_size(window));
+    }
+
+    if let Some(mesh) = meshes.get_mut(&target.quad) {
+        *mesh = Mesh::from(shape::Quad::new(Vec2::new(window.width(), window.height())));
+    }
+}
+
+fn update_settings(
+    settings: Res<RenderSettings>,
+    target: Res<PostProcessTarget>,
+    mut materials: ResMut<Assets<PostProcessMaterial>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    if let Some(material) = materials.get_mut(&target.material) {
+        material.settings = settings.post_process.unwrap_or_default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uniform() {
+        let settings = PostProcessSettings {
+            exposure: 1.5,
+            tonemapping: Tonemapping::Aces,
+            ..Default::default()
+        };
+
+        let uniform = PostProcessUniform::from(&settings);
+        assert_eq!(uniform.exposure, 1.5);
+        assert_eq!(uniform.tonemapping, 2);
+        assert_eq!(uniform.bloom_intensity, Bloom::default().intensity);
+        assert_eq!(uniform.vignette_strength, Vignette::default().strength);
+
+        // Effects which are off don't do anything
+        let uniform = PostProcessUniform::from(&PostProcessSettings {
+            bloom: None,
+            vignette: None,
+            ..settings
+        });
+        assert_eq!(uniform.bloom_intensity, 0.0);
+        assert_eq!(uniform.vignette_strength, 0.0);
+    }
+}
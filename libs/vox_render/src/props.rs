@@ -0,0 +1,572 @@
+use bevy::{
+    core_pipeline::Opaque3d,
+    ecs::system::{
+        lifetimeless::{Read, SQuery, SRes},
+        SystemParamItem,
+    },
+    pbr::{MeshPipeline, MeshPipelineKey, MeshUniform, SetMeshBindGroup, SetMeshViewBindGroup},
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        mesh::{GpuBufferInfo, Indices, MeshVertexBufferLayout},
+        render_asset::RenderAssets,
+        render_component::{ExtractComponent, ExtractComponentPlugin},
+        render_phase::{
+            AddRenderCommand, DrawFunctions, EntityRenderCommand, RenderCommandResult, RenderPhase,
+            SetItemPipeline, TrackedRenderPass,
+        },
+        render_resource::{
+            std140::AsStd140, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+            BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, BufferBindingType,
+            BufferInitDescriptor, BufferSize, BufferUsages, PipelineCache, PrimitiveTopology,
+            RenderPipelineDescriptor, ShaderStages, SpecializedMeshPipeline,
+            SpecializedMeshPipelineError, SpecializedMeshPipelines, VertexAttribute,
+            VertexBufferLayout, VertexFormat, VertexStepMode,
+        },
+        renderer::RenderDevice,
+        view::{ExtractedView, Msaa},
+        RenderApp, RenderStage,
+    },
+    utils::HashMap,
+};
+use bytemuck::{Pod, Zeroable};
+use vox::{
+    chunk::{self, ChunkKind},
+    voxel::{KindRegistry, PropShape},
+};
+
+use crate::wind::{WindBuffer, WindUniform};
+
+pub const PROPS_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 12_904_175_336_281_640_187);
+
+/// Half width of billboard quads. Slightly less than half a voxel, so props don't touch their neighbors.
+const BILLBOARD_HALF_WIDTH: f32 = 0.45;
+const POST_HALF_WIDTH: f32 = 0.0625;
+const POST_HEIGHT: f32 = 0.625;
+
+/**
+  Per instance data of a prop, uploaded as a vertex buffer. Position is relative to the chunk.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+#[repr(C)]
+pub struct PropInstance {
+    pub position: Vec3,
+    pub scale: f32,
+    pub color: [f32; 4],
+    /// How much the top of the prop sways in the wind. Its bottom is rooted to the ground.
+    pub sway: f32,
+}
+
+/**
+  All props of a single shape inside a chunk. Entities holding it are drawn once, with one instance per prop,
+  using the shape mesh in their `Handle<Mesh>`.
+*/
+#[derive(Component, Debug, Default, Clone)]
+pub struct PropInstances(pub Vec<PropInstance>);
+
+impl ExtractComponent for PropInstances {
+    type Query = &'static PropInstances;
+    type Filter = ();
+
+    fn extract_component(item: bevy::ecs::query::QueryItem<Self::Query>) -> Self {
+        item.clone()
+    }
+}
+
+/// Shared mesh of each prop shape.
+pub struct PropMeshes(HashMap<PropShape, Handle<Mesh>>);
+
+impl PropMeshes {
+    pub fn get(&self, shape: PropShape) -> Handle<Mesh> {
+        self.0[&shape].clone()
+    }
+}
+
+impl FromWorld for PropMeshes {
+    fn from_world(world: &mut World) -> Self {
+        let mut meshes = world
+            .get_resource_mut::<Assets<Mesh>>()
+            .expect("RenderPlugin must be added before VoxRenderPlugin");
+
+        let mut map = HashMap::default();
+        map.insert(PropShape::Billboard, meshes.add(billboard_mesh()));
+        map.insert(PropShape::Post, meshes.add(post_mesh()));
+
+        Self(map)
+    }
+}
+
+/**
+  Two quads crossing each other diagonally, standing on the origin.
+*/
+pub fn billboard_mesh() -> Mesh {
+    let w = BILLBOARD_HALF_WIDTH;
+    let quads = [
+        ([(-w, -w), (w, w)], Vec3::new(1.0, 0.0, -1.0).normalize()),
+        ([(-w, w), (w, -w)], Vec3::new(1.0, 0.0, 1.0).normalize()),
+    ];
+
+    let mut positions = vec![];
+    let mut normals = vec![];
+    let mut uvs = vec![];
+    let mut indices = vec![];
+
+    for ([(x0, z0), (x1, z1)], normal) in quads {
+        let base = positions.len() as u32;
+
+        positions.extend([[x0, 0.0, z0], [x1, 0.0, z1], [x1, 1.0, z1], [x0, 1.0, z0]]);
+        normals.extend([normal.to_array(); 4]);
+        uvs.extend([[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]]);
+        indices.extend([0, 1, 2, 2, 3, 0].map(|i| base + i));
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(indices)));
+
+    mesh
+}
+
+/**
+  Thin stick, standing on the origin.
+*/
+pub fn post_mesh() -> Mesh {
+    shape::Box {
+        min_x: -POST_HALF_WIDTH,
+        max_x: POST_HALF_WIDTH,
+        min_y: 0.0,
+        max_y: POST_HEIGHT,
+        min_z: -POST_HALF_WIDTH,
+        max_z: POST_HALF_WIDTH,
+    }
+    .into()
+}
+
+/**
+  Collects the instances of every prop inside the given chunk, grouped by shape.
+  Props stands on the bottom center of their voxel.
+*/
+pub fn prop_instances(
+    kind: &ChunkKind,
+    registry: &KindRegistry,
+) -> HashMap<PropShape, Vec<PropInstance>> {
+    let mut instances = HashMap::<PropShape, Vec<PropInstance>>::default();
+
+    for voxel in chunk::voxels() {
+        let desc = match registry.get(kind.get(voxel)) {
+            Some(desc) => desc,
+            None => continue,
+        };
+
+        if let Some(shape) = desc.prop {
+            let (r, g, b, _) = desc.color;
+
+            instances.entry(shape).or_default().push(PropInstance {
+                position: voxel.as_vec3() + Vec3::new(0.5, 0.0, 0.5),
+                scale: 1.0,
+                color: Color::rgb(r, g, b).as_linear_rgba_f32(),
+                sway: if desc.foliage { 1.0 } else { 0.0 },
+            });
+        }
+    }
+
+    instances
+}
+
+pub struct PropsPlugin;
+
+impl Plugin for PropsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(ExtractComponentPlugin::<PropInstances>::default());
+
+        app.sub_app_mut(RenderApp)
+            .add_render_command::<Opaque3d, DrawProps>()
+            .init_resource::<PropPipeline>()
+            .init_resource::<SpecializedMeshPipelines<PropPipeline>>()
+            .add_system_to_stage(RenderStage::Prepare, prepare_prop_buffers)
+            .add_system_to_stage(RenderStage::Queue, queue_props)
+            .add_system_to_stage(RenderStage::Queue, queue_prop_wind_bind_group);
+    }
+}
+
+#[derive(Component)]
+struct PropBuffer {
+    buffer: bevy::render::render_resource::Buffer,
+    length: usize,
+}
+
+fn prepare_prop_buffers(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    q: Query<(Entity, &PropInstances)>,
+) {
+    for (entity, instances) in q.iter() {
+        let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("prop_instance_buffer"),
+            contents: bytemuck::cast_slice(&instances.0),
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        });
+
+        commands.entity(entity).insert(PropBuffer {
+            buffer,
+            length: instances.0.len(),
+        });
+    }
+}
+
+/// Bind group of the wind buffer, which is the same every frame, so it's only created once.
+struct PropWindBindGroup(BindGroup);
+
+fn queue_prop_wind_bind_group(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    prop_pipeline: Res<PropPipeline>,
+    wind_buffer: Res<WindBuffer>,
+    bind_group: Option<Res<PropWindBindGroup>>,
+) {
+    if bind_group.is_some() {
+        return;
+    }
+
+    commands.insert_resource(PropWindBindGroup(render_device.create_bind_group(
+        &BindGroupDescriptor {
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: wind_buffer.0.as_entire_binding(),
+            }],
+            label: Some("prop_wind_bind_group"),
+            layout: &prop_pipeline.wind_layout,
+        },
+    )));
+}
+
+#[allow(clippy::too_many_arguments)]
+fn queue_props(
+    draw_functions: Res<DrawFunctions<Opaque3d>>,
+    prop_pipeline: Res<PropPipeline>,
+    msaa: Res<Msaa>,
+    meshes: Res<RenderAssets<Mesh>>,
+    mut pipelines: ResMut<SpecializedMeshPipelines<PropPipeline>>,
+    mut pipeline_cache: ResMut<PipelineCache>,
+    props: Query<(Entity, &MeshUniform, &Handle<Mesh>), With<PropInstances>>,
+    mut views: Query<(&ExtractedView, &mut RenderPhase<Opaque3d>)>,
+) {
+    let draw_props = draw_functions.read().get_id::<DrawProps>().unwrap();
+    let msaa_key = MeshPipelineKey::from_msaa_samples(msaa.samples);
+
+    for (view, mut opaque_phase) in views.iter_mut() {
+        let inverse_view_row_2 = view.transform.compute_matrix().inverse().row(2);
+
+        // Only visible props have a mesh uniform, so frustum culling is already applied
+        for (entity, mesh_uniform, mesh_handle) in props.iter() {
+            let mesh = match meshes.get(mesh_handle) {
+                Some(mesh) => mesh,
+                None => continue,
+            };
+
+            let key = msaa_key | MeshPipelineKey::from_primitive_topology(mesh.primitive_topology);
+
+            let pipeline = match pipelines.specialize(
+                &mut pipeline_cache,
+                &prop_pipeline,
+                key,
+                &mesh.layout,
+            ) {
+                Ok(pipeline) => pipeline,
+                Err(err) => {
+                    error!("Failed to specialize prop pipeline: {}", err);
+                    continue;
+                }
+            };
+
+            opaque_phase.add(Opaque3d {
+                entity,
+                pipeline,
+                draw_function: draw_props,
+                distance: -inverse_view_row_2.dot(mesh_uniform.transform.col(3)),
+            });
+        }
+    }
+}
+
+/**
+  Mesh pipeline with an extra per instance vertex buffer holding [`PropInstance`], and the wind uniform.
+*/
+struct PropPipeline {
+    shader: Handle<Shader>,
+    mesh_pipeline: MeshPipeline,
+    wind_layout: BindGroupLayout,
+}
+
+impl FromWorld for PropPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let mesh_pipeline = world
+            .get_resource::<MeshPipeline>()
+            .expect("PbrPlugin must be added before VoxRenderPlugin")
+            .clone();
+
+        let render_device = world.get_resource::<RenderDevice>().unwrap();
+        let wind_layout = render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: BufferSize::new(WindUniform::std140_size_static() as u64),
+                },
+                count: None,
+            }],
+            label: Some("prop_wind_layout"),
+        });
+
+        Self {
+            shader: PROPS_SHADER_HANDLE.typed(),
+            mesh_pipeline,
+            wind_layout,
+        }
+    }
+}
+
+impl SpecializedMeshPipeline for PropPipeline {
+    type Key = MeshPipelineKey;
+
+    fn specialize(
+        &self,
+        key: Self::Key,
+        layout: &MeshVertexBufferLayout,
+    ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
+        let mut descriptor = self.mesh_pipeline.specialize(key, layout)?;
+
+        descriptor.vertex.shader = self.shader.clone();
+        // Locations 0 to 2 are taken by position, normal and uv
+        descriptor.vertex.buffers.push(VertexBufferLayout {
+            array_stride: std::mem::size_of::<PropInstance>() as u64,
+            step_mode: VertexStepMode::Instance,
+            attributes: vec![
+                VertexAttribute {
+                    format: VertexFormat::Float32x4,
+                    offset: 0,
+                    shader_location: 3,
+                },
+                VertexAttribute {
+                    format: VertexFormat::Float32x4,
+                    offset: VertexFormat::Float32x4.size(),
+                    shader_location: 4,
+                },
+                VertexAttribute {
+                    format: VertexFormat::Float32,
+                    offset: VertexFormat::Float32x4.size() * 2,
+                    shader_location: 5,
+                },
+            ],
+        });
+
+        if let Some(layout) = descriptor.layout.as_mut() {
+            layout.push(self.wind_layout.clone());
+        }
+
+        if let Some(fragment) = descriptor.fragment.as_mut() {
+            fragment.shader = self.shader.clone();
+        }
+
+        // Billboards are single quads, which must be visible from both sides
+        descriptor.primitive.cull_mode = None;
+
+        Ok(descriptor)
+    }
+}
+
+type DrawProps = (
+    SetItemPipeline,
+    SetMeshViewBindGroup<0>,
+    SetMeshBindGroup<1>,
+    SetPropWindBindGroup<2>,
+    DrawPropInstances,
+);
+
+struct SetPropWindBindGroup<const I: usize>;
+
+impl<const I: usize> EntityRenderCommand for SetPropWindBindGroup<I> {
+    type Param = SRes<PropWindBindGroup>;
+
+    fn render<'w>(
+        _view: Entity,
+        _item: Entity,
+        bind_group: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        pass.set_bind_group(I, &bind_group.into_inner().0, &[]);
+
+        RenderCommandResult::Success
+    }
+}
+
+struct DrawPropInstances;
+
+impl EntityRenderCommand for DrawPropInstances {
+    type Param = (
+        SRes<RenderAssets<Mesh>>,
+        SQuery<Read<Handle<Mesh>>>,
+        SQuery<Read<PropBuffer>>,
+    );
+
+    fn render<'w>(
+        _view: Entity,
+        item: Entity,
+        (meshes, mesh_query, buffer_query): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let (mesh_handle, prop_buffer) = match (mesh_query.get(item), buffer_query.get_inner(item))
+        {
+            (Ok(mesh_handle), Ok(prop_buffer)) => (mesh_handle, prop_buffer),
+            _ => return RenderCommandResult::Failure,
+        };
+
+        let gpu_mesh = match meshes.into_inner().get(mesh_handle) {
+            Some(gpu_mesh) => gpu_mesh,
+            None => return RenderCommandResult::Failure,
+        };
+
+        pass.set_vertex_buffer(0, gpu_mesh.vertex_buffer.slice(..));
+        pass.set_vertex_buffer(1, prop_buffer.buffer.slice(..));
+
+        let instances = 0..prop_buffer.length as u32;
+
+        match &gpu_mesh.buffer_info {
+            GpuBufferInfo::Indexed {
+                buffer,
+                index_format,
+                count,
+            } => {
+                pass.set_index_buffer(buffer.slice(..), 0, *index_format);
+                pass.draw_indexed(0..*count, 0, instances);
+            }
+            GpuBufferInfo::NonIndexed { vertex_count } => {
+                pass.draw(0..*vertex_count, instances);
+            }
+        }
+
+        RenderCommandResult::Success
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vox::voxel::{KindDescription, MeshShape};
+
+    #[test]
+    fn prop_instances() {
+        let registry = KindRegistry::new(vec![
+            KindDescription {
+                name: "Stone".to_string(),
+                id: 1,
+                color: (0.5, 0.5, 0.5, 1.0),
+                shape: MeshShape::Cube,
+                prop: None,
+                directional: false,
+                light: 0,
+                tick: None,
+                signal: None,
+                toggle: None,
+                hardness: 0.0,
+                tool_tier: 0,
+                block_entity: None,
+                explosive: None,
+                liquid: None,
+                climbable: false,
+                surface: None,
+                foliage: false,
+                tint: None,
+                portal: None,
+            },
+            KindDescription {
+                name: "Flower".to_string(),
+                id: 2,
+                color: (1.0, 0.0, 0.0, 1.0),
+                shape: MeshShape::Cube,
+                prop: Some(PropShape::Billboard),
+                directional: false,
+                light: 0,
+                tick: None,
+                signal: None,
+                toggle: None,
+                hardness: 0.0,
+                tool_tier: 0,
+                block_entity: None,
+                explosive: None,
+                liquid: None,
+                climbable: false,
+                surface: None,
+                foliage: true,
+                tint: None,
+                portal: None,
+            },
+            KindDescription {
+                name: "Torch".to_string(),
+                id: 3,
+                color: (1.0, 1.0, 0.0, 1.0),
+                shape: MeshShape::Cube,
+                prop: Some(PropShape::Post),
+                directional: false,
+                light: 0,
+                tick: None,
+                signal: None,
+                toggle: None,
+                hardness: 0.0,
+                tool_tier: 0,
+                block_entity: None,
+                explosive: None,
+                liquid: None,
+                climbable: false,
+                surface: None,
+                foliage: false,
+                tint: None,
+                portal: None,
+            },
+        ]);
+
+        let mut kind = ChunkKind::default();
+        kind.set((0, 0, 0).into(), 1.into());
+        kind.set((0, 1, 0).into(), 2.into());
+        kind.set((3, 1, 4).into(), 2.into());
+        kind.set((5, 5, 5).into(), 3.into());
+
+        let instances = super::prop_instances(&kind, &registry);
+
+        assert_eq!(instances.len(), 2);
+        assert_eq!(instances[&PropShape::Billboard].len(), 2);
+        assert_eq!(instances[&PropShape::Post].len(), 1);
+
+        let torch = instances[&PropShape::Post][0];
+        assert_eq!(torch.position, Vec3::new(5.5, 5.0, 5.5));
+        assert_eq!(torch.color, [1.0, 1.0, 0.0, 1.0]);
+
+        // Only foliage sways in the wind
+        assert_eq!(torch.sway, 0.0);
+        assert!(instances[&PropShape::Billboard]
+            .iter()
+            .all(|flower| flower.sway == 1.0));
+    }
+
+    #[test]
+    fn billboard_mesh() {
+        let mesh = super::billboard_mesh();
+        let aabb = mesh.compute_aabb().unwrap();
+
+        // Must fit inside a voxel, when standing on its bottom center
+        assert!(aabb
+            .min()
+            .cmpge(bevy::math::Vec3A::new(-0.5, 0.0, -0.5))
+            .all());
+        assert!(aabb
+            .max()
+            .cmple(bevy::math::Vec3A::new(0.5, 1.0, 0.5))
+            .all());
+        assert_eq!(mesh.indices().unwrap().len(), 12);
+    }
+}
@@ -0,0 +1,77 @@
+#import bevy_pbr::mesh_view_bind_group
+#import bevy_pbr::mesh_struct
+#import vox_render::wind
+
+struct ChunkAoMaterial {
+    base_color: vec4<f32>;
+    sky_color: vec4<f32>;
+    ground_color: vec4<f32>;
+};
+
+[[group(1), binding(0)]]
+var<uniform> material: ChunkAoMaterial;
+
+[[group(1), binding(1)]]
+var<uniform> wind: Wind;
+
+[[group(1), binding(2)]]
+var colormap: texture_2d<f32>;
+
+[[group(1), binding(3)]]
+var colormap_sampler: sampler;
+
+[[group(2), binding(0)]]
+var<uniform> mesh: Mesh;
+
+// Light which fully occluded vertices still receive
+let MIN_AMBIENT: f32 = 0.3;
+
+struct Vertex {
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] normal: vec3<f32>;
+    [[location(2)]] ao: f32;
+    [[location(3)]] sway: f32;
+    [[location(4)]] tint: vec2<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] world_normal: vec3<f32>;
+    [[location(1)]] ao: f32;
+    [[location(2)]] tint: vec2<f32>;
+};
+
+[[stage(vertex)]]
+fn vertex(vertex: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+
+    var world_position = mesh.model * vec4<f32>(vertex.position, 1.0);
+    world_position = world_position + vec4<f32>(wind_offset(wind, world_position.xyz, vertex.sway), 0.0);
+    out.clip_position = view.view_proj * world_position;
+    out.world_normal = mat3x3<f32>(
+        mesh.inverse_transpose_model[0].xyz,
+        mesh.inverse_transpose_model[1].xyz,
+        mesh.inverse_transpose_model[2].xyz
+    ) * vertex.normal;
+    out.ao = vertex.ao;
+    out.tint = vertex.tint;
+
+    return out;
+}
+
+struct FragmentInput {
+    [[location(0)]] world_normal: vec3<f32>;
+    [[location(1)]] ao: f32;
+    [[location(2)]] tint: vec2<f32>;
+};
+
+[[stage(fragment)]]
+fn fragment(input: FragmentInput) -> [[location(0)]] vec4<f32> {
+    // Faces pointing up gets the sky color, while faces pointing down gets the ground color
+    let up = normalize(input.world_normal).y * 0.5 + 0.5;
+    let hemisphere = mix(material.ground_color.rgb, material.sky_color.rgb, up);
+    let occlusion = mix(MIN_AMBIENT, 1.0, input.ao);
+    let color = material.base_color.rgb * textureSample(colormap, colormap_sampler, input.tint).rgb;
+
+    return vec4<f32>(color * hemisphere * occlusion, material.base_color.a);
+}
@@ -0,0 +1,177 @@
+// Must match ChunkCullUniform on culling.rs
+struct ChunkCullUniform {
+    view_proj: mat4x4<f32>;
+    // Width and height of the depth pyramid, its levels and how many chunks there are
+    size: vec4<u32>;
+};
+
+// Must match GpuChunk on culling.rs
+struct Chunk {
+    min: vec3<f32>;
+    index_count: u32;
+    max: vec3<f32>;
+    padding: u32;
+};
+
+struct Chunks {
+    chunks: array<Chunk>;
+};
+
+// Arguments of draw_indexed_indirect
+struct DrawArgs {
+    index_count: u32;
+    instance_count: u32;
+    first_index: u32;
+    base_vertex: i32;
+    first_instance: u32;
+};
+
+struct DrawArgsList {
+    args: array<DrawArgs>;
+};
+
+struct Visibility {
+    visible: array<u32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> culling: ChunkCullUniform;
+
+[[group(0), binding(1)]]
+var<storage, read> chunks: Chunks;
+
+[[group(0), binding(2)]]
+var<storage, read_write> draws: DrawArgsList;
+
+[[group(0), binding(3)]]
+var<storage, read_write> visibility: Visibility;
+
+[[group(1), binding(0)]]
+var pyramid: texture_2d<f32>;
+
+fn draw_args(index_count: u32, visible: bool) -> DrawArgs {
+    var args: DrawArgs;
+    args.index_count = index_count;
+    args.instance_count = select(0u, 1u, visible);
+    args.first_index = 0u;
+    args.base_vertex = 0;
+    args.first_instance = 0u;
+
+    return args;
+}
+
+// Chunks visible on the last frame are drawn on the depth prepass, since they're likely to hide the others
+[[stage(compute), workgroup_size(64)]]
+fn occluders([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    let i = id.x;
+    if (i >= culling.size.w) {
+        return;
+    }
+
+    draws.args[i] = draw_args(chunks.chunks[i].index_count, visibility.visible[i] != 0u);
+}
+
+// Texel of the given pyramid level covering the given texel of the first level
+fn pyramid_texel(texel: vec2<i32>, level: i32) -> vec2<i32> {
+    let last = textureDimensions(pyramid, level) - 1;
+    return min(vec2<i32>(texel.x >> u32(level), texel.y >> u32(level)), last);
+}
+
+// Whether the chunk is outside of the view, or behind what was drawn on the depth pyramid
+fn is_hidden(chunk: Chunk) -> bool {
+    var ndc_min = vec2<f32>(1.0, 1.0);
+    var ndc_max = vec2<f32>(-1.0, -1.0);
+    // Depth is reversed, so the closest corner has the biggest one
+    var closest = 0.0;
+    var behind_camera = false;
+
+    // How many corners are outside of each plane: left, right, bottom, top and near
+    var outside = array<u32, 5>(0u, 0u, 0u, 0u, 0u);
+
+    for (var i = 0u; i < 8u; i = i + 1u) {
+        let corner = vec3<f32>(
+            select(chunk.min.x, chunk.max.x, (i & 1u) != 0u),
+            select(chunk.min.y, chunk.max.y, (i & 2u) != 0u),
+            select(chunk.min.z, chunk.max.z, (i & 4u) != 0u)
+        );
+        let clip = culling.view_proj * vec4<f32>(corner, 1.0);
+
+        outside[0] = outside[0] + select(0u, 1u, clip.x < -clip.w);
+        outside[1] = outside[1] + select(0u, 1u, clip.x > clip.w);
+        outside[2] = outside[2] + select(0u, 1u, clip.y < -clip.w);
+        outside[3] = outside[3] + select(0u, 1u, clip.y > clip.w);
+        outside[4] = outside[4] + select(0u, 1u, clip.z > clip.w);
+
+        if (clip.w <= 0.0) {
+            behind_camera = true;
+        } else {
+            let ndc = clip.xyz / clip.w;
+            ndc_min = min(ndc_min, ndc.xy);
+            ndc_max = max(ndc_max, ndc.xy);
+            closest = max(closest, ndc.z);
+        }
+    }
+
+    for (var plane = 0; plane < 5; plane = plane + 1) {
+        if (outside[plane] == 8u) {
+            return true;
+        }
+    }
+
+    // Chunks around the camera can't be projected, and are never hidden anyway
+    if (behind_camera || closest >= 1.0) {
+        return false;
+    }
+
+    // Textures go down, while the view goes up
+    let size = vec2<f32>(f32(culling.size.x), f32(culling.size.y));
+    let last = vec2<i32>(i32(culling.size.x) - 1, i32(culling.size.y) - 1);
+    let uv_min = vec2<f32>(ndc_min.x, -ndc_max.y) * 0.5 + 0.5;
+    let uv_max = vec2<f32>(ndc_max.x, -ndc_min.y) * 0.5 + 0.5;
+    let texel_min = clamp(vec2<i32>(floor(uv_min * size)), vec2<i32>(0, 0), last);
+    let texel_max = clamp(vec2<i32>(floor(uv_max * size)), vec2<i32>(0, 0), last);
+
+    // Smallest level where the chunk covers at most 2x2 texels
+    let levels = i32(culling.size.z);
+    var level = 0;
+    var min_texel = texel_min;
+    var max_texel = texel_max;
+    loop {
+        min_texel = pyramid_texel(texel_min, level);
+        max_texel = pyramid_texel(texel_max, level);
+
+        if (level == levels - 1 || all(max_texel - min_texel <= vec2<i32>(1, 1))) {
+            break;
+        }
+
+        level = level + 1;
+    }
+
+    let farthest = min(
+        min(
+            textureLoad(pyramid, min_texel, level).r,
+            textureLoad(pyramid, vec2<i32>(max_texel.x, min_texel.y), level).r
+        ),
+        min(
+            textureLoad(pyramid, vec2<i32>(min_texel.x, max_texel.y), level).r,
+            textureLoad(pyramid, max_texel, level).r
+        )
+    );
+
+    return closest < farthest;
+}
+
+// Fills the draw arguments of each chunk, with no instances for hidden ones
+[[stage(compute), workgroup_size(64)]]
+fn cull([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    let i = id.x;
+    if (i >= culling.size.w) {
+        return;
+    }
+
+    let chunk = chunks.chunks[i];
+    let visible = chunk.index_count > 0u && !is_hidden(chunk);
+
+    draws.args[i] = draw_args(chunk.index_count, visible);
+    visibility.visible[i] = select(0u, 1u, visible);
+}
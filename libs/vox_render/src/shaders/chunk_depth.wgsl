@@ -0,0 +1,37 @@
+#import bevy_pbr::mesh_view_bind_group
+#import bevy_pbr::mesh_struct
+
+[[group(1), binding(0)]]
+var<uniform> mesh: Mesh;
+
+struct Vertex {
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] sway: f32;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] sway: f32;
+};
+
+[[stage(vertex)]]
+fn vertex(vertex: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+
+    out.clip_position = view.view_proj * mesh.model * vec4<f32>(vertex.position, 1.0);
+    out.sway = vertex.sway;
+
+    return out;
+}
+
+struct FragmentInput {
+    [[location(0)]] sway: f32;
+};
+
+// Swaying faces move away from where they're drawn here, so they can't hide anything
+[[stage(fragment)]]
+fn fragment(input: FragmentInput) {
+    if (input.sway > 0.0) {
+        discard;
+    }
+}
@@ -0,0 +1,55 @@
+#import bevy_pbr::mesh_view_bind_group
+#import bevy_pbr::mesh_struct
+#import vox_render::wind
+
+// Bindings 0 to 10 are the standard material ones, used by the PBR fragment shader
+[[group(1), binding(11)]]
+var<uniform> wind: Wind;
+
+[[group(2), binding(0)]]
+var<uniform> mesh: Mesh;
+
+// Same as the PBR mesh vertex shader, but foliage vertices sway in the wind.
+// Chunk meshes always have tangents, so the PBR fragment shader always expects them.
+// Chunks have no textures, so the texture coordinates are the vertex tint, which looks up the base color
+// texture, the biome colormap.
+struct Vertex {
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] normal: vec3<f32>;
+    [[location(2)]] tint: vec2<f32>;
+    [[location(3)]] tangent: vec4<f32>;
+    [[location(4)]] sway: f32;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] world_position: vec4<f32>;
+    [[location(1)]] world_normal: vec3<f32>;
+    [[location(2)]] uv: vec2<f32>;
+    [[location(3)]] world_tangent: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vertex(vertex: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+
+    out.world_position = mesh.model * vec4<f32>(vertex.position, 1.0);
+    out.world_position = out.world_position + vec4<f32>(wind_offset(wind, out.world_position.xyz, vertex.sway), 0.0);
+    out.world_normal = mat3x3<f32>(
+        mesh.inverse_transpose_model[0].xyz,
+        mesh.inverse_transpose_model[1].xyz,
+        mesh.inverse_transpose_model[2].xyz
+    ) * vertex.normal;
+    out.world_tangent = vec4<f32>(
+        mat3x3<f32>(
+            mesh.model[0].xyz,
+            mesh.model[1].xyz,
+            mesh.model[2].xyz
+        ) * vertex.tangent.xyz,
+        vertex.tangent.w
+    );
+    out.uv = vertex.tint;
+    out.clip_position = view.view_proj * out.world_position;
+
+    return out;
+}
@@ -0,0 +1,49 @@
+// Builds the depth pyramid, one level at a time. Each texel holds the farthest depth of the texels it covers
+// on the level below, which is the smallest one, since depth is reversed.
+
+[[group(0), binding(0)]]
+var depth: texture_depth_2d;
+
+[[group(0), binding(1)]]
+var input: texture_2d<f32>;
+
+[[group(0), binding(2)]]
+var output: texture_storage_2d<r32float, write>;
+
+[[stage(compute), workgroup_size(8, 8)]]
+fn copy_depth([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    let texel = vec2<i32>(id.xy);
+    if (any(texel >= textureDimensions(output))) {
+        return;
+    }
+
+    textureStore(output, texel, vec4<f32>(textureLoad(depth, texel, 0), 0.0, 0.0, 0.0));
+}
+
+[[stage(compute), workgroup_size(8, 8)]]
+fn downsample([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    let texel = vec2<i32>(id.xy);
+    let size = textureDimensions(output);
+    if (any(texel >= size)) {
+        return;
+    }
+
+    // The last row and column also cover the texels left over by odd sizes, so none is skipped
+    let input_size = textureDimensions(input);
+    var end = texel * 2 + 1;
+    if (texel.x == size.x - 1) {
+        end.x = input_size.x - 1;
+    }
+    if (texel.y == size.y - 1) {
+        end.y = input_size.y - 1;
+    }
+
+    var farthest = 1.0;
+    for (var y = texel.y * 2; y <= end.y; y = y + 1) {
+        for (var x = texel.x * 2; x <= end.x; x = x + 1) {
+            farthest = min(farthest, textureLoad(input, vec2<i32>(x, y), 0).r);
+        }
+    }
+
+    textureStore(output, texel, vec4<f32>(farthest, 0.0, 0.0, 0.0));
+}
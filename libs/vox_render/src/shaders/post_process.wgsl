@@ -0,0 +1,92 @@
+#import bevy_sprite::mesh2d_view_bind_group
+#import bevy_sprite::mesh2d_struct
+
+struct PostProcess {
+    exposure: f32;
+    tonemapping: u32;
+    bloom_threshold: f32;
+    bloom_intensity: f32;
+    bloom_radius: f32;
+    vignette_strength: f32;
+    vignette_radius: f32;
+};
+
+[[group(1), binding(0)]]
+var<uniform> settings: PostProcess;
+[[group(1), binding(1)]]
+var frame: texture_2d<f32>;
+[[group(1), binding(2)]]
+var frame_sampler: sampler;
+
+// Must match the tonemapping of PostProcessUniform
+let TONEMAPPING_REINHARD: u32 = 1u;
+let TONEMAPPING_ACES: u32 = 2u;
+
+// Samples taken around each pixel for the bloom, on two rings
+let BLOOM_SAMPLES: i32 = 12;
+let TAU: f32 = 6.28318530718;
+
+struct FragmentInput {
+    [[builtin(front_facing)]] is_front: bool;
+    [[location(0)]] world_position: vec4<f32>;
+    [[location(1)]] world_normal: vec3<f32>;
+    [[location(2)]] uv: vec2<f32>;
+#ifdef VERTEX_TANGENTS
+    [[location(3)]] world_tangent: vec4<f32>;
+#endif
+};
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+// Part of the color above the bloom threshold
+fn bright(uv: vec2<f32>) -> vec3<f32> {
+    let color = textureSample(frame, frame_sampler, uv).rgb;
+    let lum = luminance(color);
+
+    return color * max(lum - settings.bloom_threshold, 0.0) / max(lum, 0.0001);
+}
+
+// Narkowicz's fit of the ACES filmic curve
+fn aces(color: vec3<f32>) -> vec3<f32> {
+    let mapped = (color * (2.51 * color + 0.03)) / (color * (2.43 * color + 0.59) + 0.14);
+    return clamp(mapped, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+[[stage(fragment)]]
+fn fragment(in: FragmentInput) -> [[location(0)]] vec4<f32> {
+    var color = textureSample(frame, frame_sampler, in.uv).rgb;
+
+    if (settings.bloom_intensity > 0.0) {
+        let texel = settings.bloom_radius / vec2<f32>(textureDimensions(frame));
+        var bloom = vec3<f32>(0.0);
+
+        for (var i: i32 = 0; i < BLOOM_SAMPLES; i = i + 1) {
+            let angle = f32(i) * TAU / f32(BLOOM_SAMPLES);
+            let ring = select(0.5, 1.0, i % 2 == 0);
+            bloom = bloom + bright(in.uv + vec2<f32>(cos(angle), sin(angle)) * texel * ring);
+        }
+
+        color = color + bloom / f32(BLOOM_SAMPLES) * settings.bloom_intensity;
+    }
+
+    color = color * settings.exposure;
+
+    if (settings.tonemapping == TONEMAPPING_REINHARD) {
+        color = color / (color + vec3<f32>(1.0));
+    } else if (settings.tonemapping == TONEMAPPING_ACES) {
+        color = aces(color);
+    }
+
+    if (settings.vignette_strength > 0.0) {
+        // Zero on the center, one on the corners
+        let distance = length(in.uv - vec2<f32>(0.5)) * 1.41421356;
+        let t = clamp((distance - settings.vignette_radius) / (1.0 - settings.vignette_radius), 0.0, 1.0);
+        let vignette = t * t * (3.0 - 2.0 * t);
+
+        color = color * (1.0 - vignette * settings.vignette_strength);
+    }
+
+    return vec4<f32>(color, 1.0);
+}
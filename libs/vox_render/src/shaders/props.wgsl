@@ -0,0 +1,58 @@
+#import bevy_pbr::mesh_view_bind_group
+#import bevy_pbr::mesh_struct
+#import vox_render::wind
+
+[[group(1), binding(0)]]
+var<uniform> mesh: Mesh;
+
+[[group(2), binding(0)]]
+var<uniform> wind: Wind;
+
+// Light received by the bottom of props
+let MIN_AMBIENT: f32 = 0.5;
+
+struct Vertex {
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] normal: vec3<f32>;
+    [[location(2)]] uv: vec2<f32>;
+    [[location(3)]] i_position_scale: vec4<f32>;
+    [[location(4)]] i_color: vec4<f32>;
+    [[location(5)]] i_sway: f32;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] color: vec4<f32>;
+    [[location(1)]] uv: vec2<f32>;
+};
+
+[[stage(vertex)]]
+fn vertex(vertex: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+
+    let position = vertex.position * vertex.i_position_scale.w + vertex.i_position_scale.xyz;
+    var world_position = mesh.model * vec4<f32>(position, 1.0);
+
+    // Props are rooted to the ground, so their sway grows toward the top
+    let sway = vertex.i_sway * vertex.position.y;
+    world_position = world_position + vec4<f32>(wind_offset(wind, world_position.xyz, sway), 0.0);
+
+    out.clip_position = view.view_proj * world_position;
+    out.color = vertex.i_color;
+    out.uv = vertex.uv;
+
+    return out;
+}
+
+struct FragmentInput {
+    [[location(0)]] color: vec4<f32>;
+    [[location(1)]] uv: vec2<f32>;
+};
+
+[[stage(fragment)]]
+fn fragment(input: FragmentInput) -> [[location(0)]] vec4<f32> {
+    // Darken props toward their base, which is hidden among the neighbors
+    let occlusion = mix(1.0, MIN_AMBIENT, input.uv.y);
+
+    return vec4<f32>(input.color.rgb * occlusion, 1.0);
+}
@@ -0,0 +1,22 @@
+#define_import_path vox_render::wind
+
+struct Wind {
+    direction: vec3<f32>;
+    strength: f32;
+    time: f32;
+    frequency: f32;
+};
+
+let TAU: f32 = 6.28318530718;
+
+// How much foliage leans with the wind, while the rest of the strength is its back and forth sway
+let WIND_LEAN: f32 = 0.6;
+
+// Offset of a vertex at the given world position, swaying by the given amount
+fn wind_offset(wind: Wind, world_position: vec3<f32>, sway: f32) -> vec3<f32> {
+    // Waves travel along the wind, so neighbor plants sway one after another instead of all at once
+    let phase = dot(world_position, wind.direction) * 0.35 + (world_position.x + world_position.z) * 0.15;
+    let wave = sin(wind.time * wind.frequency * TAU - phase) * 0.5 + 0.5;
+
+    return wind.direction * wind.strength * sway * mix(WIND_LEAN, 1.0, wave);
+}
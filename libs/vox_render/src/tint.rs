@@ -0,0 +1,218 @@
+use bevy::{
+    prelude::*,
+    render::render_resource::{
+        Extent3d, FilterMode, SamplerDescriptor, TextureDimension, TextureFormat,
+    },
+};
+use vox::{biome::Biome, chunk, voxel::Tint, world::VoxWorld};
+
+/// How far, in columns, biomes are blended around each vertex, so their colors fade into each other on borders.
+const BLEND_RADIUS: i32 = 2;
+
+/**
+  Biomes ordered from the coldest to the hottest, as laid out along the colormap. Neighbor biomes are always
+  next to each other on it, so blending their position blends their colors.
+*/
+const GRADIENT: [Biome; 3] = [Biome::Snowy, Biome::Plains, Biome::Desert];
+
+/// Tint of each colormap row. Untinted kinds are on the first one, which has the same color on every biome.
+const ROWS: [Option<Tint>; 4] = [
+    None,
+    Some(Tint::Grass),
+    Some(Tint::Foliage),
+    Some(Tint::Water),
+];
+
+/// Color of voxels which aren't tinted.
+const UNTINTED_COLOR: Color = Color::rgb(0.3, 0.6, 0.3);
+
+fn color(biome: Biome, tint: Tint) -> Color {
+    match (biome, tint) {
+        (Biome::Plains, Tint::Grass) => Color::rgb(0.3, 0.6, 0.3),
+        (Biome::Desert, Tint::Grass) => Color::rgb(0.6, 0.6, 0.3),
+        (Biome::Snowy, Tint::Grass) => Color::rgb(0.4, 0.55, 0.5),
+        (Biome::Plains, Tint::Foliage) => Color::rgb(0.2, 0.5, 0.15),
+        (Biome::Desert, Tint::Foliage) => Color::rgb(0.45, 0.5, 0.15),
+        (Biome::Snowy, Tint::Foliage) => Color::rgb(0.25, 0.45, 0.35),
+        (Biome::Plains, Tint::Water) => Color::rgb(0.2, 0.4, 0.8),
+        (Biome::Desert, Tint::Water) => Color::rgb(0.2, 0.55, 0.7),
+        (Biome::Snowy, Tint::Water) => Color::rgb(0.25, 0.35, 0.7),
+    }
+}
+
+/// Position of the biome along the colormap, from 0.0 (coldest) to 1.0 (hottest).
+fn gradient(biome: Biome) -> f32 {
+    let idx = GRADIENT
+        .iter()
+        .position(|other| *other == biome)
+        .unwrap_or(0);
+    idx as f32 / (GRADIENT.len() - 1) as f32
+}
+
+/**
+  Colors of each tint on each biome, with a texel for each biome on the [`GRADIENT`] and a row for each tint
+  on [`ROWS`]. It's linearly filtered, so colors looked up in between biomes are blended.
+*/
+pub fn colormap() -> Image {
+    let data = ROWS
+        .iter()
+        .flat_map(|tint| {
+            GRADIENT.iter().map(move |biome| match tint {
+                Some(tint) => color(*biome, *tint),
+                None => UNTINTED_COLOR,
+            })
+        })
+        .flat_map(|color| color.as_rgba_f32().map(|c| (c * 255.0).round() as u8))
+        .collect();
+
+    let mut image = Image::new(
+        Extent3d {
+            width: GRADIENT.len() as u32,
+            height: ROWS.len() as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    );
+
+    image.sampler_descriptor = SamplerDescriptor {
+        mag_filter: FilterMode::Linear,
+        min_filter: FilterMode::Linear,
+        ..Default::default()
+    };
+
+    image
+}
+
+/// Colormap coordinates of the given tint, at the given position along the biome gradient.
+pub fn coordinates(tint: Option<Tint>, gradient: f32) -> Vec2 {
+    let row = ROWS.iter().position(|other| *other == tint).unwrap_or(0);
+    let (width, height) = (GRADIENT.len() as f32, ROWS.len() as f32);
+
+    // Texel centers are half a texel in, so the ends of the gradient get the exact biome colors
+    Vec2::new(
+        (gradient.clamp(0.0, 1.0) * (width - 1.0) + 0.5) / width,
+        (row as f32 + 0.5) / height,
+    )
+}
+
+/**
+  Position along the biome gradient of each column corner of a chunk, blended with the biomes around it, so
+  vertices on biome borders get a color in between both biomes.
+*/
+pub struct BiomeBlend {
+    corners: Vec<f32>,
+}
+
+impl BiomeBlend {
+    /// Blends the biomes stored on the world, on the chunk base height.
+    pub fn new(world: &VoxWorld, local: IVec3) -> Self {
+        let base = chunk::to_world(local).as_ivec3().y;
+        Self::from_fn(local, |column| {
+            world.biome(IVec3::new(column.x, base, column.y))
+        })
+    }
+
+    /// Blends the biomes around the chunk, which `biome_at` returns for each world column.
+    pub fn from_fn(local: IVec3, biome_at: impl Fn(IVec2) -> Biome) -> Self {
+        let size = chunk::AXIS_SIZE as i32;
+        let world = chunk::to_world(local).as_ivec3();
+
+        // Corners on the chunk sides blend columns outside of it, up to the blend radius
+        let span = size + BLEND_RADIUS * 2;
+        let columns = (0..span)
+            .flat_map(|x| (0..span).map(move |z| IVec2::new(x, z)))
+            .map(|column| {
+                gradient(biome_at(
+                    IVec2::new(world.x, world.z) + column - IVec2::splat(BLEND_RADIUS),
+                ))
+            })
+            .collect::<Vec<_>>();
+
+        let blended = |x: i32, z: i32| {
+            let area = (0..BLEND_RADIUS * 2)
+                .flat_map(|dx| (0..BLEND_RADIUS * 2).map(move |dz| (x + dx) * span + z + dz))
+                .map(|idx| columns[idx as usize]);
+
+            area.sum::<f32>() / (BLEND_RADIUS * BLEND_RADIUS * 4) as f32
+        };
+
+        Self {
+            corners: (0..=size)
+                .flat_map(|x| (0..=size).map(move |z| (x, z)))
+                .map(|(x, z)| blended(x, z))
+                .collect(),
+        }
+    }
+
+    /// Position along the biome gradient of the given vertex, relative to the chunk.
+    pub fn get(&self, vertex: Vec3) -> f32 {
+        let size = chunk::AXIS_SIZE as i32;
+        let corner = vertex
+            .round()
+            .as_ivec3()
+            .clamp(IVec3::ZERO, IVec3::splat(size));
+
+        self.corners[(corner.x * (size + 1) + corner.z) as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colormap() {
+        let image = super::colormap();
+        let size = image.texture_descriptor.size;
+
+        assert_eq!(size.width as usize, GRADIENT.len());
+        assert_eq!(size.height as usize, ROWS.len());
+        assert_eq!(image.data.len(), GRADIENT.len() * ROWS.len() * 4);
+    }
+
+    #[test]
+    fn coordinates() {
+        let untinted = super::coordinates(None, 0.0);
+        assert_eq!(untinted, Vec2::new(0.5 / 3.0, 0.5 / 4.0));
+
+        // Ends of the gradient are on the center of the first and last texels
+        let water = super::coordinates(Some(Tint::Water), 1.0);
+        assert_eq!(water, Vec2::new(2.5 / 3.0, 3.5 / 4.0));
+
+        let grass = super::coordinates(Some(Tint::Grass), gradient(Biome::Plains));
+        assert_eq!(grass, Vec2::new(0.5, 1.5 / 4.0));
+    }
+
+    #[test]
+    fn biome_blend() {
+        let size = chunk::AXIS_SIZE as f32;
+        let half = chunk::AXIS_SIZE as i32 / 2;
+
+        // Plains on the chunk first half, and desert on the other one
+        let blend = BiomeBlend::from_fn(IVec3::new(1, 0, 0), |column| {
+            if column.x < chunk::AXIS_SIZE as i32 + half {
+                Biome::Plains
+            } else {
+                Biome::Desert
+            }
+        });
+
+        assert_eq!(blend.get(Vec3::ZERO), gradient(Biome::Plains));
+        assert_eq!(
+            blend.get(Vec3::new(size, 5.0, size)),
+            gradient(Biome::Desert)
+        );
+
+        // The border is half way in between both biomes
+        let border = blend.get(Vec3::new(half as f32, 0.0, 3.0));
+        assert_eq!(
+            border,
+            (gradient(Biome::Plains) + gradient(Biome::Desert)) / 2.0
+        );
+
+        // Vertices in between corners get the closest one
+        assert_eq!(blend.get(Vec3::new(0.2, 0.0, 0.4)), blend.get(Vec3::ZERO));
+    }
+}
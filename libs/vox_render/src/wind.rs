@@ -0,0 +1,172 @@
+use bevy::{
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        render_resource::{
+            std140::{AsStd140, Std140},
+            Buffer, BufferDescriptor, BufferUsages,
+        },
+        renderer::{RenderDevice, RenderQueue},
+        RenderApp, RenderStage,
+    },
+};
+
+use crate::RenderSettings;
+
+/// Shader with the wind uniform and the displacement of swaying vertices, imported as `vox_render::wind`.
+pub const WIND_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 2_518_960_437_805_112_694);
+
+/**
+  Wind blowing over the world, which sways foliage like grass and leaves. Its strength rises on gusts, which
+  come every `gust_period` seconds.
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Wind {
+    /// Horizontal direction the wind blows to.
+    pub direction: Vec2,
+    /// How far, in voxels, fully swaying vertices are pushed by the wind.
+    pub strength: f32,
+    /// Strength added on the peak of gusts.
+    pub gust_strength: f32,
+    /// Seconds between the peak of each gust.
+    pub gust_period: f32,
+    /// How many times per second foliage sways back and forth.
+    pub frequency: f32,
+}
+
+impl Default for Wind {
+    fn default() -> Self {
+        Self {
+            direction: Vec2::new(1.0, 0.3),
+            strength: 0.06,
+            gust_strength: 0.12,
+            gust_period: 9.0,
+            frequency: 0.8,
+        }
+    }
+}
+
+impl Wind {
+    /// Uniform of the wind at the given time, in seconds.
+    pub fn uniform(&self, time: f32) -> WindUniform {
+        let direction = self.direction.normalize_or_zero();
+        let gust = gust(time, self.gust_period);
+
+        WindUniform {
+            direction: Vec3::new(direction.x, 0.0, direction.y),
+            strength: self.strength + self.gust_strength * gust,
+            time,
+            frequency: self.frequency,
+        }
+    }
+}
+
+/// Wind uniform shared by every shader which sways foliage. See `wind.wgsl`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, AsStd140)]
+pub struct WindUniform {
+    pub direction: Vec3,
+    pub strength: f32,
+    pub time: f32,
+    pub frequency: f32,
+}
+
+/**
+  Buffer holding the [`WindUniform`] of the current frame, on the render world. It's written every frame, so
+  materials bind it once and never need to be prepared again for the wind to change.
+*/
+pub struct WindBuffer(pub Buffer);
+
+impl FromWorld for WindBuffer {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world
+            .get_resource::<RenderDevice>()
+            .expect("RenderPlugin must be added before VoxRenderPlugin");
+
+        Self(render_device.create_buffer(&BufferDescriptor {
+            label: Some("wind_uniform_buffer"),
+            size: WindUniform::std140_size_static() as u64,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        }))
+    }
+}
+
+pub struct WindPlugin;
+
+impl Plugin for WindPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Wind>();
+
+        app.sub_app_mut(RenderApp)
+            .init_resource::<WindUniform>()
+            .init_resource::<WindBuffer>()
+            .add_system_to_stage(RenderStage::Extract, extract_wind)
+            .add_system_to_stage(RenderStage::Prepare, prepare_wind);
+    }
+}
+
+/**
+  How strong the gust is at the given time, from 0.0 (calm) to 1.0 (peak). Gusts rise and fade quickly, so
+  the wind is calm most of the time.
+*/
+pub fn gust(time: f32, period: f32) -> f32 {
+    if period <= 0.0 {
+        return 0.0;
+    }
+
+    let wave = (1.0 - (time / period * std::f32::consts::TAU).cos()) / 2.0;
+    wave.powi(4)
+}
+
+/// Foliage stands still when the wind is disabled on [`RenderSettings`].
+fn extract_wind(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<RenderSettings>,
+    wind: Res<Wind>,
+) {
+    let uniform = if settings.wind {
+        wind.uniform(time.seconds_since_startup() as f32)
+    } else {
+        WindUniform::default()
+    };
+
+    commands.insert_resource(uniform);
+}
+
+fn prepare_wind(
+    uniform: Res<WindUniform>,
+    buffer: Res<WindBuffer>,
+    render_queue: Res<RenderQueue>,
+) {
+    render_queue.write_buffer(&buffer.0, 0, uniform.as_std140().as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gust() {
+        assert_eq!(super::gust(0.0, 10.0), 0.0);
+        assert!((super::gust(5.0, 10.0) - 1.0).abs() < 1e-6);
+        assert!(super::gust(2.5, 10.0) < 0.5);
+        assert_eq!(super::gust(5.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn uniform() {
+        let wind = Wind {
+            direction: Vec2::new(3.0, 4.0),
+            ..Default::default()
+        };
+
+        let calm = wind.uniform(0.0);
+        assert!(calm.direction.abs_diff_eq(Vec3::new(0.6, 0.0, 0.8), 1e-6));
+        assert_eq!(calm.strength, wind.strength);
+
+        let peak = wind.uniform(wind.gust_period / 2.0);
+        assert!((peak.strength - (wind.strength + wind.gust_strength)).abs() < 1e-6);
+    }
+}
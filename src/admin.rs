@@ -0,0 +1,874 @@
+use bevy::prelude::*;
+use sha1::{Digest, Sha1};
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream},
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use vox::{
+    pipeline::{ChunkUpdated, WorldOrigin},
+    schedule::UpdateSchedule,
+    world::VoxWorld,
+};
+
+use crate::{
+    console::Console,
+    net::{self, ClientConnected, ClientDisconnected, NetServer, PlayerPositions},
+    MainCamera,
+};
+
+/// How long a connection may take to send its whole request, or to receive the answer, before it's dropped.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(1);
+/// Request lines and headers bigger than this are rejected.
+const MAX_HEAD_LEN: usize = 8192;
+/// Request bodies bigger than this are rejected, since admin requests are only short commands.
+const MAX_BODY_LEN: usize = 4096;
+
+/// Appended to the key of WebSocket handshakes before hashing it, as RFC 6455 tells.
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// How often WebSocket connections without events are pinged, so closed ones are noticed.
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xa;
+
+/**
+  Address the admin endpoint listens on. The endpoint is only enabled when this resource is inserted.
+*/
+pub struct AdminConfig {
+    pub addr: SocketAddr,
+}
+
+impl AdminConfig {
+    /// Reads the address given by the `--admin <addr>` command line argument, if any.
+    pub fn from_args(args: &[String]) -> Option<Self> {
+        crate::addr_arg(args, "--admin").map(|addr| Self { addr })
+    }
+}
+
+/// What the admin threads send to the game.
+enum AdminMessage {
+    /// A routed request, waiting for the game to answer it with a status and a JSON body.
+    Request {
+        route: Route,
+        answer: Sender<(u16, String)>,
+    },
+    /// A WebSocket connection was opened, which gets every event pushed from then on.
+    Subscribe(Sender<String>),
+}
+
+/// Messages of the admin threads. The receiver is only behind a mutex so it can be a resource.
+struct AdminRequests(Mutex<Receiver<AdminMessage>>);
+
+/// Open WebSocket connections, which are forgotten once they're closed.
+#[derive(Default)]
+struct AdminSockets(Vec<Sender<String>>);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub body: String,
+    /// The `Sec-WebSocket-Key` header, sent by clients asking to upgrade the connection to a WebSocket.
+    pub websocket_key: Option<String>,
+}
+
+/**
+  Operations exposed by the admin endpoint:
+
+  - `GET /stats`: world statistics.
+  - `GET /players`: connected players and their world position.
+  - `POST /command`: runs the request body as a console command.
+  - `POST /invalidate/<x>/<y>/<z>`: processes the given chunk again, as if it had changed.
+  - `GET /events`: upgrades to a WebSocket, which is pushed events as JSON text messages, and runs the text
+    messages it receives as console commands. Events are `{"event":"joined","name":...}` and
+    `{"event":"left","name":...}` for players, and `{"event":"console","output":...}` for what the console
+    prints, like the result of commands.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub enum Route {
+    Stats,
+    Players,
+    Command(String),
+    Invalidate(IVec3),
+    /// Holds the key of the WebSocket handshake.
+    Events(String),
+}
+
+pub struct AdminPlugin;
+
+impl Plugin for AdminPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AdminSockets>()
+            .add_startup_system(setup_admin)
+            .add_system(serve_admin)
+            .add_system(push_admin_events.after(serve_admin));
+    }
+}
+
+/// Value of the given header of a request head, if it's there.
+fn header<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    head.lines().skip(1).find_map(|line| {
+        let (header, value) = line.split_once(':')?;
+        header.eq_ignore_ascii_case(name).then(|| value.trim())
+    })
+}
+
+/**
+  Parses the request line and body of an HTTP request. Headers are ignored, except for the WebSocket key.
+*/
+pub fn parse_request(text: &str) -> Option<Request> {
+    let mut parts = text.splitn(2, "\r\n\r\n");
+    let head = parts.next()?;
+    let body = parts.next().unwrap_or_default();
+
+    let mut tokens = head.lines().next()?.split_whitespace();
+    let method = tokens.next()?;
+    let path = tokens.next()?;
+
+    Some(Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        body: body.trim().to_string(),
+        websocket_key: header(head, "sec-websocket-key").map(str::to_string),
+    })
+}
+
+/**
+  Finds the route of the given request. On failure, returns the HTTP status which should be answered.
+*/
+pub fn route(request: &Request) -> Result<Route, u16> {
+    let segments = request
+        .path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>();
+
+    match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["stats"]) => Ok(Route::Stats),
+        ("GET", ["players"]) => Ok(Route::Players),
+        ("POST", ["command"]) if !request.body.is_empty() => {
+            Ok(Route::Command(request.body.clone()))
+        }
+        ("POST", ["command"]) => Err(400),
+        ("POST", ["invalidate", x, y, z]) => match (x.parse(), y.parse(), z.parse()) {
+            (Ok(x), Ok(y), Ok(z)) => Ok(Route::Invalidate(IVec3::new(x, y, z))),
+            _ => Err(400),
+        },
+        ("GET", ["events"]) => request.websocket_key.clone().map(Route::Events).ok_or(426),
+        (_, ["stats" | "players" | "command" | "invalidate" | "events", ..]) => Err(405),
+        _ => Err(404),
+    }
+}
+
+fn response(status: u16, body: &str) -> String {
+    let reason = match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        413 => "Payload Too Large",
+        426 => "Upgrade Required",
+        _ => "Error",
+    };
+
+    format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    )
+}
+
+fn json_vec3(v: Vec3) -> String {
+    format!("[{},{},{}]", v.x, v.y, v.z)
+}
+
+/// The given text as a JSON string, quoted and escaped.
+fn json_string(text: &str) -> String {
+    let mut json = String::with_capacity(text.len() + 2);
+    json.push('"');
+
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+
+    json.push('"');
+    json
+}
+
+/// Answer to a WebSocket handshake with the given key, switching the connection to the WebSocket protocol.
+fn handshake(key: &str) -> String {
+    let accept = base64::encode(Sha1::digest(format!("{}{}", key, WEBSOCKET_GUID)));
+
+    format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept
+    )
+}
+
+/// Writes a whole, unmasked WebSocket frame, as servers send them.
+fn write_frame(stream: &mut impl Write, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    let mut frame = vec![0x80 | opcode];
+
+    match payload.len() {
+        len if len < 126 => frame.push(len as u8),
+        len if len <= u16::MAX as usize => {
+            frame.push(126);
+            frame.extend((len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend((len as u64).to_be_bytes());
+        }
+    }
+
+    frame.extend_from_slice(payload);
+    stream.write_all(&frame)
+}
+
+/**
+  Reads a WebSocket frame sent by a client, returning its opcode and unmasked payload. Clients must mask their
+  frames, and admin messages are short, so fragmented frames or ones bigger than [`MAX_BODY_LEN`] are refused.
+*/
+fn read_frame(stream: &mut impl Read) -> io::Result<(u8, Vec<u8>)> {
+    let mut head = [0; 2];
+    stream.read_exact(&mut head)?;
+
+    let len = match head[1] & 0x7f {
+        126 => {
+            let mut len = [0; 2];
+            stream.read_exact(&mut len)?;
+            u16::from_be_bytes(len) as u64
+        }
+        127 => {
+            let mut len = [0; 8];
+            stream.read_exact(&mut len)?;
+            u64::from_be_bytes(len)
+        }
+        len => len as u64,
+    };
+
+    let (fin, masked) = (head[0] & 0x80 != 0, head[1] & 0x80 != 0);
+    if !fin || !masked || len > MAX_BODY_LEN as u64 {
+        return Err(io::Error::new(ErrorKind::InvalidData, "Unsupported frame"));
+    }
+
+    let mut mask = [0; 4];
+    stream.read_exact(&mut mask)?;
+
+    let mut payload = vec![0; len as usize];
+    stream.read_exact(&mut payload)?;
+
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+
+    Ok((head[0] & 0x0f, payload))
+}
+
+/**
+  Writes the events pushed to a WebSocket connection, pinging it while there are none. Stops once writing
+  fails, which drops the receiver, so the game forgets the connection.
+*/
+fn push_frames(stream: Arc<Mutex<TcpStream>>, pushed: Receiver<String>) {
+    loop {
+        let (opcode, payload) = match pushed.recv_timeout(PING_INTERVAL) {
+            Ok(event) => (OPCODE_TEXT, event),
+            Err(RecvTimeoutError::Timeout) => (OPCODE_PING, String::new()),
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+
+        let mut stream = stream.lock().expect("Admin socket lock poisoned");
+        if write_frame(&mut *stream, opcode, payload.as_bytes()).is_err() {
+            return;
+        }
+    }
+}
+
+/**
+  Reads the frames of a WebSocket connection, running text messages as console commands, until the client
+  closes it or it breaks.
+*/
+fn read_frames(mut reader: TcpStream, stream: Arc<Mutex<TcpStream>>, sender: Sender<AdminMessage>) {
+    while let Ok((opcode, payload)) = read_frame(&mut reader) {
+        let reply = match opcode {
+            OPCODE_TEXT => {
+                let line = String::from_utf8_lossy(&payload).trim().to_string();
+                let (answer, _) = mpsc::channel();
+
+                // Results are printed by the console, which pushes them back as events
+                if !line.is_empty() {
+                    let route = Route::Command(line);
+                    if sender
+                        .send(AdminMessage::Request { route, answer })
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+
+                continue;
+            }
+            OPCODE_PING => OPCODE_PONG,
+            OPCODE_CLOSE => OPCODE_CLOSE,
+            _ => continue,
+        };
+
+        let mut stream = stream.lock().expect("Admin socket lock poisoned");
+        if write_frame(&mut *stream, reply, &payload).is_err() || reply == OPCODE_CLOSE {
+            break;
+        }
+    }
+
+    let _ = reader.shutdown(Shutdown::Both);
+}
+
+/**
+  Upgrades the connection to a WebSocket, which is then handled by its own threads: one pushing events and
+  another one reading commands. The game is told about it, so it pushes events there.
+*/
+fn accept_socket(
+    mut stream: TcpStream,
+    key: &str,
+    sender: &Sender<AdminMessage>,
+) -> io::Result<()> {
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.write_all(handshake(key).as_bytes())?;
+    stream.set_read_timeout(None)?;
+
+    let (events, pushed) = mpsc::channel();
+    if sender.send(AdminMessage::Subscribe(events)).is_err() {
+        return Ok(());
+    }
+
+    let writer = Arc::new(Mutex::new(stream.try_clone()?));
+    let reader_writer = writer.clone();
+    let sender = sender.clone();
+
+    std::thread::spawn(move || push_frames(writer, pushed));
+    std::thread::spawn(move || read_frames(stream, reader_writer, sender));
+
+    Ok(())
+}
+
+/// Value of the `Content-Length` header of the given request head, which is zero when there's none.
+fn content_length(head: &str) -> Result<usize, u16> {
+    for line in head.lines() {
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                return value.trim().parse().map_err(|_| 400u16);
+            }
+        }
+    }
+
+    Ok(0)
+}
+
+/// Reads whatever is available on the stream, waiting at most until the given deadline.
+fn read_some(stream: &mut TcpStream, deadline: Instant, buf: &mut [u8]) -> Result<usize, u16> {
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    if remaining.is_zero() {
+        return Err(408);
+    }
+
+    stream
+        .set_read_timeout(Some(remaining))
+        .map_err(|_| 500u16)?;
+
+    match stream.read(buf) {
+        Ok(read) => Ok(read),
+        Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => Err(408),
+        Err(_) => Err(400),
+    }
+}
+
+/**
+  Reads a whole request from the stream, which must be done before the given deadline. The body length is
+  taken from the `Content-Length` header. On failure, returns the HTTP status which should be answered.
+*/
+fn read_request(stream: &mut TcpStream, deadline: Instant) -> Result<String, u16> {
+    let mut bytes = Vec::new();
+    let mut buf = [0; 1024];
+
+    let head_len = loop {
+        if let Some(idx) = bytes.windows(4).position(|window| window == b"\r\n\r\n") {
+            break idx + 4;
+        }
+
+        if bytes.len() > MAX_HEAD_LEN {
+            return Err(413);
+        }
+
+        match read_some(stream, deadline, &mut buf)? {
+            // Connection closed before the head ended, so there's no body either
+            0 => break bytes.len(),
+            read => bytes.extend_from_slice(&buf[..read]),
+        }
+    };
+
+    if head_len > MAX_HEAD_LEN {
+        return Err(413);
+    }
+
+    let body_len = content_length(&String::from_utf8_lossy(&bytes[..head_len]))?;
+    if body_len > MAX_BODY_LEN {
+        return Err(413);
+    }
+
+    while bytes.len() < head_len + body_len {
+        match read_some(stream, deadline, &mut buf)? {
+            0 => return Err(400),
+            read => bytes.extend_from_slice(&buf[..read]),
+        }
+    }
+
+    bytes.truncate(head_len + body_len);
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/**
+  Reads, routes and answers connections one at a time. Routed requests are sent to the game, which answers
+  them on its next frame, so slow connections only delay other admin requests, never a frame.
+*/
+fn listen(listener: TcpListener, sender: Sender<AdminMessage>) {
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+
+        let routed = read_request(&mut stream, Instant::now() + REQUEST_TIMEOUT).and_then(|text| {
+            let request = parse_request(&text).ok_or(400u16)?;
+            debug!("Admin request from {:?}: {:?}", stream.peer_addr(), request);
+            route(&request)
+        });
+
+        let (status, body) = match routed {
+            Ok(Route::Events(key)) => {
+                if let Err(err) = accept_socket(stream, &key, &sender) {
+                    warn!("Failed to accept admin WebSocket: {}", err);
+                }
+
+                continue;
+            }
+            Ok(route) => {
+                let (answer, answered) = mpsc::channel();
+
+                // The game is gone once its end of the channel is dropped
+                if sender
+                    .send(AdminMessage::Request { route, answer })
+                    .is_err()
+                {
+                    return;
+                }
+
+                match answered.recv() {
+                    Ok(answer) => answer,
+                    Err(_) => return,
+                }
+            }
+            Err(status) => (status, "{}".to_string()),
+        };
+
+        let written = stream
+            .set_write_timeout(Some(REQUEST_TIMEOUT))
+            .and_then(|_| stream.write_all(response(status, &body).as_bytes()));
+
+        if written.is_err() {
+            warn!(
+                "Failed to answer admin request from {:?}",
+                stream.peer_addr()
+            );
+        }
+    }
+}
+
+fn setup_admin(mut commands: Commands, config: Option<Res<AdminConfig>>) {
+    let config = match config {
+        Some(config) => config,
+        None => return,
+    };
+
+    let listener = TcpListener::bind(config.addr)
+        .unwrap_or_else(|_| panic!("Unable to bind admin endpoint to {}", config.addr));
+
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || listen(listener, sender));
+
+    info!("Admin endpoint listening on {}", config.addr);
+    commands.insert_resource(AdminRequests(Mutex::new(receiver)));
+}
+
+/**
+  Answers admin requests routed by the admin thread. Requests are only read there, so this never stalls a
+  frame, whatever the connection does.
+*/
+#[allow(clippy::too_many_arguments)]
+fn serve_admin(
+    requests: Option<Res<AdminRequests>>,
+    world: Res<VoxWorld>,
+    origin: Res<WorldOrigin>,
+    scheduled: Res<UpdateSchedule>,
+    server: Option<Res<NetServer>>,
+    positions: Res<PlayerPositions>,
+    mut console: ResMut<Console>,
+    mut sockets: ResMut<AdminSockets>,
+    mut writer: EventWriter<ChunkUpdated>,
+    host: Query<&Transform, With<MainCamera>>,
+) {
+    let requests = match requests {
+        Some(requests) => requests,
+        None => return,
+    };
+
+    let receiver = requests.0.lock().expect("Admin requests lock poisoned");
+
+    for message in receiver.try_iter() {
+        let (route, answer) = match message {
+            AdminMessage::Request { route, answer } => (route, answer),
+            AdminMessage::Subscribe(events) => {
+                sockets.0.push(events);
+                continue;
+            }
+        };
+
+        let answered = match route {
+            Route::Stats => (
+                200,
+                format!(
+                    "{{\"loaded_chunks\":{},\"tick\":{},\"origin\":{}}}",
+                    world.len(),
+                    scheduled.tick(),
+                    json_vec3(origin.0.as_vec3())
+                ),
+            ),
+            Route::Players => {
+                let name = |client| match &server {
+                    Some(server) => server.player_name(client),
+                    None => net::player_name(client),
+                };
+
+                // Names are only letters, digits and underscores, so they never need escaping
+                let players = host
+                    .iter()
+                    .map(|transform| (name(None), origin.to_world(transform.translation)))
+                    .chain(
+                        positions
+                            .iter()
+                            .map(|(client, position)| (name(Some(client)), position)),
+                    )
+                    .map(|(name, position)| {
+                        format!(
+                            "{{\"name\":\"{}\",\"position\":{}}}",
+                            name,
+                            json_vec3(position)
+                        )
+                    })
+                    .collect::<Vec<_>>();
+
+                (200, format!("[{}]", players.join(",")))
+            }
+            Route::Command(line) => {
+                // Commands runs with the console ones, so their output goes to the console and the log
+                console.submit(line);
+                (202, "{}".to_string())
+            }
+            Route::Invalidate(local) if world.get(local).is_some() => {
+                writer.send(ChunkUpdated(local));
+                (202, "{}".to_string())
+            }
+            Route::Invalidate(_) => (404, "{\"error\":\"Chunk not loaded\"}".to_string()),
+            // Upgraded by the admin thread, so it never gets here
+            Route::Events(_) => (400, "{}".to_string()),
+        };
+
+        // The admin thread always waits for its answers, unless it died
+        let _ = answer.send(answered);
+    }
+}
+
+/**
+  Pushes players joining and leaving, and what the console prints, to the open WebSocket connections.
+  Connections which were closed are forgotten.
+*/
+fn push_admin_events(
+    server: Option<Res<NetServer>>,
+    console: Res<Console>,
+    mut sockets: ResMut<AdminSockets>,
+    mut last_output: Local<String>,
+    mut connected: EventReader<ClientConnected>,
+    mut disconnected: EventReader<ClientDisconnected>,
+) {
+    let name = |client| match &server {
+        Some(server) => json_string(&server.player_name(Some(client))),
+        None => json_string(&net::player_name(Some(client))),
+    };
+
+    let mut events = connected
+        .iter()
+        .map(|ClientConnected(client)| {
+            format!("{{\"event\":\"joined\",\"name\":{}}}", name(*client))
+        })
+        .chain(disconnected.iter().map(|ClientDisconnected(client)| {
+            format!("{{\"event\":\"left\",\"name\":{}}}", name(*client))
+        }))
+        .collect::<Vec<_>>();
+
+    if console.output() != *last_output {
+        *last_output = console.output().to_string();
+        events.push(format!(
+            "{{\"event\":\"console\",\"output\":{}}}",
+            json_string(&last_output)
+        ));
+    }
+
+    for event in events {
+        sockets
+            .0
+            .retain(|socket| socket.send(event.clone()).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, path: &str, body: &str) -> Request {
+        Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            body: body.to_string(),
+            websocket_key: None,
+        }
+    }
+
+    /// Connected pair of streams, the first one being the client end.
+    fn connect() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+
+        (client, server)
+    }
+
+    #[test]
+    fn read_request() {
+        let deadline = || Instant::now() + Duration::from_millis(100);
+
+        let text = "POST /command HTTP/1.1\r\nContent-Length: 5\r\n\r\nwhere";
+        let (mut client, mut server) = connect();
+        client.write_all(text.as_bytes()).unwrap();
+        assert_eq!(
+            super::read_request(&mut server, deadline()),
+            Ok(text.to_string())
+        );
+
+        // Clients sending too slow are dropped, even when they keep sending something
+        let (mut client, mut server) = connect();
+        client.write_all(b"GET /stats HTTP/1.1\r\n").unwrap();
+        assert_eq!(super::read_request(&mut server, deadline()), Err(408));
+
+        let (mut client, mut server) = connect();
+        let head = format!(
+            "GET /stats HTTP/1.1\r\nHost: {}\r\n\r\n",
+            "a".repeat(MAX_HEAD_LEN)
+        );
+        client.write_all(head.as_bytes()).unwrap();
+        assert_eq!(super::read_request(&mut server, deadline()), Err(413));
+
+        let (mut client, mut server) = connect();
+        let head = format!(
+            "POST /command HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY_LEN + 1
+        );
+        client.write_all(head.as_bytes()).unwrap();
+        assert_eq!(super::read_request(&mut server, deadline()), Err(413));
+    }
+
+    #[test]
+    fn parse_request() {
+        let text = "POST /command HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\r\nwhere";
+
+        assert_eq!(
+            super::parse_request(text),
+            Some(request("POST", "/command", "where"))
+        );
+        assert_eq!(
+            super::parse_request("GET /stats HTTP/1.1\r\n\r\n"),
+            Some(request("GET", "/stats", ""))
+        );
+        assert_eq!(
+            super::parse_request(
+                "GET /events HTTP/1.1\r\nUpgrade: websocket\r\nSec-WebSocket-Key: abc=\r\n\r\n"
+            ),
+            Some(Request {
+                websocket_key: Some("abc=".to_string()),
+                ..request("GET", "/events", "")
+            })
+        );
+        assert_eq!(super::parse_request(""), None);
+        assert_eq!(super::parse_request("GET\r\n\r\n"), None);
+    }
+
+    #[test]
+    fn route() {
+        assert_eq!(
+            super::route(&request("GET", "/stats", "")),
+            Ok(Route::Stats)
+        );
+        assert_eq!(
+            super::route(&request("GET", "/players/", "")),
+            Ok(Route::Players)
+        );
+        assert_eq!(
+            super::route(&request("POST", "/command", "tp 0 10 0")),
+            Ok(Route::Command("tp 0 10 0".to_string()))
+        );
+        assert_eq!(
+            super::route(&request("POST", "/invalidate/1/-2/3", "")),
+            Ok(Route::Invalidate((1, -2, 3).into()))
+        );
+
+        assert_eq!(super::route(&request("POST", "/command", "")), Err(400));
+        assert_eq!(
+            super::route(&request("POST", "/invalidate/1/a/3", "")),
+            Err(400)
+        );
+        assert_eq!(
+            super::route(&Request {
+                websocket_key: Some("abc=".to_string()),
+                ..request("GET", "/events", "")
+            }),
+            Ok(Route::Events("abc=".to_string()))
+        );
+
+        assert_eq!(super::route(&request("GET", "/events", "")), Err(426));
+        assert_eq!(super::route(&request("POST", "/stats", "")), Err(405));
+        assert_eq!(super::route(&request("GET", "/", "")), Err(404));
+    }
+
+    /// Frame as clients send them, masked with the given key.
+    fn client_frame(opcode: u8, payload: &[u8], mask: [u8; 4]) -> Vec<u8> {
+        let mut frame = vec![0x80 | opcode, 0x80 | payload.len() as u8];
+        frame.extend(mask);
+        frame.extend(
+            payload
+                .iter()
+                .enumerate()
+                .map(|(i, byte)| byte ^ mask[i % 4]),
+        );
+        frame
+    }
+
+    #[test]
+    fn json_string() {
+        assert_eq!(super::json_string("tp 0 10 0"), "\"tp 0 10 0\"");
+        assert_eq!(
+            super::json_string("say \"hi\"\\\n\t"),
+            "\"say \\\"hi\\\"\\\\\\n\\u0009\""
+        );
+    }
+
+    #[test]
+    fn handshake() {
+        // Example of RFC 6455
+        assert!(super::handshake("dGhlIHNhbXBsZSBub25jZQ==")
+            .contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+    }
+
+    #[test]
+    fn frames() {
+        let mut written = vec![];
+        write_frame(&mut written, OPCODE_TEXT, b"hi").unwrap();
+        assert_eq!(written, vec![0x81, 2, b'h', b'i']);
+
+        let long = vec![7; 300];
+        let mut written = vec![];
+        write_frame(&mut written, OPCODE_TEXT, &long).unwrap();
+        assert_eq!(&written[..4], &[0x81, 126, 1, 44]);
+        assert_eq!(&written[4..], long.as_slice());
+
+        let frame = client_frame(OPCODE_TEXT, b"where", [1, 2, 3, 4]);
+        assert_eq!(
+            read_frame(&mut frame.as_slice()).unwrap(),
+            (OPCODE_TEXT, b"where".to_vec())
+        );
+
+        // Servers must refuse unmasked frames
+        assert!(read_frame(&mut written.as_slice()).is_err());
+
+        let mut frame = vec![0x81, 0x80 | 127];
+        frame.extend((MAX_BODY_LEN as u64 + 1).to_be_bytes());
+        assert!(read_frame(&mut frame.as_slice()).is_err());
+    }
+
+    #[test]
+    fn websocket() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || listen(listener, sender));
+
+        let mut client = TcpStream::connect(addr).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        client
+            .write_all(
+                b"GET /events HTTP/1.1\r\nUpgrade: websocket\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n",
+            )
+            .unwrap();
+
+        let mut head = vec![0; super::handshake("dGhlIHNhbXBsZSBub25jZQ==").len()];
+        client.read_exact(&mut head).unwrap();
+        assert!(String::from_utf8(head)
+            .unwrap()
+            .starts_with("HTTP/1.1 101 Switching Protocols"));
+
+        let timeout = Duration::from_secs(5);
+        let events = match receiver.recv_timeout(timeout).unwrap() {
+            AdminMessage::Subscribe(events) => events,
+            AdminMessage::Request { route, .. } => panic!("Unexpected request {:?}", route),
+        };
+
+        // Events are pushed as they happen
+        events.send("{}".to_string()).unwrap();
+        let mut frame = [0; 4];
+        client.read_exact(&mut frame).unwrap();
+        assert_eq!(frame, [0x81, 2, b'{', b'}']);
+
+        // Text messages are commands
+        client
+            .write_all(&client_frame(OPCODE_TEXT, b"where\n", [9, 8, 7, 6]))
+            .unwrap();
+        match receiver.recv_timeout(timeout).unwrap() {
+            AdminMessage::Request { route, .. } => {
+                assert_eq!(route, Route::Command("where".to_string()))
+            }
+            AdminMessage::Subscribe(_) => panic!("Unexpected subscription"),
+        }
+
+        client
+            .write_all(&client_frame(OPCODE_CLOSE, &[], [1, 1, 1, 1]))
+            .unwrap();
+        let mut frame = [0; 2];
+        client.read_exact(&mut frame).unwrap();
+        assert_eq!(frame, [0x80 | OPCODE_CLOSE, 0]);
+    }
+}
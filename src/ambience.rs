@@ -0,0 +1,190 @@
+use bevy::{audio::AudioSink, prelude::*};
+use vox::{chunk, heightmap::Heightmap, light::LightWorld, pipeline::WorldOrigin};
+
+use crate::{
+    game_state::GameState,
+    underwater::{self, AudioMuffle, CameraLiquid},
+    MainCamera,
+};
+
+/// Seconds it takes to fully crossfade from an ambience loop to another.
+const FADE_TIME: f32 = 2.0;
+/// Volume of the ambience loop of the current surroundings.
+const AMBIENCE_VOLUME: f32 = 0.5;
+/// Places under a roof are caves when their sky light is below this, so open sided shelters are still surface.
+const CAVE_SKY_LIGHT: u8 = 8;
+
+/// Surroundings of the camera, each with its own ambience loop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ambience {
+    Surface,
+    Cave,
+    Underwater,
+}
+
+impl Ambience {
+    const ALL: [Ambience; 3] = [Ambience::Surface, Ambience::Cave, Ambience::Underwater];
+
+    fn path(&self) -> &'static str {
+        match self {
+            Ambience::Surface => "sounds/ambience/surface.ogg",
+            Ambience::Cave => "sounds/ambience/cave.ogg",
+            Ambience::Underwater => "sounds/ambience/underwater.ogg",
+        }
+    }
+}
+
+/**
+  Ambience loops, which are all played at once from the start and crossfaded by changing their volumes, so
+  coming back to some surroundings doesn't restart their loop.
+*/
+#[derive(Default)]
+struct AmbienceLoops {
+    current: Option<Ambience>,
+    /// Sink and volume of each ambience loop, in the order of [`Ambience::ALL`].
+    loops: Vec<(Handle<AudioSink>, f32)>,
+}
+
+pub struct AmbiencePlugin;
+
+impl Plugin for AmbiencePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AmbienceLoops>()
+            .add_startup_system(play_loops)
+            .add_system(classify_surroundings.after(underwater::detect_camera_liquid))
+            .add_system(crossfade_loops.after(classify_surroundings));
+    }
+}
+
+/**
+  Classifies the given surroundings. Being inside a liquid is underwater, while being under a roof, away from
+  the sky light, is a cave. Anywhere else is the surface.
+*/
+pub fn classify(submerged: bool, exposed: bool, sky_light: u8) -> Ambience {
+    if submerged {
+        Ambience::Underwater
+    } else if !exposed && sky_light < CAVE_SKY_LIGHT {
+        Ambience::Cave
+    } else {
+        Ambience::Surface
+    }
+}
+
+/// Moves `volume` towards `target`, by at most `step`.
+pub fn crossfade(volume: f32, target: f32, step: f32) -> f32 {
+    if volume < target {
+        (volume + step).min(target)
+    } else {
+        (volume - step).max(target)
+    }
+}
+
+fn play_loops(
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    audio_sinks: Res<Assets<AudioSink>>,
+    mut loops: ResMut<AmbienceLoops>,
+) {
+    loops.loops = Ambience::ALL
+        .iter()
+        .map(|ambience| {
+            let source = asset_server.load(ambience.path());
+            let sink = audio.play_with_settings(source, PlaybackSettings::LOOP.with_volume(0.0));
+            (audio_sinks.get_handle(sink), 0.0)
+        })
+        .collect();
+}
+
+/// Classifies the camera surroundings. There are none outside of games, so the ambience goes silent.
+fn classify_surroundings(
+    state: Res<State<GameState>>,
+    liquid: Res<CameraLiquid>,
+    heightmap: Res<Heightmap>,
+    light: Res<LightWorld>,
+    origin: Res<WorldOrigin>,
+    mut loops: ResMut<AmbienceLoops>,
+    q: Query<&Transform, With<MainCamera>>,
+) {
+    let camera = match q.get_single() {
+        Ok(transform) if matches!(state.current(), GameState::InGame | GameState::Paused) => {
+            transform.translation
+        }
+        _ => {
+            loops.current = None;
+            return;
+        }
+    };
+
+    let voxel = origin.to_voxel(camera);
+    let (local, chunk_voxel) = chunk::split_voxel(voxel);
+
+    let ambience = classify(
+        liquid.is_inside(),
+        heightmap.is_exposed(voxel),
+        light.sky(local, chunk_voxel),
+    );
+
+    if loops.current != Some(ambience) {
+        debug!("Ambience changed to {:?}", ambience);
+        loops.current = Some(ambience);
+    }
+}
+
+fn crossfade_loops(
+    time: Res<Time>,
+    muffle: Res<AudioMuffle>,
+    audio_sinks: Res<Assets<AudioSink>>,
+    mut loops: ResMut<AmbienceLoops>,
+) {
+    let current = loops.current;
+    let step = AMBIENCE_VOLUME * time.delta_seconds() / FADE_TIME;
+
+    for (ambience, (sink, volume)) in Ambience::ALL.iter().zip(loops.loops.iter_mut()) {
+        let target = if current == Some(*ambience) {
+            AMBIENCE_VOLUME
+        } else {
+            0.0
+        };
+
+        *volume = crossfade(*volume, target, step);
+
+        // The underwater loop is what's heard through the liquid, so it's the only one not muffled
+        let muffle = if *ambience == Ambience::Underwater {
+            1.0
+        } else {
+            muffle.0
+        };
+
+        // Sinks only exist once their loop is loaded and playing
+        if let Some(sink) = audio_sinks.get(sink.clone()) {
+            sink.set_volume(*volume * muffle);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify() {
+        assert_eq!(super::classify(false, true, 15), Ambience::Surface);
+        assert_eq!(super::classify(false, false, 0), Ambience::Cave);
+
+        // Shelters still get sky light from their open sides
+        assert_eq!(super::classify(false, false, 12), Ambience::Surface);
+
+        assert_eq!(super::classify(true, true, 15), Ambience::Underwater);
+        assert_eq!(super::classify(true, false, 0), Ambience::Underwater);
+    }
+
+    #[test]
+    fn crossfade() {
+        assert_eq!(super::crossfade(0.0, 0.5, 0.25), 0.25);
+        assert_eq!(super::crossfade(0.5, 0.0, 0.25), 0.25);
+
+        // Never goes past the target
+        assert_eq!(super::crossfade(0.0, 0.5, 1.0), 0.5);
+        assert_eq!(super::crossfade(0.5, 0.0, 1.0), 0.0);
+    }
+}
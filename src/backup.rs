@@ -0,0 +1,393 @@
+use bevy::prelude::*;
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use vox::meta::{WorldMeta, META_PATH};
+
+/// Folder holding everything saved about the world.
+const SAVE_DIR: &str = "cache";
+/// Folder holding one folder per backup, named by when it was made, in seconds since the unix epoch.
+const BACKUPS_DIR: &str = "backups";
+/// Folder holding the worlds which aren't being played, named like backups, but never deleted.
+const WORLDS_DIR: &str = "worlds";
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(10 * 60);
+const DEFAULT_COUNT: usize = 5;
+
+/**
+  How often the world is backed up and how many backups are kept. Older backups are deleted as new ones are
+  made. Backups are disabled when `count` is zero.
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BackupConfig {
+    pub interval: Duration,
+    pub count: usize,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            interval: DEFAULT_INTERVAL,
+            count: DEFAULT_COUNT,
+        }
+    }
+}
+
+impl BackupConfig {
+    /// Reads the `--backup-interval <seconds>` and `--backup-count <count>` command line arguments.
+    pub fn from_args(args: &[String]) -> Self {
+        let arg = |flag: &str| {
+            let idx = args.iter().position(|arg| arg == flag)?;
+            let value = args
+                .get(idx + 1)
+                .unwrap_or_else(|| panic!("{} requires a number", flag));
+
+            Some(
+                value
+                    .parse::<u64>()
+                    .unwrap_or_else(|_| panic!("Invalid number for {}: {}", flag, value)),
+            )
+        };
+
+        let default = Self::default();
+
+        Self {
+            interval: arg("--backup-interval").map_or(default.interval, Duration::from_secs),
+            count: arg("--backup-count").map_or(default.count, |count| count as usize),
+        }
+    }
+}
+
+pub struct BackupPlugin;
+
+impl Plugin for BackupPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BackupConfig>().add_system(backup_world);
+    }
+}
+
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    std::fs::create_dir_all(to)?;
+
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), target)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Names of all backups on the given folder, from the oldest to the newest.
+pub fn list(backups: &Path) -> io::Result<Vec<String>> {
+    if !backups.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut names = std::fs::read_dir(backups)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter_map(|name| name.parse::<u64>().ok())
+        .collect::<Vec<_>>();
+
+    names.sort_unstable();
+
+    Ok(names.into_iter().map(|name| name.to_string()).collect())
+}
+
+/**
+  Copies the save folder to a new backup, named by the given time, in seconds since the unix epoch.
+  Then deletes the oldest backups, so only `count` are kept.
+*/
+pub fn create(save: &Path, backups: &Path, time: u64, count: usize) -> io::Result<PathBuf> {
+    let path = backups.join(time.to_string());
+    copy_dir(save, &path)?;
+
+    let names = list(backups)?;
+    for name in names.iter().take(names.len().saturating_sub(count)) {
+        std::fs::remove_dir_all(backups.join(name))?;
+    }
+
+    Ok(path)
+}
+
+/**
+  Replaces the save folder by the given backup, or by the newest one when none is given. The current save
+  folder is backed up first, so restoring can be undone. Returns the name of the restored backup.
+*/
+pub fn restore(save: &Path, backups: &Path, name: Option<&str>, time: u64) -> io::Result<String> {
+    let names = list(backups)?;
+
+    let name = match name {
+        Some(name) if names.iter().any(|other| other == name) => name.to_string(),
+        Some(name) => {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Backup {} not found", name),
+            ))
+        }
+        None => names.last().cloned().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "There are no backups to restore")
+        })?,
+    };
+
+    if save.exists() {
+        // Nothing is deleted, since the restored backup may be an older one
+        copy_dir(save, &backups.join(time.to_string()))?;
+        std::fs::remove_dir_all(save)?;
+    }
+
+    copy_dir(&backups.join(&name), save)?;
+
+    Ok(name)
+}
+
+/**
+  Backs up the save folder and deletes it, so a new world can take its place. Older backups are deleted, so
+  only `count` are kept, but this one is always kept. Returns where the backup is, or none when there was
+  nothing saved.
+*/
+pub fn archive(
+    save: &Path,
+    backups: &Path,
+    time: u64,
+    count: usize,
+) -> io::Result<Option<PathBuf>> {
+    if !save.exists() {
+        return Ok(None);
+    }
+
+    let path = create(save, backups, time, count.max(1))?;
+    std::fs::remove_dir_all(save)?;
+
+    Ok(Some(path))
+}
+
+/**
+  Saved worlds on the given folder, which aren't being played, from the newest to the oldest. Returns the
+  name of their folders along with their metadata.
+*/
+pub fn saved_worlds(worlds: &Path) -> io::Result<Vec<(String, WorldMeta)>> {
+    let meta_file = Path::new(META_PATH)
+        .file_name()
+        .expect("META_PATH must have a file name");
+
+    Ok(list(worlds)?
+        .into_iter()
+        .rev()
+        .map(|name| (worlds.join(&name).join(meta_file), name))
+        .filter(|(path, _)| path.exists())
+        .map(|(path, name)| (name, WorldMeta::load(&path)))
+        .collect())
+}
+
+/**
+  Plays the given saved world, setting the one being played aside on the saved worlds. Unlike restoring a
+  backup, the loaded world is taken out of the saved worlds, so it isn't listed twice.
+*/
+pub fn load(save: &Path, worlds: &Path, name: &str, time: u64) -> io::Result<()> {
+    restore(save, worlds, Some(name), time)?;
+    std::fs::remove_dir_all(worlds.join(name))
+}
+
+/// Sets the world save folder aside on the saved worlds, so a new world can take its place. See [`archive`].
+pub fn shelve_world() -> io::Result<Option<PathBuf>> {
+    archive(
+        Path::new(SAVE_DIR),
+        Path::new(WORLDS_DIR),
+        now(),
+        usize::MAX,
+    )
+}
+
+/// Saved worlds which can be loaded, see [`saved_worlds`].
+pub fn list_worlds() -> io::Result<Vec<(String, WorldMeta)>> {
+    saved_worlds(Path::new(WORLDS_DIR))
+}
+
+/// Loads the given saved world, see [`load`].
+pub fn load_world(name: &str) -> io::Result<()> {
+    load(Path::new(SAVE_DIR), Path::new(WORLDS_DIR), name, now())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/**
+  Runs the `restore [backup]` command line subcommand, which restores the given backup, or the newest one.
+  The game isn't started, since the save folder must not be in use while it's replaced.
+*/
+pub fn restore_command(args: &[String]) {
+    let name = args.first().map(String::as_str);
+
+    match restore(Path::new(SAVE_DIR), Path::new(BACKUPS_DIR), name, now()) {
+        Ok(name) => println!("Restored backup {}", name),
+        Err(err) => {
+            let names = list(Path::new(BACKUPS_DIR)).unwrap_or_default();
+
+            eprintln!("Unable to restore backup: {}", err);
+            eprintln!("Available backups: {}", names.join(", "));
+            std::process::exit(1);
+        }
+    }
+}
+
+/**
+  Backs up the save folder on the configured interval. Copying is done on another thread, so big worlds
+  don't stall the game.
+*/
+fn backup_world(mut last: Local<f64>, time: Res<Time>, config: Res<BackupConfig>) {
+    let elapsed = time.seconds_since_startup();
+
+    if config.count == 0 || elapsed - *last < config.interval.as_secs_f64() {
+        return;
+    }
+
+    *last = elapsed;
+
+    if !Path::new(SAVE_DIR).exists() {
+        return;
+    }
+
+    let count = config.count;
+    std::thread::spawn(move || {
+        match create(Path::new(SAVE_DIR), Path::new(BACKUPS_DIR), now(), count) {
+            Ok(path) => info!("World backed up to {}", path.display()),
+            Err(err) => error!("Failed to back up world: {}", err),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, text: &str) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, text).unwrap();
+    }
+
+    fn read(path: &Path) -> String {
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn backup_config() {
+        let args = ["eterno", "--backup-interval", "60", "--backup-count", "2"]
+            .map(String::from)
+            .to_vec();
+
+        assert_eq!(
+            BackupConfig::from_args(&args),
+            BackupConfig {
+                interval: Duration::from_secs(60),
+                count: 2,
+            }
+        );
+        assert_eq!(BackupConfig::from_args(&[]), BackupConfig::default());
+    }
+
+    #[test]
+    fn create_restore() {
+        let root = std::env::temp_dir().join("eterno_backup_create_restore");
+        let _ = std::fs::remove_dir_all(&root);
+
+        let (save, backups) = (root.join("save"), root.join("backups"));
+        write(&save.join("world.ron"), "first");
+        write(&save.join("chunks/0_0_0.bin"), "chunk");
+
+        create(&save, &backups, 10, 2).unwrap();
+        write(&save.join("world.ron"), "second");
+        create(&save, &backups, 20, 2).unwrap();
+        write(&save.join("world.ron"), "third");
+        create(&save, &backups, 30, 2).unwrap();
+
+        // Only the newest ones are kept
+        assert_eq!(list(&backups).unwrap(), vec!["20", "30"]);
+        assert_eq!(read(&backups.join("30/chunks/0_0_0.bin")), "chunk");
+
+        write(&save.join("world.ron"), "griefed");
+        assert_eq!(restore(&save, &backups, Some("20"), 40).unwrap(), "20");
+        assert_eq!(read(&save.join("world.ron")), "second");
+        assert_eq!(read(&backups.join("40/world.ron")), "griefed");
+
+        assert_eq!(restore(&save, &backups, None, 50).unwrap(), "40");
+        assert_eq!(read(&save.join("world.ron")), "griefed");
+
+        assert!(restore(&save, &backups, Some("1"), 60).is_err());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn archive() {
+        let root = std::env::temp_dir().join("eterno_backup_archive");
+        let _ = std::fs::remove_dir_all(&root);
+
+        let (save, backups) = (root.join("save"), root.join("backups"));
+        assert_eq!(super::archive(&save, &backups, 10, 0).unwrap(), None);
+
+        write(&save.join("world.ron"), "old");
+
+        // Kept even with backups disabled
+        let path = super::archive(&save, &backups, 20, 0).unwrap().unwrap();
+        assert_eq!(read(&path.join("world.ron")), "old");
+        assert!(!save.exists());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn saved_worlds() {
+        let root = std::env::temp_dir().join("eterno_backup_saved_worlds");
+        let _ = std::fs::remove_dir_all(&root);
+
+        let (save, worlds) = (root.join("save"), root.join("worlds"));
+        let meta = |name: &str| WorldMeta {
+            name: name.to_string(),
+            ..Default::default()
+        };
+
+        meta("first").save(&save.join("world.ron"));
+        super::archive(&save, &worlds, 10, usize::MAX).unwrap();
+        meta("second").save(&save.join("world.ron"));
+        super::archive(&save, &worlds, 20, usize::MAX).unwrap();
+
+        // Folders without metadata aren't worlds
+        std::fs::create_dir_all(worlds.join("30")).unwrap();
+
+        assert_eq!(
+            super::saved_worlds(&worlds).unwrap(),
+            vec![
+                ("20".to_string(), meta("second")),
+                ("10".to_string(), meta("first"))
+            ]
+        );
+
+        // Loading a world sets aside none, since nothing is being played
+        load(&save, &worlds, "10", 40).unwrap();
+        assert_eq!(WorldMeta::load(&save.join("world.ron")), meta("first"));
+
+        meta("third").save(&save.join("world.ron"));
+        load(&save, &worlds, "20", 50).unwrap();
+        assert_eq!(WorldMeta::load(&save.join("world.ron")), meta("second"));
+        assert_eq!(
+            super::saved_worlds(&worlds).unwrap(),
+            vec![("50".to_string(), meta("third"))]
+        );
+
+        assert!(load(&save, &worlds, "10", 60).is_err());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,413 @@
+use bevy::{pbr::NotShadowCaster, prelude::*};
+use vox::{
+    bounds::Bounds,
+    chunk,
+    edit::{self, BrushShape, Region, Structure},
+    pipeline::{SetVoxel, WorldOrigin},
+    voxel::{Kind, KindRegistry},
+    world::VoxWorld,
+};
+
+use crate::{
+    chat::Chat,
+    console::Console,
+    selection::{self, SelectedKind, VoxelTarget},
+    signs::Signs,
+};
+
+const TOGGLE_KEY: KeyCode = KeyCode::B;
+/// While held, brushes erase voxels instead of filling them.
+const ERASE_KEY: KeyCode = KeyCode::LControl;
+
+const DEFAULT_BRUSH_RADIUS: i32 = 3;
+const MAX_BRUSH_RADIUS: i32 = 16;
+
+/// How much the selection box grows past the selected voxels, so its faces doesn't fight with voxel faces.
+const BOX_MARGIN: f32 = 0.02;
+
+/**
+  Operations over the selected region, issued through the console.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuildCommand {
+    /// Sets all selected voxels to the given kind.
+    Fill(Kind),
+    /// Sets the faces of the selection to the given kind and empties its inside.
+    Hollow(Kind),
+    /// Replaces the first kind by the second one on the selection.
+    Replace(Kind, Kind),
+    /// Copies the selection to the clipboard.
+    Copy,
+    /// Pastes the clipboard where a voxel would be placed.
+    Paste,
+    /// Saves the selection as a structure template with the given name.
+    Save(String),
+    /// Sets the brush used by left clicks, or goes back to picking corners.
+    Brush(Option<BrushShape>),
+    /// Sets the brush radius.
+    Radius(i32),
+}
+
+/**
+  Builder mode state, toggled by the `B` key. While active, left clicks pick the corners of the selection,
+  or apply the brush when there is one.
+*/
+pub struct Builder {
+    pub active: bool,
+    corners: [Option<IVec3>; 2],
+    /// Which corner is picked by the next click.
+    next: usize,
+    clipboard: Option<Structure>,
+    brush: Option<BrushShape>,
+    radius: i32,
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self {
+            active: false,
+            corners: [None; 2],
+            next: 0,
+            clipboard: None,
+            brush: None,
+            radius: DEFAULT_BRUSH_RADIUS,
+        }
+    }
+}
+
+impl Builder {
+    /// Sets the next corner of the selection. After both corners are set, a new selection is started.
+    pub fn pick(&mut self, position: IVec3) {
+        if self.next == 0 {
+            self.corners = [Some(position), None];
+        } else {
+            self.corners[1] = Some(position);
+        }
+
+        self.next = (self.next + 1) % self.corners.len();
+    }
+
+    /// Region between both corners, once they are picked.
+    pub fn region(&self) -> Option<Region> {
+        match self.corners {
+            [Some(a), Some(b)] => Some(Region::from_corners(a, b)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Component)]
+struct SelectionBox;
+
+pub struct BuilderPlugin;
+
+impl Plugin for BuilderPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Builder>()
+            .add_event::<BuildCommand>()
+            .add_startup_system(setup_selection_box)
+            .add_system(toggle_builder)
+            .add_system(pick_corner.after(toggle_builder))
+            .add_system(apply_brush.after(toggle_builder))
+            .add_system(update_selection_box.after(pick_corner))
+            .add_system(run_commands);
+    }
+}
+
+fn parse_kind(arg: &str) -> Result<Kind, String> {
+    arg.parse::<u16>()
+        .map(Kind::from)
+        .map_err(|_| format!("Invalid kind: {}", arg))
+}
+
+/**
+  Parses the arguments of the builder command with the given name.
+*/
+pub fn parse(name: &str, args: &[&str]) -> Result<BuildCommand, String> {
+    match (name, args) {
+        ("fill", [kind]) => parse_kind(kind).map(BuildCommand::Fill),
+        ("hollow", [kind]) => parse_kind(kind).map(BuildCommand::Hollow),
+        ("replace", [from, to]) => Ok(BuildCommand::Replace(parse_kind(from)?, parse_kind(to)?)),
+        ("copy", []) => Ok(BuildCommand::Copy),
+        ("paste", []) => Ok(BuildCommand::Paste),
+        ("save", [name]) => Ok(BuildCommand::Save(name.to_string())),
+        ("brush", [shape]) => match *shape {
+            "sphere" => Ok(BuildCommand::Brush(Some(BrushShape::Sphere))),
+            "cylinder" => Ok(BuildCommand::Brush(Some(BrushShape::Cylinder))),
+            "smooth" => Ok(BuildCommand::Brush(Some(BrushShape::Smooth))),
+            "off" => Ok(BuildCommand::Brush(None)),
+            _ => Err(format!("Unknown brush: {}", shape)),
+        },
+        ("radius", [radius]) => match radius.parse::<i32>() {
+            Ok(radius) if (1..=MAX_BRUSH_RADIUS).contains(&radius) => {
+                Ok(BuildCommand::Radius(radius))
+            }
+            _ => Err(format!("Radius must be between 1 and {}", MAX_BRUSH_RADIUS)),
+        },
+        _ => Err(format!("Invalid arguments for {}", name)),
+    }
+}
+
+fn setup_selection_box(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands
+        .spawn_bundle(PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Cube { size: 1.0 })),
+            material: materials.add(StandardMaterial {
+                base_color: Color::rgba(0.3, 0.6, 1.0, 0.25),
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                ..Default::default()
+            }),
+            visibility: Visibility { is_visible: false },
+            ..Default::default()
+        })
+        .insert(NotShadowCaster)
+        .insert(SelectionBox);
+}
+
+fn toggle_builder(
+    console: Res<Console>,
+    chat: Res<Chat>,
+    signs: Res<Signs>,
+    keyboard: Res<Input<KeyCode>>,
+    mut builder: ResMut<Builder>,
+) {
+    if !console.visible && !chat.open && !signs.is_editing() && keyboard.just_pressed(TOGGLE_KEY) {
+        builder.active = !builder.active;
+        info!("Builder mode: {}", builder.active);
+    }
+}
+
+fn pick_corner(
+    target: Res<VoxelTarget>,
+    mouse: Res<Input<MouseButton>>,
+    mut builder: ResMut<Builder>,
+) {
+    if !builder.active || builder.brush.is_some() || !mouse.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    if let Some(target) = target.0 {
+        builder.pick(chunk::join_voxel(target.chunk, target.voxel));
+    }
+}
+
+/**
+  Applies the brush around the targeted voxel, filling with the selected kind or erasing.
+*/
+fn apply_brush(
+    world: Res<VoxWorld>,
+    builder: Res<Builder>,
+    selected: Res<SelectedKind>,
+    target: Res<VoxelTarget>,
+    mouse: Res<Input<MouseButton>>,
+    keyboard: Res<Input<KeyCode>>,
+    mut writer: EventWriter<SetVoxel>,
+) {
+    if !builder.active || !mouse.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    let (shape, target) = match (builder.brush, target.0) {
+        (Some(shape), Some(target)) => (shape, target),
+        _ => return,
+    };
+
+    let kind = if keyboard.pressed(ERASE_KEY) {
+        Kind::default()
+    } else {
+        selected.0
+    };
+
+    let center = chunk::join_voxel(target.chunk, target.voxel);
+    let edits = edit::brush(&world, shape, center, builder.radius, kind);
+
+    debug!("Brush {:?} changed {} voxels", shape, edits.len());
+    writer.send_batch(edits.into_iter());
+}
+
+/**
+  Render space transform of a box around the given region.
+*/
+pub fn box_transform(origin: &WorldOrigin, region: &Region) -> Transform {
+    let (local, voxel) = chunk::split_voxel(region.min);
+    let min = origin.voxel_bounds(local, voxel).min;
+    let bounds = Bounds::new(min, min + region.size().as_vec3());
+
+    Transform {
+        translation: bounds.center(),
+        scale: bounds.size() + Vec3::splat(BOX_MARGIN * 2.0),
+        ..Default::default()
+    }
+}
+
+fn update_selection_box(
+    origin: Res<WorldOrigin>,
+    builder: Res<Builder>,
+    mut q: Query<(&mut Transform, &mut Visibility), With<SelectionBox>>,
+) {
+    if !builder.is_changed() && !origin.is_changed() {
+        return;
+    }
+
+    for (mut transform, mut visibility) in q.iter_mut() {
+        match builder.region() {
+            Some(region) if builder.active => {
+                *transform = box_transform(&origin, &region);
+                visibility.is_visible = true;
+            }
+            _ => visibility.is_visible = false,
+        }
+    }
+}
+
+fn run_commands(
+    world: Res<VoxWorld>,
+    registry: Res<KindRegistry>,
+    target: Res<VoxelTarget>,
+    mut builder: ResMut<Builder>,
+    mut console: ResMut<Console>,
+    mut reader: EventReader<BuildCommand>,
+    mut writer: EventWriter<SetVoxel>,
+) {
+    for command in reader.iter() {
+        let is_known = |kind: &Kind| kind.is_empty() || registry.get(*kind).is_some();
+
+        let edits = match (command, builder.region()) {
+            (BuildCommand::Brush(shape), _) => {
+                builder.brush = *shape;
+                console.print(format!("Brush: {:?}", shape));
+                continue;
+            }
+            (BuildCommand::Radius(radius), _) => {
+                builder.radius = *radius;
+                console.print(format!("Brush radius: {}", radius));
+                continue;
+            }
+            (BuildCommand::Paste, _) => match (&builder.clipboard, target.0) {
+                (Some(structure), Some(target)) => {
+                    let (local, voxel) = selection::placement(&target);
+                    structure.paste(&world, chunk::join_voxel(local, voxel))
+                }
+                (None, _) => {
+                    console.print("Clipboard is empty".to_string());
+                    continue;
+                }
+                (_, None) => {
+                    console.print("No target to paste at".to_string());
+                    continue;
+                }
+            },
+            (_, None) => {
+                console.print("Select two corners first".to_string());
+                continue;
+            }
+            (BuildCommand::Fill(kind), _) | (BuildCommand::Hollow(kind), _) if !is_known(kind) => {
+                console.print(format!("Unknown kind: {}", kind.id()));
+                continue;
+            }
+            (BuildCommand::Replace(from, to), _) if !is_known(from) || !is_known(to) => {
+                console.print("Unknown kind".to_string());
+                continue;
+            }
+            (BuildCommand::Fill(kind), Some(region)) => edit::fill(&world, &region, *kind),
+            (BuildCommand::Hollow(kind), Some(region)) => edit::hollow(&world, &region, *kind),
+            (BuildCommand::Replace(from, to), Some(region)) => {
+                edit::replace(&world, &region, *from, *to)
+            }
+            (BuildCommand::Copy, Some(region)) => {
+                builder.clipboard = Some(Structure::copy(&world, &region));
+                console.print(format!("Copied {} voxels", region.positions().count()));
+                continue;
+            }
+            (BuildCommand::Save(name), Some(region)) => {
+                let path = edit::structure_path(name);
+                Structure::copy(&world, &region).save(&path);
+                console.print(format!("Saved structure to {}", path.display()));
+                continue;
+            }
+        };
+
+        console.print(format!("Changed {} voxels", edits.len()));
+        writer.send_batch(edits.into_iter());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            super::parse("fill", &["3"]),
+            Ok(BuildCommand::Fill(3.into()))
+        );
+        assert_eq!(
+            super::parse("replace", &["1", "0"]),
+            Ok(BuildCommand::Replace(1.into(), 0.into()))
+        );
+        assert_eq!(
+            super::parse("save", &["house"]),
+            Ok(BuildCommand::Save("house".to_string()))
+        );
+        assert_eq!(super::parse("copy", &[]), Ok(BuildCommand::Copy));
+
+        assert!(super::parse("fill", &[]).is_err());
+        assert!(super::parse("hollow", &["a"]).is_err());
+        assert!(super::parse("replace", &["1"]).is_err());
+        assert_eq!(
+            super::parse("brush", &["smooth"]),
+            Ok(BuildCommand::Brush(Some(BrushShape::Smooth)))
+        );
+        assert_eq!(
+            super::parse("brush", &["off"]),
+            Ok(BuildCommand::Brush(None))
+        );
+        assert_eq!(super::parse("radius", &["5"]), Ok(BuildCommand::Radius(5)));
+
+        assert!(super::parse("paste", &["1"]).is_err());
+        assert!(super::parse("brush", &["cone"]).is_err());
+        assert!(super::parse("radius", &["0"]).is_err());
+        assert!(super::parse("radius", &["100"]).is_err());
+    }
+
+    #[test]
+    fn pick() {
+        let mut builder = Builder::default();
+        assert_eq!(builder.region(), None);
+
+        builder.pick((1, 2, 3).into());
+        assert_eq!(builder.region(), None);
+
+        builder.pick((-1, 0, 5).into());
+        assert_eq!(
+            builder.region(),
+            Some(Region::from_corners((1, 2, 3).into(), (-1, 0, 5).into()))
+        );
+
+        // A third click starts a new selection
+        builder.pick((0, 0, 0).into());
+        assert_eq!(builder.region(), None);
+    }
+
+    #[test]
+    fn box_transform() {
+        let origin = WorldOrigin((100, 0, 0).into());
+        let region = Region::from_corners(
+            IVec3::new(100 * chunk::AXIS_SIZE as i32, 0, 0),
+            IVec3::new(100 * chunk::AXIS_SIZE as i32 + 1, 2, 0),
+        );
+
+        let transform = super::box_transform(&origin, &region);
+        assert_eq!(transform.translation, Vec3::new(1.0, 1.5, 0.5));
+        assert_eq!(
+            transform.scale,
+            Vec3::new(2.0, 3.0, 1.0) + Vec3::splat(BOX_MARGIN * 2.0)
+        );
+    }
+}
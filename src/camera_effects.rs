@@ -0,0 +1,354 @@
+use bevy::{prelude::*, transform::TransformSystem};
+use vox::{
+    explosion::Explosion,
+    pipeline::{Exploded, WorldOrigin},
+};
+
+use crate::{
+    console::Console,
+    underwater::{self, CameraLiquid},
+    MainCamera,
+};
+
+/// How much trauma is lost each second.
+const TRAUMA_DECAY: f32 = 1.0;
+/// Trauma added by explosions right on the camera. It fades out until `SHAKE_RANGE` times the radius.
+const EXPLOSION_TRAUMA: f32 = 1.0;
+const SHAKE_RANGE: f32 = 4.0;
+/// How fast the camera shakes, in radians per second of the noise waves.
+const SHAKE_FREQUENCY: f32 = 40.0;
+
+/// Bob cycles per voxel walked.
+const BOB_FREQUENCY: f32 = 0.8;
+/// Horizontal speed, in voxels per second, at which the bobbing is the strongest.
+const BOB_FULL_SPEED: f32 = 6.0;
+
+/// Camera effects settings, changed by the `bobbing` and `shake` console commands.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraEffectsSettings {
+    pub bobbing: bool,
+    pub shake: bool,
+    /// Highest the camera bobs up and down, in voxels.
+    pub bob_height: f32,
+    /// Farthest the camera is moved by the strongest shake, in voxels.
+    pub shake_offset: f32,
+    /// Most the camera rolls on the strongest shake, in radians.
+    pub shake_angle: f32,
+}
+
+impl Default for CameraEffectsSettings {
+    fn default() -> Self {
+        Self {
+            bobbing: true,
+            shake: true,
+            bob_height: 0.05,
+            shake_offset: 0.3,
+            shake_angle: 0.05,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraEffectCommand {
+    Bobbing(bool),
+    Shake(bool),
+}
+
+/**
+  Trauma based camera shake. Things like explosions add trauma, which shakes the camera by its square, so
+  small amounts are barely noticed, and fades out over time.
+*/
+#[derive(Default)]
+pub struct CameraShake {
+    trauma: f32,
+}
+
+impl CameraShake {
+    /// Adds trauma, from 0 to 1, to the camera. Trauma is capped at 1.
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).clamp(0.0, 1.0);
+    }
+
+    /// How strong the shake is, from 0 to 1.
+    pub fn shake(&self) -> f32 {
+        self.trauma * self.trauma
+    }
+
+    fn decay(&mut self, dt: f32) {
+        self.trauma = (self.trauma - TRAUMA_DECAY * dt).max(0.0);
+    }
+}
+
+/// Offsets applied to the camera on this frame, which are taken back on the start of the next one.
+#[derive(Default)]
+struct AppliedOffset {
+    translation: Vec3,
+    rotation: Quat,
+    /// Camera position, without any offsets, on the last frame.
+    last_position: Option<Vec3>,
+    /// How far along the bob cycle the camera is, in radians.
+    bob_phase: f32,
+}
+
+pub struct CameraEffectsPlugin;
+
+impl Plugin for CameraEffectsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraEffectsSettings>()
+            .init_resource::<CameraShake>()
+            .init_resource::<AppliedOffset>()
+            .add_event::<CameraEffectCommand>()
+            .add_system_to_stage(CoreStage::PreUpdate, remove_offsets)
+            .add_system(run_camera_effect_commands)
+            .add_system(shake_on_explosions)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                apply_offsets.before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+/// Parses the `bobbing` and `shake` commands, which turn camera effects on or off.
+pub fn parse(name: &str, args: &[&str]) -> Result<CameraEffectCommand, String> {
+    let enabled = match args {
+        ["on"] => true,
+        ["off"] => false,
+        _ => return Err(format!("Usage: {} on|off", name)),
+    };
+
+    match name {
+        "bobbing" => Ok(CameraEffectCommand::Bobbing(enabled)),
+        "shake" => Ok(CameraEffectCommand::Shake(enabled)),
+        _ => Err(format!("Unknown camera effect: {}", name)),
+    }
+}
+
+/// Trauma added by the explosion to a camera at the given world position.
+pub fn explosion_trauma(explosion: &Explosion, position: Vec3) -> f32 {
+    let range = explosion.radius * SHAKE_RANGE;
+    let distance = explosion.center.distance(position);
+
+    (1.0 - distance / range).max(0.0) * EXPLOSION_TRAUMA
+}
+
+/**
+  Camera offset and roll of a shake as strong as `shake`, at the given time, in seconds. Each axis follows
+  its own mix of waves, so the shake doesn't look like it repeats.
+*/
+pub fn shake_offset(shake: f32, time: f32, settings: &CameraEffectsSettings) -> (Vec3, f32) {
+    let wave = |seed: f32| {
+        let t = time * SHAKE_FREQUENCY;
+        ((t + seed).sin() + (t * 1.7 + seed * 3.1).sin() * 0.5) / 1.5
+    };
+
+    let offset = Vec3::new(wave(0.0), wave(11.0), wave(23.0)) * settings.shake_offset * shake;
+    let roll = wave(37.0) * settings.shake_angle * shake;
+
+    (offset, roll)
+}
+
+/**
+  Camera offset of the bobbing at the given phase, when moving at the given horizontal speed. The camera
+  bobs up and down twice each cycle, once for each step, and sways sideways once.
+*/
+pub fn bob_offset(phase: f32, speed: f32, settings: &CameraEffectsSettings) -> Vec3 {
+    let strength = (speed / BOB_FULL_SPEED).min(1.0) * settings.bob_height;
+
+    Vec3::new(phase.sin() * 0.5, (phase * 2.0).sin().abs(), 0.0) * strength
+}
+
+fn run_camera_effect_commands(
+    mut settings: ResMut<CameraEffectsSettings>,
+    mut console: ResMut<Console>,
+    mut reader: EventReader<CameraEffectCommand>,
+) {
+    for command in reader.iter() {
+        let (name, enabled) = match *command {
+            CameraEffectCommand::Bobbing(enabled) => {
+                settings.bobbing = enabled;
+                ("View bobbing", enabled)
+            }
+            CameraEffectCommand::Shake(enabled) => {
+                settings.shake = enabled;
+                ("Camera shake", enabled)
+            }
+        };
+
+        let state = if enabled { "enabled" } else { "disabled" };
+        console.print(format!("{} {}", name, state));
+    }
+}
+
+fn shake_on_explosions(
+    origin: Res<WorldOrigin>,
+    mut shake: ResMut<CameraShake>,
+    mut reader: EventReader<Exploded>,
+    q: Query<&Transform, With<MainCamera>>,
+) {
+    let transform = match q.get_single() {
+        Ok(transform) => transform,
+        Err(_) => return,
+    };
+
+    let position = origin.to_world(transform.translation);
+
+    for Exploded { explosion, .. } in reader.iter() {
+        shake.add_trauma(explosion_trauma(explosion, position));
+    }
+}
+
+/// Takes back the offsets applied on the last frame, so nothing else sees them.
+fn remove_offsets(
+    mut applied: ResMut<AppliedOffset>,
+    mut q: Query<&mut Transform, With<MainCamera>>,
+) {
+    if let Ok(mut transform) = q.get_single_mut() {
+        transform.translation -= applied.translation;
+        transform.rotation *= applied.rotation.inverse();
+    }
+
+    applied.translation = Vec3::ZERO;
+    applied.rotation = Quat::IDENTITY;
+}
+
+/**
+  Applies the view bobbing, camera shake and underwater sway, right before transforms are propagated, so
+  they're only seen on the rendered frame.
+*/
+fn apply_offsets(
+    time: Res<Time>,
+    settings: Res<CameraEffectsSettings>,
+    liquid: Res<CameraLiquid>,
+    mut shake: ResMut<CameraShake>,
+    mut applied: ResMut<AppliedOffset>,
+    mut q: Query<&mut Transform, With<MainCamera>>,
+) {
+    let mut transform = match q.get_single_mut() {
+        Ok(transform) => transform,
+        Err(_) => return,
+    };
+
+    let dt = time.delta_seconds();
+    shake.decay(dt);
+
+    let moved = applied
+        .last_position
+        .map(|last| transform.translation - last)
+        .unwrap_or_default();
+    applied.last_position = Some(transform.translation);
+
+    let speed = if dt > 0.0 {
+        Vec2::new(moved.x, moved.z).length() / dt
+    } else {
+        0.0
+    };
+
+    // Teleports and streaming recenters aren't walking
+    let speed = if speed > BOB_FULL_SPEED * 10.0 {
+        0.0
+    } else {
+        speed
+    };
+
+    let mut translation = Vec3::ZERO;
+    let mut rotation = Quat::IDENTITY;
+
+    if settings.bobbing && speed > 0.0 {
+        applied.bob_phase = (applied.bob_phase
+            + speed * dt * BOB_FREQUENCY * std::f32::consts::TAU)
+            % std::f32::consts::TAU;
+
+        let bob = bob_offset(applied.bob_phase, speed, &settings);
+        translation += transform.rotation * Vec3::X * bob.x + Vec3::Y * bob.y;
+    }
+
+    if settings.shake && shake.shake() > 0.0 {
+        let (offset, roll) = shake_offset(
+            shake.shake(),
+            time.seconds_since_startup() as f32,
+            &settings,
+        );
+        translation += offset;
+        rotation = Quat::from_rotation_z(roll);
+    }
+
+    // Liquids bend the light, so the view slowly sways while inside them
+    if liquid.is_inside() {
+        rotation *= Quat::from_rotation_z(underwater::sway(time.seconds_since_startup() as f32));
+    }
+
+    transform.translation += translation;
+    transform.rotation *= rotation;
+    applied.translation = translation;
+    applied.rotation = rotation;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            super::parse("bobbing", &["off"]),
+            Ok(CameraEffectCommand::Bobbing(false))
+        );
+        assert_eq!(
+            super::parse("shake", &["on"]),
+            Ok(CameraEffectCommand::Shake(true))
+        );
+        assert!(super::parse("shake", &[]).is_err());
+        assert!(super::parse("shake", &["maybe"]).is_err());
+    }
+
+    #[test]
+    fn camera_shake() {
+        let mut shake = CameraShake::default();
+
+        shake.add_trauma(0.5);
+        assert_eq!(shake.shake(), 0.25);
+
+        shake.add_trauma(2.0);
+        assert_eq!(shake.shake(), 1.0);
+
+        shake.decay(0.5 / TRAUMA_DECAY);
+        assert_eq!(shake.shake(), 0.25);
+        shake.decay(10.0);
+        assert_eq!(shake.shake(), 0.0);
+    }
+
+    #[test]
+    fn explosion_trauma() {
+        let explosion = Explosion::new(Vec3::ZERO, 4.0, 4.0);
+
+        assert_eq!(
+            super::explosion_trauma(&explosion, Vec3::ZERO),
+            EXPLOSION_TRAUMA
+        );
+        assert!(super::explosion_trauma(&explosion, Vec3::X * 8.0) < EXPLOSION_TRAUMA);
+        assert_eq!(super::explosion_trauma(&explosion, Vec3::X * 100.0), 0.0);
+    }
+
+    #[test]
+    fn offsets() {
+        let settings = CameraEffectsSettings::default();
+
+        // No shake or bobbing when still
+        assert_eq!(super::shake_offset(0.0, 1.0, &settings), (Vec3::ZERO, 0.0));
+        assert_eq!(super::bob_offset(1.0, 0.0, &settings), Vec3::ZERO);
+
+        let (offset, roll) = super::shake_offset(1.0, 1.0, &settings);
+        assert!(offset.abs().max_element() <= settings.shake_offset);
+        assert!(roll.abs() <= settings.shake_angle);
+
+        // Bobbing gets stronger with speed, up to a limit
+        let slow = super::bob_offset(1.0, 1.0, &settings);
+        let fast = super::bob_offset(1.0, BOB_FULL_SPEED, &settings);
+        assert!(fast.y > slow.y);
+        assert_eq!(
+            super::bob_offset(1.0, BOB_FULL_SPEED * 2.0, &settings),
+            fast
+        );
+        assert!(fast.y <= settings.bob_height);
+    }
+}
@@ -0,0 +1,287 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use vox::pipeline::WorldOrigin;
+
+use crate::{console::Console, screenshot::Screenshots, MainCamera};
+
+/// Folder camera paths are loaded from, as `<name>.ron`.
+const CAMERA_PATHS_DIR: &str = "camera_paths";
+/// Folder recorded fly-throughs are saved to, one folder per camera path.
+const RECORDINGS_DIR: &str = "screenshots";
+/// Frame rate of recorded fly-throughs. It doesn't depend on how fast frames are rendered, so recordings
+/// of the same path always have the same frames.
+const RECORD_FPS: f32 = 30.0;
+
+/// Where the camera is and what it looks at, both in world space, at the given time, in seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Keyframe {
+    pub time: f32,
+    pub position: Vec3,
+    pub look_at: Vec3,
+}
+
+/**
+  Scripted camera movement, which passes through all keyframes in time order. The camera moves smoothly
+  between keyframes, following a Catmull-Rom spline.
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CameraPath {
+    pub keyframes: Vec<Keyframe>,
+}
+
+fn catmull_rom(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f32) -> Vec3 {
+    let (t2, t3) = (t * t, t * t * t);
+
+    0.5 * (2.0 * p1
+        + (p2 - p0) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+}
+
+impl CameraPath {
+    /// Loads the camera path with the given name. Paths are checked, since they're written by hand.
+    pub fn load(name: &str) -> Result<Self, String> {
+        let path = Path::new(CAMERA_PATHS_DIR).join(name).with_extension("ron");
+
+        let file = std::fs::File::open(&path)
+            .map_err(|_| format!("Unable to open file {}", path.display()))?;
+
+        let camera_path: Self = ron::de::from_reader(file)
+            .map_err(|err| format!("Failed to parse file {}: {}", path.display(), err))?;
+
+        camera_path.validate()?;
+        Ok(camera_path)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.keyframes.is_empty() {
+            return Err("Camera path has no keyframes".to_string());
+        }
+
+        if self.keyframes.windows(2).any(|w| w[0].time >= w[1].time) {
+            return Err("Camera path keyframes must be in time order".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Time of the last keyframe.
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |keyframe| keyframe.time)
+    }
+
+    /**
+      Camera position and look at point at the given time. Times before the first keyframe or after the
+      last one are clamped to them.
+    */
+    pub fn sample(&self, time: f32) -> (Vec3, Vec3) {
+        let keyframes = &self.keyframes;
+        let after = keyframes.iter().position(|keyframe| keyframe.time > time);
+
+        let i = match after {
+            Some(0) => return (keyframes[0].position, keyframes[0].look_at),
+            Some(i) => i - 1,
+            None => {
+                let last = keyframes[keyframes.len() - 1];
+                return (last.position, last.look_at);
+            }
+        };
+
+        // Ends are repeated, so the first and last segments have the neighbors the spline needs
+        let k0 = keyframes[i.saturating_sub(1)];
+        let (k1, k2) = (keyframes[i], keyframes[i + 1]);
+        let k3 = keyframes[(i + 2).min(keyframes.len() - 1)];
+
+        let t = (time - k1.time) / (k2.time - k1.time);
+
+        (
+            catmull_rom(k0.position, k1.position, k2.position, k3.position, t),
+            catmull_rom(k0.look_at, k1.look_at, k2.look_at, k3.look_at, t),
+        )
+    }
+}
+
+/**
+  Commands which fly the camera through camera paths, issued through the console.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub enum CameraPathCommand {
+    /// Flies through the camera path with the given name, saving each frame when recording.
+    Play { name: String, record: bool },
+    /// Stops the current camera path.
+    Stop,
+}
+
+/**
+  Parses the arguments of the `campath` command.
+*/
+pub fn parse(args: &[&str]) -> Result<CameraPathCommand, String> {
+    match args {
+        ["stop"] => Ok(CameraPathCommand::Stop),
+        [name] => Ok(CameraPathCommand::Play {
+            name: name.to_string(),
+            record: false,
+        }),
+        [name, "record"] => Ok(CameraPathCommand::Play {
+            name: name.to_string(),
+            record: true,
+        }),
+        _ => Err("Usage: campath <name> [record] or campath stop".to_string()),
+    }
+}
+
+/// Camera path being flown through.
+struct Flight {
+    name: String,
+    path: CameraPath,
+    time: f32,
+    /// Folder frames are saved to and the next frame number, when recording.
+    recording: Option<(PathBuf, u32)>,
+}
+
+#[derive(Default)]
+struct CameraPathState {
+    flight: Option<Flight>,
+}
+
+pub struct CameraPathPlugin;
+
+impl Plugin for CameraPathPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraPathState>()
+            .add_event::<CameraPathCommand>()
+            .add_system(run_camera_path_commands)
+            .add_system(fly_camera_path.after(run_camera_path_commands));
+    }
+}
+
+fn run_camera_path_commands(
+    mut state: ResMut<CameraPathState>,
+    mut console: ResMut<Console>,
+    mut reader: EventReader<CameraPathCommand>,
+) {
+    for command in reader.iter() {
+        match command {
+            CameraPathCommand::Play { name, record } => match CameraPath::load(name) {
+                Ok(path) => {
+                    let recording = record.then(|| (Path::new(RECORDINGS_DIR).join(name), 0));
+
+                    state.flight = Some(Flight {
+                        name: name.clone(),
+                        path,
+                        time: 0.0,
+                        recording,
+                    });
+                    console.print(format!("Flying through {}", name));
+                }
+                Err(err) => console.print(err),
+            },
+            CameraPathCommand::Stop => match state.flight.take() {
+                Some(flight) => console.print(format!("Stopped flying through {}", flight.name)),
+                None => console.print("No camera path is being flown through".to_string()),
+            },
+        }
+    }
+}
+
+/**
+  Moves the camera along the current camera path. When recording, the path advances a fixed time per frame
+  and only once the previous frame was captured, so slow frames don't skip parts of the path.
+*/
+fn fly_camera_path(
+    time: Res<Time>,
+    origin: Res<WorldOrigin>,
+    mut state: ResMut<CameraPathState>,
+    mut screenshots: ResMut<Screenshots>,
+    mut console: ResMut<Console>,
+    mut q: Query<&mut Transform, With<MainCamera>>,
+) {
+    let flight = match state.flight.as_mut() {
+        Some(flight) => flight,
+        None => return,
+    };
+
+    match &flight.recording {
+        Some(_) if screenshots.is_pending() => return,
+        Some((_, frame)) => flight.time = *frame as f32 / RECORD_FPS,
+        None => flight.time += time.delta_seconds(),
+    }
+
+    if flight.time > flight.path.duration() {
+        console.print(format!("Finished flying through {}", flight.name));
+        state.flight = None;
+        return;
+    }
+
+    let (position, look_at) = flight.path.sample(flight.time);
+
+    if let Ok(mut transform) = q.get_single_mut() {
+        *transform = Transform::from_translation(origin.from_world(position))
+            .looking_at(origin.from_world(look_at), Vec3::Y);
+    }
+
+    if let Some((dir, frame)) = flight.recording.as_mut() {
+        screenshots.take(dir.join(format!("{:05}.png", frame)));
+        *frame += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyframe(time: f32, x: f32) -> Keyframe {
+        Keyframe {
+            time,
+            position: Vec3::new(x, 10.0, 0.0),
+            look_at: Vec3::ZERO,
+        }
+    }
+
+    #[test]
+    fn sample() {
+        let path = CameraPath {
+            keyframes: vec![keyframe(0.0, 0.0), keyframe(1.0, 10.0), keyframe(3.0, 30.0)],
+        };
+
+        assert_eq!(path.validate(), Ok(()));
+        assert_eq!(path.duration(), 3.0);
+
+        // Passes through all keyframes and is clamped past the ends
+        assert_eq!(path.sample(-1.0).0, Vec3::new(0.0, 10.0, 0.0));
+        assert_eq!(path.sample(1.0).0, Vec3::new(10.0, 10.0, 0.0));
+        assert_eq!(path.sample(5.0).0, Vec3::new(30.0, 10.0, 0.0));
+
+        let (position, look_at) = path.sample(0.5);
+        assert!(position.x > 0.0 && position.x < 10.0);
+        assert!((position.y - 10.0).abs() < 1e-5);
+        assert_eq!(look_at, Vec3::ZERO);
+
+        let unordered = CameraPath {
+            keyframes: vec![keyframe(1.0, 0.0), keyframe(1.0, 10.0)],
+        };
+        assert!(unordered.validate().is_err());
+        assert!(CameraPath { keyframes: vec![] }.validate().is_err());
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            super::parse(&["intro"]),
+            Ok(CameraPathCommand::Play {
+                name: "intro".to_string(),
+                record: false
+            })
+        );
+        assert_eq!(
+            super::parse(&["intro", "record"]),
+            Ok(CameraPathCommand::Play {
+                name: "intro".to_string(),
+                record: true
+            })
+        );
+        assert_eq!(super::parse(&["stop"]), Ok(CameraPathCommand::Stop));
+        assert!(super::parse(&[]).is_err());
+    }
+}
@@ -0,0 +1,384 @@
+use bevy::{prelude::*, window::ReceivedCharacter};
+use std::collections::VecDeque;
+
+use crate::{
+    console::{self, Console},
+    net::{
+        self, ClientConnected, ClientDisconnected, ClientId, ClientMessage, FromClient, FromServer,
+        NetClient, NetServer, ServerMessage,
+    },
+    signs::Signs,
+    FONT_PATH,
+};
+
+const OPEN_KEY: KeyCode = KeyCode::T;
+
+const FONT_SIZE: f32 = 16.0;
+
+/// How many received lines are kept on screen.
+const MAX_LINES: usize = 10;
+/// How many sent lines can be recalled with the arrow keys.
+const MAX_HISTORY: usize = 32;
+
+/**
+  Lines sent by the player, which can be browsed with the up and down keys while typing.
+*/
+#[derive(Default)]
+pub struct ChatHistory {
+    sent: VecDeque<String>,
+    /// Which sent line is being browsed, counting from the most recent one.
+    cursor: Option<usize>,
+}
+
+impl ChatHistory {
+    pub fn push(&mut self, line: String) {
+        self.sent.push_front(line);
+        self.sent.truncate(MAX_HISTORY);
+        self.cursor = None;
+    }
+
+    /// Moves to an older line, stopping at the oldest one.
+    pub fn previous(&mut self) -> Option<&str> {
+        let cursor = match self.cursor {
+            Some(cursor) => (cursor + 1).min(self.sent.len().saturating_sub(1)),
+            None => 0,
+        };
+
+        self.cursor = Some(cursor);
+        self.sent.get(cursor).map(String::as_str)
+    }
+
+    /// Moves to a newer line. Moving past the most recent line returns nothing, to start a new one.
+    pub fn next(&mut self) -> Option<&str> {
+        self.cursor = match self.cursor {
+            Some(0) | None => None,
+            Some(cursor) => Some(cursor - 1),
+        };
+
+        self.cursor
+            .and_then(|cursor| self.sent.get(cursor))
+            .map(String::as_str)
+    }
+}
+
+/**
+  Chat box, opened by the `T` key. Lines starting with `/` are run as console commands by the server.
+*/
+#[derive(Default)]
+pub struct Chat {
+    pub open: bool,
+    input: String,
+    lines: VecDeque<String>,
+    history: ChatHistory,
+}
+
+impl Chat {
+    fn push(&mut self, line: String) {
+        info!("[Chat] {}", line);
+
+        self.lines.push_back(line);
+
+        while self.lines.len() > MAX_LINES {
+            self.lines.pop_front();
+        }
+    }
+}
+
+/// A chat line typed by the local player.
+struct LocalChat(String);
+
+#[derive(Component)]
+struct ChatText;
+
+pub struct ChatPlugin;
+
+impl Plugin for ChatPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Chat>()
+            .add_event::<LocalChat>()
+            .add_startup_system(setup_chat)
+            .add_system(read_chat_input)
+            .add_system(handle_chat.after(read_chat_input))
+            .add_system(receive_chat)
+            .add_system(announce_clients)
+            .add_system(
+                update_chat_text
+                    .after(handle_chat)
+                    .after(receive_chat)
+                    .after(announce_clients),
+            );
+    }
+}
+
+/**
+  Returns the console command of a chat line, if it's prefixed by `/`.
+*/
+pub fn command(line: &str) -> Option<&str> {
+    line.strip_prefix('/')
+}
+
+fn format_line(from: Option<&str>, text: &str) -> String {
+    match from {
+        Some(from) => format!("<{}> {}", from, text),
+        None => text.to_string(),
+    }
+}
+
+fn setup_chat(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let style = TextStyle {
+        font: asset_server.load(FONT_PATH),
+        font_size: FONT_SIZE,
+        color: Color::WHITE,
+    };
+
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(10.0),
+                    bottom: Val::Px(10.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text {
+                sections: vec![
+                    TextSection {
+                        value: String::default(),
+                        style: style.clone(),
+                    },
+                    TextSection {
+                        value: String::default(),
+                        style: TextStyle {
+                            color: Color::YELLOW,
+                            ..style
+                        },
+                    },
+                ],
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(ChatText);
+}
+
+fn read_chat_input(
+    console: Res<Console>,
+    signs: Res<Signs>,
+    keyboard: Res<Input<KeyCode>>,
+    mut chat: ResMut<Chat>,
+    mut char_reader: EventReader<ReceivedCharacter>,
+    mut writer: EventWriter<LocalChat>,
+) {
+    // Characters must always be consumed, so the key which opens the chat isn't typed into it
+    let chars = char_reader.iter().map(|evt| evt.char).collect::<Vec<_>>();
+
+    if !chat.open {
+        if !console.visible && !signs.is_editing() && keyboard.just_pressed(OPEN_KEY) {
+            chat.open = true;
+        }
+        return;
+    }
+
+    if keyboard.just_pressed(KeyCode::Escape) {
+        chat.open = false;
+        chat.input.clear();
+        return;
+    }
+
+    for c in chars {
+        if !c.is_control() {
+            chat.input.push(c);
+        }
+    }
+
+    if keyboard.just_pressed(KeyCode::Back) {
+        chat.input.pop();
+    }
+
+    if keyboard.just_pressed(KeyCode::Up) {
+        chat.input = chat.history.previous().unwrap_or_default().to_string();
+    } else if keyboard.just_pressed(KeyCode::Down) {
+        chat.input = chat.history.next().unwrap_or_default().to_string();
+    }
+
+    if keyboard.just_pressed(KeyCode::Return) {
+        let line = std::mem::take(&mut chat.input);
+        chat.open = false;
+
+        if !line.trim().is_empty() {
+            chat.history.push(line.clone());
+            writer.send(LocalChat(line));
+        }
+    }
+}
+
+/**
+  Handles chat lines as the server. Lines typed locally are forwarded to the server instead, when connected
+  to one.
+*/
+fn handle_chat(
+    mut chat: ResMut<Chat>,
+    mut console: ResMut<Console>,
+    client: Option<ResMut<NetClient>>,
+    mut server: Option<ResMut<NetServer>>,
+    mut local_reader: EventReader<LocalChat>,
+    mut client_reader: EventReader<FromClient>,
+) {
+    if let Some(mut client) = client {
+        for LocalChat(line) in local_reader.iter() {
+            client.send(&ClientMessage::Chat(line.clone()));
+        }
+
+        return;
+    }
+
+    let local = local_reader.iter().map(|LocalChat(line)| (None, line));
+    let remote = client_reader.iter().filter_map(|evt| match &evt.message {
+        ClientMessage::Chat(line) => Some((Some(evt.client), line)),
+        _ => None,
+    });
+
+    for (sender, line) in local.chain(remote) {
+        let name = match server.as_ref() {
+            Some(server) => server.player_name(sender),
+            None => net::player_name(sender),
+        };
+
+        if let Some(command) = command(line) {
+            let reply = match console::parse(command) {
+                Ok(_) => {
+                    info!("{} runs command: {}", name, command);
+                    console.submit(command.to_string());
+                    None
+                }
+                Err(err) => Some(err),
+            };
+
+            match (reply, sender, server.as_mut()) {
+                (Some(reply), Some(client), Some(server)) => server.send(
+                    client,
+                    &ServerMessage::Chat {
+                        from: None,
+                        text: reply,
+                    },
+                ),
+                (Some(reply), None, _) => chat.push(reply),
+                _ => (),
+            }
+
+            continue;
+        }
+
+        chat.push(format_line(Some(&name), line));
+
+        if let Some(server) = server.as_mut() {
+            server.broadcast(&ServerMessage::Chat {
+                from: Some(name),
+                text: line.clone(),
+            });
+        }
+    }
+}
+
+fn receive_chat(mut chat: ResMut<Chat>, mut reader: EventReader<FromServer>) {
+    for FromServer(message) in reader.iter() {
+        match message {
+            ServerMessage::Chat { from, text } => chat.push(format_line(from.as_deref(), text)),
+            ServerMessage::LoginRejected(reason) => {
+                chat.push(format!("Unable to join the server: {}", reason))
+            }
+            _ => (),
+        }
+    }
+}
+
+fn announce_clients(
+    server: Option<ResMut<NetServer>>,
+    mut chat: ResMut<Chat>,
+    mut connected_reader: EventReader<ClientConnected>,
+    mut disconnected_reader: EventReader<ClientDisconnected>,
+) {
+    let mut server = match server {
+        Some(server) => server,
+        None => return,
+    };
+
+    let joined = connected_reader
+        .iter()
+        .map(|ClientConnected(client)| (*client, "joined"));
+    let left = disconnected_reader
+        .iter()
+        .map(|ClientDisconnected(client)| (*client, "left"));
+
+    for (client, action) in joined.chain(left).collect::<Vec<(ClientId, _)>>() {
+        let text = format!("{} {} the game", server.player_name(Some(client)), action);
+
+        chat.push(text.clone());
+        server.broadcast(&ServerMessage::Chat { from: None, text });
+    }
+}
+
+fn update_chat_text(chat: Res<Chat>, mut q: Query<&mut Text, With<ChatText>>) {
+    if !chat.is_changed() {
+        return;
+    }
+
+    for mut text in q.iter_mut() {
+        text.sections[0].value = chat
+            .lines
+            .iter()
+            .map(|line| format!("{}\n", line))
+            .collect();
+
+        text.sections[1].value = if chat.open {
+            format!("say: {}", chat.input)
+        } else {
+            String::default()
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command() {
+        assert_eq!(super::command("/tp 0 0 0"), Some("tp 0 0 0"));
+        assert_eq!(super::command("hello /tp"), None);
+    }
+
+    #[test]
+    fn history() {
+        let mut history = ChatHistory::default();
+        assert_eq!(history.previous(), None);
+
+        history.push("first".to_string());
+        history.push("second".to_string());
+
+        assert_eq!(history.previous(), Some("second"));
+        assert_eq!(history.previous(), Some("first"));
+        assert_eq!(history.previous(), Some("first"));
+        assert_eq!(history.next(), Some("second"));
+        assert_eq!(history.next(), None);
+
+        // Sending a line starts browsing from the most recent one again
+        history.previous();
+        history.push("third".to_string());
+        assert_eq!(history.previous(), Some("third"));
+    }
+
+    #[test]
+    fn chat_lines() {
+        let mut chat = Chat::default();
+
+        for i in 0..MAX_LINES + 5 {
+            chat.push(format!("line {}", i));
+        }
+
+        assert_eq!(chat.lines.len(), MAX_LINES);
+        assert_eq!(chat.lines[0], "line 5");
+    }
+}
@@ -0,0 +1,154 @@
+use bevy::prelude::*;
+use std::path::Path;
+use vox::{
+    claim::Claim,
+    meta::{WorldMeta, META_PATH},
+};
+
+use crate::{builder::Builder, console::Console, net};
+
+/**
+  Commands which manage claimed regions, issued through the console.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClaimCommand {
+    /// Claims the builder selection for the given owner, or for the host when there is none.
+    Claim { name: String, owner: Option<String> },
+    /// Protects the builder selection from every player.
+    Protect(String),
+    /// Removes the claim with the given name.
+    Unclaim(String),
+    /// Lists all claims.
+    List,
+}
+
+pub struct ClaimsPlugin;
+
+impl Plugin for ClaimsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ClaimCommand>()
+            .add_system(run_claim_commands);
+    }
+}
+
+/**
+  Parses the arguments of the claim command with the given name.
+*/
+pub fn parse(name: &str, args: &[&str]) -> Result<ClaimCommand, String> {
+    match (name, args) {
+        ("claim", [claim]) => Ok(ClaimCommand::Claim {
+            name: claim.to_string(),
+            owner: None,
+        }),
+        ("claim", [claim, owner]) => Ok(ClaimCommand::Claim {
+            name: claim.to_string(),
+            owner: Some(owner.to_string()),
+        }),
+        ("protect", [claim]) => Ok(ClaimCommand::Protect(claim.to_string())),
+        ("unclaim", [claim]) => Ok(ClaimCommand::Unclaim(claim.to_string())),
+        ("claims", []) => Ok(ClaimCommand::List),
+        _ => Err(format!("Invalid arguments for {}", name)),
+    }
+}
+
+fn run_claim_commands(
+    builder: Res<Builder>,
+    mut meta: ResMut<WorldMeta>,
+    mut console: ResMut<Console>,
+    mut reader: EventReader<ClaimCommand>,
+) {
+    for command in reader.iter() {
+        let (name, owner) = match command {
+            ClaimCommand::Claim { name, owner } => (
+                name,
+                Some(owner.clone().unwrap_or_else(|| net::player_name(None))),
+            ),
+            ClaimCommand::Protect(name) => (name, None),
+            ClaimCommand::Unclaim(name) => {
+                match meta.claims.remove(name) {
+                    Some(_) => {
+                        meta.save(Path::new(META_PATH));
+                        console.print(format!("Removed claim {}", name));
+                    }
+                    None => console.print(format!("Unknown claim: {}", name)),
+                }
+                continue;
+            }
+            ClaimCommand::List => {
+                let claims = meta
+                    .claims
+                    .iter()
+                    .map(|claim| {
+                        format!(
+                            "{} ({}): {} to {}",
+                            claim.name,
+                            claim.owner.as_deref().unwrap_or("protected"),
+                            claim.region.min,
+                            claim.region.max
+                        )
+                    })
+                    .collect::<Vec<_>>();
+
+                console.print(if claims.is_empty() {
+                    "No claims".to_string()
+                } else {
+                    claims.join("\n")
+                });
+                continue;
+            }
+        };
+
+        let region = match builder.region() {
+            Some(region) => region,
+            None => {
+                console.print("Select two corners first".to_string());
+                continue;
+            }
+        };
+
+        let claim = Claim {
+            name: name.clone(),
+            owner,
+            region,
+        };
+
+        match meta.claims.claim(claim) {
+            Ok(()) => {
+                meta.save(Path::new(META_PATH));
+                console.print(format!("Claimed {}", name));
+            }
+            Err(err) => console.print(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            super::parse("claim", &["house"]),
+            Ok(ClaimCommand::Claim {
+                name: "house".to_string(),
+                owner: None
+            })
+        );
+        assert_eq!(
+            super::parse("claim", &["house", "Player1"]),
+            Ok(ClaimCommand::Claim {
+                name: "house".to_string(),
+                owner: Some("Player1".to_string())
+            })
+        );
+        assert_eq!(
+            super::parse("protect", &["spawn"]),
+            Ok(ClaimCommand::Protect("spawn".to_string()))
+        );
+        assert_eq!(super::parse("claims", &[]), Ok(ClaimCommand::List));
+
+        assert!(super::parse("claim", &[]).is_err());
+        assert!(super::parse("unclaim", &["a", "b"]).is_err());
+    }
+}
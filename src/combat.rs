@@ -0,0 +1,208 @@
+use bevy::prelude::*;
+use std::path::Path;
+use vox::{
+    combat::{self, Health},
+    meta::{WorldMeta, META_PATH},
+};
+
+use crate::{chat::Chat, console::Console, messages::Messages, spectator::Spectator, MainCamera};
+
+const ATTACK_KEY: KeyCode = KeyCode::R;
+
+/// How far, in voxels, melee attacks reach.
+const ATTACK_REACH: f32 = 3.0;
+/// Radians, on each side of where the player looks, melee attacks sweep.
+const ATTACK_ANGLE: f32 = 0.5;
+const ATTACK_DAMAGE: f32 = 4.0;
+/// Speed, in voxels per second, hit entities are knocked back at.
+const ATTACK_KNOCKBACK: f32 = 8.0;
+/// Seconds between melee attacks.
+const ATTACK_COOLDOWN: f32 = 0.4;
+
+/// Radius of the sphere, around their translation, entities are hit on.
+const HIT_RADIUS: f32 = 0.5;
+/// Seconds hit entities flash for.
+const FLASH_TIME: f32 = 0.15;
+
+const PLAYER_HEALTH: f32 = 20.0;
+
+/**
+  Health of the player. It's a resource, instead of a component, since the player is the camera, which is
+  detached from the player body while spectating.
+*/
+pub struct PlayerHealth(pub Health);
+
+impl Default for PlayerHealth {
+    fn default() -> Self {
+        Self(Health::new(PLAYER_HEALTH))
+    }
+}
+
+/**
+  Sent when an entity with [`Health`] is hit by the player and survives it, so whatever it is can react, like
+  mobs fighting back.
+*/
+pub struct EntityHit {
+    pub target: Entity,
+    /// Velocity, in voxels per second, the target is knocked back at.
+    pub knockback: Vec3,
+}
+
+/// Seconds left until the player can attack again.
+#[derive(Default)]
+struct AttackCooldown(f32);
+
+/// Material flashed by hit entities.
+struct FlashMaterial(Handle<StandardMaterial>);
+
+impl FromWorld for FlashMaterial {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world
+            .get_resource_mut::<Assets<StandardMaterial>>()
+            .expect("PbrPlugin must be added before CombatPlugin");
+
+        Self(materials.add(StandardMaterial {
+            base_color: Color::rgb(1.0, 0.2, 0.2),
+            unlit: true,
+            ..Default::default()
+        }))
+    }
+}
+
+/// An entity flashing after being hit, with the material it's restored to afterwards.
+#[derive(Component)]
+struct HitFlash {
+    remaining: f32,
+    material: Handle<StandardMaterial>,
+}
+
+pub struct CombatPlugin;
+
+impl Plugin for CombatPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PlayerHealth>()
+            .init_resource::<AttackCooldown>()
+            .init_resource::<FlashMaterial>()
+            .add_event::<EntityHit>()
+            .add_system(attack)
+            .add_system(flash_hits.after(attack))
+            .add_system(respawn_player);
+    }
+}
+
+/**
+  Hits every entity with [`Health`] in front of the player. Killed entities are despawned, while the ones
+  which survive flash and are knocked back. There's no player body to swing with while spectating.
+*/
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn attack(
+    mut commands: Commands,
+    time: Res<Time>,
+    console: Res<Console>,
+    chat: Res<Chat>,
+    spectator: Res<Spectator>,
+    keyboard: Res<Input<KeyCode>>,
+    flash: Res<FlashMaterial>,
+    mut cooldown: ResMut<AttackCooldown>,
+    mut writer: EventWriter<EntityHit>,
+    camera: Query<&GlobalTransform, With<MainCamera>>,
+    mut targets: Query<(
+        Entity,
+        &GlobalTransform,
+        &mut Health,
+        Option<&Handle<StandardMaterial>>,
+        Option<&HitFlash>,
+    )>,
+) {
+    cooldown.0 = (cooldown.0 - time.delta_seconds()).max(0.0);
+
+    if console.visible
+        || chat.open
+        || spectator.is_active()
+        || cooldown.0 > 0.0
+        || !keyboard.just_pressed(ATTACK_KEY)
+    {
+        return;
+    }
+
+    let eyes = match camera.get_single() {
+        Ok(transform) => transform,
+        Err(_) => return,
+    };
+
+    cooldown.0 = ATTACK_COOLDOWN;
+
+    for (entity, transform, mut health, material, flashing) in targets.iter_mut() {
+        let target = transform.translation;
+
+        if !combat::in_sweep(
+            eyes.translation,
+            eyes.forward(),
+            ATTACK_REACH,
+            ATTACK_ANGLE,
+            target,
+            HIT_RADIUS,
+        ) {
+            continue;
+        }
+
+        if health.damage(ATTACK_DAMAGE) {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        writer.send(EntityHit {
+            target: entity,
+            knockback: combat::knockback(eyes.translation, target, ATTACK_KNOCKBACK),
+        });
+
+        // Entities hit again while flashing keep the material they had before the first hit
+        let restored = match (flashing, material) {
+            (Some(flashing), _) => Some(flashing.material.clone()),
+            (None, Some(material)) => Some(material.clone()),
+            (None, None) => None,
+        };
+
+        if let Some(material) = restored {
+            commands
+                .entity(entity)
+                .insert(flash.0.clone())
+                .insert(HitFlash {
+                    remaining: FLASH_TIME,
+                    material,
+                });
+        }
+    }
+}
+
+fn flash_hits(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut q: Query<(Entity, &mut HitFlash, &mut Handle<StandardMaterial>)>,
+) {
+    for (entity, mut flash, mut material) in q.iter_mut() {
+        flash.remaining -= time.delta_seconds();
+
+        if flash.remaining <= 0.0 {
+            *material = flash.material.clone();
+            commands.entity(entity).remove::<HitFlash>();
+        }
+    }
+}
+
+/// The player gets back to full health once killed, and the death is counted on the world stats.
+fn respawn_player(
+    mut health: ResMut<PlayerHealth>,
+    mut meta: ResMut<WorldMeta>,
+    mut messages: ResMut<Messages>,
+) {
+    if !health.0.is_dead() {
+        return;
+    }
+
+    health.0 = Health::new(health.0.max);
+    meta.stats.record_death();
+    meta.save(Path::new(META_PATH));
+    messages.show_title("You died");
+    messages.show_subtitle(format!("Deaths: {}", meta.stats.deaths()));
+}
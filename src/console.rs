@@ -0,0 +1,427 @@
+use bevy::{prelude::*, window::ReceivedCharacter};
+use vox::pipeline::{RecenterStreaming, SimulationControl, WorldOrigin};
+
+use crate::{
+    builder::BuildCommand, camera_effects::CameraEffectCommand, camera_path::CameraPathCommand,
+    chat::Chat, claims::ClaimCommand, mods::ModCommand, signs::Signs, spectator::ToggleSpectator,
+    tickets::TicketCommand, MainCamera, FONT_PATH,
+};
+
+const TOGGLE_KEY: KeyCode = KeyCode::Grave;
+const TOGGLE_CHAR: char = '`';
+
+const FONT_SIZE: f32 = 18.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Teleports the player to the given world position.
+    Tp(Vec3),
+    /// Teleports the player to the center of the given chunk.
+    TpChunk(IVec3),
+    /// Prints the player world position and chunk.
+    Where,
+    /// Runs an operation over the builder selection.
+    Build(BuildCommand),
+    /// Manages claimed regions.
+    Claim(ClaimCommand),
+    /// Pauses the simulation tick.
+    Pause,
+    /// Resumes the simulation tick.
+    Resume,
+    /// Runs the given number of simulation ticks while paused.
+    Step(u32),
+    /// Flies the camera through a camera path.
+    CameraPath(CameraPathCommand),
+    /// Detaches the camera from the player, or puts it back.
+    Spectate,
+    /// Manages chunk regions kept loaded.
+    Ticket(TicketCommand),
+    /// Runs a command registered by a mod.
+    Mod(ModCommand),
+    /// Turns view bobbing or camera shake on or off.
+    CameraEffect(CameraEffectCommand),
+}
+
+/**
+  Developer console, toggled by the backtick key. Each submitted line is parsed as a [`Command`].
+  Lines can also be submitted by other systems, like the admin endpoint.
+*/
+#[derive(Default)]
+pub struct Console {
+    pub visible: bool,
+    input: String,
+    output: String,
+    /// Lines submitted but not executed yet.
+    queued: Vec<String>,
+}
+
+impl Console {
+    /// Queues a command line to be executed on this frame, as if it was typed.
+    pub fn submit(&mut self, line: String) {
+        self.queued.push(line);
+    }
+
+    /// Replaces the console output with the given message.
+    pub fn print(&mut self, output: String) {
+        info!("{}", output);
+        self.output = output;
+    }
+
+    /// Message printed last, usually the result of the last command.
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+}
+
+#[derive(Component)]
+struct ConsoleText;
+
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Console>()
+            .add_startup_system(setup_console)
+            .add_system(toggle_console)
+            .add_system(read_input.after(toggle_console))
+            .add_system(execute_commands.after(read_input))
+            .add_system(update_console_text.after(execute_commands));
+    }
+}
+
+fn parse_args<T: std::str::FromStr>(args: &[&str]) -> Result<[T; 3], String> {
+    match args {
+        [x, y, z] => {
+            let parse = |arg: &str| {
+                arg.parse::<T>()
+                    .map_err(|_| format!("Invalid number: {}", arg))
+            };
+
+            Ok([parse(x)?, parse(y)?, parse(z)?])
+        }
+        _ => Err(format!("Expected 3 arguments, got {}", args.len())),
+    }
+}
+
+pub fn parse(line: &str) -> Result<Command, String> {
+    let mut tokens = line.split_whitespace();
+
+    let name = match tokens.next() {
+        Some(name) => name,
+        None => return Err("Empty command".to_string()),
+    };
+
+    let args = tokens.collect::<Vec<_>>();
+
+    match name {
+        "tp" => parse_args::<f32>(&args).map(|pos| Command::Tp(pos.into())),
+        "tpchunk" => parse_args::<i32>(&args).map(|local| Command::TpChunk(local.into())),
+        "where" if args.is_empty() => Ok(Command::Where),
+        "where" => Err("where takes no arguments".to_string()),
+        "fill" | "hollow" | "replace" | "copy" | "paste" | "save" | "brush" | "radius" => {
+            crate::builder::parse(name, &args).map(Command::Build)
+        }
+        "claim" | "protect" | "unclaim" | "claims" => {
+            crate::claims::parse(name, &args).map(Command::Claim)
+        }
+        "pause" if args.is_empty() => Ok(Command::Pause),
+        "resume" if args.is_empty() => Ok(Command::Resume),
+        "step" => match args.as_slice() {
+            [] => Ok(Command::Step(1)),
+            [count] => count
+                .parse()
+                .map(Command::Step)
+                .map_err(|_| format!("Invalid step count: {}", count)),
+            _ => Err("step takes at most one argument".to_string()),
+        },
+        "spectate" if args.is_empty() => Ok(Command::Spectate),
+        "ticket" => crate::tickets::parse(&args).map(Command::Ticket),
+        "campath" => crate::camera_path::parse(&args).map(Command::CameraPath),
+        "mod" => crate::mods::parse(&args).map(Command::Mod),
+        "bobbing" | "shake" => crate::camera_effects::parse(name, &args).map(Command::CameraEffect),
+        "pause" | "resume" | "spectate" => Err(format!("{} takes no arguments", name)),
+        _ => Err(format!("Unknown command: {}", name)),
+    }
+}
+
+/**
+  Render space position where the player should be placed when teleporting to the given chunk.
+*/
+pub fn chunk_center(origin: &WorldOrigin, local: IVec3) -> Vec3 {
+    origin.chunk_bounds(local).center()
+}
+
+fn setup_console(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                display: Display::None,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(10.0),
+                    top: Val::Px(10.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text {
+                sections: vec![
+                    TextSection {
+                        value: String::default(),
+                        style: TextStyle {
+                            font: asset_server.load(FONT_PATH),
+                            font_size: FONT_SIZE,
+                            color: Color::GRAY,
+                        },
+                    },
+                    TextSection {
+                        value: String::default(),
+                        style: TextStyle {
+                            font: asset_server.load(FONT_PATH),
+                            font_size: FONT_SIZE,
+                            color: Color::WHITE,
+                        },
+                    },
+                ],
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(ConsoleText);
+}
+
+fn toggle_console(
+    chat: Res<Chat>,
+    signs: Res<Signs>,
+    keyboard: Res<Input<KeyCode>>,
+    mut console: ResMut<Console>,
+) {
+    if !chat.open && !signs.is_editing() && keyboard.just_pressed(TOGGLE_KEY) {
+        console.visible = !console.visible;
+        console.input.clear();
+    }
+}
+
+fn read_input(
+    keyboard: Res<Input<KeyCode>>,
+    mut console: ResMut<Console>,
+    mut char_reader: EventReader<ReceivedCharacter>,
+) {
+    // Characters must always be consumed, so they don't leak into the console once it's opened
+    let chars = char_reader.iter().map(|evt| evt.char).collect::<Vec<_>>();
+
+    if !console.visible {
+        return;
+    }
+
+    for c in chars {
+        if c != TOGGLE_CHAR && !c.is_control() {
+            console.input.push(c);
+        }
+    }
+
+    if keyboard.just_pressed(KeyCode::Back) {
+        console.input.pop();
+    }
+
+    if keyboard.just_pressed(KeyCode::Return) {
+        let line = std::mem::take(&mut console.input);
+        console.submit(line);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn execute_commands(
+    origin: Res<WorldOrigin>,
+    mut console: ResMut<Console>,
+    mut simulation: ResMut<SimulationControl>,
+    mut recenter_writer: EventWriter<RecenterStreaming>,
+    mut build_writer: EventWriter<BuildCommand>,
+    mut claim_writer: EventWriter<ClaimCommand>,
+    mut camera_path_writer: EventWriter<CameraPathCommand>,
+    mut spectator_writer: EventWriter<ToggleSpectator>,
+    mut ticket_writer: EventWriter<TicketCommand>,
+    mut mod_writer: EventWriter<ModCommand>,
+    mut camera_effect_writer: EventWriter<CameraEffectCommand>,
+    mut camera: Query<(&mut Transform, &mut GlobalTransform), With<MainCamera>>,
+) {
+    if console.queued.is_empty() {
+        return;
+    }
+
+    let (mut transform, mut global_transform) = match camera.get_single_mut() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+
+    for line in std::mem::take(&mut console.queued) {
+        let teleport_to = match parse(&line) {
+            Ok(Command::Tp(position)) => Some(origin.from_world(position)),
+            Ok(Command::TpChunk(local)) => Some(chunk_center(&origin, local)),
+            Ok(Command::Where) => {
+                let position = transform.translation;
+                console.print(format!(
+                    "Position: {} Chunk: {}",
+                    origin.to_world(position),
+                    origin.to_local(position)
+                ));
+                None
+            }
+            Ok(Command::Build(command)) => {
+                // Builder commands print their own output once they run
+                build_writer.send(command);
+                None
+            }
+            Ok(Command::Claim(command)) => {
+                claim_writer.send(command);
+                None
+            }
+            Ok(Command::Pause) => {
+                simulation.pause();
+                console.print("Simulation paused".to_string());
+                None
+            }
+            Ok(Command::Resume) => {
+                simulation.resume();
+                console.print("Simulation resumed".to_string());
+                None
+            }
+            Ok(Command::Step(_)) if !simulation.is_paused() => {
+                console.print("Simulation must be paused to step it".to_string());
+                None
+            }
+            Ok(Command::Step(count)) => {
+                simulation.step(count);
+                console.print(format!("Stepping {} ticks", count));
+                None
+            }
+            Ok(Command::CameraPath(command)) => {
+                camera_path_writer.send(command);
+                None
+            }
+            Ok(Command::Spectate) => {
+                spectator_writer.send(ToggleSpectator);
+                None
+            }
+            Ok(Command::Ticket(command)) => {
+                ticket_writer.send(command);
+                None
+            }
+            Ok(Command::Mod(command)) => {
+                mod_writer.send(command);
+                None
+            }
+            Ok(Command::CameraEffect(command)) => {
+                camera_effect_writer.send(command);
+                None
+            }
+            Err(err) => {
+                console.print(err);
+                None
+            }
+        };
+
+        if let Some(position) = teleport_to {
+            // Global transform is also updated, so streaming doesn't have to wait for transform propagation
+            transform.translation = position;
+            global_transform.translation = position;
+            recenter_writer.send(RecenterStreaming);
+
+            console.print(format!("Teleported to {}", origin.to_world(position)));
+        }
+    }
+}
+
+fn update_console_text(
+    console: Res<Console>,
+    mut q: Query<(&mut Text, &mut Style), With<ConsoleText>>,
+) {
+    if !console.is_changed() {
+        return;
+    }
+
+    for (mut text, mut style) in q.iter_mut() {
+        style.display = if console.visible {
+            Display::Flex
+        } else {
+            Display::None
+        };
+
+        text.sections[0].value = format!("{}\n", console.output);
+        text.sections[1].value = format!("> {}", console.input);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vox::chunk;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            super::parse("tp 1 -2.5 300000"),
+            Ok(Command::Tp(Vec3::new(1.0, -2.5, 300000.0)))
+        );
+        assert_eq!(
+            super::parse("  tpchunk 10 0   -20 "),
+            Ok(Command::TpChunk((10, 0, -20).into()))
+        );
+        assert_eq!(super::parse("where"), Ok(Command::Where));
+
+        assert!(super::parse("").is_err());
+        assert!(super::parse("tp 1 2").is_err());
+        assert!(super::parse("tp 1 2 a").is_err());
+        assert!(super::parse("tpchunk 1 2 3.5").is_err());
+        assert!(super::parse("where 1").is_err());
+        assert_eq!(
+            super::parse("fill 3"),
+            Ok(Command::Build(BuildCommand::Fill(3.into())))
+        );
+
+        assert_eq!(super::parse("pause"), Ok(Command::Pause));
+        assert_eq!(super::parse("step"), Ok(Command::Step(1)));
+        assert_eq!(super::parse("step 20"), Ok(Command::Step(20)));
+        assert_eq!(
+            super::parse("campath stop"),
+            Ok(Command::CameraPath(CameraPathCommand::Stop))
+        );
+        assert_eq!(super::parse("spectate"), Ok(Command::Spectate));
+        assert_eq!(
+            super::parse("shake off"),
+            Ok(Command::CameraEffect(CameraEffectCommand::Shake(false)))
+        );
+        assert!(super::parse("spectate now").is_err());
+        assert!(super::parse("step -1").is_err());
+        assert!(super::parse("resume now").is_err());
+
+        assert_eq!(
+            super::parse("mod greet world"),
+            Ok(Command::Mod(ModCommand {
+                name: "greet".to_string(),
+                args: vec!["world".to_string()],
+            }))
+        );
+        assert!(super::parse("mod").is_err());
+
+        assert!(super::parse("fly").is_err());
+        assert!(super::parse("copy all").is_err());
+    }
+
+    #[test]
+    fn chunk_center() {
+        let origin = WorldOrigin::default();
+        let center = super::chunk_center(&origin, (1, -1, 0).into());
+
+        assert_eq!(origin.to_local(center), (1, -1, 0).into());
+        assert_eq!(center, Vec3::new(1.5, -0.5, 0.5) * chunk::AXIS_SIZE as f32);
+
+        // Far away chunks are still placed near the render origin
+        let origin = WorldOrigin((1_000_000, 0, 0).into());
+        let local = (1_000_001, 0, 0).into();
+        let center = super::chunk_center(&origin, local);
+
+        assert_eq!(origin.to_local(center), local);
+        assert_eq!(center, Vec3::new(1.5, 0.5, 0.5) * chunk::AXIS_SIZE as f32);
+    }
+}
@@ -0,0 +1,369 @@
+use bevy::prelude::*;
+use vox::{
+    block_entity::{BlockEntities, BlockEntityData, Container},
+    chunk,
+    item::Inventory,
+    voxel::{Kind, KindRegistry},
+    world::VoxWorld,
+};
+
+use crate::{chat::Chat, console::Console, signs::Signs, FONT_PATH};
+
+const CLOSE_KEY: KeyCode = KeyCode::Escape;
+
+const FONT_SIZE: f32 = 16.0;
+
+const COLUMN_WIDTH: f32 = 260.0;
+const SLOT_HEIGHT: f32 = 28.0;
+const SLOT_MARGIN: f32 = 2.0;
+
+const SLOT_COLOR: Color = Color::rgba(0.2, 0.2, 0.2, 0.9);
+const HOVERED_COLOR: Color = Color::rgba(0.35, 0.35, 0.35, 0.9);
+
+/**
+  Send this to open the container block entity at the given chunk and voxel, or none to close the open one.
+*/
+pub struct UseContainer(pub Option<(IVec3, IVec3)>);
+
+/**
+  Container UI state. While a container is open, clicking an item moves one of it between the container and
+  the player inventory, and the mouse doesn't break or place voxels.
+*/
+#[derive(Default)]
+pub struct Containers {
+    open: Option<(IVec3, IVec3)>,
+    /// Contents shown by the UI, of the container and the inventory, so it's only rebuilt when they change.
+    shown: Option<(Inventory, Inventory)>,
+    ui: Option<ContainerUi>,
+}
+
+impl Containers {
+    pub fn is_open(&self) -> bool {
+        self.open.is_some()
+    }
+}
+
+struct ContainerUi {
+    root: Entity,
+    container: Entity,
+    inventory: Entity,
+}
+
+/// Item listed on the UI. Clicking it moves one of it to the other side.
+#[derive(Component)]
+struct Slot {
+    kind: Kind,
+    in_container: bool,
+}
+
+pub struct ContainersPlugin;
+
+impl Plugin for ContainersPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Containers>()
+            .add_event::<UseContainer>()
+            .add_startup_system(setup_containers)
+            .add_system(use_containers)
+            .add_system(move_on_click.after(use_containers))
+            .add_system(update_slots.after(move_on_click));
+    }
+}
+
+/**
+  Moves one item of the given kind from one inventory to the other. Returns false when there is none to move.
+*/
+pub fn move_item(from: &mut Inventory, to: &mut Inventory, kind: Kind) -> bool {
+    if from.remove(kind, 1) {
+        to.add(kind, 1);
+        true
+    } else {
+        false
+    }
+}
+
+fn column(parent: &mut ChildBuilder, style: &TextStyle, title: &str) -> Entity {
+    let mut list = None;
+
+    parent
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Px(COLUMN_WIDTH), Val::Percent(60.0)),
+                margin: Rect::all(Val::Px(SLOT_MARGIN * 4.0)),
+                // UI flows from the bottom, so columns are reversed to list items from the top
+                flex_direction: FlexDirection::ColumnReverse,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(title, style.clone(), Default::default()),
+                ..Default::default()
+            });
+
+            list = Some(
+                parent
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::ColumnReverse,
+                            ..Default::default()
+                        },
+                        color: Color::NONE.into(),
+                        ..Default::default()
+                    })
+                    .id(),
+            );
+        });
+
+    list.expect("Column list is always spawned")
+}
+
+fn setup_containers(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut containers: ResMut<Containers>,
+) {
+    let style = TextStyle {
+        font: asset_server.load(FONT_PATH),
+        font_size: FONT_SIZE,
+        color: Color::WHITE,
+    };
+
+    let mut lists = (None, None);
+
+    let root = commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                display: Display::None,
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            lists.0 = Some(column(parent, &style, "Container"));
+            lists.1 = Some(column(parent, &style, "Inventory"));
+        })
+        .id();
+
+    if let (Some(container), Some(inventory)) = lists {
+        containers.ui = Some(ContainerUi {
+            root,
+            container,
+            inventory,
+        });
+    }
+}
+
+/**
+  Opens and closes containers. Containers are closed when their voxel is replaced, like when another player
+  breaks it.
+*/
+#[allow(clippy::too_many_arguments)]
+fn use_containers(
+    console: Res<Console>,
+    chat: Res<Chat>,
+    signs: Res<Signs>,
+    keyboard: Res<Input<KeyCode>>,
+    world: Res<VoxWorld>,
+    registry: Res<KindRegistry>,
+    mut block_entities: ResMut<BlockEntities>,
+    mut containers: ResMut<Containers>,
+    mut reader: EventReader<UseContainer>,
+    mut q: Query<&mut Style>,
+) {
+    let was_open = containers.open;
+
+    for UseContainer(target) in reader.iter() {
+        containers.open = *target;
+    }
+
+    if !console.visible && !chat.open && !signs.is_editing() && keyboard.just_pressed(CLOSE_KEY) {
+        containers.open = None;
+    }
+
+    if let Some((chunk, voxel)) = containers.open {
+        let kind = world.get_voxel(chunk::join_voxel(chunk, voxel));
+
+        if kind.and_then(|kind| registry.block_entity(kind)) != Some(Container::NAME) {
+            containers.open = None;
+        } else if block_entities.get(chunk, voxel).is_none() {
+            // Containers placed before block entities existed have none yet
+            block_entities.sync(chunk, voxel, Some(Container::NAME));
+        }
+    }
+
+    if containers.open == was_open {
+        return;
+    }
+
+    containers.shown = None;
+
+    if let Some(Ok(mut style)) = containers.ui.as_ref().map(|ui| q.get_mut(ui.root)) {
+        style.display = if containers.open.is_some() {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+}
+
+fn move_on_click(
+    containers: Res<Containers>,
+    mut block_entities: ResMut<BlockEntities>,
+    mut inventory: ResMut<Inventory>,
+    q: Query<(&Interaction, &Slot), Changed<Interaction>>,
+) {
+    let (chunk, voxel) = match containers.open {
+        Some(open) => open,
+        None => return,
+    };
+
+    for (interaction, slot) in q.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        let container = match block_entities.get_as_mut::<Container>(chunk, voxel) {
+            Some(container) => &mut container.items,
+            None => return,
+        };
+
+        if slot.in_container {
+            move_item(container, &mut inventory, slot.kind);
+        } else {
+            move_item(&mut inventory, container, slot.kind);
+        }
+    }
+}
+
+fn spawn_slots(
+    commands: &mut Commands,
+    list: Entity,
+    style: &TextStyle,
+    registry: &KindRegistry,
+    items: &Inventory,
+    in_container: bool,
+) {
+    commands.entity(list).despawn_descendants();
+
+    commands.entity(list).with_children(|parent| {
+        for (kind, count) in items.iter() {
+            parent
+                .spawn_bundle(ButtonBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(100.0), Val::Px(SLOT_HEIGHT)),
+                        margin: Rect::all(Val::Px(SLOT_MARGIN)),
+                        align_items: AlignItems::Center,
+                        padding: Rect::all(Val::Px(SLOT_MARGIN * 2.0)),
+                        ..Default::default()
+                    },
+                    color: SLOT_COLOR.into(),
+                    ..Default::default()
+                })
+                .insert(Slot { kind, in_container })
+                .with_children(|parent| {
+                    parent.spawn_bundle(TextBundle {
+                        text: Text::with_section(
+                            format!("{} x{}", registry.name(kind), count),
+                            style.clone(),
+                            Default::default(),
+                        ),
+                        ..Default::default()
+                    });
+                });
+        }
+    });
+}
+
+/**
+  Lists the items of the open container and the inventory, rebuilding the lists only when they change.
+*/
+#[allow(clippy::too_many_arguments)]
+fn update_slots(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    registry: Res<KindRegistry>,
+    block_entities: Res<BlockEntities>,
+    inventory: Res<Inventory>,
+    mut containers: ResMut<Containers>,
+    mut q: Query<(&Interaction, &mut UiColor), With<Slot>>,
+) {
+    for (interaction, mut color) in q.iter_mut() {
+        let new_color = match interaction {
+            Interaction::Hovered | Interaction::Clicked => HOVERED_COLOR,
+            Interaction::None => SLOT_COLOR,
+        };
+
+        if color.0 != new_color {
+            color.0 = new_color;
+        }
+    }
+
+    let container = match containers
+        .open
+        .and_then(|(chunk, voxel)| block_entities.get_as::<Container>(chunk, voxel))
+    {
+        Some(container) => container,
+        None => return,
+    };
+
+    let contents = (container.items.clone(), inventory.clone());
+    if containers.shown.as_ref() == Some(&contents) {
+        return;
+    }
+
+    let (container_list, inventory_list) = match &containers.ui {
+        Some(ui) => (ui.container, ui.inventory),
+        None => return,
+    };
+
+    let style = TextStyle {
+        font: asset_server.load(FONT_PATH),
+        font_size: FONT_SIZE,
+        color: Color::WHITE,
+    };
+
+    spawn_slots(
+        &mut commands,
+        container_list,
+        &style,
+        &registry,
+        &contents.0,
+        true,
+    );
+    spawn_slots(
+        &mut commands,
+        inventory_list,
+        &style,
+        &registry,
+        &contents.1,
+        false,
+    );
+
+    containers.shown = Some(contents);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn move_item() {
+        let mut from = Inventory::default();
+        let mut to = Inventory::default();
+        from.add(3.into(), 2);
+
+        assert!(super::move_item(&mut from, &mut to, 3.into()));
+        assert!(super::move_item(&mut from, &mut to, 3.into()));
+        assert!(!super::move_item(&mut from, &mut to, 3.into()));
+
+        assert!(from.is_empty());
+        assert_eq!(to.count(3.into()), 2);
+    }
+}
@@ -0,0 +1,230 @@
+use bevy::prelude::*;
+use vox::{
+    craft::{Recipe, RecipeRegistry},
+    item::Inventory,
+    voxel::KindRegistry,
+};
+
+use crate::{chat::Chat, console::Console, signs::Signs, FONT_PATH};
+
+const TOGGLE_KEY: KeyCode = KeyCode::Tab;
+
+const FONT_SIZE: f32 = 16.0;
+
+const COLUMNS: usize = 3;
+const CELL_WIDTH: f32 = 220.0;
+const CELL_HEIGHT: f32 = 80.0;
+const CELL_MARGIN: f32 = 4.0;
+
+const CRAFTABLE_COLOR: Color = Color::rgba(0.2, 0.4, 0.2, 0.9);
+const HOVERED_COLOR: Color = Color::rgba(0.3, 0.6, 0.3, 0.9);
+const MISSING_COLOR: Color = Color::rgba(0.2, 0.2, 0.2, 0.9);
+
+/**
+  Crafting panel state. While open, the mouse is used to click on recipes, so it doesn't break or place
+  voxels.
+*/
+#[derive(Default)]
+pub struct Crafting {
+    pub open: bool,
+    root: Option<Entity>,
+}
+
+/// Cell of the crafting grid, holding the index of its recipe on the [`RecipeRegistry`].
+#[derive(Component)]
+struct RecipeCell(usize);
+
+pub struct CraftingPlugin;
+
+impl Plugin for CraftingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Crafting>()
+            .add_startup_system(setup_crafting)
+            .add_system(toggle_crafting)
+            .add_system(craft_on_click)
+            .add_system(update_cells.after(craft_on_click));
+    }
+}
+
+/// Describes the given recipe, like `2 Log + 1 Slab -> 1 Door`.
+pub fn describe(registry: &KindRegistry, recipe: &Recipe) -> String {
+    let inputs = recipe
+        .inputs
+        .iter()
+        .map(|input| format!("{} {}", input.count, registry.name(input.kind.into())))
+        .collect::<Vec<_>>();
+
+    format!(
+        "{} -> {} {}",
+        inputs.join(" + "),
+        recipe.output.count,
+        registry.name(recipe.output.kind.into())
+    )
+}
+
+fn setup_crafting(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    registry: Res<KindRegistry>,
+    recipes: Res<RecipeRegistry>,
+    mut crafting: ResMut<Crafting>,
+) {
+    let style = TextStyle {
+        font: asset_server.load(FONT_PATH),
+        font_size: FONT_SIZE,
+        color: Color::WHITE,
+    };
+
+    let root = commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                display: Display::None,
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(
+                            Val::Px((CELL_WIDTH + CELL_MARGIN * 2.0) * COLUMNS as f32),
+                            Val::Auto,
+                        ),
+                        // UI flows from the bottom, so lines wrap in reverse to list recipes from the top
+                        flex_wrap: FlexWrap::WrapReverse,
+                        ..Default::default()
+                    },
+                    color: Color::NONE.into(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    for (idx, recipe) in recipes.iter().enumerate() {
+                        parent
+                            .spawn_bundle(ButtonBundle {
+                                style: Style {
+                                    size: Size::new(Val::Px(CELL_WIDTH), Val::Px(CELL_HEIGHT)),
+                                    margin: Rect::all(Val::Px(CELL_MARGIN)),
+                                    padding: Rect::all(Val::Px(CELL_MARGIN)),
+                                    justify_content: JustifyContent::Center,
+                                    align_items: AlignItems::Center,
+                                    ..Default::default()
+                                },
+                                color: MISSING_COLOR.into(),
+                                ..Default::default()
+                            })
+                            .insert(RecipeCell(idx))
+                            .with_children(|parent| {
+                                parent.spawn_bundle(TextBundle {
+                                    text: Text::with_section(
+                                        describe(&registry, recipe),
+                                        style.clone(),
+                                        Default::default(),
+                                    ),
+                                    ..Default::default()
+                                });
+                            });
+                    }
+                });
+        })
+        .id();
+
+    crafting.root = Some(root);
+}
+
+fn toggle_crafting(
+    console: Res<Console>,
+    chat: Res<Chat>,
+    signs: Res<Signs>,
+    keyboard: Res<Input<KeyCode>>,
+    mut crafting: ResMut<Crafting>,
+    mut q: Query<&mut Style>,
+) {
+    if console.visible || chat.open || signs.is_editing() || !keyboard.just_pressed(TOGGLE_KEY) {
+        return;
+    }
+
+    crafting.open = !crafting.open;
+
+    if let Some(Ok(mut style)) = crafting.root.map(|root| q.get_mut(root)) {
+        style.display = if crafting.open {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+}
+
+fn craft_on_click(
+    crafting: Res<Crafting>,
+    recipes: Res<RecipeRegistry>,
+    mut inventory: ResMut<Inventory>,
+    q: Query<(&Interaction, &RecipeCell), Changed<Interaction>>,
+) {
+    if !crafting.open {
+        return;
+    }
+
+    for (interaction, RecipeCell(idx)) in q.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        if let Some(recipe) = recipes.get(*idx) {
+            recipe.craft(&mut inventory);
+        }
+    }
+}
+
+/**
+  Highlights recipes which can be crafted with the current inventory, and the one under the mouse.
+*/
+fn update_cells(
+    recipes: Res<RecipeRegistry>,
+    inventory: Res<Inventory>,
+    mut q: Query<(&Interaction, &RecipeCell, &mut UiColor), With<Button>>,
+) {
+    for (interaction, RecipeCell(idx), mut color) in q.iter_mut() {
+        let craftable = matches!(recipes.get(*idx), Some(recipe) if recipe.can_craft(&inventory));
+
+        let new_color = match interaction {
+            _ if !craftable => MISSING_COLOR,
+            Interaction::Hovered | Interaction::Clicked => HOVERED_COLOR,
+            Interaction::None => CRAFTABLE_COLOR,
+        };
+
+        // Avoid triggering change detection on every frame
+        if color.0 != new_color {
+            color.0 = new_color;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vox::craft::ItemStack;
+
+    #[test]
+    fn describe() {
+        let registry = KindRegistry::load(std::path::Path::new(crate::KIND_DESCRIPTIONS_PATH));
+
+        let recipe = Recipe {
+            inputs: vec![
+                ItemStack { kind: 9, count: 2 },
+                ItemStack { kind: 5, count: 1 },
+            ],
+            output: ItemStack { kind: 16, count: 1 },
+        };
+
+        assert_eq!(
+            super::describe(&registry, &recipe),
+            "2 Log + 1 Slab -> 1 Door"
+        );
+    }
+}
@@ -0,0 +1,536 @@
+use bevy::{prelude::*, utils::HashMap};
+use rand::Rng;
+use std::path::Path;
+use vox::{
+    bounds, fluid,
+    item::{self, DroppedItem, Inventory, DROPS_PATH},
+    pipeline::{
+        BlockDecayed, BlockEntityRemoved, ChunkLoaded, ChunkUnloaded, Exploded, WorldOrigin,
+    },
+    surface,
+    voxel::{Kind, KindRegistry},
+    world::VoxWorld,
+};
+
+use crate::{
+    physics::Collider,
+    projectiles::{ProjectileHit, ProjectileTarget},
+    selection::VoxelBroken,
+    spectator::Spectator,
+    MainCamera,
+};
+
+/// Half the size of the cube drops are rendered as.
+const DROP_HALF_SIZE: f32 = 0.125;
+/// Radians per second drops spin around themselves.
+const SPIN_SPEED: f32 = 2.0;
+/// Upwards speed drops are thrown at when the voxel breaks, in voxels per second.
+const POP_SPEED: f32 = 4.0;
+
+/// In voxels per second squared.
+const GRAVITY: f32 = 20.0;
+const MAX_FALL_SPEED: f32 = 30.0;
+
+/// How long, in seconds, a drop lies around before despawning.
+const DESPAWN_TIME: f32 = 300.0;
+/// How long, in seconds, before a new drop can be picked up, so it can be seen popping out of the voxel.
+const PICKUP_DELAY: f32 = 0.5;
+/// How close, in voxels, the player must be to pick up drops.
+const PICKUP_RADIUS: f32 = 1.2;
+/// How far below the eyes the player feet are.
+const PLAYER_HEIGHT: f32 = 1.6;
+/// How much of the velocity of projectiles hitting drops is passed to them.
+const PROJECTILE_PUSH: f32 = 0.5;
+/// Chance of voxels which decayed, like leaves of a cut tree, to drop, so whole trees don't litter the ground.
+const DECAY_DROP_CHANCE: f64 = 0.25;
+
+/// An item lying on the ground, which the player picks up by walking into it.
+#[derive(Component)]
+struct ItemDrop {
+    kind: Kind,
+    count: u32,
+    velocity: Vec3,
+    age: f32,
+}
+
+/// Mesh shared by all drops and the material of each kind, created as needed.
+pub struct DropModels {
+    pub mesh: Handle<Mesh>,
+    materials: HashMap<u16, Handle<StandardMaterial>>,
+}
+
+impl FromWorld for DropModels {
+    fn from_world(world: &mut World) -> Self {
+        let mut meshes = world
+            .get_resource_mut::<Assets<Mesh>>()
+            .expect("PbrPlugin must be added before DropsPlugin");
+        let mesh = meshes.add(Mesh::from(shape::Cube {
+            size: DROP_HALF_SIZE * 2.0,
+        }));
+
+        Self {
+            mesh,
+            materials: Default::default(),
+        }
+    }
+}
+
+impl DropModels {
+    pub fn material(
+        &mut self,
+        registry: &KindRegistry,
+        materials: &mut Assets<StandardMaterial>,
+        kind: Kind,
+    ) -> Handle<StandardMaterial> {
+        self.materials
+            .entry(kind.id())
+            .or_insert_with(|| {
+                let (r, g, b, a) = registry
+                    .get(kind)
+                    .map_or((1.0, 0.0, 1.0, 1.0), |desc| desc.color);
+
+                materials.add(Color::rgba(r, g, b, a).into())
+            })
+            .clone()
+    }
+}
+
+pub struct DropsPlugin;
+
+impl Plugin for DropsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Inventory>()
+            .init_resource::<DropModels>()
+            .add_system(spawn_drops)
+            .add_system(spill_containers)
+            .add_system(explode_drops)
+            .add_system(decay_drops)
+            .add_system(hit_drops)
+            .add_system(simulate_drops)
+            .add_system(pick_up_drops.after(simulate_drops))
+            .add_system(save_drops)
+            .add_system(load_drops);
+    }
+}
+
+/**
+  Moves a falling drop, at the given render space position, for `dt` seconds. Drops stop when they hit any
+  voxel `solid` returns true for, which receives render space positions. Landed drops keep sliding, until the
+  ground friction stops them.
+*/
+pub fn fall(position: Vec3, velocity: Vec3, dt: f32, solid: impl Fn(Vec3) -> bool) -> (Vec3, Vec3) {
+    let mut position = position;
+    let mut velocity = velocity;
+
+    velocity.y = (velocity.y - GRAVITY * dt).max(-MAX_FALL_SPEED);
+
+    let horizontal = position + Vec3::new(velocity.x, 0.0, velocity.z) * dt;
+    if solid(horizontal) {
+        velocity.x = 0.0;
+        velocity.z = 0.0;
+    } else {
+        position = horizontal;
+    }
+
+    let edge = position + Vec3::Y * (DROP_HALF_SIZE * velocity.y.signum() + velocity.y * dt);
+    if !solid(edge) {
+        position.y += velocity.y * dt;
+    } else if velocity.y < 0.0 {
+        // Rests on top of the voxel below
+        position.y = edge.y.floor() + 1.0 + DROP_HALF_SIZE;
+        velocity.y = 0.0;
+    } else {
+        velocity.y = 0.0;
+    }
+
+    (position, velocity)
+}
+
+pub fn is_solid(
+    world: &VoxWorld,
+    registry: &KindRegistry,
+    origin: &WorldOrigin,
+    render: Vec3,
+) -> bool {
+    match world.get_voxel(origin.to_voxel(render)) {
+        Some(kind) if kind.is_empty() => false,
+        Some(kind) => match registry.get(kind) {
+            Some(desc) => desc.prop.is_none() && desc.liquid.is_none(),
+            None => true,
+        },
+        // Drops wait for unloaded chunks, instead of falling through them
+        None => true,
+    }
+}
+
+fn spawn_drop(
+    commands: &mut Commands,
+    models: &mut DropModels,
+    registry: &KindRegistry,
+    materials: &mut Assets<StandardMaterial>,
+    translation: Vec3,
+    drop: ItemDrop,
+) {
+    let material = models.material(registry, materials, drop.kind);
+
+    commands
+        .spawn_bundle(PbrBundle {
+            mesh: models.mesh.clone(),
+            material,
+            transform: Transform::from_translation(translation),
+            ..Default::default()
+        })
+        .insert(drop)
+        .insert(ProjectileTarget {
+            radius: DROP_HALF_SIZE * 2.0,
+        })
+        .insert(Collider {
+            half_extents: Vec3::splat(DROP_HALF_SIZE * 2.0),
+        });
+}
+
+fn spawn_drops(
+    mut commands: Commands,
+    origin: Res<WorldOrigin>,
+    registry: Res<KindRegistry>,
+    mut models: ResMut<DropModels>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut reader: EventReader<VoxelBroken>,
+) {
+    for VoxelBroken { chunk, voxel, kind } in reader.iter() {
+        let translation = origin.voxel_bounds(*chunk, *voxel).center();
+
+        spawn_drop(
+            &mut commands,
+            &mut models,
+            &registry,
+            &mut materials,
+            translation,
+            ItemDrop {
+                kind: kind.id().into(),
+                count: 1,
+                velocity: Vec3::Y * POP_SPEED,
+                age: 0.0,
+            },
+        );
+    }
+}
+
+/**
+  Drops the items held by block entities which were broken, like chests, one drop for each kind.
+*/
+fn spill_containers(
+    mut commands: Commands,
+    origin: Res<WorldOrigin>,
+    registry: Res<KindRegistry>,
+    mut models: ResMut<DropModels>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut reader: EventReader<BlockEntityRemoved>,
+) {
+    for BlockEntityRemoved {
+        chunk,
+        voxel,
+        entity,
+    } in reader.iter()
+    {
+        let inventory = match entity.contents() {
+            Some(inventory) => inventory,
+            None => continue,
+        };
+
+        let translation = origin.voxel_bounds(*chunk, *voxel).center();
+
+        for (idx, (kind, count)) in inventory.iter().enumerate() {
+            // Spread drops around, so they don't all look like a single one
+            let angle = idx as f32 * std::f32::consts::TAU / inventory.len() as f32;
+
+            spawn_drop(
+                &mut commands,
+                &mut models,
+                &registry,
+                &mut materials,
+                translation,
+                ItemDrop {
+                    kind,
+                    count,
+                    velocity: Vec3::new(angle.cos(), POP_SPEED, angle.sin()),
+                    age: 0.0,
+                },
+            );
+        }
+    }
+}
+
+/**
+  Drops some of the voxels destroyed by explosions, fewer the stronger the explosion is, and throws drops
+  lying around away from it.
+*/
+fn explode_drops(
+    mut commands: Commands,
+    origin: Res<WorldOrigin>,
+    registry: Res<KindRegistry>,
+    mut models: ResMut<DropModels>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut reader: EventReader<Exploded>,
+    mut q: Query<(&Transform, &mut ItemDrop)>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for Exploded {
+        explosion,
+        destroyed,
+    } in reader.iter()
+    {
+        for (transform, mut drop) in q.iter_mut() {
+            drop.velocity += explosion.knockback(origin.to_world(transform.translation));
+        }
+
+        let chance = 1.0 / explosion.power.max(1.0);
+
+        for (position, kind) in destroyed {
+            if !rng.gen_bool(chance as f64) {
+                continue;
+            }
+
+            let center = bounds::voxel(*position).center();
+
+            spawn_drop(
+                &mut commands,
+                &mut models,
+                &registry,
+                &mut materials,
+                origin.from_world(center),
+                ItemDrop {
+                    kind: kind.id().into(),
+                    count: 1,
+                    velocity: explosion.knockback(center) + Vec3::Y * POP_SPEED,
+                    age: 0.0,
+                },
+            );
+        }
+    }
+}
+
+/// Drops some of the voxels which decayed. They fall out of the voxel, instead of popping up.
+fn decay_drops(
+    mut commands: Commands,
+    origin: Res<WorldOrigin>,
+    registry: Res<KindRegistry>,
+    mut models: ResMut<DropModels>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut reader: EventReader<BlockDecayed>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for BlockDecayed { chunk, voxel, kind } in reader.iter() {
+        if !rng.gen_bool(DECAY_DROP_CHANCE) {
+            continue;
+        }
+
+        spawn_drop(
+            &mut commands,
+            &mut models,
+            &registry,
+            &mut materials,
+            origin.voxel_bounds(*chunk, *voxel).center(),
+            ItemDrop {
+                kind: kind.id().into(),
+                count: 1,
+                velocity: Vec3::ZERO,
+                age: 0.0,
+            },
+        );
+    }
+}
+
+/// Pushes drops hit by projectiles along with them.
+fn hit_drops(mut reader: EventReader<ProjectileHit>, mut q: Query<&mut ItemDrop>) {
+    for ProjectileHit { target, velocity } in reader.iter() {
+        if let Ok(mut drop) = q.get_mut(*target) {
+            drop.velocity += *velocity * PROJECTILE_PUSH;
+        }
+    }
+}
+
+/**
+  Makes drops fall and spin, and despawns the ones which were lying around for too long. Drops float on
+  liquids, and slide on the ground until its friction stops them.
+*/
+fn simulate_drops(
+    mut commands: Commands,
+    time: Res<Time>,
+    world: Res<VoxWorld>,
+    registry: Res<KindRegistry>,
+    origin: Res<WorldOrigin>,
+    mut q: Query<(Entity, &mut Transform, &mut ItemDrop)>,
+) {
+    let dt = time.delta_seconds();
+
+    for (entity, mut transform, mut drop) in q.iter_mut() {
+        drop.age += dt;
+
+        if drop.age > DESPAWN_TIME {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        let bottom = origin.to_world(transform.translation) - Vec3::Y * DROP_HALF_SIZE;
+        if let Some((depth, liquid)) =
+            fluid::submersion(&world, &registry, bottom, DROP_HALF_SIZE * 2.0)
+        {
+            drop.velocity = fluid::float(drop.velocity, depth, liquid, GRAVITY, dt);
+        }
+
+        if let Some(ground) = surface::ground(&world, &registry, bottom) {
+            drop.velocity = surface::walk(drop.velocity, Vec3::ZERO, ground, dt);
+        }
+
+        let (position, velocity) = fall(transform.translation, drop.velocity, dt, |render| {
+            is_solid(&world, &registry, &origin, render)
+        });
+
+        transform.translation = position;
+        transform.rotation = Quat::from_rotation_y(drop.age * SPIN_SPEED);
+        drop.velocity = velocity;
+    }
+}
+
+/**
+  Moves drops touching the player into the inventory. While spectating, the camera is detached from the
+  player, so nothing is picked up.
+*/
+fn pick_up_drops(
+    mut commands: Commands,
+    spectator: Res<Spectator>,
+    mut inventory: ResMut<Inventory>,
+    camera: Query<&Transform, With<MainCamera>>,
+    drops: Query<(Entity, &Transform, &ItemDrop)>,
+) {
+    if spectator.is_active() {
+        return;
+    }
+
+    let eyes = match camera.get_single() {
+        Ok(transform) => transform.translation,
+        Err(_) => return,
+    };
+
+    for (entity, transform, drop) in drops.iter() {
+        if drop.age < PICKUP_DELAY {
+            continue;
+        }
+
+        // Distance to the segment between the player feet and eyes
+        let body_y = transform
+            .translation
+            .y
+            .clamp(eyes.y - PLAYER_HEIGHT, eyes.y);
+        let closest = Vec3::new(eyes.x, body_y, eyes.z);
+
+        if closest.distance(transform.translation) <= PICKUP_RADIUS {
+            inventory.add(drop.kind, drop.count);
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/**
+  Saves and despawns the drops lying on unloaded chunks.
+*/
+fn save_drops(
+    mut commands: Commands,
+    origin: Res<WorldOrigin>,
+    mut reader: EventReader<ChunkUnloaded>,
+    q: Query<(Entity, &Transform, &ItemDrop)>,
+) {
+    for ChunkUnloaded(local) in reader.iter() {
+        let drops = q
+            .iter()
+            .filter(|(_, transform, _)| origin.to_local(transform.translation) == *local)
+            .map(|(entity, transform, drop)| {
+                commands.entity(entity).despawn();
+
+                DroppedItem {
+                    kind: drop.kind,
+                    count: drop.count,
+                    offset: transform.translation - origin.to_render(*local),
+                    age: drop.age,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        if !drops.is_empty() {
+            item::save_drops(Path::new(DROPS_PATH), *local, &drops);
+        }
+    }
+}
+
+/**
+  Spawns the drops saved when the chunk was last unloaded.
+*/
+fn load_drops(
+    mut commands: Commands,
+    origin: Res<WorldOrigin>,
+    registry: Res<KindRegistry>,
+    mut models: ResMut<DropModels>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut reader: EventReader<ChunkLoaded>,
+) {
+    for ChunkLoaded(local) in reader.iter() {
+        for dropped in item::take_drops(Path::new(DROPS_PATH), *local) {
+            spawn_drop(
+                &mut commands,
+                &mut models,
+                &registry,
+                &mut materials,
+                origin.to_render(*local) + dropped.offset,
+                ItemDrop {
+                    kind: dropped.kind,
+                    count: dropped.count,
+                    velocity: Vec3::ZERO,
+                    age: dropped.age,
+                },
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fall() {
+        // Floor made of the voxels below y = 0
+        let solid = |render: Vec3| render.y < 0.0;
+
+        let (position, velocity) = super::fall(Vec3::new(0.5, 2.0, 0.5), Vec3::ZERO, 0.1, solid);
+        assert!(position.y < 2.0);
+        assert!(velocity.y < 0.0);
+
+        // Lands on the floor, still sliding
+        let (position, velocity) = super::fall(
+            Vec3::new(0.5, 0.2, 0.5),
+            Vec3::new(1.0, -5.0, 0.0),
+            0.1,
+            solid,
+        );
+        assert_eq!(position.y, DROP_HALF_SIZE);
+        assert_eq!(velocity, Vec3::X);
+
+        // Resting drops stay in place
+        let (rest, _) = super::fall(position, Vec3::ZERO, 0.1, solid);
+        assert_eq!(rest, position);
+
+        // Walls stop horizontal movement
+        let wall = |render: Vec3| render.x >= 1.0;
+        let (position, velocity) = super::fall(
+            Vec3::new(0.9, 5.0, 0.5),
+            Vec3::new(5.0, 0.0, 0.0),
+            0.1,
+            wall,
+        );
+        assert_eq!(position.x, 0.9);
+        assert_eq!(velocity.x, 0.0);
+    }
+}
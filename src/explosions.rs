@@ -0,0 +1,331 @@
+use bevy::prelude::*;
+use rand::Rng;
+use vox::{
+    bounds, chunk,
+    pipeline::{ChunkUnloaded, Exploded, FuseLit, WorldOrigin},
+    voxel::KindRegistry,
+    world::VoxWorld,
+};
+
+use crate::{
+    drops::{self, DropModels},
+    spectator::Spectator,
+    MainCamera,
+};
+
+/// Most debris particles spawned by a single explosion.
+const MAX_DEBRIS: usize = 32;
+/// How long, in seconds, debris particles last.
+const DEBRIS_LIFETIME: f32 = 1.5;
+/// Size of debris particles, relative to item drops.
+const DEBRIS_SCALE: f32 = 0.6;
+/// Upwards speed, in voxels per second, added to debris so it flies in an arc.
+const DEBRIS_POP_SPEED: f32 = 6.0;
+/// Most random speed, in voxels per second, added to debris on each axis, so it spreads around.
+const DEBRIS_SPREAD: f32 = 2.0;
+
+/// How many times per second lit fuses swell and shrink back.
+const FUSE_BLINK_RATE: f32 = 2.0;
+/// How much lit fuses swell, relative to their size.
+const FUSE_SWELL: f32 = 0.1;
+
+/// How much of the knockback velocity is lost each second.
+const KNOCKBACK_DRAG: f32 = 4.0;
+/// Knockback slower than this, in voxels per second, stops.
+const MIN_KNOCKBACK_SPEED: f32 = 0.1;
+
+/// A piece of a destroyed voxel, flying away from an explosion.
+#[derive(Component)]
+struct Debris {
+    velocity: Vec3,
+    age: f32,
+}
+
+/**
+  An explosive voxel whose fuse was lit, shown until it blows up. The voxel itself is gone, so this is only
+  what the player sees.
+*/
+#[derive(Component)]
+pub struct LitFuse {
+    /// World voxel it was lit at, which is where it blows up.
+    position: IVec3,
+    elapsed: f32,
+}
+
+/// Mesh of lit fuses, which are as big as a voxel.
+struct FuseModel {
+    mesh: Handle<Mesh>,
+}
+
+impl FromWorld for FuseModel {
+    fn from_world(world: &mut World) -> Self {
+        let mut meshes = world
+            .get_resource_mut::<Assets<Mesh>>()
+            .expect("PbrPlugin must be added before ExplosionsPlugin");
+        let mesh = meshes.add(Mesh::from(shape::Cube { size: 1.0 }));
+
+        Self { mesh }
+    }
+}
+
+/// Pushes the entity away from an explosion, slowing down until it stops.
+#[derive(Component)]
+struct Knockback(Vec3);
+
+pub struct ExplosionsPlugin;
+
+impl Plugin for ExplosionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FuseModel>()
+            .add_system(spawn_lit_fuses)
+            .add_system(update_lit_fuses)
+            .add_system(spawn_debris)
+            .add_system(simulate_debris)
+            .add_system(knock_back_player)
+            .add_system(apply_knockback.after(knock_back_player));
+    }
+}
+
+/**
+  Moves something being knocked back by `dt` seconds, returning the new velocity. Nothing moves into voxels
+  `solid` returns true for, which receives render space positions, and hitting one stops the knockback.
+*/
+pub fn knock_back(
+    position: Vec3,
+    velocity: Vec3,
+    dt: f32,
+    solid: impl Fn(Vec3) -> bool,
+) -> (Vec3, Vec3) {
+    let target = position + velocity * dt;
+
+    if solid(target) {
+        return (position, Vec3::ZERO);
+    }
+
+    let velocity = velocity * (1.0 - KNOCKBACK_DRAG * dt).max(0.0);
+
+    if velocity.length() < MIN_KNOCKBACK_SPEED {
+        (target, Vec3::ZERO)
+    } else {
+        (target, velocity)
+    }
+}
+
+fn spawn_lit_fuses(
+    mut commands: Commands,
+    origin: Res<WorldOrigin>,
+    registry: Res<KindRegistry>,
+    model: Res<FuseModel>,
+    mut models: ResMut<DropModels>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut reader: EventReader<FuseLit>,
+) {
+    for FuseLit {
+        chunk, voxel, kind, ..
+    } in reader.iter()
+    {
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: model.mesh.clone(),
+                material: models.material(&registry, &mut materials, *kind),
+                transform: Transform::from_translation(
+                    origin.voxel_bounds(*chunk, *voxel).center(),
+                ),
+                ..Default::default()
+            })
+            .insert(LitFuse {
+                position: chunk::join_voxel(*chunk, *voxel),
+                elapsed: 0.0,
+            });
+    }
+}
+
+/**
+  Makes lit fuses swell and shrink, and despawns them once they blow up, or when their chunk is unloaded, since
+  they can't be seen anyway. The fuse itself keeps running and is saved with the chunk.
+*/
+fn update_lit_fuses(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut exploded_reader: EventReader<Exploded>,
+    mut unloaded_reader: EventReader<ChunkUnloaded>,
+    mut q: Query<(Entity, &mut Transform, &mut LitFuse)>,
+) {
+    let centers = exploded_reader
+        .iter()
+        .map(|evt| evt.explosion.center)
+        .collect::<Vec<_>>();
+    let unloaded = unloaded_reader
+        .iter()
+        .map(|ChunkUnloaded(local)| *local)
+        .collect::<Vec<_>>();
+
+    for (entity, mut transform, mut fuse) in q.iter_mut() {
+        let center = bounds::voxel(fuse.position).center();
+        let (local, _) = chunk::split_voxel(fuse.position);
+
+        if centers.contains(&center) || unloaded.contains(&local) {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        fuse.elapsed += time.delta_seconds();
+
+        let blink = (fuse.elapsed * FUSE_BLINK_RATE * std::f32::consts::TAU).sin() * 0.5 + 0.5;
+        transform.scale = Vec3::splat(1.0 + blink * FUSE_SWELL);
+    }
+}
+
+/**
+  Spawns debris particles flying away from destroyed voxels. Big explosions only get some of their voxels
+  turned into debris, spread over all of them.
+*/
+fn spawn_debris(
+    mut commands: Commands,
+    origin: Res<WorldOrigin>,
+    registry: Res<KindRegistry>,
+    mut models: ResMut<DropModels>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut reader: EventReader<Exploded>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for Exploded {
+        explosion,
+        destroyed,
+    } in reader.iter()
+    {
+        let step = destroyed.len() / MAX_DEBRIS + 1;
+
+        for (position, kind) in destroyed.iter().step_by(step) {
+            let center = bounds::voxel(*position).center();
+            let spread = Vec3::new(
+                rng.gen_range(-DEBRIS_SPREAD..=DEBRIS_SPREAD),
+                rng.gen_range(0.0..=DEBRIS_SPREAD),
+                rng.gen_range(-DEBRIS_SPREAD..=DEBRIS_SPREAD),
+            );
+
+            commands
+                .spawn_bundle(PbrBundle {
+                    mesh: models.mesh.clone(),
+                    material: models.material(&registry, &mut materials, *kind),
+                    transform: Transform::from_translation(origin.from_world(center))
+                        .with_scale(Vec3::splat(DEBRIS_SCALE)),
+                    ..Default::default()
+                })
+                .insert(Debris {
+                    velocity: explosion.knockback(center) + Vec3::Y * DEBRIS_POP_SPEED + spread,
+                    age: 0.0,
+                });
+        }
+    }
+}
+
+fn simulate_debris(
+    mut commands: Commands,
+    time: Res<Time>,
+    world: Res<VoxWorld>,
+    registry: Res<KindRegistry>,
+    origin: Res<WorldOrigin>,
+    mut q: Query<(Entity, &mut Transform, &mut Debris)>,
+) {
+    let dt = time.delta_seconds();
+
+    for (entity, mut transform, mut debris) in q.iter_mut() {
+        debris.age += dt;
+
+        if debris.age > DEBRIS_LIFETIME {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        let (position, velocity) =
+            drops::fall(transform.translation, debris.velocity, dt, |render| {
+                drops::is_solid(&world, &registry, &origin, render)
+            });
+
+        transform.translation = position;
+        transform.rotate(Quat::from_rotation_x(velocity.length() * dt));
+        debris.velocity = velocity;
+    }
+}
+
+/// Knocks the player back from explosions around it. While spectating, the body is left alone.
+fn knock_back_player(
+    mut commands: Commands,
+    origin: Res<WorldOrigin>,
+    spectator: Res<Spectator>,
+    mut reader: EventReader<Exploded>,
+    mut q: Query<(Entity, &Transform, Option<&mut Knockback>), With<MainCamera>>,
+) {
+    let (entity, transform, knockback) = match q.get_single_mut() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+
+    let push = reader.iter().fold(Vec3::ZERO, |acc, evt| {
+        acc + evt
+            .explosion
+            .knockback(origin.to_world(transform.translation))
+    });
+
+    if push == Vec3::ZERO || spectator.is_active() {
+        return;
+    }
+
+    match knockback {
+        Some(mut knockback) => knockback.0 += push,
+        None => {
+            commands.entity(entity).insert(Knockback(push));
+        }
+    }
+}
+
+fn apply_knockback(
+    mut commands: Commands,
+    time: Res<Time>,
+    world: Res<VoxWorld>,
+    registry: Res<KindRegistry>,
+    origin: Res<WorldOrigin>,
+    mut q: Query<(Entity, &mut Transform, &mut Knockback)>,
+) {
+    for (entity, mut transform, mut knockback) in q.iter_mut() {
+        let (position, velocity) = knock_back(
+            transform.translation,
+            knockback.0,
+            time.delta_seconds(),
+            |render| drops::is_solid(&world, &registry, &origin, render),
+        );
+
+        transform.translation = position;
+        knockback.0 = velocity;
+
+        if velocity == Vec3::ZERO {
+            commands.entity(entity).remove::<Knockback>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn knock_back() {
+        let open = |_: Vec3| false;
+
+        let (position, velocity) = super::knock_back(Vec3::ZERO, Vec3::X * 10.0, 0.1, open);
+        assert_eq!(position, Vec3::X);
+        assert!(velocity.x > 0.0 && velocity.x < 10.0);
+
+        // Slow knockback stops
+        let (_, velocity) = super::knock_back(Vec3::ZERO, Vec3::X * 0.05, 0.1, open);
+        assert_eq!(velocity, Vec3::ZERO);
+
+        // Walls stop it right away
+        let wall = |render: Vec3| render.x >= 0.5;
+        let (position, velocity) = super::knock_back(Vec3::ZERO, Vec3::X * 10.0, 0.1, wall);
+        assert_eq!(position, Vec3::ZERO);
+        assert_eq!(velocity, Vec3::ZERO);
+    }
+}
@@ -0,0 +1,410 @@
+use bevy::{app::AppExit, input::InputSystem, prelude::*, ui::UiSystem};
+use std::{hash::Hash, path::Path};
+use vox::{
+    meta::{WorldMeta, META_PATH},
+    pipeline::{self, GenesisConfig, GenesisHooks, GenesisWorkers, SimulationControl},
+    replay::ReplayPlayer,
+    voxel::KindRegistry,
+    world::VoxWorld,
+};
+
+use crate::{
+    backup,
+    chat::Chat,
+    console::Console,
+    containers::Containers,
+    gamepad::{self, GamepadInput},
+    net::{ClientConfig, ServerConfig},
+    signs::Signs,
+    FONT_PATH,
+};
+
+const PAUSE_KEY: KeyCode = KeyCode::Escape;
+
+const TITLE_SIZE: f32 = 64.0;
+const FONT_SIZE: f32 = 24.0;
+
+const BUTTON_WIDTH: f32 = 260.0;
+const BUTTON_HEIGHT: f32 = 48.0;
+const BUTTON_MARGIN: f32 = 8.0;
+
+/// Saved worlds listed on the main menu, the most recently played ones.
+const MAX_SAVED_WORLDS: usize = 5;
+
+const BUTTON_COLOR: Color = Color::rgba(0.2, 0.2, 0.2, 0.9);
+const HOVERED_COLOR: Color = Color::rgba(0.3, 0.6, 0.3, 0.9);
+
+/**
+  Where the game is at. Games start on the main menu, where new worlds can be created, go through loading
+  while the world around the player streams in, and are played in game, which can be paused. Player input
+  only reaches the game while in game.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameState {
+    MainMenu,
+    NewWorld,
+    Loading,
+    InGame,
+    Paused,
+}
+
+/// Screen shown on menus or while paused, which is gone once the state is left.
+#[derive(Component)]
+struct MenuScreen;
+
+/// Button on a menu screen, which is highlighted while hovered.
+#[derive(Component)]
+struct MenuItem;
+
+/// Button of the main menu which loads the saved world on the given folder, see [`backup::load_world`].
+#[derive(Component)]
+struct SavedWorld(String);
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+enum MenuButton {
+    Play,
+    NewWorld,
+    Resume,
+    Quit,
+}
+
+pub struct GameStatePlugin;
+
+impl Plugin for GameStatePlugin {
+    fn build(&self, app: &mut App) {
+        // Servers, clients and replays already know which world to play, so they skip the main menu
+        let skip_menu = app.world.contains_resource::<ServerConfig>()
+            || app.world.contains_resource::<ClientConfig>()
+            || app.world.contains_resource::<ReplayPlayer>();
+
+        let initial = if skip_menu {
+            GameState::Loading
+        } else {
+            GameState::MainMenu
+        };
+
+        app.add_state(initial)
+            .add_system_set(SystemSet::on_enter(GameState::MainMenu).with_system(setup_main_menu))
+            .add_system_set(
+                SystemSet::on_update(GameState::MainMenu).with_system(load_saved_worlds),
+            )
+            .add_system_set(SystemSet::on_exit(GameState::MainMenu).with_system(despawn_menus))
+            .add_system_set(SystemSet::on_exit(GameState::NewWorld).with_system(despawn_menus))
+            .add_system_set(SystemSet::on_enter(GameState::Paused).with_system(setup_pause_menu))
+            .add_system_set(SystemSet::on_exit(GameState::Paused).with_system(despawn_menus))
+            .add_system(highlight_menu_items)
+            .add_system(click_menu_buttons)
+            .add_system(pause_simulation)
+            .add_system_to_stage(CoreStage::PreUpdate, toggle_pause.after(InputSystem))
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                hold_input
+                    .after(toggle_pause)
+                    .after(UiSystem::Focus)
+                    .after(gamepad::read_gamepad),
+            );
+    }
+}
+
+/// Spawns the root of a menu screen, which is gone once the state is left.
+pub fn menu_root(commands: &mut Commands, color: Color) -> Entity {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: color.into(),
+            ..Default::default()
+        })
+        .insert(MenuScreen)
+        .id()
+}
+
+pub fn spawn_title(parent: &mut ChildBuilder, font: Handle<Font>, title: &str) {
+    parent.spawn_bundle(TextBundle {
+        style: Style {
+            margin: Rect::all(Val::Px(TITLE_SIZE / 2.0)),
+            ..Default::default()
+        },
+        text: Text::with_section(
+            title,
+            TextStyle {
+                font,
+                font_size: TITLE_SIZE,
+                color: Color::WHITE,
+            },
+            Default::default(),
+        ),
+        ..Default::default()
+    });
+}
+
+/// Spawns a menu button, with the given component telling what it does.
+pub fn spawn_button(
+    parent: &mut ChildBuilder,
+    font: Handle<Font>,
+    label: &str,
+    button: impl Component,
+) -> Entity {
+    parent
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(BUTTON_WIDTH), Val::Px(BUTTON_HEIGHT)),
+                margin: Rect::all(Val::Px(BUTTON_MARGIN)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: BUTTON_COLOR.into(),
+            ..Default::default()
+        })
+        .insert(MenuItem)
+        .insert(button)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    label,
+                    TextStyle {
+                        font,
+                        font_size: FONT_SIZE,
+                        color: Color::WHITE,
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+        })
+        .id()
+}
+
+/// Name worlds are shown with, since worlds from before they had names don't have one.
+fn world_name(meta: &WorldMeta) -> &str {
+    if meta.name.is_empty() {
+        "world"
+    } else {
+        &meta.name
+    }
+}
+
+/**
+  Main menu, which continues the world saved on the cache, when there's one, loads one of the saved worlds or
+  creates a new one, with its own seed.
+*/
+fn setup_main_menu(mut commands: Commands, asset_server: Res<AssetServer>, meta: Res<WorldMeta>) {
+    let font = asset_server.load(FONT_PATH);
+
+    let root = menu_root(&mut commands, Color::BLACK);
+    commands.entity(root).with_children(|parent| {
+        spawn_title(parent, font.clone(), "Eterno");
+
+        if Path::new(META_PATH).exists() {
+            let label = format!("Continue {}", world_name(&meta));
+            spawn_button(parent, font.clone(), &label, MenuButton::Play);
+        }
+
+        let saved = backup::list_worlds().unwrap_or_else(|err| {
+            error!("Failed to list saved worlds: {}", err);
+            vec![]
+        });
+
+        for (folder, saved_meta) in saved.into_iter().take(MAX_SAVED_WORLDS) {
+            let label = format!("Load {}", world_name(&saved_meta));
+            spawn_button(parent, font.clone(), &label, SavedWorld(folder));
+        }
+
+        spawn_button(parent, font.clone(), "New world", MenuButton::NewWorld);
+        spawn_button(parent, font, "Quit", MenuButton::Quit);
+    });
+}
+
+fn setup_pause_menu(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load(FONT_PATH);
+
+    let root = menu_root(&mut commands, Color::rgba(0.0, 0.0, 0.0, 0.6));
+    commands.entity(root).with_children(|parent| {
+        spawn_title(parent, font.clone(), "Paused");
+        spawn_button(parent, font.clone(), "Resume", MenuButton::Resume);
+        spawn_button(parent, font, "Quit", MenuButton::Quit);
+    });
+}
+
+fn despawn_menus(mut commands: Commands, q: Query<Entity, With<MenuScreen>>) {
+    for entity in q.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn highlight_menu_items(
+    mut q: Query<(&Interaction, &mut UiColor), (Changed<Interaction>, With<MenuItem>)>,
+) {
+    for (interaction, mut color) in q.iter_mut() {
+        *color = match interaction {
+            Interaction::Hovered | Interaction::Clicked => HOVERED_COLOR,
+            Interaction::None => BUTTON_COLOR,
+        }
+        .into();
+    }
+}
+
+fn click_menu_buttons(
+    mut state: ResMut<State<GameState>>,
+    mut exit_writer: EventWriter<AppExit>,
+    q: Query<(&Interaction, &MenuButton), Changed<Interaction>>,
+) {
+    for (interaction, button) in q.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        match button {
+            MenuButton::Play => {
+                let _ = state.set(GameState::Loading);
+            }
+            MenuButton::NewWorld => {
+                let _ = state.set(GameState::NewWorld);
+            }
+            MenuButton::Resume => {
+                let _ = state.set(GameState::InGame);
+            }
+            MenuButton::Quit => exit_writer.send(AppExit),
+        }
+    }
+}
+
+/**
+  Loads the clicked saved world, setting the one on the cache aside, and starts generating chunks out of the
+  loaded world generators.
+*/
+#[allow(clippy::too_many_arguments)]
+fn load_saved_worlds(
+    world: Res<VoxWorld>,
+    registry: Res<KindRegistry>,
+    genesis_config: Res<GenesisConfig>,
+    hooks: Res<GenesisHooks>,
+    mut meta: ResMut<WorldMeta>,
+    mut workers: ResMut<GenesisWorkers>,
+    mut state: ResMut<State<GameState>>,
+    q: Query<(&Interaction, &SavedWorld), Changed<Interaction>>,
+) {
+    for (interaction, SavedWorld(folder)) in q.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        if let Err(err) = backup::load_world(folder) {
+            error!("Failed to load saved world {}: {}", folder, err);
+            continue;
+        }
+
+        let loaded = WorldMeta::load(Path::new(META_PATH));
+        info!("Loaded world {}", world_name(&loaded));
+
+        *workers =
+            pipeline::start_genesis(&world, *genesis_config, hooks.clone(), &loaded, &registry);
+        *meta = loaded;
+
+        let _ = state.set(GameState::Loading);
+        return;
+    }
+}
+
+/**
+  The pause key pauses and resumes the game. While typing or using a container, it's left for them to close
+  what's open instead.
+*/
+fn toggle_pause(
+    console: Res<Console>,
+    chat: Res<Chat>,
+    signs: Res<Signs>,
+    containers: Res<Containers>,
+    keyboard: Res<Input<KeyCode>>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if !keyboard.just_pressed(PAUSE_KEY) {
+        return;
+    }
+
+    match state.current() {
+        GameState::InGame
+            if !console.visible && !chat.open && !signs.is_editing() && !containers.is_open() =>
+        {
+            let _ = state.set(GameState::Paused);
+        }
+        GameState::Paused => {
+            let _ = state.set(GameState::InGame);
+        }
+        _ => (),
+    }
+}
+
+/**
+  The world is only simulated while in game. Networked games keep being simulated, since others are still
+  playing on them.
+*/
+fn pause_simulation(
+    state: Res<State<GameState>>,
+    server: Option<Res<ServerConfig>>,
+    client: Option<Res<ClientConfig>>,
+    mut simulation: ResMut<SimulationControl>,
+) {
+    if !state.is_changed() || server.is_some() || client.is_some() {
+        return;
+    }
+
+    if *state.current() == GameState::InGame {
+        simulation.resume();
+    } else {
+        simulation.pause();
+    }
+}
+
+/// Lets go of everything pressed, so nothing reaches the player systems.
+fn release_all<T: Copy + Eq + Hash + Send + Sync + 'static>(input: &mut Input<T>) {
+    for pressed in input.get_pressed().copied().collect::<Vec<_>>() {
+        input.reset(pressed);
+    }
+
+    input.clear();
+}
+
+/// Holds back the player input while not in game, after menus had their chance to use the mouse.
+fn hold_input(
+    state: Res<State<GameState>>,
+    mut keyboard: ResMut<Input<KeyCode>>,
+    mut mouse: ResMut<Input<MouseButton>>,
+    mut gamepad: ResMut<GamepadInput>,
+) {
+    if *state.current() == GameState::InGame {
+        return;
+    }
+
+    release_all(&mut keyboard);
+    release_all(&mut mouse);
+    gamepad.movement = Vec2::ZERO;
+    gamepad.look = Vec2::ZERO;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn release_all() {
+        let mut keyboard = Input::<KeyCode>::default();
+        keyboard.press(KeyCode::W);
+        keyboard.press(KeyCode::E);
+        keyboard.release(KeyCode::E);
+
+        super::release_all(&mut keyboard);
+
+        assert!(!keyboard.pressed(KeyCode::W));
+        assert!(!keyboard.just_pressed(KeyCode::W));
+        assert!(!keyboard.just_released(KeyCode::E));
+    }
+}
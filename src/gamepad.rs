@@ -0,0 +1,250 @@
+use bevy::{input::InputSystem, prelude::*};
+
+use crate::console::Console;
+
+/// Key or mouse button a gamepad button stands for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+}
+
+/// How gamepads are read. Insert it before [`GamepadPlugin`] to change it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GamepadConfig {
+    /// Sticks tilted less than this, from 0 to 1, are ignored, so worn sticks don't drift.
+    pub dead_zone: f32,
+    /// Radians turned per second with the right stick fully tilted.
+    pub look_speed: f32,
+    /// Gamepad buttons and the key or mouse button each of them presses.
+    pub bindings: Vec<(GamepadButtonType, Binding)>,
+}
+
+impl Default for GamepadConfig {
+    fn default() -> Self {
+        Self {
+            dead_zone: 0.2,
+            look_speed: 3.0,
+            bindings: vec![
+                (
+                    GamepadButtonType::RightTrigger2,
+                    Binding::Mouse(MouseButton::Left),
+                ),
+                (
+                    GamepadButtonType::LeftTrigger2,
+                    Binding::Mouse(MouseButton::Right),
+                ),
+                (GamepadButtonType::South, Binding::Key(KeyCode::Space)),
+                (GamepadButtonType::East, Binding::Key(KeyCode::C)),
+                (GamepadButtonType::West, Binding::Key(KeyCode::E)),
+                (GamepadButtonType::North, Binding::Key(KeyCode::Q)),
+                (GamepadButtonType::RightTrigger, Binding::Key(KeyCode::F)),
+                (GamepadButtonType::LeftThumb, Binding::Key(KeyCode::LShift)),
+                (GamepadButtonType::Select, Binding::Key(KeyCode::Tab)),
+                (GamepadButtonType::Start, Binding::Key(KeyCode::M)),
+            ],
+        }
+    }
+}
+
+/**
+  Stick state of the gamepad in use, which is the first one connected and still around. Sticks are read
+  with the dead zone already applied, from -1 to 1 on each axis, with up and right being positive.
+*/
+#[derive(Default)]
+pub struct GamepadInput {
+    /// Movement from the left stick.
+    pub movement: Vec2,
+    /// Camera turning from the right stick.
+    pub look: Vec2,
+    /// Connected gamepads, in the order they were connected.
+    connected: Vec<Gamepad>,
+    /// Keys and mouse buttons held down by gamepad buttons.
+    held: Vec<Binding>,
+}
+
+impl GamepadInput {
+    fn active(&self) -> Option<Gamepad> {
+        self.connected.first().copied()
+    }
+
+    fn connect(&mut self, gamepad: Gamepad) {
+        if !self.connected.contains(&gamepad) {
+            self.connected.push(gamepad);
+        }
+    }
+
+    /// Forgets the gamepad, returning whether it was the one in use.
+    fn disconnect(&mut self, gamepad: Gamepad) -> bool {
+        let active = self.active() == Some(gamepad);
+        self.connected.retain(|connected| *connected != gamepad);
+
+        active
+    }
+
+    /// Returns the direction to move to, relative to the camera facing, from the left stick.
+    pub fn move_direction(&self, transform: &Transform) -> Vec3 {
+        transform.forward() * self.movement.y + transform.right() * self.movement.x
+    }
+}
+
+/**
+  Plays with gamepads. The left stick moves and the right stick turns the camera, while the other buttons
+  press the keys and mouse buttons of what they're bound to, so anything done with those can be done with
+  gamepads too. Gamepads can be plugged in and out at any time: the first one connected is used, until
+  it's gone and the next one takes over.
+*/
+pub struct GamepadPlugin;
+
+impl Plugin for GamepadPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GamepadConfig>()
+            .init_resource::<GamepadInput>()
+            .add_system_to_stage(CoreStage::PreUpdate, connect_gamepads.after(InputSystem))
+            .add_system_to_stage(CoreStage::PreUpdate, read_gamepad.after(connect_gamepads));
+    }
+}
+
+/**
+  Applies a radial dead zone to the stick. Tilts within the dead zone are ignored, and the ones past it are
+  scaled, so the stick still goes smoothly from 0 to 1.
+*/
+pub fn apply_dead_zone(stick: Vec2, dead_zone: f32) -> Vec2 {
+    let tilt = stick.length();
+
+    if tilt <= dead_zone {
+        return Vec2::ZERO;
+    }
+
+    let scaled = ((tilt - dead_zone) / (1.0 - dead_zone)).min(1.0);
+    stick / tilt * scaled
+}
+
+fn release(binding: Binding, keyboard: &mut Input<KeyCode>, mouse: &mut Input<MouseButton>) {
+    match binding {
+        Binding::Key(key) => keyboard.release(key),
+        Binding::Mouse(button) => mouse.release(button),
+    }
+}
+
+/// Keeps track of gamepads being plugged in and out, letting go of anything held by the one gone.
+fn connect_gamepads(
+    mut input: ResMut<GamepadInput>,
+    mut console: ResMut<Console>,
+    mut keyboard: ResMut<Input<KeyCode>>,
+    mut mouse: ResMut<Input<MouseButton>>,
+    mut reader: EventReader<GamepadEvent>,
+) {
+    for GamepadEvent(gamepad, event) in reader.iter() {
+        match event {
+            GamepadEventType::Connected => {
+                input.connect(*gamepad);
+                console.print(format!("Gamepad {} connected", gamepad.0));
+            }
+            GamepadEventType::Disconnected => {
+                if input.disconnect(*gamepad) {
+                    for binding in std::mem::take(&mut input.held) {
+                        release(binding, &mut keyboard, &mut mouse);
+                    }
+
+                    input.movement = Vec2::ZERO;
+                    input.look = Vec2::ZERO;
+                }
+
+                console.print(format!("Gamepad {} disconnected", gamepad.0));
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Reads the sticks and presses or releases the keys and mouse buttons bound to the gamepad buttons.
+pub(super) fn read_gamepad(
+    config: Res<GamepadConfig>,
+    axes: Res<Axis<GamepadAxis>>,
+    buttons: Res<Input<GamepadButton>>,
+    mut input: ResMut<GamepadInput>,
+    mut keyboard: ResMut<Input<KeyCode>>,
+    mut mouse: ResMut<Input<MouseButton>>,
+) {
+    let gamepad = match input.active() {
+        Some(gamepad) => gamepad,
+        None => return,
+    };
+
+    let stick = |x, y| {
+        let axis = |axis_type| {
+            axes.get(GamepadAxis(gamepad, axis_type))
+                .unwrap_or_default()
+        };
+        apply_dead_zone(Vec2::new(axis(x), axis(y)), config.dead_zone)
+    };
+
+    input.movement = stick(GamepadAxisType::LeftStickX, GamepadAxisType::LeftStickY);
+    input.look = stick(GamepadAxisType::RightStickX, GamepadAxisType::RightStickY);
+
+    for &(button_type, binding) in &config.bindings {
+        let button = GamepadButton(gamepad, button_type);
+
+        if buttons.just_pressed(button) {
+            match binding {
+                Binding::Key(key) => keyboard.press(key),
+                Binding::Mouse(button) => mouse.press(button),
+            }
+            input.held.push(binding);
+        } else if buttons.just_released(button) {
+            release(binding, &mut keyboard, &mut mouse);
+            input.held.retain(|held| *held != binding);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply_dead_zone() {
+        assert_eq!(super::apply_dead_zone(Vec2::new(0.1, 0.1), 0.2), Vec2::ZERO);
+        assert_eq!(super::apply_dead_zone(Vec2::X, 0.2), Vec2::X);
+        let half = super::apply_dead_zone(Vec2::new(0.6, 0.0), 0.2);
+        assert!((half - Vec2::X * 0.5).length() < 1e-5);
+
+        // Keeps the direction, and diagonals don't go past full tilt
+        let stick = super::apply_dead_zone(Vec2::ONE, 0.2);
+        assert!((stick - Vec2::ONE.normalize()).length() < 1e-5);
+    }
+
+    #[test]
+    fn hot_plug() {
+        let mut input = GamepadInput::default();
+        assert_eq!(input.active(), None);
+
+        input.connect(Gamepad(1));
+        input.connect(Gamepad(0));
+        input.connect(Gamepad(1));
+        assert_eq!(input.active(), Some(Gamepad(1)));
+
+        // The next gamepad takes over once the one in use is gone
+        assert!(!input.disconnect(Gamepad(0)));
+        input.connect(Gamepad(0));
+        assert!(input.disconnect(Gamepad(1)));
+        assert_eq!(input.active(), Some(Gamepad(0)));
+
+        assert!(input.disconnect(Gamepad(0)));
+        assert_eq!(input.active(), None);
+    }
+
+    #[test]
+    fn move_direction() {
+        let input = GamepadInput {
+            movement: Vec2::new(1.0, -0.5),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            input.move_direction(&Transform::default()),
+            Vec3::new(1.0, 0.0, 0.5)
+        );
+    }
+}
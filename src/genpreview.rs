@@ -0,0 +1,234 @@
+use bevy::prelude::*;
+use image::{Rgb, RgbImage};
+use std::{path::PathBuf, str::FromStr};
+use vox::{
+    biome::{self, Biome},
+    generator::{Terrain, WorldGenerator},
+};
+
+use crate::path_arg;
+
+/// Biggest preview, in columns on each axis, so a typo doesn't take forever to render.
+const MAX_SIZE: u32 = 4096;
+
+const WATER_COLOR: [f32; 3] = [0.2, 0.4, 0.8];
+
+/// What the preview colors columns by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreviewLayer {
+    /// Grayscale, from black on the lowest columns to white on the highest.
+    Height,
+    /// Color of each biome, shaded by height.
+    Biome,
+}
+
+impl FromStr for PreviewLayer {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "height" => Ok(PreviewLayer::Height),
+            "biome" => Ok(PreviewLayer::Biome),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Region and generator of a preview, read from the `genpreview` command line arguments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreviewConfig {
+    pub generator: WorldGenerator,
+    /// World column on the top left corner of the preview.
+    pub origin: IVec2,
+    /// Columns on each axis, one pixel each.
+    pub size: u32,
+    pub layer: PreviewLayer,
+    pub output: PathBuf,
+}
+
+impl Default for PreviewConfig {
+    fn default() -> Self {
+        Self {
+            generator: WorldGenerator::default(),
+            origin: IVec2::ZERO,
+            size: 512,
+            layer: PreviewLayer::Biome,
+            output: PathBuf::from("genpreview.png"),
+        }
+    }
+}
+
+/// Reads the value following the given flag on the command line, like `--size 256`.
+fn arg<T: FromStr>(args: &[String], flag: &str) -> Option<T> {
+    let idx = args.iter().position(|arg| arg == flag)?;
+    let value = args
+        .get(idx + 1)
+        .unwrap_or_else(|| panic!("{} requires a value", flag));
+
+    Some(
+        value
+            .parse()
+            .unwrap_or_else(|_| panic!("Invalid value for {}: {}", flag, value)),
+    )
+}
+
+impl PreviewConfig {
+    /**
+      Reads the preview from the given arguments, like `--seed 42 --x -256 --z -256 --size 512 --layer height
+      --out map.png`. Missing arguments keep their defaults, which are the ones of new noise worlds.
+    */
+    pub fn from_args(args: &[String]) -> Self {
+        let default = Self::default();
+
+        let size = arg(args, "--size").unwrap_or(default.size);
+        assert!(
+            (1..=MAX_SIZE).contains(&size),
+            "--size must be from 1 to {}",
+            MAX_SIZE
+        );
+
+        Self {
+            generator: WorldGenerator {
+                seed: arg(args, "--seed").unwrap_or(default.generator.seed),
+                height: arg(args, "--height").unwrap_or(default.generator.height),
+                ..default.generator
+            },
+            origin: IVec2::new(
+                arg(args, "--x").unwrap_or(default.origin.x),
+                arg(args, "--z").unwrap_or(default.origin.y),
+            ),
+            size,
+            layer: arg(args, "--layer").unwrap_or(default.layer),
+            output: path_arg(args, "--out").unwrap_or(default.output),
+        }
+    }
+}
+
+fn biome_color(biome: Biome) -> [f32; 3] {
+    match biome {
+        Biome::Plains => [0.3, 0.6, 0.3],
+        Biome::Desert => [0.85, 0.8, 0.5],
+        Biome::Snowy => [0.9, 0.9, 0.95],
+    }
+}
+
+/// Color of a column of the given height, on a terrain going up to `max`. Flooded columns are water.
+fn column_color(
+    layer: PreviewLayer,
+    biome: Biome,
+    height: f32,
+    max: f32,
+    flooded: bool,
+) -> Rgb<u8> {
+    let shade = (height / max).clamp(0.0, 1.0);
+
+    let color = match (layer, flooded) {
+        (_, true) => WATER_COLOR.map(|c| c * (0.5 + shade)),
+        (PreviewLayer::Height, false) => [shade; 3],
+        (PreviewLayer::Biome, false) => biome_color(biome).map(|c| c * (0.5 + shade / 2.0)),
+    };
+
+    Rgb(color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8))
+}
+
+/// Top-down map of the previewed region, with a pixel for each column. X goes right and Z goes down.
+pub fn render(config: &PreviewConfig) -> RgbImage {
+    let terrain = Terrain::new(&config.generator);
+    let size = config.size as usize;
+    let max = config.generator.height as f32;
+
+    let heights = terrain.heights(config.origin.x, config.origin.y, size);
+    let water_level = terrain.water_level();
+
+    RgbImage::from_fn(config.size, config.size, |x, z| {
+        let height = heights[x as usize * size + z as usize];
+        let biome = biome::at(config.origin + IVec2::new(x as i32, z as i32));
+        let flooded = matches!(water_level, Some(level) if height.floor() < level);
+
+        column_color(config.layer, biome, height, max, flooded)
+    })
+}
+
+/**
+  Runs the `genpreview` command line subcommand, which renders a top-down map of the terrain generated by
+  the given seed to a PNG, without starting the game, so generator changes can be checked right away.
+*/
+pub fn genpreview_command(args: &[String]) {
+    let config = PreviewConfig::from_args(args);
+
+    render(&config)
+        .save(&config.output)
+        .unwrap_or_else(|_| panic!("Unable to save preview {}", config.output.display()));
+
+    println!(
+        "Saved {:?} preview of {}x{} columns from {} to {}",
+        config.layer,
+        config.size,
+        config.size,
+        config.origin,
+        config.output.display()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn from_args() {
+        assert_eq!(PreviewConfig::from_args(&[]), PreviewConfig::default());
+
+        let config = PreviewConfig::from_args(&args(&[
+            "--seed", "7", "--x", "-64", "--z", "32", "--size", "16", "--layer", "height", "--out",
+            "map.png",
+        ]));
+
+        assert_eq!(config.generator.seed, 7);
+        assert_eq!(config.origin, IVec2::new(-64, 32));
+        assert_eq!(config.size, 16);
+        assert_eq!(config.layer, PreviewLayer::Height);
+        assert_eq!(config.output, PathBuf::from("map.png"));
+    }
+
+    #[test]
+    #[should_panic]
+    fn from_args_invalid_layer() {
+        PreviewConfig::from_args(&args(&["--layer", "temperature"]));
+    }
+
+    #[test]
+    fn render() {
+        let config = PreviewConfig {
+            size: 32,
+            layer: PreviewLayer::Height,
+            ..Default::default()
+        };
+
+        let image = super::render(&config);
+        assert_eq!(image.dimensions(), (32, 32));
+
+        // Same seed, same map
+        assert_eq!(super::render(&config), image);
+    }
+
+    #[test]
+    fn column_color() {
+        let color = |layer, height, flooded| {
+            super::column_color(layer, Biome::Plains, height, 10.0, flooded)
+        };
+
+        assert_eq!(color(PreviewLayer::Height, 0.0, false), Rgb([0, 0, 0]));
+        assert_eq!(
+            color(PreviewLayer::Height, 10.0, false),
+            Rgb([255, 255, 255])
+        );
+        assert_ne!(
+            color(PreviewLayer::Biome, 5.0, true),
+            color(PreviewLayer::Biome, 5.0, false)
+        );
+    }
+}
@@ -0,0 +1,306 @@
+use bevy::prelude::*;
+use vox::{
+    fluid::MAX_AIR,
+    item::{self, Inventory, ToolRegistry},
+    voxel::KindRegistry,
+};
+
+use crate::{
+    player::PlayerAir,
+    selection::{HeldTool, Mining, SelectedKind, VoxelTarget},
+    sprint::{CharacterConfig, Sprint},
+    FONT_PATH,
+};
+
+const CROSSHAIR_SIZE: f32 = 16.0;
+const CROSSHAIR_THICKNESS: f32 = 2.0;
+const CROSSHAIR_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.8);
+
+const FONT_SIZE: f32 = 20.0;
+
+#[derive(Component)]
+struct TargetText;
+
+#[derive(Component)]
+struct SelectedText;
+
+#[derive(Component)]
+struct ToolText;
+
+#[derive(Component)]
+struct InventoryText;
+
+#[derive(Component)]
+struct StaminaText;
+
+#[derive(Component)]
+struct AirText;
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(setup_hud)
+            .add_system(update_target_text)
+            .add_system(update_selected_text)
+            .add_system(update_tool_text)
+            .add_system(update_inventory_text)
+            .add_system(update_stamina_text)
+            .add_system(update_air_text);
+    }
+}
+
+fn setup_hud(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn_bundle(UiCameraBundle::default());
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(CROSSHAIR_SIZE), Val::Px(CROSSHAIR_SIZE)),
+                        ..Default::default()
+                    },
+                    color: Color::NONE.into(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    let offset = (CROSSHAIR_SIZE - CROSSHAIR_THICKNESS) / 2.0;
+
+                    // Vertical bar
+                    parent.spawn_bundle(crosshair_bar(
+                        Rect {
+                            left: Val::Px(offset),
+                            top: Val::Px(0.0),
+                            ..Default::default()
+                        },
+                        Size::new(Val::Px(CROSSHAIR_THICKNESS), Val::Px(CROSSHAIR_SIZE)),
+                    ));
+
+                    // Horizontal bar
+                    parent.spawn_bundle(crosshair_bar(
+                        Rect {
+                            left: Val::Px(0.0),
+                            top: Val::Px(offset),
+                            ..Default::default()
+                        },
+                        Size::new(Val::Px(CROSSHAIR_SIZE), Val::Px(CROSSHAIR_THICKNESS)),
+                    ));
+                });
+        });
+
+    let font = asset_server.load(FONT_PATH);
+
+    commands
+        .spawn_bundle(hud_text(font.clone(), FONT_SIZE + 14.0))
+        .insert(TargetText);
+
+    commands
+        .spawn_bundle(hud_text(font.clone(), 10.0))
+        .insert(SelectedText);
+
+    commands
+        .spawn_bundle(hud_text(font.clone(), FONT_SIZE * 2.0 + 18.0))
+        .insert(ToolText);
+
+    commands
+        .spawn_bundle(hud_text(font.clone(), FONT_SIZE * 3.0 + 22.0))
+        .insert(InventoryText);
+
+    commands
+        .spawn_bundle(hud_text(font.clone(), FONT_SIZE * 4.0 + 26.0))
+        .insert(StaminaText);
+
+    commands
+        .spawn_bundle(hud_text(font, FONT_SIZE * 5.0 + 30.0))
+        .insert(AirText);
+}
+
+fn crosshair_bar(position: Rect<Val>, size: Size<Val>) -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position,
+            size,
+            ..Default::default()
+        },
+        color: CROSSHAIR_COLOR.into(),
+        ..Default::default()
+    }
+}
+
+fn hud_text(font: Handle<Font>, bottom: f32) -> TextBundle {
+    TextBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: Rect {
+                left: Val::Px(10.0),
+                bottom: Val::Px(bottom),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        text: Text::with_section(
+            "",
+            TextStyle {
+                font,
+                font_size: FONT_SIZE,
+                color: Color::WHITE,
+            },
+            Default::default(),
+        ),
+        ..Default::default()
+    }
+}
+
+/**
+  Shows the targeted kind, with how much of it was broken, or the tier needed to break it when the held
+  tool can't.
+*/
+fn update_target_text(
+    registry: Res<KindRegistry>,
+    tools: Res<ToolRegistry>,
+    held: Res<HeldTool>,
+    target: Res<VoxelTarget>,
+    mining: Res<Mining>,
+    mut q: Query<&mut Text, With<TargetText>>,
+) {
+    if !target.is_changed() && !mining.is_changed() && !held.is_changed() {
+        return;
+    }
+
+    let desc = match target.0.and_then(|target| registry.get(target.kind)) {
+        Some(desc) => desc,
+        None => {
+            for mut text in q.iter_mut() {
+                text.sections[0].value.clear();
+            }
+            return;
+        }
+    };
+
+    let tool = held.0.and_then(|idx| tools.get(idx));
+    let value = match item::mining_time(desc, tool) {
+        None => format!("Target: {} (needs tier {} tool)", desc.name, desc.tool_tier),
+        Some(_) if mining.progress > 0.0 => format!(
+            "Target: {} {:.0}%",
+            desc.name,
+            mining.progress.min(1.0) * 100.0
+        ),
+        Some(_) => format!("Target: {}", desc.name),
+    };
+
+    for mut text in q.iter_mut() {
+        text.sections[0].value = value.clone();
+    }
+}
+
+fn update_selected_text(
+    registry: Res<KindRegistry>,
+    selected: Res<SelectedKind>,
+    mut q: Query<&mut Text, With<SelectedText>>,
+) {
+    if !selected.is_changed() {
+        return;
+    }
+
+    for mut text in q.iter_mut() {
+        text.sections[0].value = format!("Selected: {}", registry.name(selected.0));
+    }
+}
+
+fn update_tool_text(
+    tools: Res<ToolRegistry>,
+    held: Res<HeldTool>,
+    mut q: Query<&mut Text, With<ToolText>>,
+) {
+    if !held.is_changed() {
+        return;
+    }
+
+    let name = held
+        .0
+        .and_then(|idx| tools.get(idx))
+        .map_or("Hands", |tool| &tool.name);
+
+    for mut text in q.iter_mut() {
+        text.sections[0].value = format!("Tool: {}", name);
+    }
+}
+
+fn update_inventory_text(
+    registry: Res<KindRegistry>,
+    inventory: Res<Inventory>,
+    mut q: Query<&mut Text, With<InventoryText>>,
+) {
+    if !inventory.is_changed() {
+        return;
+    }
+
+    let items = inventory
+        .iter()
+        .map(|(kind, count)| format!("{} x{}", registry.name(kind), count))
+        .collect::<Vec<_>>();
+
+    for mut text in q.iter_mut() {
+        text.sections[0].value = if items.is_empty() {
+            String::new()
+        } else {
+            format!("Items: {}", items.join(", "))
+        };
+    }
+}
+
+/// Shows the stamina left, while it isn't full.
+fn update_stamina_text(
+    config: Res<CharacterConfig>,
+    sprint: Res<Sprint>,
+    mut q: Query<&mut Text, With<StaminaText>>,
+) {
+    if !sprint.is_changed() {
+        return;
+    }
+
+    let value = match config.stamina {
+        Some(stamina) if sprint.stamina < stamina.max => {
+            format!("Stamina: {:.0}%", sprint.stamina / stamina.max * 100.0)
+        }
+        _ => String::new(),
+    };
+
+    for mut text in q.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+/// Shows the air left, while it isn't full.
+fn update_air_text(air: Res<PlayerAir>, mut q: Query<&mut Text, With<AirText>>) {
+    if !air.is_changed() {
+        return;
+    }
+
+    let value = if air.0.air < MAX_AIR {
+        format!("Air: {:.0}%", air.0.air / MAX_AIR * 100.0)
+    } else {
+        String::new()
+    };
+
+    for mut text in q.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use bevy::prelude::*;
+use vox::{
+    heightmap::Heightmap,
+    pipeline::{StreamingAnchor, WorldOrigin},
+    query,
+    world::VoxWorld,
+};
+use vox_render::entities::ChunkEntityMap;
+
+use crate::{game_state::GameState, MainCamera, FONT_PATH};
+
+/// Radius, in chunks, around the camera which is loaded and meshed before the player gets control.
+const SPAWN_RADIUS: u32 = 2;
+/// Players get control after this many seconds, even when not everything is loaded, like on slow servers.
+const MAX_LOADING_TIME: f64 = 60.0;
+
+const FONT_SIZE: f32 = 24.0;
+
+const BAR_WIDTH: f32 = 400.0;
+const BAR_HEIGHT: f32 = 16.0;
+const BAR_COLOR: Color = Color::rgb(0.3, 0.6, 0.3);
+const BAR_BACKGROUND_COLOR: Color = Color::rgb(0.2, 0.2, 0.2);
+
+/**
+  Progress of [`GameState::Loading`], when entering the world. Until the chunks around the spawn are
+  generated and meshed, a loading screen covers the world, so nobody falls into the void.
+*/
+#[derive(Default)]
+struct Loading {
+    /// How much of the spawn area is ready, from 0 to 1.
+    progress: f32,
+    /// When loading started, in seconds since startup.
+    started: f64,
+}
+
+#[derive(Component)]
+struct LoadingScreen;
+
+#[derive(Component)]
+struct LoadingText;
+
+#[derive(Component)]
+struct LoadingBar;
+
+pub struct LoadingPlugin;
+
+impl Plugin for LoadingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Loading>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Loading)
+                    .with_system(start_loading)
+                    .with_system(setup_loading_screen),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Loading)
+                    .with_system(track_loading)
+                    .with_system(update_loading_screen.after(track_loading)),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Loading).with_system(despawn_loading_screen),
+            );
+    }
+}
+
+/**
+  Fraction, from 0 to 1, of the chunks within `radius` of `center` which are ready to be played on, as told
+  by `is_ready`.
+*/
+pub fn progress(center: IVec3, radius: u32, is_ready: impl Fn(IVec3) -> bool) -> f32 {
+    let (ready, total) = query::sphere(center, radius).fold((0, 0), |(ready, total), local| {
+        (ready + is_ready(local) as usize, total + 1)
+    });
+
+    ready as f32 / total as f32
+}
+
+/// Starts streaming the world around the camera, which was kept from loading anything until now.
+fn start_loading(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut loading: ResMut<Loading>,
+    q: Query<Entity, With<MainCamera>>,
+) {
+    *loading = Loading {
+        progress: 0.0,
+        started: time.seconds_since_startup(),
+    };
+
+    for entity in q.iter() {
+        commands.entity(entity).insert(StreamingAnchor::default());
+    }
+}
+
+fn setup_loading_screen(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::BLACK.into(),
+            ..Default::default()
+        })
+        .insert(LoadingScreen)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    style: Style {
+                        margin: Rect::all(Val::Px(FONT_SIZE / 2.0)),
+                        ..Default::default()
+                    },
+                    text: Text::with_section(
+                        "Loading world",
+                        TextStyle {
+                            font: asset_server.load(FONT_PATH),
+                            font_size: FONT_SIZE,
+                            color: Color::WHITE,
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(LoadingText);
+
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(BAR_WIDTH), Val::Px(BAR_HEIGHT)),
+                        ..Default::default()
+                    },
+                    color: BAR_BACKGROUND_COLOR.into(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Percent(0.0), Val::Percent(100.0)),
+                                ..Default::default()
+                            },
+                            color: BAR_COLOR.into(),
+                            ..Default::default()
+                        })
+                        .insert(LoadingBar);
+                });
+        });
+}
+
+/**
+  Checks how much of the spawn area is ready. Chunks are ready once they're generated and meshed, and
+  loading is done once they all are and the surface the player spawns on is known.
+*/
+#[allow(clippy::too_many_arguments)]
+fn track_loading(
+    time: Res<Time>,
+    world: Res<VoxWorld>,
+    origin: Res<WorldOrigin>,
+    heightmap: Res<Heightmap>,
+    entity_map: Res<ChunkEntityMap>,
+    mut loading: ResMut<Loading>,
+    mut state: ResMut<State<GameState>>,
+    q: Query<&Transform, With<MainCamera>>,
+) {
+    let transform = match q.get_single() {
+        Ok(transform) => transform,
+        Err(_) => return,
+    };
+
+    let center = origin.to_local(transform.translation);
+    loading.progress = progress(center, SPAWN_RADIUS, |local| {
+        world.exists(local) && entity_map.0.contains_key(&local)
+    });
+
+    let voxel = origin.to_voxel(transform.translation);
+    let spawned = heightmap.surface(IVec2::new(voxel.x, voxel.z)).is_some();
+
+    let elapsed = time.seconds_since_startup() - loading.started;
+
+    if (loading.progress >= 1.0 && spawned) || elapsed > MAX_LOADING_TIME {
+        let _ = state.set(GameState::InGame);
+    }
+}
+
+fn update_loading_screen(
+    loading: Res<Loading>,
+    mut texts: Query<&mut Text, With<LoadingText>>,
+    mut bars: Query<&mut Style, With<LoadingBar>>,
+) {
+    if !loading.is_changed() {
+        return;
+    }
+
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = format!("Loading world {:.0}%", loading.progress * 100.0);
+    }
+
+    for mut style in bars.iter_mut() {
+        style.size.width = Val::Percent(loading.progress * 100.0);
+    }
+}
+
+fn despawn_loading_screen(mut commands: Commands, q: Query<Entity, With<LoadingScreen>>) {
+    for entity in q.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress() {
+        let center = IVec3::new(3, 0, -2);
+
+        assert_eq!(super::progress(center, 2, |_| false), 0.0);
+        assert_eq!(super::progress(center, 2, |_| true), 1.0);
+
+        // Only the center is ready, out of the center and its six neighbors
+        let progress = super::progress(center, 1, |local| local == center);
+        assert_eq!(progress, 1.0 / 7.0);
+    }
+}
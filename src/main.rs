@@ -1,8 +1,276 @@
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+
 use bevy::prelude::*;
+use vox::{
+    craft::RecipeRegistry,
+    heightmap::Heightmap,
+    item::ToolRegistry,
+    meta::{WorldMeta, META_PATH},
+    pipeline::{PipelinePlugin, StreamingCenter, WorldOrigin},
+    replay::{ReplayPlayer, ReplayRecorder},
+    tick::RandomTickConfig,
+    voxel::KindRegistry,
+};
+use vox_render::VoxRenderPlugin;
+
+mod admin;
+mod ambience;
+mod backup;
+mod builder;
+mod camera_effects;
+mod camera_path;
+mod chat;
+mod claims;
+mod combat;
+mod console;
+mod containers;
+mod crafting;
+mod drops;
+mod explosions;
+mod game_state;
+mod gamepad;
+mod genpreview;
+mod hud;
+mod loading;
+mod messages;
+mod minimap;
+mod mobs;
+mod mods;
+mod music;
+mod nameplates;
+mod net;
+mod new_world;
+mod physics;
+mod player;
+mod portals;
+mod projectiles;
+mod screenshot;
+mod selection;
+mod signs;
+mod spectator;
+mod sprint;
+mod stats;
+mod tickets;
+mod underwater;
+mod weather;
+mod world_map;
+
+const KIND_DESCRIPTIONS_PATH: &str = "assets/voxels/kind_descriptions.ron";
+const TOOL_DESCRIPTIONS_PATH: &str = "assets/items/tool_descriptions.ron";
+const RECIPES_PATH: &str = "assets/items/recipes.ron";
+const MUSIC_CONFIG_PATH: &str = "assets/music/music.ron";
+
+/// Dir the asset server loads assets from. Paths of assets loaded by it are relative to this dir.
+pub(crate) const ASSETS_DIR: &str = "assets";
+
+/// Font of every text drawn over the game, like the HUD, the console, menus and signs.
+pub(crate) const FONT_PATH: &str = "fonts/FiraMono-Medium.ttf";
+
+/// How high above the surface, in voxels, the camera is placed when spawning.
+const SPAWN_EYE_HEIGHT: f32 = 1.7;
+
+/// Marks the camera which the player sees the world through.
+#[derive(Component)]
+pub struct MainCamera;
 
 fn main() {
-    App::new()
-        .insert_resource(Msaa { samples: 4 })
+    let args = std::env::args().collect::<Vec<_>>();
+
+    if args.get(1).map(String::as_str) == Some("restore") {
+        backup::restore_command(&args[2..]);
+        return;
+    }
+
+    if args.get(1).map(String::as_str) == Some("genpreview") {
+        genpreview::genpreview_command(&args[2..]);
+        return;
+    }
+
+    let mut app = App::new();
+
+    if let Some(config) = admin::AdminConfig::from_args(&args) {
+        app.insert_resource(config);
+    }
+
+    if let Some(config) = net::ServerConfig::from_args(&args) {
+        app.insert_resource(config);
+    } else if let Some(config) = net::ClientConfig::from_args(&args) {
+        // The server owns the simulation, so clients only apply the changes it sends
+        app.insert_resource(config)
+            .insert_resource(RandomTickConfig { per_chunk: 0 });
+    }
+
+    if let Some(path) = path_arg(&args, "--record") {
+        app.insert_resource(ReplayRecorder::create(&path));
+    }
+
+    if let Some(path) = path_arg(&args, "--replay") {
+        // Random ticks were recorded, so running them again would change the outcome
+        app.insert_resource(ReplayPlayer::load(&path))
+            .insert_resource(RandomTickConfig { per_chunk: 0 });
+    }
+
+    let kinds = KindRegistry::load(Path::new(KIND_DESCRIPTIONS_PATH));
+    let recipes = RecipeRegistry::load(Path::new(RECIPES_PATH), &kinds);
+
+    app.insert_resource(Msaa { samples: 4 })
+        .insert_resource(backup::BackupConfig::from_args(&args))
+        .insert_resource(kinds)
+        .insert_resource(recipes)
+        .insert_resource(ToolRegistry::load(Path::new(TOOL_DESCRIPTIONS_PATH)))
+        .insert_resource(music::MusicConfig::load(Path::new(MUSIC_CONFIG_PATH)))
+        .insert_resource(WorldMeta::load(Path::new(META_PATH)))
         .add_plugins(DefaultPlugins)
+        .add_plugin(PipelinePlugin)
+        .add_plugin(VoxRenderPlugin)
+        .add_plugin(selection::SelectionPlugin)
+        .add_plugin(hud::HudPlugin)
+        .add_plugin(game_state::GameStatePlugin)
+        .add_plugin(loading::LoadingPlugin)
+        .add_plugin(new_world::NewWorldPlugin)
+        .add_plugin(console::ConsolePlugin)
+        .add_plugin(minimap::MinimapPlugin)
+        .add_plugin(world_map::WorldMapPlugin)
+        .add_plugin(weather::WeatherPlugin)
+        .add_plugin(builder::BuilderPlugin)
+        .add_plugin(admin::AdminPlugin)
+        .add_plugin(net::NetPlugin)
+        .add_plugin(chat::ChatPlugin)
+        .add_plugin(claims::ClaimsPlugin)
+        .add_plugin(backup::BackupPlugin)
+        .add_plugin(screenshot::ScreenshotPlugin)
+        .add_plugin(camera_path::CameraPathPlugin)
+        .add_plugin(gamepad::GamepadPlugin)
+        .add_plugin(player::PlayerPlugin)
+        .add_plugin(spectator::SpectatorPlugin)
+        .add_plugin(sprint::SprintPlugin)
+        .add_plugin(camera_effects::CameraEffectsPlugin)
+        .add_plugin(underwater::UnderwaterPlugin)
+        .add_plugin(ambience::AmbiencePlugin)
+        .add_plugin(music::MusicPlugin)
+        .add_plugin(tickets::TicketsPlugin)
+        .add_plugin(drops::DropsPlugin)
+        .add_plugin(explosions::ExplosionsPlugin)
+        .add_plugin(projectiles::ProjectilesPlugin)
+        .add_plugin(crafting::CraftingPlugin)
+        .add_plugin(containers::ContainersPlugin)
+        .add_plugin(signs::SignsPlugin)
+        .add_plugin(mods::ModsPlugin)
+        .add_plugin(stats::StatsPlugin)
+        .add_plugin(mobs::MobsPlugin)
+        .add_plugin(combat::CombatPlugin)
+        .add_plugin(physics::PhysicsPlugin)
+        .add_plugin(portals::PortalsPlugin)
+        .add_plugin(nameplates::NameplatesPlugin)
+        .add_plugin(messages::MessagesPlugin)
+        .add_startup_system(setup)
+        .add_system(spawn_on_surface)
         .run();
-}
\ No newline at end of file
+}
+
+/**
+  Reads the address following the given flag on the command line, like `--host 127.0.0.1:7777`.
+*/
+pub fn addr_arg(args: &[String], flag: &str) -> Option<SocketAddr> {
+    let idx = args.iter().position(|arg| arg == flag)?;
+    let addr = args
+        .get(idx + 1)
+        .unwrap_or_else(|| panic!("{} requires an address, like 127.0.0.1:7777", flag));
+
+    Some(
+        addr.parse()
+            .unwrap_or_else(|_| panic!("Invalid address for {}: {}", flag, addr)),
+    )
+}
+
+/// Reads the path following the given flag on the command line, like `--replay replays/bug.bin`.
+pub fn path_arg(args: &[String], flag: &str) -> Option<PathBuf> {
+    let idx = args.iter().position(|arg| arg == flag)?;
+    let path = args
+        .get(idx + 1)
+        .unwrap_or_else(|| panic!("{} requires a path", flag));
+
+    Some(PathBuf::from(path))
+}
+
+fn setup(mut commands: Commands) {
+    commands
+        .spawn_bundle(PerspectiveCameraBundle {
+            transform: Transform::from_xyz(0.0, 48.0, 0.0)
+                .looking_at(Vec3::new(32.0, 16.0, 32.0), Vec3::Y),
+            ..Default::default()
+        })
+        .insert(MainCamera)
+        .insert(StreamingCenter);
+
+    commands.spawn_bundle(DirectionalLightBundle {
+        directional_light: DirectionalLight {
+            shadows_enabled: true,
+            ..Default::default()
+        },
+        transform: Transform::from_xyz(10.0, 30.0, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+        ..Default::default()
+    });
+}
+
+/**
+  Places the camera on the surface of the column it's over, as soon as the heightmap knows it.
+*/
+fn spawn_on_surface(
+    mut spawned: Local<bool>,
+    origin: Res<WorldOrigin>,
+    heightmap: Res<Heightmap>,
+    mut q: Query<&mut Transform, With<MainCamera>>,
+) {
+    if *spawned {
+        return;
+    }
+
+    let mut transform = match q.get_single_mut() {
+        Ok(transform) => transform,
+        Err(_) => return,
+    };
+
+    let world = origin.to_voxel(transform.translation);
+
+    if let Some(surface) = heightmap.surface(IVec2::new(world.x, world.z)) {
+        let surface = origin.from_world(surface.as_vec3());
+        transform.translation.y = surface.y + SPAWN_EYE_HEIGHT;
+
+        *spawned = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn addr_arg() {
+        let args = ["eterno", "--host", "127.0.0.1:7777"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            super::addr_arg(&args, "--host"),
+            Some("127.0.0.1:7777".parse().unwrap())
+        );
+        assert_eq!(super::addr_arg(&args, "--connect"), None);
+    }
+
+    #[test]
+    fn path_arg() {
+        let args = ["eterno", "--replay", "replays/bug.bin"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            super::path_arg(&args, "--replay"),
+            Some("replays/bug.bin".into())
+        );
+        assert_eq!(super::path_arg(&args, "--record"), None);
+    }
+}
@@ -0,0 +1,288 @@
+use bevy::prelude::*;
+use std::collections::VecDeque;
+use vox::{biome::Biome, pipeline::WorldOrigin, world::VoxWorld};
+
+use crate::{game_state::GameState, MainCamera, FONT_PATH};
+
+const TITLE_FONT_SIZE: f32 = 64.0;
+const SUBTITLE_FONT_SIZE: f32 = 28.0;
+const TOAST_FONT_SIZE: f32 = 20.0;
+
+/// Seconds titles and subtitles are shown for, fading included.
+const TITLE_TIME: f32 = 3.0;
+/// Seconds toasts are shown for, fading included.
+const TOAST_TIME: f32 = 4.0;
+/// Seconds messages take to fade in, and to fade out.
+const FADE_TIME: f32 = 0.5;
+
+/// Opacity of a message shown for `elapsed` out of `duration` seconds, fading in and out on its ends.
+pub fn opacity(elapsed: f32, duration: f32) -> f32 {
+    let fade_in = elapsed / FADE_TIME;
+    let fade_out = (duration - elapsed) / FADE_TIME;
+
+    fade_in.min(fade_out).clamp(0.0, 1.0)
+}
+
+/// Messages waiting to be shown one after the other, each for the same duration.
+struct MessageQueue {
+    duration: f32,
+    queue: VecDeque<String>,
+    /// Text of the message being shown, with how many seconds it was shown for.
+    current: Option<(String, f32)>,
+}
+
+impl MessageQueue {
+    fn new(duration: f32) -> Self {
+        Self {
+            duration,
+            queue: VecDeque::default(),
+            current: None,
+        }
+    }
+
+    fn push(&mut self, text: String) {
+        self.queue.push_back(text);
+    }
+
+    /// Advances the shown message, which is replaced by the next queued one once it's over.
+    fn tick(&mut self, dt: f32) {
+        if let Some((_, elapsed)) = &mut self.current {
+            *elapsed += dt;
+
+            if *elapsed >= self.duration {
+                self.current = None;
+            }
+        }
+
+        if self.current.is_none() {
+            self.current = self.queue.pop_front().map(|text| (text, 0.0));
+        }
+    }
+
+    /// Text of the message being shown, with its opacity.
+    fn current(&self) -> Option<(&str, f32)> {
+        self.current
+            .as_ref()
+            .map(|(text, elapsed)| (text.as_str(), opacity(*elapsed, self.duration)))
+    }
+}
+
+/**
+  Messages shown over the game, fading in and out. Titles are shown on the middle of the screen, with
+  subtitles right below them, while toasts are shown on a corner, for less important things. Each kind is
+  shown one at a time, with later messages waiting for earlier ones to be over.
+*/
+pub struct Messages {
+    title: MessageQueue,
+    subtitle: MessageQueue,
+    toast: MessageQueue,
+}
+
+impl Default for Messages {
+    fn default() -> Self {
+        Self {
+            title: MessageQueue::new(TITLE_TIME),
+            subtitle: MessageQueue::new(TITLE_TIME),
+            toast: MessageQueue::new(TOAST_TIME),
+        }
+    }
+}
+
+impl Messages {
+    pub fn show_title(&mut self, text: impl Into<String>) {
+        self.title.push(text.into());
+    }
+
+    pub fn show_subtitle(&mut self, text: impl Into<String>) {
+        self.subtitle.push(text.into());
+    }
+
+    pub fn show_toast(&mut self, text: impl Into<String>) {
+        self.toast.push(text.into());
+    }
+}
+
+/// Texts messages are shown on.
+struct MessageTexts {
+    title: Entity,
+    subtitle: Entity,
+    toast: Entity,
+}
+
+pub struct MessagesPlugin;
+
+impl Plugin for MessagesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Messages>()
+            .add_startup_system(setup_messages)
+            .add_system(announce_biome)
+            .add_system(update_messages.after(announce_biome));
+    }
+}
+
+fn message_text(font: Handle<Font>, font_size: f32) -> TextBundle {
+    TextBundle {
+        text: Text::with_section(
+            String::default(),
+            TextStyle {
+                font,
+                font_size,
+                color: Color::NONE,
+            },
+            TextAlignment {
+                horizontal: HorizontalAlign::Center,
+                ..Default::default()
+            },
+        ),
+        ..Default::default()
+    }
+}
+
+fn setup_messages(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load::<Font, _>(FONT_PATH);
+
+    let title = commands
+        .spawn_bundle(message_text(font.clone(), TITLE_FONT_SIZE))
+        .id();
+    let subtitle = commands
+        .spawn_bundle(message_text(font.clone(), SUBTITLE_FONT_SIZE))
+        .id();
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                // Columns grow upwards, so it's reversed to keep subtitles below titles
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .push_children(&[title, subtitle]);
+
+    let mut toast = message_text(font, TOAST_FONT_SIZE);
+    toast.style = Style {
+        position_type: PositionType::Absolute,
+        position: Rect {
+            right: Val::Px(10.0),
+            top: Val::Px(10.0),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let toast = commands.spawn_bundle(toast).id();
+
+    commands.insert_resource(MessageTexts {
+        title,
+        subtitle,
+        toast,
+    });
+}
+
+/// Shows a toast when the player walks into another biome. Joining a game doesn't count as entering one.
+fn announce_biome(
+    state: Res<State<GameState>>,
+    origin: Res<WorldOrigin>,
+    world: Res<VoxWorld>,
+    mut messages: ResMut<Messages>,
+    mut last: Local<Option<Biome>>,
+    q: Query<&GlobalTransform, With<MainCamera>>,
+) {
+    let voxel = match q.get_single() {
+        Ok(transform) if *state.current() == GameState::InGame => {
+            origin.to_voxel(transform.translation)
+        }
+        _ => {
+            *last = None;
+            return;
+        }
+    };
+
+    let biome = world.biome(voxel);
+
+    if last.is_some() && *last != Some(biome) {
+        messages.show_toast(format!("Entering {:?}", biome));
+    }
+
+    *last = Some(biome);
+}
+
+fn update_messages(
+    time: Res<Time>,
+    mut messages: ResMut<Messages>,
+    texts: Option<Res<MessageTexts>>,
+    mut q: Query<&mut Text>,
+) {
+    let texts = match texts {
+        Some(texts) => texts,
+        None => return,
+    };
+
+    let dt = time.delta_seconds();
+    let messages = &mut *messages;
+
+    for (queue, entity) in [
+        (&mut messages.title, texts.title),
+        (&mut messages.subtitle, texts.subtitle),
+        (&mut messages.toast, texts.toast),
+    ] {
+        queue.tick(dt);
+
+        let mut text = match q.get_mut(entity) {
+            Ok(text) => text,
+            Err(_) => continue,
+        };
+
+        match queue.current() {
+            Some((value, opacity)) => {
+                let section = &mut text.sections[0];
+                section.value = value.to_string();
+                section.style.color = Color::rgba(1.0, 1.0, 1.0, opacity);
+            }
+            // Texts are only touched when there's something to clear, so they aren't laid out every frame
+            None if !text.sections[0].value.is_empty() => {
+                text.sections[0].value.clear();
+            }
+            None => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opacity() {
+        assert_eq!(super::opacity(0.0, 3.0), 0.0);
+        assert_eq!(super::opacity(FADE_TIME / 2.0, 3.0), 0.5);
+        assert_eq!(super::opacity(1.5, 3.0), 1.0);
+        assert_eq!(super::opacity(3.0 - FADE_TIME / 2.0, 3.0), 0.5);
+        assert_eq!(super::opacity(4.0, 3.0), 0.0);
+    }
+
+    #[test]
+    fn queue() {
+        let mut queue = MessageQueue::new(1.0);
+        queue.push("first".to_string());
+        queue.push("second".to_string());
+
+        queue.tick(0.0);
+        assert_eq!(queue.current(), Some(("first", 0.0)));
+
+        queue.tick(0.5);
+        assert_eq!(queue.current(), Some(("first", 1.0)));
+
+        // Later messages wait for earlier ones to be over
+        queue.tick(0.6);
+        assert_eq!(queue.current(), Some(("second", 0.0)));
+
+        queue.tick(1.0);
+        assert_eq!(queue.current(), None);
+    }
+}
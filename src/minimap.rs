@@ -0,0 +1,323 @@
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+    utils::HashMap,
+};
+use std::collections::HashSet;
+use vox::{
+    chunk::{self, ChunkKind},
+    pipeline::{ChunkUnloaded, ChunkUpdated, WorldOrigin},
+    voxel::{Kind, KindRegistry},
+    world::VoxWorld,
+};
+
+use crate::MainCamera;
+
+/// How many chunks, around the center, are displayed on each direction.
+const MAP_RADIUS: i32 = 4;
+const MAP_SIZE: usize = MAP_RADIUS as usize * 2 * chunk::AXIS_SIZE;
+const DISPLAY_SIZE: f32 = 192.0;
+
+pub(super) const BACKGROUND_COLOR: [u8; 4] = [0, 0, 0, 160];
+const PLAYER_COLOR: Color = Color::RED;
+
+/// The highest non-empty voxel of each column of a single chunk, indexed by [`column_index`].
+pub type ChunkSurface = Vec<Option<(i32, Kind)>>;
+
+/**
+  Top surfaces of all loaded chunks, grouped by chunk column (x, z) and then by chunk height.
+*/
+#[derive(Default)]
+pub struct MinimapColumns(HashMap<IVec2, HashMap<i32, ChunkSurface>>);
+
+impl MinimapColumns {
+    fn set(&mut self, local: IVec3, surface: ChunkSurface) {
+        self.0
+            .entry(to_column(local))
+            .or_default()
+            .insert(local.y, surface);
+    }
+
+    fn remove(&mut self, local: IVec3) {
+        let column = to_column(local);
+
+        if let Some(surfaces) = self.0.get_mut(&column) {
+            surfaces.remove(&local.y);
+
+            if surfaces.is_empty() {
+                self.0.remove(&column);
+            }
+        }
+    }
+
+    /**
+      Returns the kind of the highest non-empty voxel of the given voxel column, across all loaded chunks.
+    */
+    pub fn top(&self, column: IVec2, x: usize, z: usize) -> Option<Kind> {
+        let surfaces = self.0.get(&column)?;
+        let index = column_index(x, z);
+
+        surfaces
+            .iter()
+            .filter_map(|(chunk_y, surface)| surface[index].map(|(y, kind)| (chunk_y, y, kind)))
+            .max_by_key(|(chunk_y, y, _)| (**chunk_y, *y))
+            .map(|(_, _, kind)| kind)
+    }
+}
+
+pub struct Minimap {
+    image: Handle<Image>,
+    center: IVec2,
+}
+
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MinimapColumns>()
+            .add_startup_system(setup_minimap)
+            .add_system(update_minimap);
+    }
+}
+
+pub(super) fn to_column(local: IVec3) -> IVec2 {
+    IVec2::new(local.x, local.z)
+}
+
+fn column_index(x: usize, z: usize) -> usize {
+    x * chunk::AXIS_SIZE + z
+}
+
+pub fn chunk_surface(kind: &ChunkKind) -> ChunkSurface {
+    let mut surface = vec![None; chunk::AXIS_SIZE * chunk::AXIS_SIZE];
+
+    for x in 0..chunk::AXIS_SIZE {
+        for z in 0..chunk::AXIS_SIZE {
+            surface[column_index(x, z)] = (0..chunk::AXIS_SIZE as i32).rev().find_map(|y| {
+                let kind = kind.get((x as i32, y, z as i32).into());
+                (!kind.is_empty()).then_some((y, kind))
+            });
+        }
+    }
+
+    surface
+}
+
+/**
+  Returns the pixel index, on the minimap image, of the first voxel column of the given chunk column.
+  Returns `None` if the chunk column is outside the map.
+*/
+pub fn pixel_origin(center: IVec2, column: IVec2) -> Option<(usize, usize)> {
+    let offset = column - center + IVec2::splat(MAP_RADIUS);
+
+    if offset.min_element() < 0 || offset.max_element() >= MAP_RADIUS * 2 {
+        None
+    } else {
+        Some((
+            offset.x as usize * chunk::AXIS_SIZE,
+            offset.y as usize * chunk::AXIS_SIZE,
+        ))
+    }
+}
+
+pub(super) fn kind_color(registry: &KindRegistry, kind: Option<Kind>) -> [u8; 4] {
+    match kind.and_then(|kind| registry.get(kind)) {
+        Some(desc) => {
+            let (r, g, b, _) = desc.color;
+            [r, g, b, 1.0].map(|c| (c.clamp(0.0, 1.0) * 255.0) as u8)
+        }
+        None => BACKGROUND_COLOR,
+    }
+}
+
+fn paint_column(
+    data: &mut [u8],
+    center: IVec2,
+    column: IVec2,
+    columns: &MinimapColumns,
+    registry: &KindRegistry,
+) {
+    let (origin_x, origin_z) = match pixel_origin(center, column) {
+        Some(origin) => origin,
+        None => return,
+    };
+
+    for x in 0..chunk::AXIS_SIZE {
+        for z in 0..chunk::AXIS_SIZE {
+            let pixel = ((origin_z + z) * MAP_SIZE + origin_x + x) * 4;
+            let color = kind_color(registry, columns.top(column, x, z));
+
+            data[pixel..pixel + 4].copy_from_slice(&color);
+        }
+    }
+}
+
+fn setup_minimap(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    let image = images.add(Image::new_fill(
+        Extent3d {
+            width: MAP_SIZE as u32,
+            height: MAP_SIZE as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &BACKGROUND_COLOR,
+        TextureFormat::Rgba8UnormSrgb,
+    ));
+
+    commands
+        .spawn_bundle(ImageBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(10.0),
+                    top: Val::Px(10.0),
+                    ..Default::default()
+                },
+                size: Size::new(Val::Px(DISPLAY_SIZE), Val::Px(DISPLAY_SIZE)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            image: image.clone().into(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent.spawn_bundle(NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Px(4.0), Val::Px(4.0)),
+                    ..Default::default()
+                },
+                color: PLAYER_COLOR.into(),
+                ..Default::default()
+            });
+        });
+
+    commands.insert_resource(Minimap {
+        image,
+        center: IVec2::ZERO,
+    });
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(super) fn update_minimap(
+    world: Res<VoxWorld>,
+    origin: Res<WorldOrigin>,
+    registry: Res<KindRegistry>,
+    mut minimap: ResMut<Minimap>,
+    mut columns: ResMut<MinimapColumns>,
+    mut images: ResMut<Assets<Image>>,
+    mut updated_reader: EventReader<ChunkUpdated>,
+    mut unloaded_reader: EventReader<ChunkUnloaded>,
+    camera: Query<&GlobalTransform, With<MainCamera>>,
+) {
+    let mut dirty_columns = HashSet::new();
+
+    for ChunkUpdated(local) in updated_reader.iter() {
+        if let Some(kind) = world.get(*local) {
+            columns.set(*local, chunk_surface(&kind));
+            dirty_columns.insert(to_column(*local));
+        }
+    }
+
+    for ChunkUnloaded(local) in unloaded_reader.iter() {
+        columns.remove(*local);
+        dirty_columns.insert(to_column(*local));
+    }
+
+    if let Ok(transform) = camera.get_single() {
+        let center = to_column(origin.to_local(transform.translation));
+
+        // When the center moves, the whole map must be painted again
+        if center != minimap.center {
+            minimap.center = center;
+
+            dirty_columns.extend(
+                (-MAP_RADIUS..MAP_RADIUS)
+                    .flat_map(|x| (-MAP_RADIUS..MAP_RADIUS).map(move |z| IVec2::new(x, z)))
+                    .map(|offset| center + offset),
+            );
+        }
+    }
+
+    if dirty_columns.is_empty() {
+        return;
+    }
+
+    if let Some(image) = images.get_mut(&minimap.image) {
+        for column in dirty_columns {
+            paint_column(&mut image.data, minimap.center, column, &columns, &registry);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunk_surface() {
+        let mut kind = ChunkKind::default();
+        kind.set((0, 0, 0).into(), 1.into());
+        kind.set((0, 5, 0).into(), 2.into());
+        kind.set((3, 15, 4).into(), 3.into());
+
+        let surface = super::chunk_surface(&kind);
+
+        assert_eq!(surface[column_index(0, 0)], Some((5, 2.into())));
+        assert_eq!(surface[column_index(3, 4)], Some((15, 3.into())));
+        assert_eq!(surface[column_index(4, 3)], None);
+    }
+
+    #[test]
+    fn top() {
+        let mut columns = MinimapColumns::default();
+
+        let mut bottom = ChunkKind::default();
+        bottom.set((1, 15, 1).into(), 1.into());
+        bottom.set((2, 3, 2).into(), 1.into());
+
+        let mut top = ChunkKind::default();
+        top.set((1, 0, 1).into(), 2.into());
+
+        columns.set((3, -1, 3).into(), super::chunk_surface(&bottom));
+        columns.set((3, 0, 3).into(), super::chunk_surface(&top));
+
+        let column = IVec2::new(3, 3);
+        assert_eq!(columns.top(column, 1, 1), Some(2.into()));
+        assert_eq!(columns.top(column, 2, 2), Some(1.into()));
+        assert_eq!(columns.top(column, 5, 5), None);
+        assert_eq!(columns.top(IVec2::ZERO, 1, 1), None);
+
+        columns.remove((3, 0, 3).into());
+        assert_eq!(columns.top(column, 1, 1), Some(1.into()));
+
+        columns.remove((3, -1, 3).into());
+        assert!(columns.0.is_empty());
+    }
+
+    #[test]
+    fn pixel_origin() {
+        let center = IVec2::new(10, -10);
+
+        assert_eq!(
+            super::pixel_origin(center, center - IVec2::splat(MAP_RADIUS)),
+            Some((0, 0))
+        );
+        assert_eq!(
+            super::pixel_origin(center, center),
+            Some((MAP_SIZE / 2, MAP_SIZE / 2))
+        );
+        assert_eq!(
+            super::pixel_origin(center, center + IVec2::splat(MAP_RADIUS - 1)),
+            Some((MAP_SIZE - chunk::AXIS_SIZE, MAP_SIZE - chunk::AXIS_SIZE))
+        );
+        assert_eq!(
+            super::pixel_origin(center, center + IVec2::new(MAP_RADIUS, 0)),
+            None
+        );
+        assert_eq!(
+            super::pixel_origin(center, center - IVec2::new(0, MAP_RADIUS + 1)),
+            None
+        );
+    }
+}
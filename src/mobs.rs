@@ -0,0 +1,303 @@
+use bevy::prelude::*;
+use vox::{
+    body::Body,
+    broadphase::{self, Broadphase},
+    chunk,
+    combat::Health,
+    fluid::{self, AirMeter},
+    light::LightWorld,
+    mob::{self, MobAction, MobBehavior, MobBrain, SpawnRules},
+    pipeline::WorldOrigin,
+    surface,
+    voxel::KindRegistry,
+    world::VoxWorld,
+};
+
+use crate::{
+    combat::{EntityHit, PlayerHealth},
+    drops,
+    game_state::GameState,
+    nameplates::Nameplate,
+    physics::Collider,
+    MainCamera,
+};
+
+/// How often, in seconds, mobs try to spawn.
+const SPAWN_INTERVAL: f32 = 0.5;
+/// How many spots are tried on each spawn, since most random spots are solid, in the air or too bright.
+const SPAWN_ATTEMPTS: usize = 4;
+
+const MOB_WIDTH: f32 = 0.6;
+const MOB_HEIGHT: f32 = 1.8;
+/// Mobs breathe through their eyes, which are a bit below the top of their body, like the player.
+const MOB_EYES: f32 = 1.6;
+const MOB_BODY: Body = Body {
+    width: MOB_WIDTH,
+    height: MOB_HEIGHT,
+    max_step: 1.0,
+};
+
+/**
+  How fast, in voxels per second squared, mobs walking into each other are pushed apart. It's faster than
+  they accelerate on the ground, so they can't walk through each other.
+*/
+const PUSH_ACCELERATION: f32 = 60.0;
+
+/// In voxels per second squared.
+const GRAVITY: f32 = 20.0;
+const MAX_FALL_SPEED: f32 = 30.0;
+
+/// A hostile mob, which spawns in the dark around the player.
+#[derive(Component, Default)]
+pub struct HostileMob {
+    /// In voxels per second.
+    velocity: Vec3,
+    air: AirMeter,
+}
+
+struct SpawnTimer(Timer);
+
+impl Default for SpawnTimer {
+    fn default() -> Self {
+        Self(Timer::from_seconds(SPAWN_INTERVAL, true))
+    }
+}
+
+/// Mesh and material of hostile mobs.
+struct MobModel {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for MobModel {
+    fn from_world(world: &mut World) -> Self {
+        let mut meshes = world
+            .get_resource_mut::<Assets<Mesh>>()
+            .expect("PbrPlugin must be added before MobsPlugin");
+        let mesh = meshes.add(Mesh::from(shape::Box::new(
+            MOB_WIDTH, MOB_HEIGHT, MOB_WIDTH,
+        )));
+
+        let mut materials = world
+            .get_resource_mut::<Assets<StandardMaterial>>()
+            .expect("PbrPlugin must be added before MobsPlugin");
+        let material = materials.add(Color::rgb(0.3, 0.6, 0.3).into());
+
+        Self { mesh, material }
+    }
+}
+
+pub struct MobsPlugin;
+
+impl Plugin for MobsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SpawnRules>()
+            .init_resource::<MobBehavior>()
+            .init_resource::<SpawnTimer>()
+            .init_resource::<MobModel>()
+            .add_system(spawn_mobs)
+            .add_system(hit_mobs)
+            .add_system(push_mobs)
+            .add_system(move_mobs.after(hit_mobs).after(push_mobs))
+            .add_system(despawn_mobs);
+    }
+}
+
+/// Mobs only spawn while playing, and never past the budget.
+#[allow(clippy::too_many_arguments)]
+fn spawn_mobs(
+    mut commands: Commands,
+    time: Res<Time>,
+    state: Res<State<GameState>>,
+    rules: Res<SpawnRules>,
+    world: Res<VoxWorld>,
+    registry: Res<KindRegistry>,
+    light: Res<LightWorld>,
+    origin: Res<WorldOrigin>,
+    behavior: Res<MobBehavior>,
+    model: Res<MobModel>,
+    mut timer: ResMut<SpawnTimer>,
+    camera: Query<&Transform, With<MainCamera>>,
+    mobs: Query<(), With<HostileMob>>,
+) {
+    if !timer.0.tick(time.delta()).just_finished() || *state.current() != GameState::InGame {
+        return;
+    }
+
+    let center = match camera.get_single() {
+        Ok(transform) => origin.to_voxel(transform.translation),
+        Err(_) => return,
+    };
+
+    let mut rng = rand::thread_rng();
+    let mut alive = mobs.iter().count();
+
+    for _ in 0..SPAWN_ATTEMPTS {
+        if alive >= rules.budget {
+            return;
+        }
+
+        let candidate = rules.candidate(center, &mut rng);
+        let spot = match mob::find_spot(&world, &registry, &light, candidate, rules.max_light) {
+            Some(spot) => spot,
+            None => continue,
+        };
+
+        // Mob meshes are centered, so they're raised to stand on the spot floor
+        let (local, voxel) = chunk::split_voxel(spot);
+        let feet = origin.voxel_bounds(local, voxel).min + Vec3::new(0.5, 0.0, 0.5);
+
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: model.mesh.clone(),
+                material: model.material.clone(),
+                transform: Transform::from_translation(feet + Vec3::Y * MOB_HEIGHT / 2.0),
+                ..Default::default()
+            })
+            .insert(HostileMob::default())
+            .insert(MobBrain::default())
+            .insert(Health::new(behavior.health))
+            .insert(Collider {
+                half_extents: Vec3::new(MOB_WIDTH, MOB_HEIGHT, MOB_WIDTH) / 2.0,
+            })
+            .insert(Nameplate {
+                name: None,
+                height: MOB_HEIGHT / 2.0,
+            });
+
+        alive += 1;
+    }
+}
+
+/// Mobs hit by the player are knocked back and fight back.
+fn hit_mobs(mut reader: EventReader<EntityHit>, mut q: Query<(&mut HostileMob, &mut MobBrain)>) {
+    for EntityHit { target, knockback } in reader.iter() {
+        if let Ok((mut mob, mut brain)) = q.get_mut(*target) {
+            mob.velocity += *knockback;
+            brain.provoke();
+        }
+    }
+}
+
+/// Mobs overlapping each other are pushed apart, so they don't pile up while chasing the player.
+fn push_mobs(
+    time: Res<Time>,
+    broadphase: Res<Broadphase>,
+    mut q: Query<(&Transform, &mut HostileMob)>,
+) {
+    for (a, b) in broadphase.pairs() {
+        let (a_center, b_center) = match (q.get(a), q.get(b)) {
+            (Ok((a, _)), Ok((b, _))) => (a.translation, b.translation),
+            _ => continue,
+        };
+
+        let push =
+            broadphase::push_apart(a_center, b_center) * PUSH_ACCELERATION * time.delta_seconds();
+
+        if let Ok((_, mut mob)) = q.get_mut(a) {
+            mob.velocity += push;
+        }
+        if let Ok((_, mut mob)) = q.get_mut(b) {
+            mob.velocity -= push;
+        }
+    }
+}
+
+/**
+  Runs the brain of each mob and moves it, walking towards the player when chasing it. Mobs fall, step up
+  ledges, walk on the ground and float on liquids like the player does. Mobs with their eyes inside a liquid
+  run out of air, and drown once it's gone. Nothing moves outside of games.
+*/
+#[allow(clippy::too_many_arguments)]
+fn move_mobs(
+    mut commands: Commands,
+    time: Res<Time>,
+    state: Res<State<GameState>>,
+    world: Res<VoxWorld>,
+    registry: Res<KindRegistry>,
+    origin: Res<WorldOrigin>,
+    behavior: Res<MobBehavior>,
+    mut health: ResMut<PlayerHealth>,
+    camera: Query<&Transform, (With<MainCamera>, Without<HostileMob>)>,
+    mut q: Query<(
+        Entity,
+        &mut Transform,
+        &mut HostileMob,
+        &mut MobBrain,
+        &mut Health,
+    )>,
+) {
+    let player = match camera.get_single() {
+        Ok(transform) if *state.current() == GameState::InGame => transform.translation,
+        _ => return,
+    };
+
+    let dt = time.delta_seconds();
+    let solid = |voxel: IVec3| drops::is_solid(&world, &registry, &origin, voxel.as_vec3());
+
+    for (entity, mut transform, mut mob, mut brain, mut mob_health) in q.iter_mut() {
+        let feet = transform.translation - Vec3::Y * MOB_HEIGHT / 2.0;
+        let towards = Vec3::new(player.x - feet.x, 0.0, player.z - feet.z).normalize_or_zero();
+
+        let wish = match brain.think(&behavior, transform.translation.distance(player), dt) {
+            MobAction::Wait => Vec3::ZERO,
+            MobAction::Walk => towards * behavior.speed,
+            MobAction::Attack => {
+                health.0.damage(behavior.damage);
+                Vec3::ZERO
+            }
+        };
+
+        let liquid = fluid::submersion(&world, &registry, origin.to_world(feet), MOB_HEIGHT);
+
+        let ground = match liquid {
+            Some(_) => Some(fluid::SWIM_SURFACE),
+            None => surface::ground(&world, &registry, origin.to_world(feet)),
+        };
+        if let Some(ground) = ground {
+            mob.velocity = surface::walk(mob.velocity, wish, ground, dt);
+        }
+        if let Some((depth, liquid)) = liquid {
+            mob.velocity = fluid::float(mob.velocity, depth, liquid, GRAVITY, dt);
+        }
+        mob.velocity.y = (mob.velocity.y - GRAVITY * dt).max(-MAX_FALL_SPEED);
+
+        let horizontal = Vec3::new(mob.velocity.x, 0.0, mob.velocity.z) * dt;
+        let moved = MOB_BODY.step_move(feet, horizontal, solid);
+
+        let (feet, blocked) = MOB_BODY.fall(moved, mob.velocity.y * dt, solid);
+        if blocked {
+            mob.velocity.y = 0.0;
+        }
+
+        transform.translation = feet + Vec3::Y * MOB_HEIGHT / 2.0;
+        transform.rotation = Quat::from_rotation_y(towards.x.atan2(towards.z));
+
+        let eyes = origin.to_voxel(feet + Vec3::Y * MOB_EYES);
+        let submerged =
+            matches!(world.get_voxel(eyes), Some(kind) if registry.liquid(kind).is_some());
+        let damage = mob.air.breathe(submerged, dt);
+
+        if damage > 0.0 && mob_health.damage(damage) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn despawn_mobs(
+    mut commands: Commands,
+    rules: Res<SpawnRules>,
+    camera: Query<&Transform, With<MainCamera>>,
+    mobs: Query<(Entity, &Transform), With<HostileMob>>,
+) {
+    let center = match camera.get_single() {
+        Ok(transform) => transform.translation,
+        Err(_) => return,
+    };
+
+    for (entity, transform) in mobs.iter() {
+        if rules.should_despawn(transform.translation, center) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use bevy::prelude::*;
+use std::path::Path;
+use vox::{
+    chunk,
+    modding::{BlockEvent, ModCall, ModEvent, Mods},
+    pipeline::{BlockBroken, BlockPlaced},
+    voxel::Kind,
+    world::VoxWorld,
+};
+
+use crate::{console::Console, net::EditRequest, selection::VoxelBroken};
+
+/// Where mods are loaded from, one file each.
+const MODS_PATH: &str = "mods";
+
+/// A console command to be handled by the mod which registered it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModCommand {
+    pub name: String,
+    pub args: Vec<String>,
+}
+
+pub struct ModsPlugin;
+
+impl Plugin for ModsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Mods>()
+            .add_event::<ModCommand>()
+            .add_startup_system(load_mods)
+            .add_system(run_mods);
+    }
+}
+
+/**
+  Parses the arguments of the `mod` command, which runs a command registered by a mod.
+*/
+pub fn parse(args: &[&str]) -> Result<ModCommand, String> {
+    match args {
+        [name, args @ ..] => Ok(ModCommand {
+            name: name.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+        }),
+        [] => Err("Usage: mod <command> [args]".to_string()),
+    }
+}
+
+fn apply_calls(
+    calls: Vec<(usize, ModCall)>,
+    world: &VoxWorld,
+    mods: &mut Mods,
+    console: &mut Console,
+    writer: &mut EventWriter<EditRequest>,
+) {
+    for (idx, call) in calls {
+        match call {
+            ModCall::GetVoxel(position) => {
+                let kind = world.get_voxel(position).map(|kind| kind.id());
+                mods.reply(idx, ModEvent::Voxel { position, kind });
+            }
+            ModCall::SetVoxel { position, kind } => {
+                let (chunk, voxel) = chunk::split_voxel(position);
+                writer.send(EditRequest {
+                    chunk,
+                    voxel,
+                    kind: kind.into(),
+                });
+            }
+            ModCall::Print(line) => console.print(line),
+            // Handled by the mods themselves
+            ModCall::Subscribe(_) | ModCall::RegisterCommand(_) => (),
+        }
+    }
+}
+
+fn load_mods(
+    world: Res<VoxWorld>,
+    mut mods: ResMut<Mods>,
+    mut console: ResMut<Console>,
+    mut writer: EventWriter<EditRequest>,
+) {
+    let calls = mods.load_dir(Path::new(MODS_PATH));
+    apply_calls(calls, &world, &mut mods, &mut console, &mut writer);
+}
+
+/**
+  Feeds voxel events and console commands to mods, and applies what they ask for.
+*/
+#[allow(clippy::too_many_arguments)]
+fn run_mods(
+    world: Res<VoxWorld>,
+    mut mods: ResMut<Mods>,
+    mut console: ResMut<Console>,
+    mut writer: EventWriter<EditRequest>,
+    mut command_reader: EventReader<ModCommand>,
+    mut placed_reader: EventReader<BlockPlaced>,
+    mut removed_reader: EventReader<BlockBroken>,
+    mut broken_reader: EventReader<VoxelBroken>,
+) {
+    let mut calls = mods.flush();
+
+    let placed = placed_reader
+        .iter()
+        .map(|evt| (evt.chunk, evt.voxel, evt.kind));
+    let removed = removed_reader
+        .iter()
+        .map(|evt| (evt.chunk, evt.voxel, Default::default()));
+
+    for (chunk, voxel, kind) in placed.chain(removed).collect::<Vec<(_, _, Kind)>>() {
+        calls.extend(mods.dispatch(
+            BlockEvent::Changed,
+            ModEvent::Changed {
+                position: chunk::join_voxel(chunk, voxel),
+                kind: kind.id(),
+            },
+        ));
+    }
+
+    for VoxelBroken { chunk, voxel, kind } in broken_reader.iter() {
+        calls.extend(mods.dispatch(
+            BlockEvent::Broken,
+            ModEvent::Broken {
+                position: chunk::join_voxel(*chunk, *voxel),
+                kind: kind.id(),
+            },
+        ));
+    }
+
+    for command in command_reader.iter() {
+        match mods.run_command(&command.name, &command.args) {
+            Ok(command_calls) => calls.extend(command_calls),
+            Err(err) => console.print(err),
+        }
+    }
+
+    apply_calls(calls, &world, &mut mods, &mut console, &mut writer);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            super::parse(&["echo", "a", "b"]),
+            Ok(ModCommand {
+                name: "echo".to_string(),
+                args: vec!["a".to_string(), "b".to_string()],
+            })
+        );
+        assert!(super::parse(&[]).is_err());
+    }
+}
@@ -0,0 +1,290 @@
+use bevy::{audio::AudioSink, prelude::*};
+use serde::Deserialize;
+use std::path::Path;
+use vox::{biome::Biome, pipeline::WorldOrigin, world::VoxWorld};
+
+use crate::{
+    ambience, explosions::LitFuse, game_state::GameState, underwater::AudioMuffle, MainCamera,
+};
+
+/// How close, in voxels, lit fuses must be to the camera to be a danger.
+const DANGER_RADIUS: f32 = 16.0;
+
+/**
+  Time of day the music plays on. It's set by whatever drives the day cycle, and there's none yet, so it's
+  always day.
+*/
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum TimeOfDay {
+    #[default]
+    Day,
+    Night,
+}
+
+/// What the music reacts to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MusicContext {
+    /// Biome of the column the camera is on.
+    pub biome: Biome,
+    pub time: TimeOfDay,
+    /// Whether something dangerous, like a lit fuse, is around the camera.
+    pub danger: bool,
+}
+
+/// When a track may play. Conditions left out match anything.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct MusicRule {
+    pub biome: Option<Biome>,
+    pub time: Option<TimeOfDay>,
+    pub danger: Option<bool>,
+}
+
+impl MusicRule {
+    pub fn matches(&self, context: &MusicContext) -> bool {
+        self.biome.is_none_or(|biome| biome == context.biome)
+            && self.time.is_none_or(|time| time == context.time)
+            && self.danger.is_none_or(|danger| danger == context.danger)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct MusicTrack {
+    pub name: String,
+    /// Asset path of the track, which is looped while it plays.
+    pub path: String,
+    #[serde(default)]
+    pub when: MusicRule,
+}
+
+/**
+  Music tracks and the rules of when they play, read from a RON file. The first track whose rule matches the
+  current [`MusicContext`] plays, so more specific tracks, like danger ones, must come first.
+*/
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MusicConfig {
+    pub volume: f32,
+    /// Seconds it takes to fully crossfade from a track to another.
+    pub fade_time: f32,
+    pub tracks: Vec<MusicTrack>,
+}
+
+impl MusicConfig {
+    pub fn load(path: &Path) -> Self {
+        let file = std::fs::File::open(path)
+            .unwrap_or_else(|_| panic!("Failed opening music config file at {}", path.display()));
+
+        let config: MusicConfig = ron::de::from_reader(file)
+            .unwrap_or_else(|_| panic!("Failed to parse music config {}", path.display()));
+
+        assert!(
+            config.fade_time > 0.0,
+            "Music must have a positive fade time"
+        );
+
+        config
+    }
+
+    /// Index of the track to play on the given context, if any.
+    pub fn select(&self, context: &MusicContext) -> Option<usize> {
+        self.tracks
+            .iter()
+            .position(|track| track.when.matches(context))
+    }
+}
+
+struct PlayingTrack {
+    track: usize,
+    sink: Handle<AudioSink>,
+    volume: f32,
+}
+
+/**
+  Tracks being played. Only the current one fades in, while the others fade out and are stopped once silent.
+  Tracks which come back while still fading out fade in again, instead of starting over.
+*/
+#[derive(Default)]
+struct MusicPlayer {
+    current: Option<usize>,
+    playing: Vec<PlayingTrack>,
+}
+
+pub struct MusicPlugin;
+
+impl Plugin for MusicPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TimeOfDay>()
+            .init_resource::<MusicPlayer>()
+            .add_system(select_track)
+            .add_system(fade_tracks.after(select_track));
+    }
+}
+
+/// Picks the track for the camera surroundings. There's no music outside of games.
+#[allow(clippy::too_many_arguments)]
+fn select_track(
+    state: Res<State<GameState>>,
+    config: Res<MusicConfig>,
+    time: Res<TimeOfDay>,
+    origin: Res<WorldOrigin>,
+    world: Res<VoxWorld>,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    audio_sinks: Res<Assets<AudioSink>>,
+    mut player: ResMut<MusicPlayer>,
+    q_camera: Query<&Transform, With<MainCamera>>,
+    q_fuses: Query<&Transform, With<LitFuse>>,
+) {
+    let selected = match q_camera.get_single() {
+        Ok(transform) if matches!(state.current(), GameState::InGame | GameState::Paused) => {
+            let camera = transform.translation;
+            let voxel = origin.to_voxel(camera);
+
+            let context = MusicContext {
+                biome: world.biome(voxel),
+                time: *time,
+                danger: q_fuses
+                    .iter()
+                    .any(|fuse| fuse.translation.distance(camera) <= DANGER_RADIUS),
+            };
+
+            config.select(&context)
+        }
+        _ => None,
+    };
+
+    if selected == player.current {
+        return;
+    }
+
+    player.current = selected;
+
+    if let Some(track) = selected {
+        debug!("Music changed to {}", config.tracks[track].name);
+
+        if !player.playing.iter().any(|playing| playing.track == track) {
+            let source = asset_server.load(config.tracks[track].path.as_str());
+            let sink = audio.play_with_settings(source, PlaybackSettings::LOOP.with_volume(0.0));
+
+            player.playing.push(PlayingTrack {
+                track,
+                sink: audio_sinks.get_handle(sink),
+                volume: 0.0,
+            });
+        }
+    }
+}
+
+fn fade_tracks(
+    time: Res<Time>,
+    config: Res<MusicConfig>,
+    muffle: Res<AudioMuffle>,
+    audio_sinks: Res<Assets<AudioSink>>,
+    mut player: ResMut<MusicPlayer>,
+) {
+    let current = player.current;
+    let step = config.volume * time.delta_seconds() / config.fade_time;
+
+    player.playing.retain_mut(|playing| {
+        let target = if current == Some(playing.track) {
+            config.volume
+        } else {
+            0.0
+        };
+
+        playing.volume = ambience::crossfade(playing.volume, target, step);
+
+        // Sinks only exist once their track is loaded and playing
+        let sink = audio_sinks.get(playing.sink.clone());
+        if let Some(sink) = sink {
+            sink.set_volume(playing.volume * muffle.0);
+        }
+
+        if playing.volume > 0.0 || current == Some(playing.track) {
+            return true;
+        }
+
+        if let Some(sink) = sink {
+            sink.stop();
+        }
+
+        false
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> MusicConfig {
+        ron::from_str(
+            r#"(
+                volume: 0.4,
+                fade_time: 4.0,
+                tracks: [
+                    (name: "Danger", path: "danger.ogg", when: (danger: Some(true))),
+                    (name: "Snowy night", path: "snowy_night.ogg", when: (biome: Some(Snowy), time: Some(Night))),
+                    (name: "Desert", path: "desert.ogg", when: (biome: Some(Desert))),
+                    (name: "Anywhere", path: "anywhere.ogg"),
+                ],
+            )"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn select() {
+        let config = config();
+        let context = MusicContext {
+            biome: Biome::Plains,
+            time: TimeOfDay::Day,
+            danger: false,
+        };
+
+        assert_eq!(config.select(&context), Some(3));
+        assert_eq!(
+            config.select(&MusicContext {
+                biome: Biome::Desert,
+                ..context
+            }),
+            Some(2)
+        );
+
+        // All conditions must match
+        let snowy = MusicContext {
+            biome: Biome::Snowy,
+            ..context
+        };
+        assert_eq!(config.select(&snowy), Some(3));
+        assert_eq!(
+            config.select(&MusicContext {
+                time: TimeOfDay::Night,
+                ..snowy
+            }),
+            Some(1)
+        );
+
+        // Earlier tracks win
+        assert_eq!(
+            config.select(&MusicContext {
+                danger: true,
+                ..snowy
+            }),
+            Some(0)
+        );
+
+        let config = MusicConfig {
+            tracks: vec![],
+            ..config
+        };
+        assert_eq!(config.select(&context), None);
+    }
+
+    #[test]
+    fn load() {
+        let path = format!("{}assets/music/music.ron", env!("CARGO_WORKSPACE_DIR"));
+        let config = MusicConfig::load(Path::new(&path));
+
+        assert!(!config.tracks.is_empty());
+    }
+}
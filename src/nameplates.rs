@@ -0,0 +1,271 @@
+use bevy::{prelude::*, ui::UiSystem, utils::HashMap};
+use vox::{combat::Health, pipeline::WorldOrigin, query, voxel::KindRegistry, world::VoxWorld};
+
+use crate::{selection, MainCamera, FONT_PATH};
+
+const FONT_SIZE: f32 = 16.0;
+
+/// Size, in pixels, of health bars seen up close.
+const BAR_WIDTH: f32 = 60.0;
+const BAR_HEIGHT: f32 = 6.0;
+
+/// Plates closer than this, in voxels, are drawn at their full size.
+const FULL_SIZE_DISTANCE: f32 = 4.0;
+/// Smallest size plates shrink to, relative to their full size.
+const MIN_SCALE: f32 = 0.4;
+/// Plates further than this, in voxels, aren't drawn at all.
+const MAX_DISTANCE: f32 = 48.0;
+
+/// How far, in voxels, above the top of the entity plates float.
+const PLATE_OFFSET: f32 = 0.3;
+
+/**
+  Floats a plate above the entity, with its name, if any, and a health bar, once it has [`Health`] and was
+  hurt. Plates are drawn over the screen where the entity is, so they always face the camera.
+*/
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct Nameplate {
+    pub name: Option<String>,
+    /// How far, in voxels, from the entity translation its top is.
+    pub height: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NameplateSettings {
+    /// When set, plates behind terrain are hidden, like the entities they float above.
+    pub depth_test: bool,
+}
+
+impl Default for NameplateSettings {
+    fn default() -> Self {
+        Self { depth_test: true }
+    }
+}
+
+/// UI nodes drawing the plate of an entity.
+struct PlateNodes {
+    root: Entity,
+    text: Entity,
+    bar: Entity,
+    fill: Entity,
+}
+
+/// Plate nodes of each entity with a [`Nameplate`].
+#[derive(Default)]
+struct Plates(HashMap<Entity, PlateNodes>);
+
+pub struct NameplatesPlugin;
+
+impl Plugin for NameplatesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NameplateSettings>()
+            .init_resource::<Plates>()
+            .add_system(spawn_plates)
+            .add_system(despawn_plates)
+            // Plates follow entities after they moved on update, and before the UI is laid out
+            .add_system_to_stage(CoreStage::PostUpdate, place_plates.before(UiSystem::Flex));
+    }
+}
+
+/// Size of plates at the given distance from the camera, relative to their full size.
+pub fn scale(distance: f32) -> f32 {
+    (FULL_SIZE_DISTANCE / distance.max(f32::EPSILON)).clamp(MIN_SCALE, 1.0)
+}
+
+/// Whether an opaque voxel is in between the camera `eyes` and the render space `position`.
+fn is_occluded(
+    world: &VoxWorld,
+    registry: &KindRegistry,
+    origin: &WorldOrigin,
+    eyes: Vec3,
+    position: Vec3,
+) -> bool {
+    let offset = position - eyes;
+    let raycast = query::raycast(eyes, offset.normalize_or_zero(), offset.length());
+
+    selection::first_hit(world, origin, raycast, |kind| registry.is_opaque(kind)).is_some()
+}
+
+fn spawn_plates(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut plates: ResMut<Plates>,
+    q: Query<(Entity, &Nameplate), Added<Nameplate>>,
+) {
+    for (entity, nameplate) in q.iter() {
+        let text = commands
+            .spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    nameplate.name.clone().unwrap_or_default(),
+                    TextStyle {
+                        font: asset_server.load(FONT_PATH),
+                        font_size: FONT_SIZE,
+                        color: Color::WHITE,
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            })
+            .id();
+
+        let fill = commands
+            .spawn_bundle(NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                    ..Default::default()
+                },
+                color: Color::rgb(0.8, 0.1, 0.1).into(),
+                ..Default::default()
+            })
+            .id();
+
+        let bar = commands
+            .spawn_bundle(NodeBundle {
+                style: Style {
+                    display: Display::None,
+                    size: Size::new(Val::Px(BAR_WIDTH), Val::Px(BAR_HEIGHT)),
+                    ..Default::default()
+                },
+                color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+                ..Default::default()
+            })
+            .push_children(&[fill])
+            .id();
+
+        let root = commands
+            .spawn_bundle(NodeBundle {
+                style: Style {
+                    display: Display::None,
+                    position_type: PositionType::Absolute,
+                    flex_direction: FlexDirection::ColumnReverse,
+                    align_items: AlignItems::Center,
+                    ..Default::default()
+                },
+                color: Color::NONE.into(),
+                ..Default::default()
+            })
+            .push_children(&[text, bar])
+            .id();
+
+        plates.0.insert(
+            entity,
+            PlateNodes {
+                root,
+                text,
+                bar,
+                fill,
+            },
+        );
+    }
+}
+
+/// Plates of entities which are gone, or lost their [`Nameplate`], are gone too.
+fn despawn_plates(mut commands: Commands, mut plates: ResMut<Plates>, q: Query<&Nameplate>) {
+    plates.0.retain(|entity, nodes| {
+        let alive = q.get(*entity).is_ok();
+        if !alive {
+            commands.entity(nodes.root).despawn_recursive();
+        }
+        alive
+    });
+}
+
+/**
+  Moves each plate over its entity on the screen, sized by how far it is. Plates of entities behind the
+  camera, too far away or, when depth tested, behind terrain are hidden.
+*/
+#[allow(clippy::too_many_arguments)]
+fn place_plates(
+    windows: Res<Windows>,
+    images: Res<Assets<Image>>,
+    world: Res<VoxWorld>,
+    registry: Res<KindRegistry>,
+    origin: Res<WorldOrigin>,
+    settings: Res<NameplateSettings>,
+    plates: Res<Plates>,
+    camera: Query<(&Camera, &Transform), With<MainCamera>>,
+    targets: Query<(&Transform, &Nameplate, Option<&Health>)>,
+    mut styles: Query<&mut Style>,
+    mut texts: Query<&mut Text>,
+) {
+    let (camera, camera_transform) = match camera.get_single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    let eyes = camera_transform.translation;
+
+    for (entity, nodes) in plates.0.iter() {
+        let (transform, nameplate, health) = match targets.get(*entity) {
+            Ok(target) => target,
+            Err(_) => continue,
+        };
+
+        let position = transform.translation + Vec3::Y * (nameplate.height + PLATE_OFFSET);
+        let distance = position.distance(eyes);
+        let health = health.filter(|health| health.is_damaged());
+
+        let screen = camera
+            .world_to_screen(
+                &windows,
+                &images,
+                &GlobalTransform::from(*camera_transform),
+                position,
+            )
+            .filter(|_| distance <= MAX_DISTANCE)
+            .filter(|_| nameplate.name.is_some() || health.is_some())
+            .filter(|_| {
+                !settings.depth_test || !is_occluded(&world, &registry, &origin, eyes, position)
+            });
+
+        let scale = self::scale(distance);
+
+        if let Ok(mut style) = styles.get_mut(nodes.root) {
+            match screen {
+                Some(screen) => {
+                    style.display = Display::Flex;
+                    // Plates grow up from the point above the entity, centered on it
+                    style.position = Rect {
+                        left: Val::Px(screen.x - BAR_WIDTH * scale / 2.0),
+                        bottom: Val::Px(screen.y),
+                        ..Default::default()
+                    };
+                    style.size.width = Val::Px(BAR_WIDTH * scale);
+                }
+                None => {
+                    style.display = Display::None;
+                    continue;
+                }
+            }
+        }
+
+        if let Ok(mut text) = texts.get_mut(nodes.text) {
+            text.sections[0].style.font_size = FONT_SIZE * scale;
+        }
+
+        if let Ok(mut style) = styles.get_mut(nodes.bar) {
+            style.display = if health.is_some() {
+                Display::Flex
+            } else {
+                Display::None
+            };
+            style.size = Size::new(Val::Px(BAR_WIDTH * scale), Val::Px(BAR_HEIGHT * scale));
+        }
+
+        if let (Some(health), Ok(mut style)) = (health, styles.get_mut(nodes.fill)) {
+            style.size.width = Val::Percent(health.fraction() * 100.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scale() {
+        assert_eq!(super::scale(0.0), 1.0);
+        assert_eq!(super::scale(FULL_SIZE_DISTANCE), 1.0);
+        assert_eq!(super::scale(FULL_SIZE_DISTANCE * 2.0), 0.5);
+        assert_eq!(super::scale(MAX_DISTANCE * 10.0), MIN_SCALE);
+    }
+}
@@ -0,0 +1,126 @@
+use bevy::prelude::*;
+use std::{
+    net::{SocketAddr, TcpStream},
+    time::Duration,
+};
+use vox::meta::WorldMeta;
+
+use super::{
+    connection::Connection,
+    handshake::{Login, WorldParams, DEFAULT_NAME, PROTOCOL_VERSION},
+    protocol::{ClientMessage, ServerMessage},
+};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/**
+  Address of the server to connect to. The game only connects to a server when this resource is inserted.
+*/
+pub struct ClientConfig {
+    pub addr: SocketAddr,
+    pub name: String,
+}
+
+impl ClientConfig {
+    /**
+      Reads the address given by the `--connect <addr>` command line argument, if any, and the player name
+      given by `--name <name>`.
+    */
+    pub fn from_args(args: &[String]) -> Option<Self> {
+        let name = args
+            .iter()
+            .position(|arg| arg == "--name")
+            .and_then(|idx| args.get(idx + 1))
+            .map_or(DEFAULT_NAME, String::as_str);
+
+        crate::addr_arg(args, "--connect").map(|addr| Self {
+            addr,
+            name: name.to_string(),
+        })
+    }
+}
+
+/**
+  Connection to the server hosting the world.
+*/
+pub struct NetClient {
+    connection: Connection,
+}
+
+impl NetClient {
+    /// Queues a message to the server.
+    pub fn send(&mut self, message: &ClientMessage) {
+        self.connection.send(message);
+    }
+}
+
+/// A message received from the server.
+pub struct FromServer(pub ServerMessage);
+
+/// Sent when the connection to the server is closed or broken.
+pub struct ServerDisconnected;
+
+pub(super) fn setup_client(
+    mut commands: Commands,
+    meta: Res<WorldMeta>,
+    config: Option<Res<ClientConfig>>,
+) {
+    let config = match config {
+        Some(config) => config,
+        None => return,
+    };
+
+    let mut connection = TcpStream::connect_timeout(&config.addr, CONNECT_TIMEOUT)
+        .and_then(Connection::new)
+        .unwrap_or_else(|_| panic!("Unable to connect to server {}", config.addr));
+
+    info!("Connected to server {}", config.addr);
+
+    connection.send(&ClientMessage::Login(Login {
+        version: PROTOCOL_VERSION,
+        name: config.name.clone(),
+        world: WorldParams::of(&meta),
+    }));
+
+    commands.insert_resource(NetClient { connection });
+}
+
+pub(super) fn receive_from_server(
+    mut commands: Commands,
+    client: Option<ResMut<NetClient>>,
+    mut writer: EventWriter<FromServer>,
+    mut disconnected_writer: EventWriter<ServerDisconnected>,
+) {
+    let mut client = match client {
+        Some(client) => client,
+        None => return,
+    };
+
+    match client.connection.receive() {
+        Ok(messages) => {
+            for message in &messages {
+                match message {
+                    ServerMessage::Welcome(id) => info!("Logged in as client {}", id),
+                    ServerMessage::LoginRejected(reason) => {
+                        error!("Server rejected login: {}", reason)
+                    }
+                    _ => (),
+                }
+            }
+
+            writer.send_batch(messages.into_iter().map(FromServer));
+        }
+        Err(err) => {
+            warn!("Disconnected from server: {}", err);
+            commands.remove_resource::<NetClient>();
+            disconnected_writer.send(ServerDisconnected);
+        }
+    }
+}
+
+pub(super) fn flush_server(client: Option<ResMut<NetClient>>) {
+    if let Some(mut client) = client {
+        // A broken connection is noticed, and handled, when receiving
+        let _ = client.connection.flush();
+    }
+}
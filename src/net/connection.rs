@@ -0,0 +1,118 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    net::{SocketAddr, TcpStream},
+};
+
+use super::protocol::{self, Decoder};
+
+/**
+  A non-blocking stream of messages. Outgoing messages are buffered and written as the socket accepts them,
+  so sending never stalls a frame.
+*/
+pub struct Connection {
+    stream: TcpStream,
+    addr: SocketAddr,
+    decoder: Decoder,
+    outgoing: Vec<u8>,
+}
+
+impl Connection {
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+
+        Ok(Self {
+            addr: stream.peer_addr()?,
+            stream,
+            decoder: Decoder::default(),
+            outgoing: vec![],
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Queues the message to be sent on the next flush.
+    pub fn send<T: Serialize>(&mut self, message: &T) {
+        self.outgoing.extend(protocol::encode(message));
+    }
+
+    /**
+      Writes as much of the queued messages as the socket accepts.
+    */
+    pub fn flush(&mut self) -> io::Result<()> {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(written) => {
+                    self.outgoing.drain(..written);
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(())
+    }
+
+    /**
+      Returns all messages which have fully arrived. Fails when the connection was closed or is broken.
+    */
+    pub fn receive<T: DeserializeOwned>(&mut self) -> io::Result<Vec<T>> {
+        let mut buffer = [0; 4096];
+
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => return Err(ErrorKind::ConnectionAborted.into()),
+                Ok(read) => self.decoder.push(&buffer[..read]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) => return Err(err),
+            }
+        }
+
+        let mut messages = vec![];
+
+        while let Some(message) = self
+            .decoder
+            .next()
+            .map_err(|err| io::Error::new(ErrorKind::InvalidData, err))?
+        {
+            messages.push(message);
+        }
+
+        Ok(messages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::protocol::ClientMessage;
+    use std::net::TcpListener;
+
+    #[test]
+    fn send_receive() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut client = Connection::new(TcpStream::connect(addr).unwrap()).unwrap();
+        let mut server = Connection::new(listener.accept().unwrap().0).unwrap();
+
+        let message = ClientMessage::Chat("hello".to_string());
+        client.send(&message);
+        client.send(&message);
+        client.flush().unwrap();
+
+        let mut received = vec![];
+        while received.len() < 2 {
+            received.extend(server.receive::<ClientMessage>().unwrap());
+        }
+
+        assert_eq!(received, vec![message.clone(), message]);
+
+        drop(client);
+        while server.receive::<ClientMessage>().is_ok() {}
+    }
+}
@@ -0,0 +1,584 @@
+use bevy::prelude::*;
+use std::collections::HashMap;
+use vox::{
+    bounds, chunk,
+    claim::Claims,
+    meta::WorldMeta,
+    pipeline::{SetVoxel, WorldOrigin},
+    voxel::{Kind, KindRegistry},
+    world::VoxWorld,
+};
+
+use super::{
+    client::{FromServer, NetClient},
+    interest::Subscriptions,
+    protocol::{ClientMessage, Rejection, ServerMessage},
+    server::{ClientDisconnected, ClientId, FromClient, NetServer},
+};
+use crate::{spectator::Spectator, MainCamera};
+
+/// Max distance, in voxels, between a player and a voxel it changes. It's a bit more than the client reach,
+/// since the server only knows where the player was a moment ago.
+const MAX_REACH: f32 = 10.0;
+
+/// How many voxel changes per second a player can sustain.
+const EDITS_PER_SECOND: f32 = 10.0;
+/// How many voxel changes a player can do at once, after not changing anything for a while.
+const EDITS_BURST: f32 = 20.0;
+
+/// How far, in voxels, the player must move before its position is sent to the server again.
+const POSITION_THRESHOLD: f32 = 0.25;
+
+/// Fastest a player can move, in voxels per second, with room to spare over falling at full speed.
+const MAX_SPEED: f32 = 60.0;
+/// How much farther than [`MAX_SPEED`] allows, in voxels, positions may be, since they arrive in bursts.
+const MOVE_SLACK: f32 = 4.0;
+/// How long, in seconds, a player must stay around a position it jumped to, before the server trusts it.
+const TELEPORT_DELAY: f64 = 2.0;
+
+/**
+  Send this when the player changes a voxel. Offline, or when hosting, it's applied right away. When connected
+  to a server it's also applied right away, but the server may reject it and roll it back later.
+*/
+pub struct EditRequest {
+    pub chunk: IVec3,
+    pub voxel: IVec3,
+    pub kind: Kind,
+}
+
+/**
+  Token bucket which allows short bursts of changes, while limiting how many changes are done over time.
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimiter {
+    tokens: f32,
+    last: f64,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self {
+            tokens: EDITS_BURST,
+            last: 0.0,
+        }
+    }
+}
+
+impl RateLimiter {
+    /// Consumes a token, if any is available at the given time, in seconds.
+    pub fn allow(&mut self, now: f64) -> bool {
+        let elapsed = (now - self.last).max(0.0) as f32;
+        self.tokens = (self.tokens + elapsed * EDITS_PER_SECOND).min(EDITS_BURST);
+        self.last = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Checks if a player at the given position and time, in seconds, could be at `to` at `now`.
+fn reachable((from, since): (Vec3, f64), to: Vec3, now: f64) -> bool {
+    let elapsed = (now - since).max(0.0) as f32;
+    from.distance(to) <= elapsed * MAX_SPEED + MOVE_SLACK
+}
+
+#[derive(Default)]
+struct PlayerState {
+    /// Last trusted position of the player eyes, and when it was sent.
+    position: Option<(Vec3, f64)>,
+    /// Position the player jumped to, which isn't trusted yet, and since when it's around there.
+    jumped: Option<(Vec3, f64)>,
+    limiter: RateLimiter,
+}
+
+impl PlayerState {
+    fn moved(&mut self, position: Vec3, now: f64) -> bool {
+        if !position.is_finite() {
+            return false;
+        }
+
+        self.settle(now);
+
+        match self.position {
+            Some(last) if reachable(last, position, now) => {
+                self.position = Some((position, now));
+                self.jumped = None;
+                true
+            }
+            _ => {
+                self.jumped = match self.jumped {
+                    Some((jumped, since)) if reachable((jumped, now), position, now) => {
+                        Some((position, since))
+                    }
+                    _ => Some((position, now)),
+                };
+                false
+            }
+        }
+    }
+
+    /// Trusts the position the player jumped to, once it stayed around there long enough.
+    fn settle(&mut self, now: f64) {
+        if let Some((position, since)) = self.jumped {
+            if now - since >= TELEPORT_DELAY {
+                self.position = Some((position, now));
+                self.jumped = None;
+            }
+        }
+    }
+}
+
+/**
+  Checks voxel changes requested by clients, before the server applies them.
+*/
+#[derive(Default)]
+pub struct EditValidator {
+    players: HashMap<ClientId, PlayerState>,
+}
+
+impl EditValidator {
+    /**
+      Updates the world position of the given player eyes, at the given time in seconds. Positions farther than
+      the player could have moved since the last one, like the first one or after a teleport, are only trusted
+      once the player stays around there for [`TELEPORT_DELAY`]. Returns if the position is trusted right away.
+    */
+    pub fn moved(&mut self, client: ClientId, position: Vec3, now: f64) -> bool {
+        self.players.entry(client).or_default().moved(position, now)
+    }
+
+    pub fn remove(&mut self, client: ClientId) {
+        self.players.remove(&client);
+    }
+
+    /**
+      Checks if the given client, logged in with the given name, can change the given world voxel to the given
+      kind at the given time, in seconds. Players which never sent their position can't change anything, and
+      voxels of claimed regions can only be changed by their owners.
+    */
+    #[allow(clippy::too_many_arguments)]
+    pub fn validate(
+        &mut self,
+        client: ClientId,
+        name: &str,
+        position: IVec3,
+        kind: Kind,
+        now: f64,
+        claims: &Claims,
+        registry: &KindRegistry,
+    ) -> Result<(), Rejection> {
+        // Kinds are sent to other clients as they are, so anything but a known kind facing a known side is refused
+        if registry.get(kind).is_none() || kind.with_facing(kind.facing()) != kind {
+            return Err(Rejection::UnknownKind);
+        }
+
+        let player = self.players.entry(client).or_default();
+        player.settle(now);
+
+        let center = bounds::voxel(position).center();
+        match player.position {
+            Some((eyes, _)) if eyes.distance(center) <= MAX_REACH => (),
+            _ => return Err(Rejection::OutOfReach),
+        }
+
+        if !claims.can_edit(name, position) {
+            return Err(Rejection::Protected);
+        }
+
+        if !player.limiter.allow(now) {
+            return Err(Rejection::RateLimited);
+        }
+
+        Ok(())
+    }
+}
+
+/**
+  World position of a voxel sent by a client, as long as the voxel is inside its chunk and the chunk is loaded.
+  Both are checked before joining them, since clients may send any coordinates, even ones which overflow.
+*/
+fn edit_position(world: &VoxWorld, chunk: IVec3, voxel: IVec3) -> Option<IVec3> {
+    if chunk::is_within_bounds(voxel) && world.exists(chunk) {
+        Some(chunk::join_voxel(chunk, voxel))
+    } else {
+        None
+    }
+}
+
+/**
+  Applies edits of the local player. When connected to a server, edits are also sent to it.
+*/
+pub(super) fn request_edits(
+    mut sequence: Local<u32>,
+    mut client: Option<ResMut<NetClient>>,
+    mut reader: EventReader<EditRequest>,
+    mut writer: EventWriter<SetVoxel>,
+) {
+    for EditRequest { chunk, voxel, kind } in reader.iter() {
+        if let Some(client) = client.as_mut() {
+            *sequence = sequence.wrapping_add(1);
+
+            client.send(&ClientMessage::SetVoxel {
+                sequence: *sequence,
+                chunk: *chunk,
+                voxel: *voxel,
+                kind: *kind,
+            });
+        }
+
+        writer.send(SetVoxel {
+            chunk: *chunk,
+            voxel: *voxel,
+            kind: *kind,
+        });
+    }
+}
+
+/**
+  Validates edits requested by clients. Accepted edits are applied, while rejected ones are sent back to
+  the client with the voxel kind the server has, so it can roll back.
+*/
+#[allow(clippy::too_many_arguments)]
+pub(super) fn validate_edits(
+    time: Res<Time>,
+    world: Res<VoxWorld>,
+    meta: Res<WorldMeta>,
+    registry: Res<KindRegistry>,
+    server: Option<ResMut<NetServer>>,
+    mut validator: ResMut<EditValidator>,
+    mut reader: EventReader<FromClient>,
+    mut writer: EventWriter<SetVoxel>,
+) {
+    let mut server = match server {
+        Some(server) => server,
+        None => return,
+    };
+
+    for FromClient { client, message } in reader.iter() {
+        match *message {
+            ClientMessage::Position(position)
+                if !validator.moved(*client, position, time.seconds_since_startup()) =>
+            {
+                debug!("Client {} jumped to {}", client, position);
+            }
+            ClientMessage::SetVoxel {
+                sequence,
+                chunk,
+                voxel,
+                kind,
+            } => {
+                // Voxels out of chunk bounds are never valid, so they're treated as unknown
+                let position = edit_position(&world, chunk, voxel);
+                let current = position.and_then(|position| world.get_voxel(position));
+
+                let result = match (position, current) {
+                    (Some(position), Some(_)) => validator.validate(
+                        *client,
+                        &server.player_name(Some(*client)),
+                        position,
+                        kind,
+                        time.seconds_since_startup(),
+                        &meta.claims,
+                        &registry,
+                    ),
+                    _ => Err(Rejection::Unloaded),
+                };
+
+                match result {
+                    Ok(()) => writer.send(SetVoxel { chunk, voxel, kind }),
+                    Err(reason) => {
+                        debug!(
+                            "Rejected edit of client {} at {} {}: {:?}",
+                            client, chunk, voxel, reason
+                        );
+
+                        server.send(
+                            *client,
+                            &ServerMessage::EditRejected {
+                                sequence,
+                                chunk,
+                                voxel,
+                                kind: current,
+                                reason,
+                            },
+                        );
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Every voxel change done on the server is sent to the clients subscribed to its chunk, whatever caused it.
+pub(super) fn broadcast_voxels(
+    server: Option<ResMut<NetServer>>,
+    subscriptions: Res<Subscriptions>,
+    mut reader: EventReader<SetVoxel>,
+) {
+    if let Some(mut server) = server {
+        for SetVoxel { chunk, voxel, kind } in reader.iter() {
+            let message = ServerMessage::VoxelChanged {
+                chunk: *chunk,
+                voxel: *voxel,
+                kind: *kind,
+            };
+
+            for client in subscriptions.subscribers(*chunk) {
+                server.send(client, &message);
+            }
+        }
+    }
+}
+
+pub(super) fn forget_clients(
+    mut validator: ResMut<EditValidator>,
+    mut reader: EventReader<ClientDisconnected>,
+) {
+    for ClientDisconnected(client) in reader.iter() {
+        validator.remove(*client);
+    }
+}
+
+/**
+  Applies voxel changes from the server, including rollbacks of rejected local edits.
+*/
+pub(super) fn apply_server_voxels(
+    mut reader: EventReader<FromServer>,
+    mut writer: EventWriter<SetVoxel>,
+) {
+    for FromServer(message) in reader.iter() {
+        match *message {
+            ServerMessage::VoxelChanged { chunk, voxel, kind } => {
+                writer.send(SetVoxel { chunk, voxel, kind })
+            }
+            ServerMessage::EditRejected {
+                sequence,
+                chunk,
+                voxel,
+                kind,
+                reason,
+            } => {
+                debug!("Edit {} rejected by server: {:?}", sequence, reason);
+
+                if let Some(kind) = kind {
+                    writer.send(SetVoxel { chunk, voxel, kind });
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Sends where the player is. While spectating, the player stays where its body was left.
+pub(super) fn send_position(
+    mut last: Local<Option<Vec3>>,
+    origin: Res<WorldOrigin>,
+    spectator: Res<Spectator>,
+    client: Option<ResMut<NetClient>>,
+    q: Query<&Transform, With<MainCamera>>,
+) {
+    let (mut client, transform) = match (client, q.get_single()) {
+        (Some(client), Ok(transform)) if !spectator.is_active() => (client, transform),
+        _ => return,
+    };
+
+    let position = origin.to_world(transform.translation);
+
+    let moved = match *last {
+        Some(last) => last.distance(position) > POSITION_THRESHOLD,
+        None => true,
+    };
+
+    if moved {
+        client.send(&ClientMessage::Position(position));
+        *last = Some(position);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vox::{chunk::ChunkKind, claim::Claim, edit::Region};
+
+    #[test]
+    fn rate_limiter() {
+        let mut limiter = RateLimiter::default();
+
+        let allowed = (0..100).filter(|_| limiter.allow(0.0)).count();
+        assert_eq!(allowed, EDITS_BURST as usize);
+
+        // Tokens come back over time
+        assert!(!limiter.allow(0.05));
+        assert!(limiter.allow(0.1));
+        assert!(!limiter.allow(0.1));
+    }
+
+    #[test]
+    fn validate() {
+        let mut validator = EditValidator::default();
+        let mut claims = Claims::default();
+        let registry = KindRegistry::load(std::path::Path::new(crate::KIND_DESCRIPTIONS_PATH));
+        let stone = Kind::from(1);
+
+        // Position is unknown yet
+        assert_eq!(
+            validator.validate(1, "Alice", IVec3::ZERO, stone, 0.0, &claims, &registry),
+            Err(Rejection::OutOfReach)
+        );
+
+        // The first position is only trusted after a while
+        assert!(!validator.moved(1, Vec3::new(0.5, 2.0, 0.5), 0.0));
+        assert_eq!(
+            validator.validate(1, "Alice", IVec3::ZERO, stone, 0.0, &claims, &registry),
+            Err(Rejection::OutOfReach)
+        );
+
+        let now = TELEPORT_DELAY;
+        assert_eq!(
+            validator.validate(1, "Alice", IVec3::ZERO, stone, now, &claims, &registry),
+            Ok(())
+        );
+        assert_eq!(
+            validator.validate(
+                1,
+                "Alice",
+                (20, 0, 0).into(),
+                stone,
+                now,
+                &claims,
+                &registry
+            ),
+            Err(Rejection::OutOfReach)
+        );
+
+        // Kinds must be known, facing one of the sides
+        assert_eq!(
+            validator.validate(
+                1,
+                "Alice",
+                IVec3::ZERO,
+                4000.into(),
+                now,
+                &claims,
+                &registry
+            ),
+            Err(Rejection::UnknownKind)
+        );
+        assert_eq!(
+            validator.validate(
+                1,
+                "Alice",
+                IVec3::ZERO,
+                0xF001.into(),
+                now,
+                &claims,
+                &registry
+            ),
+            Err(Rejection::UnknownKind)
+        );
+
+        let region = Region::from_corners((-1, -1, -1).into(), (1, 1, 1).into());
+        claims
+            .claim(Claim {
+                name: "spawn".to_string(),
+                owner: None,
+                region,
+            })
+            .unwrap();
+        claims
+            .claim(Claim {
+                name: "house".to_string(),
+                owner: Some("Alice".to_string()),
+                region: Region::from_corners((0, 3, 0).into(), (0, 3, 0).into()),
+            })
+            .unwrap();
+
+        assert_eq!(
+            validator.validate(1, "Alice", IVec3::ZERO, stone, now, &claims, &registry),
+            Err(Rejection::Protected)
+        );
+        assert_eq!(
+            validator.validate(1, "Alice", (0, 3, 0).into(), stone, now, &claims, &registry),
+            Ok(())
+        );
+
+        validator.moved(2, Vec3::new(0.5, 2.0, 0.5), 0.0);
+        assert_eq!(
+            validator.validate(2, "Bob", (0, 3, 0).into(), stone, now, &claims, &registry),
+            Err(Rejection::Protected)
+        );
+    }
+
+    #[test]
+    fn moved() {
+        let mut validator = EditValidator::default();
+        let start = Vec3::new(0.5, 2.0, 0.5);
+
+        assert!(!validator.moved(1, start, 0.0));
+        assert!(!validator.moved(1, Vec3::NAN, 0.0));
+
+        // Walking around, the player stays where it jumped to, until it's trusted
+        assert!(!validator.moved(1, start + Vec3::X, 1.0));
+        assert!(validator.moved(1, start, TELEPORT_DELAY));
+        assert!(validator.moved(1, start + Vec3::X * MAX_SPEED, TELEPORT_DELAY + 1.0));
+
+        // Faking a position right before an edit doesn't make the edit reachable
+        let far = Vec3::new(1000.0, 2.0, 0.5);
+        assert!(!validator.moved(1, far, TELEPORT_DELAY + 1.1));
+
+        let claims = Claims::default();
+        let registry = KindRegistry::load(std::path::Path::new(crate::KIND_DESCRIPTIONS_PATH));
+        let result = validator.validate(
+            1,
+            "Alice",
+            far.as_ivec3(),
+            Kind::from(1),
+            TELEPORT_DELAY + 1.1,
+            &claims,
+            &registry,
+        );
+        assert_eq!(result, Err(Rejection::OutOfReach));
+
+        // Unless it stays there, like after a teleport
+        let result = validator.validate(
+            1,
+            "Alice",
+            far.as_ivec3(),
+            Kind::from(1),
+            TELEPORT_DELAY * 3.0,
+            &claims,
+            &registry,
+        );
+        assert_eq!(result, Ok(()));
+    }
+
+    #[test]
+    fn edit_position() {
+        let world = VoxWorld::default();
+        world.add(IVec3::ONE, ChunkKind::default());
+
+        assert_eq!(
+            super::edit_position(&world, IVec3::ONE, (1, 2, 3).into()),
+            Some(chunk::join_voxel(IVec3::ONE, (1, 2, 3).into()))
+        );
+
+        // Extreme coordinates are refused before they're joined
+        assert_eq!(
+            super::edit_position(&world, IVec3::splat(i32::MAX), IVec3::ZERO),
+            None
+        );
+        assert_eq!(
+            super::edit_position(&world, IVec3::ONE, IVec3::splat(i32::MIN)),
+            None
+        );
+        assert_eq!(
+            super::edit_position(&world, IVec3::splat(i32::MIN), IVec3::splat(i32::MAX)),
+            None
+        );
+        assert_eq!(super::edit_position(&world, IVec3::ZERO, IVec3::ZERO), None);
+    }
+}
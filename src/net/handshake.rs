@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+use vox::{chunk, meta::WorldMeta};
+
+/// Bump this whenever messages change, so games of different versions refuse each other.
+pub const PROTOCOL_VERSION: u32 = 11;
+
+/// Name used when none is given by the `--name` command line argument.
+pub const DEFAULT_NAME: &str = "Player";
+const MAX_NAME_LEN: usize = 16;
+
+/**
+  Parameters which must match between the server and clients, since clients generate chunks on their own.
+  Kinds don't need to match, since clients use the ones sent by the server.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorldParams {
+    pub seed: u64,
+    pub chunk_size: u32,
+}
+
+impl WorldParams {
+    /// Parameters of the given world.
+    pub fn of(meta: &WorldMeta) -> Self {
+        Self {
+            seed: meta.generator.seed,
+            chunk_size: chunk::AXIS_SIZE as u32,
+        }
+    }
+}
+
+/// First message sent by clients. Nothing else is accepted before it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Login {
+    pub version: u32,
+    pub name: String,
+    pub world: WorldParams,
+}
+
+/**
+  Checks if a client can join a server with the given world parameters, while the given names are used
+  by other players. On failure, returns why, so the player knows what to fix.
+*/
+pub fn check_login<'a>(
+    login: &Login,
+    world: &WorldParams,
+    mut names: impl Iterator<Item = &'a str>,
+) -> Result<(), String> {
+    if login.version != PROTOCOL_VERSION {
+        return Err(format!(
+            "Protocol version mismatch: server uses {} and client uses {}",
+            PROTOCOL_VERSION, login.version
+        ));
+    }
+
+    if login.world.seed != world.seed {
+        return Err(format!(
+            "World seed mismatch: server uses {} and client uses {}",
+            world.seed, login.world.seed
+        ));
+    }
+
+    if login.world.chunk_size != world.chunk_size {
+        return Err(format!(
+            "Chunk size mismatch: server uses {} and client uses {}",
+            world.chunk_size, login.world.chunk_size
+        ));
+    }
+
+    let name = &login.name;
+    if name.is_empty()
+        || name.len() > MAX_NAME_LEN
+        || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err(format!(
+            "Invalid name \"{}\": use up to {} letters, digits or underscores",
+            name, MAX_NAME_LEN
+        ));
+    }
+
+    if name == super::HOST_NAME || names.any(|other| other == name) {
+        return Err(format!("Name \"{}\" is already in use", name));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_login() {
+        let world = WorldParams {
+            seed: 1,
+            chunk_size: 16,
+        };
+        let login = |name: &str, version, world| Login {
+            version,
+            name: name.to_string(),
+            world,
+        };
+        let check = |login: &Login| super::check_login(login, &world, ["Taken"].into_iter());
+
+        assert_eq!(check(&login("Alice", PROTOCOL_VERSION, world)), Ok(()));
+
+        assert!(check(&login("Alice", PROTOCOL_VERSION + 1, world))
+            .unwrap_err()
+            .contains("Protocol version"));
+        assert!(check(&login(
+            "Alice",
+            PROTOCOL_VERSION,
+            WorldParams { seed: 2, ..world }
+        ))
+        .unwrap_err()
+        .contains("seed"));
+        assert!(check(&login(
+            "Alice",
+            PROTOCOL_VERSION,
+            WorldParams {
+                chunk_size: 32,
+                ..world
+            }
+        ))
+        .unwrap_err()
+        .contains("Chunk size"));
+
+        assert!(check(&login("", PROTOCOL_VERSION, world)).is_err());
+        assert!(check(&login("Bad Name", PROTOCOL_VERSION, world)).is_err());
+        assert!(check(&login("Taken", PROTOCOL_VERSION, world))
+            .unwrap_err()
+            .contains("already in use"));
+        assert!(check(&login(super::super::HOST_NAME, PROTOCOL_VERSION, world)).is_err());
+    }
+}
@@ -0,0 +1,311 @@
+use bevy::prelude::*;
+use std::collections::{HashMap, HashSet};
+use vox::{
+    chunk::{self, ChunkKind},
+    pipeline::{ChunkLoaded, SetVoxel, StreamingAnchor, StreamingConfig, WorldOrigin},
+    query,
+    voxel::Kind,
+    world::VoxWorld,
+};
+
+use super::{
+    client::FromServer,
+    protocol::{ClientMessage, ServerMessage},
+    server::{ClientDisconnected, ClientId, FromClient, NetServer},
+};
+
+/**
+  Chunks each client is subscribed to, which are the ones around it, within the streaming radius.
+  Clients only receive chunk data and voxel changes of chunks they are subscribed to.
+*/
+#[derive(Default)]
+pub struct Subscriptions {
+    clients: HashMap<ClientId, HashSet<IVec3>>,
+}
+
+impl Subscriptions {
+    /**
+      Subscribes the client to the chunks around the given center, dropping the ones which are now too far.
+      Returns the chunks which were subscribed and the ones which were dropped.
+    */
+    pub fn update(
+        &mut self,
+        client: ClientId,
+        center: IVec3,
+        radius: u32,
+    ) -> (Vec<IVec3>, Vec<IVec3>) {
+        let subscribed = self.clients.entry(client).or_default();
+        let in_view = query::sphere(center, radius).collect::<HashSet<_>>();
+
+        let added = in_view.difference(subscribed).copied().collect();
+        let removed = subscribed.difference(&in_view).copied().collect();
+
+        *subscribed = in_view;
+
+        (added, removed)
+    }
+
+    /// Clients subscribed to the given chunk.
+    pub fn subscribers(&self, local: IVec3) -> impl Iterator<Item = ClientId> + '_ {
+        self.clients
+            .iter()
+            .filter(move |(_, chunks)| chunks.contains(&local))
+            .map(|(client, _)| *client)
+    }
+
+    pub fn remove(&mut self, client: ClientId) {
+        self.clients.remove(&client);
+    }
+}
+
+/**
+  Chunk data received from the server for chunks which aren't loaded locally yet.
+*/
+#[derive(Default)]
+pub struct ServerChunks {
+    pending: HashMap<IVec3, Vec<(u16, Kind)>>,
+}
+
+/**
+  Run length encodes the chunk voxels, in [`chunk::voxels`] order. Generated chunks have long runs
+  of the same kind, so they're much smaller this way.
+*/
+pub fn encode_chunk(kind: &ChunkKind) -> Vec<(u16, Kind)> {
+    let mut runs: Vec<(u16, Kind)> = vec![];
+
+    for voxel in chunk::voxels() {
+        let current = kind.get(voxel);
+
+        match runs.last_mut() {
+            Some((count, kind)) if *kind == current && *count < u16::MAX => *count += 1,
+            _ => runs.push((1, current)),
+        }
+    }
+
+    runs
+}
+
+/**
+  Returns the changes needed to turn the local chunk into the one encoded by the server.
+*/
+pub fn diff_chunk(local: IVec3, kind: &ChunkKind, runs: &[(u16, Kind)]) -> Vec<SetVoxel> {
+    let kinds = runs
+        .iter()
+        .flat_map(|(count, kind)| std::iter::repeat_n(*kind, *count as usize));
+
+    chunk::voxels()
+        .zip(kinds)
+        .filter(|(voxel, server)| kind.get(*voxel) != *server)
+        .map(|(voxel, server)| SetVoxel {
+            chunk: local,
+            voxel,
+            kind: server,
+        })
+        .collect()
+}
+
+fn send_chunk(server: &mut NetServer, world: &VoxWorld, client: ClientId, local: IVec3) {
+    if let Some(kind) = world.get(local) {
+        server.send(
+            client,
+            &ServerMessage::ChunkData {
+                local,
+                runs: encode_chunk(&kind),
+            },
+        );
+    }
+}
+
+/**
+  Streaming anchor of each client on the server, so chunks around clients are loaded even when they're far
+  from the host.
+*/
+#[derive(Default)]
+pub struct ClientAnchors(HashMap<ClientId, Entity>);
+
+pub(super) fn anchor_clients(
+    mut commands: Commands,
+    origin: Res<WorldOrigin>,
+    mut anchors: ResMut<ClientAnchors>,
+    mut reader: EventReader<FromClient>,
+    mut disconnected_reader: EventReader<ClientDisconnected>,
+) {
+    for FromClient { client, message } in reader.iter() {
+        if let ClientMessage::Position(position) = message {
+            let translation = origin.from_world(*position);
+            let transform = Transform::from_translation(translation);
+
+            match anchors.0.get(client) {
+                Some(entity) => {
+                    commands
+                        .entity(*entity)
+                        .insert(transform)
+                        .insert(GlobalTransform::from(transform));
+                }
+                None => {
+                    let entity = commands
+                        .spawn()
+                        .insert(transform)
+                        .insert(GlobalTransform::from(transform))
+                        .insert(StreamingAnchor::default())
+                        .id();
+
+                    anchors.0.insert(*client, entity);
+                }
+            }
+        }
+    }
+
+    for ClientDisconnected(client) in disconnected_reader.iter() {
+        if let Some(entity) = anchors.0.remove(client) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/**
+  Moves the subscriptions of clients as they move, sending the chunks they now see and dropping the others.
+*/
+pub(super) fn update_subscriptions(
+    config: Res<StreamingConfig>,
+    world: Res<VoxWorld>,
+    server: Option<ResMut<NetServer>>,
+    mut subscriptions: ResMut<Subscriptions>,
+    mut reader: EventReader<FromClient>,
+) {
+    let mut server = match server {
+        Some(server) => server,
+        None => return,
+    };
+
+    for FromClient { client, message } in reader.iter() {
+        if let ClientMessage::Position(position) = message {
+            let center = chunk::to_local(*position);
+            let (added, removed) = subscriptions.update(*client, center, config.radius);
+
+            for local in added {
+                send_chunk(&mut server, &world, *client, local);
+            }
+
+            for local in removed {
+                server.send(*client, &ServerMessage::ChunkDropped(local));
+            }
+        }
+    }
+}
+
+/// Chunks loaded on the server after clients subscribed to them are sent as soon as they are loaded.
+pub(super) fn send_loaded_chunks(
+    world: Res<VoxWorld>,
+    server: Option<ResMut<NetServer>>,
+    subscriptions: Res<Subscriptions>,
+    mut reader: EventReader<ChunkLoaded>,
+) {
+    if let Some(mut server) = server {
+        for ChunkLoaded(local) in reader.iter() {
+            for client in subscriptions.subscribers(*local) {
+                send_chunk(&mut server, &world, client, *local);
+            }
+        }
+    }
+}
+
+pub(super) fn unsubscribe_clients(
+    mut subscriptions: ResMut<Subscriptions>,
+    mut reader: EventReader<ClientDisconnected>,
+) {
+    for ClientDisconnected(client) in reader.iter() {
+        subscriptions.remove(*client);
+    }
+}
+
+/**
+  Applies chunk data from the server over the locally generated chunks. Chunks not loaded locally yet are
+  kept until they are.
+*/
+pub(super) fn apply_server_chunks(
+    world: Res<VoxWorld>,
+    mut chunks: ResMut<ServerChunks>,
+    mut reader: EventReader<FromServer>,
+    mut loaded_reader: EventReader<ChunkLoaded>,
+    mut writer: EventWriter<SetVoxel>,
+) {
+    for FromServer(message) in reader.iter() {
+        match message {
+            ServerMessage::ChunkData { local, runs } => match world.get(*local) {
+                Some(kind) => writer.send_batch(diff_chunk(*local, &kind, runs).into_iter()),
+                None => {
+                    chunks.pending.insert(*local, runs.clone());
+                }
+            },
+            ServerMessage::ChunkDropped(local) => {
+                chunks.pending.remove(local);
+            }
+            _ => (),
+        }
+    }
+
+    for ChunkLoaded(local) in loaded_reader.iter() {
+        if let (Some(runs), Some(kind)) = (chunks.pending.remove(local), world.get(*local)) {
+            writer.send_batch(diff_chunk(*local, &kind, &runs).into_iter());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subscriptions() {
+        let mut subscriptions = Subscriptions::default();
+
+        let (added, removed) = subscriptions.update(1, IVec3::ZERO, 1);
+        assert_eq!(added.len(), 7);
+        assert!(removed.is_empty());
+
+        // Moving one chunk away keeps the shared chunks
+        let (added, removed) = subscriptions.update(1, IVec3::X, 1);
+        assert_eq!(added.len(), 5);
+        assert_eq!(removed.len(), 5);
+
+        assert_eq!(subscriptions.subscribers(2 * IVec3::X).count(), 1);
+        assert_eq!(subscriptions.subscribers(-IVec3::X).count(), 0);
+
+        subscriptions.update(2, IVec3::ZERO, 1);
+        let mut subscribers = subscriptions.subscribers(IVec3::ZERO).collect::<Vec<_>>();
+        subscribers.sort_unstable();
+        assert_eq!(subscribers, vec![1, 2]);
+
+        subscriptions.remove(1);
+        assert_eq!(
+            subscriptions.subscribers(IVec3::ZERO).collect::<Vec<_>>(),
+            vec![2]
+        );
+        assert_eq!(subscriptions.subscribers(2 * IVec3::X).count(), 0);
+    }
+
+    #[test]
+    fn encode_diff_chunk() {
+        let mut server = ChunkKind::default();
+        server.set((1, 2, 3).into(), 1.into());
+        server.set((15, 15, 15).into(), 2.into());
+
+        let runs = encode_chunk(&server);
+        assert_eq!(runs.len(), 4);
+
+        let mut local = ChunkKind::default();
+        local.set((1, 2, 3).into(), 1.into());
+        local.set((0, 0, 0).into(), 3.into());
+
+        let mut edits = diff_chunk(IVec3::X, &local, &runs);
+        edits.sort_by_key(|edit| edit.voxel.x);
+
+        assert_eq!(edits.len(), 2);
+        assert_eq!(edits[0].chunk, IVec3::X);
+        assert_eq!(edits[0].voxel, IVec3::ZERO);
+        assert_eq!(edits[0].kind, 0.into());
+        assert_eq!(edits[1].voxel, (15, 15, 15).into());
+        assert_eq!(edits[1].kind, 2.into());
+    }
+}
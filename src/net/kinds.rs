@@ -0,0 +1,82 @@
+use bevy::prelude::*;
+use vox::{
+    generator::TerrainKinds,
+    meta::WorldMeta,
+    pipeline::{self, ChunkUpdated, GenesisConfig, GenesisHooks, GenesisWorkers},
+    voxel::KindRegistry,
+    world::VoxWorld,
+};
+
+use super::{
+    client::{FromServer, NetClient, ServerDisconnected},
+    protocol::ServerMessage,
+    server::{ClientConnected, NetServer},
+};
+
+/// Sends the server kind descriptions to clients as soon as they log in.
+pub(super) fn send_kinds(
+    registry: Res<KindRegistry>,
+    server: Option<ResMut<NetServer>>,
+    mut reader: EventReader<ClientConnected>,
+) {
+    if let Some(mut server) = server {
+        for ClientConnected(client) in reader.iter() {
+            let descriptions = registry.descriptions().cloned().collect();
+            server.send(*client, &ServerMessage::Kinds(descriptions));
+        }
+    }
+}
+
+/**
+  Replaces the local kind descriptions by the server ones. Chunks loaded before are rendered again, since
+  they were meshed with the local descriptions, and genesis is started again when terrain is made of other
+  kinds. Invalid descriptions drop the connection to the server.
+*/
+#[allow(clippy::too_many_arguments)]
+pub(super) fn receive_kinds(
+    mut commands: Commands,
+    world: Res<VoxWorld>,
+    meta: Res<WorldMeta>,
+    genesis_config: Res<GenesisConfig>,
+    hooks: Res<GenesisHooks>,
+    mut workers: ResMut<GenesisWorkers>,
+    mut registry: ResMut<KindRegistry>,
+    mut reader: EventReader<FromServer>,
+    mut writer: EventWriter<ChunkUpdated>,
+    mut disconnected_writer: EventWriter<ServerDisconnected>,
+) {
+    for FromServer(message) in reader.iter() {
+        if let ServerMessage::Kinds(descriptions) = message {
+            let received = KindRegistry::try_new(descriptions.clone()).and_then(|received| {
+                TerrainKinds::new(&received).map(|terrain_kinds| (received, terrain_kinds))
+            });
+
+            let (received, terrain_kinds) = match received {
+                Ok(received) => received,
+                Err(err) => {
+                    error!("Server sent invalid kind descriptions: {}", err);
+                    commands.remove_resource::<NetClient>();
+                    disconnected_writer.send(ServerDisconnected);
+                    return;
+                }
+            };
+
+            if received.hash() != registry.hash() {
+                info!("Using kind descriptions from the server, which differ from the local ones");
+            }
+
+            if TerrainKinds::new(&registry).ok() != Some(terrain_kinds) {
+                *workers = pipeline::start_genesis(
+                    &world,
+                    *genesis_config,
+                    hooks.clone(),
+                    &meta,
+                    &received,
+                );
+            }
+
+            *registry = received;
+            writer.send_batch(world.locals().into_iter().map(ChunkUpdated));
+        }
+    }
+}
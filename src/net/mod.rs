@@ -0,0 +1,89 @@
+use bevy::prelude::*;
+
+mod client;
+mod connection;
+mod edits;
+mod handshake;
+mod interest;
+mod kinds;
+mod protocol;
+mod replication;
+mod server;
+
+pub use client::{ClientConfig, FromServer, NetClient, ServerDisconnected};
+pub use edits::{EditRequest, EditValidator};
+pub use interest::Subscriptions;
+pub use protocol::{ClientMessage, ServerMessage};
+pub use replication::PlayerPositions;
+pub use server::{
+    ClientConnected, ClientDisconnected, ClientId, FromClient, NetServer, ServerConfig,
+};
+
+/// Name of the player hosting the game, or playing offline.
+const HOST_NAME: &str = "Host";
+
+/**
+  Name which identifies a player on chat and claims. The host player has no client id.
+*/
+pub fn player_name(client: Option<ClientId>) -> String {
+    match client {
+        Some(client) => format!("Player{}", client),
+        None => HOST_NAME.to_string(),
+    }
+}
+
+/**
+  Hosts a server, or connects to one, depending on which of [`ServerConfig`] or [`ClientConfig`] is inserted.
+  Without any of them the game runs offline and nothing is done.
+*/
+pub struct NetPlugin;
+
+impl Plugin for NetPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EditValidator>()
+            .init_resource::<Subscriptions>()
+            .init_resource::<interest::ServerChunks>()
+            .init_resource::<interest::ClientAnchors>()
+            .init_resource::<replication::PlayerPositions>()
+            .init_resource::<replication::ReplicatedEntities>()
+            .init_resource::<replication::ServerClock>()
+            .init_resource::<replication::PlayerModel>()
+            .add_event::<EditRequest>()
+            .add_event::<ClientConnected>()
+            .add_event::<ClientDisconnected>()
+            .add_event::<FromClient>()
+            .add_event::<FromServer>()
+            .add_event::<ServerDisconnected>()
+            .add_startup_system(server::setup_server)
+            .add_startup_system(client::setup_client)
+            .add_system_to_stage(CoreStage::PreUpdate, server::accept_clients)
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                server::receive_from_clients.after(server::accept_clients),
+            )
+            .add_system_to_stage(CoreStage::PostUpdate, server::flush_clients)
+            .add_system_to_stage(CoreStage::Last, server::forget_names)
+            .add_system_to_stage(CoreStage::PreUpdate, client::receive_from_server)
+            .add_system_to_stage(CoreStage::PostUpdate, client::flush_server)
+            .add_system(edits::request_edits)
+            .add_system(edits::validate_edits)
+            .add_system(edits::forget_clients)
+            .add_system(edits::apply_server_voxels)
+            .add_system(edits::send_position)
+            .add_system(kinds::send_kinds)
+            .add_system(kinds::receive_kinds)
+            .add_system(interest::anchor_clients)
+            .add_system(interest::update_subscriptions)
+            .add_system(interest::send_loaded_chunks)
+            .add_system(interest::unsubscribe_clients)
+            .add_system(interest::apply_server_chunks)
+            .add_system(replication::track_players)
+            .add_system(replication::send_snapshots.after(replication::track_players))
+            .add_system(replication::receive_snapshots)
+            .add_system(replication::interpolate_transforms.after(replication::receive_snapshots))
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                edits::broadcast_voxels.before(server::flush_clients),
+            );
+    }
+}
@@ -0,0 +1,245 @@
+use bevy::prelude::*;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use vox::voxel::{Kind, KindDescription};
+
+use super::{handshake::Login, replication::NetEntity, server::ClientId};
+
+/// Messages bigger than this are treated as a broken stream.
+pub const MAX_MESSAGE_LEN: usize = 1 << 20;
+
+/// Length of the header prefixed to each message, holding the message length.
+const HEADER_LEN: usize = 4;
+
+/**
+  Messages sent by clients to the server.
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ClientMessage {
+    /// Must be the first message sent. It's kept as the first variant, so it's decoded the same way on every version.
+    Login(Login),
+    /// A chat line typed by the player. Lines starting with `/` are commands.
+    Chat(String),
+    /// World position of the player eyes.
+    Position(Vec3),
+    /**
+      Asks to change a voxel, which the client already changed locally. The sequence number is sent back
+      if the server rejects the change.
+    */
+    SetVoxel {
+        sequence: u32,
+        chunk: IVec3,
+        voxel: IVec3,
+        kind: Kind,
+    },
+}
+
+/**
+  Why the server refused to change a voxel.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Rejection {
+    /// The voxel is too far away from the player.
+    OutOfReach,
+    /// The player is changing voxels too fast.
+    RateLimited,
+    /// The voxel is inside a region the player can't change.
+    Protected,
+    /// The voxel chunk isn't loaded on the server.
+    Unloaded,
+    /// The voxel kind isn't known by the server, or faces nowhere.
+    UnknownKind,
+}
+
+/**
+  Messages sent by the server to clients.
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ServerMessage {
+    /// The server refused the login, for the given reason, and closed the connection. Kept as the first variant,
+    /// so clients of any version can read it.
+    LoginRejected(String),
+    /// The login was accepted, with the given client id.
+    Welcome(ClientId),
+    /// Kind descriptions of the server, which clients use instead of their own.
+    Kinds(Vec<KindDescription>),
+    /// A chat line to be shown. Messages from the server itself have no sender.
+    Chat { from: Option<String>, text: String },
+    /// Voxels of a chunk the client got subscribed to, run length encoded.
+    ChunkData {
+        local: IVec3,
+        runs: Vec<(u16, Kind)>,
+    },
+    /// The client isn't subscribed to this chunk anymore, so it won't receive its changes.
+    ChunkDropped(IVec3),
+    /**
+      World position of replicated entities at the given server time, in seconds, with the names of the ones
+      which have one, like players.
+    */
+    Snapshot {
+        time: f64,
+        entities: Vec<(NetEntity, Vec3)>,
+        names: Vec<(NetEntity, String)>,
+    },
+    /// A voxel was changed on the server.
+    VoxelChanged {
+        chunk: IVec3,
+        voxel: IVec3,
+        kind: Kind,
+    },
+    /**
+      A voxel change requested by the client was refused. The client must roll the voxel back to `kind`,
+      which is the kind the server has, if the server knows it.
+    */
+    EditRejected {
+        sequence: u32,
+        chunk: IVec3,
+        voxel: IVec3,
+        kind: Option<Kind>,
+        reason: Rejection,
+    },
+}
+
+/**
+  Serializes a message, prefixed by its length, so it can be split again from a stream.
+*/
+pub fn encode<T: Serialize>(message: &T) -> Vec<u8> {
+    let body = bincode::serialize(message).expect("Messages must always be serializable");
+
+    let mut bytes = Vec::with_capacity(HEADER_LEN + body.len());
+    bytes.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    bytes.extend(body);
+    bytes
+}
+
+/**
+  Splits messages out of a stream of bytes, which may arrive in pieces of any size.
+*/
+#[derive(Default)]
+pub struct Decoder {
+    buffer: Vec<u8>,
+}
+
+impl Decoder {
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /**
+      Returns the next complete message, if it has already arrived. Fails when the stream is broken,
+      in which case the connection should be dropped.
+    */
+    pub fn next<T: DeserializeOwned>(&mut self) -> Result<Option<T>, String> {
+        if self.buffer.len() < HEADER_LEN {
+            return Ok(None);
+        }
+
+        let mut header = [0; HEADER_LEN];
+        header.copy_from_slice(&self.buffer[..HEADER_LEN]);
+        let len = u32::from_le_bytes(header) as usize;
+
+        if len > MAX_MESSAGE_LEN {
+            return Err(format!("Message too big: {} bytes", len));
+        }
+
+        if self.buffer.len() < HEADER_LEN + len {
+            return Ok(None);
+        }
+
+        let message = bincode::deserialize(&self.buffer[HEADER_LEN..HEADER_LEN + len])
+            .map_err(|err| format!("Invalid message: {}", err));
+
+        self.buffer.drain(..HEADER_LEN + len);
+
+        message.map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vox::voxel::{MeshShape, SignalRole};
+
+    #[test]
+    fn encode_decode() {
+        let messages = vec![
+            ClientMessage::Chat("hello".to_string()),
+            ClientMessage::Chat("/where".to_string()),
+        ];
+
+        let bytes = messages.iter().flat_map(encode).collect::<Vec<_>>();
+
+        // Bytes arriving one by one
+        let mut decoder = Decoder::default();
+        let mut decoded = vec![];
+
+        for byte in bytes {
+            decoder.push(&[byte]);
+
+            if let Some(message) = decoder.next::<ClientMessage>().unwrap() {
+                decoded.push(message);
+            }
+        }
+
+        assert_eq!(decoded, messages);
+        assert_eq!(decoder.next::<ClientMessage>(), Ok(None));
+    }
+
+    #[test]
+    fn encode_decode_server() {
+        let message = ServerMessage::EditRejected {
+            sequence: 7,
+            chunk: (1, -2, 3).into(),
+            voxel: (15, 0, 4).into(),
+            kind: Some(2.into()),
+            reason: Rejection::RateLimited,
+        };
+
+        let mut decoder = Decoder::default();
+        decoder.push(&encode(&message));
+        assert_eq!(decoder.next(), Ok(Some(message)));
+    }
+
+    #[test]
+    fn encode_decode_kinds() {
+        let message = ServerMessage::Kinds(vec![KindDescription {
+            name: "Lamp".to_string(),
+            id: 7,
+            color: (1.0, 0.9, 0.5, 1.0),
+            shape: MeshShape::Slab,
+            prop: None,
+            directional: true,
+            light: 12,
+            tick: None,
+            signal: Some(SignalRole::Actuator {
+                powered: 7,
+                unpowered: 8,
+            }),
+            toggle: Some(8),
+            hardness: 1.5,
+            tool_tier: 1,
+            block_entity: None,
+            explosive: None,
+            liquid: None,
+            climbable: false,
+            surface: None,
+            foliage: false,
+            tint: None,
+            portal: None,
+        }]);
+
+        let mut decoder = Decoder::default();
+        decoder.push(&encode(&message));
+        assert_eq!(decoder.next(), Ok(Some(message)));
+    }
+
+    #[test]
+    fn decode_broken() {
+        let mut decoder = Decoder::default();
+        decoder.push(&u32::MAX.to_le_bytes());
+        assert!(decoder.next::<ClientMessage>().is_err());
+
+        let mut decoder = Decoder::default();
+        decoder.push(&[1, 0, 0, 0, 200]);
+        assert!(decoder.next::<ClientMessage>().is_err());
+    }
+}
@@ -0,0 +1,391 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use vox::pipeline::WorldOrigin;
+
+use super::{
+    client::FromServer,
+    protocol::{ClientMessage, ServerMessage},
+    server::{ClientDisconnected, ClientId, FromClient, NetServer},
+};
+use crate::{nameplates::Nameplate, MainCamera};
+
+/// How many snapshots per second the server sends.
+const SEND_RATE: f64 = 20.0;
+/// How far behind the server clients render replicated entities, so there's always a newer snapshot to
+/// interpolate to, even when one arrives late.
+const INTERPOLATION_DELAY: f64 = 0.1;
+/// How many snapshots each replicated entity keeps.
+const MAX_SNAPSHOTS: usize = 32;
+/// How fast the estimated server clock follows the received snapshots.
+const CLOCK_SMOOTHING: f64 = 0.1;
+
+const PLAYER_WIDTH: f32 = 0.6;
+const PLAYER_HEIGHT: f32 = 1.8;
+/// Player positions are their eyes, which are a bit below the top of the player box.
+const PLAYER_EYES: f32 = 1.6;
+
+/**
+  Entities replicated from the server to clients. Each variant is its own id space, so new kinds of
+  replicated entities can be added without clashing with the existing ones.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum NetEntity {
+    /// A player, which is the host when there is no client id.
+    Player(Option<ClientId>),
+}
+
+/// World position of a replicated entity at the given server time, in seconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Snapshot {
+    pub time: f64,
+    pub position: Vec3,
+}
+
+/**
+  Snapshots received for a replicated entity, in time order. The entity is rendered in between them,
+  so it moves smoothly even though the server sends only a few snapshots per second.
+*/
+#[derive(Component, Debug, Default)]
+pub struct SnapshotBuffer {
+    snapshots: VecDeque<Snapshot>,
+}
+
+impl SnapshotBuffer {
+    /// Adds a snapshot. Snapshots older than the last one arrived out of order and are discarded.
+    pub fn push(&mut self, snapshot: Snapshot) {
+        if let Some(last) = self.snapshots.back() {
+            if snapshot.time <= last.time {
+                return;
+            }
+        }
+
+        self.snapshots.push_back(snapshot);
+
+        while self.snapshots.len() > MAX_SNAPSHOTS {
+            self.snapshots.pop_front();
+        }
+    }
+
+    /**
+      Interpolates the position at the given time. Times before the first snapshot or after the last one
+      are clamped to them, since guessing where the entity went looks worse than waiting a bit.
+    */
+    pub fn sample(&self, time: f64) -> Option<Vec3> {
+        let after = self.snapshots.iter().position(|s| s.time >= time);
+
+        match after {
+            Some(0) => self.snapshots.front().map(|s| s.position),
+            Some(i) => {
+                let (from, to) = (self.snapshots[i - 1], self.snapshots[i]);
+                let t = (time - from.time) / (to.time - from.time);
+
+                Some(from.position.lerp(to.position, t as f32))
+            }
+            None => self.snapshots.back().map(|s| s.position),
+        }
+    }
+
+    /// Drops snapshots which won't be needed to sample the given time or any later one.
+    pub fn discard_before(&mut self, time: f64) {
+        while self.snapshots.len() > 2 && self.snapshots[1].time <= time {
+            self.snapshots.pop_front();
+        }
+    }
+}
+
+/**
+  Estimate of the server clock on clients, used to know which snapshots should be rendered.
+*/
+#[derive(Default)]
+pub struct ServerClock {
+    /// Server time minus local time, in seconds.
+    offset: Option<f64>,
+}
+
+impl ServerClock {
+    /// Updates the estimate with a snapshot sent at the given server time, received at the given local time.
+    pub fn received(&mut self, server: f64, local: f64) {
+        let offset = server - local;
+
+        self.offset = Some(match self.offset {
+            Some(current) => current + (offset - current) * CLOCK_SMOOTHING,
+            None => offset,
+        });
+    }
+
+    /// Server time which should be rendered at the given local time.
+    pub fn render_time(&self, local: f64) -> Option<f64> {
+        self.offset
+            .map(|offset| local + offset - INTERPOLATION_DELAY)
+    }
+}
+
+/// Mesh and material shared by all replicated players.
+pub struct PlayerModel {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for PlayerModel {
+    fn from_world(world: &mut World) -> Self {
+        let mut meshes = world
+            .get_resource_mut::<Assets<Mesh>>()
+            .expect("PbrPlugin must be added before NetPlugin");
+        let mesh = meshes.add(Mesh::from(shape::Box::new(
+            PLAYER_WIDTH,
+            PLAYER_HEIGHT,
+            PLAYER_WIDTH,
+        )));
+
+        let mut materials = world
+            .get_resource_mut::<Assets<StandardMaterial>>()
+            .expect("PbrPlugin must be added before NetPlugin");
+        let material = materials.add(Color::rgb(0.8, 0.5, 0.3).into());
+
+        Self { mesh, material }
+    }
+}
+
+/// Last position sent by each client, which is replicated to the others.
+#[derive(Default)]
+pub struct PlayerPositions(HashMap<ClientId, Vec3>);
+
+impl PlayerPositions {
+    /// World position of each client player, which is only known while hosting.
+    pub fn iter(&self) -> impl Iterator<Item = (ClientId, Vec3)> + '_ {
+        self.0.iter().map(|(client, position)| (*client, *position))
+    }
+}
+
+/// Entities spawned for each replicated entity.
+#[derive(Default)]
+pub struct ReplicatedEntities(HashMap<NetEntity, Entity>);
+
+pub(super) fn track_players(
+    mut positions: ResMut<PlayerPositions>,
+    mut reader: EventReader<FromClient>,
+    mut disconnected_reader: EventReader<ClientDisconnected>,
+) {
+    for FromClient { client, message } in reader.iter() {
+        if let ClientMessage::Position(position) = message {
+            positions.0.insert(*client, *position);
+        }
+    }
+
+    for ClientDisconnected(client) in disconnected_reader.iter() {
+        positions.0.remove(client);
+    }
+}
+
+/**
+  Sends, at a fixed rate, the position of all players to each client, except the client own player.
+*/
+pub(super) fn send_snapshots(
+    mut last: Local<f64>,
+    time: Res<Time>,
+    origin: Res<WorldOrigin>,
+    positions: Res<PlayerPositions>,
+    server: Option<ResMut<NetServer>>,
+    q: Query<&Transform, With<MainCamera>>,
+) {
+    let mut server = match server {
+        Some(server) => server,
+        None => return,
+    };
+
+    let now = time.seconds_since_startup();
+    if now - *last < 1.0 / SEND_RATE {
+        return;
+    }
+    *last = now;
+
+    let host = q.get_single().ok().map(|transform| {
+        (
+            NetEntity::Player(None),
+            origin.to_world(transform.translation),
+        )
+    });
+
+    let players = positions
+        .0
+        .iter()
+        .map(|(client, position)| (NetEntity::Player(Some(*client)), *position))
+        .chain(host)
+        .collect::<Vec<_>>();
+
+    for client in positions.0.keys() {
+        let entities = players
+            .iter()
+            .filter(|(entity, _)| *entity != NetEntity::Player(Some(*client)))
+            .copied()
+            .collect::<Vec<_>>();
+
+        let names = entities
+            .iter()
+            .map(|(entity, _)| match entity {
+                NetEntity::Player(client) => (*entity, server.player_name(*client)),
+            })
+            .collect();
+
+        server.send(
+            *client,
+            &ServerMessage::Snapshot {
+                time: now,
+                entities,
+                names,
+            },
+        );
+    }
+}
+
+/**
+  Spawns an entity for each replicated entity the server sends and buffers their snapshots. Entities
+  missing from a snapshot are gone from the server, so they're despawned.
+*/
+pub(super) fn receive_snapshots(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut clock: ResMut<ServerClock>,
+    mut replicated: ResMut<ReplicatedEntities>,
+    model: Res<PlayerModel>,
+    mut reader: EventReader<FromServer>,
+    mut q: Query<&mut SnapshotBuffer>,
+) {
+    for FromServer(message) in reader.iter() {
+        let (server_time, entities, names) = match message {
+            ServerMessage::Snapshot {
+                time,
+                entities,
+                names,
+            } => (*time, entities, names),
+            _ => continue,
+        };
+
+        clock.received(server_time, time.seconds_since_startup());
+
+        replicated.0.retain(|net_entity, entity| {
+            let keep = entities.iter().any(|(other, _)| other == net_entity);
+            if !keep {
+                commands.entity(*entity).despawn_recursive();
+            }
+            keep
+        });
+
+        for (net_entity, position) in entities {
+            let snapshot = Snapshot {
+                time: server_time,
+                position: *position,
+            };
+
+            match replicated.0.get(net_entity) {
+                Some(entity) => {
+                    if let Ok(mut buffer) = q.get_mut(*entity) {
+                        buffer.push(snapshot);
+                    }
+                }
+                None => {
+                    let mut buffer = SnapshotBuffer::default();
+                    buffer.push(snapshot);
+
+                    let name = names
+                        .iter()
+                        .find(|(other, _)| other == net_entity)
+                        .map(|(_, name)| name.clone());
+
+                    let entity = commands
+                        .spawn_bundle(PbrBundle {
+                            mesh: model.mesh.clone(),
+                            material: model.material.clone(),
+                            ..Default::default()
+                        })
+                        .insert(buffer)
+                        .insert(Nameplate {
+                            name,
+                            height: PLAYER_HEIGHT / 2.0,
+                        })
+                        .id();
+
+                    replicated.0.insert(*net_entity, entity);
+                }
+            }
+        }
+    }
+}
+
+/**
+  Moves all replicated entities to where they were on the server a moment ago, interpolating between the
+  snapshots around that moment.
+*/
+pub(super) fn interpolate_transforms(
+    time: Res<Time>,
+    clock: Res<ServerClock>,
+    origin: Res<WorldOrigin>,
+    mut q: Query<(&mut Transform, &mut SnapshotBuffer)>,
+) {
+    let render_time = match clock.render_time(time.seconds_since_startup()) {
+        Some(render_time) => render_time,
+        None => return,
+    };
+
+    for (mut transform, mut buffer) in q.iter_mut() {
+        if let Some(position) = buffer.sample(render_time) {
+            // Boxes are centered, while positions are the player eyes
+            let center = position - Vec3::Y * (PLAYER_EYES - PLAYER_HEIGHT / 2.0);
+            transform.translation = origin.from_world(center);
+        }
+
+        buffer.discard_before(render_time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(time: f64, x: f32) -> Snapshot {
+        Snapshot {
+            time,
+            position: Vec3::new(x, 0.0, 0.0),
+        }
+    }
+
+    #[test]
+    fn snapshot_buffer() {
+        let mut buffer = SnapshotBuffer::default();
+        assert_eq!(buffer.sample(0.0), None);
+
+        buffer.push(snapshot(1.0, 0.0));
+        buffer.push(snapshot(2.0, 10.0));
+        // Out of order snapshots are ignored
+        buffer.push(snapshot(1.5, 100.0));
+
+        assert_eq!(buffer.sample(0.5), Some(Vec3::ZERO));
+        assert_eq!(buffer.sample(1.25), Some(Vec3::X * 2.5));
+        assert_eq!(buffer.sample(3.0), Some(Vec3::X * 10.0));
+
+        buffer.push(snapshot(3.0, 20.0));
+        buffer.discard_before(2.5);
+        assert_eq!(buffer.snapshots.len(), 2);
+        assert_eq!(buffer.sample(2.5), Some(Vec3::X * 15.0));
+
+        for i in 0..MAX_SNAPSHOTS * 2 {
+            buffer.push(snapshot(4.0 + i as f64, 0.0));
+        }
+        assert_eq!(buffer.snapshots.len(), MAX_SNAPSHOTS);
+    }
+
+    #[test]
+    fn server_clock() {
+        let mut clock = ServerClock::default();
+        assert_eq!(clock.render_time(0.0), None);
+
+        clock.received(10.0, 1.0);
+        assert_eq!(clock.render_time(2.0), Some(11.0 - INTERPOLATION_DELAY));
+
+        // Jitter only moves the estimate a bit
+        clock.received(12.0, 2.0);
+        let render_time = clock.render_time(2.0).unwrap() + INTERPOLATION_DELAY;
+        assert!(render_time > 11.0 && render_time < 11.5);
+    }
+}
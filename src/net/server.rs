@@ -0,0 +1,284 @@
+use bevy::prelude::*;
+use std::{
+    collections::BTreeMap,
+    net::{SocketAddr, TcpListener},
+};
+use vox::meta::WorldMeta;
+
+use super::{
+    connection::Connection,
+    handshake::{self, Login, WorldParams},
+    protocol::{ClientMessage, ServerMessage},
+};
+
+pub type ClientId = u32;
+
+/**
+  Address the server listens on. The game only hosts a server when this resource is inserted.
+*/
+pub struct ServerConfig {
+    pub addr: SocketAddr,
+}
+
+impl ServerConfig {
+    /// Reads the address given by the `--host <addr>` command line argument, if any.
+    pub fn from_args(args: &[String]) -> Option<Self> {
+        crate::addr_arg(args, "--host").map(|addr| Self { addr })
+    }
+}
+
+/**
+  Clients connected to this game, which is hosting the world.
+*/
+pub struct NetServer {
+    listener: TcpListener,
+    world: WorldParams,
+    /// Connections which didn't log in yet.
+    pending: BTreeMap<ClientId, Connection>,
+    clients: BTreeMap<ClientId, Connection>,
+    /// Names given by clients on login. They're kept for a while after the client disconnects, so systems
+    /// handling the disconnection can still use them.
+    names: BTreeMap<ClientId, String>,
+    next_id: ClientId,
+}
+
+impl NetServer {
+    pub fn bind(addr: SocketAddr, world: WorldParams) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+
+        Ok(Self {
+            listener,
+            world,
+            pending: BTreeMap::new(),
+            clients: BTreeMap::new(),
+            names: BTreeMap::new(),
+            next_id: 0,
+        })
+    }
+
+    /// Name the given player logged in with. The host player has no client id.
+    pub fn player_name(&self, client: Option<ClientId>) -> String {
+        match client.and_then(|client| self.names.get(&client)) {
+            Some(name) => name.clone(),
+            None => super::player_name(client),
+        }
+    }
+
+    /**
+      Handles the first message of a pending connection, which must be a login. Accepted connections become
+      clients, while rejected ones are told why and dropped.
+    */
+    fn login(&mut self, client: ClientId, mut connection: Connection, login: Login) -> bool {
+        let names = self
+            .clients
+            .keys()
+            .filter_map(|client| self.names.get(client))
+            .map(String::as_str);
+
+        match handshake::check_login(&login, &self.world, names) {
+            Ok(()) => {
+                info!(
+                    "Client {} logged in from {} as {}",
+                    client,
+                    connection.addr(),
+                    login.name
+                );
+
+                connection.send(&ServerMessage::Welcome(client));
+                self.clients.insert(client, connection);
+                self.names.insert(client, login.name);
+                true
+            }
+            Err(reason) => {
+                info!(
+                    "Rejected login of client {} from {}: {}",
+                    client,
+                    connection.addr(),
+                    reason
+                );
+
+                // The connection is dropped right away, so this is the only chance to send the reason
+                connection.send(&ServerMessage::LoginRejected(reason));
+                let _ = connection.flush();
+                false
+            }
+        }
+    }
+
+    /// Queues a message to the given client. Does nothing if it isn't connected anymore.
+    pub fn send(&mut self, client: ClientId, message: &ServerMessage) {
+        if let Some(connection) = self.clients.get_mut(&client) {
+            connection.send(message);
+        }
+    }
+
+    /// Queues a message to all connected clients.
+    pub fn broadcast(&mut self, message: &ServerMessage) {
+        for connection in self.clients.values_mut() {
+            connection.send(message);
+        }
+    }
+
+    /// Drops the connection of the given client.
+    pub fn disconnect(&mut self, client: ClientId) -> bool {
+        self.clients.remove(&client).is_some()
+    }
+}
+
+/// Sent when a new client logs in to the server.
+pub struct ClientConnected(pub ClientId);
+
+/// Sent when a client connection is closed or broken.
+pub struct ClientDisconnected(pub ClientId);
+
+/// A message received from a client.
+pub struct FromClient {
+    pub client: ClientId,
+    pub message: ClientMessage,
+}
+
+pub(super) fn setup_server(
+    mut commands: Commands,
+    meta: Res<WorldMeta>,
+    config: Option<Res<ServerConfig>>,
+) {
+    let config = match config {
+        Some(config) => config,
+        None => return,
+    };
+
+    let server = NetServer::bind(config.addr, WorldParams::of(&meta))
+        .unwrap_or_else(|_| panic!("Unable to host server on {}", config.addr));
+
+    info!("Hosting server on {}", config.addr);
+    commands.insert_resource(server);
+}
+
+pub(super) fn accept_clients(server: Option<ResMut<NetServer>>) {
+    let mut server = match server {
+        Some(server) => server,
+        None => return,
+    };
+
+    while let Ok((stream, addr)) = server.listener.accept() {
+        let connection = match Connection::new(stream) {
+            Ok(connection) => connection,
+            Err(err) => {
+                warn!("Failed to accept client {}: {}", addr, err);
+                continue;
+            }
+        };
+
+        let id = server.next_id;
+        server.next_id += 1;
+        server.pending.insert(id, connection);
+
+        debug!("Client {} connected from {}", id, addr);
+    }
+}
+
+pub(super) fn receive_from_clients(
+    server: Option<ResMut<NetServer>>,
+    mut writer: EventWriter<FromClient>,
+    mut connected_writer: EventWriter<ClientConnected>,
+    mut disconnected_writer: EventWriter<ClientDisconnected>,
+) {
+    let mut server = match server {
+        Some(server) => server,
+        None => return,
+    };
+
+    for (client, mut connection) in std::mem::take(&mut server.pending) {
+        let mut messages = match connection.receive() {
+            Ok(messages) => messages.into_iter(),
+            Err(err) => {
+                debug!("Client {} left before logging in: {}", client, err);
+                continue;
+            }
+        };
+
+        match messages.next() {
+            None => {
+                server.pending.insert(client, connection);
+            }
+            Some(ClientMessage::Login(login)) => {
+                if server.login(client, connection, login) {
+                    connected_writer.send(ClientConnected(client));
+
+                    // Messages sent right after the login
+                    writer.send_batch(messages.map(|message| FromClient { client, message }));
+                }
+            }
+            Some(_) => warn!("Client {} sent a message before logging in", client),
+        }
+    }
+
+    let mut disconnected = vec![];
+
+    for (client, connection) in server.clients.iter_mut() {
+        match connection.receive() {
+            Ok(messages) => writer.send_batch(messages.into_iter().map(|message| FromClient {
+                client: *client,
+                message,
+            })),
+            Err(err) => {
+                debug!(
+                    "Client {} connection from {} lost: {}",
+                    client,
+                    connection.addr(),
+                    err
+                );
+                disconnected.push(*client);
+            }
+        }
+    }
+
+    for client in disconnected {
+        server.disconnect(client);
+        info!("Client {} disconnected", client);
+        disconnected_writer.send(ClientDisconnected(client));
+    }
+}
+
+pub(super) fn flush_clients(
+    server: Option<ResMut<NetServer>>,
+    mut writer: EventWriter<ClientDisconnected>,
+) {
+    let mut server = match server {
+        Some(server) => server,
+        None => return,
+    };
+
+    let mut disconnected = vec![];
+
+    for (client, connection) in server.clients.iter_mut() {
+        if connection.flush().is_err() {
+            disconnected.push(*client);
+        }
+    }
+
+    for client in disconnected {
+        server.disconnect(client);
+        info!("Client {} disconnected", client);
+        writer.send(ClientDisconnected(client));
+    }
+}
+
+/**
+  Forgets the names of disconnected clients. Clients disconnected after the update stage are only handled
+  by other systems on the next frame, so names are kept for one more frame.
+*/
+pub(super) fn forget_names(
+    mut disconnected: Local<Vec<ClientId>>,
+    server: Option<ResMut<NetServer>>,
+    mut reader: EventReader<ClientDisconnected>,
+) {
+    if let Some(mut server) = server {
+        for client in disconnected.drain(..) {
+            server.names.remove(&client);
+        }
+
+        disconnected.extend(reader.iter().map(|ClientDisconnected(client)| *client));
+    }
+}
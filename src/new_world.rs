@@ -0,0 +1,446 @@
+use bevy::{
+    input::{keyboard::KeyboardInput, ElementState},
+    prelude::*,
+};
+use std::path::Path;
+use vox::{
+    generator::{GeneratorKind, WorldGenerator},
+    meta::{WorldMeta, META_PATH},
+    pipeline::{self, GenesisConfig, GenesisHooks, GenesisWorkers},
+    voxel::KindRegistry,
+    world::VoxWorld,
+};
+
+use crate::{
+    backup,
+    game_state::{self, GameState},
+    FONT_PATH,
+};
+
+const FONT_SIZE: f32 = 20.0;
+
+const MAX_NAME_LEN: usize = 32;
+const MAX_PATH_LEN: usize = 128;
+/// Highest terrain which can be asked for, in voxels.
+const MAX_HEIGHT: u32 = 256;
+
+const GENERATORS: [&str; 3] = ["Noise", "Flat", "Heightmap"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Name,
+    Seed,
+    Generator,
+    Height,
+    Image,
+}
+
+const FIELDS: [Field; 5] = [
+    Field::Name,
+    Field::Seed,
+    Field::Generator,
+    Field::Height,
+    Field::Image,
+];
+
+/// Button of the new world screen.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+enum FormButton {
+    /// Clicking a field types on it, or picks the next generator.
+    Field(Field),
+    Create,
+    Back,
+}
+
+#[derive(Component)]
+struct ErrorText;
+
+/**
+  Values typed on the new world screen. Numbers are kept as typed, so they can be checked once the world is
+  created.
+*/
+#[derive(Debug, Clone, PartialEq)]
+struct NewWorldForm {
+    name: String,
+    /// When empty, a random seed is used.
+    seed: String,
+    /// Index of the chosen generator on [`GENERATORS`].
+    generator: usize,
+    height: String,
+    /// Heightmap image path, used by the heightmap generator.
+    image: String,
+    focused: Option<Field>,
+    error: Option<String>,
+}
+
+impl Default for NewWorldForm {
+    fn default() -> Self {
+        Self {
+            name: "New world".to_string(),
+            seed: String::new(),
+            generator: 0,
+            height: WorldGenerator::default().height.to_string(),
+            image: String::new(),
+            focused: Some(Field::Name),
+            error: None,
+        }
+    }
+}
+
+impl NewWorldForm {
+    /// Types the given character on the focused field. Number fields only take digits.
+    fn type_char(&mut self, c: char) {
+        let (value, max_len, digits) = match self.focused {
+            Some(Field::Name) => (&mut self.name, MAX_NAME_LEN, false),
+            Some(Field::Seed) => (&mut self.seed, u64::MAX.to_string().len(), true),
+            Some(Field::Height) => (&mut self.height, MAX_HEIGHT.to_string().len(), true),
+            Some(Field::Image) => (&mut self.image, MAX_PATH_LEN, false),
+            Some(Field::Generator) | None => return,
+        };
+
+        if !c.is_control() && (!digits || c.is_ascii_digit()) && value.len() < max_len {
+            value.push(c);
+        }
+    }
+
+    fn erase(&mut self) {
+        match self.focused {
+            Some(Field::Name) => self.name.pop(),
+            Some(Field::Seed) => self.seed.pop(),
+            Some(Field::Height) => self.height.pop(),
+            Some(Field::Image) => self.image.pop(),
+            Some(Field::Generator) | None => None,
+        };
+    }
+
+    /// Focuses the given field. The generator field isn't typed on, so it picks the next generator instead.
+    fn click(&mut self, field: Field) {
+        if field == Field::Generator {
+            self.generator = (self.generator + 1) % GENERATORS.len();
+        } else {
+            self.focused = Some(field);
+        }
+    }
+
+    fn label(&self, field: Field) -> String {
+        let (name, value) = match field {
+            Field::Name => ("Name", self.name.as_str()),
+            Field::Seed if self.seed.is_empty() && self.focused != Some(field) => {
+                ("Seed", "random")
+            }
+            Field::Seed => ("Seed", self.seed.as_str()),
+            Field::Generator => ("Generator", GENERATORS[self.generator]),
+            Field::Height => ("Height", self.height.as_str()),
+            Field::Image => ("Heightmap image", self.image.as_str()),
+        };
+
+        let cursor = if self.focused == Some(field) { "_" } else { "" };
+        format!("{}: {}{}", name, value, cursor)
+    }
+
+    /**
+      Metadata of the world described by the form, using `random_seed` when no seed was typed. On failure,
+      returns what must be fixed.
+    */
+    fn meta(&self, random_seed: u64) -> Result<WorldMeta, String> {
+        let name = self.name.trim();
+        if name.is_empty() {
+            return Err("The world needs a name".to_string());
+        }
+
+        let seed = if self.seed.is_empty() {
+            random_seed
+        } else {
+            self.seed
+                .parse()
+                .map_err(|_| format!("Invalid seed: {}", self.seed))?
+        };
+
+        let height = self
+            .height
+            .parse::<u32>()
+            .ok()
+            .filter(|height| (1..=MAX_HEIGHT).contains(height))
+            .ok_or_else(|| format!("Height must be from 1 to {}", MAX_HEIGHT))?;
+
+        let kind = match GENERATORS[self.generator] {
+            "Flat" => GeneratorKind::Flat,
+            "Heightmap" if Path::new(&self.image).is_file() => {
+                GeneratorKind::Heightmap(self.image.clone())
+            }
+            "Heightmap" => return Err(format!("Heightmap image not found: {}", self.image)),
+            _ => GeneratorKind::Noise,
+        };
+
+        Ok(WorldMeta {
+            name: name.to_string(),
+            generator: WorldGenerator { kind, seed, height },
+            ..Default::default()
+        })
+    }
+}
+
+pub struct NewWorldPlugin;
+
+impl Plugin for NewWorldPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NewWorldForm>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::NewWorld).with_system(setup_new_world_screen),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::NewWorld)
+                    .with_system(type_on_form)
+                    .with_system(click_form_buttons)
+                    .with_system(
+                        update_form_texts
+                            .after(type_on_form)
+                            .after(click_form_buttons),
+                    ),
+            );
+    }
+}
+
+fn setup_new_world_screen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut form: ResMut<NewWorldForm>,
+) {
+    *form = NewWorldForm::default();
+
+    let font = asset_server.load(FONT_PATH);
+    let root = game_state::menu_root(&mut commands, Color::BLACK);
+
+    commands.entity(root).with_children(|parent| {
+        game_state::spawn_title(parent, font.clone(), "New world");
+
+        for field in FIELDS {
+            game_state::spawn_button(parent, font.clone(), "", FormButton::Field(field));
+        }
+
+        parent
+            .spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    "",
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: FONT_SIZE,
+                        color: Color::rgb(1.0, 0.4, 0.4),
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            })
+            .insert(ErrorText);
+
+        if Path::new(META_PATH).exists() {
+            game_state::spawn_title(parent, font.clone(), "");
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    "The current world is kept on the saved worlds",
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: FONT_SIZE,
+                        color: Color::WHITE,
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+        }
+
+        game_state::spawn_button(parent, font.clone(), "Create", FormButton::Create);
+        game_state::spawn_button(parent, font, "Back", FormButton::Back);
+    });
+}
+
+/**
+  Types on the focused field. Keyboard input is held back outside of the game, so raw keyboard events are
+  used for erasing.
+*/
+fn type_on_form(
+    mut form: ResMut<NewWorldForm>,
+    mut char_reader: EventReader<ReceivedCharacter>,
+    mut key_reader: EventReader<KeyboardInput>,
+) {
+    for evt in char_reader.iter() {
+        form.type_char(evt.char);
+    }
+
+    for evt in key_reader.iter() {
+        if evt.state == ElementState::Pressed && evt.key_code == Some(KeyCode::Back) {
+            form.erase();
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn click_form_buttons(
+    world: Res<VoxWorld>,
+    registry: Res<KindRegistry>,
+    genesis_config: Res<GenesisConfig>,
+    hooks: Res<GenesisHooks>,
+    mut form: ResMut<NewWorldForm>,
+    mut meta: ResMut<WorldMeta>,
+    mut workers: ResMut<GenesisWorkers>,
+    mut state: ResMut<State<GameState>>,
+    q: Query<(&Interaction, &FormButton), Changed<Interaction>>,
+) {
+    for (interaction, button) in q.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        match button {
+            FormButton::Field(field) => form.click(*field),
+            FormButton::Back => {
+                let _ = state.set(GameState::MainMenu);
+            }
+            FormButton::Create => {
+                let new_meta = match form.meta(rand::random()) {
+                    Ok(meta) => meta,
+                    Err(err) => {
+                        form.error = Some(err);
+                        continue;
+                    }
+                };
+
+                match backup::shelve_world() {
+                    Ok(Some(path)) => info!("Previous world kept on {}", path.display()),
+                    Ok(None) => (),
+                    Err(err) => {
+                        form.error =
+                            Some(format!("Failed to set the current world aside: {}", err));
+                        continue;
+                    }
+                }
+
+                new_meta.save(Path::new(META_PATH));
+                info!("Created world {}", new_meta.name);
+
+                // Chunks of the new world are generated by its own generators
+                *workers = pipeline::start_genesis(
+                    &world,
+                    *genesis_config,
+                    hooks.clone(),
+                    &new_meta,
+                    &registry,
+                );
+                *meta = new_meta;
+
+                let _ = state.set(GameState::Loading);
+            }
+        }
+    }
+}
+
+fn update_form_texts(
+    form: Res<NewWorldForm>,
+    buttons: Query<(&FormButton, &Children)>,
+    mut texts: Query<&mut Text, Without<ErrorText>>,
+    mut errors: Query<&mut Text, With<ErrorText>>,
+) {
+    if !form.is_changed() {
+        return;
+    }
+
+    for (button, children) in buttons.iter() {
+        let field = match button {
+            FormButton::Field(field) => *field,
+            _ => continue,
+        };
+
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                text.sections[0].value = form.label(field);
+            }
+        }
+    }
+
+    for mut text in errors.iter_mut() {
+        text.sections[0].value = form.error.clone().unwrap_or_default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn type_char() {
+        let mut form = NewWorldForm {
+            focused: Some(Field::Seed),
+            ..Default::default()
+        };
+
+        for c in "4x2\n".chars() {
+            form.type_char(c);
+        }
+        assert_eq!(form.seed, "42");
+
+        form.erase();
+        assert_eq!(form.seed, "4");
+
+        form.click(Field::Name);
+        form.type_char('!');
+        assert_eq!(form.name, "New world!");
+        assert_eq!(form.label(Field::Name), "Name: New world!_");
+        assert_eq!(form.label(Field::Seed), "Seed: 4");
+
+        // The generator field picks the next generator, keeping the focus where it was
+        form.click(Field::Generator);
+        assert_eq!(form.generator, 1);
+        assert_eq!(form.focused, Some(Field::Name));
+        form.type_char('.');
+        assert_eq!(form.label(Field::Generator), "Generator: Flat");
+    }
+
+    #[test]
+    fn meta() {
+        let form = NewWorldForm::default();
+
+        let meta = form.meta(7).unwrap();
+        assert_eq!(meta.name, "New world");
+        assert_eq!(
+            meta.generator,
+            WorldGenerator {
+                seed: 7,
+                ..Default::default()
+            }
+        );
+
+        let flat = NewWorldForm {
+            seed: "42".to_string(),
+            generator: 1,
+            height: "10".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            flat.meta(7).unwrap().generator,
+            WorldGenerator {
+                kind: GeneratorKind::Flat,
+                seed: 42,
+                height: 10,
+            }
+        );
+
+        let invalid = |form: NewWorldForm| form.meta(7).is_err();
+        assert!(invalid(NewWorldForm {
+            name: " ".to_string(),
+            ..Default::default()
+        }));
+        assert!(invalid(NewWorldForm {
+            height: "0".to_string(),
+            ..Default::default()
+        }));
+        assert!(invalid(NewWorldForm {
+            height: "999".to_string(),
+            ..Default::default()
+        }));
+        assert!(invalid(NewWorldForm {
+            generator: 2,
+            image: "missing.png".to_string(),
+            ..Default::default()
+        }));
+    }
+}
@@ -0,0 +1,39 @@
+use bevy::prelude::*;
+use vox::{bounds::Bounds, broadphase::Broadphase};
+
+/**
+  Box around the translation of dynamic entities, like mobs and item drops, which is added to the
+  [`Broadphase`] so they can find what they collide with.
+*/
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Collider {
+    pub half_extents: Vec3,
+}
+
+impl Collider {
+    /// Render space bounds of the collider at the given translation.
+    pub fn bounds(&self, translation: Vec3) -> Bounds {
+        Bounds::new(
+            translation - self.half_extents,
+            translation + self.half_extents,
+        )
+    }
+}
+
+pub struct PhysicsPlugin;
+
+impl Plugin for PhysicsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Broadphase>()
+            .add_system_to_stage(CoreStage::PreUpdate, build_broadphase);
+    }
+}
+
+/// Rebuilds the broadphase before anything moves, so every system of the frame sees the same one.
+fn build_broadphase(mut broadphase: ResMut<Broadphase>, q: Query<(Entity, &Transform, &Collider)>) {
+    broadphase.clear();
+
+    for (entity, transform, collider) in q.iter() {
+        broadphase.insert(entity, collider.bounds(transform.translation));
+    }
+}
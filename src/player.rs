@@ -0,0 +1,354 @@
+use bevy::{input::mouse::MouseMotion, prelude::*};
+use vox::{
+    body::{Body, SNEAK_HEIGHT, SNEAK_SPEED},
+    climb,
+    fluid::{self, AirMeter},
+    pipeline::WorldOrigin,
+    surface,
+    voxel::{KindRegistry, Surface},
+    world::VoxWorld,
+};
+
+use crate::{
+    chat::Chat,
+    combat::PlayerHealth,
+    console::Console,
+    drops,
+    game_state::GameState,
+    gamepad::{GamepadConfig, GamepadInput},
+    signs::Signs,
+    spectator::{self, Spectator},
+    sprint::{CharacterConfig, Sprint},
+    underwater::CameraLiquid,
+    MainCamera,
+};
+
+/// While held, mouse movement turns the camera.
+const LOOK_BUTTON: MouseButton = MouseButton::Middle;
+/// Radians turned per pixel of mouse movement.
+const LOOK_SENSITIVITY: f32 = 0.003;
+/// How close to straight up or down the camera can look, so it never flips over.
+const MAX_PITCH: f32 = 1.54;
+
+/// Jumps, or climbs and swims up.
+const JUMP_KEY: KeyCode = KeyCode::Space;
+/// While held, the player sneaks, which is slower but never walks off edges. It also climbs and swims down.
+const SNEAK_KEY: KeyCode = KeyCode::C;
+
+pub(crate) const BODY_WIDTH: f32 = 0.6;
+pub(crate) const BODY_HEIGHT: f32 = 1.8;
+/// The camera is the player eyes, which are a bit below the top of the body.
+pub(crate) const BODY_EYES: f32 = 1.6;
+
+/// Walking speed, in voxels per second.
+const WALK_SPEED: f32 = 4.5;
+/// Vertical speed, in voxels per second, jumps start with. It's enough to jump a bit higher than a voxel.
+const JUMP_SPEED: f32 = 7.5;
+/// In voxels per second squared, like mobs.
+const GRAVITY: f32 = 20.0;
+const MAX_FALL_SPEED: f32 = 30.0;
+/// The air is walked on like a slippery surface, so the player can steer a bit while jumping or falling.
+const AIR: Surface = Surface {
+    friction: 0.25,
+    speed: 1.0,
+};
+/// Vertical speed, in voxels per second, the player swims up or down with.
+const SWIM_SPEED: f32 = 3.0;
+
+/// How the player is moving while walking around.
+#[derive(Default)]
+struct PlayerMotion {
+    /// In voxels per second.
+    velocity: Vec3,
+    sneaking: bool,
+}
+
+/**
+  Air left to the player. Like [`PlayerHealth`], it's a resource, since the player is the camera.
+*/
+#[derive(Default)]
+pub struct PlayerAir(pub AirMeter);
+
+pub struct PlayerPlugin;
+
+impl Plugin for PlayerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PlayerMotion>()
+            .init_resource::<PlayerAir>()
+            .add_system(look)
+            .add_system_set(
+                SystemSet::on_update(GameState::InGame)
+                    .with_system(walk)
+                    .with_system(breathe),
+            );
+    }
+}
+
+/// Body of the player, which steps up ledges as high as the character config allows, and is lower sneaking.
+fn body(config: &CharacterConfig, sneaking: bool) -> Body {
+    let body = Body {
+        width: BODY_WIDTH,
+        height: BODY_HEIGHT,
+        max_step: config.max_step,
+    };
+
+    if sneaking {
+        body.sneaking()
+    } else {
+        body
+    }
+}
+
+/// How high the player eyes are above its feet.
+fn eyes(sneaking: bool) -> f32 {
+    if sneaking {
+        BODY_EYES * SNEAK_HEIGHT
+    } else {
+        BODY_EYES
+    }
+}
+
+/**
+  Returns the horizontal direction to walk to, relative to where the camera is heading to, from the pressed
+  keys. Looking up or down doesn't change it.
+*/
+fn walk_direction(keyboard: &Input<KeyCode>, yaw: f32) -> Vec3 {
+    let heading = Transform::from_rotation(Quat::from_rotation_y(yaw));
+    let dir = spectator::move_direction(keyboard, &heading);
+
+    Vec3::new(dir.x, 0.0, dir.z).normalize_or_zero()
+}
+
+/// Vertical input to climb or swim with, from -1 to go down to 1 to go up. Pressing both holds on.
+fn vertical_input(up: bool, down: bool) -> f32 {
+    match (up, down) {
+        (true, false) => 1.0,
+        (false, true) => -1.0,
+        _ => 0.0,
+    }
+}
+
+/// Turns the camera, both walking and spectating, with either the mouse or a gamepad.
+fn look(
+    time: Res<Time>,
+    mouse: Res<Input<MouseButton>>,
+    gamepad: Res<GamepadInput>,
+    gamepad_config: Res<GamepadConfig>,
+    mut motion_reader: EventReader<MouseMotion>,
+    mut q: Query<&mut Transform, With<MainCamera>>,
+) {
+    // Mouse motion must always be consumed, so old motion doesn't turn the camera once the button is held
+    let motion = motion_reader
+        .iter()
+        .fold(Vec2::ZERO, |sum, evt| sum + evt.delta);
+
+    let mut transform = match q.get_single_mut() {
+        Ok(transform) => transform,
+        Err(_) => return,
+    };
+
+    // Turning right and down is positive, like mouse motion
+    let stick = Vec2::new(gamepad.look.x, -gamepad.look.y);
+    let mut turn = stick * gamepad_config.look_speed * time.delta_seconds();
+
+    if mouse.pressed(LOOK_BUTTON) {
+        turn += motion * LOOK_SENSITIVITY;
+    }
+
+    if turn != Vec2::ZERO {
+        let (yaw, pitch, _) = transform.rotation.to_euler(EulerRot::YXZ);
+        let yaw = yaw - turn.x;
+        let pitch = (pitch - turn.y).clamp(-MAX_PITCH, MAX_PITCH);
+
+        transform.rotation = Quat::from_euler(EulerRot::YXZ, yaw, pitch, 0.0);
+    }
+}
+
+/**
+  Walks the player around while it's not spectating, faster while sprinting. The player falls, jumps, steps
+  up ledges and walks on the ground like mobs do. Sneaking lowers the player and slows it down, stopping it
+  right before walking off edges, and the player only stands up once there's room for it. While touching a
+  climbable voxel, like a ladder, the jump and sneak keys climb up and down, and the player holds on without
+  them. Inside liquids the player floats, moves slower and swims up and down with the same keys. Keys and the
+  left stick are ignored while typing on the console, chat or a sign.
+*/
+#[allow(clippy::too_many_arguments)]
+fn walk(
+    time: Res<Time>,
+    spectator: Res<Spectator>,
+    console: Res<Console>,
+    chat: Res<Chat>,
+    signs: Res<Signs>,
+    config: Res<CharacterConfig>,
+    sprint: Res<Sprint>,
+    keyboard: Res<Input<KeyCode>>,
+    gamepad: Res<GamepadInput>,
+    world: Res<VoxWorld>,
+    registry: Res<KindRegistry>,
+    origin: Res<WorldOrigin>,
+    mut motion: ResMut<PlayerMotion>,
+    mut q: Query<&mut Transform, With<MainCamera>>,
+) {
+    if spectator.is_active() {
+        return;
+    }
+
+    let mut transform = match q.get_single_mut() {
+        Ok(transform) => transform,
+        Err(_) => return,
+    };
+
+    let dt = time.delta_seconds();
+    let solid = |voxel: IVec3| drops::is_solid(&world, &registry, &origin, voxel.as_vec3());
+    let typing = console.visible || chat.open || signs.is_editing();
+
+    let feet = transform.translation - Vec3::Y * eyes(motion.sneaking);
+
+    motion.sneaking = if !typing && keyboard.pressed(SNEAK_KEY) {
+        true
+    } else {
+        motion.sneaking && body(&config, false).collides(feet, &solid)
+    };
+
+    let body = body(&config, motion.sneaking);
+    let on_ground = body.on_ground(feet, &solid);
+
+    let climbing = climb::touches_climbable(
+        &world,
+        &registry,
+        origin.to_world(feet),
+        body.width,
+        body.height,
+    );
+
+    let (wish, jump) = if typing {
+        (Vec3::ZERO, false)
+    } else {
+        let (yaw, _, _) = transform.rotation.to_euler(EulerRot::YXZ);
+        let heading = Transform::from_rotation(Quat::from_rotation_y(yaw));
+        let dir = (walk_direction(&keyboard, yaw) + gamepad.move_direction(&heading))
+            .clamp_length_max(1.0);
+
+        let speed = if motion.sneaking {
+            WALK_SPEED * SNEAK_SPEED
+        } else {
+            WALK_SPEED * sprint.multiplier(&config)
+        };
+
+        (dir * speed, keyboard.pressed(JUMP_KEY))
+    };
+
+    let liquid = fluid::submersion(&world, &registry, origin.to_world(feet), body.height);
+
+    // Standing over an edge is standing on the usual ground, even if the voxel right below is empty
+    let ground = if liquid.is_some() {
+        fluid::SWIM_SURFACE
+    } else if on_ground {
+        surface::ground(&world, &registry, origin.to_world(feet)).unwrap_or_default()
+    } else {
+        AIR
+    };
+
+    let mut velocity = surface::walk(motion.velocity, wish, ground, dt);
+
+    let vertical = vertical_input(jump, motion.sneaking && !typing);
+
+    if climbing {
+        velocity = climb::climb(velocity, vertical);
+    } else {
+        if let Some((depth, liquid)) = liquid {
+            velocity = fluid::float(velocity, depth, liquid, GRAVITY, dt);
+
+            if vertical != 0.0 {
+                velocity.y = vertical * SWIM_SPEED;
+            }
+        } else if on_ground && jump && velocity.y <= 0.0 {
+            velocity.y = JUMP_SPEED;
+        }
+        velocity.y = (velocity.y - GRAVITY * dt).max(-MAX_FALL_SPEED);
+    }
+
+    let horizontal = Vec3::new(velocity.x, 0.0, velocity.z) * dt;
+    let moved = if motion.sneaking {
+        body.sneak_move(feet, horizontal, solid)
+    } else {
+        body.step_move(feet, horizontal, solid)
+    };
+
+    let (feet, blocked) = body.fall(moved, velocity.y * dt, solid);
+    if blocked {
+        velocity.y = 0.0;
+    }
+
+    motion.velocity = velocity;
+    transform.translation = feet + Vec3::Y * eyes(motion.sneaking);
+}
+
+/**
+  The player runs out of air while its eyes are inside a liquid, and drowns once it's gone. Air is back once
+  the player dies. Nothing is breathed while spectating, since the camera isn't where the player is.
+*/
+fn breathe(
+    time: Res<Time>,
+    spectator: Res<Spectator>,
+    liquid: Res<CameraLiquid>,
+    mut air: ResMut<PlayerAir>,
+    mut health: ResMut<PlayerHealth>,
+) {
+    if spectator.is_active() {
+        return;
+    }
+
+    let damage = air.0.breathe(liquid.is_inside(), time.delta_seconds());
+
+    if damage > 0.0 && health.0.damage(damage) {
+        air.0 = AirMeter::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn walk_direction() {
+        let mut keyboard = Input::<KeyCode>::default();
+
+        assert_eq!(super::walk_direction(&keyboard, 0.0), Vec3::ZERO);
+
+        keyboard.press(KeyCode::W);
+        assert_eq!(super::walk_direction(&keyboard, 0.0), -Vec3::Z);
+
+        // Flying keys don't slow walking down
+        keyboard.press(KeyCode::Space);
+        let dir = super::walk_direction(&keyboard, 0.0);
+        assert!((dir + Vec3::Z).length() < 1e-5);
+
+        let dir = super::walk_direction(&keyboard, std::f32::consts::FRAC_PI_2);
+        assert!((dir + Vec3::X).length() < 1e-5);
+    }
+
+    #[test]
+    fn vertical_input() {
+        assert_eq!(super::vertical_input(true, false), 1.0);
+        assert_eq!(super::vertical_input(false, true), -1.0);
+        assert_eq!(super::vertical_input(true, true), 0.0);
+        assert_eq!(super::vertical_input(false, false), 0.0);
+    }
+
+    #[test]
+    fn body() {
+        let config = CharacterConfig {
+            max_step: 0.5,
+            ..Default::default()
+        };
+
+        assert_eq!(super::body(&config, false).max_step, 0.5);
+        assert_eq!(super::body(&config, false).height, BODY_HEIGHT);
+
+        // Sneaking bodies are lower, with the eyes lowered along
+        let sneaking = super::body(&config, true);
+        assert_eq!(sneaking.height, BODY_HEIGHT * SNEAK_HEIGHT);
+        assert!(eyes(true) < sneaking.height);
+    }
+}
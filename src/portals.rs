@@ -0,0 +1,239 @@
+use bevy::prelude::*;
+use vox::{
+    chunk,
+    dimension::{self, Dimensions},
+    heightmap::Heightmap,
+    meta::WorldMeta,
+    pipeline::{RecenterStreaming, SetVoxel, WorldOrigin},
+    voxel::{Kind, KindRegistry},
+    world::VoxWorld,
+};
+
+use crate::{
+    game_state::GameState, messages::Messages, spectator::Spectator, MainCamera, SPAWN_EYE_HEIGHT,
+};
+
+/// Voxels along the X axis between the portal back and where the player arrives, so they don't walk into it.
+const ARRIVAL_DISTANCE: i32 = 2;
+
+/// Index of the dimension the player is in, which is the one chunks are streamed from.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ActiveDimension(pub usize);
+
+/**
+  Arrival on another dimension, waiting for its chunks to be loaded, so the player can be placed on its surface
+  with a portal back right next to them. Columns are in world voxels.
+*/
+struct Arrival {
+    portal: IVec2,
+    kind: Kind,
+    player: IVec2,
+}
+
+/// Arrival the player is waiting for, if any. Portals aren't entered while waiting.
+#[derive(Default)]
+struct PendingArrival(Option<Arrival>);
+
+pub struct PortalsPlugin;
+
+impl Plugin for PortalsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ActiveDimension>()
+            .init_resource::<PendingArrival>()
+            .add_startup_system(check_portals)
+            .add_system_set(
+                SystemSet::on_update(GameState::InGame)
+                    .with_system(enter_portals)
+                    .with_system(arrive.after(enter_portals))
+                    .with_system(track_dimension.after(arrive)),
+            );
+    }
+}
+
+/**
+  Dimension the player goes to, with where on it, when walking into a portal to the `target` dimension on the
+  given world voxel. Portals lead to their target, and back to the overworld when already on it. Returns
+  `None` when the target doesn't exist on this world.
+*/
+pub fn destination(dimensions: &Dimensions, target: &str, voxel: IVec3) -> Option<(usize, IVec3)> {
+    let target = dimensions.index(target)?;
+    let (local, _) = chunk::split_voxel(voxel);
+
+    let index = if dimension::index_of(local) == target as i32 {
+        0
+    } else {
+        target
+    };
+
+    Some((index, dimension::move_voxel(voxel, index)))
+}
+
+/// Warns about portals to dimensions this world doesn't have, since walking into them does nothing.
+fn check_portals(registry: Res<KindRegistry>, meta: Res<WorldMeta>) {
+    for desc in registry.descriptions() {
+        if let Some(target) = &desc.portal {
+            if meta.dimensions.index(target).is_none() {
+                warn!(
+                    "Kind {} is a portal to unknown dimension {}",
+                    desc.name, target
+                );
+            }
+        }
+    }
+}
+
+/**
+  Teleports the player to the dimension of the portal they walked into. Both the eyes and the feet of the
+  player count, and spectators fly through portals without entering them.
+*/
+#[allow(clippy::too_many_arguments)]
+fn enter_portals(
+    world: Res<VoxWorld>,
+    registry: Res<KindRegistry>,
+    meta: Res<WorldMeta>,
+    origin: Res<WorldOrigin>,
+    spectator: Res<Spectator>,
+    mut pending: ResMut<PendingArrival>,
+    mut recenter_writer: EventWriter<RecenterStreaming>,
+    mut q: Query<(&mut Transform, &mut GlobalTransform), With<MainCamera>>,
+) {
+    if pending.0.is_some() || spectator.is_active() {
+        return;
+    }
+
+    let (mut transform, mut global_transform) = match q.get_single_mut() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+
+    let eyes = origin.to_voxel(transform.translation);
+    let feet = origin.to_voxel(transform.translation - Vec3::Y * SPAWN_EYE_HEIGHT);
+
+    let portal = [eyes, feet].into_iter().find_map(|voxel| {
+        let kind = world.get_voxel(voxel)?;
+        registry.portal(kind).map(|target| (voxel, kind, target))
+    });
+
+    let (voxel, kind, target) = match portal {
+        Some(portal) => portal,
+        None => return,
+    };
+
+    let (index, destination) = match destination(&meta.dimensions, target, voxel) {
+        Some(destination) => destination,
+        None => return,
+    };
+
+    let arrival = destination + IVec3::X * ARRIVAL_DISTANCE;
+
+    // The height is only known once the chunks there are loaded, so it's fixed on arrival
+    let mut position = origin.from_world(arrival.as_vec3() + Vec3::new(0.5, 0.0, 0.5));
+    position.y = transform.translation.y;
+
+    // Global transform is also updated, so streaming doesn't have to wait for transform propagation
+    transform.translation = position;
+    global_transform.translation = position;
+    recenter_writer.send(RecenterStreaming);
+
+    info!(
+        "Entered portal to {}",
+        meta.dimensions.name(index).unwrap_or_default()
+    );
+
+    pending.0 = Some(Arrival {
+        portal: IVec2::new(destination.x, destination.z),
+        kind,
+        player: IVec2::new(arrival.x, arrival.z),
+    });
+}
+
+/**
+  Places the player on the surface once the chunks they arrived on are loaded, with a portal back next to them,
+  unless there's something on its place already.
+*/
+fn arrive(
+    world: Res<VoxWorld>,
+    origin: Res<WorldOrigin>,
+    heightmap: Res<Heightmap>,
+    mut pending: ResMut<PendingArrival>,
+    mut writer: EventWriter<SetVoxel>,
+    mut q: Query<(&mut Transform, &mut GlobalTransform), With<MainCamera>>,
+) {
+    let arrival = match &pending.0 {
+        Some(arrival) => arrival,
+        None => return,
+    };
+
+    let (portal, player) = match (
+        heightmap.surface(arrival.portal),
+        heightmap.surface(arrival.player),
+    ) {
+        (Some(portal), Some(player)) => (portal, player),
+        _ => return,
+    };
+
+    if let Ok((mut transform, mut global_transform)) = q.get_single_mut() {
+        let surface = origin.from_world(player.as_vec3());
+        transform.translation.y = surface.y + SPAWN_EYE_HEIGHT;
+        global_transform.translation.y = transform.translation.y;
+    }
+
+    if matches!(world.get_voxel(portal), Some(kind) if kind.is_empty()) {
+        let (chunk, voxel) = chunk::split_voxel(portal);
+        writer.send(SetVoxel {
+            chunk,
+            voxel,
+            kind: arrival.kind,
+        });
+    }
+
+    pending.0 = None;
+}
+
+/// Keeps the [`ActiveDimension`] up to date, showing the name of each dimension the player enters.
+fn track_dimension(
+    meta: Res<WorldMeta>,
+    origin: Res<WorldOrigin>,
+    mut active: ResMut<ActiveDimension>,
+    mut messages: ResMut<Messages>,
+    q: Query<&GlobalTransform, With<MainCamera>>,
+) {
+    let index = match q.get_single() {
+        Ok(transform) => dimension::index_of(origin.to_local(transform.translation)),
+        Err(_) => return,
+    };
+
+    // Teleports outside of any dimension, like from the console, keep the last one
+    let index = match usize::try_from(index) {
+        Ok(index) if meta.dimensions.name(index).is_some() => index,
+        _ => return,
+    };
+
+    if active.0 != index {
+        active.0 = index;
+        messages.show_title(meta.dimensions.name(index).unwrap_or_default());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn destination() {
+        let dimensions = Dimensions::default();
+        let voxel = IVec3::new(-20, 5, 7);
+
+        // Portals lead to their dimension, and back to the overworld from there
+        let (index, there) = super::destination(&dimensions, "highlands", voxel).unwrap();
+        assert_eq!(index, 1);
+        assert_eq!(there, dimension::move_voxel(voxel, 1));
+
+        assert_eq!(
+            super::destination(&dimensions, "highlands", there),
+            Some((0, voxel))
+        );
+
+        assert_eq!(super::destination(&dimensions, "nowhere", voxel), None);
+    }
+}
@@ -0,0 +1,333 @@
+use bevy::prelude::*;
+use vox::{
+    bounds::Bounds,
+    broadphase::Broadphase,
+    chunk, fluid,
+    pipeline::WorldOrigin,
+    query,
+    voxel::{Kind, KindRegistry},
+    world::VoxWorld,
+};
+
+use crate::{
+    chat::Chat,
+    console::Console,
+    net::EditRequest,
+    selection::{self, VoxelBroken},
+    signs::Signs,
+    MainCamera,
+};
+
+const THROW_KEY: KeyCode = KeyCode::F;
+
+/// Speed projectiles are thrown at, in voxels per second.
+const THROW_SPEED: f32 = 30.0;
+/// In voxels per second squared.
+const GRAVITY: f32 = 20.0;
+/// How fast, in voxels per second, a projectile must be to break a voxel, for each second of its hardness.
+const SPEED_PER_HARDNESS: f32 = 60.0;
+/// How long, in seconds, projectiles last, be them flying or stuck.
+const LIFETIME: f32 = 30.0;
+
+/// Size of the projectile model, which is long on its flying direction.
+const PROJECTILE_WIDTH: f32 = 0.05;
+const PROJECTILE_LENGTH: f32 = 0.6;
+
+/**
+  Sent when a projectile hits an entity with a [`ProjectileTarget`], which decides what happens to itself.
+  The projectile is gone after it.
+*/
+pub struct ProjectileHit {
+    pub target: Entity,
+    /// Velocity the projectile had when it hit, in voxels per second.
+    pub velocity: Vec3,
+}
+
+/**
+  Marks entities which projectiles can hit, as a sphere around their translation. Targets are found through
+  the [`Broadphase`], so they also need a [`crate::physics::Collider`] covering the sphere.
+*/
+#[derive(Component)]
+pub struct ProjectileTarget {
+    pub radius: f32,
+}
+
+/// A thrown projectile. Once it hits a voxel it doesn't break, it sticks into it until the voxel is gone.
+#[derive(Component)]
+struct Projectile {
+    velocity: Vec3,
+    /// World voxel it's stuck into, if any.
+    stuck: Option<IVec3>,
+    age: f32,
+}
+
+/// Mesh and material shared by all projectiles.
+struct ProjectileModel {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for ProjectileModel {
+    fn from_world(world: &mut World) -> Self {
+        let mut meshes = world
+            .get_resource_mut::<Assets<Mesh>>()
+            .expect("PbrPlugin must be added before ProjectilesPlugin");
+        let mesh = meshes.add(Mesh::from(shape::Box::new(
+            PROJECTILE_WIDTH,
+            PROJECTILE_WIDTH,
+            PROJECTILE_LENGTH,
+        )));
+
+        let mut materials = world
+            .get_resource_mut::<Assets<StandardMaterial>>()
+            .expect("PbrPlugin must be added before ProjectilesPlugin");
+        let material = materials.add(Color::rgb(0.6, 0.5, 0.4).into());
+
+        Self { mesh, material }
+    }
+}
+
+pub struct ProjectilesPlugin;
+
+impl Plugin for ProjectilesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ProjectileModel>()
+            .add_event::<ProjectileHit>()
+            .add_system(throw_projectiles)
+            .add_system(move_projectiles);
+    }
+}
+
+/// Whether a projectile flying at the given speed breaks a voxel with the given hardness.
+pub fn breaks(speed: f32, hardness: f32) -> bool {
+    speed > hardness * SPEED_PER_HARDNESS
+}
+
+/**
+  Distance, from `from`, where the segment to `to` first touches the given sphere, if it does at all.
+*/
+pub fn segment_hits_sphere(from: Vec3, to: Vec3, center: Vec3, radius: f32) -> Option<f32> {
+    let offset = to - from;
+    let length_sq = offset.length_squared();
+
+    let t = if length_sq > 0.0 {
+        ((center - from).dot(offset) / length_sq).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+
+    let closest = from + offset * t;
+    let distance = closest.distance(center);
+
+    if distance > radius {
+        return None;
+    }
+
+    // Backs off from the closest point to where the segment enters the sphere
+    let inside = (radius * radius - distance * distance).sqrt();
+    Some((t * length_sq.sqrt() - inside).max(0.0))
+}
+
+fn throw_projectiles(
+    mut commands: Commands,
+    console: Res<Console>,
+    chat: Res<Chat>,
+    signs: Res<Signs>,
+    keyboard: Res<Input<KeyCode>>,
+    model: Res<ProjectileModel>,
+    camera: Query<&GlobalTransform, With<MainCamera>>,
+) {
+    if console.visible || chat.open || signs.is_editing() || !keyboard.just_pressed(THROW_KEY) {
+        return;
+    }
+
+    let transform = match camera.get_single() {
+        Ok(transform) => transform,
+        Err(_) => return,
+    };
+
+    let dir = transform.forward();
+
+    commands
+        .spawn_bundle(PbrBundle {
+            mesh: model.mesh.clone(),
+            material: model.material.clone(),
+            transform: Transform::from_translation(transform.translation)
+                .with_rotation(Quat::from_rotation_arc(Vec3::Z, dir)),
+            ..Default::default()
+        })
+        .insert(Projectile {
+            velocity: dir * THROW_SPEED,
+            stuck: None,
+            age: 0.0,
+        });
+}
+
+/**
+  Whether projectiles hit voxels of the given kind. Props, like tall grass, are flown through, and liquids
+  only slow projectiles down.
+*/
+fn is_solid(registry: &KindRegistry, kind: Kind) -> bool {
+    registry
+        .get(kind)
+        .map(|desc| desc.prop.is_none() && desc.liquid.is_none())
+        .unwrap_or(true)
+}
+
+/**
+  Moves flying projectiles, sweeping the ray between where they are and where they'll be against voxels and
+  targets, so fast projectiles can't fly through thin walls. Projectiles break voxels they are fast enough
+  for, like the player mining them, and stick into the others. Stuck projectiles fall again once the voxel
+  they are stuck into is gone.
+*/
+#[allow(clippy::too_many_arguments)]
+fn move_projectiles(
+    mut commands: Commands,
+    time: Res<Time>,
+    world: Res<VoxWorld>,
+    registry: Res<KindRegistry>,
+    origin: Res<WorldOrigin>,
+    broadphase: Res<Broadphase>,
+    mut edit_writer: EventWriter<EditRequest>,
+    mut broken_writer: EventWriter<VoxelBroken>,
+    mut hit_writer: EventWriter<ProjectileHit>,
+    mut q: Query<(Entity, &mut Transform, &mut Projectile)>,
+    targets: Query<(Entity, &GlobalTransform, &ProjectileTarget)>,
+) {
+    let dt = time.delta_seconds();
+
+    for (entity, mut transform, mut projectile) in q.iter_mut() {
+        projectile.age += dt;
+
+        if projectile.age > LIFETIME {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        if let Some(stuck) = projectile.stuck {
+            match world.get_voxel(stuck) {
+                Some(kind) if !kind.is_empty() => continue,
+                // Unloaded voxels are gone too, so projectiles don't hang in the air when they load again
+                _ => projectile.stuck = None,
+            }
+        }
+
+        let bottom = origin.to_world(transform.translation) - Vec3::Y * PROJECTILE_WIDTH / 2.0;
+        if let Some((depth, liquid)) =
+            fluid::submersion(&world, &registry, bottom, PROJECTILE_WIDTH)
+        {
+            projectile.velocity = fluid::float(projectile.velocity, depth, liquid, GRAVITY, dt);
+        }
+
+        projectile.velocity.y -= GRAVITY * dt;
+
+        let from = transform.translation;
+        let to = from + projectile.velocity * dt;
+
+        let voxel_hit = selection::first_hit(&world, &origin, query::sweep(from, to), |kind| {
+            is_solid(&registry, kind)
+        });
+        let voxel_distance = voxel_hit.map(|(_, position)| position.distance(from));
+
+        let target_hit = broadphase
+            .query(&Bounds::new(from.min(to), from.max(to)))
+            .into_iter()
+            .filter_map(|target| targets.get(target).ok())
+            .filter_map(|(target, target_transform, target_sphere)| {
+                segment_hits_sphere(from, to, target_transform.translation, target_sphere.radius)
+                    .map(|distance| (target, distance))
+            })
+            .filter(|(_, distance)| {
+                voxel_distance
+                    .map(|voxel| *distance < voxel)
+                    .unwrap_or(true)
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b));
+
+        if let Some((target, _)) = target_hit {
+            hit_writer.send(ProjectileHit {
+                target,
+                velocity: projectile.velocity,
+            });
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        let (target, position) = match voxel_hit {
+            Some(hit) => hit,
+            None => {
+                transform.translation = to;
+                transform.rotation =
+                    Quat::from_rotation_arc(Vec3::Z, projectile.velocity.normalize());
+                continue;
+            }
+        };
+
+        // Voxels which need a tool to be mined can't be broken by projectiles either
+        let breakable = registry
+            .get(target.kind)
+            .filter(|desc| desc.tool_tier == 0)
+            .map(|desc| breaks(projectile.velocity.length(), desc.hardness))
+            .unwrap_or_default();
+
+        if breakable {
+            edit_writer.send(EditRequest {
+                chunk: target.chunk,
+                voxel: target.voxel,
+                kind: Kind::default(),
+            });
+            broken_writer.send(VoxelBroken {
+                chunk: target.chunk,
+                voxel: target.voxel,
+                kind: target.kind,
+            });
+            commands.entity(entity).despawn();
+        } else {
+            // The tip goes a bit into the voxel, so it looks stuck into it
+            let dir = projectile.velocity.normalize();
+            transform.translation = position + dir * PROJECTILE_LENGTH * 0.25;
+
+            projectile.velocity = Vec3::ZERO;
+            projectile.stuck = Some(chunk::join_voxel(target.chunk, target.voxel));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn breaks() {
+        assert!(super::breaks(THROW_SPEED, 0.3));
+        assert!(!super::breaks(THROW_SPEED, 1.5));
+        assert!(!super::breaks(0.0, 0.0));
+    }
+
+    #[test]
+    fn segment_hits_sphere() {
+        let from = Vec3::ZERO;
+        let to = Vec3::X * 10.0;
+
+        assert_eq!(
+            super::segment_hits_sphere(from, to, Vec3::X * 5.0, 1.0),
+            Some(4.0)
+        );
+        assert_eq!(
+            super::segment_hits_sphere(from, to, Vec3::new(5.0, 2.0, 0.0), 1.0),
+            None
+        );
+
+        // Spheres past the end of the segment aren't reached yet
+        assert_eq!(
+            super::segment_hits_sphere(from, to, Vec3::X * 12.0, 1.0),
+            None
+        );
+
+        // Segments starting inside the sphere hit it right away
+        assert_eq!(
+            super::segment_hits_sphere(from, from, Vec3::ZERO, 1.0),
+            Some(0.0)
+        );
+    }
+}
@@ -0,0 +1,368 @@
+use bevy::{
+    core_pipeline::node::MAIN_PASS_DRIVER,
+    prelude::*,
+    render::{
+        camera::RenderTarget,
+        render_asset::RenderAssets,
+        render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext},
+        render_resource::{
+            Buffer, BufferDescriptor, BufferUsages, Extent3d, ImageCopyBuffer, ImageDataLayout,
+            MapMode, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+        },
+        renderer::{RenderContext, RenderDevice},
+        texture::BevyDefault,
+        RenderApp, RenderStage,
+    },
+};
+use std::{
+    collections::VecDeque,
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::MainCamera;
+
+const SCREENSHOT_KEY: KeyCode = KeyCode::F2;
+/// Folder screenshots taken by the screenshot key are saved to.
+const SCREENSHOTS_DIR: &str = "screenshots";
+
+const SCREENSHOT_NODE: &str = "screenshot";
+
+/**
+  Saves frames rendered by the main camera to png files. While capturing, the main camera renders to an image
+  instead of the window, since window textures can't be copied from.
+*/
+#[derive(Default)]
+pub struct Screenshots {
+    /// Files waiting for a frame, in the order they were requested.
+    requested: VecDeque<PathBuf>,
+    /// Image the main camera renders to, while there are frames to capture.
+    target: Option<Handle<Image>>,
+    /// File the frame rendered on this frame is saved to.
+    capturing: Option<PathBuf>,
+}
+
+impl Screenshots {
+    /// Saves the next frame rendered to the given png file.
+    pub fn take(&mut self, path: PathBuf) {
+        self.requested.push_back(path);
+    }
+
+    /// Returns true while there are requested frames which weren't rendered yet.
+    pub fn is_pending(&self) -> bool {
+        !self.requested.is_empty()
+    }
+}
+
+pub struct ScreenshotPlugin;
+
+impl Plugin for ScreenshotPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Screenshots>()
+            .add_system(take_screenshot)
+            // After all systems which may request frames or move the camera on this frame
+            .add_system_to_stage(CoreStage::PostUpdate, capture_frames);
+
+        let render_app = app.sub_app_mut(RenderApp);
+        render_app
+            .add_system_to_stage(RenderStage::Extract, extract_screenshot)
+            .add_system_to_stage(RenderStage::Prepare, prepare_screenshot)
+            .add_system_to_stage(RenderStage::Cleanup, save_screenshot);
+
+        let mut graph = render_app.world.resource_mut::<RenderGraph>();
+        graph.add_node(SCREENSHOT_NODE, ScreenshotNode);
+        graph
+            .add_node_edge(MAIN_PASS_DRIVER, SCREENSHOT_NODE)
+            .expect("Main pass driver node must exist");
+    }
+}
+
+fn take_screenshot(keyboard: Res<Input<KeyCode>>, mut screenshots: ResMut<Screenshots>) {
+    if keyboard.just_pressed(SCREENSHOT_KEY) {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis());
+
+        screenshots.take(Path::new(SCREENSHOTS_DIR).join(format!("{}.png", time)));
+    }
+}
+
+fn target_image(width: u32, height: u32) -> Image {
+    let size = Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    };
+
+    let mut image = Image {
+        texture_descriptor: TextureDescriptor {
+            label: Some("screenshot_target"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            // Must match the format of the window, which all pipelines are specialized for
+            format: TextureFormat::bevy_default(),
+            usage: TextureUsages::RENDER_ATTACHMENT
+                | TextureUsages::COPY_SRC
+                | TextureUsages::COPY_DST
+                | TextureUsages::TEXTURE_BINDING,
+        },
+        ..Default::default()
+    };
+
+    image.resize(size);
+    image
+}
+
+/**
+  Switches the main camera between the window and the target image, one frame at a time. A new target image
+  is only rendered to on the next frame, since it may not be on the GPU yet.
+*/
+fn capture_frames(
+    windows: Res<Windows>,
+    mut images: ResMut<Assets<Image>>,
+    mut screenshots: ResMut<Screenshots>,
+    mut q: Query<&mut Camera, With<MainCamera>>,
+) {
+    let (mut camera, window) = match (q.get_single_mut(), windows.get_primary()) {
+        (Ok(camera), Some(window)) => (camera, window),
+        _ => return,
+    };
+
+    screenshots.capturing = None;
+
+    let path = match screenshots.requested.pop_front() {
+        Some(path) => path,
+        None => {
+            if let Some(target) = screenshots.target.take() {
+                camera.target = RenderTarget::Window(window.id());
+                images.remove(target);
+            }
+            return;
+        }
+    };
+
+    let (width, height) = (window.physical_width(), window.physical_height());
+
+    let ready = match screenshots
+        .target
+        .as_ref()
+        .and_then(|target| images.get(target))
+    {
+        Some(image) => {
+            let size = image.texture_descriptor.size;
+            size.width == width && size.height == height
+        }
+        None => false,
+    };
+
+    if ready {
+        screenshots.capturing = Some(path);
+    } else {
+        if let Some(target) = screenshots.target.take() {
+            images.remove(target);
+        }
+
+        let target = images.add(target_image(width, height));
+        camera.target = RenderTarget::Image(target.clone());
+
+        screenshots.target = Some(target);
+        screenshots.requested.push_front(path);
+    }
+}
+
+/// Image to be copied after the main pass and where to save it, on the render world.
+struct ExtractedScreenshot {
+    image: Handle<Image>,
+    path: PathBuf,
+}
+
+fn extract_screenshot(mut commands: Commands, screenshots: Res<Screenshots>) {
+    match (&screenshots.target, &screenshots.capturing) {
+        (Some(image), Some(path)) => commands.insert_resource(ExtractedScreenshot {
+            image: image.clone(),
+            path: path.clone(),
+        }),
+        _ => commands.remove_resource::<ExtractedScreenshot>(),
+    }
+}
+
+/// Buffer the captured image is copied to, so it can be read back.
+struct ScreenshotBuffer {
+    buffer: Buffer,
+    size: Extent3d,
+    /// Rows of copied images must be aligned, so they may be bigger than the image rows.
+    padded_bytes_per_row: usize,
+    format: TextureFormat,
+    path: PathBuf,
+}
+
+fn prepare_screenshot(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    screenshot: Option<Res<ExtractedScreenshot>>,
+    images: Res<RenderAssets<Image>>,
+) {
+    let (screenshot, image) = match screenshot
+        .as_ref()
+        .and_then(|screenshot| Some((screenshot, images.get(&screenshot.image)?)))
+    {
+        Some(found) => found,
+        None => {
+            commands.remove_resource::<ScreenshotBuffer>();
+            return;
+        }
+    };
+
+    let size = Extent3d {
+        width: image.size.width as u32,
+        height: image.size.height as u32,
+        depth_or_array_layers: 1,
+    };
+
+    let bytes_per_row = size.width as usize * image.texture_format.describe().block_size as usize;
+    let padded_bytes_per_row = RenderDevice::align_copy_bytes_per_row(bytes_per_row);
+
+    let buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("screenshot_buffer"),
+        size: (padded_bytes_per_row * size.height as usize) as u64,
+        usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    commands.insert_resource(ScreenshotBuffer {
+        buffer,
+        size,
+        padded_bytes_per_row,
+        format: image.texture_format,
+        path: screenshot.path.clone(),
+    });
+}
+
+/// Copies the image the main camera rendered to into the screenshot buffer.
+struct ScreenshotNode;
+
+impl Node for ScreenshotNode {
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let (screenshot, buffer) = match (
+            world.get_resource::<ExtractedScreenshot>(),
+            world.get_resource::<ScreenshotBuffer>(),
+        ) {
+            (Some(screenshot), Some(buffer)) => (screenshot, buffer),
+            _ => return Ok(()),
+        };
+
+        let images = world.resource::<RenderAssets<Image>>();
+        if let Some(image) = images.get(&screenshot.image) {
+            render_context.command_encoder.copy_texture_to_buffer(
+                image.texture.as_image_copy(),
+                ImageCopyBuffer {
+                    buffer: &buffer.buffer,
+                    layout: ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: NonZeroU32::new(buffer.padded_bytes_per_row as u32),
+                        rows_per_image: None,
+                    },
+                },
+                buffer.size,
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/**
+  Converts rows of a copied image to tightly packed RGBA pixels.
+*/
+pub fn to_rgba(data: &[u8], width: usize, padded_bytes_per_row: usize, bgra: bool) -> Vec<u8> {
+    let mut pixels = Vec::with_capacity(data.len());
+
+    for row in data.chunks(padded_bytes_per_row) {
+        for pixel in row[..width * 4].chunks(4) {
+            if bgra {
+                pixels.extend([pixel[2], pixel[1], pixel[0], pixel[3]]);
+            } else {
+                pixels.extend_from_slice(pixel);
+            }
+        }
+    }
+
+    pixels
+}
+
+/**
+  Reads back the screenshot buffer, once the frame was rendered, and saves it on another thread, since
+  encoding big images takes a while.
+*/
+fn save_screenshot(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    screenshot: Option<Res<ScreenshotBuffer>>,
+) {
+    let screenshot = match screenshot {
+        Some(screenshot) => screenshot,
+        None => return,
+    };
+
+    let slice = screenshot.buffer.slice(..);
+    render_device.map_buffer(&slice, MapMode::Read);
+
+    let bgra = matches!(
+        screenshot.format,
+        TextureFormat::Bgra8UnormSrgb | TextureFormat::Bgra8Unorm
+    );
+    let pixels = to_rgba(
+        &slice.get_mapped_range(),
+        screenshot.size.width as usize,
+        screenshot.padded_bytes_per_row,
+        bgra,
+    );
+    screenshot.buffer.unmap();
+
+    let (width, height) = (screenshot.size.width, screenshot.size.height);
+    let path = screenshot.path.clone();
+    commands.remove_resource::<ScreenshotBuffer>();
+
+    std::thread::spawn(move || {
+        if let Some(dir) = path.parent() {
+            if let Err(err) = std::fs::create_dir_all(dir) {
+                error!("Unable to create dir {}: {}", dir.display(), err);
+                return;
+            }
+        }
+
+        match image::save_buffer(&path, &pixels, width, height, image::ColorType::Rgba8) {
+            Ok(()) => info!("Screenshot saved to {}", path.display()),
+            Err(err) => error!("Failed to save screenshot {}: {}", path.display(), err),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn to_rgba() {
+        // Two pixels wide, with rows padded to 12 bytes
+        let data = [
+            1, 2, 3, 4, 5, 6, 7, 8, 0, 0, 0, 0, //
+            9, 10, 11, 12, 13, 14, 15, 16, 0, 0, 0, 0,
+        ];
+
+        assert_eq!(
+            super::to_rgba(&data, 2, 12, false),
+            vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16]
+        );
+        assert_eq!(
+            super::to_rgba(&data, 2, 12, true),
+            vec![3, 2, 1, 4, 7, 6, 5, 8, 11, 10, 9, 12, 15, 14, 13, 16]
+        );
+    }
+}
@@ -0,0 +1,609 @@
+use bevy::{input::mouse::MouseWheel, prelude::*};
+use vox::{
+    block_entity::{BlockEntityData, Container, Sign},
+    chunk,
+    item::{self, ToolRegistry},
+    pipeline::{LightFuse, WorldOrigin},
+    query::{self, RaycastHit},
+    voxel::{Kind, KindRegistry, Side, SIDES},
+    world::VoxWorld,
+};
+
+use crate::{
+    builder::Builder,
+    chat::Chat,
+    console::Console,
+    containers::{Containers, UseContainer},
+    crafting::Crafting,
+    net::EditRequest,
+    signs::{EditSign, Signs},
+    MainCamera,
+};
+
+/// Max distance, in voxels, which the player can reach.
+const REACH: f32 = 8.0;
+
+const INTERACT_KEY: KeyCode = KeyCode::E;
+/// Cycles through the tools, and bare hands.
+const TOOL_KEY: KeyCode = KeyCode::Q;
+
+const KIND_KEYS: [KeyCode; 9] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TargetVoxel {
+    pub chunk: IVec3,
+    pub voxel: IVec3,
+    /// Normal of the face hit by the raycast, pointing outwards the voxel.
+    pub normal: IVec3,
+    pub kind: Kind,
+}
+
+/// The non-empty voxel the main camera is looking at, if any is within reach.
+#[derive(Default)]
+pub struct VoxelTarget(pub Option<TargetVoxel>);
+
+/// The kind which will be used when placing voxels.
+pub struct SelectedKind(pub Kind);
+
+impl Default for SelectedKind {
+    fn default() -> Self {
+        Self(1.into())
+    }
+}
+
+/// Sent when the player places a voxel, with the kind placed.
+pub struct VoxelPlaced(pub Kind);
+
+/// Sent when the player breaks a voxel, with the kind it had.
+pub struct VoxelBroken {
+    pub chunk: IVec3,
+    pub voxel: IVec3,
+    pub kind: Kind,
+}
+
+/// Tool held by the player, as an index on the [`ToolRegistry`], or none for bare hands.
+#[derive(Default)]
+pub struct HeldTool(pub Option<usize>);
+
+/// How much of the targeted voxel was broken, from 0 to 1. It's reset whenever the target changes.
+#[derive(Default)]
+pub struct Mining {
+    target: Option<(IVec3, IVec3)>,
+    pub progress: f32,
+}
+
+impl Mining {
+    fn reset(&mut self) {
+        self.target = None;
+        self.progress = 0.0;
+    }
+}
+
+pub struct SelectionPlugin;
+
+impl Plugin for SelectionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<VoxelTarget>()
+            .init_resource::<SelectedKind>()
+            .init_resource::<HeldTool>()
+            .init_resource::<Mining>()
+            .add_event::<VoxelPlaced>()
+            .add_event::<VoxelBroken>()
+            .add_system(update_target)
+            .add_system(select_kind)
+            .add_system(select_tool)
+            .add_system(place_voxel.after(update_target))
+            .add_system(mine_voxel.after(update_target))
+            .add_system(interact.after(update_target));
+    }
+}
+
+/**
+  Finds the first non-empty voxel hit by the given ray. The ray `start` is in render space,
+  so the raycast doesn't lose precision far away from the world origin. The ray is swept, so voxels whose
+  edges are barely crossed are still found.
+*/
+pub fn find_target(
+    world: &VoxWorld,
+    origin: &WorldOrigin,
+    start: Vec3,
+    dir: Vec3,
+    range: f32,
+) -> Option<TargetVoxel> {
+    first_hit(
+        world,
+        origin,
+        query::sweep(start, start + dir * range),
+        |_| true,
+    )
+    .map(|(target, _)| target)
+}
+
+/**
+  Finds the first non-empty voxel among the given render space raycast hits, which `hits` returns true for.
+  Returns it with the render space position where the ray entered it.
+*/
+pub fn first_hit(
+    world: &VoxWorld,
+    origin: &WorldOrigin,
+    raycast: Vec<(RaycastHit, Vec<RaycastHit>)>,
+    hits: impl Fn(Kind) -> bool,
+) -> Option<(TargetVoxel, Vec3)> {
+    for (chunk_hit, voxels_hit) in raycast {
+        let local = chunk_hit.local + origin.0;
+
+        let chunk = match world.get(local) {
+            Some(chunk) => chunk,
+            None => continue,
+        };
+
+        for voxel_hit in voxels_hit {
+            let kind = chunk.get(voxel_hit.local);
+
+            if kind.is_empty() || !hits(kind) {
+                continue;
+            }
+
+            // The first voxel of each chunk has no normal, since the ray just entered the chunk
+            let normal = if voxel_hit.normal == IVec3::ZERO {
+                chunk_hit.normal
+            } else {
+                voxel_hit.normal
+            };
+
+            let target = TargetVoxel {
+                chunk: local,
+                voxel: voxel_hit.local,
+                normal,
+                kind,
+            };
+
+            return Some((target, voxel_hit.position));
+        }
+    }
+
+    None
+}
+
+fn update_target(
+    world: Res<VoxWorld>,
+    origin: Res<WorldOrigin>,
+    mut target: ResMut<VoxelTarget>,
+    camera: Query<&GlobalTransform, With<MainCamera>>,
+) {
+    let new_target = camera.get_single().ok().and_then(|transform| {
+        find_target(
+            &world,
+            &origin,
+            transform.translation,
+            transform.forward(),
+            REACH,
+        )
+    });
+
+    // Avoid triggering change detection when nothing has changed
+    if target.0 != new_target {
+        target.0 = new_target;
+    }
+}
+
+/**
+  Returns the chunk and voxel where a new voxel is placed, when placing against the given target.
+  It's the voxel next to the hit face, which may be on a neighbor chunk.
+*/
+pub fn placement(target: &TargetVoxel) -> (IVec3, IVec3) {
+    let (dir, voxel) = chunk::overlap_voxel(target.voxel + target.normal);
+    (target.chunk + dir, voxel)
+}
+
+/**
+  Facing of a climbable kind placed against the given target, so it leans on the targeted face. Climbable
+  kinds, like ladders, only hold on the sides of full cubes.
+*/
+pub fn climbable_facing(registry: &KindRegistry, target: &TargetVoxel) -> Option<Side> {
+    if target.normal.y != 0 || !registry.is_opaque(target.kind) {
+        return None;
+    }
+
+    SIDES.into_iter().find(|side| side.dir() == target.normal)
+}
+
+/**
+  Places the selected kind next to the targeted face. Directional kinds faces back to the camera, while
+  climbable ones lean on the targeted face, and aren't placed where there's nothing to lean on.
+*/
+#[allow(clippy::too_many_arguments)]
+fn place_voxel(
+    crafting: Res<Crafting>,
+    containers: Res<Containers>,
+    signs: Res<Signs>,
+    registry: Res<KindRegistry>,
+    target: Res<VoxelTarget>,
+    selected: Res<SelectedKind>,
+    mouse: Res<Input<MouseButton>>,
+    camera: Query<&GlobalTransform, With<MainCamera>>,
+    mut writer: EventWriter<EditRequest>,
+    mut placed_writer: EventWriter<VoxelPlaced>,
+    mut sign_writer: EventWriter<EditSign>,
+) {
+    if crafting.open
+        || containers.is_open()
+        || signs.is_editing()
+        || !mouse.just_pressed(MouseButton::Right)
+    {
+        return;
+    }
+
+    let (target, transform) = match (target.0, camera.get_single()) {
+        (Some(target), Ok(transform)) => (target, transform),
+        _ => return,
+    };
+
+    let kind = if registry.is_climbable(selected.0) {
+        match climbable_facing(&registry, &target) {
+            Some(facing) => selected.0.with_facing(facing),
+            None => return,
+        }
+    } else if registry.is_directional(selected.0) {
+        selected
+            .0
+            .with_facing(Side::facing_towards(transform.forward()))
+    } else {
+        selected.0
+    };
+
+    let (chunk, voxel) = placement(&target);
+    writer.send(EditRequest { chunk, voxel, kind });
+    placed_writer.send(VoxelPlaced(kind));
+
+    // Signs are written right after being placed
+    if registry.block_entity(kind) == Some(Sign::NAME) {
+        sign_writer.send(EditSign(chunk, voxel));
+    }
+}
+
+/**
+  Removes the targeted voxel once the left button was held long enough for its hardness and the held tool.
+  Kinds without hardness are removed on click. Left clicks are used by the selection tools while on builder
+  mode, and to pick items while crafting or using a container.
+*/
+#[allow(clippy::too_many_arguments)]
+fn mine_voxel(
+    time: Res<Time>,
+    builder: Res<Builder>,
+    crafting: Res<Crafting>,
+    containers: Res<Containers>,
+    signs: Res<Signs>,
+    registry: Res<KindRegistry>,
+    tools: Res<ToolRegistry>,
+    held: Res<HeldTool>,
+    target: Res<VoxelTarget>,
+    mouse: Res<Input<MouseButton>>,
+    mut mining: ResMut<Mining>,
+    mut writer: EventWriter<EditRequest>,
+    mut broken_writer: EventWriter<VoxelBroken>,
+) {
+    let target = match target.0 {
+        Some(target)
+            if !builder.active
+                && !crafting.open
+                && !containers.is_open()
+                && !signs.is_editing()
+                && mouse.pressed(MouseButton::Left) =>
+        {
+            target
+        }
+        _ => {
+            // Avoid triggering change detection when nothing is being mined
+            if mining.target.is_some() {
+                mining.reset();
+            }
+            return;
+        }
+    };
+
+    let tool = held.0.and_then(|idx| tools.get(idx));
+    let duration = match registry
+        .get(target.kind)
+        .and_then(|desc| item::mining_time(desc, tool))
+    {
+        Some(duration) => duration,
+        None => return,
+    };
+
+    let broken = if duration <= 0.0 {
+        mouse.just_pressed(MouseButton::Left)
+    } else {
+        if mining.target != Some((target.chunk, target.voxel)) {
+            mining.target = Some((target.chunk, target.voxel));
+            mining.progress = 0.0;
+        }
+
+        mining.progress += time.delta_seconds() / duration;
+        mining.progress >= 1.0
+    };
+
+    if broken {
+        mining.reset();
+
+        writer.send(EditRequest {
+            chunk: target.chunk,
+            voxel: target.voxel,
+            kind: Kind::default(),
+        });
+
+        broken_writer.send(VoxelBroken {
+            chunk: target.chunk,
+            voxel: target.voxel,
+            kind: target.kind,
+        });
+    }
+}
+
+/**
+  Toggles the targeted voxel to its other state, like opening or closing a door, opens the targeted
+  container, edits the targeted sign or lights the fuse of the targeted explosive. Interacting again while a container is open closes it.
+*/
+#[allow(clippy::too_many_arguments)]
+fn interact(
+    console: Res<Console>,
+    chat: Res<Chat>,
+    signs: Res<Signs>,
+    registry: Res<KindRegistry>,
+    containers: Res<Containers>,
+    target: Res<VoxelTarget>,
+    keyboard: Res<Input<KeyCode>>,
+    mut writer: EventWriter<EditRequest>,
+    mut container_writer: EventWriter<UseContainer>,
+    mut sign_writer: EventWriter<EditSign>,
+    mut fuse_writer: EventWriter<LightFuse>,
+) {
+    if console.visible || chat.open || signs.is_editing() || !keyboard.just_pressed(INTERACT_KEY) {
+        return;
+    }
+
+    if containers.is_open() {
+        container_writer.send(UseContainer(None));
+        return;
+    }
+
+    let target = match target.0 {
+        Some(target) => target,
+        None => return,
+    };
+
+    if registry.block_entity(target.kind) == Some(Container::NAME) {
+        container_writer.send(UseContainer(Some((target.chunk, target.voxel))));
+    } else if registry.block_entity(target.kind) == Some(Sign::NAME) {
+        sign_writer.send(EditSign(target.chunk, target.voxel));
+    } else if registry.explosive(target.kind).is_some() {
+        fuse_writer.send(LightFuse {
+            chunk: target.chunk,
+            voxel: target.voxel,
+        });
+    } else if let Some(kind) = registry.toggle(target.kind) {
+        writer.send(EditRequest {
+            chunk: target.chunk,
+            voxel: target.voxel,
+            kind,
+        });
+    }
+}
+
+fn select_tool(
+    console: Res<Console>,
+    chat: Res<Chat>,
+    signs: Res<Signs>,
+    tools: Res<ToolRegistry>,
+    keyboard: Res<Input<KeyCode>>,
+    mut held: ResMut<HeldTool>,
+) {
+    if console.visible || chat.open || signs.is_editing() || !keyboard.just_pressed(TOOL_KEY) {
+        return;
+    }
+
+    held.0 = match held.0 {
+        None if !tools.is_empty() => Some(0),
+        Some(idx) if idx + 1 < tools.len() => Some(idx + 1),
+        _ => None,
+    };
+}
+
+fn select_kind(
+    console: Res<Console>,
+    chat: Res<Chat>,
+    signs: Res<Signs>,
+    registry: Res<KindRegistry>,
+    keyboard: Res<Input<KeyCode>>,
+    mut wheel_reader: EventReader<MouseWheel>,
+    mut selected: ResMut<SelectedKind>,
+) {
+    // Number keys are used to type console commands, chat lines and signs
+    if console.visible || chat.open || signs.is_editing() {
+        return;
+    }
+
+    for (idx, key) in KIND_KEYS.iter().enumerate() {
+        let kind = (idx as u16 + 1).into();
+        if keyboard.just_pressed(*key) && registry.get(kind).is_some() {
+            selected.0 = kind;
+        }
+    }
+
+    let scroll = wheel_reader.iter().map(|evt| evt.y).sum::<f32>();
+    if scroll.abs() < f32::EPSILON {
+        return;
+    }
+
+    let mut kinds = registry
+        .kinds()
+        .filter(|kind| !kind.is_empty())
+        .collect::<Vec<_>>();
+
+    if kinds.is_empty() {
+        return;
+    }
+
+    kinds.sort_by_key(|kind| kind.id());
+
+    let current = kinds.iter().position(|k| *k == selected.0).unwrap_or(0);
+    let next = if scroll > 0.0 {
+        (current + 1) % kinds.len()
+    } else {
+        (current + kinds.len() - 1) % kinds.len()
+    };
+
+    selected.0 = kinds[next];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vox::chunk::ChunkKind;
+
+    #[test]
+    fn find_target() {
+        let world = VoxWorld::default();
+        let origin = WorldOrigin::default();
+        let mut kind = ChunkKind::default();
+        kind.set((5, 5, 5).into(), 2.into());
+        world.add(IVec3::ZERO, kind);
+
+        let target =
+            super::find_target(&world, &origin, Vec3::new(5.5, 10.5, 5.5), -Vec3::Y, REACH)
+                .expect("Voxel should be targeted");
+
+        assert_eq!(target.chunk, IVec3::ZERO);
+        assert_eq!(target.voxel, (5, 5, 5).into());
+        assert_eq!(target.normal, IVec3::Y);
+        assert_eq!(target.kind, 2.into());
+
+        // Looking to the opposite direction
+        assert!(
+            super::find_target(&world, &origin, Vec3::new(5.5, 10.5, 5.5), Vec3::Y, REACH)
+                .is_none()
+        );
+
+        // Out of reach
+        assert!(
+            super::find_target(&world, &origin, Vec3::new(5.5, 15.5, 5.5), -Vec3::Y, 5.0).is_none()
+        );
+    }
+
+    #[test]
+    fn find_target_across_chunks() {
+        let world = VoxWorld::default();
+        let origin = WorldOrigin::default();
+        world.add(IVec3::ZERO, ChunkKind::default());
+
+        let mut kind = ChunkKind::default();
+        kind.set((5, 15, 5).into(), 1.into());
+        world.add((0, -1, 0).into(), kind);
+
+        let target = super::find_target(&world, &origin, Vec3::new(5.5, 3.5, 5.5), -Vec3::Y, REACH)
+            .expect("Voxel should be targeted");
+
+        assert_eq!(target.chunk, (0, -1, 0).into());
+        assert_eq!(target.voxel, (5, 15, 5).into());
+        assert_eq!(target.normal, IVec3::Y);
+    }
+
+    #[test]
+    fn placement() {
+        let target = TargetVoxel {
+            chunk: (1, 0, -1).into(),
+            voxel: (5, 5, 5).into(),
+            normal: IVec3::Y,
+            kind: 1.into(),
+        };
+
+        assert_eq!(
+            super::placement(&target),
+            ((1, 0, -1).into(), (5, 6, 5).into())
+        );
+
+        // Placing against a face on the chunk border goes to the neighbor chunk
+        let target = TargetVoxel {
+            voxel: (0, 5, 5).into(),
+            normal: -IVec3::X,
+            ..target
+        };
+
+        assert_eq!(
+            super::placement(&target),
+            ((0, 0, -1).into(), (chunk::AXIS_ENDING as i32, 5, 5).into())
+        );
+    }
+
+    #[test]
+    fn climbable_facing() {
+        let registry = KindRegistry::load(std::path::Path::new(crate::KIND_DESCRIPTIONS_PATH));
+        let log = TargetVoxel {
+            chunk: IVec3::ZERO,
+            voxel: (5, 5, 5).into(),
+            normal: IVec3::X,
+            kind: 9.into(),
+        };
+
+        assert_eq!(super::climbable_facing(&registry, &log), Some(Side::Right));
+        assert_eq!(
+            super::climbable_facing(
+                &registry,
+                &TargetVoxel {
+                    normal: -IVec3::Z,
+                    ..log
+                }
+            ),
+            Some(Side::Back)
+        );
+
+        // Nothing to lean on over the top of voxels, nor on thin shapes
+        assert_eq!(
+            super::climbable_facing(
+                &registry,
+                &TargetVoxel {
+                    normal: IVec3::Y,
+                    ..log
+                }
+            ),
+            None
+        );
+        assert_eq!(
+            super::climbable_facing(
+                &registry,
+                &TargetVoxel {
+                    kind: 18.into(),
+                    ..log
+                }
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn find_target_far_from_origin() {
+        let world = VoxWorld::default();
+        let origin = WorldOrigin((1_000_000, 0, -1_000_000).into());
+
+        let mut kind = ChunkKind::default();
+        kind.set((5, 5, 5).into(), 2.into());
+        world.add(origin.0, kind);
+
+        let target =
+            super::find_target(&world, &origin, Vec3::new(5.5, 10.5, 5.5), -Vec3::Y, REACH)
+                .expect("Voxel should be targeted");
+
+        assert_eq!(target.chunk, origin.0);
+        assert_eq!(target.voxel, (5, 5, 5).into());
+    }
+}
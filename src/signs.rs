@@ -0,0 +1,447 @@
+use ab_glyph::{point, Font, FontVec, PxScale, ScaleFont};
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+    utils::HashMap,
+    window::ReceivedCharacter,
+};
+use std::path::Path;
+use vox::{
+    block_entity::{BlockEntities, BlockEntityData, Sign},
+    bounds, chunk,
+    voxel::{KindRegistry, Side},
+    world::VoxWorld,
+};
+use vox_render::entities::{ChunkEntity, ChunkEntityMap};
+
+use crate::{chat::Chat, console::Console, ASSETS_DIR, FONT_PATH};
+
+const FONT_SIZE: f32 = 16.0;
+
+/// Size, in pixels, of the texture each sign is drawn to.
+const TEXTURE_SIZE: usize = 256;
+/// Font size, in pixels, of the text drawn to sign textures.
+const TEXT_SCALE: f32 = 28.0;
+const LINE_HEIGHT: f32 = 36.0;
+const TEXT_COLOR: [u8; 3] = [25, 20, 15];
+
+const MAX_LINES: usize = 4;
+const MAX_LINE_CHARS: usize = 14;
+const MAX_TEXT_LEN: usize = MAX_LINES * MAX_LINE_CHARS;
+
+/// How far from the voxel face the text is drawn, so it doesn't fight with the chunk mesh.
+const FACE_OFFSET: f32 = 0.01;
+
+/**
+  Send this to start editing the text of the sign at the given chunk and voxel.
+*/
+pub struct EditSign(pub IVec3, pub IVec3);
+
+/**
+  Sign editing state, and the text quads drawn on loaded signs. While editing, typed characters go to the
+  sign, until `Return` confirms or `Escape` discards them.
+*/
+#[derive(Default)]
+pub struct Signs {
+    editing: Option<(IVec3, IVec3)>,
+    input: String,
+    quads: HashMap<(IVec3, IVec3), SignQuad>,
+}
+
+impl Signs {
+    pub fn is_editing(&self) -> bool {
+        self.editing.is_some()
+    }
+}
+
+/// Text quad drawn on a sign, and what it was drawn for, so it's only redrawn when the sign changes.
+struct SignQuad {
+    entity: Entity,
+    text: String,
+    facing: Side,
+}
+
+#[derive(Component)]
+struct SignTextQuad;
+
+#[derive(Component)]
+struct SignInputText;
+
+/// Font signs are drawn with, and the quad mesh shared by them.
+struct SignAssets {
+    font: FontVec,
+    mesh: Handle<Mesh>,
+}
+
+impl FromWorld for SignAssets {
+    fn from_world(world: &mut World) -> Self {
+        // Read directly, since it's needed before the asset server loads it
+        let path = Path::new(ASSETS_DIR).join(FONT_PATH);
+        let data = std::fs::read(&path)
+            .unwrap_or_else(|_| panic!("Unable to read font file {}", path.display()));
+        let font = FontVec::try_from_vec(data)
+            .unwrap_or_else(|_| panic!("Failed to parse font file {}", path.display()));
+
+        let mut meshes = world
+            .get_resource_mut::<Assets<Mesh>>()
+            .expect("PbrPlugin must be added before SignsPlugin");
+        let mesh = meshes.add(Mesh::from(shape::Quad::new(Vec2::ONE)));
+
+        Self { font, mesh }
+    }
+}
+
+pub struct SignsPlugin;
+
+impl Plugin for SignsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Signs>()
+            .init_resource::<SignAssets>()
+            .add_event::<EditSign>()
+            .add_startup_system(setup_signs)
+            .add_system(edit_signs)
+            .add_system(update_input_text.after(edit_signs))
+            // Chunk entities are spawned and despawned on update, so quads are only attached after it
+            .add_system_to_stage(CoreStage::PostUpdate, draw_signs);
+    }
+}
+
+/**
+  Splits the sign text into the lines drawn on it, breaking on spaces when possible. Text which doesn't fit
+  on the sign is cut.
+*/
+pub fn wrap(text: &str) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+
+    for word in text.split_whitespace() {
+        let mut word = word.chars().collect::<Vec<_>>();
+
+        if !line.is_empty() && line.chars().count() + 1 + word.len() > MAX_LINE_CHARS {
+            lines.push(std::mem::take(&mut line));
+        }
+
+        // Words longer than a line are broken anywhere
+        while word.len() > MAX_LINE_CHARS {
+            let rest = word.split_off(MAX_LINE_CHARS);
+            lines.push(word.into_iter().collect());
+            word = rest;
+        }
+
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.extend(word);
+    }
+
+    if !line.is_empty() {
+        lines.push(line);
+    }
+
+    lines.truncate(MAX_LINES);
+    lines
+}
+
+/**
+  Draws the given lines, centered, to a square RGBA texture of `TEXTURE_SIZE` pixels. Pixels without text
+  are transparent.
+*/
+pub fn rasterize(font: &impl Font, lines: &[String]) -> Vec<u8> {
+    let mut data = vec![0; TEXTURE_SIZE * TEXTURE_SIZE * 4];
+    let font = font.as_scaled(PxScale::from(TEXT_SCALE));
+
+    let top = (TEXTURE_SIZE as f32 - LINE_HEIGHT * lines.len() as f32) / 2.0;
+
+    for (row, line) in lines.iter().enumerate() {
+        let width = line
+            .chars()
+            .map(|c| font.h_advance(font.glyph_id(c)))
+            .sum::<f32>();
+
+        let mut x = (TEXTURE_SIZE as f32 - width) / 2.0;
+        let y = top + LINE_HEIGHT * row as f32 + font.ascent();
+
+        for c in line.chars() {
+            let mut glyph = font.scaled_glyph(c);
+            glyph.position = point(x, y);
+            x += font.h_advance(glyph.id);
+
+            let outlined = match font.outline_glyph(glyph) {
+                Some(outlined) => outlined,
+                None => continue,
+            };
+
+            let bounds = outlined.px_bounds();
+
+            outlined.draw(|gx, gy, coverage| {
+                let px = bounds.min.x as i32 + gx as i32;
+                let py = bounds.min.y as i32 + gy as i32;
+
+                if px < 0 || py < 0 || px >= TEXTURE_SIZE as i32 || py >= TEXTURE_SIZE as i32 {
+                    return;
+                }
+
+                let idx = (py as usize * TEXTURE_SIZE + px as usize) * 4;
+                let alpha = (coverage.clamp(0.0, 1.0) * 255.0) as u8;
+
+                data[idx..idx + 3].copy_from_slice(&TEXT_COLOR);
+                data[idx + 3] = data[idx + 3].max(alpha);
+            });
+        }
+    }
+
+    data
+}
+
+fn setup_signs(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(10.0),
+                    bottom: Val::Px(40.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                String::default(),
+                TextStyle {
+                    font: asset_server.load(FONT_PATH),
+                    font_size: FONT_SIZE,
+                    color: Color::YELLOW,
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(SignInputText);
+}
+
+/**
+  Types the sign text. The text is only written once confirmed, and only if the voxel is still a sign, since
+  it could've been broken meanwhile.
+*/
+#[allow(clippy::too_many_arguments)]
+fn edit_signs(
+    console: Res<Console>,
+    chat: Res<Chat>,
+    keyboard: Res<Input<KeyCode>>,
+    world: Res<VoxWorld>,
+    registry: Res<KindRegistry>,
+    mut block_entities: ResMut<BlockEntities>,
+    mut signs: ResMut<Signs>,
+    mut char_reader: EventReader<ReceivedCharacter>,
+    mut reader: EventReader<EditSign>,
+) {
+    // Characters must always be consumed, so the key which starts editing isn't typed into the sign
+    let chars = char_reader.iter().map(|evt| evt.char).collect::<Vec<_>>();
+
+    if let Some((chunk, voxel)) = signs.editing {
+        for c in chars {
+            if !c.is_control() && signs.input.chars().count() < MAX_TEXT_LEN {
+                signs.input.push(c);
+            }
+        }
+
+        if keyboard.just_pressed(KeyCode::Back) {
+            signs.input.pop();
+        }
+
+        if keyboard.just_pressed(KeyCode::Escape) {
+            signs.editing = None;
+            signs.input.clear();
+        } else if keyboard.just_pressed(KeyCode::Return) {
+            let input = std::mem::take(&mut signs.input);
+            signs.editing = None;
+
+            let kind = world.get_voxel(chunk::join_voxel(chunk, voxel));
+            if kind.and_then(|kind| registry.block_entity(kind)) == Some(Sign::NAME) {
+                block_entities.sync(chunk, voxel, Some(Sign::NAME));
+
+                if let Some(sign) = block_entities.get_as_mut::<Sign>(chunk, voxel) {
+                    sign.text = input.trim().to_string();
+                }
+            }
+        }
+    }
+
+    for EditSign(chunk, voxel) in reader.iter() {
+        if console.visible || chat.open {
+            continue;
+        }
+
+        signs.editing = Some((*chunk, *voxel));
+        signs.input = block_entities
+            .get_as::<Sign>(*chunk, *voxel)
+            .map(|sign| sign.text.clone())
+            .unwrap_or_default();
+    }
+}
+
+fn update_input_text(signs: Res<Signs>, mut q: Query<&mut Text, With<SignInputText>>) {
+    if !signs.is_changed() {
+        return;
+    }
+
+    for mut text in q.iter_mut() {
+        text.sections[0].value = if signs.is_editing() {
+            format!("sign: {}_", signs.input)
+        } else {
+            String::default()
+        };
+    }
+}
+
+/**
+  Keeps a text quad on each sign with text, attached to its chunk entity. Quads are drawn again when the
+  sign changes, and when they were despawned along with their chunk entity, like when it's remeshed.
+*/
+#[allow(clippy::too_many_arguments)]
+fn draw_signs(
+    mut commands: Commands,
+    world: Res<VoxWorld>,
+    block_entities: Res<BlockEntities>,
+    entity_map: Res<ChunkEntityMap>,
+    assets: Res<SignAssets>,
+    mut signs: ResMut<Signs>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    quads: Query<(), With<SignTextQuad>>,
+    chunks: Query<(), With<ChunkEntity>>,
+) {
+    let texts = block_entities
+        .iter()
+        .filter_map(|(local, voxel, entity)| {
+            let sign = entity.as_any().downcast_ref::<Sign>()?;
+            let kind = world.get_voxel(chunk::join_voxel(local, voxel))?;
+
+            if sign.text.is_empty() {
+                None
+            } else {
+                Some(((local, voxel), (sign.text.as_str(), kind.facing())))
+            }
+        })
+        .collect::<HashMap<_, _>>();
+
+    signs.quads.retain(|key, quad| {
+        let alive = quads.get(quad.entity).is_ok();
+        let current = matches!(
+            texts.get(key),
+            Some((text, facing)) if *text == quad.text && *facing == quad.facing
+        );
+
+        if alive && !current {
+            commands.entity(quad.entity).despawn_recursive();
+        }
+
+        alive && current
+    });
+
+    for (&(local, voxel), &(text, facing)) in texts.iter() {
+        if signs.quads.contains_key(&(local, voxel)) {
+            continue;
+        }
+
+        let chunk_entity = match entity_map.0.get(&local) {
+            Some(&entity) if chunks.get(entity).is_ok() => entity,
+            _ => continue,
+        };
+
+        let image = images.add(Image::new(
+            Extent3d {
+                width: TEXTURE_SIZE as u32,
+                height: TEXTURE_SIZE as u32,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            rasterize(&assets.font, &wrap(text)),
+            TextureFormat::Rgba8UnormSrgb,
+        ));
+
+        let material = materials.add(StandardMaterial {
+            base_color_texture: Some(image),
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..Default::default()
+        });
+
+        let center = bounds::voxel(voxel).center();
+        let transform = Transform {
+            translation: center + facing.normal() * (0.5 + FACE_OFFSET),
+            rotation: Side::facing_rotation(facing),
+            ..Default::default()
+        };
+
+        let mut entity = None;
+        commands.entity(chunk_entity).with_children(|parent| {
+            entity = Some(
+                parent
+                    .spawn_bundle(PbrBundle {
+                        mesh: assets.mesh.clone(),
+                        material,
+                        transform,
+                        ..Default::default()
+                    })
+                    .insert(SignTextQuad)
+                    .id(),
+            );
+        });
+
+        if let Some(entity) = entity {
+            signs.quads.insert(
+                (local, voxel),
+                SignQuad {
+                    entity,
+                    text: text.to_string(),
+                    facing,
+                },
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrap() {
+        assert!(super::wrap("  ").is_empty());
+        assert_eq!(super::wrap("Hello  world"), vec!["Hello world"]);
+        assert_eq!(
+            super::wrap("Welcome to the village"),
+            vec!["Welcome to the", "village"]
+        );
+        assert_eq!(
+            super::wrap("abcdefghijklmnopqrstuvwxyz"),
+            vec!["abcdefghijklmn", "opqrstuvwxyz"]
+        );
+
+        // Lines which don't fit are cut
+        assert_eq!(
+            super::wrap("a b c d e f g h i j k l m n o p q r s t u v w x y z 0 1 2 3 4 5 6 7 8 9")
+                .len(),
+            MAX_LINES
+        );
+    }
+
+    #[test]
+    fn rasterize() {
+        let data = std::fs::read(Path::new(ASSETS_DIR).join(FONT_PATH)).unwrap();
+        let font = FontVec::try_from_vec(data).unwrap();
+
+        let empty = super::rasterize(&font, &[]);
+        assert_eq!(empty.len(), TEXTURE_SIZE * TEXTURE_SIZE * 4);
+        assert!(empty.iter().all(|byte| *byte == 0));
+
+        let text = super::rasterize(&font, &["Hi".to_string()]);
+        let alphas = text.chunks(4).map(|pixel| pixel[3]).collect::<Vec<_>>();
+        assert!(alphas.iter().any(|alpha| *alpha > 0));
+
+        // Text is centered, so nothing is drawn on the texture borders
+        assert!(alphas[..TEXTURE_SIZE].iter().all(|alpha| *alpha == 0));
+        assert!(alphas.iter().step_by(TEXTURE_SIZE).all(|alpha| *alpha == 0));
+    }
+}
@@ -0,0 +1,230 @@
+use bevy::prelude::*;
+use vox::pipeline::StreamingAnchor;
+
+use crate::{
+    chat::Chat,
+    console::Console,
+    gamepad::GamepadInput,
+    player::{BODY_EYES, BODY_HEIGHT, BODY_WIDTH},
+    signs::Signs,
+    sprint::{CharacterConfig, Sprint},
+    MainCamera,
+};
+
+const UP_KEY: KeyCode = KeyCode::Space;
+const DOWN_KEY: KeyCode = KeyCode::C;
+const SLOW_KEY: KeyCode = KeyCode::LAlt;
+
+/// Flying speed, in voxels per second.
+const SPEED: f32 = 12.0;
+const SLOW_MULTIPLIER: f32 = 0.2;
+/// Radius, in chunks, kept loaded around the body, so the world around the player keeps being simulated.
+const BODY_STREAMING_RADIUS: u32 = 2;
+
+/// Send this to enter or leave spectator mode.
+pub struct ToggleSpectator;
+
+/**
+  Spectator mode state. While spectating, the camera flies freely, without colliding with anything, while the
+  player body stays where it was left. Chunks are streamed around the camera, so they keep loading wherever it
+  flies to, while the chunks around the body are kept loaded. Leaving spectator mode puts the camera back in
+  the body.
+*/
+#[derive(Default)]
+pub struct Spectator {
+    /// Body left behind by the player, while spectating.
+    body: Option<Entity>,
+}
+
+impl Spectator {
+    pub fn is_active(&self) -> bool {
+        self.body.is_some()
+    }
+}
+
+/// Marks the player body, which stays in place while the camera is detached from it.
+#[derive(Component)]
+struct PlayerBody {
+    /// Where the camera was when it left the body, relative to the body center.
+    eyes: Transform,
+}
+
+/// Mesh and material of the player body.
+struct BodyModel {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for BodyModel {
+    fn from_world(world: &mut World) -> Self {
+        let mut meshes = world
+            .get_resource_mut::<Assets<Mesh>>()
+            .expect("PbrPlugin must be added before SpectatorPlugin");
+        let mesh = meshes.add(Mesh::from(shape::Box::new(
+            BODY_WIDTH,
+            BODY_HEIGHT,
+            BODY_WIDTH,
+        )));
+
+        let mut materials = world
+            .get_resource_mut::<Assets<StandardMaterial>>()
+            .expect("PbrPlugin must be added before SpectatorPlugin");
+        let material = materials.add(Color::rgb(0.3, 0.5, 0.8).into());
+
+        Self { mesh, material }
+    }
+}
+
+pub struct SpectatorPlugin;
+
+impl Plugin for SpectatorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Spectator>()
+            .init_resource::<BodyModel>()
+            .add_event::<ToggleSpectator>()
+            .add_system(toggle_spectator)
+            .add_system(fly.after(toggle_spectator));
+    }
+}
+
+fn toggle_spectator(
+    mut commands: Commands,
+    model: Res<BodyModel>,
+    mut spectator: ResMut<Spectator>,
+    mut console: ResMut<Console>,
+    mut reader: EventReader<ToggleSpectator>,
+    mut camera: Query<&mut Transform, (With<MainCamera>, Without<PlayerBody>)>,
+    bodies: Query<(&Transform, &PlayerBody)>,
+) {
+    if reader.iter().count() == 0 {
+        return;
+    }
+
+    let mut transform = match camera.get_single_mut() {
+        Ok(transform) => transform,
+        Err(_) => return,
+    };
+
+    match spectator.body.take() {
+        Some(entity) => {
+            if let Ok((body, PlayerBody { eyes })) = bodies.get(entity) {
+                *transform = body.mul_transform(*eyes);
+            }
+
+            commands.entity(entity).despawn_recursive();
+            console.print("Spectator mode disabled".to_string());
+        }
+        None => {
+            let center = transform.translation - Vec3::Y * (BODY_EYES - BODY_HEIGHT / 2.0);
+            let eyes = Transform {
+                translation: transform.translation - center,
+                ..*transform
+            };
+
+            let entity = commands
+                .spawn_bundle(PbrBundle {
+                    mesh: model.mesh.clone(),
+                    material: model.material.clone(),
+                    transform: Transform::from_translation(center),
+                    ..Default::default()
+                })
+                .insert(PlayerBody { eyes })
+                .insert(StreamingAnchor {
+                    radius: Some(BODY_STREAMING_RADIUS),
+                })
+                .id();
+
+            spectator.body = Some(entity);
+            console.print("Spectator mode enabled".to_string());
+        }
+    }
+}
+
+/**
+  Returns the direction to move to, relative to the camera facing, from the pressed keys.
+*/
+pub fn move_direction(keyboard: &Input<KeyCode>, transform: &Transform) -> Vec3 {
+    let forward = transform.forward();
+    let right = transform.right();
+
+    let bindings = [
+        (KeyCode::W, forward),
+        (KeyCode::S, -forward),
+        (KeyCode::D, right),
+        (KeyCode::A, -right),
+        (UP_KEY, Vec3::Y),
+        (DOWN_KEY, -Vec3::Y),
+    ];
+
+    bindings
+        .iter()
+        .filter(|(key, _)| keyboard.pressed(*key))
+        .fold(Vec3::ZERO, |sum, (_, dir)| sum + *dir)
+        .normalize_or_zero()
+}
+
+/**
+  Moves the camera while spectating, faster while sprinting, with either the keyboard or a gamepad. Keys and
+  the left stick are ignored while typing on the console, chat or a sign.
+*/
+#[allow(clippy::too_many_arguments)]
+fn fly(
+    time: Res<Time>,
+    spectator: Res<Spectator>,
+    console: Res<Console>,
+    chat: Res<Chat>,
+    signs: Res<Signs>,
+    config: Res<CharacterConfig>,
+    sprint: Res<Sprint>,
+    keyboard: Res<Input<KeyCode>>,
+    gamepad: Res<GamepadInput>,
+    mut q: Query<&mut Transform, With<MainCamera>>,
+) {
+    if !spectator.is_active() {
+        return;
+    }
+
+    let mut transform = match q.get_single_mut() {
+        Ok(transform) => transform,
+        Err(_) => return,
+    };
+
+    if console.visible || chat.open || signs.is_editing() {
+        return;
+    }
+
+    let speed = if sprint.active {
+        SPEED * sprint.multiplier(&config)
+    } else if keyboard.pressed(SLOW_KEY) {
+        SPEED * SLOW_MULTIPLIER
+    } else {
+        SPEED
+    };
+
+    let dir = (move_direction(&keyboard, &transform) + gamepad.move_direction(&transform))
+        .clamp_length_max(1.0);
+    transform.translation += dir * speed * time.delta_seconds();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn move_direction() {
+        let transform = Transform::default();
+        let mut keyboard = Input::<KeyCode>::default();
+
+        assert_eq!(super::move_direction(&keyboard, &transform), Vec3::ZERO);
+
+        keyboard.press(KeyCode::W);
+        assert_eq!(super::move_direction(&keyboard, &transform), -Vec3::Z);
+
+        // Opposite keys cancel out and diagonals are as fast as straight lines
+        keyboard.press(KeyCode::S);
+        keyboard.press(KeyCode::D);
+        keyboard.press(UP_KEY);
+        let dir = super::move_direction(&keyboard, &transform);
+        assert!((dir - Vec3::new(1.0, 1.0, 0.0).normalize()).length() < 1e-5);
+    }
+}
@@ -0,0 +1,215 @@
+use bevy::prelude::*;
+
+use crate::{chat::Chat, console::Console, gamepad::GamepadInput, signs::Signs, MainCamera};
+
+const SPRINT_KEY: KeyCode = KeyCode::LShift;
+
+/// How fast, per second, the field of view follows the sprint kick.
+const FOV_SPEED: f32 = 8.0;
+
+/// How stamina is spent while sprinting and regenerated otherwise, in stamina points.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StaminaConfig {
+    pub max: f32,
+    /// Spent each second while sprinting.
+    pub drain: f32,
+    /// Regenerated each second while not sprinting.
+    pub regen: f32,
+}
+
+impl Default for StaminaConfig {
+    fn default() -> Self {
+        Self {
+            max: 10.0,
+            drain: 1.0,
+            regen: 2.0,
+        }
+    }
+}
+
+/// How the player character moves. Insert it before [`SprintPlugin`] to change it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CharacterConfig {
+    /// Speed while sprinting, relative to the usual speed.
+    pub sprint_multiplier: f32,
+    /// Radians the field of view widens while sprinting.
+    pub fov_kick: f32,
+    /// When set, sprinting spends stamina, and stops once it's gone.
+    pub stamina: Option<StaminaConfig>,
+    /// Highest ledge, in voxels, the player steps up on while walking.
+    pub max_step: f32,
+}
+
+impl Default for CharacterConfig {
+    fn default() -> Self {
+        Self {
+            sprint_multiplier: 1.5,
+            fov_kick: 0.15,
+            stamina: Some(Default::default()),
+            max_step: 1.0,
+        }
+    }
+}
+
+/**
+  Whether the player is sprinting, with the stamina left. Sprinting needs the sprint key held while moving,
+  and some stamina left when it's enabled.
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sprint {
+    pub active: bool,
+    pub stamina: f32,
+}
+
+impl FromWorld for Sprint {
+    fn from_world(world: &mut World) -> Self {
+        let config = world.get_resource_or_insert_with(CharacterConfig::default);
+
+        Self {
+            active: false,
+            stamina: config
+                .stamina
+                .map(|stamina| stamina.max)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl Sprint {
+    /// Updates the sprint for `dt` seconds, given whether the player wants to sprint.
+    pub fn update(&mut self, wants: bool, config: &CharacterConfig, dt: f32) {
+        let stamina = match config.stamina {
+            Some(stamina) => stamina,
+            None => {
+                self.active = wants;
+                return;
+            }
+        };
+
+        self.active = wants && self.stamina > 0.0;
+
+        self.stamina = if self.active {
+            (self.stamina - stamina.drain * dt).max(0.0)
+        } else {
+            (self.stamina + stamina.regen * dt).min(stamina.max)
+        };
+    }
+
+    /// Speed multiplier of the current sprint state.
+    pub fn multiplier(&self, config: &CharacterConfig) -> f32 {
+        if self.active {
+            config.sprint_multiplier
+        } else {
+            1.0
+        }
+    }
+}
+
+pub struct SprintPlugin;
+
+impl Plugin for SprintPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CharacterConfig>()
+            .init_resource::<Sprint>()
+            .add_system(update_sprint)
+            .add_system(kick_fov.after(update_sprint));
+    }
+}
+
+/// The player sprints while holding the sprint key and moving, both walking and spectating.
+#[allow(clippy::too_many_arguments)]
+fn update_sprint(
+    time: Res<Time>,
+    config: Res<CharacterConfig>,
+    console: Res<Console>,
+    chat: Res<Chat>,
+    signs: Res<Signs>,
+    keyboard: Res<Input<KeyCode>>,
+    gamepad: Res<GamepadInput>,
+    mut sprint: ResMut<Sprint>,
+) {
+    let typing = console.visible || chat.open || signs.is_editing();
+    let moving = [KeyCode::W, KeyCode::A, KeyCode::S, KeyCode::D]
+        .iter()
+        .any(|key| keyboard.pressed(*key))
+        || gamepad.movement != Vec2::ZERO;
+    let wants = !typing && moving && keyboard.pressed(SPRINT_KEY);
+
+    sprint.update(wants, &config, time.delta_seconds());
+}
+
+/// Widens the field of view while sprinting, easing in and out of it.
+fn kick_fov(
+    mut base: Local<Option<f32>>,
+    time: Res<Time>,
+    config: Res<CharacterConfig>,
+    sprint: Res<Sprint>,
+    mut q: Query<&mut PerspectiveProjection, With<MainCamera>>,
+) {
+    let mut projection = match q.get_single_mut() {
+        Ok(projection) => projection,
+        Err(_) => return,
+    };
+
+    let base = *base.get_or_insert(projection.fov);
+    let target = if sprint.active {
+        base + config.fov_kick
+    } else {
+        base
+    };
+
+    if (projection.fov - target).abs() > f32::EPSILON {
+        let blend = (FOV_SPEED * time.delta_seconds()).min(1.0);
+        projection.fov += (target - projection.fov) * blend;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update() {
+        let config = CharacterConfig::default();
+        let stamina = config.stamina.unwrap();
+        let mut sprint = Sprint {
+            active: false,
+            stamina: stamina.max,
+        };
+
+        sprint.update(true, &config, 1.0);
+        assert!(sprint.active);
+        assert_eq!(sprint.stamina, stamina.max - stamina.drain);
+        assert_eq!(sprint.multiplier(&config), config.sprint_multiplier);
+
+        // Runs out of stamina and stops
+        sprint.update(true, &config, stamina.max);
+        assert_eq!(sprint.stamina, 0.0);
+        sprint.update(true, &config, 1.0);
+        assert!(!sprint.active);
+        assert_eq!(sprint.multiplier(&config), 1.0);
+
+        // Regenerates while not sprinting
+        sprint.update(false, &config, 1.0);
+        assert_eq!(sprint.stamina, stamina.regen * 2.0);
+        sprint.update(false, &config, stamina.max);
+        assert_eq!(sprint.stamina, stamina.max);
+    }
+
+    #[test]
+    fn update_without_stamina() {
+        let config = CharacterConfig {
+            stamina: None,
+            ..Default::default()
+        };
+        let mut sprint = Sprint {
+            active: false,
+            stamina: 0.0,
+        };
+
+        sprint.update(true, &config, 100.0);
+        assert!(sprint.active);
+        sprint.update(false, &config, 1.0);
+        assert!(!sprint.active);
+    }
+}
@@ -0,0 +1,314 @@
+use bevy::prelude::*;
+use std::path::Path;
+use vox::{
+    meta::{WorldMeta, META_PATH},
+    pipeline::WorldOrigin,
+    stats::WorldStats,
+    voxel::{Kind, KindRegistry},
+};
+
+use crate::{
+    chat::Chat,
+    console::Console,
+    game_state::GameState,
+    selection::{VoxelBroken, VoxelPlaced},
+    spectator::Spectator,
+    MainCamera, FONT_PATH,
+};
+
+const FONT_SIZE: f32 = 18.0;
+
+const TOGGLE_KEY: KeyCode = KeyCode::J;
+
+/// How often, in seconds, stats are saved, when something changed.
+const SAVE_INTERVAL: f32 = 5.0;
+
+/// Moves longer than this, in voxels, on a single frame are teleports, which aren't traveled.
+const TELEPORT_DISTANCE: f32 = 8.0;
+
+/// How many kinds are listed on each of the placed and broken lists.
+const TOP_KINDS: usize = 8;
+
+/// Stats screen, which lists the stats of the world while visible.
+struct StatsScreen {
+    root: Entity,
+    text: Entity,
+    visible: bool,
+}
+
+/// Saves the world metadata every `SAVE_INTERVAL`, but only when stats changed since the last save.
+struct SaveTimer {
+    timer: Timer,
+    dirty: bool,
+}
+
+impl Default for SaveTimer {
+    fn default() -> Self {
+        Self {
+            timer: Timer::from_seconds(SAVE_INTERVAL, true),
+            dirty: false,
+        }
+    }
+}
+
+/// World position of the camera on the last frame it was traveling, if it was.
+#[derive(Default)]
+struct LastPosition(Option<Vec3>);
+
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SaveTimer>()
+            .init_resource::<LastPosition>()
+            .add_startup_system(setup_stats_screen)
+            .add_system(count_voxels)
+            .add_system(travel)
+            .add_system(toggle_stats_screen)
+            .add_system(update_stats_screen.after(count_voxels).after(travel))
+            .add_system(save_stats.after(count_voxels).after(travel));
+    }
+}
+
+/// Distance traveled between two world positions, which is none for teleports.
+pub fn traveled(from: Vec3, to: Vec3) -> Option<f32> {
+    let distance = from.distance(to);
+    (distance <= TELEPORT_DISTANCE).then_some(distance)
+}
+
+/// Text listing the given stats, with the kinds placed and broken the most.
+pub fn describe(stats: &WorldStats, registry: &KindRegistry) -> String {
+    let name = |kind: Kind| {
+        registry
+            .get(kind)
+            .map(|desc| desc.name.clone())
+            .unwrap_or_else(|| format!("#{}", kind.id()))
+    };
+
+    let list = |title: &str, total: u64, kinds: &mut Vec<(Kind, u64)>| {
+        // Most counted first, ties by id, so the list doesn't shuffle around
+        kinds.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.id().cmp(&b.id())));
+
+        let mut text = format!("\n{}: {}\n", title, total);
+        for (kind, count) in kinds.iter().take(TOP_KINDS) {
+            text += &format!("  {}: {}\n", name(*kind), count);
+        }
+        text
+    };
+
+    format!(
+        "Distance traveled: {:.0} voxels\nDeaths: {}\n{}{}",
+        stats.distance(),
+        stats.deaths(),
+        list(
+            "Blocks placed",
+            stats.total_placed(),
+            &mut stats.placed_kinds().collect()
+        ),
+        list(
+            "Blocks broken",
+            stats.total_broken(),
+            &mut stats.broken_kinds().collect()
+        ),
+    )
+}
+
+fn setup_stats_screen(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let mut text = None;
+
+    let root = commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                display: Display::None,
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            text = Some(
+                parent
+                    .spawn_bundle(TextBundle {
+                        text: Text::with_section(
+                            String::default(),
+                            TextStyle {
+                                font: asset_server.load(FONT_PATH),
+                                font_size: FONT_SIZE,
+                                color: Color::WHITE,
+                            },
+                            Default::default(),
+                        ),
+                        ..Default::default()
+                    })
+                    .id(),
+            );
+        })
+        .id();
+
+    commands.insert_resource(StatsScreen {
+        root,
+        text: text.expect("Stats text is spawned with the screen"),
+        visible: false,
+    });
+}
+
+fn count_voxels(
+    mut meta: ResMut<WorldMeta>,
+    mut save_timer: ResMut<SaveTimer>,
+    mut placed_reader: EventReader<VoxelPlaced>,
+    mut broken_reader: EventReader<VoxelBroken>,
+) {
+    for VoxelPlaced(kind) in placed_reader.iter() {
+        meta.stats.record_placed(*kind);
+        save_timer.dirty = true;
+    }
+
+    for VoxelBroken { kind, .. } in broken_reader.iter() {
+        meta.stats.record_broken(*kind);
+        save_timer.dirty = true;
+    }
+}
+
+/// Only the player traveling counts, so flying around while spectating or outside of games doesn't.
+fn travel(
+    state: Res<State<GameState>>,
+    spectator: Res<Spectator>,
+    origin: Res<WorldOrigin>,
+    mut meta: ResMut<WorldMeta>,
+    mut save_timer: ResMut<SaveTimer>,
+    mut last: ResMut<LastPosition>,
+    q: Query<&GlobalTransform, With<MainCamera>>,
+) {
+    let position = match q.get_single() {
+        Ok(transform) if *state.current() == GameState::InGame && !spectator.is_active() => {
+            origin.to_world(transform.translation)
+        }
+        _ => {
+            last.0 = None;
+            return;
+        }
+    };
+
+    if let Some(distance) = last.0.and_then(|from| traveled(from, position)) {
+        if distance > 0.0 {
+            meta.stats.record_distance(distance);
+            save_timer.dirty = true;
+        }
+    }
+
+    last.0 = Some(position);
+}
+
+fn toggle_stats_screen(
+    console: Res<Console>,
+    chat: Res<Chat>,
+    keyboard: Res<Input<KeyCode>>,
+    mut screen: ResMut<StatsScreen>,
+    mut q: Query<&mut Style>,
+) {
+    // Keys typed on the console or chat shouldn't toggle the screen
+    if console.visible || chat.open || !keyboard.just_pressed(TOGGLE_KEY) {
+        return;
+    }
+
+    screen.visible = !screen.visible;
+
+    if let Ok(mut style) = q.get_mut(screen.root) {
+        style.display = if screen.visible {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+}
+
+fn update_stats_screen(
+    registry: Res<KindRegistry>,
+    meta: Res<WorldMeta>,
+    screen: Res<StatsScreen>,
+    mut q: Query<&mut Text>,
+) {
+    // There is no need to describe the stats while they aren't visible
+    if !screen.visible || !(screen.is_changed() || meta.is_changed()) {
+        return;
+    }
+
+    if let Ok(mut text) = q.get_mut(screen.text) {
+        text.sections[0].value = describe(&meta.stats, &registry);
+    }
+}
+
+fn save_stats(time: Res<Time>, meta: Res<WorldMeta>, mut save_timer: ResMut<SaveTimer>) {
+    if !save_timer.timer.tick(time.delta()).just_finished() || !save_timer.dirty {
+        return;
+    }
+
+    meta.save(Path::new(META_PATH));
+    save_timer.dirty = false;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vox::voxel::{KindDescription, MeshShape};
+
+    fn description(id: u16, name: &str) -> KindDescription {
+        KindDescription {
+            name: name.to_string(),
+            id,
+            color: (1.0, 1.0, 1.0, 1.0),
+            shape: MeshShape::Cube,
+            prop: None,
+            directional: false,
+            light: 0,
+            tick: None,
+            signal: None,
+            toggle: None,
+            hardness: 0.0,
+            tool_tier: 0,
+            block_entity: None,
+            explosive: None,
+            liquid: None,
+            climbable: false,
+            surface: None,
+            foliage: false,
+            tint: None,
+            portal: None,
+        }
+    }
+
+    #[test]
+    fn traveled() {
+        let from = Vec3::new(1.0, 2.0, 3.0);
+
+        assert_eq!(super::traveled(from, from), Some(0.0));
+        assert_eq!(super::traveled(from, from + Vec3::X * 3.0), Some(3.0));
+        assert_eq!(
+            super::traveled(from, from + Vec3::Y * (TELEPORT_DISTANCE + 1.0)),
+            None
+        );
+    }
+
+    #[test]
+    fn describe() {
+        let registry = KindRegistry::new(vec![description(1, "Stone"), description(2, "Dirt")]);
+        let mut stats = WorldStats::default();
+
+        stats.record_placed(2.into());
+        stats.record_placed(1.into());
+        stats.record_placed(1.into());
+        stats.record_broken(5.into());
+        stats.record_distance(12.4);
+
+        assert_eq!(
+            super::describe(&stats, &registry),
+            "Distance traveled: 12 voxels\nDeaths: 0\n\
+            \nBlocks placed: 3\n  Stone: 2\n  Dirt: 1\n\
+            \nBlocks broken: 1\n  #5: 1\n"
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use bevy::prelude::*;
+use std::path::Path;
+use vox::{
+    edit::Region,
+    meta::{WorldMeta, META_PATH},
+    pipeline::WorldOrigin,
+    ticket::Ticket,
+};
+
+use crate::{console::Console, MainCamera};
+
+/**
+  Commands which manage tickets, the chunk regions kept loaded without players around, issued through the
+  console.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub enum TicketCommand {
+    /// Keeps the chunks within the given radius, in chunks, around the player loaded.
+    Add { name: String, radius: i32 },
+    /// Removes the ticket with the given name.
+    Remove(String),
+    /// Lists all tickets.
+    List,
+}
+
+pub struct TicketsPlugin;
+
+impl Plugin for TicketsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<TicketCommand>()
+            .add_system(run_ticket_commands);
+    }
+}
+
+/**
+  Parses the arguments of the `ticket` command.
+*/
+pub fn parse(args: &[&str]) -> Result<TicketCommand, String> {
+    match args {
+        ["add", name] => Ok(TicketCommand::Add {
+            name: name.to_string(),
+            radius: 0,
+        }),
+        ["add", name, radius] => match radius.parse() {
+            Ok(radius) if radius >= 0 => Ok(TicketCommand::Add {
+                name: name.to_string(),
+                radius,
+            }),
+            _ => Err(format!("Invalid ticket radius: {}", radius)),
+        },
+        ["remove", name] => Ok(TicketCommand::Remove(name.to_string())),
+        ["list"] => Ok(TicketCommand::List),
+        _ => Err(
+            "Usage: ticket add <name> [radius], ticket remove <name> or ticket list".to_string(),
+        ),
+    }
+}
+
+fn run_ticket_commands(
+    origin: Res<WorldOrigin>,
+    mut meta: ResMut<WorldMeta>,
+    mut console: ResMut<Console>,
+    mut reader: EventReader<TicketCommand>,
+    q: Query<&Transform, With<MainCamera>>,
+) {
+    for command in reader.iter() {
+        match command {
+            TicketCommand::Add { name, radius } => {
+                let center = match q.get_single() {
+                    Ok(transform) => origin.to_local(transform.translation),
+                    Err(_) => continue,
+                };
+
+                let ticket = Ticket {
+                    name: name.clone(),
+                    region: Region::from_corners(
+                        center - IVec3::splat(*radius),
+                        center + IVec3::splat(*radius),
+                    ),
+                };
+
+                match meta.tickets.add(ticket) {
+                    Ok(()) => {
+                        meta.save(Path::new(META_PATH));
+                        console.print(format!("Added ticket {}", name));
+                    }
+                    Err(err) => console.print(err),
+                }
+            }
+            TicketCommand::Remove(name) => match meta.tickets.remove(name) {
+                Some(_) => {
+                    meta.save(Path::new(META_PATH));
+                    console.print(format!("Removed ticket {}", name));
+                }
+                None => console.print(format!("Unknown ticket: {}", name)),
+            },
+            TicketCommand::List => {
+                let tickets = meta
+                    .tickets
+                    .iter()
+                    .map(|ticket| {
+                        format!(
+                            "{}: chunks {} to {}",
+                            ticket.name, ticket.region.min, ticket.region.max
+                        )
+                    })
+                    .collect::<Vec<_>>();
+
+                console.print(if tickets.is_empty() {
+                    "No tickets".to_string()
+                } else {
+                    tickets.join("\n")
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            super::parse(&["add", "spawn", "2"]),
+            Ok(TicketCommand::Add {
+                name: "spawn".to_string(),
+                radius: 2
+            })
+        );
+        assert_eq!(
+            super::parse(&["add", "farm"]),
+            Ok(TicketCommand::Add {
+                name: "farm".to_string(),
+                radius: 0
+            })
+        );
+        assert_eq!(
+            super::parse(&["remove", "farm"]),
+            Ok(TicketCommand::Remove("farm".to_string()))
+        );
+        assert_eq!(super::parse(&["list"]), Ok(TicketCommand::List));
+
+        assert!(super::parse(&["add", "spawn", "-1"]).is_err());
+        assert!(super::parse(&[]).is_err());
+    }
+}
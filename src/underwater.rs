@@ -0,0 +1,164 @@
+use bevy::prelude::*;
+use vox::{
+    pipeline::WorldOrigin,
+    voxel::{Kind, KindRegistry},
+    world::VoxWorld,
+};
+
+use crate::{ambience, MainCamera};
+
+/// How opaque the overlay tinting the screen with the liquid color is.
+const OVERLAY_ALPHA: f32 = 0.4;
+/// Volume of muffled sounds, relative to their usual volume.
+const MUFFLED_VOLUME: f32 = 0.3;
+/// Seconds it takes sounds to be fully muffled, or to be back to their usual volume.
+const MUFFLE_TIME: f32 = 0.3;
+
+/// Most the camera sways while inside a liquid, in radians.
+const SWAY_ANGLE: f32 = 0.02;
+/// How many times per second the camera sways back and forth while inside a liquid.
+const SWAY_RATE: f32 = 0.4;
+
+/// Liquid kind the camera is inside of, if any. It's checked each frame, so effects can react to it.
+#[derive(Default)]
+pub struct CameraLiquid {
+    pub kind: Option<Kind>,
+}
+
+impl CameraLiquid {
+    pub fn is_inside(&self) -> bool {
+        self.kind.is_some()
+    }
+}
+
+/**
+  Volume sounds are played at, relative to their usual volume. Sounds are muffled while the camera is inside
+  a liquid.
+*/
+pub struct AudioMuffle(pub f32);
+
+impl Default for AudioMuffle {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+/// Fullscreen overlay tinting the screen with the color of the liquid the camera is inside of.
+#[derive(Component)]
+struct LiquidOverlay;
+
+pub struct UnderwaterPlugin;
+
+impl Plugin for UnderwaterPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraLiquid>()
+            .init_resource::<AudioMuffle>()
+            .add_startup_system(setup_overlay)
+            .add_system(detect_camera_liquid)
+            .add_system(update_overlay.after(detect_camera_liquid))
+            .add_system(muffle_audio.after(detect_camera_liquid));
+    }
+}
+
+/// Camera roll while inside a liquid, at the given time, in seconds.
+pub fn sway(time: f32) -> f32 {
+    (time * SWAY_RATE * std::f32::consts::TAU).sin() * SWAY_ANGLE
+}
+
+/// Moves the muffle towards being fully muffled, or back to the usual volume, over `dt` seconds.
+pub fn muffle(volume: f32, inside: bool, dt: f32) -> f32 {
+    let target = if inside { MUFFLED_VOLUME } else { 1.0 };
+    let step = (1.0 - MUFFLED_VOLUME) * dt / MUFFLE_TIME;
+
+    ambience::crossfade(volume, target, step)
+}
+
+fn setup_overlay(mut commands: Commands) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .insert(LiquidOverlay);
+}
+
+pub(super) fn detect_camera_liquid(
+    world: Res<VoxWorld>,
+    registry: Res<KindRegistry>,
+    origin: Res<WorldOrigin>,
+    mut liquid: ResMut<CameraLiquid>,
+    q: Query<&Transform, With<MainCamera>>,
+) {
+    let kind = q.get_single().ok().and_then(|transform| {
+        world
+            .get_voxel(origin.to_voxel(transform.translation))
+            .filter(|kind| registry.liquid(*kind).is_some())
+    });
+
+    if liquid.kind != kind {
+        liquid.kind = kind;
+    }
+}
+
+fn update_overlay(
+    registry: Res<KindRegistry>,
+    liquid: Res<CameraLiquid>,
+    mut q: Query<&mut UiColor, With<LiquidOverlay>>,
+) {
+    if !liquid.is_changed() {
+        return;
+    }
+
+    let color = match liquid.kind.and_then(|kind| registry.get(kind)) {
+        Some(desc) => {
+            let (r, g, b, _) = desc.color;
+            Color::rgba(r, g, b, OVERLAY_ALPHA)
+        }
+        None => Color::NONE,
+    };
+
+    for mut overlay in q.iter_mut() {
+        *overlay = color.into();
+    }
+}
+
+fn muffle_audio(time: Res<Time>, liquid: Res<CameraLiquid>, mut muffle: ResMut<AudioMuffle>) {
+    let volume = self::muffle(muffle.0, liquid.is_inside(), time.delta_seconds());
+
+    if volume != muffle.0 {
+        muffle.0 = volume;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn muffle() {
+        let step = (1.0 - MUFFLED_VOLUME) * 0.1 / MUFFLE_TIME;
+
+        assert_eq!(super::muffle(1.0, true, 0.1), 1.0 - step);
+        assert_eq!(
+            super::muffle(MUFFLED_VOLUME, false, 0.1),
+            MUFFLED_VOLUME + step
+        );
+
+        // Never goes past the target
+        assert_eq!(super::muffle(1.0, true, 10.0), MUFFLED_VOLUME);
+        assert_eq!(super::muffle(MUFFLED_VOLUME, false, 10.0), 1.0);
+        assert_eq!(super::muffle(1.0, false, 0.1), 1.0);
+    }
+
+    #[test]
+    fn sway() {
+        assert_eq!(super::sway(0.0), 0.0);
+        assert!((super::sway(0.25 / SWAY_RATE) - SWAY_ANGLE).abs() < 1e-6);
+        assert!((0..100).all(|t| super::sway(t as f32 * 0.1).abs() <= SWAY_ANGLE));
+    }
+}
@@ -0,0 +1,172 @@
+use bevy::{pbr::NotShadowCaster, prelude::*};
+use rand::Rng;
+use std::path::Path;
+use vox::{
+    biome::Precipitation,
+    heightmap::Heightmap,
+    meta::{WorldMeta, META_PATH},
+    pipeline::{OriginShifted, WorldOrigin},
+    world::VoxWorld,
+};
+
+use crate::MainCamera;
+
+/// How far, in voxels, around the camera particles are spawned.
+const SPAWN_RADIUS: f32 = 16.0;
+/// How high above the camera particles are spawned.
+const SPAWN_HEIGHT: f32 = 12.0;
+const PARTICLES_PER_SECOND: f32 = 300.0;
+
+const RAIN_SPEED: f32 = 14.0;
+const SNOW_SPEED: f32 = 2.5;
+
+/// Meshes and materials shared by all precipitation particles.
+struct PrecipitationAssets {
+    rain_mesh: Handle<Mesh>,
+    rain_material: Handle<StandardMaterial>,
+    snow_mesh: Handle<Mesh>,
+    snow_material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for PrecipitationAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mut meshes = world
+            .get_resource_mut::<Assets<Mesh>>()
+            .expect("PbrPlugin must be added before WeatherPlugin");
+        let rain_mesh = meshes.add(Mesh::from(shape::Box::new(0.02, 0.4, 0.02)));
+        let snow_mesh = meshes.add(Mesh::from(shape::Cube { size: 0.08 }));
+
+        let mut materials = world
+            .get_resource_mut::<Assets<StandardMaterial>>()
+            .expect("PbrPlugin must be added before WeatherPlugin");
+        let rain_material = materials.add(StandardMaterial {
+            base_color: Color::rgba(0.6, 0.7, 1.0, 0.6),
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..Default::default()
+        });
+        let snow_material = materials.add(StandardMaterial {
+            base_color: Color::WHITE,
+            unlit: true,
+            ..Default::default()
+        });
+
+        Self {
+            rain_mesh,
+            rain_material,
+            snow_mesh,
+            snow_material,
+        }
+    }
+}
+
+/**
+  A falling rain drop or snow flake. It's removed when it reaches the ground, or a roof.
+*/
+#[derive(Component)]
+struct Particle {
+    speed: f32,
+    /// Render space height where the particle hits something.
+    floor: f32,
+}
+
+pub struct WeatherPlugin;
+
+impl Plugin for WeatherPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PrecipitationAssets>()
+            .add_system(update_weather)
+            .add_system(spawn_particles)
+            .add_system(fall_particles);
+    }
+}
+
+fn update_weather(time: Res<Time>, mut meta: ResMut<WorldMeta>) {
+    if meta
+        .weather
+        .tick(time.delta_seconds(), &mut rand::thread_rng())
+    {
+        debug!("Weather changed: {:?}", meta.weather);
+        meta.save(Path::new(META_PATH));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    meta: Res<WorldMeta>,
+    origin: Res<WorldOrigin>,
+    world: Res<VoxWorld>,
+    heightmap: Res<Heightmap>,
+    assets: Res<PrecipitationAssets>,
+    q: Query<&Transform, With<MainCamera>>,
+) {
+    if !meta.weather.precipitating {
+        return;
+    }
+
+    let camera = match q.get_single() {
+        Ok(transform) => transform.translation,
+        Err(_) => return,
+    };
+
+    let mut rng = rand::thread_rng();
+    let count = (PARTICLES_PER_SECOND * time.delta_seconds()).round() as usize;
+
+    for _ in 0..count {
+        let position = camera
+            + Vec3::new(
+                rng.gen_range(-SPAWN_RADIUS..SPAWN_RADIUS),
+                SPAWN_HEIGHT,
+                rng.gen_range(-SPAWN_RADIUS..SPAWN_RADIUS),
+            );
+
+        let voxel = origin.to_voxel(position);
+
+        // Nothing falls indoors or on dry biomes
+        let precipitation = match meta.weather.precipitation_at(&world, &heightmap, voxel) {
+            Some(precipitation) => precipitation,
+            None => continue,
+        };
+
+        let floor = match heightmap.height(IVec2::new(voxel.x, voxel.z)) {
+            Some(height) => position.y - (voxel.y - height - 1) as f32,
+            None => camera.y - SPAWN_HEIGHT,
+        };
+
+        let (mesh, material, speed) = match precipitation {
+            Precipitation::Rain => (&assets.rain_mesh, &assets.rain_material, RAIN_SPEED),
+            Precipitation::Snow => (&assets.snow_mesh, &assets.snow_material, SNOW_SPEED),
+        };
+
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: mesh.clone(),
+                material: material.clone(),
+                transform: Transform::from_translation(position),
+                ..Default::default()
+            })
+            .insert(NotShadowCaster)
+            .insert(Particle { speed, floor });
+    }
+}
+
+fn fall_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut reader: EventReader<OriginShifted>,
+    mut q: Query<(Entity, &mut Transform, &mut Particle)>,
+) {
+    // Floors are in render space, so they must follow the origin
+    let shift = reader.iter().fold(0.0, |acc, evt| acc + evt.0.y);
+
+    for (entity, mut transform, mut particle) in q.iter_mut() {
+        particle.floor += shift;
+        transform.translation.y -= particle.speed * time.delta_seconds();
+
+        if transform.translation.y <= particle.floor {
+            commands.entity(entity).despawn();
+        }
+    }
+}
@@ -0,0 +1,350 @@
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+    utils::HashMap,
+};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use vox::{
+    chunk,
+    pipeline::{ChunkUpdated, WorldOrigin},
+    voxel::KindRegistry,
+};
+
+use crate::{
+    console::Console,
+    minimap::{self, MinimapColumns},
+    MainCamera,
+};
+
+const EXPLORED_PATH: &str = "cache/explored.bin";
+/// How often, in seconds, explored chunks are saved, when there is something new.
+const SAVE_INTERVAL: f32 = 5.0;
+
+/// How many colors, on each axis, are kept for each explored chunk column.
+const SUMMARY_SIZE: usize = 4;
+const SUMMARY_STEP: usize = chunk::AXIS_SIZE / SUMMARY_SIZE;
+
+/// How many chunk columns, around the center, are displayed on each direction.
+const MAP_RADIUS: i32 = 64;
+const MAP_SIZE: usize = MAP_RADIUS as usize * 2 * SUMMARY_SIZE;
+const DISPLAY_SIZE: f32 = 768.0;
+
+const TOGGLE_KEY: KeyCode = KeyCode::M;
+
+/// Colors of a chunk column, as seen from above, sampled on a `SUMMARY_SIZE` x `SUMMARY_SIZE` grid.
+pub type ChunkSummary = [[u8; 4]; SUMMARY_SIZE * SUMMARY_SIZE];
+
+/**
+  Color summaries of every chunk column the player has ever loaded, persisted across sessions.
+*/
+#[derive(Default, Debug, PartialEq, Deserialize, Serialize)]
+pub struct ExploredChunks(HashMap<IVec2, ChunkSummary>);
+
+impl ExploredChunks {
+    pub fn load(path: &Path) -> Self {
+        if !path.exists() {
+            return Self::default();
+        }
+
+        let file = match std::fs::File::open(path) {
+            Ok(file) => file,
+            Err(err) => {
+                error!("Unable to open file {}: {}", path.display(), err);
+                return Self::default();
+            }
+        };
+
+        bincode::deserialize_from(file).unwrap_or_else(|err| {
+            error!("Failed to parse file {}: {}", path.display(), err);
+            Self::default()
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let file = std::fs::File::create(path)?;
+        bincode::serialize_into(file, self)?;
+
+        Ok(())
+    }
+
+    pub fn get(&self, column: IVec2) -> Option<&ChunkSummary> {
+        self.0.get(&column)
+    }
+}
+
+struct WorldMap {
+    image: Handle<Image>,
+    root: Entity,
+    center: IVec2,
+    visible: bool,
+    /// Whether explored chunks has changed since the map was last painted.
+    dirty: bool,
+}
+
+struct SaveTimer {
+    timer: Timer,
+    dirty: bool,
+}
+
+pub struct WorldMapPlugin;
+
+impl Plugin for WorldMapPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ExploredChunks::load(Path::new(EXPLORED_PATH)))
+            .insert_resource(SaveTimer {
+                timer: Timer::from_seconds(SAVE_INTERVAL, true),
+                dirty: false,
+            })
+            .add_startup_system(setup_world_map)
+            .add_system(explore_chunks.after(minimap::update_minimap))
+            .add_system(toggle_world_map)
+            .add_system(
+                paint_world_map
+                    .after(explore_chunks)
+                    .after(toggle_world_map),
+            )
+            .add_system(save_explored_chunks.after(explore_chunks));
+    }
+}
+
+pub fn chunk_summary(
+    columns: &MinimapColumns,
+    column: IVec2,
+    registry: &KindRegistry,
+) -> ChunkSummary {
+    let mut summary = [minimap::BACKGROUND_COLOR; SUMMARY_SIZE * SUMMARY_SIZE];
+
+    for x in 0..SUMMARY_SIZE {
+        for z in 0..SUMMARY_SIZE {
+            // Sample the center voxel column of each summary cell
+            let kind = columns.top(
+                column,
+                x * SUMMARY_STEP + SUMMARY_STEP / 2,
+                z * SUMMARY_STEP + SUMMARY_STEP / 2,
+            );
+
+            summary[z * SUMMARY_SIZE + x] = minimap::kind_color(registry, kind);
+        }
+    }
+
+    summary
+}
+
+fn paint(data: &mut [u8], center: IVec2, explored: &ExploredChunks) {
+    for (idx, pixel) in data.chunks_exact_mut(4).enumerate() {
+        let (x, z) = ((idx % MAP_SIZE) as i32, (idx / MAP_SIZE) as i32);
+
+        let summary_size = SUMMARY_SIZE as i32;
+        let column =
+            center - IVec2::splat(MAP_RADIUS) + IVec2::new(x / summary_size, z / summary_size);
+
+        let color = match explored.get(column) {
+            Some(summary) => {
+                let cell = (z % summary_size) * summary_size + x % summary_size;
+                summary[cell as usize]
+            }
+            None => minimap::BACKGROUND_COLOR,
+        };
+
+        pixel.copy_from_slice(&color);
+    }
+}
+
+fn setup_world_map(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    let image = images.add(Image::new_fill(
+        Extent3d {
+            width: MAP_SIZE as u32,
+            height: MAP_SIZE as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &minimap::BACKGROUND_COLOR,
+        TextureFormat::Rgba8UnormSrgb,
+    ));
+
+    let root = commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                display: Display::None,
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent.spawn_bundle(ImageBundle {
+                style: Style {
+                    size: Size::new(Val::Px(DISPLAY_SIZE), Val::Px(DISPLAY_SIZE)),
+                    ..Default::default()
+                },
+                image: image.clone().into(),
+                ..Default::default()
+            });
+        })
+        .id();
+
+    commands.insert_resource(WorldMap {
+        image,
+        root,
+        center: IVec2::ZERO,
+        visible: false,
+        dirty: true,
+    });
+}
+
+fn explore_chunks(
+    registry: Res<KindRegistry>,
+    columns: Res<MinimapColumns>,
+    mut explored: ResMut<ExploredChunks>,
+    mut world_map: ResMut<WorldMap>,
+    mut save_timer: ResMut<SaveTimer>,
+    mut reader: EventReader<ChunkUpdated>,
+) {
+    let mut changed = false;
+
+    for ChunkUpdated(local) in reader.iter() {
+        let column = minimap::to_column(*local);
+        let summary = chunk_summary(&columns, column, &registry);
+
+        if explored.get(column) != Some(&summary) {
+            explored.0.insert(column, summary);
+            changed = true;
+        }
+    }
+
+    if changed {
+        world_map.dirty = true;
+        save_timer.dirty = true;
+    }
+}
+
+fn toggle_world_map(
+    console: Res<Console>,
+    keyboard: Res<Input<KeyCode>>,
+    mut world_map: ResMut<WorldMap>,
+    mut q: Query<&mut Style>,
+) {
+    // Keys typed on the console shouldn't toggle the map
+    if console.visible || !keyboard.just_pressed(TOGGLE_KEY) {
+        return;
+    }
+
+    world_map.visible = !world_map.visible;
+
+    if let Ok(mut style) = q.get_mut(world_map.root) {
+        style.display = if world_map.visible {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+}
+
+fn paint_world_map(
+    origin: Res<WorldOrigin>,
+    explored: Res<ExploredChunks>,
+    mut world_map: ResMut<WorldMap>,
+    mut images: ResMut<Assets<Image>>,
+    camera: Query<&GlobalTransform, With<MainCamera>>,
+) {
+    // There is no need to paint the map while it isn't visible
+    if !world_map.visible {
+        return;
+    }
+
+    let center = camera
+        .get_single()
+        .map(|transform| minimap::to_column(origin.to_local(transform.translation)))
+        .unwrap_or(world_map.center);
+
+    if !world_map.dirty && center == world_map.center {
+        return;
+    }
+
+    if let Some(image) = images.get_mut(&world_map.image) {
+        paint(&mut image.data, center, &explored);
+    }
+
+    world_map.center = center;
+    world_map.dirty = false;
+}
+
+fn save_explored_chunks(
+    time: Res<Time>,
+    explored: Res<ExploredChunks>,
+    mut save_timer: ResMut<SaveTimer>,
+) {
+    if !save_timer.timer.tick(time.delta()).just_finished() || !save_timer.dirty {
+        return;
+    }
+
+    match explored.save(Path::new(EXPLORED_PATH)) {
+        Ok(_) => save_timer.dirty = false,
+        Err(err) => error!("Failed to save explored chunks: {}", err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_load() {
+        let path = std::env::temp_dir().join("eterno_explored_save_load.bin");
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(ExploredChunks::load(&path), ExploredChunks::default());
+
+        let mut explored = ExploredChunks::default();
+        explored.0.insert(
+            (1, -2).into(),
+            [[1, 2, 3, 255]; SUMMARY_SIZE * SUMMARY_SIZE],
+        );
+        explored.0.insert(
+            (-5, 3).into(),
+            [[4, 5, 6, 255]; SUMMARY_SIZE * SUMMARY_SIZE],
+        );
+
+        explored.save(&path).unwrap();
+
+        let loaded = ExploredChunks::load(&path);
+        assert_eq!(loaded, explored);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn paint() {
+        let mut explored = ExploredChunks::default();
+        let mut summary = [[0, 0, 0, 255]; SUMMARY_SIZE * SUMMARY_SIZE];
+        summary[SUMMARY_SIZE + 2] = [255, 0, 0, 255];
+        explored.0.insert((10, 20).into(), summary);
+
+        let center = IVec2::new(10, 20);
+        let mut data = vec![0; MAP_SIZE * MAP_SIZE * 4];
+        super::paint(&mut data, center, &explored);
+
+        let pixel = |x: usize, z: usize| {
+            let idx = (z * MAP_SIZE + x) * 4;
+            [data[idx], data[idx + 1], data[idx + 2], data[idx + 3]]
+        };
+
+        // The center column starts right at the middle of the map
+        let origin = MAP_SIZE / 2;
+        assert_eq!(pixel(origin, origin), [0, 0, 0, 255]);
+        assert_eq!(pixel(origin + 2, origin + 1), [255, 0, 0, 255]);
+
+        // Unexplored columns
+        assert_eq!(pixel(0, 0), minimap::BACKGROUND_COLOR);
+        assert_eq!(pixel(origin - 1, origin), minimap::BACKGROUND_COLOR);
+    }
+}